@@ -0,0 +1,23 @@
+//! Selecting crate artifacts for removal in large target directories.
+
+use cargo_hold::bench::Artifacts;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+fn bench_select_artifacts_for_removal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("select_artifacts_for_removal");
+    for crates in [1_000, 10_000, 100_000] {
+        let artifacts = Artifacts::synthetic(crates);
+        let max_size = artifacts.size() / 2;
+        group.bench_with_input(
+            BenchmarkId::from_parameter(crates),
+            &artifacts,
+            |b, artifacts| {
+                b.iter(|| artifacts.select_for_removal(max_size));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_select_artifacts_for_removal);
+criterion_main!(benches);
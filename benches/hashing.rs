@@ -0,0 +1,72 @@
+//! Hashing single files, and categorizing the files of a tree as `salvage`
+//! does.
+
+use std::fs;
+use std::path::PathBuf;
+
+use cargo_hold::bench::{FileState, StateMetadata, analyze_files, hash_file};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tempfile::TempDir;
+
+fn bench_hash_file(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut group = c.benchmark_group("hash_file");
+    for size in [4 << 10, 1 << 20, 64 << 20] {
+        let path = temp_dir.path().join(format!("{size}.bin"));
+        fs::write(&path, vec![0x5a; size]).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &path, |b, path| {
+            b.iter(|| hash_file(path).unwrap());
+        });
+    }
+    group.finish();
+}
+
+/// A tree of `files` small files and the metadata `stow` recorded for it,
+/// before a tenth of the files changed and a tenth were added
+fn tree(files: usize) -> (TempDir, Vec<PathBuf>, StateMetadata) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut tracked = Vec::with_capacity(files);
+    let mut metadata = StateMetadata::new();
+    for i in 0..files {
+        let path = PathBuf::from(format!("crate_{}/src/module_{i}.rs", i % 100));
+        let full_path = temp_dir.path().join(&path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        let content = format!("pub fn f{i}() -> usize {{ {i} }}\n");
+        fs::write(&full_path, &content).unwrap();
+
+        let stowed = if i % 10 == 1 {
+            format!("pub fn f{i}() -> usize {{ {} }}\n", i + 1)
+        } else {
+            content
+        };
+        if i % 10 != 2 {
+            metadata
+                .upsert(FileState {
+                    path: path.clone(),
+                    size: stowed.len() as u64,
+                    hash: *blake3::hash(stowed.as_bytes()).as_bytes(),
+                    mtime_nanos: 1_700_000_000_000_000_000,
+                })
+                .unwrap();
+        }
+        tracked.push(path);
+    }
+    (temp_dir, tracked, metadata)
+}
+
+fn bench_analyze_files(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analyze_files");
+    group.sample_size(10);
+    for files in [1_000, 10_000] {
+        let (temp_dir, tracked, metadata) = tree(files);
+        group.throughput(Throughput::Elements(files as u64));
+        group.bench_function(BenchmarkId::from_parameter(files), |b| {
+            b.iter(|| analyze_files(temp_dir.path(), &tracked, &metadata));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hash_file, bench_analyze_files);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Saving and loading metadata for deep monorepo trees.
+
+use std::path::PathBuf;
+
+use cargo_hold::bench::{FileState, HoldContext, StateMetadata, load_metadata, save_metadata};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use tempfile::TempDir;
+
+/// Metadata of `files` files spread over the crates of a few services
+fn monorepo(files: usize) -> StateMetadata {
+    let mut metadata = StateMetadata::new();
+    for i in 0..files {
+        metadata
+            .upsert(FileState {
+                path: PathBuf::from(format!(
+                    "services/service_{}/crates/crate_{}/src/handlers/module_{i}.rs",
+                    i % 7,
+                    i % 61
+                )),
+                size: i as u64,
+                hash: *blake3::hash(&i.to_le_bytes()).as_bytes(),
+                mtime_nanos: 1_700_000_000_000_000_000 + i as u64,
+            })
+            .unwrap();
+    }
+    metadata
+}
+
+fn bench_metadata(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let context = HoldContext::default();
+    let mut group = c.benchmark_group("metadata");
+    group.sample_size(10);
+    for files in [10_000, 100_000] {
+        let metadata = monorepo(files);
+        let path = temp_dir.path().join(format!("{files}.metadata"));
+        group.bench_with_input(BenchmarkId::new("save", files), &metadata, |b, metadata| {
+            b.iter(|| save_metadata(&context, metadata, &path).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("load", files), &path, |b, path| {
+            b.iter(|| load_metadata(&context, path).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_metadata);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! File names found in the target directory.
+
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|name: &str| {
+    if let Some((krate, hash)) = cargo_hold::fuzz::parse_crate_artifact_name(Path::new(name)) {
+        assert_eq!(hash.len(), 16);
+        assert!(!krate.is_empty());
+    }
+});
@@ -0,0 +1,10 @@
+//! Metadata restored from a cache may be anything: decoding it must fail
+//! cleanly, never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = cargo_hold::fuzz::parse_metadata(data);
+});
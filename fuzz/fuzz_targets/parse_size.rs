@@ -0,0 +1,9 @@
+//! Sizes given on the command line or in the environment.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|s: &str| {
+    let _ = cargo_hold::fuzz::parse_size(s);
+});
@@ -0,0 +1,103 @@
+//! Entry points for the benchmarks in `benches/`.
+//!
+//! Only built with the `bench` feature; not part of the public API.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "gc")]
+use std::time::{Duration, SystemTime};
+
+use crate::commands::salvage;
+pub use crate::context::HoldContext;
+#[cfg(feature = "gc")]
+use crate::gc::{
+    ArtifactInfo, Budget, CrateArtifact, PreservePolicy, select_artifacts_for_removal,
+};
+pub use crate::hashing::hash_file;
+use crate::logging::Logger;
+pub use crate::metadata::{load_metadata, save_metadata};
+pub use crate::state::{FileState, StateMetadata};
+
+/// Categorizes `tracked_files` against `metadata` as `salvage` does, returning
+/// the number of unchanged, modified, and added files
+pub fn analyze_files(
+    repo_root: &Path,
+    tracked_files: &[PathBuf],
+    metadata: &StateMetadata,
+) -> (usize, usize, usize) {
+    let log = Logger::new(0, true);
+    let analysis = salvage::analyze_files(
+        &HoldContext::default(),
+        repo_root,
+        tracked_files,
+        &HashSet::new(),
+        metadata,
+        None,
+        None,
+        &log,
+    );
+    (
+        analysis.unchanged.len(),
+        analysis.modified.len(),
+        analysis.added.len(),
+    )
+}
+
+/// Crate artifacts of a synthetic target directory
+#[cfg(feature = "gc")]
+pub struct Artifacts(Vec<CrateArtifact>);
+
+#[cfg(feature = "gc")]
+impl Artifacts {
+    /// `crates` crates of up to 1 MiB each, last modified within the last 60
+    /// days
+    pub fn synthetic(crates: usize) -> Self {
+        let now = SystemTime::now();
+        Self(
+            (0..crates)
+                .map(|i| {
+                    let size = (i as u64 * 104_729) % (1 << 20) + 1;
+                    let mtime = now - Duration::from_secs((i as u64 * 7_919) % (60 * 86_400));
+                    let hash = format!("{:016x}", i as u64 * 0x9e37_79b9);
+                    CrateArtifact {
+                        name: format!("crate_{}", i % 1_000),
+                        artifacts: vec![ArtifactInfo {
+                            path: PathBuf::from(format!("debug/deps/libcrate_{i}-{hash}.rlib")),
+                            size,
+                            modified: mtime,
+                        }],
+                        hash,
+                        total_size: size,
+                        newest_mtime: mtime,
+                        eviction_score: 0.0,
+                        rebuild_secs: None,
+                        inputs_unchanged: false,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Total size of the artifacts in bytes
+    pub fn size(&self) -> u64 {
+        self.0.iter().map(|artifact| artifact.total_size).sum()
+    }
+
+    /// Number of crates GC selects for removal to fit `max_size`, with a 30
+    /// day age threshold
+    pub fn select_for_removal(&self, max_size: u64) -> usize {
+        let budget = Budget {
+            size: self.size(),
+            max_size: Some(max_size),
+            ..Budget::default()
+        };
+        select_artifacts_for_removal(
+            &self.0,
+            &budget,
+            Duration::from_secs(30 * 86_400),
+            &PreservePolicy::default(),
+            &Logger::new(0, true),
+        )
+        .len()
+    }
+}
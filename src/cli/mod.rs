@@ -13,7 +13,7 @@
 //!
 //! // Access the parsed command
 //! match &cli.command() {
-//!     Commands::Anchor => println!("Running anchor command"),
+//!     Commands::Anchor { .. } => println!("Running anchor command"),
 //!     Commands::Voyage { gc, .. } => {
 //!         println!("Running voyage with size limit: {:?}", gc.max_target_size());
 //!     }
@@ -21,15 +21,21 @@
 //! }
 //! ```
 
+use std::ffi::OsString;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::path::{Path, PathBuf};
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Arg, Args, Command, Parser, Subcommand};
 
 use crate::error::{HoldError, Result};
+use crate::hashing::DEFAULT_BATCH_SIZE;
 
 #[cfg(test)]
 mod tests;
 
+/// Prefix of the environment variable behind every option
+pub const ENV_PREFIX: &str = "CARGO_HOLD_";
+
 /// Main command-line interface for cargo-hold.
 ///
 /// This struct represents the top-level CLI configuration, containing both
@@ -43,7 +49,9 @@ mod tests;
     version,
     about = "A CI tool to ensure Cargo's incremental compilation is reliable",
     long_about = None,
-    propagate_version = true
+    propagate_version = true,
+    mut_args = with_env,
+    mut_subcommands = subcommand_with_env
 )]
 pub struct Cli {
     #[command(flatten)]
@@ -61,48 +69,321 @@ pub struct Cli {
 #[derive(Parser)]
 pub struct GlobalOpts {
     /// Path to the target directory (defaults to ./target)
-    #[arg(
-        long,
-        global = true,
-        default_value = "target",
-        env = "CARGO_HOLD_TARGET_DIR"
-    )]
+    #[arg(long, global = true, default_value = "target")]
     target_dir: PathBuf,
 
+    /// Path to cargo's build directory, when `build.build-dir` keeps
+    /// intermediate artifacts apart from the target directory (defaults to
+    /// `CARGO_BUILD_BUILD_DIR`); GC cleans it with a budget of its own
+    #[arg(long, global = true)]
+    build_dir: Option<PathBuf>,
+
     /// Path to the metadata file (defaults to
     /// `<target-dir>/cargo-hold.metadata`)
-    #[arg(long, global = true, env = "CARGO_HOLD_METADATA_PATH")]
+    #[arg(long, global = true)]
     metadata_path: Option<PathBuf>,
 
     /// Enable verbose output (use multiple times for more verbosity)
-    #[arg(short, long, global = true, action = clap::ArgAction::Count, env = "CARGO_HOLD_VERBOSE")]
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
     verbose: u8,
 
     /// Silence all output except for errors
-    #[arg(
-        short,
-        long,
-        global = true,
-        conflicts_with = "verbose",
-        env = "CARGO_HOLD_QUIET"
-    )]
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
     quiet: bool,
+
+    /// Guarantee no network access: fail if any path is a remote URL
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Run at background priority (the lowest CPU priority and, on Linux, the
+    /// idle I/O class; background processing mode on Windows) so that a build
+    /// or tests running alongside are not slowed down
+    #[arg(long, global = true)]
+    background: bool,
+
+    /// Fail the command if it reported any warnings
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// Print nothing but a single summary line per command on stdout (and
+    /// errors on stderr)
+    #[arg(long, global = true, conflicts_with_all = ["verbose", "quiet"])]
+    summary_only: bool,
+
+    /// CI system to report to: "auto" detects it from the environment
+    /// (default), or "github", "gitlab", "buildkite", "none"
+    #[arg(long, global = true, default_value = "auto")]
+    ci: String,
+
+    /// Append the command's figures to this file as OpenMetrics text, for
+    /// GitLab's `artifacts:reports:metrics`
+    #[arg(long, global = true)]
+    metrics_report: Option<PathBuf>,
+
+    /// When the command fails, write a JSON report of the error, its
+    /// diagnostic code and causes, and the context it failed in to this file
+    #[arg(long, global = true)]
+    error_report: Option<PathBuf>,
+
+    /// How to read the KB, MB, GB and TB size suffixes: "binary" as powers of
+    /// 1024 (default), or "si" as powers of 1000, which also prints sizes in
+    /// SI units. KiB, MiB, GiB and TiB are always binary.
+    #[arg(long, global = true, default_value = "binary")]
+    size_units: String,
+
+    /// How tracked files are discovered: "auto" detects Jujutsu, Mercurial,
+    /// or Git (default), "libgit2" reads the Git index directly, "git" runs
+    /// `git ls-files`, which copes with partial clones, "hg" runs `hg files`,
+    /// and "jj" runs `jj file list`
+    #[arg(long, global = true, default_value = "auto")]
+    vcs: String,
+
+    /// How files managed by Git LFS are hashed: "content" hashes whatever is
+    /// checked out (default), "pointer" hashes the LFS pointer, computing it
+    /// from the content when git-lfs smudged the file, so that checkouts with
+    /// and without git-lfs agree
+    #[arg(long, global = true, default_value = "content")]
+    lfs: String,
+
+    /// Files larger than this (e.g., "1G", "500M", or bytes) are hashed
+    /// according to --large-file-policy; by default every file is hashed in
+    /// full
+    #[arg(long, global = true)]
+    large_file_threshold: Option<String>,
+
+    /// How files above --large-file-threshold are tracked: "full" hashes them
+    /// like any other file (default), "head:<SIZE>" hashes only their first
+    /// SIZE bytes ("head" alone hashes 1M), and "skip" compares their size
+    /// alone
+    #[arg(long, global = true, default_value = "full")]
+    large_file_policy: String,
+
+    /// Number of tracked files stow and salvage hash at a time; lower it to
+    /// bound memory use on huge repositories
+    #[arg(long, global = true, default_value_t = DEFAULT_BATCH_SIZE)]
+    batch_size: NonZeroUsize,
+
+    /// Load the file states of the metadata without validating them, which is
+    /// faster for huge repositories. Only for metadata that never left this
+    /// machine: a corrupted or crafted file can crash cargo-hold
+    #[arg(long, global = true)]
+    trusted_metadata: bool,
+
+    /// Secret key to sign the metadata with when saving it, and to check its
+    /// signature with when loading it, so a tampered cache is ignored. Best
+    /// set through its environment variable to keep it out of process
+    /// listings
+    #[arg(long, global = true, hide_env_values = true)]
+    metadata_key: Option<String>,
+
+    /// Preset of GC defaults: "ci" cleans aggressively (default), "local"
+    /// keeps the cargo home, incremental data, and docs, and asks before
+    /// freeing more than 1 GiB
+    #[arg(long, global = true, default_value = "ci")]
+    profile: String,
+
+    /// Whether GC treats the machine as ephemeral, skipping cargo binaries
+    /// cleanup and cleanup of a cargo home baked into a container image:
+    /// "auto" detects containers, Kubernetes, and GitHub-hosted runners
+    /// (default), "on", or "off"
+    #[arg(long, global = true, default_value = "auto")]
+    ephemeral: String,
+
+    /// Salt the recorded hashes with this value instead of the repository's
+    /// origin URL, so that metadata of other repositories sharing a cache is
+    /// never mistaken for this one's; "none" records unsalted hashes
+    #[arg(long, global = true)]
+    hash_salt: Option<String>,
+
+    /// Keep the timestamps of all file contents seen by recent stows in this
+    /// file, so files moved or copied from them inherit their timestamp
+    /// instead of counting as added
+    #[arg(long, global = true)]
+    dedup_store: Option<PathBuf>,
+
+    /// Keep the files listed by the Git index in this file, and reuse them
+    /// in later invocations until the index changes
+    #[arg(long, global = true)]
+    discovery_cache: Option<PathBuf>,
+
+    /// Configuration file defining hooks run around commands (default:
+    /// .cargo-hold.json in the current directory, if present)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Write a JSON report of the files salvage found modified, added, or
+    /// removed, and whether dependency manifests changed, to this file
+    #[arg(long, global = true)]
+    salvage_report: Option<PathBuf>,
+
+    /// Leave tracked files below this path, relative to the repository root,
+    /// out of the metadata (repeatable or comma-separated)
+    #[arg(long, global = true, value_delimiter = ',')]
+    exclude: Vec<PathBuf>,
+
+    /// Hash tracked files below the target directory, which are left out by
+    /// default so that garbage collection and stow do not undo each other
+    #[arg(long, global = true)]
+    hash_target_dir: bool,
 }
 
 /// Shared garbage collection arguments.
 #[derive(Args, Debug, Clone, Default)]
 pub struct GcArgs {
-    /// Maximum target directory size (e.g., "5G", "500M", or bytes)
-    #[arg(long, env = "CARGO_HOLD_MAX_TARGET_SIZE")]
+    /// Maximum target directory size (e.g., "5G", "500M", or bytes), or a
+    /// percentage of the filesystem holding it (e.g., "40%")
+    #[arg(long)]
     max_target_size: Option<String>,
 
+    /// Maximum number of files and directories in the target directory
+    #[arg(long)]
+    max_target_files: Option<u64>,
+
+    /// Maximum size of cargo's build directory (see `--build-dir`), like
+    /// `--max-target-size`
+    #[arg(long)]
+    max_build_dir_size: Option<String>,
+
+    /// Maximum size of the coverage data in the target directory
+    /// (`*.profraw`, `*.profdata`, and `llvm-cov-target`), like
+    /// `--max-target-size`
+    #[arg(long)]
+    max_coverage_size: Option<String>,
+
     /// Additional binaries to preserve in ~/.cargo/bin (comma-separated)
-    #[arg(
-        long,
-        value_delimiter = ',',
-        env = "CARGO_HOLD_PRESERVE_CARGO_BINARIES"
-    )]
+    #[arg(long, value_delimiter = ',')]
     preserve_cargo_binaries: Vec<String>,
+
+    /// Never evict units used by the last build, as recorded in this file
+    /// (a `--unit-graph` document or `--message-format=json`/`--timings=json`
+    /// output), or by the upcoming build, given its unit graph
+    #[arg(long, visible_alias = "unit-graph")]
+    preserve_units_from: Option<PathBuf>,
+
+    /// Preserve artifacts from this many of the most recent builds (default:
+    /// 1)
+    #[arg(long)]
+    preserve_last_builds: Option<usize>,
+
+    /// Age threshold as a duration (e.g., "36h", "2d"); overrides the
+    /// threshold given in days
+    #[arg(long)]
+    age_threshold: Option<String>,
+
+    /// Age threshold for a single area as AREA=DURATION (e.g., "deps=12h",
+    /// "registry-cache=90d"); areas: deps, incremental, registry-cache,
+    /// registry-src, git, bin, coverage, nextest, trybuild, snapshots, dx,
+    /// wasm-bindgen, trunk (comma-separated or repeated). AREA=evict removes
+    /// the whole area, and AREA=keep never removes the outputs of a tool
+    /// (coverage through trunk)
+    #[arg(long, value_delimiter = ',')]
+    age_threshold_for: Vec<String>,
+
+    /// Never remove git checkouts for revisions referenced by these
+    /// lockfiles (in addition to the Cargo.lock of the current directory and
+    /// next to the target directory)
+    #[arg(long = "lockfile", value_delimiter = ',')]
+    lockfiles: Vec<PathBuf>,
+
+    /// How old the previous build timestamp may be before its artifacts stop
+    /// being preserved (e.g., "2d", "12h"; defaults to the age threshold)
+    #[arg(long)]
+    preserve_window: Option<String>,
+
+    /// How long before the previous build timestamp artifacts still count as
+    /// part of that build (e.g., "2h", "30m"; default: 5m)
+    #[arg(long)]
+    preserve_buffer: Option<String>,
+
+    /// How file sizes are measured for the size cap and reporting:
+    /// "allocated" disk blocks (default) or "apparent" logical length
+    #[arg(long)]
+    size_mode: Option<String>,
+
+    /// What to do when a cargo build is using the target directory: "wait"
+    /// for it to finish (default), "skip" GC, or "fail"
+    #[arg(long)]
+    gc_when_busy: Option<String>,
+
+    /// Shell command that decides which artifacts to keep or evict. It reads
+    /// one JSON object per artifact on stdin and prints "keep", "evict",
+    /// "default", or an eviction score per line
+    #[arg(long)]
+    gc_policy_cmd: Option<String>,
+
+    /// Whether to follow cargo's global cache tracking database
+    /// ($CARGO_HOME/.global-cache, cargo 1.78+) for registry and git cleanup:
+    /// "auto" uses it when present (default), "off" uses file modification
+    /// times
+    #[arg(long)]
+    global_cache: Option<String>,
+
+    /// Confine garbage collection with Landlock (Linux 5.13+) to writing
+    /// below the target directory, the build directory, and the cleaned
+    /// parts of the cargo home: "off" (default), "auto" where the kernel
+    /// supports it, or "on" to fail without it
+    #[arg(long)]
+    gc_sandbox: Option<String>,
+
+    /// Whether measuring sizes descends into symbolic links to directories:
+    /// "skip" counts each link as itself (default), "follow" counts what it
+    /// points to. Artifacts are grouped and removed as links either way.
+    #[arg(long)]
+    gc_symlinks: Option<String>,
+
+    /// Apply size limits even while the GC metrics are warming up: until a
+    /// few runs are recorded, only age evicts artifacts
+    #[arg(long, env = "CARGO_HOLD_GC_FORCE")]
+    force: bool,
+
+    /// Number of GC runs the GC metrics behind auto-sizing remember
+    /// (default: 20); kept in the metadata for later runs, and a shorter
+    /// window drops the older runs
+    #[arg(long)]
+    gc_metrics_window: Option<NonZeroU32>,
+
+    /// Cargo home whose registry, git checkouts, and binaries are cleaned
+    /// (default: $CARGO_HOME, else ~/.cargo)
+    #[arg(long)]
+    cargo_home: Option<PathBuf>,
+
+    /// Write every path removed by GC (or, in a dry run, that would be
+    /// removed) with its size to this file, or "-" for stdout
+    #[arg(long)]
+    removal_list: Option<PathBuf>,
+
+    /// Format of --removal-list: "json" (default) or "nul" for NUL-delimited
+    /// "<size>\t<path>" records
+    #[arg(long)]
+    removal_list_format: Option<String>,
+
+    /// Append every path removed by GC, with its size, the phase that
+    /// removed it, and why, to this audit log; dry runs are not logged.
+    /// Rotated to <path>.1 once it grows past 16 MiB
+    #[arg(long)]
+    gc_audit_log: Option<PathBuf>,
+
+    /// Format of --gc-audit-log: "text" (default) for tab-separated lines or
+    /// "jsonl" for a JSON object per line
+    #[arg(long)]
+    gc_audit_log_format: Option<String>,
+
+    /// Stop GC after this long (e.g. "120s" or "5m"), evicting the largest
+    /// artifact groups first and leaving the rest for the next run
+    #[arg(long)]
+    gc_max_duration: Option<String>,
+
+    /// Compress cold artifact groups in place instead of removing them; a
+    /// group is only removed once it is selected again while compressed.
+    /// Run `cargo hold thaw` to decompress them before building
+    #[arg(long)]
+    gc_compress_cold: bool,
+
+    /// Read the rustc dep-info of every crate to evict those whose workspace
+    /// sources changed since they were built, and keep those whose sources
+    /// did not regardless of their age
+    #[arg(long)]
+    gc_dep_info: bool,
 }
 
 impl GcArgs {
@@ -110,19 +391,382 @@ impl GcArgs {
     pub fn new(max_target_size: Option<String>, preserve_cargo_binaries: Vec<String>) -> Self {
         Self {
             max_target_size,
+            max_target_files: None,
+            max_build_dir_size: None,
+            max_coverage_size: None,
             preserve_cargo_binaries,
+            preserve_units_from: None,
+            preserve_last_builds: None,
+            age_threshold: None,
+            age_threshold_for: Vec::new(),
+            lockfiles: Vec::new(),
+            preserve_window: None,
+            preserve_buffer: None,
+            size_mode: None,
+            gc_when_busy: None,
+            gc_policy_cmd: None,
+            global_cache: None,
+            gc_sandbox: None,
+            gc_symlinks: None,
+            force: false,
+            gc_metrics_window: None,
+            cargo_home: None,
+            removal_list: None,
+            removal_list_format: None,
+            gc_audit_log: None,
+            gc_audit_log_format: None,
+            gc_max_duration: None,
+            gc_compress_cold: false,
+            gc_dep_info: false,
         }
     }
 
+    /// Set how many of the most recent builds are preserved.
+    pub fn with_preserve_last_builds(mut self, builds: usize) -> Self {
+        self.preserve_last_builds = Some(builds);
+        self
+    }
+
+    /// Set the age threshold as a duration string.
+    pub fn with_age_threshold(mut self, threshold: impl Into<String>) -> Self {
+        self.age_threshold = Some(threshold.into());
+        self
+    }
+
+    /// Add a per-area age threshold (`AREA=DURATION`).
+    pub fn with_age_threshold_for(mut self, threshold: impl Into<String>) -> Self {
+        self.age_threshold_for.push(threshold.into());
+        self
+    }
+
+    /// Add a lockfile whose git dependency revisions are kept.
+    pub fn with_lockfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lockfiles.push(path.into());
+        self
+    }
+
+    /// Set the previous build preservation window.
+    pub fn with_preserve_window(mut self, window: impl Into<String>) -> Self {
+        self.preserve_window = Some(window.into());
+        self
+    }
+
+    /// Set the previous build preservation buffer.
+    pub fn with_preserve_buffer(mut self, buffer: impl Into<String>) -> Self {
+        self.preserve_buffer = Some(buffer.into());
+        self
+    }
+
+    /// Set how file sizes are measured.
+    pub fn with_size_mode(mut self, mode: impl Into<String>) -> Self {
+        self.size_mode = Some(mode.into());
+        self
+    }
+
+    /// Set what to do when a build is using the target directory.
+    pub fn with_gc_when_busy(mut self, policy: impl Into<String>) -> Self {
+        self.gc_when_busy = Some(policy.into());
+        self
+    }
+
+    /// Set the maximum size of cargo's build directory.
+    pub fn with_max_build_dir_size(mut self, size: impl Into<String>) -> Self {
+        self.max_build_dir_size = Some(size.into());
+        self
+    }
+
+    /// Set the maximum number of files and directories in the target
+    /// directory.
+    pub fn with_max_target_files(mut self, files: u64) -> Self {
+        self.max_target_files = Some(files);
+        self
+    }
+
+    /// Set the command deciding which artifacts to keep or evict.
+    pub fn with_gc_policy_cmd(mut self, command: impl Into<String>) -> Self {
+        self.gc_policy_cmd = Some(command.into());
+        self
+    }
+
+    /// Set whether cargo's global cache tracking database is used.
+    pub fn with_global_cache(mut self, mode: impl Into<String>) -> Self {
+        self.global_cache = Some(mode.into());
+        self
+    }
+
+    /// Set whether garbage collection is confined with Landlock.
+    pub fn with_gc_sandbox(mut self, mode: impl Into<String>) -> Self {
+        self.gc_sandbox = Some(mode.into());
+        self
+    }
+
+    /// Set whether measuring sizes follows symbolic links.
+    pub fn with_gc_symlinks(mut self, policy: impl Into<String>) -> Self {
+        self.gc_symlinks = Some(policy.into());
+        self
+    }
+
+    /// Set whether size limits apply while the GC metrics warm up.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Set how many GC runs the GC metrics remember.
+    pub fn with_gc_metrics_window(mut self, window: NonZeroU32) -> Self {
+        self.gc_metrics_window = Some(window);
+        self
+    }
+
+    /// Set the cargo home to clean.
+    pub fn with_cargo_home(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cargo_home = Some(path.into());
+        self
+    }
+
+    /// Set where the list of removed paths is written.
+    pub fn with_removal_list(mut self, path: impl Into<PathBuf>) -> Self {
+        self.removal_list = Some(path.into());
+        self
+    }
+
+    /// Set the format of the list of removed paths.
+    pub fn with_removal_list_format(mut self, format: impl Into<String>) -> Self {
+        self.removal_list_format = Some(format.into());
+        self
+    }
+
+    /// Set the audit log removed paths are appended to.
+    pub fn with_gc_audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.gc_audit_log = Some(path.into());
+        self
+    }
+
+    /// Set the format of the audit log.
+    pub fn with_gc_audit_log_format(mut self, format: impl Into<String>) -> Self {
+        self.gc_audit_log_format = Some(format.into());
+        self
+    }
+
+    /// Set the time budget of GC.
+    pub fn with_gc_max_duration(mut self, duration: impl Into<String>) -> Self {
+        self.gc_max_duration = Some(duration.into());
+        self
+    }
+
+    /// Set whether cold artifact groups are compressed before removal.
+    pub fn with_gc_compress_cold(mut self, enabled: bool) -> Self {
+        self.gc_compress_cold = enabled;
+        self
+    }
+
+    /// Set whether dep-info decides which crates are stale or fresh.
+    pub fn with_gc_dep_info(mut self, enabled: bool) -> Self {
+        self.gc_dep_info = enabled;
+        self
+    }
+
+    /// Set the build units file used to protect artifacts of the last build.
+    pub fn with_preserve_units_from(mut self, path: impl Into<PathBuf>) -> Self {
+        self.preserve_units_from = Some(path.into());
+        self
+    }
+
     /// Get the max target size flag.
     pub fn max_target_size(&self) -> Option<&str> {
         self.max_target_size.as_deref()
     }
 
+    /// Get the max build directory size flag.
+    pub fn max_build_dir_size(&self) -> Option<&str> {
+        self.max_build_dir_size.as_deref()
+    }
+
+    /// Get the max coverage size flag.
+    pub fn max_coverage_size(&self) -> Option<&str> {
+        self.max_coverage_size.as_deref()
+    }
+
+    /// Get the max target files flag.
+    pub fn max_target_files(&self) -> Option<u64> {
+        self.max_target_files
+    }
+
     /// Get the list of binaries to preserve.
     pub fn preserve_cargo_binaries(&self) -> &[String] {
         &self.preserve_cargo_binaries
     }
+
+    /// Get the build units file, if any.
+    pub fn preserve_units_from(&self) -> Option<&Path> {
+        self.preserve_units_from.as_deref()
+    }
+
+    /// Get how many of the most recent builds are preserved.
+    pub fn preserve_last_builds(&self) -> usize {
+        self.preserve_last_builds.unwrap_or(1)
+    }
+
+    /// Get the age threshold duration flag.
+    pub fn age_threshold(&self) -> Option<&str> {
+        self.age_threshold.as_deref()
+    }
+
+    /// Get the per-area age threshold flags.
+    pub fn age_threshold_for(&self) -> &[String] {
+        &self.age_threshold_for
+    }
+
+    /// Get the lockfile flags.
+    pub fn lockfiles(&self) -> &[PathBuf] {
+        &self.lockfiles
+    }
+
+    /// Get the previous build preservation window flag.
+    pub fn preserve_window(&self) -> Option<&str> {
+        self.preserve_window.as_deref()
+    }
+
+    /// Get the previous build preservation buffer flag.
+    pub fn preserve_buffer(&self) -> Option<&str> {
+        self.preserve_buffer.as_deref()
+    }
+
+    /// Get the size mode flag.
+    pub fn size_mode(&self) -> Option<&str> {
+        self.size_mode.as_deref()
+    }
+
+    /// Get the busy target directory policy flag.
+    pub fn gc_when_busy(&self) -> Option<&str> {
+        self.gc_when_busy.as_deref()
+    }
+
+    /// Get the GC policy command flag.
+    pub fn gc_policy_cmd(&self) -> Option<&str> {
+        self.gc_policy_cmd.as_deref()
+    }
+
+    /// Get the global cache database mode flag.
+    pub fn global_cache(&self) -> Option<&str> {
+        self.global_cache.as_deref()
+    }
+
+    /// Get the GC sandbox mode flag.
+    pub fn gc_sandbox(&self) -> Option<&str> {
+        self.gc_sandbox.as_deref()
+    }
+
+    /// Get the GC symbolic link policy flag.
+    pub fn gc_symlinks(&self) -> Option<&str> {
+        self.gc_symlinks.as_deref()
+    }
+
+    /// Check if size limits apply while the GC metrics warm up.
+    pub fn force(&self) -> bool {
+        self.force
+    }
+
+    /// Get the GC metrics window flag.
+    pub fn gc_metrics_window(&self) -> Option<NonZeroU32> {
+        self.gc_metrics_window
+    }
+
+    /// Get the cargo home flag.
+    pub fn cargo_home(&self) -> Option<&Path> {
+        self.cargo_home.as_deref()
+    }
+
+    /// Get the removal list destination flag.
+    pub fn removal_list(&self) -> Option<&Path> {
+        self.removal_list.as_deref()
+    }
+
+    /// Get the removal list format flag.
+    pub fn removal_list_format(&self) -> Option<&str> {
+        self.removal_list_format.as_deref()
+    }
+
+    /// Get the audit log path flag.
+    pub fn gc_audit_log(&self) -> Option<&Path> {
+        self.gc_audit_log.as_deref()
+    }
+
+    /// Get the audit log format flag.
+    pub fn gc_audit_log_format(&self) -> Option<&str> {
+        self.gc_audit_log_format.as_deref()
+    }
+
+    /// Get the GC time budget flag.
+    pub fn gc_max_duration(&self) -> Option<&str> {
+        self.gc_max_duration.as_deref()
+    }
+
+    /// Check if cold artifact groups are compressed before removal.
+    pub fn gc_compress_cold(&self) -> bool {
+        self.gc_compress_cold
+    }
+
+    /// Check if dep-info decides which crates are stale or fresh.
+    pub fn gc_dep_info(&self) -> bool {
+        self.gc_dep_info
+    }
+}
+
+/// Shared anchor arguments.
+#[derive(Args, Debug, Clone, Default)]
+pub struct AnchorArgs {
+    /// Remove cargo fingerprints of workspace members with modified files and
+    /// of every workspace member depending on them
+    #[arg(long)]
+    invalidate_fingerprints: bool,
+
+    /// After restoring timestamps, report the crates cargo will rebuild
+    /// because a source is newer than their fingerprint
+    #[arg(long)]
+    check_rebuilds: bool,
+
+    /// Write the crates cargo will rebuild as JSON to this file (implies
+    /// --check-rebuilds)
+    #[arg(long)]
+    rebuild_report: Option<PathBuf>,
+}
+
+impl AnchorArgs {
+    /// Build anchor args for programmatic use.
+    pub fn new(invalidate_fingerprints: bool) -> Self {
+        Self {
+            invalidate_fingerprints,
+            ..Self::default()
+        }
+    }
+
+    /// Report the crates cargo will rebuild after timestamps are restored.
+    pub fn with_check_rebuilds(mut self, enabled: bool) -> Self {
+        self.check_rebuilds = enabled;
+        self
+    }
+
+    /// Write the crates cargo will rebuild as JSON to `path`.
+    pub fn with_rebuild_report(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rebuild_report = Some(path.into());
+        self
+    }
+
+    /// Check if fingerprint invalidation is enabled.
+    pub fn invalidate_fingerprints(&self) -> bool {
+        self.invalidate_fingerprints
+    }
+
+    /// Check if the crates cargo will rebuild are reported.
+    pub fn check_rebuilds(&self) -> bool {
+        self.check_rebuilds || self.rebuild_report.is_some()
+    }
+
+    /// Get the path the crates cargo will rebuild are written to.
+    pub fn rebuild_report(&self) -> Option<&Path> {
+        self.rebuild_report.as_deref()
+    }
 }
 
 impl GlobalOpts {
@@ -151,6 +795,30 @@ impl GlobalOpts {
         &self.target_dir
     }
 
+    /// Get the absolute build directory path, when it is apart from the
+    /// target directory: `--build-dir`, or else cargo's
+    /// `CARGO_BUILD_BUILD_DIR` unless it uses a template cargo-hold cannot
+    /// expand (such as `{workspace-path-hash}`)
+    pub fn get_build_dir(&self) -> Option<PathBuf> {
+        let build_dir = match self.build_dir() {
+            Some(dir) => normalize_path(dir),
+            None => {
+                let dir = std::env::var_os("CARGO_BUILD_BUILD_DIR")?;
+                let dir = dir.to_str()?.replace("{workspace-root}", ".");
+                if dir.contains('{') {
+                    return None;
+                }
+                normalize_path(dir)
+            }
+        };
+        (build_dir != self.get_target_dir()).then_some(build_dir)
+    }
+
+    /// Get the build directory option
+    pub fn build_dir(&self) -> Option<&Path> {
+        self.build_dir.as_deref()
+    }
+
     /// Get the metadata path option
     pub fn metadata_path(&self) -> Option<&Path> {
         self.metadata_path.as_deref()
@@ -165,6 +833,126 @@ impl GlobalOpts {
     pub fn quiet(&self) -> bool {
         self.quiet
     }
+
+    /// Check if offline mode is enabled
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Check if background priority is enabled
+    pub fn background(&self) -> bool {
+        self.background
+    }
+
+    /// Check if warnings fail the command
+    pub fn deny_warnings(&self) -> bool {
+        self.deny_warnings
+    }
+
+    /// Check if only a one-line summary is printed
+    pub fn summary_only(&self) -> bool {
+        self.summary_only
+    }
+
+    /// Get the CI system flag
+    pub fn ci(&self) -> &str {
+        &self.ci
+    }
+
+    /// Get the metrics report path option
+    pub fn metrics_report(&self) -> Option<&Path> {
+        self.metrics_report.as_deref()
+    }
+
+    /// Get the error report path option
+    pub fn error_report(&self) -> Option<&Path> {
+        self.error_report.as_deref()
+    }
+
+    /// Get the size units flag
+    pub fn size_units(&self) -> &str {
+        &self.size_units
+    }
+
+    /// Get the version control backend option
+    pub fn vcs(&self) -> &str {
+        &self.vcs
+    }
+
+    /// Get the Git LFS hashing mode option
+    pub fn lfs(&self) -> &str {
+        &self.lfs
+    }
+
+    /// Get the size above which the large file policy applies, if set
+    pub fn large_file_threshold(&self) -> Option<&str> {
+        self.large_file_threshold.as_deref()
+    }
+
+    /// Get the large file policy option
+    pub fn large_file_policy(&self) -> &str {
+        &self.large_file_policy
+    }
+
+    /// Get the number of files analyzed per batch
+    pub fn batch_size(&self) -> NonZeroUsize {
+        self.batch_size
+    }
+
+    /// Check if the metadata's file states are loaded without validation
+    pub fn trusted_metadata(&self) -> bool {
+        self.trusted_metadata
+    }
+
+    /// Get the key metadata files are signed with, if set
+    pub fn metadata_key(&self) -> Option<&str> {
+        self.metadata_key.as_deref()
+    }
+
+    /// Get the GC preset option
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Get the ephemeral machine option
+    pub fn ephemeral(&self) -> &str {
+        &self.ephemeral
+    }
+
+    /// Get the salt of the recorded hashes, if set
+    pub fn hash_salt(&self) -> Option<&str> {
+        self.hash_salt.as_deref()
+    }
+
+    /// Get the path of the dedup store, if set
+    pub fn dedup_store(&self) -> Option<&Path> {
+        self.dedup_store.as_deref()
+    }
+
+    /// Get the path of the discovery cache, if set
+    pub fn discovery_cache(&self) -> Option<&Path> {
+        self.discovery_cache.as_deref()
+    }
+
+    /// Get the path of the configuration file, if set
+    pub fn config(&self) -> Option<&Path> {
+        self.config.as_deref()
+    }
+
+    /// Get the path of the salvage report, if set
+    pub fn salvage_report(&self) -> Option<&Path> {
+        self.salvage_report.as_deref()
+    }
+
+    /// Get the paths whose tracked files are left out
+    pub fn exclude(&self) -> &[PathBuf] {
+        &self.exclude
+    }
+
+    /// Check if tracked files below the target directory are hashed
+    pub fn hash_target_dir(&self) -> bool {
+        self.hash_target_dir
+    }
 }
 
 /// Builder for constructing `GlobalOpts` programmatically.
@@ -175,9 +963,34 @@ impl GlobalOpts {
 #[derive(Default)]
 pub struct GlobalOptsBuilder {
     target_dir: Option<PathBuf>,
+    build_dir: Option<PathBuf>,
     metadata_path: Option<PathBuf>,
     verbose: u8,
     quiet: bool,
+    offline: bool,
+    background: bool,
+    deny_warnings: bool,
+    summary_only: bool,
+    ci: Option<String>,
+    metrics_report: Option<PathBuf>,
+    error_report: Option<PathBuf>,
+    size_units: Option<String>,
+    vcs: Option<String>,
+    lfs: Option<String>,
+    large_file_threshold: Option<String>,
+    large_file_policy: Option<String>,
+    batch_size: Option<NonZeroUsize>,
+    trusted_metadata: bool,
+    metadata_key: Option<String>,
+    profile: Option<String>,
+    ephemeral: Option<String>,
+    hash_salt: Option<String>,
+    dedup_store: Option<PathBuf>,
+    discovery_cache: Option<PathBuf>,
+    config: Option<PathBuf>,
+    salvage_report: Option<PathBuf>,
+    exclude: Vec<PathBuf>,
+    hash_target_dir: bool,
 }
 
 impl GlobalOptsBuilder {
@@ -187,6 +1000,12 @@ impl GlobalOptsBuilder {
         self
     }
 
+    /// Set the build directory path.
+    pub fn build_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.build_dir = Some(dir.into());
+        self
+    }
+
     /// Set the metadata file path.
     pub fn metadata_path(mut self, path: Option<impl Into<PathBuf>>) -> Self {
         self.metadata_path = path.map(|p| p.into());
@@ -205,13 +1024,183 @@ impl GlobalOptsBuilder {
         self
     }
 
+    /// Enable or disable offline mode.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Enable or disable background priority.
+    pub fn background(mut self, background: bool) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Enable or disable failing on warnings.
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+
+    /// Enable or disable printing only a one-line summary.
+    pub fn summary_only(mut self, summary_only: bool) -> Self {
+        self.summary_only = summary_only;
+        self
+    }
+
+    /// Set the CI system to report to.
+    pub fn ci(mut self, ci: impl Into<String>) -> Self {
+        self.ci = Some(ci.into());
+        self
+    }
+
+    /// Set the file the command's figures are appended to.
+    pub fn metrics_report(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.metrics_report = path.map(|p| p.into());
+        self
+    }
+
+    /// Set the file a failed command's error report is written to.
+    pub fn error_report(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.error_report = path.map(|p| p.into());
+        self
+    }
+
+    /// Set the units KB, MB, GB and TB are read in.
+    pub fn size_units(mut self, units: impl Into<String>) -> Self {
+        self.size_units = Some(units.into());
+        self
+    }
+
+    /// Set how tracked files are discovered.
+    pub fn vcs(mut self, vcs: impl Into<String>) -> Self {
+        self.vcs = Some(vcs.into());
+        self
+    }
+
+    /// Set how files managed by Git LFS are hashed.
+    pub fn lfs(mut self, lfs: impl Into<String>) -> Self {
+        self.lfs = Some(lfs.into());
+        self
+    }
+
+    /// Set the size above which the large file policy applies.
+    pub fn large_file_threshold(mut self, size: Option<impl Into<String>>) -> Self {
+        self.large_file_threshold = size.map(|s| s.into());
+        self
+    }
+
+    /// Set how files above the large file threshold are tracked.
+    pub fn large_file_policy(mut self, policy: impl Into<String>) -> Self {
+        self.large_file_policy = Some(policy.into());
+        self
+    }
+
+    /// Set the number of files analyzed per batch.
+    pub fn batch_size(mut self, files: NonZeroUsize) -> Self {
+        self.batch_size = Some(files);
+        self
+    }
+
+    /// Enable or disable loading the metadata's file states without
+    /// validation.
+    pub fn trusted_metadata(mut self, trusted: bool) -> Self {
+        self.trusted_metadata = trusted;
+        self
+    }
+
+    /// Set the key metadata files are signed with.
+    pub fn metadata_key(mut self, key: Option<impl Into<String>>) -> Self {
+        self.metadata_key = key.map(|k| k.into());
+        self
+    }
+
+    /// Set the preset of GC defaults.
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Set whether GC treats the machine as ephemeral.
+    pub fn ephemeral(mut self, mode: impl Into<String>) -> Self {
+        self.ephemeral = Some(mode.into());
+        self
+    }
+
+    /// Set the salt of the recorded hashes.
+    pub fn hash_salt(mut self, salt: Option<impl Into<String>>) -> Self {
+        self.hash_salt = salt.map(|s| s.into());
+        self
+    }
+
+    /// Set the path of the dedup store.
+    pub fn dedup_store(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.dedup_store = path.map(|p| p.into());
+        self
+    }
+
+    /// Set the path of the discovery cache.
+    pub fn discovery_cache(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.discovery_cache = path.map(|p| p.into());
+        self
+    }
+
+    /// Set the path of the configuration file.
+    pub fn config(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.config = path.map(|p| p.into());
+        self
+    }
+
+    /// Set the path of the salvage report.
+    pub fn salvage_report(mut self, path: Option<impl Into<PathBuf>>) -> Self {
+        self.salvage_report = path.map(|p| p.into());
+        self
+    }
+
+    /// Add a path whose tracked files are left out.
+    pub fn exclude(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exclude.push(path.into());
+        self
+    }
+
+    /// Hash tracked files below the target directory.
+    pub fn hash_target_dir(mut self, enabled: bool) -> Self {
+        self.hash_target_dir = enabled;
+        self
+    }
+
     /// Build the `GlobalOpts` instance with the configured values.
     pub fn build(self) -> GlobalOpts {
         GlobalOpts {
             target_dir: self.target_dir.unwrap_or_else(|| PathBuf::from("target")),
+            build_dir: self.build_dir,
             metadata_path: self.metadata_path,
             verbose: self.verbose,
             quiet: self.quiet,
+            offline: self.offline,
+            background: self.background,
+            deny_warnings: self.deny_warnings,
+            summary_only: self.summary_only,
+            ci: self.ci.unwrap_or_else(|| "auto".to_string()),
+            metrics_report: self.metrics_report,
+            error_report: self.error_report,
+            size_units: self.size_units.unwrap_or_else(|| "binary".to_string()),
+            vcs: self.vcs.unwrap_or_else(|| "auto".to_string()),
+            lfs: self.lfs.unwrap_or_else(|| "content".to_string()),
+            large_file_threshold: self.large_file_threshold,
+            large_file_policy: self.large_file_policy.unwrap_or_else(|| "full".to_string()),
+            batch_size: self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+            trusted_metadata: self.trusted_metadata,
+            metadata_key: self.metadata_key,
+            profile: self.profile.unwrap_or_else(|| "ci".to_string()),
+            ephemeral: self.ephemeral.unwrap_or_else(|| "auto".to_string()),
+            hash_salt: self.hash_salt,
+            dedup_store: self.dedup_store,
+            discovery_cache: self.discovery_cache,
+            config: self.config,
+            salvage_report: self.salvage_report,
+            exclude: self.exclude,
+            hash_target_dir: self.hash_target_dir,
         }
     }
 }
@@ -360,7 +1349,10 @@ pub enum Commands {
     ///
     /// Use this command in CI before running `cargo build` to ensure
     /// incremental compilation works correctly with cached artifacts.
-    Anchor,
+    Anchor {
+        #[command(flatten)]
+        anchor: AnchorArgs,
+    },
 
     /// Salvage file timestamps from the metadata
     ///
@@ -392,6 +1384,37 @@ pub enum Commands {
     /// - You're troubleshooting incremental compilation issues
     Bilge,
 
+    /// Show which tracked files changed since the metadata was stowed
+    ///
+    /// Prints `A`, `M`, or `D` and the path of every added, modified, or
+    /// removed file. Files still holding their recorded size and timestamp
+    /// are not hashed, and only directories whose rollup hashes differ from
+    /// the recorded ones are compared file by file.
+    Diff {
+        /// Print the changed directories at most this many levels deep, with
+        /// the number of changes below each, instead of every file
+        #[arg(long)]
+        depth: Option<usize>,
+    },
+
+    /// Compare two metadata files, e.g. ones saved on different runners
+    ///
+    /// Prints `A` or `D` and the path of every file only the newer or only
+    /// the older file records, `M` for files recorded with a different hash
+    /// or size, and `T` for files whose timestamp alone differs, followed by
+    /// the GC metrics that changed. Neither file is modified.
+    Compare {
+        /// The older metadata file
+        old: PathBuf,
+
+        /// The newer metadata file
+        new: PathBuf,
+
+        /// Print the report as a JSON document (see `cargo hold schema`)
+        #[arg(long, env = "CARGO_HOLD_COMPARE_JSON")]
+        json: bool,
+    },
+
     /// Heave ho! Clean up old build artifacts
     ///
     /// Performs garbage collection on build artifacts to reclaim disk space:
@@ -409,20 +1432,45 @@ pub enum Commands {
         gc: GcArgs,
 
         /// Show what would be deleted without actually deleting
-        #[arg(long, env = "CARGO_HOLD_DRY_RUN")]
+        #[arg(long)]
         dry_run: bool,
 
         /// Enable debug output for garbage collection
-        #[arg(long, env = "CARGO_HOLD_DEBUG")]
+        #[arg(long)]
         debug: bool,
 
         /// Age threshold in days for removing artifacts (default: 7)
-        #[arg(long, default_value = "7", env = "CARGO_HOLD_AGE_THRESHOLD_DAYS")]
+        #[arg(long, default_value = "7")]
         age_threshold_days: u32,
 
         /// Enable auto max-target-size suggestions derived from prior runs.
-        #[arg(long, default_value_t = true, env = "CARGO_HOLD_AUTO_MAX_TARGET_SIZE")]
+        #[arg(long, default_value_t = true)]
         auto_max_target_size: bool,
+
+        /// Only clean subtrees of the target directory matching this glob
+        /// (e.g., "target/debug", "target/**/build"); may be repeated
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Compare this run's settings and each --policy in dry runs,
+        /// reporting what each would remove without deleting anything
+        #[arg(long)]
+        simulate: bool,
+
+        /// Compute, print, and record the auto max-target-size suggestion
+        /// without enforcing it, to observe it before trusting it
+        #[arg(long)]
+        suggest_only: bool,
+
+        /// Candidate policy for --simulate, as comma-separated settings
+        /// overriding this run's: cap=SIZE, files=N, age=DURATION,
+        /// costs=on|off, and, last, policy-cmd=COMMAND; may be repeated
+        #[arg(
+            long = "policy",
+            requires = "simulate",
+            env = "CARGO_HOLD_SIMULATE_POLICY"
+        )]
+        policies: Vec<String>,
     },
 
     /// Full voyage - anchor and heave in one command
@@ -432,27 +1480,349 @@ pub enum Commands {
     /// 2. Then runs heave to clean up old artifacts and manage disk usage
     ///
     /// This is ideal for CI pipelines that need both timestamp management
-    /// and disk space control in a single command.
+    /// and disk space control in a single command. Either step can be
+    /// skipped, so one entrypoint can serve every pipeline with the behavior
+    /// chosen through environment variables.
     Voyage {
+        #[command(flatten)]
+        anchor: AnchorArgs,
+
+        #[command(flatten)]
+        gc: GcArgs,
+
+        /// Show what would be deleted without actually deleting
+        #[arg(long)]
+        gc_dry_run: bool,
+
+        /// Enable debug output for garbage collection
+        #[arg(long)]
+        gc_debug: bool,
+
+        /// Age threshold in days for garbage collection (default: 7)
+        #[arg(long, default_value = "7")]
+        gc_age_threshold_days: u32,
+
+        /// Enable auto max-target-size suggestions derived from prior runs.
+        #[arg(long, default_value_t = true, env = "CARGO_HOLD_AUTO_MAX_TARGET_SIZE")]
+        gc_auto_max_target_size: bool,
+
+        /// Skip the anchor step and only run garbage collection
+        #[arg(long, conflicts_with = "no_gc")]
+        no_anchor: bool,
+
+        /// Skip garbage collection and only run the anchor step
+        #[arg(long)]
+        no_gc: bool,
+    },
+
+    /// Anchor or voyage every repository listed in a manifest
+    ///
+    /// For runners hosting many small projects: each repository gets its own
+    /// target directory and metadata, while the cargo home they share is
+    /// cleaned once up front, keeping the git dependencies of every
+    /// repository's lockfile. A `max-total-size` in the manifest splits one
+    /// size budget between the target directories, and a `disk-budget` covers
+    /// them and the cargo home together. Repositories are processed
+    /// one at a time unless `--jobs` says otherwise, and the outcome of all of
+    /// them is summarized in a single report. `--removal-list`,
+    /// `--preserve-units-from`, and `--rebuild-report` name a file of a single
+    /// repository, so the manifest gives them per repository instead.
+    Batch {
+        /// TOML manifest listing the repositories
+        #[arg(long)]
+        manifest: PathBuf,
+
+        /// Command run in each repository
+        #[arg(long, default_value = "voyage", value_parser = ["anchor", "voyage"])]
+        command: String,
+
+        /// Number of repositories processed at once
+        #[arg(long, default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Write the aggregated report as JSON to this file
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        #[command(flatten)]
+        anchor: AnchorArgs,
+
+        #[command(flatten)]
+        gc: GcArgs,
+
+        /// Show what would be deleted without actually deleting
+        #[arg(long)]
+        gc_dry_run: bool,
+
+        /// Enable debug output for garbage collection
+        #[arg(long)]
+        gc_debug: bool,
+
+        /// Age threshold in days for garbage collection (default: 7)
+        #[arg(long, default_value = "7")]
+        gc_age_threshold_days: u32,
+
+        /// Enable auto max-target-size suggestions derived from prior runs.
+        #[arg(long, default_value_t = true, env = "CARGO_HOLD_AUTO_MAX_TARGET_SIZE")]
+        gc_auto_max_target_size: bool,
+    },
+
+    /// Prepare for a build - salvage and heave, without stowing
+    ///
+    /// The pre-build half of a voyage split in two: timestamps are restored
+    /// and garbage is collected, but the file state is left for `dock` to
+    /// stow once the build has finished.
+    Embark {
         #[command(flatten)]
         gc: GcArgs,
 
         /// Show what would be deleted without actually deleting
-        #[arg(long, env = "CARGO_HOLD_GC_DRY_RUN")]
+        #[arg(long)]
         gc_dry_run: bool,
 
         /// Enable debug output for garbage collection
-        #[arg(long, env = "CARGO_HOLD_GC_DEBUG")]
+        #[arg(long)]
         gc_debug: bool,
 
         /// Age threshold in days for garbage collection (default: 7)
-        #[arg(long, default_value = "7", env = "CARGO_HOLD_GC_AGE_THRESHOLD_DAYS")]
+        #[arg(long, default_value = "7")]
         gc_age_threshold_days: u32,
 
         /// Enable auto max-target-size suggestions derived from prior runs.
         #[arg(long, default_value_t = true, env = "CARGO_HOLD_AUTO_MAX_TARGET_SIZE")]
         gc_auto_max_target_size: bool,
     },
+
+    /// Wrap up a build - stow and record the build's artifact timestamps
+    ///
+    /// The post-build half of a voyage split in two. Stowing after the build
+    /// and recording the newest artifact of every profile lets the next GC
+    /// preserve exactly what this build produced.
+    Dock,
+
+    /// Record the outcome of a build for GC heuristics
+    ///
+    /// Run after the build with its status and, optionally, how long it
+    /// took. While builds fail, GC keeps preserving the artifacts of the last
+    /// successful one, and auto-sizing leaves more headroom when rebuilds
+    /// are slow. With cargo's timing data, GC also evicts crates that are
+    /// cheap to rebuild before expensive ones.
+    ReportBuild {
+        /// Build outcome: "success" or "failure"
+        #[arg(long, env = "CARGO_HOLD_BUILD_STATUS")]
+        status: String,
+
+        /// How long the build took (e.g., "812s", "14m")
+        #[arg(long, env = "CARGO_HOLD_BUILD_DURATION")]
+        duration: Option<String>,
+
+        /// Cargo JSON messages with the build's timings (the output of
+        /// `cargo build --timings=json -Z unstable-options`), recording how
+        /// long each crate took to build
+        #[arg(long, env = "CARGO_HOLD_BUILD_TIMINGS")]
+        timings: Option<PathBuf>,
+    },
+
+    /// Write the recipe of a dependency build for a container image
+    ///
+    /// Copies every tracked manifest, lockfile, and toolchain or cargo
+    /// configuration file to the output directory, with a stub for every
+    /// target, all timestamped at the same epoch, so the recipe layer only
+    /// changes with the manifests. Build the dependencies in the recipe,
+    /// then copy the full sources on top and run `anchor`: unchanged
+    /// manifests keep their timestamps and only the real sources rebuild.
+    Plan {
+        /// Directory the recipe is written to; must be new or empty
+        #[arg(long, env = "CARGO_HOLD_PLAN_OUTPUT")]
+        output: PathBuf,
+
+        /// Modification time of every recipe file, in seconds since the Unix
+        /// epoch (default: `SOURCE_DATE_EPOCH`, or 1)
+        #[arg(long, env = "CARGO_HOLD_PLAN_EPOCH")]
+        epoch: Option<u64>,
+    },
+
+    /// Remove all artifacts of a single crate
+    ///
+    /// Deletes the crate's deps, fingerprints, build script output, and
+    /// incremental data from every profile directory, forcing cargo to
+    /// rebuild it (and everything depending on it) on the next build.
+    CleanCrate {
+        /// Name of the crate (dashes and underscores are interchangeable)
+        name: String,
+
+        /// Show what would be deleted without actually deleting
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Pin crates so garbage collection never evicts their artifacts
+    ///
+    /// Records crate name patterns in the metadata file. Patterns are crate
+    /// names or globs with `*` and `?` (e.g. `librocksdb-sys` or `*-sys`);
+    /// dashes and underscores are interchangeable. Without patterns, lists
+    /// the pinned crates.
+    Pin {
+        /// Crate names or globs to pin
+        patterns: Vec<String>,
+    },
+
+    /// Unpin crates pinned with `pin`
+    Unpin {
+        /// Patterns to unpin, exactly as they were pinned
+        #[arg(required = true)]
+        patterns: Vec<String>,
+    },
+
+    /// Seed the GC history from cargo-sweep or sccache
+    ///
+    /// Records when the last build ran, as noted by `cargo sweep --stamp` or
+    /// by sccache's statistics, as if cargo-hold had collected garbage then,
+    /// so the first `heave` after migrating preserves that build's artifacts
+    /// instead of evicting everything outside the age window.
+    ImportFrom {
+        /// The tool the file comes from
+        #[arg(value_parser = ["sweep", "sccache"])]
+        source: String,
+
+        /// cargo-sweep's sweep.timestamp, or the output of `sccache
+        /// --show-stats --stats-format=json` saved after the build
+        path: PathBuf,
+    },
+
+    /// Thaw artifacts compressed by --gc-compress-cold
+    ///
+    /// Decompresses every artifact listed in the target directory's
+    /// compressed artifact manifest back to its original path and
+    /// modification time, so cargo reuses it instead of rebuilding the
+    /// crate. Compressed copies of artifacts cargo has since rebuilt are
+    /// discarded.
+    Thaw,
+
+    /// Record the size and hash of every file in the target directory
+    ///
+    /// Writes `cargo-hold.seal.json` into the target directory. Run it as the
+    /// last step before the target directory is cached, then run `verify`
+    /// after the cache is restored.
+    Seal,
+
+    /// Check a restored target directory against its seal
+    ///
+    /// Reports every sealed file that is missing or corrupted, failing
+    /// before cargo trips over a truncated cache download. The size of every
+    /// file is checked, and a random sample of files is hashed.
+    Verify {
+        /// Hash every sealed file instead of a sample
+        #[arg(long, env = "CARGO_HOLD_VERIFY_FULL")]
+        full: bool,
+
+        /// Number of files to hash when not verifying fully
+        #[arg(long, default_value_t = 64, env = "CARGO_HOLD_VERIFY_SAMPLE")]
+        sample: usize,
+    },
+
+    /// Report on the target directory to guide GC settings
+    ///
+    /// `--age-histogram` buckets the crate artifacts by age, with the size
+    /// of each bucket and how much an age threshold at its start would
+    /// remove, followed by age percentiles weighted by size.
+    #[command(arg_required_else_help = true)]
+    Report {
+        /// Print a histogram and percentiles of the age of crate artifacts
+        #[arg(long)]
+        age_histogram: bool,
+    },
+
+    /// Install git hooks that salvage timestamps after checkouts and merges
+    ///
+    /// Writes `post-checkout` and `post-merge` hooks that run
+    /// `cargo hold salvage`, keeping local timestamps in sync with content
+    /// when switching branches or pulling. The hooks directory honors
+    /// `core.hooksPath`, and hooks not written by cargo-hold are left alone
+    /// unless `--force` is given.
+    InstallHooks {
+        /// Overwrite existing hooks that were not installed by cargo-hold
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print the JSON Schema of the JSON documents cargo-hold writes
+    ///
+    /// Covers the `--removal-list` output, the seal manifest, and the input
+    /// of `--gc-policy-cmd`. The schema only ever grows: properties are added
+    /// as optional, never removed or changed, and its `schema_version` is
+    /// bumped with every addition.
+    Schema,
+
+    /// Update cargo-hold to the latest release
+    ///
+    /// Downloads the release archive for this platform with `curl`, checks
+    /// it against the SHA-256 checksums published with the release, and
+    /// replaces the running binary. Set `CARGO_HOLD_UPDATE_CHECK=1` to be told
+    /// about new releases after every command instead.
+    SelfUpdate {
+        /// Only report whether a newer release is available
+        #[arg(long, env = "CARGO_HOLD_SELF_UPDATE_CHECK")]
+        check: bool,
+    },
+
+    /// Run the plugin `cargo-hold-<name>` found on `PATH`
+    ///
+    /// Any command cargo-hold does not know is looked up as an executable
+    /// named after it, which is run with the remaining arguments and the
+    /// resolved global options in `CARGO_HOLD_*` environment variables.
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+/// Give an option the environment variable named after its long flag, e.g.
+/// `CARGO_HOLD_MAX_TARGET_SIZE` for `--max-target-size`, unless it names one
+/// itself. Positional arguments have no environment variable.
+fn with_env(arg: Arg) -> Arg {
+    match arg.get_long() {
+        Some(long) if arg.get_env().is_none() => {
+            let env = format!("{ENV_PREFIX}{}", long.to_uppercase().replace('-', "_"));
+            arg.env(env)
+        }
+        _ => arg,
+    }
+}
+
+fn subcommand_with_env(command: Command) -> Command {
+    command.mut_args(with_env)
+}
+
+impl Commands {
+    /// The command's name, as typed on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Anchor { .. } => "anchor",
+            Commands::Salvage => "salvage",
+            Commands::Stow => "stow",
+            Commands::Bilge => "bilge",
+            Commands::Diff { .. } => "diff",
+            Commands::Compare { .. } => "compare",
+            Commands::Heave { .. } => "heave",
+            Commands::Voyage { .. } => "voyage",
+            Commands::Batch { .. } => "batch",
+            Commands::Embark { .. } => "embark",
+            Commands::Dock => "dock",
+            Commands::ReportBuild { .. } => "report-build",
+            Commands::Plan { .. } => "plan",
+            Commands::CleanCrate { .. } => "clean-crate",
+            Commands::Pin { .. } => "pin",
+            Commands::Unpin { .. } => "unpin",
+            Commands::ImportFrom { .. } => "import-from",
+            Commands::Thaw => "thaw",
+            Commands::Seal => "seal",
+            Commands::Verify { .. } => "verify",
+            Commands::Report { .. } => "report",
+            Commands::InstallHooks { .. } => "install-hooks",
+            Commands::SelfUpdate { .. } => "self-update",
+            Commands::Schema => "schema",
+            Commands::External(_) => "external",
+        }
+    }
 }
 
 impl Cli {
@@ -1,13 +1,15 @@
+use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 
 use clap::Parser;
 
-use crate::cli::{Cli, Commands, normalize_path};
+use crate::cli::{AnchorArgs, Cli, Commands, ENV_PREFIX, normalize_path};
+use crate::gc::{SandboxMode, SymlinkPolicy};
 
 #[test]
 fn test_cli_parsing() {
     let cli = Cli::parse_from(["cargo-hold", "anchor"]);
-    assert!(matches!(cli.command(), Commands::Anchor));
+    assert!(matches!(cli.command(), Commands::Anchor { .. }));
     assert_eq!(cli.global_opts().target_dir(), Path::new("target"));
     assert!(cli.global_opts().metadata_path().is_none());
     // get_metadata_path now returns absolute paths
@@ -76,14 +78,16 @@ fn test_cli_builder() {
         .target_dir("custom/target")
         .verbose(2)
         .quiet(false)
-        .command(Commands::Anchor)
+        .command(Commands::Anchor {
+            anchor: AnchorArgs::default(),
+        })
         .build()
         .expect("Failed to build CLI");
 
     assert_eq!(cli.global_opts().target_dir(), Path::new("custom/target"));
     assert_eq!(cli.global_opts().verbose(), 2);
     assert!(!cli.global_opts().quiet());
-    assert!(matches!(cli.command(), Commands::Anchor));
+    assert!(matches!(cli.command(), Commands::Anchor { .. }));
 
     // Test builder with metadata path
     let cli = Cli::builder()
@@ -127,3 +131,916 @@ fn test_normalize_path() {
     assert!(normalized.is_absolute());
     assert!(normalized.ends_with("a/c/e"));
 }
+
+#[test]
+fn test_install_hooks_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "install-hooks"]);
+    assert!(matches!(
+        cli.command(),
+        Commands::InstallHooks { force: false }
+    ));
+
+    let cli = Cli::parse_from(["cargo-hold", "install-hooks", "--force"]);
+    assert!(matches!(
+        cli.command(),
+        Commands::InstallHooks { force: true }
+    ));
+}
+
+#[test]
+fn test_preserve_units_from_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--preserve-units-from",
+        ".cargo/ci-unit-graph",
+    ]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(
+        gc.preserve_units_from(),
+        Some(Path::new(".cargo/ci-unit-graph"))
+    );
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert!(gc.preserve_units_from().is_none());
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--unit-graph", "units.json"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.preserve_units_from(), Some(Path::new("units.json")));
+}
+
+#[test]
+fn test_size_mode_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--size-mode", "apparent"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.size_mode(), Some("apparent"));
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert!(gc.size_mode().is_none());
+}
+
+#[test]
+fn test_simulate_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--simulate",
+        "--policy",
+        "cap=5G,age=3d",
+        "--policy",
+        "costs=off",
+    ]);
+    let Commands::Heave {
+        simulate, policies, ..
+    } = cli.command()
+    else {
+        panic!("expected heave command");
+    };
+    assert!(simulate);
+    assert_eq!(policies, &["cap=5G,age=3d", "costs=off"]);
+
+    assert!(Cli::try_parse_from(["cargo-hold", "heave", "--policy", "cap=5G"]).is_err());
+}
+
+#[test]
+fn test_suggest_only_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--suggest-only"]);
+    let Commands::Heave { suggest_only, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert!(suggest_only);
+}
+
+#[test]
+fn test_gc_compress_cold_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--gc-compress-cold"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert!(gc.gc_compress_cold());
+
+    let cli = Cli::parse_from(["cargo-hold", "thaw"]);
+    assert!(matches!(cli.command(), Commands::Thaw));
+}
+
+#[test]
+fn test_build_dir_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--build-dir",
+        "/cache/build",
+        "--max-build-dir-size",
+        "20G",
+    ]);
+    assert_eq!(
+        cli.global_opts().build_dir(),
+        Some(Path::new("/cache/build"))
+    );
+    assert_eq!(
+        cli.global_opts().get_build_dir(),
+        Some(PathBuf::from("/cache/build"))
+    );
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.max_build_dir_size(), Some("20G"));
+
+    // The target directory is no build directory apart from itself
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--target-dir",
+        "/ws/target",
+        "--build-dir",
+        "/ws/target",
+    ]);
+    assert_eq!(cli.global_opts().get_build_dir(), None);
+}
+
+#[test]
+fn test_max_coverage_size_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "voyage",
+        "--max-coverage-size",
+        "5G",
+        "--age-threshold-for",
+        "coverage=2d",
+    ]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.max_coverage_size(), Some("5G"));
+    assert_eq!(gc.age_threshold_for(), ["coverage=2d"]);
+}
+
+#[test]
+fn test_gc_dep_info_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--gc-dep-info"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert!(gc.gc_dep_info());
+
+    let cli = Cli::parse_from(["cargo-hold", "heave"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert!(!gc.gc_dep_info());
+}
+
+#[test]
+fn test_unknown_commands_parse_as_external() {
+    let cli = Cli::parse_from(["cargo-hold", "-v", "audit", "--deny", "stale"]);
+    let Commands::External(args) = cli.command() else {
+        panic!("Expected External command");
+    };
+    assert_eq!(args, &["audit", "--deny", "stale"]);
+    assert_eq!(cli.global_opts().verbose(), 1);
+}
+
+#[test]
+fn test_verify_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "verify"]);
+    let Commands::Verify { full, sample } = cli.command() else {
+        panic!("Expected Verify command");
+    };
+    assert!(!full);
+    assert_eq!(*sample, 64);
+
+    let cli = Cli::parse_from(["cargo-hold", "verify", "--full", "--sample", "10"]);
+    let Commands::Verify { full, sample } = cli.command() else {
+        panic!("Expected Verify command");
+    };
+    assert!(full);
+    assert_eq!(*sample, 10);
+}
+
+#[test]
+fn test_pin_and_unpin_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "pin", "rocksdb-sys", "*-sys"]);
+    let Commands::Pin { patterns } = cli.command() else {
+        panic!("Expected Pin command");
+    };
+    assert_eq!(patterns, &["rocksdb-sys", "*-sys"]);
+
+    let cli = Cli::parse_from(["cargo-hold", "pin"]);
+    assert!(matches!(cli.command(), Commands::Pin { patterns } if patterns.is_empty()));
+
+    let cli = Cli::parse_from(["cargo-hold", "unpin", "rocksdb-sys"]);
+    let Commands::Unpin { patterns } = cli.command() else {
+        panic!("Expected Unpin command");
+    };
+    assert_eq!(patterns, &["rocksdb-sys"]);
+
+    assert!(Cli::try_parse_from(["cargo-hold", "unpin"]).is_err());
+}
+
+#[test]
+fn test_max_target_files_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--max-target-files", "500000"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.max_target_files(), Some(500_000));
+}
+
+#[test]
+fn test_gc_when_busy_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--gc-when-busy", "skip"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.gc_when_busy(), Some("skip"));
+}
+
+#[test]
+fn test_age_threshold_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--age-threshold",
+        "36h",
+        "--age-threshold-for",
+        "deps=12h,registry-cache=90d",
+    ]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.age_threshold(), Some("36h"));
+    assert_eq!(gc.age_threshold_for(), ["deps=12h", "registry-cache=90d"]);
+}
+
+#[test]
+fn test_lockfile_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "voyage",
+        "--lockfile",
+        "a/Cargo.lock",
+        "--lockfile",
+        "b/Cargo.lock",
+    ]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(
+        gc.lockfiles(),
+        [PathBuf::from("a/Cargo.lock"), PathBuf::from("b/Cargo.lock")]
+    );
+}
+
+#[test]
+fn test_gc_policy_cmd_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--gc-policy-cmd",
+        "./policy.sh --strict",
+    ]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.gc_policy_cmd(), Some("./policy.sh --strict"));
+}
+
+#[test]
+fn test_global_cache_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--global-cache", "off"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.global_cache(), Some("off"));
+}
+
+#[test]
+fn test_gc_sandbox_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--gc-sandbox", "on"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.gc_sandbox(), Some("on"));
+    assert_eq!(
+        gc.gc_sandbox().unwrap().parse::<SandboxMode>().unwrap(),
+        SandboxMode::On
+    );
+    assert!("strict".parse::<SandboxMode>().is_err());
+}
+
+#[test]
+fn test_compare_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "compare",
+        "a.metadata",
+        "b.metadata",
+        "--json",
+    ]);
+    let Commands::Compare { old, new, json } = cli.command() else {
+        panic!("expected compare command");
+    };
+    assert_eq!(old, Path::new("a.metadata"));
+    assert_eq!(new, Path::new("b.metadata"));
+    assert!(json);
+}
+
+#[test]
+fn test_import_from_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "import-from", "sweep", "sweep.timestamp"]);
+    let Commands::ImportFrom { source, path } = cli.command() else {
+        panic!("expected import-from command");
+    };
+    assert_eq!(source, "sweep");
+    assert_eq!(path, Path::new("sweep.timestamp"));
+
+    let result = Cli::try_parse_from(["cargo-hold", "import-from", "cachepot", "stats.json"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_gc_force_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--max-target-size", "5G", "--force"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert!(gc.force());
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert!(!gc.force());
+}
+
+#[test]
+fn test_gc_metrics_window_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--gc-metrics-window", "52"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.gc_metrics_window().map(NonZeroU32::get), Some(52));
+
+    let result = Cli::try_parse_from(["cargo-hold", "heave", "--gc-metrics-window", "0"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_gc_symlinks_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--gc-symlinks", "follow"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(
+        gc.gc_symlinks().unwrap().parse::<SymlinkPolicy>().unwrap(),
+        SymlinkPolicy::Follow
+    );
+}
+
+#[test]
+fn test_removal_list_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--dry-run",
+        "--removal-list",
+        "-",
+        "--removal-list-format",
+        "nul",
+        "--gc-audit-log",
+        "/var/log/cargo-hold-gc.jsonl",
+        "--gc-audit-log-format",
+        "jsonl",
+    ]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.removal_list(), Some(Path::new("-")));
+    assert_eq!(gc.removal_list_format(), Some("nul"));
+    assert_eq!(
+        gc.gc_audit_log(),
+        Some(Path::new("/var/log/cargo-hold-gc.jsonl"))
+    );
+    assert_eq!(gc.gc_audit_log_format(), Some("jsonl"));
+}
+
+#[test]
+fn test_gc_max_duration_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--gc-max-duration", "120s"]);
+    let Commands::Voyage { gc, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert_eq!(gc.gc_max_duration(), Some("120s"));
+}
+
+#[test]
+fn test_cargo_home_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave", "--cargo-home", "/mnt/cache/cargo"]);
+    let Commands::Heave { gc, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(gc.cargo_home(), Some(Path::new("/mnt/cache/cargo")));
+}
+
+#[test]
+fn test_heave_only_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--only",
+        "target/debug",
+        "--only",
+        "target/**/build",
+    ]);
+    let Commands::Heave { only, .. } = cli.command() else {
+        panic!("expected heave command");
+    };
+    assert_eq!(only, &["target/debug", "target/**/build"]);
+}
+
+#[test]
+fn test_clean_crate_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "clean-crate", "my-proto", "--dry-run"]);
+    let Commands::CleanCrate { name, dry_run } = cli.command() else {
+        panic!("expected clean-crate command");
+    };
+    assert_eq!(name, "my-proto");
+    assert!(dry_run);
+}
+
+#[test]
+fn test_summary_only_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "anchor", "--summary-only"]);
+    assert!(cli.global_opts().summary_only());
+    assert_eq!(cli.command().name(), "anchor");
+
+    assert!(Cli::try_parse_from(["cargo-hold", "--summary-only", "-v", "anchor"]).is_err());
+    assert!(Cli::try_parse_from(["cargo-hold", "--summary-only", "--quiet", "anchor"]).is_err());
+}
+
+#[test]
+fn test_ci_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave"]);
+    assert_eq!(cli.global_opts().ci(), "auto");
+    assert!(cli.global_opts().metrics_report().is_none());
+    assert!(cli.global_opts().error_report().is_none());
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "heave",
+        "--ci",
+        "gitlab",
+        "--metrics-report",
+        "metrics.txt",
+        "--error-report",
+        "error.json",
+    ]);
+    assert_eq!(cli.global_opts().ci(), "gitlab");
+    assert_eq!(
+        cli.global_opts().metrics_report(),
+        Some(Path::new("metrics.txt"))
+    );
+    assert_eq!(
+        cli.global_opts().error_report(),
+        Some(Path::new("error.json"))
+    );
+}
+
+#[test]
+fn test_background_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage"]);
+    assert!(!cli.global_opts().background());
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--background"]);
+    assert!(cli.global_opts().background());
+}
+
+#[test]
+fn test_size_units_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave"]);
+    assert_eq!(cli.global_opts().size_units(), "binary");
+
+    let cli = Cli::parse_from(["cargo-hold", "--size-units", "si", "heave"]);
+    assert_eq!(cli.global_opts().size_units(), "si");
+}
+
+#[test]
+fn test_vcs_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert_eq!(cli.global_opts().vcs(), "auto");
+
+    let cli = Cli::parse_from(["cargo-hold", "salvage", "--vcs", "git"]);
+    assert_eq!(cli.global_opts().vcs(), "git");
+}
+
+#[test]
+fn test_lfs_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert_eq!(cli.global_opts().lfs(), "content");
+
+    let cli = Cli::parse_from(["cargo-hold", "--lfs", "pointer", "salvage"]);
+    assert_eq!(cli.global_opts().lfs(), "pointer");
+}
+
+#[test]
+fn test_large_file_options_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "anchor"]);
+    assert_eq!(cli.global_opts().large_file_threshold(), None);
+    assert_eq!(cli.global_opts().large_file_policy(), "full");
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "stow",
+        "--large-file-threshold",
+        "1G",
+        "--large-file-policy",
+        "head:16M",
+    ]);
+    assert_eq!(cli.global_opts().large_file_threshold(), Some("1G"));
+    assert_eq!(cli.global_opts().large_file_policy(), "head:16M");
+}
+
+#[test]
+fn test_batch_size_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert_eq!(cli.global_opts().batch_size().get(), 16_384);
+
+    let cli = Cli::parse_from(["cargo-hold", "--batch-size", "1000", "anchor"]);
+    assert_eq!(cli.global_opts().batch_size().get(), 1000);
+
+    assert!(Cli::try_parse_from(["cargo-hold", "--batch-size", "0", "anchor"]).is_err());
+}
+
+#[test]
+fn test_trusted_metadata_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "salvage"]);
+    assert!(!cli.global_opts().trusted_metadata());
+
+    let cli = Cli::parse_from(["cargo-hold", "anchor", "--trusted-metadata"]);
+    assert!(cli.global_opts().trusted_metadata());
+}
+
+#[test]
+fn test_metadata_key_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert_eq!(cli.global_opts().metadata_key(), None);
+
+    let cli = Cli::parse_from(["cargo-hold", "--metadata-key", "s3cret", "salvage"]);
+    assert_eq!(cli.global_opts().metadata_key(), Some("s3cret"));
+}
+
+#[test]
+fn test_profile_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave"]);
+    assert_eq!(cli.global_opts().profile(), "ci");
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--profile", "local"]);
+    assert_eq!(cli.global_opts().profile(), "local");
+}
+
+#[test]
+fn test_ephemeral_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "heave"]);
+    assert_eq!(cli.global_opts().ephemeral(), "auto");
+
+    let cli = Cli::parse_from(["cargo-hold", "--ephemeral", "off", "voyage"]);
+    assert_eq!(cli.global_opts().ephemeral(), "off");
+}
+
+#[test]
+fn test_diff_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "diff"]);
+    assert!(matches!(cli.command(), Commands::Diff { depth: None }));
+
+    let cli = Cli::parse_from(["cargo-hold", "diff", "--depth", "2"]);
+    assert!(matches!(cli.command(), Commands::Diff { depth: Some(2) }));
+    assert_eq!(cli.command().name(), "diff");
+}
+
+#[test]
+fn test_plan_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "plan", "--output", "recipe"]);
+    let Commands::Plan { output, epoch } = cli.command() else {
+        panic!("expected plan");
+    };
+    assert_eq!(output, Path::new("recipe"));
+    assert_eq!(*epoch, None);
+    assert_eq!(cli.command().name(), "plan");
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "plan",
+        "--output",
+        "recipe",
+        "--epoch",
+        "1700000000",
+    ]);
+    assert!(matches!(
+        cli.command(),
+        Commands::Plan {
+            epoch: Some(1_700_000_000),
+            ..
+        }
+    ));
+    assert!(Cli::try_parse_from(["cargo-hold", "plan"]).is_err());
+}
+
+#[test]
+fn test_hash_salt_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert_eq!(cli.global_opts().hash_salt(), None);
+
+    let cli = Cli::parse_from(["cargo-hold", "salvage", "--hash-salt", "none"]);
+    assert_eq!(cli.global_opts().hash_salt(), Some("none"));
+}
+
+#[test]
+fn test_dedup_store_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert!(cli.global_opts().dedup_store().is_none());
+
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--dedup-store", "cache/dedup"]);
+    assert_eq!(
+        cli.global_opts().dedup_store(),
+        Some(Path::new("cache/dedup"))
+    );
+}
+
+#[test]
+fn test_discovery_cache_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "anchor"]);
+    assert!(cli.global_opts().discovery_cache().is_none());
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "anchor",
+        "--discovery-cache",
+        "target/listing",
+    ]);
+    assert_eq!(
+        cli.global_opts().discovery_cache(),
+        Some(Path::new("target/listing"))
+    );
+}
+
+#[test]
+fn test_config_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert!(cli.global_opts().config().is_none());
+
+    let cli = Cli::parse_from(["cargo-hold", "--config", "ci/hold.json", "anchor"]);
+    assert_eq!(cli.global_opts().config(), Some(Path::new("ci/hold.json")));
+}
+
+#[test]
+fn test_check_rebuilds_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "anchor"]);
+    let Commands::Anchor { anchor } = cli.command() else {
+        panic!("expected anchor command");
+    };
+    assert!(!anchor.check_rebuilds());
+    assert!(anchor.rebuild_report().is_none());
+
+    let cli = Cli::parse_from(["cargo-hold", "anchor", "--check-rebuilds"]);
+    let Commands::Anchor { anchor } = cli.command() else {
+        panic!("expected anchor command");
+    };
+    assert!(anchor.check_rebuilds());
+
+    // A report implies the check
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--rebuild-report", "rebuilds.json"]);
+    let Commands::Voyage { anchor, .. } = cli.command() else {
+        panic!("expected voyage command");
+    };
+    assert!(anchor.check_rebuilds());
+    assert_eq!(anchor.rebuild_report(), Some(Path::new("rebuilds.json")));
+}
+
+#[test]
+fn test_salvage_report_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "anchor"]);
+    assert!(cli.global_opts().salvage_report().is_none());
+
+    let cli = Cli::parse_from(["cargo-hold", "salvage", "--salvage-report", "changes.json"]);
+    assert_eq!(
+        cli.global_opts().salvage_report(),
+        Some(Path::new("changes.json"))
+    );
+}
+
+#[test]
+fn test_exclusion_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "stow"]);
+    assert!(cli.global_opts().exclude().is_empty());
+    assert!(!cli.global_opts().hash_target_dir());
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "stow",
+        "--exclude",
+        "vendor,docs/generated",
+        "--exclude",
+        "assets",
+        "--hash-target-dir",
+    ]);
+    assert_eq!(
+        cli.global_opts().exclude(),
+        [
+            PathBuf::from("vendor"),
+            PathBuf::from("docs/generated"),
+            PathBuf::from("assets")
+        ]
+    );
+    assert!(cli.global_opts().hash_target_dir());
+}
+
+#[test]
+fn test_batch_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "batch", "--manifest", "repos.toml"]);
+    let Commands::Batch {
+        manifest,
+        command,
+        jobs,
+        report,
+        ..
+    } = cli.command()
+    else {
+        panic!("expected batch command");
+    };
+    assert_eq!(manifest, Path::new("repos.toml"));
+    assert_eq!(command, "voyage");
+    assert_eq!(jobs.get(), 1);
+    assert!(report.is_none());
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "batch",
+        "--manifest",
+        "repos.toml",
+        "--command",
+        "anchor",
+        "--jobs",
+        "4",
+        "--report",
+        "batch.json",
+    ]);
+    let Commands::Batch {
+        command,
+        jobs,
+        report,
+        ..
+    } = cli.command()
+    else {
+        panic!("expected batch command");
+    };
+    assert_eq!(command, "anchor");
+    assert_eq!(jobs.get(), 4);
+    assert_eq!(report.as_deref(), Some(Path::new("batch.json")));
+
+    assert!(Cli::try_parse_from(["cargo-hold", "batch"]).is_err());
+    assert!(
+        Cli::try_parse_from([
+            "cargo-hold",
+            "batch",
+            "--manifest",
+            "repos.toml",
+            "--command",
+            "stow"
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn test_voyage_skip_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "voyage", "--no-gc"]);
+    let Commands::Voyage {
+        no_anchor, no_gc, ..
+    } = cli.command()
+    else {
+        panic!("expected voyage command");
+    };
+    assert!(!no_anchor);
+    assert!(no_gc);
+
+    assert!(Cli::try_parse_from(["cargo-hold", "voyage", "--no-anchor", "--no-gc"]).is_err());
+}
+
+#[test]
+fn test_embark_and_dock_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "embark", "--max-target-size", "5G"]);
+    let Commands::Embark { gc, .. } = cli.command() else {
+        panic!("expected embark command");
+    };
+    assert_eq!(gc.max_target_size(), Some("5G"));
+    assert_eq!(cli.command().name(), "embark");
+
+    let cli = Cli::parse_from(["cargo-hold", "dock"]);
+    assert!(matches!(cli.command(), Commands::Dock));
+}
+
+#[test]
+fn test_report_build_parsing() {
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "report-build",
+        "--status",
+        "success",
+        "--duration",
+        "812s",
+        "--timings",
+        "timings.json",
+    ]);
+    let Commands::ReportBuild {
+        status,
+        duration,
+        timings,
+    } = cli.command()
+    else {
+        panic!("expected report-build command");
+    };
+    assert_eq!(status, "success");
+    assert_eq!(duration.as_deref(), Some("812s"));
+    assert_eq!(timings.as_deref(), Some(Path::new("timings.json")));
+
+    assert!(Cli::try_parse_from(["cargo-hold", "report-build"]).is_err());
+}
+
+#[test]
+fn test_report_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "report", "--age-histogram"]);
+    assert!(matches!(
+        cli.command(),
+        Commands::Report {
+            age_histogram: true
+        }
+    ));
+    assert_eq!(cli.command().name(), "report");
+}
+
+#[test]
+fn test_every_option_has_an_env_var() {
+    let mut command = <Cli as clap::CommandFactory>::command();
+    command.build();
+
+    let commands = std::iter::once(&command).chain(command.get_subcommands());
+    for command in commands {
+        for arg in command.get_arguments() {
+            let Some(long) = arg.get_long() else {
+                continue;
+            };
+            if ["help", "version"].contains(&long) {
+                continue;
+            }
+            let env = arg.get_env().map(|env| env.to_string_lossy().into_owned());
+            assert!(
+                env.as_deref()
+                    .is_some_and(|env| env.starts_with(ENV_PREFIX)),
+                "--{long} of {} has no {ENV_PREFIX}* variable",
+                command.get_name()
+            );
+        }
+    }
+
+    let install_hooks = command.find_subcommand("install-hooks").unwrap();
+    let force = install_hooks
+        .get_arguments()
+        .find(|arg| arg.get_id() == "force")
+        .unwrap();
+    assert_eq!(force.get_env().unwrap(), "CARGO_HOLD_FORCE");
+
+    let verify = command.find_subcommand("verify").unwrap();
+    let full = verify
+        .get_arguments()
+        .find(|arg| arg.get_id() == "full")
+        .unwrap();
+    assert_eq!(full.get_env().unwrap(), "CARGO_HOLD_VERIFY_FULL");
+}
+
+#[test]
+fn test_self_update_parsing() {
+    let cli = Cli::parse_from(["cargo-hold", "self-update"]);
+    assert!(matches!(
+        cli.command(),
+        Commands::SelfUpdate { check: false }
+    ));
+    assert_eq!(cli.command().name(), "self-update");
+
+    let cli = Cli::parse_from(["cargo-hold", "self-update", "--check"]);
+    assert!(matches!(
+        cli.command(),
+        Commands::SelfUpdate { check: true }
+    ));
+}
@@ -2,26 +2,204 @@
 
 use std::path::Path;
 
-use super::salvage::salvage;
-use super::stow::stow;
+use super::salvage::salvage_files;
+use super::stow::{run_post_stow_hook, stow};
+use crate::cli::AnchorArgs;
+use crate::context::HoldContext;
 use crate::error::Result;
+use crate::gc::format_age;
+use crate::hooks::{self, Hook};
+use crate::lock::{LockMode, TargetDirLock};
 use crate::logging::Logger;
+use crate::rebuilds::{find_stale_units, write_rebuild_report};
+use crate::workspace::invalidate_changed_members;
+
+/// What anchor did with the metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorOutcome {
+    /// The current state was stowed to the metadata
+    Stowed,
+    /// No tracked file changed since the metadata was stowed, so it was left
+    /// as is, down to its modification time
+    Unchanged,
+}
+
+impl AnchorOutcome {
+    /// Name of the outcome in JSON reports
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Stowed => "stowed",
+            Self::Unchanged => "unchanged",
+        }
+    }
+}
 
 /// Executes the anchor command - the main orchestrator.
 ///
 /// This command anchors your build state by performing the complete workflow:
-/// 1. Restores timestamps from the metadata
-/// 2. Scans for changes and saves the new state
+/// 1. Runs the `pre-anchor` hook
+/// 2. Restores timestamps from the metadata
+/// 3. Scans for changes and saves the new state, unless no tracked file changed
+///    since the metadata was stowed
+///
+/// Cargo's lock on `target_dir` is held while timestamps are restored.
 ///
 /// This is the recommended command for CI use.
-pub fn anchor(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path) -> Result<()> {
-    let log = Logger::new(verbose, quiet);
+pub fn anchor(
+    context: &HoldContext,
+    metadata_path: &Path,
+    target_dir: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<AnchorOutcome> {
+    anchor_with_args(
+        context,
+        metadata_path,
+        target_dir,
+        verbose,
+        quiet,
+        working_dir,
+        &AnchorArgs::default(),
+    )
+}
+
+/// Executes the anchor command with additional anchor options.
+///
+/// When [`AnchorArgs::invalidate_fingerprints`] is set, fingerprints of the
+/// workspace members owning modified or added files (and of their workspace
+/// dependents) are removed from `target_dir` before the new state is stowed.
+///
+/// With [`AnchorArgs::check_rebuilds`], the crates cargo will rebuild because
+/// a restored source is newer than their fingerprint are reported afterwards,
+/// and written to [`AnchorArgs::rebuild_report`] if set.
+///
+/// Cargo's lock on `target_dir` is held while timestamps are restored and
+/// fingerprints invalidated, so a concurrent build waits for us (and we for
+/// it).
+pub fn anchor_with_args(
+    context: &HoldContext,
+    metadata_path: &Path,
+    target_dir: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+    args: &AnchorArgs,
+) -> Result<AnchorOutcome> {
+    let log = context.logger(verbose, quiet);
     log.info("⚓ Anchoring build state...");
+    hooks::run(
+        context,
+        Hook::PreAnchor,
+        &[("CARGO_HOLD_METADATA", metadata_path.display().to_string())],
+        &log,
+    )?;
+
+    let lock_mode = if args.invalidate_fingerprints() {
+        LockMode::Exclusive
+    } else {
+        LockMode::Shared
+    };
+    let lock = TargetDirLock::acquire(target_dir, lock_mode, &log)?;
+
+    let outcome = salvage_files(context, metadata_path, verbose, quiet, working_dir)?;
+
+    if args.invalidate_fingerprints()
+        && let Some(repo_root) = &outcome.repo_root
+    {
+        let changed: Vec<_> = outcome
+            .modified
+            .iter()
+            .chain(&outcome.added)
+            .cloned()
+            .collect();
+        let stats = invalidate_changed_members(repo_root, &changed, target_dir, &log)?;
+        log.summarize_count("fingerprints invalidated", stats.fingerprints_removed);
+        if !stats.packages.is_empty() {
+            log.info(format!(
+                "🔄 Invalidated {} fingerprint{} across {} workspace member{}",
+                stats.fingerprints_removed,
+                if stats.fingerprints_removed == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                stats.packages.len(),
+                if stats.packages.len() == 1 { "" } else { "s" },
+            ));
+        }
+    }
+
+    if args.check_rebuilds()
+        && let Some(repo_root) = &outcome.repo_root
+    {
+        check_rebuilds(target_dir, repo_root, args.rebuild_report(), &log)?;
+    }
 
-    salvage(metadata_path, verbose, quiet, working_dir)?;
-    stow(metadata_path, verbose, quiet, working_dir)?;
+    drop(lock);
+
+    let anchored = if outcome.up_to_date {
+        // Rewriting the same state would only touch the file, which busts
+        // caches that deduplicate uploads
+        log.summarize("unchanged", "metadata");
+        log.info("⚓ No changes since the last stow; metadata left as is");
+        run_post_stow_hook(context, metadata_path, 0, false, &log)?;
+        AnchorOutcome::Unchanged
+    } else {
+        stow(context, metadata_path, verbose, quiet, working_dir)?;
+        AnchorOutcome::Stowed
+    };
 
     log.info("⚓ Build state anchored successfully");
 
+    Ok(anchored)
+}
+
+/// Report the crates of `target_dir` that cargo will rebuild because a
+/// source under `repo_root` is newer than their fingerprint, writing them to
+/// `report` if given
+fn check_rebuilds(
+    target_dir: &Path,
+    repo_root: &Path,
+    report: Option<&Path>,
+    log: &Logger,
+) -> Result<()> {
+    let stale = find_stale_units(target_dir, repo_root)?;
+    log.summarize_count("rebuilds expected", stale.len());
+    if stale.is_empty() {
+        log.verbose(1, "No crate is older than its sources");
+    } else {
+        let mut names: Vec<&str> = stale.iter().map(|unit| unit.name.as_str()).collect();
+        names.dedup();
+        let listed = if names.len() > 3 {
+            format!("{}, and {} more", names[..3].join(", "), names.len() - 3)
+        } else {
+            names.join(", ")
+        };
+        log.notice(format!(
+            "Cargo will rebuild {} unit{} whose sources are newer than the last build ({listed}); \
+             run with -v for the sources",
+            stale.len(),
+            if stale.len() == 1 { "" } else { "s" },
+        ));
+        for unit in &stale {
+            log.verbose(
+                1,
+                format!(
+                    "  {}/{}-{}: {} is {} newer",
+                    unit.profile,
+                    unit.name,
+                    unit.hash,
+                    unit.source.display(),
+                    format_age(unit.newer_by)
+                ),
+            );
+        }
+    }
+
+    if let Some(path) = report {
+        write_rebuild_report(&stale, path)?;
+        log.verbose(1, format!("Wrote rebuild report to {}", path.display()));
+    }
     Ok(())
 }
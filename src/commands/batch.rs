@@ -0,0 +1,859 @@
+//! Batch command: anchor or voyage across the repositories of a manifest.
+//!
+//! The manifest is a TOML file listing the repositories, relative to the
+//! manifest's directory:
+//!
+//! ```toml
+//! max-total-size = "40G"
+//!
+//! [[repo]]
+//! path = "services/api"
+//!
+//! [[repo]]
+//! path = "services/worker"
+//! target-dir = "build"
+//! max-target-size = "5G"
+//! ```
+//!
+//! `target-dir` and `metadata-path` are relative to the repository and
+//! default to `target` and `<target-dir>/cargo-hold.metadata`. So is
+//! `build-dir`, cargo's build directory when it is apart from the target
+//! directory, which defaults to `--build-dir` in every repository. So are
+//! `removal-list`, `preserve-units-from`, and `rebuild-report`, which take
+//! the place of the options of the same name: one file given on the command
+//! line would be shared by every repository. The
+//! `max-total-size` budget is split evenly between the repositories without
+//! a `max-target-size` of their own, after taking out the ones with.
+//!
+//! A `disk-budget` instead covers the target directories and the cargo home
+//! together. What the cargo home holds once cleaned is taken out of it, and
+//! when the target directories do not fit in the rest, the space to free is
+//! shared between them in proportion to their size divided by their
+//! `priority` (1 unless given), so that higher priorities give up less.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use serde_json::{Map, Value};
+use toml::{Table, Value as TomlValue};
+
+use crate::cli::{AnchorArgs, GcArgs};
+use crate::commands::anchor::{AnchorOutcome, anchor_with_args};
+use crate::commands::voyage::Voyage;
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::gc::config::{Gc, GcStats};
+use crate::gc::{self, SizeUnits};
+use crate::logging::{Logger, Metric, Summary};
+use crate::schema::SCHEMA_VERSION;
+
+/// A repository of a batch manifest
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BatchRepo {
+    /// Path as written in the manifest
+    pub(crate) name: String,
+    /// Working directory of the repository
+    pub(crate) path: PathBuf,
+    pub(crate) target_dir: PathBuf,
+    /// cargo's build directory, if apart from the target directory
+    pub(crate) build_dir: Option<PathBuf>,
+    pub(crate) metadata_path: PathBuf,
+    /// Size cap of the target directory: the repository's own, or its share
+    /// of `max-total-size`
+    pub(crate) max_target_size: Option<u64>,
+    /// Weight against giving up space to the disk budget, at least 1
+    pub(crate) priority: u32,
+    /// `--removal-list` of the repository's voyage
+    pub(crate) removal_list: Option<PathBuf>,
+    /// `--preserve-units-from` of the repository's voyage
+    pub(crate) preserve_units_from: Option<PathBuf>,
+    /// `--rebuild-report` of the repository's anchor
+    pub(crate) rebuild_report: Option<PathBuf>,
+}
+
+/// The repositories of a batch manifest
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BatchManifest {
+    pub(crate) repos: Vec<BatchRepo>,
+    /// Size budget of the target directories and the cargo home together
+    pub(crate) disk_budget: Option<u64>,
+}
+
+impl BatchManifest {
+    /// Reads the manifest at `path`, relative to `working_dir`, with sizes in
+    /// `units`
+    pub(crate) fn load(path: &Path, working_dir: &Path, units: SizeUnits) -> Result<Self> {
+        let path = working_dir.join(path);
+        let contents = std::fs::read_to_string(&path).map_err(|source| HoldError::IoError {
+            path: path.clone(),
+            source,
+        })?;
+        let base = path.parent().unwrap_or(working_dir);
+        Self::parse(&contents, base, units).map_err(|message| HoldError::InvalidBatchManifest {
+            path: path.clone(),
+            message,
+        })
+    }
+
+    fn parse(contents: &str, base: &Path, units: SizeUnits) -> std::result::Result<Self, String> {
+        let manifest: Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        let mut max_total_size = None;
+        let mut disk_budget = None;
+        let mut repos = Vec::new();
+        for (key, value) in manifest {
+            match key.as_str() {
+                "max-total-size" => max_total_size = Some(size(&key, value, units)?),
+                "disk-budget" => disk_budget = Some(size(&key, value, units)?),
+                "repo" => {
+                    let TomlValue::Array(entries) = value else {
+                        return Err("'repo' must be an array of tables ([[repo]])".to_string());
+                    };
+                    for entry in entries {
+                        let TomlValue::Table(entry) = entry else {
+                            return Err("'repo' must be an array of tables ([[repo]])".to_string());
+                        };
+                        repos.push(BatchRepo::parse(entry, base, units)?);
+                    }
+                }
+                _ => return Err(format!("unknown key '{key}'")),
+            }
+        }
+        if repos.is_empty() {
+            return Err("no repositories; list them as [[repo]] tables".to_string());
+        }
+        if max_total_size.is_some() && disk_budget.is_some() {
+            return Err("give either max-total-size or disk-budget, not both".to_string());
+        }
+
+        if let Some(total) = max_total_size {
+            let capped: u64 = repos.iter().filter_map(|repo| repo.max_target_size).sum();
+            let uncapped = repos
+                .iter()
+                .filter(|repo| repo.max_target_size.is_none())
+                .count() as u64;
+            if capped > total || (uncapped > 0 && capped == total) {
+                return Err(format!(
+                    "the repositories' own max-target-size add up to {}, leaving nothing of \
+                     max-total-size {}",
+                    units.format(capped),
+                    units.format(total)
+                ));
+            }
+            let share = (total - capped) / uncapped.max(1);
+            for repo in &mut repos {
+                repo.max_target_size.get_or_insert(share);
+            }
+        }
+        Ok(Self { repos, disk_budget })
+    }
+}
+
+impl BatchRepo {
+    fn parse(entry: Table, base: &Path, units: SizeUnits) -> std::result::Result<Self, String> {
+        let mut name = None;
+        let mut target_dir = None;
+        let mut build_dir = None;
+        let mut metadata_path = None;
+        let mut max_target_size = None;
+        let mut priority = 1;
+        let mut removal_list = None;
+        let mut preserve_units_from = None;
+        let mut rebuild_report = None;
+        for (key, value) in entry {
+            match key.as_str() {
+                "path" => name = Some(string(&key, value)?),
+                "target-dir" => target_dir = Some(string(&key, value)?),
+                "build-dir" => build_dir = Some(string(&key, value)?),
+                "metadata-path" => metadata_path = Some(string(&key, value)?),
+                "removal-list" => {
+                    let list = string(&key, value)?;
+                    if list == "-" {
+                        return Err("'removal-list' must be a file; the repositories cannot \
+                                    share stdout"
+                            .to_string());
+                    }
+                    removal_list = Some(list);
+                }
+                "preserve-units-from" => preserve_units_from = Some(string(&key, value)?),
+                "rebuild-report" => rebuild_report = Some(string(&key, value)?),
+                "max-target-size" => max_target_size = Some(size(&key, value, units)?),
+                "priority" => {
+                    priority = match value {
+                        TomlValue::Integer(priority) => u32::try_from(priority)
+                            .ok()
+                            .filter(|priority| *priority > 0),
+                        _ => None,
+                    }
+                    .ok_or("'priority' must be a positive integer")?;
+                }
+                _ => return Err(format!("unknown repository key '{key}'")),
+            }
+        }
+        let name = name.ok_or("a repository has no 'path'")?;
+        let path = base.join(&name);
+        let target_dir = path.join(target_dir.as_deref().unwrap_or("target"));
+        let metadata_path = match metadata_path {
+            Some(metadata_path) => path.join(metadata_path),
+            None => target_dir.join("cargo-hold.metadata"),
+        };
+        Ok(Self {
+            build_dir: build_dir.map(|dir| path.join(dir)),
+            removal_list: removal_list.map(|list| path.join(list)),
+            preserve_units_from: preserve_units_from.map(|units| path.join(units)),
+            rebuild_report: rebuild_report.map(|report| path.join(report)),
+            name,
+            path,
+            target_dir,
+            metadata_path,
+            max_target_size,
+            priority,
+        })
+    }
+}
+
+fn string(key: &str, value: TomlValue) -> std::result::Result<String, String> {
+    match value {
+        TomlValue::String(value) => Ok(value),
+        _ => Err(format!("'{key}' must be a string")),
+    }
+}
+
+/// A size given as a string such as "5G" in `units`, or a number of bytes
+fn size(key: &str, value: TomlValue, units: SizeUnits) -> std::result::Result<u64, String> {
+    match value {
+        TomlValue::String(value) => units.parse(&value).map_err(|e| format!("'{key}': {e}")),
+        TomlValue::Integer(bytes) => {
+            u64::try_from(bytes).map_err(|_| format!("'{key}' must not be negative"))
+        }
+        _ => Err(format!("'{key}' must be a size such as \"5G\"")),
+    }
+}
+
+/// How a repository of the batch fared
+pub(crate) struct RepoOutcome {
+    pub(crate) summary: Summary,
+    /// What anchor did with the metadata, unless the command failed
+    pub(crate) anchored: Option<AnchorOutcome>,
+    pub(crate) error: Option<String>,
+}
+
+/// Runs anchor or voyage in every repository of a manifest
+pub struct Batch<'a> {
+    pub(crate) context: &'a HoldContext,
+    pub(crate) manifest: &'a Path,
+    /// Whether to voyage rather than anchor
+    pub(crate) voyage: bool,
+    pub(crate) jobs: NonZeroUsize,
+    pub(crate) report: Option<&'a Path>,
+    /// `--build-dir`, relative to every repository
+    pub(crate) build_dir: Option<&'a Path>,
+    pub(crate) anchor: &'a AnchorArgs,
+    pub(crate) gc: &'a GcArgs,
+    pub(crate) gc_dry_run: bool,
+    pub(crate) gc_debug: bool,
+    pub(crate) gc_age_threshold_days: u32,
+    pub(crate) gc_auto_max_target_size: bool,
+    pub(crate) verbose: u8,
+    pub(crate) quiet: bool,
+    pub(crate) working_dir: &'a Path,
+}
+
+impl Batch<'_> {
+    /// Executes the batch.
+    ///
+    /// For a voyage, the shared cargo home is cleaned once first, and the
+    /// target directories capped to fit the disk budget. Every repository is
+    /// then processed even when others fail, and the totals summarized and
+    /// reported.
+    pub fn run(self) -> Result<()> {
+        let log = self.context.logger(self.verbose, self.quiet);
+        let per_repository = [
+            ("--removal-list", "removal-list", self.gc.removal_list()),
+            (
+                "--preserve-units-from",
+                "preserve-units-from",
+                self.gc.preserve_units_from(),
+            ),
+            (
+                "--rebuild-report",
+                "rebuild-report",
+                self.anchor.rebuild_report(),
+            ),
+        ];
+        if let Some((option, key, _)) = per_repository.iter().find(|(.., path)| path.is_some()) {
+            return Err(HoldError::PerRepositoryOption {
+                option: option.to_string(),
+                key: key.to_string(),
+            });
+        }
+        if self.build_dir.is_some_and(Path::is_absolute) {
+            return Err(HoldError::PerRepositoryOption {
+                option: "An absolute --build-dir".to_string(),
+                key: "build-dir".to_string(),
+            });
+        }
+        let mut manifest =
+            BatchManifest::load(self.manifest, self.working_dir, self.context.size_units)?;
+        let command = if self.voyage { "voyage" } else { "anchor" };
+
+        let cargo_home_gc = if self.voyage {
+            Some(self.cargo_home_gc(&manifest.repos)?)
+        } else {
+            None
+        };
+        let cargo_home = match &cargo_home_gc {
+            Some(gc) if self.context.profile.cleans_cargo_home() => {
+                log.info("🧹 Cleaning the shared cargo home...");
+                let mut stats = GcStats::default();
+                gc.clean_cargo_home(&mut stats, self.verbose)?;
+                if let Some(path) = self.gc.gc_audit_log()
+                    && !self.gc_dry_run
+                {
+                    let format = self
+                        .gc
+                        .gc_audit_log_format()
+                        .map(str::parse::<gc::AuditLogFormat>)
+                        .transpose()?
+                        .unwrap_or_default();
+                    gc::append_audit_log(&gc.take_removals(), format, path)?;
+                }
+                Some(stats)
+            }
+            _ => None,
+        };
+        let cargo_home_size = match (manifest.disk_budget, &cargo_home_gc) {
+            (Some(budget), Some(gc)) => {
+                Some(self.apply_disk_budget(&mut manifest.repos, budget, gc, &log)?)
+            }
+            (Some(_), None) => {
+                log.verbose(1, "Ignoring the disk budget, as anchor collects no garbage");
+                None
+            }
+            (None, _) => None,
+        };
+
+        log.info(format!(
+            "🚢 Running {command} in {} repositories...",
+            manifest.repos.len()
+        ));
+        let outcomes = self.run_repos(&manifest.repos, command, &log);
+
+        let totals = totals(&outcomes);
+        let failed = outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+            .count();
+        log.summarize_count("repositories", manifest.repos.len());
+        for (label, metric) in &totals {
+            match *metric {
+                Metric::Count(count) => log.summarize_count(label, count),
+                Metric::Bytes(bytes) => log.summarize_size(label, bytes),
+            }
+        }
+        if let Some(stats) = &cargo_home {
+            log.summarize_size("cargo home freed", stats.bytes_freed);
+        }
+        if let Some(size) = cargo_home_size {
+            log.summarize_size("cargo home", size);
+        }
+        log.summarize_count("failed", failed);
+
+        if let Some(path) = self.report {
+            let report = BatchReport {
+                command,
+                repos: &manifest.repos,
+                outcomes: &outcomes,
+                totals: &totals,
+                cargo_home_freed: cargo_home.as_ref().map(|stats| stats.bytes_freed),
+                cargo_home_size,
+            };
+            report.write(path)?;
+        }
+
+        if failed > 0 {
+            return Err(HoldError::BatchFailed {
+                failed,
+                total: manifest.repos.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the command in every repository, `jobs` at a time, each in a
+    /// context of its own so that its warnings and summary stay apart from
+    /// the other repositories'. The warnings still count for the batch.
+    fn run_repos(
+        &self,
+        repos: &[BatchRepo],
+        command: &'static str,
+        log: &Logger,
+    ) -> Vec<RepoOutcome> {
+        let next = AtomicUsize::new(0);
+        let outcomes = Mutex::new(Vec::with_capacity(repos.len()));
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.jobs.get().min(repos.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(repo) = repos.get(index) else {
+                            break;
+                        };
+                        let context = self.context.for_target_dir(&repo.target_dir);
+                        let started = Instant::now();
+                        let result = self.run_repo(&context, repo);
+                        let summary =
+                            Summary::take(&context, command, started.elapsed(), result.is_err());
+                        // Warnings of every repository count for --deny-warnings
+                        self.context
+                            .warnings
+                            .fetch_add(summary.warnings, Ordering::Relaxed);
+                        if let Err(e) = &result {
+                            eprintln!("Error: {}: {e}", repo.name);
+                        }
+                        log.info(format!("{}: {summary}", repo.name));
+                        outcomes.lock().unwrap_or_else(|e| e.into_inner()).push((
+                            index,
+                            RepoOutcome {
+                                summary,
+                                anchored: result.as_ref().ok().copied().flatten(),
+                                error: result.err().map(|e| e.to_string()),
+                            },
+                        ));
+                    }
+                });
+            }
+        });
+
+        // Back in manifest order
+        let mut outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+        outcomes.sort_by_key(|(index, _)| *index);
+        outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+    }
+
+    /// Runs the command in `repo`, returning what anchor did with its
+    /// metadata
+    fn run_repo(&self, context: &HoldContext, repo: &BatchRepo) -> Result<Option<AnchorOutcome>> {
+        let anchor = match &repo.rebuild_report {
+            Some(report) => self.anchor.clone().with_rebuild_report(report),
+            None => self.anchor.clone(),
+        };
+        if !self.voyage {
+            return anchor_with_args(
+                context,
+                &repo.metadata_path,
+                &repo.target_dir,
+                self.verbose,
+                self.quiet,
+                &repo.path,
+                &anchor,
+            )
+            .map(Some);
+        }
+
+        let max_target_size = repo.max_target_size.map(|size| size.to_string());
+        let build_dir = repo
+            .build_dir
+            .clone()
+            .or_else(|| self.build_dir.map(|dir| repo.path.join(dir)));
+        let gc = self.gc;
+        Voyage::builder()
+            .context(context)
+            .metadata_path(&repo.metadata_path)
+            .target_dir(&repo.target_dir)
+            .build_dir(build_dir.as_deref())
+            .gc_args(gc)
+            .max_target_size(max_target_size.as_deref().or(gc.max_target_size()))
+            .removal_list(repo.removal_list.as_deref())
+            .preserve_units_from(repo.preserve_units_from.as_deref())
+            .gc_dry_run(self.gc_dry_run)
+            .gc_debug(self.gc_debug)
+            .keep_cargo_home(true)
+            .gc_age_threshold_days(self.gc_age_threshold_days)
+            .gc_auto_max_target_size(self.gc_auto_max_target_size)
+            .verbose(self.verbose)
+            .quiet(self.quiet)
+            .working_dir(&repo.path)
+            .anchor_args(&anchor)
+            .build()?
+            .run()
+    }
+
+    /// Caps the target directories of `repos` so that they fit in `budget`
+    /// next to the cargo home `cargo_home_gc` cleans, returning the size of
+    /// the cargo home
+    fn apply_disk_budget(
+        &self,
+        repos: &mut [BatchRepo],
+        budget: u64,
+        cargo_home_gc: &Gc,
+        log: &Logger,
+    ) -> Result<u64> {
+        let size_mode = cargo_home_gc.size_mode();
+        let cargo_home_size =
+            gc::calculate_disk_usage(&cargo_home_gc.cargo_home()?)?.get(size_mode);
+        let available = budget.checked_sub(cargo_home_size).unwrap_or_else(|| {
+            log.warn(format!(
+                "The cargo home alone ({}) exceeds the disk budget of {}",
+                log.format_size(cargo_home_size),
+                log.format_size(budget)
+            ));
+            0
+        });
+
+        let sizes = repos
+            .iter()
+            .map(|repo| {
+                let size = gc::calculate_disk_usage(&repo.target_dir)?.get(size_mode);
+                Ok((size, repo.priority))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let Some(caps) = allocate_budget(&sizes, available) else {
+            log.verbose(
+                1,
+                format!(
+                    "Target directories fit in the disk budget of {} next to the cargo home ({})",
+                    log.format_size(budget),
+                    log.format_size(cargo_home_size)
+                ),
+            );
+            return Ok(cargo_home_size);
+        };
+        for (repo, cap) in repos.iter_mut().zip(caps) {
+            let cap = repo.max_target_size.map_or(cap, |own| own.min(cap));
+            log.verbose(
+                1,
+                format!("Capping {} at {}", repo.name, log.format_size(cap)),
+            );
+            repo.max_target_size = Some(cap);
+        }
+        Ok(cargo_home_size)
+    }
+
+    /// Garbage collection of the cargo home the repositories share, keeping
+    /// the git dependencies pinned by any of their lockfiles
+    fn cargo_home_gc(&self, repos: &[BatchRepo]) -> Result<Gc> {
+        let gc = self.gc;
+        let mut builder = Gc::builder()
+            .dry_run(self.gc_dry_run)
+            .debug(self.gc_debug || self.verbose >= 2)
+            .age_threshold_days(self.gc_age_threshold_days)
+            .preserve_binaries(gc.preserve_cargo_binaries().to_vec())
+            .global_cache_mode(
+                gc.global_cache()
+                    .map(str::parse::<gc::GlobalCacheMode>)
+                    .transpose()?
+                    .unwrap_or_default(),
+            )
+            .sandbox(
+                gc.gc_sandbox()
+                    .map(str::parse::<gc::SandboxMode>)
+                    .transpose()?
+                    .unwrap_or_default(),
+            )
+            .symlinks(
+                gc.gc_symlinks()
+                    .map(str::parse::<gc::SymlinkPolicy>)
+                    .transpose()?
+                    .unwrap_or_default(),
+            )
+            .size_mode(
+                gc.size_mode()
+                    .map(str::parse::<gc::SizeMode>)
+                    .transpose()?
+                    .unwrap_or_default(),
+            )
+            .record_removals(gc.gc_audit_log().is_some())
+            .quiet(self.quiet)
+            .context(self.context);
+        if let Some(threshold) = gc.age_threshold() {
+            builder = builder.age_threshold(gc::parse_duration(threshold)?);
+        }
+        if let Some(duration) = gc.gc_max_duration() {
+            builder = builder.max_duration(gc::parse_duration(duration)?);
+        }
+        for threshold in gc.age_threshold_for() {
+            let (area, threshold) = gc::parse_area_threshold(threshold)?;
+            builder = builder.area_age_threshold(area, threshold);
+        }
+        if let Some(cargo_home) = gc.cargo_home() {
+            builder = builder.cargo_home(cargo_home);
+        }
+        if let Some(runner) = self.context.ephemeral.runner() {
+            builder = builder.runner(runner);
+        }
+        let lockfiles = gc
+            .lockfiles()
+            .iter()
+            .cloned()
+            .chain(repos.iter().map(|repo| repo.path.join("Cargo.lock")))
+            .filter(|lockfile| lockfile.is_file());
+        for lockfile in lockfiles {
+            builder = builder.lockfile(lockfile);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+/// Caps of directories of the given sizes and priorities fitting in
+/// `available` bytes, or `None` if they already fit.
+///
+/// Each directory gives up space in proportion to its size divided by its
+/// priority; a directory whose share exceeds its size gives up everything,
+/// and the rest is shared among the others.
+fn allocate_budget(sizes: &[(u64, u32)], available: u64) -> Option<Vec<u64>> {
+    let total: u64 = sizes.iter().map(|(size, _)| size).sum();
+    let mut excess = total.checked_sub(available).filter(|excess| *excess > 0)?;
+    let weight = |i: usize| sizes[i].0 as f64 / f64::from(sizes[i].1);
+
+    let mut evicted = vec![0; sizes.len()];
+    let mut open: Vec<usize> = (0..sizes.len()).filter(|&i| sizes[i].0 > 0).collect();
+    while excess > 0 && !open.is_empty() {
+        let total_weight: f64 = open.iter().map(|&i| weight(i)).sum();
+        let mut freed = 0;
+        open.retain(|&i| {
+            let share = (excess as f64 * weight(i) / total_weight).ceil() as u64;
+            let share = share.min(sizes[i].0 - evicted[i]);
+            evicted[i] += share;
+            freed += share;
+            evicted[i] < sizes[i].0
+        });
+        excess = excess.saturating_sub(freed);
+    }
+    Some(
+        sizes
+            .iter()
+            .zip(evicted)
+            .map(|((size, _), evicted)| size - evicted)
+            .collect(),
+    )
+}
+
+/// Numeric summary items added up over the repositories, in the order they
+/// were first recorded
+fn totals(outcomes: &[RepoOutcome]) -> Vec<(&'static str, Metric)> {
+    let mut totals: Vec<(&'static str, Metric)> = Vec::new();
+    for item in outcomes.iter().flat_map(|outcome| &outcome.summary.items) {
+        let Some(metric) = item.metric else {
+            continue;
+        };
+        match totals.iter_mut().find(|(label, _)| *label == item.label) {
+            Some((_, Metric::Count(total))) => {
+                if let Metric::Count(count) = metric {
+                    *total += count;
+                }
+            }
+            Some((_, Metric::Bytes(total))) => {
+                if let Metric::Bytes(bytes) = metric {
+                    *total += bytes;
+                }
+            }
+            None => totals.push((item.label, metric)),
+        }
+    }
+    totals
+}
+
+fn metrics<'a>(items: impl IntoIterator<Item = (&'a str, Metric)>) -> Map<String, Value> {
+    items
+        .into_iter()
+        .map(|(label, metric)| {
+            let value = match metric {
+                Metric::Count(count) => Value::from(count),
+                Metric::Bytes(bytes) => Value::from(bytes),
+            };
+            (label.to_string(), value)
+        })
+        .collect()
+}
+
+/// Report of a batch, written by `--report`
+pub(crate) struct BatchReport<'a> {
+    pub(crate) command: &'static str,
+    pub(crate) repos: &'a [BatchRepo],
+    /// Outcomes of the repositories, in the same order
+    pub(crate) outcomes: &'a [RepoOutcome],
+    pub(crate) totals: &'a [(&'static str, Metric)],
+    /// Bytes freed in the cargo home, if it was cleaned
+    pub(crate) cargo_home_freed: Option<u64>,
+    /// Size of the cargo home, if measured for a disk budget
+    pub(crate) cargo_home_size: Option<u64>,
+}
+
+impl BatchReport<'_> {
+    /// The report as a JSON document
+    pub(crate) fn to_json(&self) -> Value {
+        let repositories: Vec<Value> = self
+            .repos
+            .iter()
+            .zip(self.outcomes)
+            .map(|(repo, outcome)| {
+                let mut entry = serde_json::json!({
+                    "path": repo.name,
+                    "target_dir": repo.target_dir.to_string_lossy(),
+                    "failed": outcome.error.is_some(),
+                    "elapsed_secs": outcome.summary.elapsed.as_secs_f64(),
+                    "warnings": outcome.summary.warnings,
+                    "metrics": metrics(
+                        outcome
+                            .summary
+                            .items
+                            .iter()
+                            .filter_map(|item| Some((item.label, item.metric?)))
+                    ),
+                });
+                if let Some(size) = repo.max_target_size {
+                    entry["max_target_size"] = Value::from(size);
+                }
+                if let Some(anchored) = outcome.anchored {
+                    entry["metadata"] = Value::from(anchored.as_str());
+                }
+                if let Some(error) = &outcome.error {
+                    entry["error"] = Value::from(error.as_str());
+                }
+                entry
+            })
+            .collect();
+        let mut report = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "command": self.command,
+            "repositories": repositories,
+            "totals": metrics(self.totals.iter().copied()),
+            "failed": self.outcomes.iter().filter(|outcome| outcome.error.is_some()).count(),
+        });
+        if let Some(bytes) = self.cargo_home_freed {
+            report["cargo_home_freed"] = Value::from(bytes);
+        }
+        if let Some(bytes) = self.cargo_home_size {
+            report["cargo_home_size"] = Value::from(bytes);
+        }
+        report
+    }
+
+    /// Write the report to `path`
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let io_error = |source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+        serde_json::to_writer_pretty(&mut out, &self.to_json())
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(out))
+            .and_then(|()| out.flush())
+            .map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = BatchManifest::parse(
+            r#"
+[[repo]]
+path = "api"
+
+[[repo]]
+path = "worker"
+target-dir = "build"
+build-dir = "scratch"
+metadata-path = "hold.metadata"
+max-target-size = 1024
+priority = 3
+removal-list = "removed.json"
+preserve-units-from = "target/units.json"
+rebuild-report = "rebuilds.json"
+"#,
+            Path::new("/ci"),
+            SizeUnits::Binary,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.repos,
+            [
+                BatchRepo {
+                    name: "api".to_string(),
+                    path: PathBuf::from("/ci/api"),
+                    target_dir: PathBuf::from("/ci/api/target"),
+                    build_dir: None,
+                    metadata_path: PathBuf::from("/ci/api/target/cargo-hold.metadata"),
+                    max_target_size: None,
+                    priority: 1,
+                    removal_list: None,
+                    preserve_units_from: None,
+                    rebuild_report: None,
+                },
+                BatchRepo {
+                    name: "worker".to_string(),
+                    path: PathBuf::from("/ci/worker"),
+                    target_dir: PathBuf::from("/ci/worker/build"),
+                    build_dir: Some(PathBuf::from("/ci/worker/scratch")),
+                    metadata_path: PathBuf::from("/ci/worker/hold.metadata"),
+                    max_target_size: Some(1024),
+                    priority: 3,
+                    removal_list: Some(PathBuf::from("/ci/worker/removed.json")),
+                    preserve_units_from: Some(PathBuf::from("/ci/worker/target/units.json")),
+                    rebuild_report: Some(PathBuf::from("/ci/worker/rebuilds.json")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_manifest() {
+        for manifest in [
+            "not toml =",
+            "",
+            "repo = \"api\"",
+            "repos = []",
+            "[[repo]]\ntarget-dir = \"target\"",
+            "[[repo]]\npath = \"api\"\nmax-target-size = \"lots\"",
+            "[[repo]]\npath = \"api\"\nmax-target-size = -1",
+            "[[repo]]\npath = \"api\"\ncargo-home = \"/cargo\"",
+            "[[repo]]\npath = \"api\"\npriority = 0",
+            "[[repo]]\npath = \"api\"\nremoval-list = \"-\"",
+            "disk-budget = \"10G\"\nmax-total-size = \"5G\"\n[[repo]]\npath = \"api\"",
+            // The repositories' own caps leave nothing for the others
+            "max-total-size = 100\n[[repo]]\npath = \"a\"\nmax-target-size = 100\n[[repo]]\npath \
+             = \"b\"",
+        ] {
+            assert!(
+                BatchManifest::parse(manifest, Path::new("/ci"), SizeUnits::Binary).is_err(),
+                "{manifest}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_allocate_budget() {
+        // Already within the budget
+        assert_eq!(allocate_budget(&[(100, 1), (100, 1)], 200), None);
+        // In proportion to size
+        assert_eq!(
+            allocate_budget(&[(100, 1), (300, 1)], 200),
+            Some(vec![50, 150])
+        );
+        // Higher priorities give up less
+        assert_eq!(
+            allocate_budget(&[(100, 1), (100, 3)], 100),
+            Some(vec![25, 75])
+        );
+        // A directory cannot give up more than it holds
+        assert_eq!(
+            allocate_budget(&[(10, 1), (100, 10)], 50),
+            Some(vec![0, 50])
+        );
+        assert_eq!(allocate_budget(&[(0, 1), (100, 1)], 0), Some(vec![0, 0]));
+    }
+}
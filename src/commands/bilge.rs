@@ -2,13 +2,13 @@
 
 use std::path::Path;
 
+use crate::context::HoldContext;
 use crate::error::Result;
-use crate::logging::Logger;
 use crate::metadata::clean_metadata;
 
 /// Executes the bilge command (remove metadata file).
-pub fn bilge(metadata_path: &Path, verbose: u8, quiet: bool) -> Result<()> {
-    let log = Logger::new(verbose, quiet);
+pub fn bilge(context: &HoldContext, metadata_path: &Path, verbose: u8, quiet: bool) -> Result<()> {
+    let log = context.logger(verbose, quiet);
     log.verbose(1, format!("Bilging out metadata at {metadata_path:?}"));
 
     clean_metadata(metadata_path)?;
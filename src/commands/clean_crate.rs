@@ -0,0 +1,77 @@
+//! Clean-crate command implementation.
+
+use std::path::Path;
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::gc;
+use crate::gc::config::Gc;
+use crate::lock::{LockMode, TargetDirLock};
+
+/// Executes the clean-crate command.
+///
+/// Removes every artifact of the named crate (deps, fingerprints, build script
+/// output, and incremental data) from all profile directories of the target
+/// directory and, if given, cargo's separate build directory, so the next
+/// build recompiles it from scratch. Cargo's target directory lock is held
+/// while removing.
+pub fn clean_crate(
+    context: &HoldContext,
+    target_dir: &Path,
+    build_dir: Option<&Path>,
+    name: &str,
+    dry_run: bool,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, format!("Cleaning artifacts of crate '{name}'..."));
+
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(TargetDirLock::acquire(
+            target_dir,
+            LockMode::Exclusive,
+            &log,
+        )?)
+    };
+
+    let mut builder = Gc::builder()
+        .target_dir(target_dir)
+        .dry_run(dry_run)
+        .quiet(quiet)
+        .context(context);
+    if let Some(build_dir) = build_dir {
+        builder = builder.build_dir(build_dir);
+    }
+    let stats = gc::clean_named_crate(&builder.build(), name, &log)?;
+
+    log.summarize_count("artifacts removed", stats.artifacts_removed);
+    log.summarize_size(
+        if dry_run { "freeable" } else { "freed" },
+        stats.bytes_freed,
+    );
+    log.summarize_count("removal errors", stats.errors);
+    log.summarize_count("removals refused", stats.refused_removals);
+
+    if stats.artifacts_removed == 0 {
+        log.info(format!("No artifacts found for crate '{name}'"));
+    } else {
+        log.info(format!(
+            "🧽 {} {} artifact{} of '{name}' across {} profile{} ({})",
+            if dry_run { "Would remove" } else { "Removed" },
+            stats.artifacts_removed,
+            if stats.artifacts_removed == 1 {
+                ""
+            } else {
+                "s"
+            },
+            stats.crates_cleaned,
+            if stats.crates_cleaned == 1 { "" } else { "s" },
+            log.format_size(stats.bytes_freed)
+        ));
+    }
+
+    Ok(())
+}
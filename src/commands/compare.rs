@@ -0,0 +1,329 @@
+//! Compare command implementation.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::gc::SizeUnits;
+use crate::metadata::read_metadata;
+use crate::schema::SCHEMA_VERSION;
+use crate::state::StateMetadata;
+
+/// A file recorded in both metadata files with a different size, hash, or
+/// timestamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChangedEntry {
+    pub(crate) path: String,
+    pub(crate) hash_changed: bool,
+    pub(crate) old_size: u64,
+    pub(crate) new_size: u64,
+    pub(crate) old_mtime_nanos: u64,
+    pub(crate) new_mtime_nanos: u64,
+}
+
+impl ChangedEntry {
+    /// Whether only the timestamp differs, which makes cargo rebuild what
+    /// depends on the file even though its contents are the same
+    fn timestamp_only(&self) -> bool {
+        !self.hash_changed && self.old_size == self.new_size
+    }
+}
+
+/// A GC metric in both metadata files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MetricDelta {
+    pub(crate) name: &'static str,
+    pub(crate) old: Option<u64>,
+    pub(crate) new: Option<u64>,
+    /// Whether the metric is a size in bytes
+    size: bool,
+}
+
+impl MetricDelta {
+    /// How much the metric grew, if both files have it
+    fn delta(&self) -> Option<i64> {
+        let delta = i128::from(self.new?) - i128::from(self.old?);
+        Some(delta.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64)
+    }
+}
+
+/// The differences between two metadata files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Comparison {
+    /// Whether the hashes were recorded with different salts, so every hash
+    /// differs
+    pub(crate) salt_changed: bool,
+    /// Files only the newer metadata records, sorted
+    pub(crate) added: Vec<String>,
+    /// Files only the older metadata records, sorted
+    pub(crate) removed: Vec<String>,
+    /// Files recorded differently, sorted by path
+    pub(crate) changed: Vec<ChangedEntry>,
+    /// Every GC metric, changed or not
+    pub(crate) metrics: Vec<MetricDelta>,
+}
+
+impl Comparison {
+    /// Compare every recorded file and GC metric of `old` and `new`
+    pub(crate) fn new(old: &StateMetadata, new: &StateMetadata) -> Self {
+        let paths: BTreeSet<&String> = old.files.keys().chain(new.files.keys()).collect();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut changed = Vec::new();
+        for path in paths {
+            match (old.files.get(path), new.files.get(path)) {
+                (None, Some(_)) => added.push(path.clone()),
+                (Some(_), None) => removed.push(path.clone()),
+                (Some(old_state), Some(new_state))
+                    if old_state.hash != new_state.hash
+                        || old_state.size != new_state.size
+                        || old_state.mtime_nanos != new_state.mtime_nanos =>
+                {
+                    changed.push(ChangedEntry {
+                        path: path.clone(),
+                        hash_changed: old_state.hash != new_state.hash,
+                        old_size: old_state.size,
+                        new_size: new_state.size,
+                        old_mtime_nanos: old_state.mtime_nanos,
+                        new_mtime_nanos: new_state.mtime_nanos,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let old_metrics = metrics(old);
+        let metrics = metrics(new)
+            .into_iter()
+            .zip(old_metrics)
+            .map(|((name, size, new), (_, _, old))| MetricDelta {
+                name,
+                old,
+                new,
+                size,
+            })
+            .collect();
+
+        Self {
+            salt_changed: old.salt_id != new.salt_id,
+            added,
+            removed,
+            changed,
+            metrics,
+        }
+    }
+
+    /// Number of files whose hash changed
+    pub(crate) fn hash_changes(&self) -> usize {
+        self.changed
+            .iter()
+            .filter(|entry| entry.hash_changed)
+            .count()
+    }
+
+    /// The report as text: a line per added (`A`), removed (`D`), or changed
+    /// file, marked `M` when its contents changed and `T` when only its
+    /// timestamp did, then the GC metrics that changed, with sizes in `units`
+    pub(crate) fn render(&self, units: SizeUnits) -> Vec<String> {
+        let mut lines: Vec<(&str, String)> = self
+            .added
+            .iter()
+            .map(|path| (path.as_str(), format!("A {path}")))
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|path| (path.as_str(), format!("D {path}"))),
+            )
+            .chain(self.changed.iter().map(|entry| {
+                let mut details = Vec::new();
+                if entry.hash_changed {
+                    details.push("hash".to_string());
+                }
+                if entry.old_size != entry.new_size {
+                    details.push(format!("size {} -> {}", entry.old_size, entry.new_size));
+                }
+                if entry.old_mtime_nanos != entry.new_mtime_nanos {
+                    let delta = entry.new_mtime_nanos as f64 - entry.old_mtime_nanos as f64;
+                    details.push(format!("mtime {:+.3}s", delta / 1e9));
+                }
+                let kind = if entry.timestamp_only() { "T" } else { "M" };
+                (
+                    entry.path.as_str(),
+                    format!("{kind} {} ({})", entry.path, details.join(", ")),
+                )
+            }))
+            .collect();
+        lines.sort();
+        let mut lines: Vec<String> = lines.into_iter().map(|(_, line)| line).collect();
+
+        let changed_metrics: Vec<&MetricDelta> = self
+            .metrics
+            .iter()
+            .filter(|metric| metric.old != metric.new)
+            .collect();
+        if !changed_metrics.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push("GC metrics:".to_string());
+            for metric in changed_metrics {
+                let value = |value: Option<u64>| match value {
+                    None => "-".to_string(),
+                    Some(value) if metric.size => units.format(value),
+                    Some(value) => value.to_string(),
+                };
+                let mut line = format!(
+                    "  {}: {} -> {}",
+                    metric.name,
+                    value(metric.old),
+                    value(metric.new)
+                );
+                match metric.delta() {
+                    Some(delta) if metric.size => {
+                        let sign = if delta < 0 { "-" } else { "+" };
+                        line.push_str(&format!(" ({sign}{})", units.format(delta.unsigned_abs())));
+                    }
+                    Some(delta) => line.push_str(&format!(" ({delta:+})")),
+                    None => {}
+                }
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// The report as a JSON document
+    pub(crate) fn to_json(&self, old_path: &Path, new_path: &Path) -> Value {
+        let changed: Vec<Value> = self
+            .changed
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "path": entry.path,
+                    "hash_changed": entry.hash_changed,
+                    "old_size": entry.old_size,
+                    "new_size": entry.new_size,
+                    "old_mtime_nanos": entry.old_mtime_nanos,
+                    "new_mtime_nanos": entry.new_mtime_nanos,
+                })
+            })
+            .collect();
+        let metrics: Vec<Value> = self
+            .metrics
+            .iter()
+            .map(|metric| {
+                serde_json::json!({
+                    "name": metric.name,
+                    "old": metric.old,
+                    "new": metric.new,
+                    "delta": metric.delta(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "old": old_path.to_string_lossy(),
+            "new": new_path.to_string_lossy(),
+            "salt_changed": self.salt_changed,
+            "added": self.added,
+            "removed": self.removed,
+            "changed": changed,
+            "gc_metrics": metrics,
+        })
+    }
+}
+
+/// The GC metrics compared, by name, with whether each is a size in bytes
+fn metrics(metadata: &StateMetadata) -> [(&'static str, bool, Option<u64>); 10] {
+    let gc_metrics = &metadata.gc_metrics;
+    let count = |len: usize| Some(len as u64);
+    [
+        ("runs", false, Some(u64::from(gc_metrics.runs))),
+        ("seed_initial_size", true, gc_metrics.seed_initial_size),
+        (
+            "last_initial_size",
+            true,
+            gc_metrics.recent_initial_sizes.last().copied(),
+        ),
+        (
+            "last_bytes_freed",
+            true,
+            gc_metrics.recent_bytes_freed.last().copied(),
+        ),
+        (
+            "last_final_size",
+            true,
+            gc_metrics.recent_final_sizes.last().copied(),
+        ),
+        ("last_suggested_cap", true, gc_metrics.last_suggested_cap),
+        (
+            "recorded_builds",
+            false,
+            count(gc_metrics.recent_builds.len()),
+        ),
+        (
+            "indexed_directories",
+            false,
+            count(gc_metrics.directory_sizes.len()),
+        ),
+        ("pinned_crates", false, count(metadata.pinned_crates.len())),
+        ("build_costs", false, count(metadata.build_costs.len())),
+    ]
+}
+
+/// Executes the compare command.
+///
+/// Reads two metadata files, e.g. ones saved on different runners, and
+/// prints the files only one of them records, the files recorded with a
+/// different hash, size, or timestamp, and how the GC metrics differ, as
+/// text or, with `json`, as a JSON document.
+pub fn compare(
+    context: &HoldContext,
+    old_path: &Path,
+    new_path: &Path,
+    json: bool,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(
+        1,
+        format!(
+            "Comparing {} with {}...",
+            old_path.display(),
+            new_path.display()
+        ),
+    );
+
+    let old = read_metadata(old_path)?;
+    let new = read_metadata(new_path)?;
+    let comparison = Comparison::new(&old, &new);
+    log.summarize_count("added", comparison.added.len());
+    log.summarize_count("removed", comparison.removed.len());
+    log.summarize_count("changed", comparison.changed.len());
+    log.summarize_count("hash changes", comparison.hash_changes());
+    if comparison.salt_changed {
+        log.warn(
+            "The metadata files were recorded with different salts (different repositories, or a \
+             different --hash-salt); every file's hash differs",
+        );
+    }
+
+    if json {
+        println!("{:#}", comparison.to_json(old_path, new_path));
+    } else {
+        for line in comparison.render(log.units()) {
+            println!("{line}");
+        }
+        if comparison.added.is_empty()
+            && comparison.removed.is_empty()
+            && comparison.changed.is_empty()
+        {
+            log.info("The metadata files record the same files");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Diff command implementation.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::commands::salvage::clean_before;
+use crate::context::HoldContext;
+use crate::discovery::{TrackedFiles, discover_tracked_files};
+use crate::error::Result;
+use crate::hashing::{Salt, get_file_size_and_mtime, hash_tracked_file};
+use crate::logging::Logger;
+use crate::metadata::load_metadata;
+use crate::state::{ChangeKind, FileChange, FileState, StateMetadata, changed_files};
+
+/// Executes the diff command.
+///
+/// Compares the tracked files with the metadata, printing a line per added
+/// (`A`), modified (`M`), or removed (`D`) file, or with `depth`, a line per
+/// changed directory at most `depth` levels deep.
+pub fn diff(
+    context: &HoldContext,
+    metadata_path: &Path,
+    depth: Option<usize>,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Comparing tracked files with metadata...");
+
+    let changes = changes_since_stow(context, metadata_path, &log, working_dir)?;
+    let count = |kind| changes.iter().filter(|change| change.kind == kind).count();
+    log.summarize_count("added", count(ChangeKind::Added));
+    log.summarize_count("modified", count(ChangeKind::Modified));
+    log.summarize_count("removed", count(ChangeKind::Removed));
+
+    match depth {
+        None => {
+            for change in &changes {
+                println!("{} {}", change.kind, change.path.display());
+            }
+        }
+        Some(depth) => {
+            for (dir, counts) in changed_dirs(&changes, depth) {
+                println!("{} {}", dir.display(), counts.join(", "));
+            }
+        }
+    }
+    if changes.is_empty() {
+        log.info("No changes since the metadata was stowed");
+    }
+    Ok(())
+}
+
+/// Tracked files that changed since the metadata at `metadata_path` was
+/// stowed, sorted by path
+///
+/// Files still holding their recorded size and timestamp are not hashed,
+/// as in `salvage`, and only the directories whose rollup hashes differ are
+/// compared file by file.
+pub(crate) fn changes_since_stow(
+    context: &HoldContext,
+    metadata_path: &Path,
+    log: &Logger,
+    working_dir: &Path,
+) -> Result<Vec<FileChange>> {
+    let metadata = load_metadata(context, metadata_path)?;
+    let clean_before = clean_before(metadata_path);
+
+    let TrackedFiles {
+        repo_root,
+        files: tracked_files,
+        lfs,
+        origin,
+        ..
+    } = discover_tracked_files(context, working_dir)?;
+    let salt = Salt::for_repo(context.hash_salt.as_deref(), origin.as_deref());
+    if !metadata.is_empty() && metadata.salt_id != salt.as_ref().map(Salt::id) {
+        log.warn(
+            "Metadata hashes were recorded with another salt (another repository, or a different \
+             --hash-salt); every file counts as changed",
+        );
+    }
+
+    let mut current = StateMetadata::new();
+    let mut errors = 0;
+    for batch in tracked_files.chunks(context.batch_size) {
+        let states: Vec<Result<FileState>> = batch
+            .par_iter()
+            .map(|path| {
+                let recorded = metadata.get(path).ok().flatten();
+                current_state(
+                    context,
+                    &repo_root.join(path),
+                    path,
+                    lfs.contains(path),
+                    recorded,
+                    salt.as_ref(),
+                    clean_before,
+                )
+            })
+            .collect();
+        for state in states {
+            match state.and_then(|state| current.upsert(state)) {
+                Ok(()) => {}
+                Err(e) => {
+                    errors += 1;
+                    log.verbose(2, format!("Warning: Could not analyze file: {e}"));
+                }
+            }
+        }
+    }
+    log.summarize_count("errors", errors);
+    if errors > 0 {
+        log.warn(format!("Failed to analyze {errors} file(s)"));
+    }
+
+    current.update_dir_hashes();
+    Ok(changed_files(&metadata, &current))
+}
+
+/// State of the tracked file at `full_path`, reusing the `recorded` hash
+/// when the file still holds its recorded size and timestamp
+fn current_state(
+    context: &HoldContext,
+    full_path: &Path,
+    path: &Path,
+    lfs: bool,
+    recorded: Option<&FileState>,
+    salt: Option<&Salt>,
+    clean_before: Option<u64>,
+) -> Result<FileState> {
+    let (size, mtime_nanos) = get_file_size_and_mtime(full_path)?;
+    let (size, hash) = match recorded {
+        Some(recorded)
+            if !lfs
+                && recorded.size == size
+                && recorded.mtime_nanos == mtime_nanos
+                && clean_before.is_some_and(|before| mtime_nanos < before) =>
+        {
+            (size, recorded.hash)
+        }
+        _ => hash_tracked_file(context, full_path, lfs, salt)?,
+    };
+    Ok(FileState {
+        path: path.to_path_buf(),
+        size,
+        hash,
+        mtime_nanos,
+    })
+}
+
+/// Changed directories at most `depth` levels deep with the number of
+/// changes of each kind below them; files at the top level are counted for
+/// `.`
+pub(crate) fn changed_dirs(changes: &[FileChange], depth: usize) -> BTreeMap<PathBuf, Vec<String>> {
+    let mut dirs: BTreeMap<PathBuf, BTreeMap<ChangeKind, usize>> = BTreeMap::new();
+    for change in changes {
+        let parent = change.path.parent().unwrap_or(Path::new(""));
+        let dir: PathBuf = parent.components().take(depth).collect();
+        let dir = if dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            dir
+        };
+        *dirs.entry(dir).or_default().entry(change.kind).or_default() += 1;
+    }
+    dirs.into_iter()
+        .map(|(dir, counts)| {
+            let counts = counts
+                .into_iter()
+                .map(|(kind, count)| {
+                    let kind = match kind {
+                        ChangeKind::Added => "added",
+                        ChangeKind::Modified => "modified",
+                        ChangeKind::Removed => "removed",
+                    };
+                    format!("{count} {kind}")
+                })
+                .collect();
+            (dir, counts)
+        })
+        .collect()
+}
@@ -0,0 +1,43 @@
+//! Dock command (stow + build record), run after the build.
+
+use std::path::Path;
+
+use crate::commands::report_build::record_build_starts;
+use crate::commands::stow::stow;
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::lock::{LockMode, TargetDirLock};
+use crate::metadata::{load_metadata, save_metadata};
+
+/// Executes the dock command.
+///
+/// Stows the state of the tracked files and records where the build that
+/// just finished started in every profile directory. The next `embark` or
+/// `heave` preserves everything from there on instead of guessing the build
+/// from the state before it.
+pub fn dock(
+    context: &HoldContext,
+    metadata_path: &Path,
+    target_dir: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.info("🛟 Docking (stow + build record)...");
+
+    let _lock = TargetDirLock::acquire(target_dir, LockMode::Shared, &log)?;
+    stow(context, metadata_path, verbose, quiet, working_dir)?;
+
+    let mut metadata = load_metadata(context, metadata_path)?;
+    let recorded = record_build_starts(&mut metadata, target_dir, &log)?.len();
+    save_metadata(context, &metadata, metadata_path)?;
+    log.summarize_count("profiles recorded", recorded);
+
+    log.info(format!(
+        "🛟 Docked; recorded the last build of {recorded} profile{}",
+        if recorded == 1 { "" } else { "s" }
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Embark command (salvage + heave), run before the build.
+
+use std::path::Path;
+
+use crate::commands::heave::Heave;
+use crate::commands::salvage::salvage;
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::lock::{LockMode, TargetDirLock};
+
+/// Executes the embark command.
+///
+/// Restores timestamps from the metadata and then collects garbage, like
+/// `voyage` without stowing. Pair it with `dock` after the build, which
+/// stows the state the build actually used.
+pub fn embark(
+    context: &HoldContext,
+    metadata_path: &Path,
+    target_dir: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+    heave: Heave<'_>,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.info("⛵ Embarking (salvage + heave)...");
+
+    {
+        let _lock = TargetDirLock::acquire(target_dir, LockMode::Shared, &log)?;
+        salvage(context, metadata_path, verbose, quiet, working_dir)?;
+    }
+
+    log.info("🧹 Starting garbage collection...");
+    heave.heave()?;
+
+    log.info("⛵ Ready for the build");
+
+    Ok(())
+}
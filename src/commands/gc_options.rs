@@ -1,18 +1,55 @@
-use std::path::Path;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 
+#[cfg(feature = "cli")]
+use crate::cli::GcArgs;
+use crate::context::HoldContext;
 use crate::error::{HoldError, Result};
 
 pub struct GcOptions<'a> {
     target_dir: &'a Path,
     max_target_size: Option<&'a str>,
+    max_target_files: Option<u64>,
+    build_dir: Option<&'a Path>,
+    max_build_dir_size: Option<&'a str>,
+    max_coverage_size: Option<&'a str>,
     auto_max_target_size: bool,
     dry_run: bool,
     debug: bool,
     preserve_cargo_binaries: &'a [String],
+    preserve_units_from: Option<&'a Path>,
+    preserve_last_builds: usize,
+    preserve_window: Option<&'a str>,
+    preserve_buffer: Option<&'a str>,
+    size_mode: Option<&'a str>,
+    gc_when_busy: Option<&'a str>,
+    gc_policy_cmd: Option<&'a str>,
+    global_cache: Option<&'a str>,
+    gc_sandbox: Option<&'a str>,
+    gc_symlinks: Option<&'a str>,
+    force: bool,
+    gc_metrics_window: Option<NonZeroU32>,
+    cargo_home: Option<&'a Path>,
+    keep_cargo_home: bool,
+    removal_list: Option<&'a Path>,
+    removal_list_format: Option<&'a str>,
+    gc_audit_log: Option<&'a Path>,
+    gc_audit_log_format: Option<&'a str>,
+    gc_max_duration: Option<&'a str>,
+    gc_compress_cold: bool,
+    gc_dep_info: bool,
+    only: &'a [String],
+    simulate: bool,
+    suggest_only: bool,
+    policies: &'a [String],
     age_threshold_days: u32,
+    age_threshold: Option<&'a str>,
+    age_threshold_for: &'a [String],
+    lockfiles: &'a [PathBuf],
     verbose: u8,
     metadata_path: Option<&'a Path>,
     quiet: bool,
+    context: HoldContext,
 }
 
 impl<'a> GcOptions<'a> {
@@ -24,6 +61,22 @@ impl<'a> GcOptions<'a> {
         self.max_target_size
     }
 
+    pub fn max_target_files(&self) -> Option<u64> {
+        self.max_target_files
+    }
+
+    pub fn build_dir(&self) -> Option<&'a Path> {
+        self.build_dir
+    }
+
+    pub fn max_build_dir_size(&self) -> Option<&'a str> {
+        self.max_build_dir_size
+    }
+
+    pub fn max_coverage_size(&self) -> Option<&'a str> {
+        self.max_coverage_size
+    }
+
     pub fn auto_max_target_size(&self) -> bool {
         self.auto_max_target_size
     }
@@ -40,10 +93,122 @@ impl<'a> GcOptions<'a> {
         self.preserve_cargo_binaries
     }
 
+    pub fn preserve_units_from(&self) -> Option<&'a Path> {
+        self.preserve_units_from
+    }
+
+    pub fn preserve_last_builds(&self) -> usize {
+        self.preserve_last_builds
+    }
+
+    pub fn preserve_window(&self) -> Option<&'a str> {
+        self.preserve_window
+    }
+
+    pub fn preserve_buffer(&self) -> Option<&'a str> {
+        self.preserve_buffer
+    }
+
+    pub fn size_mode(&self) -> Option<&'a str> {
+        self.size_mode
+    }
+
+    pub fn gc_when_busy(&self) -> Option<&'a str> {
+        self.gc_when_busy
+    }
+
+    pub fn gc_policy_cmd(&self) -> Option<&'a str> {
+        self.gc_policy_cmd
+    }
+
+    pub fn global_cache(&self) -> Option<&'a str> {
+        self.global_cache
+    }
+
+    pub fn gc_sandbox(&self) -> Option<&'a str> {
+        self.gc_sandbox
+    }
+
+    pub fn gc_symlinks(&self) -> Option<&'a str> {
+        self.gc_symlinks
+    }
+
+    pub fn force(&self) -> bool {
+        self.force
+    }
+
+    pub fn gc_metrics_window(&self) -> Option<NonZeroU32> {
+        self.gc_metrics_window
+    }
+
+    pub fn cargo_home(&self) -> Option<&'a Path> {
+        self.cargo_home
+    }
+
+    pub fn keep_cargo_home(&self) -> bool {
+        self.keep_cargo_home
+    }
+
+    pub fn removal_list(&self) -> Option<&'a Path> {
+        self.removal_list
+    }
+
+    pub fn removal_list_format(&self) -> Option<&'a str> {
+        self.removal_list_format
+    }
+
+    pub fn gc_audit_log(&self) -> Option<&'a Path> {
+        self.gc_audit_log
+    }
+
+    pub fn gc_audit_log_format(&self) -> Option<&'a str> {
+        self.gc_audit_log_format
+    }
+
+    pub fn gc_max_duration(&self) -> Option<&'a str> {
+        self.gc_max_duration
+    }
+
+    pub fn gc_compress_cold(&self) -> bool {
+        self.gc_compress_cold
+    }
+
+    pub fn gc_dep_info(&self) -> bool {
+        self.gc_dep_info
+    }
+
+    pub fn only(&self) -> &'a [String] {
+        self.only
+    }
+
+    pub fn simulate(&self) -> bool {
+        self.simulate
+    }
+
+    pub fn suggest_only(&self) -> bool {
+        self.suggest_only
+    }
+
+    pub fn policies(&self) -> &'a [String] {
+        self.policies
+    }
+
     pub fn age_threshold_days(&self) -> u32 {
         self.age_threshold_days
     }
 
+    pub fn age_threshold(&self) -> Option<&'a str> {
+        self.age_threshold
+    }
+
+    pub fn age_threshold_for(&self) -> &'a [String] {
+        self.age_threshold_for
+    }
+
+    pub fn lockfiles(&self) -> &'a [PathBuf] {
+        self.lockfiles
+    }
+
     pub fn verbose(&self) -> u8 {
         self.verbose
     }
@@ -55,19 +220,61 @@ impl<'a> GcOptions<'a> {
     pub fn quiet(&self) -> bool {
         self.quiet
     }
+
+    pub fn context(&self) -> &HoldContext {
+        &self.context
+    }
+
+    /// A logger for the command, counting its warnings in its context
+    pub(crate) fn logger(&self) -> crate::logging::Logger {
+        self.context.logger(self.verbose, self.quiet)
+    }
 }
 
 pub struct GcOptionsBuilder<'a> {
     target_dir: Option<&'a Path>,
     max_target_size: Option<&'a str>,
+    max_target_files: Option<u64>,
+    build_dir: Option<&'a Path>,
+    max_build_dir_size: Option<&'a str>,
+    max_coverage_size: Option<&'a str>,
     auto_max_target_size: bool,
     dry_run: bool,
     debug: bool,
     preserve_cargo_binaries: &'a [String],
+    preserve_units_from: Option<&'a Path>,
+    preserve_last_builds: usize,
+    preserve_window: Option<&'a str>,
+    preserve_buffer: Option<&'a str>,
+    size_mode: Option<&'a str>,
+    gc_when_busy: Option<&'a str>,
+    gc_policy_cmd: Option<&'a str>,
+    global_cache: Option<&'a str>,
+    gc_sandbox: Option<&'a str>,
+    gc_symlinks: Option<&'a str>,
+    force: bool,
+    gc_metrics_window: Option<NonZeroU32>,
+    cargo_home: Option<&'a Path>,
+    keep_cargo_home: bool,
+    removal_list: Option<&'a Path>,
+    removal_list_format: Option<&'a str>,
+    gc_audit_log: Option<&'a Path>,
+    gc_audit_log_format: Option<&'a str>,
+    gc_max_duration: Option<&'a str>,
+    gc_compress_cold: bool,
+    gc_dep_info: bool,
+    only: &'a [String],
+    simulate: bool,
+    suggest_only: bool,
+    policies: &'a [String],
     age_threshold_days: u32,
+    age_threshold: Option<&'a str>,
+    age_threshold_for: &'a [String],
+    lockfiles: &'a [PathBuf],
     verbose: u8,
     metadata_path: Option<&'a Path>,
     quiet: bool,
+    context: HoldContext,
 }
 
 impl<'a> Default for GcOptionsBuilder<'a> {
@@ -81,14 +288,47 @@ impl<'a> GcOptionsBuilder<'a> {
         Self {
             target_dir: None,
             max_target_size: None,
+            max_target_files: None,
+            build_dir: None,
+            max_build_dir_size: None,
+            max_coverage_size: None,
             auto_max_target_size: true,
             dry_run: false,
             debug: false,
             preserve_cargo_binaries: &[],
+            preserve_units_from: None,
+            preserve_last_builds: 1,
+            preserve_window: None,
+            preserve_buffer: None,
+            size_mode: None,
+            gc_when_busy: None,
+            gc_policy_cmd: None,
+            global_cache: None,
+            gc_sandbox: None,
+            gc_symlinks: None,
+            force: false,
+            gc_metrics_window: None,
+            cargo_home: None,
+            keep_cargo_home: false,
+            removal_list: None,
+            removal_list_format: None,
+            gc_audit_log: None,
+            gc_audit_log_format: None,
+            gc_max_duration: None,
+            gc_compress_cold: false,
+            gc_dep_info: false,
+            only: &[],
+            simulate: false,
+            suggest_only: false,
+            policies: &[],
             age_threshold_days: 7,
+            age_threshold: None,
+            age_threshold_for: &[],
+            lockfiles: &[],
             verbose: 0,
             metadata_path: None,
             quiet: false,
+            context: HoldContext::default(),
         }
     }
 
@@ -97,11 +337,64 @@ impl<'a> GcOptionsBuilder<'a> {
         self
     }
 
+    /// Set every option given by the shared GC arguments `gc`
+    #[cfg(feature = "cli")]
+    pub fn gc_args(self, gc: &'a GcArgs) -> Self {
+        self.max_target_size(gc.max_target_size())
+            .max_target_files(gc.max_target_files())
+            .max_build_dir_size(gc.max_build_dir_size())
+            .max_coverage_size(gc.max_coverage_size())
+            .preserve_cargo_binaries(gc.preserve_cargo_binaries())
+            .preserve_units_from(gc.preserve_units_from())
+            .preserve_last_builds(gc.preserve_last_builds())
+            .preserve_window(gc.preserve_window())
+            .preserve_buffer(gc.preserve_buffer())
+            .size_mode(gc.size_mode())
+            .gc_when_busy(gc.gc_when_busy())
+            .gc_policy_cmd(gc.gc_policy_cmd())
+            .global_cache(gc.global_cache())
+            .gc_sandbox(gc.gc_sandbox())
+            .gc_symlinks(gc.gc_symlinks())
+            .force(gc.force())
+            .gc_metrics_window(gc.gc_metrics_window())
+            .cargo_home(gc.cargo_home())
+            .removal_list(gc.removal_list())
+            .removal_list_format(gc.removal_list_format())
+            .gc_audit_log(gc.gc_audit_log())
+            .gc_audit_log_format(gc.gc_audit_log_format())
+            .gc_max_duration(gc.gc_max_duration())
+            .gc_compress_cold(gc.gc_compress_cold())
+            .gc_dep_info(gc.gc_dep_info())
+            .age_threshold(gc.age_threshold())
+            .age_threshold_for(gc.age_threshold_for())
+            .lockfiles(gc.lockfiles())
+    }
+
     pub fn max_target_size(mut self, size: Option<&'a str>) -> Self {
         self.max_target_size = size;
         self
     }
 
+    pub fn max_target_files(mut self, files: Option<u64>) -> Self {
+        self.max_target_files = files;
+        self
+    }
+
+    pub fn build_dir(mut self, path: Option<&'a Path>) -> Self {
+        self.build_dir = path;
+        self
+    }
+
+    pub fn max_build_dir_size(mut self, size: Option<&'a str>) -> Self {
+        self.max_build_dir_size = size;
+        self
+    }
+
+    pub fn max_coverage_size(mut self, size: Option<&'a str>) -> Self {
+        self.max_coverage_size = size;
+        self
+    }
+
     pub fn auto_max_target_size(mut self, enabled: bool) -> Self {
         self.auto_max_target_size = enabled;
         self
@@ -122,11 +415,151 @@ impl<'a> GcOptionsBuilder<'a> {
         self
     }
 
+    pub fn preserve_units_from(mut self, path: Option<&'a Path>) -> Self {
+        self.preserve_units_from = path;
+        self
+    }
+
+    pub fn preserve_last_builds(mut self, builds: usize) -> Self {
+        self.preserve_last_builds = builds;
+        self
+    }
+
+    pub fn preserve_window(mut self, window: Option<&'a str>) -> Self {
+        self.preserve_window = window;
+        self
+    }
+
+    pub fn preserve_buffer(mut self, buffer: Option<&'a str>) -> Self {
+        self.preserve_buffer = buffer;
+        self
+    }
+
+    pub fn size_mode(mut self, mode: Option<&'a str>) -> Self {
+        self.size_mode = mode;
+        self
+    }
+
+    pub fn gc_when_busy(mut self, policy: Option<&'a str>) -> Self {
+        self.gc_when_busy = policy;
+        self
+    }
+
+    pub fn gc_policy_cmd(mut self, command: Option<&'a str>) -> Self {
+        self.gc_policy_cmd = command;
+        self
+    }
+
+    pub fn global_cache(mut self, mode: Option<&'a str>) -> Self {
+        self.global_cache = mode;
+        self
+    }
+
+    pub fn gc_sandbox(mut self, mode: Option<&'a str>) -> Self {
+        self.gc_sandbox = mode;
+        self
+    }
+
+    pub fn gc_symlinks(mut self, policy: Option<&'a str>) -> Self {
+        self.gc_symlinks = policy;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn gc_metrics_window(mut self, window: Option<NonZeroU32>) -> Self {
+        self.gc_metrics_window = window;
+        self
+    }
+
+    pub fn cargo_home(mut self, path: Option<&'a Path>) -> Self {
+        self.cargo_home = path;
+        self
+    }
+
+    pub fn keep_cargo_home(mut self, keep: bool) -> Self {
+        self.keep_cargo_home = keep;
+        self
+    }
+
+    pub fn removal_list(mut self, path: Option<&'a Path>) -> Self {
+        self.removal_list = path;
+        self
+    }
+
+    pub fn removal_list_format(mut self, format: Option<&'a str>) -> Self {
+        self.removal_list_format = format;
+        self
+    }
+
+    pub fn gc_audit_log(mut self, path: Option<&'a Path>) -> Self {
+        self.gc_audit_log = path;
+        self
+    }
+
+    pub fn gc_audit_log_format(mut self, format: Option<&'a str>) -> Self {
+        self.gc_audit_log_format = format;
+        self
+    }
+
+    pub fn gc_max_duration(mut self, duration: Option<&'a str>) -> Self {
+        self.gc_max_duration = duration;
+        self
+    }
+
+    pub fn gc_compress_cold(mut self, enabled: bool) -> Self {
+        self.gc_compress_cold = enabled;
+        self
+    }
+
+    pub fn gc_dep_info(mut self, enabled: bool) -> Self {
+        self.gc_dep_info = enabled;
+        self
+    }
+
+    pub fn only(mut self, patterns: &'a [String]) -> Self {
+        self.only = patterns;
+        self
+    }
+
+    pub fn simulate(mut self, simulate: bool) -> Self {
+        self.simulate = simulate;
+        self
+    }
+
+    pub fn suggest_only(mut self, suggest_only: bool) -> Self {
+        self.suggest_only = suggest_only;
+        self
+    }
+
+    pub fn policies(mut self, policies: &'a [String]) -> Self {
+        self.policies = policies;
+        self
+    }
+
     pub fn age_threshold_days(mut self, days: u32) -> Self {
         self.age_threshold_days = days;
         self
     }
 
+    pub fn age_threshold(mut self, threshold: Option<&'a str>) -> Self {
+        self.age_threshold = threshold;
+        self
+    }
+
+    pub fn age_threshold_for(mut self, thresholds: &'a [String]) -> Self {
+        self.age_threshold_for = thresholds;
+        self
+    }
+
+    pub fn lockfiles(mut self, paths: &'a [PathBuf]) -> Self {
+        self.lockfiles = paths;
+        self
+    }
+
     pub fn verbose(mut self, verbose: u8) -> Self {
         self.verbose = verbose;
         self
@@ -142,20 +575,58 @@ impl<'a> GcOptionsBuilder<'a> {
         self
     }
 
+    pub fn context(mut self, context: &HoldContext) -> Self {
+        self.context = context.clone();
+        self
+    }
+
     pub fn build(self) -> Result<GcOptions<'a>> {
         Ok(GcOptions {
             target_dir: self
                 .target_dir
                 .ok_or_else(|| HoldError::ConfigError("target_dir is required".to_string()))?,
             max_target_size: self.max_target_size,
+            max_target_files: self.max_target_files,
+            build_dir: self.build_dir,
+            max_build_dir_size: self.max_build_dir_size,
+            max_coverage_size: self.max_coverage_size,
             auto_max_target_size: self.auto_max_target_size,
             dry_run: self.dry_run,
             debug: self.debug,
             preserve_cargo_binaries: self.preserve_cargo_binaries,
+            preserve_units_from: self.preserve_units_from,
+            preserve_last_builds: self.preserve_last_builds,
+            preserve_window: self.preserve_window,
+            preserve_buffer: self.preserve_buffer,
+            size_mode: self.size_mode,
+            gc_when_busy: self.gc_when_busy,
+            gc_policy_cmd: self.gc_policy_cmd,
+            global_cache: self.global_cache,
+            gc_sandbox: self.gc_sandbox,
+            gc_symlinks: self.gc_symlinks,
+            force: self.force,
+            gc_metrics_window: self.gc_metrics_window,
+            cargo_home: self.cargo_home,
+            keep_cargo_home: self.keep_cargo_home,
+            removal_list: self.removal_list,
+            removal_list_format: self.removal_list_format,
+            gc_audit_log: self.gc_audit_log,
+            gc_audit_log_format: self.gc_audit_log_format,
+            gc_max_duration: self.gc_max_duration,
+            gc_compress_cold: self.gc_compress_cold,
+            gc_dep_info: self.gc_dep_info,
+            only: self.only,
+            simulate: self.simulate,
+            suggest_only: self.suggest_only,
+            policies: self.policies,
             age_threshold_days: self.age_threshold_days,
+            age_threshold: self.age_threshold,
+            age_threshold_for: self.age_threshold_for,
+            lockfiles: self.lockfiles,
             verbose: self.verbose,
             metadata_path: self.metadata_path,
             quiet: self.quiet,
+            context: self.context,
         })
     }
 }
@@ -1,12 +1,20 @@
 //! Heave (garbage collection) command and helpers.
 
-use std::path::Path;
+use std::fs;
+use std::io::IsTerminal;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "cli")]
+use crate::cli::GcArgs;
 use crate::commands::gc_options::{GcOptions, GcOptionsBuilder};
+use crate::context::HoldContext;
 use crate::error::Result;
-use crate::gc::config::Gc;
+use crate::gc::config::{Gc, GcBuilder};
+use crate::gc::units::BuildUnits;
 use crate::gc::{self, auto_cap};
+use crate::hooks::{self, Hook};
 use crate::logging::Logger;
 use crate::metadata::{load_metadata, save_metadata};
 use crate::state::{CapTrace, StateMetadata};
@@ -37,11 +45,38 @@ impl<'a> HeaveBuilder<'a> {
         self
     }
 
+    /// Set every option given by the shared GC arguments `gc`
+    #[cfg(feature = "cli")]
+    pub fn gc_args(mut self, gc: &'a GcArgs) -> Self {
+        self.gc = self.gc.gc_args(gc);
+        self
+    }
+
     pub fn max_target_size(mut self, size: Option<&'a str>) -> Self {
         self.gc = self.gc.max_target_size(size);
         self
     }
 
+    pub fn max_target_files(mut self, files: Option<u64>) -> Self {
+        self.gc = self.gc.max_target_files(files);
+        self
+    }
+
+    pub fn build_dir(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.build_dir(path);
+        self
+    }
+
+    pub fn max_build_dir_size(mut self, size: Option<&'a str>) -> Self {
+        self.gc = self.gc.max_build_dir_size(size);
+        self
+    }
+
+    pub fn max_coverage_size(mut self, size: Option<&'a str>) -> Self {
+        self.gc = self.gc.max_coverage_size(size);
+        self
+    }
+
     pub fn auto_max_target_size(mut self, enabled: bool) -> Self {
         self.gc = self.gc.auto_max_target_size(enabled);
         self
@@ -62,6 +97,141 @@ impl<'a> HeaveBuilder<'a> {
         self
     }
 
+    pub fn preserve_units_from(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.preserve_units_from(path);
+        self
+    }
+
+    pub fn preserve_last_builds(mut self, builds: usize) -> Self {
+        self.gc = self.gc.preserve_last_builds(builds);
+        self
+    }
+
+    pub fn preserve_window(mut self, window: Option<&'a str>) -> Self {
+        self.gc = self.gc.preserve_window(window);
+        self
+    }
+
+    pub fn preserve_buffer(mut self, buffer: Option<&'a str>) -> Self {
+        self.gc = self.gc.preserve_buffer(buffer);
+        self
+    }
+
+    pub fn size_mode(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.size_mode(mode);
+        self
+    }
+
+    pub fn gc_when_busy(mut self, policy: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_when_busy(policy);
+        self
+    }
+
+    pub fn gc_policy_cmd(mut self, command: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_policy_cmd(command);
+        self
+    }
+
+    pub fn global_cache(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.global_cache(mode);
+        self
+    }
+
+    pub fn gc_sandbox(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_sandbox(mode);
+        self
+    }
+
+    pub fn gc_symlinks(mut self, policy: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_symlinks(policy);
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.gc = self.gc.force(force);
+        self
+    }
+
+    pub fn gc_metrics_window(mut self, window: Option<NonZeroU32>) -> Self {
+        self.gc = self.gc.gc_metrics_window(window);
+        self
+    }
+
+    pub fn cargo_home(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.cargo_home(path);
+        self
+    }
+
+    pub fn removal_list(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.removal_list(path);
+        self
+    }
+
+    pub fn removal_list_format(mut self, format: Option<&'a str>) -> Self {
+        self.gc = self.gc.removal_list_format(format);
+        self
+    }
+
+    pub fn gc_audit_log(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.gc_audit_log(path);
+        self
+    }
+
+    pub fn gc_audit_log_format(mut self, format: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_audit_log_format(format);
+        self
+    }
+
+    pub fn gc_max_duration(mut self, duration: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_max_duration(duration);
+        self
+    }
+
+    pub fn gc_compress_cold(mut self, enabled: bool) -> Self {
+        self.gc = self.gc.gc_compress_cold(enabled);
+        self
+    }
+
+    pub fn gc_dep_info(mut self, enabled: bool) -> Self {
+        self.gc = self.gc.gc_dep_info(enabled);
+        self
+    }
+
+    pub fn age_threshold(mut self, threshold: Option<&'a str>) -> Self {
+        self.gc = self.gc.age_threshold(threshold);
+        self
+    }
+
+    pub fn age_threshold_for(mut self, thresholds: &'a [String]) -> Self {
+        self.gc = self.gc.age_threshold_for(thresholds);
+        self
+    }
+
+    pub fn lockfiles(mut self, paths: &'a [PathBuf]) -> Self {
+        self.gc = self.gc.lockfiles(paths);
+        self
+    }
+
+    pub fn only(mut self, patterns: &'a [String]) -> Self {
+        self.gc = self.gc.only(patterns);
+        self
+    }
+
+    pub fn simulate(mut self, simulate: bool) -> Self {
+        self.gc = self.gc.simulate(simulate);
+        self
+    }
+
+    pub fn suggest_only(mut self, suggest_only: bool) -> Self {
+        self.gc = self.gc.suggest_only(suggest_only);
+        self
+    }
+
+    pub fn policies(mut self, policies: &'a [String]) -> Self {
+        self.gc = self.gc.policies(policies);
+        self
+    }
+
     pub fn age_threshold_days(mut self, days: u32) -> Self {
         self.gc = self.gc.age_threshold_days(days);
         self
@@ -82,6 +252,11 @@ impl<'a> HeaveBuilder<'a> {
         self
     }
 
+    pub fn context(mut self, context: &HoldContext) -> Self {
+        self.gc = self.gc.context(context);
+        self
+    }
+
     pub fn build(self) -> Result<Heave<'a>> {
         Ok(Heave {
             gc: self.gc.build()?,
@@ -94,24 +269,60 @@ impl<'a> Heave<'a> {
         HeaveBuilder::new()
     }
 
-    /// Execute the heave command (garbage collection)
+    /// Create a heave from already-built GC options.
+    pub(crate) fn from_options(gc: GcOptions<'a>) -> Self {
+        Self { gc }
+    }
+
+    /// Execute the heave command (garbage collection), then run the
+    /// `post-heave` hook unless in a dry run
     pub fn heave(self) -> Result<()> {
-        let log = Logger::new(self.gc.verbose(), self.gc.quiet());
+        let context = self.gc.context();
+        let log = self.gc.logger();
         log.verbose(1, "Heave ho! Starting garbage collection...");
 
         let mut max_size = if let Some(size_str) = self.gc.max_target_size() {
-            Some(gc::parse_size(size_str)?)
+            let size =
+                gc::parse_max_target_size(size_str, self.gc.target_dir(), context.size_units)?;
+            if size_str.trim().ends_with('%') {
+                log.verbose(
+                    1,
+                    format!("Max target size {size_str} is {}", log.format_size(size)),
+                );
+            }
+            Some(size)
         } else {
             None
         };
 
-        let loaded_metadata = if let Some(path) = self.gc.metadata_path() {
-            match load_metadata(path) {
+        let preserve_window = self
+            .gc
+            .preserve_window()
+            .map(gc::parse_duration)
+            .transpose()?;
+        let preserve_buffer = self
+            .gc
+            .preserve_buffer()
+            .map(gc::parse_duration)
+            .transpose()?;
+        let max_duration = self
+            .gc
+            .gc_max_duration()
+            .map(gc::parse_duration)
+            .transpose()?;
+        let policies = self
+            .gc
+            .policies()
+            .iter()
+            .map(|policy| policy.parse::<gc::SimulatedPolicy>())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut loaded_metadata = if let Some(path) = self.gc.metadata_path() {
+            match load_metadata(context, path) {
                 Ok(metadata) => Some(metadata),
                 Err(err) => {
-                    log.info(format!(
-                        "Warning: failed to load metadata for GC metrics ({}). Continuing with \
-                         defaults.",
+                    log.warn(format!(
+                        "failed to load metadata for GC metrics ({}). Continuing with defaults.",
                         err
                     ));
                     None
@@ -121,11 +332,93 @@ impl<'a> Heave<'a> {
             None
         };
 
-        let current_size = gc::calculate_directory_size(self.gc.target_dir())
-            .ok()
-            .filter(|size| *size > 0);
+        let size_mode = self
+            .gc
+            .size_mode()
+            .map(str::parse::<gc::SizeMode>)
+            .transpose()?
+            .unwrap_or_default();
+        let when_busy = self
+            .gc
+            .gc_when_busy()
+            .map(str::parse::<gc::BusyPolicy>)
+            .transpose()?
+            .unwrap_or_default();
+        let removal_list_format = self
+            .gc
+            .removal_list_format()
+            .map(str::parse::<gc::RemovalListFormat>)
+            .transpose()?
+            .unwrap_or_default();
+        let audit_log_format = self
+            .gc
+            .gc_audit_log_format()
+            .map(str::parse::<gc::AuditLogFormat>)
+            .transpose()?
+            .unwrap_or_default();
+        let global_cache_mode = self
+            .gc
+            .global_cache()
+            .map(str::parse::<gc::GlobalCacheMode>)
+            .transpose()?
+            .unwrap_or_default();
+        let sandbox = self
+            .gc
+            .gc_sandbox()
+            .map(str::parse::<gc::SandboxMode>)
+            .transpose()?
+            .unwrap_or_default();
+        let symlinks = self
+            .gc
+            .gc_symlinks()
+            .map(str::parse::<gc::SymlinkPolicy>)
+            .transpose()?
+            .unwrap_or_default();
+
+        let age_threshold = self
+            .gc
+            .age_threshold()
+            .map(gc::parse_duration)
+            .transpose()?;
+        let area_age_thresholds = self
+            .gc
+            .age_threshold_for()
+            .iter()
+            .map(|threshold| gc::parse_area_threshold(threshold))
+            .collect::<Result<Vec<_>>>()?;
+
+        let path_filter = if self.gc.only().is_empty() {
+            None
+        } else {
+            Some(gc::PathFilter::new(self.gc.target_dir(), self.gc.only())?)
+        };
 
-        let last_gc_mtime_nanos = loaded_metadata.as_ref().and_then(|m| m.last_gc_mtime_nanos);
+        if let Some(fs_type) = gc::compressing_filesystem(self.gc.target_dir()) {
+            match size_mode {
+                gc::SizeMode::Apparent => log.warn(format!(
+                    "target directory is on {fs_type}, which may compress data; apparent sizes \
+                     can overstate disk usage (consider --size-mode allocated)"
+                )),
+                gc::SizeMode::Allocated => log.verbose(
+                    1,
+                    format!("Target directory is on {fs_type}; measuring allocated blocks"),
+                ),
+            }
+        }
+
+        // Only auto-cap needs the size up front, so skip the walk otherwise
+        let current_size = || {
+            gc::calculate_disk_usage(self.gc.target_dir())
+                .ok()
+                .map(|usage| usage.get(size_mode))
+                .filter(|size| *size > 0)
+        };
+
+        let gc_mtime_history = loaded_metadata
+            .as_ref()
+            .map(StateMetadata::gc_mtime_history)
+            .unwrap_or_default();
+        let last_gc_mtime_nanos = gc_mtime_history.last().copied();
 
         if !log.quiet()
             && let Some(mtime) = last_gc_mtime_nanos
@@ -140,72 +433,406 @@ impl<'a> Heave<'a> {
             );
         }
 
+        // Until a few runs have recorded how large the target directory
+        // usually gets, a restored cache looks like it is mostly garbage, so
+        // size limits wait unless --force says otherwise
+        let runs = loaded_metadata
+            .as_ref()
+            .map_or(0, |metadata| metadata.gc_metrics.runs);
+        let warming_up =
+            !self.gc.force() && self.gc.metadata_path().is_some() && runs < auto_cap::WARMUP_RUNS;
+        let suggest_only = self.gc.suggest_only();
+        let size_limited = max_size.is_some()
+            || (self.gc.auto_max_target_size() && !suggest_only)
+            || self.gc.max_target_files().is_some()
+            || self.gc.max_coverage_size().is_some()
+            || self.gc.max_build_dir_size().is_some();
+        if warming_up && size_limited {
+            max_size = None;
+            let left = auto_cap::WARMUP_RUNS - runs;
+            log.summarize("size limits", "suspended (warming up)");
+            log.notice(format!(
+                "GC metrics are warming up ({runs} of {} runs recorded): size limits are \
+                 suspended for {left} more run{} and artifacts are only evicted by age; pass \
+                 --force to apply them now",
+                auto_cap::WARMUP_RUNS,
+                if left == 1 { "" } else { "s" }
+            ));
+        }
+
         let mut auto_cap_used = false;
+        let mut suggested_cap: Option<u64> = None;
         let mut cap_trace: Option<CapTrace> = None;
-        if max_size.is_none()
-            && self.gc.auto_max_target_size()
+        // Auto-cap history describes the whole target directory, so it does
+        // not apply to a partial GC. With --suggest-only the suggestion is
+        // only observed, so neither an explicit cap nor warming up stops it.
+        if (suggest_only || (max_size.is_none() && !warming_up && self.gc.auto_max_target_size()))
+            && path_filter.is_none()
             && let Some(metadata) = loaded_metadata.as_ref()
             && let Some((suggested, trace)) =
-                auto_cap::suggest_max_target_size(&metadata.gc_metrics, current_size)
+                auto_cap::suggest_max_target_size(&metadata.gc_metrics, current_size())
         {
-            max_size = Some(suggested);
-            auto_cap_used = true;
-            cap_trace = Some(trace.clone());
-            if !log.quiet()
-                && let Some(trace) = cap_trace.as_ref()
-            {
+            if !suggest_only {
+                max_size = Some(suggested);
+                auto_cap_used = true;
+            }
+            suggested_cap = Some(suggested);
+            log.summarize_size("suggested cap", suggested);
+            if !log.quiet() {
                 // Always log a concise summary (even without verbose) so CI logs show why the
                 // cap moved.
                 eprintln!(
-                    "Auto-selected max target size: {} (baseline {}, headroom {}, growth p90 {}%, \
-                     clamp {})",
-                    gc::format_size(suggested),
-                    gc::format_size(trace.baseline),
-                    gc::format_size(trace.growth_budget),
+                    "{} max target size: {} (baseline {}, headroom {}, growth p90 {}%, clamp {}){}",
+                    if suggest_only {
+                        "Suggested"
+                    } else {
+                        "Auto-selected"
+                    },
+                    log.format_size(suggested),
+                    log.format_size(trace.baseline),
+                    log.format_size(trace.growth_budget),
                     trace.observed_growth_pct,
-                    trace.clamp_reason
+                    trace.clamp_reason,
+                    if suggest_only {
+                        "; not enforced (--suggest-only)"
+                    } else {
+                        ""
+                    }
                 );
             }
+            cap_trace = Some(trace);
         }
 
+        let profile = context.profile;
         let mut builder = Gc::builder()
+            .context(context)
             .target_dir(self.gc.target_dir().to_path_buf())
             .dry_run(self.gc.dry_run())
             .debug(self.gc.debug() || self.gc.verbose() >= 2)
             .age_threshold_days(self.gc.age_threshold_days())
             .preserve_binaries(self.gc.preserve_cargo_binaries().to_vec())
+            .size_mode(size_mode)
+            .when_busy(when_busy)
+            .global_cache_mode(global_cache_mode)
+            .sandbox(sandbox)
+            .symlinks(symlinks)
+            .record_removals(self.gc.removal_list().is_some() || self.gc.gc_audit_log().is_some())
+            .compress_cold(self.gc.gc_compress_cold())
+            .dep_info(self.gc.gc_dep_info())
+            .keep_cargo_home(self.gc.keep_cargo_home() || !profile.cleans_cargo_home())
+            .keep_incremental(profile.keeps_incremental())
+            .keep_docs(profile.keeps_docs())
             .quiet(self.gc.quiet());
 
         if let Some(size) = max_size {
             builder = builder.max_target_size(size);
         }
+        if let Some(files) = self.gc.max_target_files()
+            && !warming_up
+        {
+            builder = builder.max_target_files(files);
+        }
+        if let Some(size_str) = self.gc.max_coverage_size()
+            && !warming_up
+        {
+            builder = builder.max_coverage_size(gc::parse_max_target_size(
+                size_str,
+                self.gc.target_dir(),
+                context.size_units,
+            )?);
+        }
+        if let Some(build_dir) = self.gc.build_dir() {
+            log.verbose(1, format!("Build directory: {}", build_dir.display()));
+            builder = builder.build_dir(build_dir);
+            if let Some(size_str) = self.gc.max_build_dir_size()
+                && !warming_up
+            {
+                builder = builder.max_build_dir_size(gc::parse_max_target_size(
+                    size_str,
+                    build_dir,
+                    context.size_units,
+                )?);
+            }
+        } else if separate_build_dir_in_use(self.gc.target_dir()) {
+            log.notice(format!(
+                "{} only holds final artifacts; cargo keeps the intermediate ones in its build \
+                 directory, which is only cleaned when given with --build-dir",
+                self.gc.target_dir().display()
+            ));
+        }
+
+        let partial = path_filter.is_some();
+        if let Some(filter) = path_filter {
+            builder = builder.path_filter(filter);
+        }
+
+        if let Some(threshold) = age_threshold {
+            builder = builder.age_threshold(threshold);
+        }
+        for (area, threshold) in area_age_thresholds {
+            builder = builder.area_age_threshold(area, threshold);
+        }
+
+        if let Some(cargo_home) = self.gc.cargo_home() {
+            builder = builder.cargo_home(cargo_home);
+        }
+
+        // Custom profiles may nest anywhere, so look them up by name
+        let workspace_root = std::path::absolute(self.gc.target_dir())
+            .ok()
+            .and_then(|target_dir| target_dir.parent().map(Path::to_path_buf));
+        if let Some(workspace_root) = workspace_root {
+            let profile_names = gc::declared_profile_names(&workspace_root, self.gc.cargo_home());
+            log.verbose(
+                2,
+                format!("Declared profiles: {}", profile_names.join(", ")),
+            );
+            builder = builder.profile_names(profile_names);
+        }
+
+        if let Some(runner) = context.ephemeral.runner() {
+            log.verbose(1, format!("Running on {runner}"));
+            builder = builder.runner(runner);
+        }
+
+        for lockfile in self.lockfiles() {
+            builder = builder.lockfile(lockfile);
+        }
+
+        if let Some(command) = self.gc.gc_policy_cmd() {
+            builder = builder.policy_command(gc::PolicyCommand::new(command));
+        }
+
+        if let Some(metadata) = loaded_metadata.as_ref()
+            && !metadata.pinned_crates.is_empty()
+        {
+            builder = builder.pinned_crates(gc::PinnedCrates::new(&metadata.pinned_crates)?);
+        }
+
+        if let Some(metadata) = loaded_metadata.as_ref()
+            && !metadata.build_costs.is_empty()
+        {
+            builder = builder.build_costs(gc::BuildCosts::new(&metadata.build_costs));
+        }
+
+        if let Some(metadata) = loaded_metadata.as_ref()
+            && !metadata.gc_metrics.profile_build_mtime_nanos.is_empty()
+        {
+            if metadata
+                .gc_metrics
+                .successful_build_before_failures()
+                .is_some()
+            {
+                log.verbose(
+                    1,
+                    "The last reported build failed; preserving artifacts from the last \
+                     successful build on",
+                );
+            }
+            builder = builder
+                .profile_build_mtime_nanos(metadata.gc_metrics.preserved_build_mtime_nanos());
+        }
+
+        // The size index covers the whole target directory and is only worth
+        // keeping when the metadata is saved afterwards
+        if self.gc.metadata_path().is_some() && !partial {
+            let directory_sizes = loaded_metadata
+                .as_mut()
+                .map(|metadata| std::mem::take(&mut metadata.gc_metrics.directory_sizes))
+                .unwrap_or_default();
+            builder = builder.directory_sizes(directory_sizes);
+        }
+
+        if !gc_mtime_history.is_empty() {
+            let preserve_last_builds = self.gc.preserve_last_builds();
+            log.verbose(
+                1,
+                format!(
+                    "Preserving artifacts from the last {} of {} recorded builds",
+                    preserve_last_builds.min(gc_mtime_history.len()),
+                    gc_mtime_history.len()
+                ),
+            );
+            builder = builder
+                .recent_build_mtime_nanos(gc_mtime_history.clone())
+                .preserve_last_builds(preserve_last_builds);
+        }
+
+        if let Some(window) = preserve_window {
+            log.verbose(
+                1,
+                format!("Preservation window: {}", gc::format_duration(window)),
+            );
+            builder = builder.preserve_window(window);
+        }
+
+        if let Some(duration) = max_duration {
+            log.verbose(1, format!("Time budget: {}", gc::format_duration(duration)));
+            builder = builder.max_duration(duration);
+        }
+
+        if let Some(buffer) = preserve_buffer {
+            log.verbose(
+                1,
+                format!("Preservation buffer: {}", gc::format_duration(buffer)),
+            );
+            builder = builder.preserve_buffer(buffer);
+        }
+
+        if let Some(path) = self.gc.preserve_units_from() {
+            if path.exists() {
+                match BuildUnits::load(path) {
+                    Ok(units) => {
+                        log.verbose(
+                            1,
+                            format!("Loaded last build units from {}", path.display()),
+                        );
+                        builder = builder.build_units(units);
+                    }
+                    Err(err) => log.warn(format!(
+                        "failed to read build units from {} ({}). Continuing without them.",
+                        path.display(),
+                        err
+                    )),
+                }
+            } else {
+                log.verbose(
+                    1,
+                    format!(
+                        "Build units file {} not found; skipping unit preservation",
+                        path.display()
+                    ),
+                );
+            }
+        }
 
-        if let Some(nanos) = last_gc_mtime_nanos {
-            builder = builder.previous_build_mtime_nanos(nanos);
+        if self.gc.simulate() {
+            // Nothing is removed, so the metadata is left as is too
+            return simulate(builder, &policies, self.gc.target_dir(), &log);
         }
 
-        let config = builder.build();
+        let mut config = builder.build();
+
+        if !self.gc.dry_run()
+            && let Some(limit) = profile.confirm_evictions_above()
+        {
+            let preview = config.preview()?;
+            if !preview.skipped_busy
+                && preview.bytes_freed > limit
+                && !confirm_eviction(preview.bytes_freed, &log)
+            {
+                log.summarize("(not confirmed)", "skipped");
+                return Ok(());
+            }
+        }
 
         let stats = config.perform_gc(self.gc.verbose())?;
+        if let Some(destination) = self.gc.removal_list() {
+            gc::write_removal_list(&stats.removed_paths, removal_list_format, destination)?;
+            log.verbose(
+                1,
+                format!(
+                    "Wrote {} removed paths to {}",
+                    stats.removed_paths.len(),
+                    destination.display()
+                ),
+            );
+        }
+        if let Some(path) = self.gc.gc_audit_log()
+            && !self.gc.dry_run()
+        {
+            gc::append_audit_log(&stats.removed_paths, audit_log_format, path)?;
+        }
+        if stats.skipped_busy {
+            log.summarize("(target busy)", "skipped");
+            // Leave metadata untouched so the next run preserves the same builds
+            return Ok(());
+        }
+
+        log.summarize_size(
+            if self.gc.dry_run() {
+                "freeable"
+            } else {
+                "freed"
+            },
+            stats.bytes_freed,
+        );
+        log.summarize_count("crates cleaned", stats.crates_cleaned);
+        log.summarize_count("removal errors", stats.errors);
+        log.summarize_count("removals refused", stats.refused_removals);
+        if stats.out_of_time {
+            log.summarize_count("crates left", stats.remaining_crates);
+            log.summarize_size("left", stats.remaining_bytes);
+            log.notice(format!(
+                "Stopped after the --gc-max-duration budget: {} selected crates ({}) and any \
+                 later cleanup are left for the next run",
+                stats.remaining_crates,
+                log.format_size(stats.remaining_bytes)
+            ));
+        }
+        log.summarize_size("target", stats.final_size);
+        if self.gc.build_dir().is_some() {
+            log.summarize_size("build dir", stats.build_dir_final_size);
+        }
+        if let Some(cap) = max_size {
+            log.summarize_size("cap", cap);
+        }
 
         if !log.quiet() {
             eprintln!("Garbage collection complete:");
-            eprintln!("  Initial size: {}", gc::format_size(stats.initial_size));
-            eprintln!("  Final size: {}", gc::format_size(stats.final_size));
-            eprintln!("  Space freed: {}", gc::format_size(stats.bytes_freed));
+            let estimated = |estimated: bool| if estimated { "~" } else { "" };
+            eprintln!(
+                "  Initial size: {}{} (apparent {}, allocated {})",
+                estimated(stats.initial_size_estimated),
+                log.format_size(stats.initial_size),
+                log.format_size(stats.initial_usage.apparent),
+                log.format_size(stats.initial_usage.allocated)
+            );
+            eprintln!(
+                "  Final size: {}{} (apparent {}, allocated {})",
+                estimated(stats.final_size_estimated),
+                log.format_size(stats.final_size),
+                log.format_size(stats.final_usage.apparent),
+                log.format_size(stats.final_usage.allocated)
+            );
+            if self.gc.build_dir().is_some() {
+                eprintln!(
+                    "  Build directory: {} -> {}",
+                    log.format_size(stats.build_dir_initial_size),
+                    log.format_size(stats.build_dir_final_size)
+                );
+            }
+            eprintln!(
+                "  Files: {} -> {} ({} removed)",
+                stats.initial_files, stats.final_files, stats.files_freed
+            );
+            eprintln!("  Size mode: {size_mode}");
+            eprintln!("  Space freed: {}", log.format_size(stats.bytes_freed));
             eprintln!("  Artifacts removed: {}", stats.artifacts_removed);
             eprintln!("  Crates cleaned: {}", stats.crates_cleaned);
+            if self.gc.gc_compress_cold() {
+                eprintln!("  Crates compressed: {}", stats.crates_compressed);
+            }
             eprintln!("  Binaries preserved: {}", stats.binaries_preserved);
+            if stats.errors > 0 {
+                eprintln!("  Removal errors: {} (not counted as freed)", stats.errors);
+            }
+            if stats.refused_removals > 0 {
+                eprintln!(
+                    "  Removals refused: {} (outside of the cleaned directories)",
+                    stats.refused_removals
+                );
+            }
             eprintln!(
                 "  Registry cleanup: {} files, {} dirs, {} freed",
                 stats.registry_files_removed,
                 stats.registry_dirs_removed,
-                gc::format_size(stats.registry_bytes_freed)
+                log.format_size(stats.registry_bytes_freed)
             );
 
             if let Some(cap) = max_size {
                 let mode = if auto_cap_used { "auto" } else { "user" };
-                eprintln!("  Cap used ({}): {}", mode, gc::format_size(cap));
+                eprintln!("  Cap used ({}): {}", mode, log.format_size(cap));
             }
 
             if self.gc.dry_run() {
@@ -213,26 +840,41 @@ impl<'a> Heave<'a> {
             }
         }
 
-        if let Some(path) = self.gc.metadata_path() {
+        if partial {
+            // A partial GC says nothing about the whole target directory or the
+            // last build, so the recorded metrics and timestamps are left as is
+            log.verbose(1, "Partial GC (--only); not updating GC metrics");
+        } else if let Some(path) = self.gc.metadata_path() {
             let mut metadata = loaded_metadata.unwrap_or_else(StateMetadata::new);
             metadata.gc_metrics.runs = metadata.gc_metrics.runs.saturating_add(1);
-            if let Some(size) = current_size {
-                metadata.gc_metrics.seed_initial_size.get_or_insert(size);
+            if stats.initial_size > 0 {
+                metadata
+                    .gc_metrics
+                    .seed_initial_size
+                    .get_or_insert(stats.initial_size);
             }
+            if let Some(window) = self.gc.gc_metrics_window() {
+                metadata.gc_metrics_window = Some(window.get());
+            }
+            let window = metadata.metrics_window();
             auto_cap::push_bounded(
                 &mut metadata.gc_metrics.recent_initial_sizes,
                 stats.initial_size,
+                window,
             );
             auto_cap::push_bounded(
                 &mut metadata.gc_metrics.recent_bytes_freed,
                 stats.bytes_freed,
+                window,
             );
             auto_cap::push_bounded(
                 &mut metadata.gc_metrics.recent_final_sizes,
                 stats.final_size,
+                window,
             );
-            if auto_cap_used {
-                metadata.gc_metrics.last_suggested_cap = max_size;
+            metadata.gc_metrics.directory_sizes = stats.directory_sizes;
+            if suggested_cap.is_some() {
+                metadata.gc_metrics.last_suggested_cap = suggested_cap;
                 metadata.gc_metrics.last_cap_trace = cap_trace.clone();
             }
 
@@ -241,12 +883,157 @@ impl<'a> Heave<'a> {
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or(Duration::ZERO)
                     .as_nanos();
-                metadata.last_gc_mtime_nanos = Some(gc_time_nanos);
+                metadata.record_gc_mtime_nanos(gc_time_nanos);
+                for (profile, nanos) in &stats.profile_build_mtime_nanos {
+                    metadata
+                        .gc_metrics
+                        .record_profile_build_mtime_nanos(profile, *nanos);
+                }
+            }
+
+            save_metadata(context, &metadata, path)?;
+        }
+
+        if self.gc.dry_run() {
+            return Ok(());
+        }
+        hooks::run(
+            context,
+            Hook::PostHeave,
+            &[
+                ("CARGO_HOLD_BYTES_FREED", stats.bytes_freed.to_string()),
+                ("CARGO_HOLD_FILES_FREED", stats.files_freed.to_string()),
+            ],
+            &log,
+        )
+    }
+
+    /// Lockfiles whose git revisions are kept: those passed explicitly, plus
+    /// the `Cargo.lock` of the current directory and of the directory holding
+    /// the target directory, when present
+    fn lockfiles(&self) -> Vec<PathBuf> {
+        let mut lockfiles = self.gc.lockfiles().to_vec();
+        let beside_target = self
+            .gc
+            .target_dir()
+            .parent()
+            .map(|parent| parent.join("Cargo.lock"));
+        for candidate in [Some(PathBuf::from("Cargo.lock")), beside_target]
+            .into_iter()
+            .flatten()
+        {
+            if candidate.is_file() && !lockfiles.contains(&candidate) {
+                lockfiles.push(candidate);
             }
+        }
+        lockfiles
+    }
+}
+
+/// Whether cargo keeps the intermediate artifacts of the target directory in
+/// a separate build directory: the target directory has no profile directory
+/// of its own, but cargo's artifact directory lock
+fn separate_build_dir_in_use(target_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(target_dir) else {
+        return false;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.path().join(".cargo-artifact-lock").exists())
+        && gc::find_profile_directories(target_dir).is_ok_and(|dirs| dirs.is_empty())
+}
+
+/// Run GC as a dry run with the settings of this run, then with each of
+/// `policies` on top of them, and print what each would remove side by side
+fn simulate(
+    builder: GcBuilder,
+    policies: &[gc::SimulatedPolicy],
+    target_dir: &Path,
+    log: &Logger,
+) -> Result<()> {
+    let mut candidates = vec![("current".to_string(), builder.clone())];
+    for policy in policies {
+        candidates.push((
+            policy.spec().to_string(),
+            policy.apply(builder.clone(), target_dir, log.units())?,
+        ));
+    }
 
-            save_metadata(&metadata, path)?;
+    let mut results = Vec::new();
+    for (name, builder) in candidates {
+        log.verbose(1, format!("Simulating {name}..."));
+        let config = builder
+            .dry_run(true)
+            .quiet(true)
+            .record_removals(log.level() >= 1)
+            .build();
+        results.push((name, config.perform_gc(0)?));
+    }
+
+    let width = results
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["Policy".len()])
+        .max()
+        .unwrap_or(0);
+    if let Some((_, stats)) = results.first() {
+        println!("Target directory: {}", log.format_size(stats.initial_size));
+    }
+    println!(
+        "{:<width$} {:>11} {:>7} {:>9}",
+        "Policy", "Freed", "Crates", "Files"
+    );
+    for (name, stats) in &results {
+        if stats.skipped_busy {
+            println!("{name:<width$} (target busy)");
+            continue;
+        }
+        println!(
+            "{name:<width$} {:>11} {:>7} {:>9}",
+            log.format_size(stats.bytes_freed),
+            stats.crates_cleaned,
+            stats.files_freed
+        );
+    }
+    for (name, stats) in &results {
+        log.verbose(1, format!("{name} would remove:"));
+        for removed in &stats.removed_paths {
+            log.verbose(
+                1,
+                format!(
+                    "  {} ({}, {})",
+                    removed.path.display(),
+                    removed.reason,
+                    log.format_size(removed.size)
+                ),
+            );
         }
+    }
+    log.summarize_count("policies", results.len());
+    Ok(())
+}
 
-        Ok(())
+/// Ask on the terminal whether GC may free `bytes`; without a terminal there
+/// is nobody to ask, so the eviction is not confirmed
+fn confirm_eviction(bytes: u64, log: &Logger) -> bool {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        log.warn(format!(
+            "GC would free {}, which the local profile only does once confirmed on a terminal; \
+             skipping GC (run it on a terminal, or with --profile ci)",
+            log.format_size(bytes)
+        ));
+        return false;
+    }
+
+    eprint!("GC would free {}. Continue? [y/N] ", log.format_size(bytes));
+    let mut answer = String::new();
+    if stdin.read_line(&mut answer).is_err() {
+        return false;
+    }
+    let confirmed = matches!(answer.trim().to_lowercase().as_str(), "y" | "yes");
+    if !confirmed {
+        log.info("Skipping GC");
     }
+    confirmed
 }
@@ -0,0 +1,124 @@
+//! Import-from command implementation.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::metadata::{load_metadata, save_metadata};
+
+/// Executes the import-from command.
+///
+/// Seeds the GC timestamp history of a metadata file with the time of the last
+/// build recorded by another tool, so the first `heave` preserves that build's
+/// artifacts instead of evicting everything outside the age window. `source`
+/// says what `path` is:
+///
+/// - `sweep`: a `sweep.timestamp` written by `cargo sweep --stamp` before the
+///   build; the artifacts written since then are kept, as `cargo sweep --file`
+///   would
+/// - `sccache`: the output of `sccache --show-stats --stats-format=json` saved
+///   after the build; the stats carry no time, so the file's modification time
+///   stands for the end of the build
+///
+/// Nothing is imported when the metadata already records a GC at or after
+/// that time.
+pub fn import_from(
+    context: &HoldContext,
+    metadata_path: &Path,
+    source: &str,
+    path: &Path,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(
+        1,
+        format!("Importing the last build from {}...", path.display()),
+    );
+
+    let build_time = match source {
+        "sweep" => sweep_timestamp(path)?,
+        "sccache" => sccache_timestamp(path)?,
+        _ => unreachable!("clap only accepts sweep and sccache"),
+    };
+    if build_time > SystemTime::now() {
+        return Err(import_failed(path, "the recorded build lies in the future"));
+    }
+    let nanos = build_time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_nanos();
+
+    let mut metadata = load_metadata(context, metadata_path)?;
+    if metadata
+        .gc_mtime_history()
+        .last()
+        .is_some_and(|last| *last >= nanos)
+    {
+        log.info("The metadata already records a later GC; nothing to import");
+        log.summarize_count("imported", 0);
+        return Ok(());
+    }
+    metadata.record_gc_mtime_nanos(nanos);
+    save_metadata(context, &metadata, metadata_path)?;
+
+    let age = SystemTime::now()
+        .duration_since(build_time)
+        .unwrap_or(Duration::ZERO);
+    log.summarize_count("imported", 1);
+    log.info(format!(
+        "📥 Imported the last {source} build ({}s ago); the next heave preserves its artifacts",
+        age.as_secs()
+    ));
+    Ok(())
+}
+
+/// The time stored by `cargo sweep --stamp`, in milliseconds since the Unix
+/// epoch
+fn sweep_timestamp(path: &Path) -> Result<SystemTime> {
+    let contents = read(path)?;
+    let millis: u64 = contents
+        .trim()
+        .parse()
+        .map_err(|_| import_failed(path, "expected milliseconds since the Unix epoch"))?;
+    Ok(UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// The modification time of sccache's JSON statistics, provided they record
+/// a compilation
+fn sccache_timestamp(path: &Path) -> Result<SystemTime> {
+    let contents = read(path)?;
+    let stats: Value = serde_json::from_str(&contents)
+        .map_err(|err| import_failed(path, &format!("invalid JSON: {err}")))?;
+    let compile_requests = stats
+        .pointer("/stats/compile_requests")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| import_failed(path, "no stats.compile_requests"))?;
+    if compile_requests == 0 {
+        return Err(import_failed(path, "the stats record no compilations"));
+    }
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn import_failed(path: &Path, message: &str) -> HoldError {
+    HoldError::ImportFailed {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,123 @@
+//! Install-hooks command implementation.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+
+/// Marker line used to recognize hooks written by cargo-hold.
+pub(crate) const HOOK_MARKER: &str = "# Installed by cargo-hold (cargo hold install-hooks)";
+
+/// Git hooks that run `salvage` after the working tree changes underneath us.
+pub(crate) const HOOK_NAMES: &[&str] = &["post-checkout", "post-merge"];
+
+/// Executes the install-hooks command.
+///
+/// Writes `post-checkout` and `post-merge` hooks into the repository's hooks
+/// directory (honoring `core.hooksPath`) so that timestamps are salvaged
+/// automatically after branch switches and merges. Hooks previously written by
+/// cargo-hold are refreshed; foreign hooks are left untouched unless `force`
+/// is set.
+pub fn install_hooks(
+    context: &HoldContext,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+    force: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+
+    let repo = Repository::discover(working_dir)
+        .map_err(|_| HoldError::RepoNotFound(working_dir.to_path_buf()))?;
+    let hooks_dir = hooks_dir(&repo)?;
+
+    fs::create_dir_all(&hooks_dir).map_err(|source| HoldError::IoError {
+        path: hooks_dir.clone(),
+        source,
+    })?;
+
+    let mut installed = 0;
+    for name in HOOK_NAMES {
+        let hook_path = hooks_dir.join(name);
+
+        if let Ok(existing) = fs::read_to_string(&hook_path)
+            && !existing.contains(HOOK_MARKER)
+            && !force
+        {
+            log.warn(format!(
+                "Skipping existing {name} hook at {} (use --force to overwrite)",
+                hook_path.display()
+            ));
+            continue;
+        }
+
+        write_hook(&hook_path)?;
+        installed += 1;
+        log.verbose(
+            1,
+            format!("Installed {name} hook at {}", hook_path.display()),
+        );
+    }
+
+    log.summarize_count("hooks installed", installed);
+    log.info(format!(
+        "⚓ Installed {installed} git hook{} in {}",
+        if installed == 1 { "" } else { "s" },
+        hooks_dir.display()
+    ));
+
+    Ok(())
+}
+
+/// Resolve the hooks directory, respecting `core.hooksPath` when configured.
+fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let configured = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("core.hooksPath").ok());
+
+    match configured {
+        Some(path) if path.is_absolute() => Ok(path),
+        Some(path) => {
+            // Relative hook paths are resolved against the working tree root.
+            let workdir = repo
+                .workdir()
+                .ok_or_else(|| HoldError::RepoNotFound(repo.path().to_path_buf()))?;
+            Ok(workdir.join(path))
+        }
+        // Linked worktrees share the hooks of the main repository
+        None => Ok(repo.commondir().join("hooks")),
+    }
+}
+
+/// Render the shell script body shared by all installed hooks.
+pub(crate) fn hook_script() -> String {
+    format!(
+        "#!/bin/sh\n{HOOK_MARKER}\n# Restores file timestamps after the working tree changes so \
+         cargo only rebuilds\n# what actually changed. Failures never block git.\ncommand -v \
+         cargo >/dev/null 2>&1 || exit 0\ncargo hold salvage --quiet || true\n"
+    )
+}
+
+fn write_hook(path: &Path) -> Result<()> {
+    fs::write(path, hook_script()).map_err(|source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|source| {
+            HoldError::IoError {
+                path: path.to_path_buf(),
+                source,
+            }
+        })?;
+    }
+
+    Ok(())
+}
@@ -1,42 +1,176 @@
 //! Implementation of cargo-hold subcommands.
 
+#[cfg(feature = "cli")]
 use std::path::{Path, PathBuf};
+#[cfg(feature = "cli")]
+use std::time::Instant;
 
+#[cfg(feature = "cli")]
 use crate::cli::{Cli, Commands};
+#[cfg(feature = "cli")]
+use crate::context::HoldContext;
+#[cfg(feature = "cli")]
 use crate::error::{HoldError, Result};
+#[cfg(feature = "cli")]
+use crate::lock::{LockMode, TargetDirLock};
+#[cfg(feature = "cli")]
+use crate::logging::{self, Logger};
+#[cfg(feature = "cli")]
+use crate::reporting::error_report::ErrorReport;
+#[cfg(feature = "cli")]
+use crate::{priority, reporting, update};
 
+#[cfg(feature = "cli")]
 pub mod anchor;
+#[cfg(feature = "cli")]
+pub mod batch;
 pub mod bilge;
+#[cfg(feature = "gc")]
+pub mod clean_crate;
+#[cfg(feature = "cli")]
+pub mod compare;
+pub mod diff;
+#[cfg(feature = "gc")]
+pub mod dock;
+#[cfg(feature = "gc")]
+pub mod embark;
+#[cfg(feature = "gc")]
 pub mod gc_options;
+#[cfg(feature = "gc")]
 pub mod heave;
+#[cfg(feature = "cli")]
+pub mod import_from;
+#[cfg(feature = "git")]
+pub mod install_hooks;
+#[cfg(feature = "gc")]
+pub mod pin;
+#[cfg(feature = "cli")]
+pub mod plan;
+#[cfg(feature = "cli")]
+pub mod plugin;
+#[cfg(feature = "cli")]
+pub mod report;
+#[cfg(feature = "gc")]
+pub mod report_build;
 pub mod salvage;
+pub mod schema;
+pub mod seal;
+pub mod self_update;
 pub mod stow;
+#[cfg(feature = "gc")]
+pub mod thaw;
+pub mod verify;
+#[cfg(feature = "cli")]
 pub mod voyage;
 
-use anchor::anchor;
+#[cfg(feature = "cli")]
+use anchor::anchor_with_args;
+#[cfg(feature = "cli")]
+use batch::Batch;
+#[cfg(feature = "cli")]
 use bilge::bilge;
+#[cfg(feature = "cli")]
+use clean_crate::clean_crate;
+#[cfg(feature = "cli")]
+use compare::compare;
+#[cfg(feature = "cli")]
+use diff::diff;
+#[cfg(feature = "cli")]
+use dock::dock;
+#[cfg(feature = "cli")]
+use embark::embark;
+#[cfg(feature = "cli")]
 use heave::Heave;
+#[cfg(feature = "cli")]
+use import_from::import_from;
+#[cfg(feature = "cli")]
+use install_hooks::install_hooks;
+#[cfg(feature = "cli")]
+use pin::{pin, unpin};
+#[cfg(feature = "cli")]
+use plan::plan;
+#[cfg(feature = "cli")]
+use plugin::Plugin;
+#[cfg(feature = "cli")]
+use report::report;
+#[cfg(feature = "cli")]
+use report_build::report_build;
+#[cfg(feature = "cli")]
 use salvage::salvage;
+#[cfg(feature = "cli")]
+use schema::schema;
+#[cfg(feature = "cli")]
+use seal::seal;
+#[cfg(feature = "cli")]
+use self_update::self_update;
+#[cfg(feature = "cli")]
 use stow::stow;
+#[cfg(feature = "cli")]
+use thaw::thaw;
+#[cfg(feature = "cli")]
+use verify::verify;
+#[cfg(feature = "cli")]
 use voyage::Voyage;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "cli"))]
 mod tests;
 
 /// Execute commands based on the parsed CLI arguments.
+#[cfg(feature = "cli")]
 pub fn execute(cli: &Cli) -> Result<()> {
     execute_with_dir(cli, None)
 }
 
 /// Execute commands with an explicit working directory.
+///
+/// With `--error-report`, a failure is also written as a JSON report.
+#[cfg(feature = "cli")]
 pub fn execute_with_dir(cli: &Cli, working_dir: Option<&Path>) -> Result<()> {
-    let quiet = cli.global_opts().quiet();
+    let result = run_command(cli, working_dir);
+    if let Err(error) = &result
+        && let Some(path) = cli.global_opts().error_report()
+    {
+        let working_dir = working_dir
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let report = ErrorReport {
+            command: cli.command().name(),
+            error,
+            working_dir: &working_dir,
+            target_dir: &cli.global_opts().get_target_dir(),
+            metadata_path: &cli.global_opts().get_metadata_path(),
+            vcs: cli.global_opts().vcs().parse().unwrap_or_default(),
+        };
+        if let Err(err) = report.write(path) {
+            Logger::new(0, cli.global_opts().quiet()).warn(format!(
+                "Could not write the error report {}: {err}",
+                path.display()
+            ));
+        }
+    }
+    result
+}
+
+/// Run the command in `working_dir`, the current directory by default.
+#[cfg(feature = "cli")]
+fn run_command(cli: &Cli, working_dir: Option<&Path>) -> Result<()> {
+    let started = Instant::now();
+    let summary_only = cli.global_opts().summary_only();
+    let quiet = cli.global_opts().quiet() || summary_only;
     let verbose = if quiet {
         0
     } else {
         cli.global_opts().verbose()
     };
 
+    // Before any worker thread starts, so that they all inherit the priority
+    if cli.global_opts().background()
+        && let Err(e) = priority::enter_background()
+    {
+        Logger::new(verbose, quiet).warn(format!("Could not lower the priority: {e}"));
+    }
+
     let current_dir = if let Some(dir) = working_dir {
         dir.to_path_buf()
     } else {
@@ -46,27 +180,84 @@ pub fn execute_with_dir(cli: &Cli, working_dir: Option<&Path>) -> Result<()> {
         })?
     };
 
+    if cli.global_opts().offline() {
+        ensure_local_paths(cli)?;
+    }
+
     let metadata_path = cli.global_opts().get_metadata_path();
     let target_dir = cli.global_opts().get_target_dir();
+    let build_dir = cli.global_opts().get_build_dir();
 
-    match cli.command() {
-        Commands::Anchor => anchor(&metadata_path, verbose, quiet, &current_dir),
-        Commands::Salvage => salvage(&metadata_path, verbose, quiet, &current_dir),
-        Commands::Stow => stow(&metadata_path, verbose, quiet, &current_dir),
-        Commands::Bilge => bilge(&metadata_path, verbose, quiet),
+    if let Commands::External(args) = cli.command() {
+        return Plugin {
+            args,
+            target_dir: &target_dir,
+            metadata_path: &metadata_path,
+            global_opts: cli.global_opts(),
+            verbose,
+            quiet,
+            working_dir: &current_dir,
+        }
+        .run();
+    }
+
+    let context = HoldContext::new(cli.global_opts(), &current_dir)?;
+    let log = context.logger(verbose, quiet);
+    reporting::begin(cli.command().name(), &log);
+
+    let result = match cli.command() {
+        Commands::Anchor { anchor } => anchor_with_args(
+            &context,
+            &metadata_path,
+            &target_dir,
+            verbose,
+            quiet,
+            &current_dir,
+            anchor,
+        )
+        .map(|_| ()),
+        Commands::Salvage => TargetDirLock::acquire(&target_dir, LockMode::Shared, &log)
+            .and_then(|_lock| salvage(&context, &metadata_path, verbose, quiet, &current_dir)),
+        Commands::Stow => stow(&context, &metadata_path, verbose, quiet, &current_dir),
+        Commands::Bilge => bilge(&context, &metadata_path, verbose, quiet),
+        Commands::Diff { depth } => diff(
+            &context,
+            &metadata_path,
+            *depth,
+            verbose,
+            quiet,
+            &current_dir,
+        ),
+        Commands::Compare { old, new, json } => compare(
+            &context,
+            &current_dir.join(old),
+            &current_dir.join(new),
+            *json,
+            verbose,
+            quiet,
+        ),
         Commands::Heave {
             gc,
             auto_max_target_size,
             dry_run,
             debug,
             age_threshold_days,
+            only,
+            simulate,
+            suggest_only,
+            policies,
         } => Heave::builder()
+            .context(&context)
             .target_dir(&target_dir)
-            .max_target_size(gc.max_target_size())
+            .gc_args(gc)
+            .build_dir(build_dir.as_deref())
             .auto_max_target_size(*auto_max_target_size)
             .dry_run(*dry_run)
             .debug(*debug)
-            .preserve_cargo_binaries(gc.preserve_cargo_binaries())
+            .only(only)
+            .simulate(*simulate)
+            .suggest_only(*suggest_only)
+            .policies(policies)
             .age_threshold_days(*age_threshold_days)
             .verbose(verbose)
             .metadata_path(&metadata_path)
@@ -74,24 +265,314 @@ pub fn execute_with_dir(cli: &Cli, working_dir: Option<&Path>) -> Result<()> {
             .build()?
             .heave(),
         Commands::Voyage {
+            anchor,
             gc,
             gc_dry_run,
             gc_debug,
             gc_age_threshold_days,
             gc_auto_max_target_size,
+            no_anchor,
+            no_gc,
         } => Voyage::builder()
+            .context(&context)
             .metadata_path(&metadata_path)
             .target_dir(&target_dir)
-            .max_target_size(gc.max_target_size())
+            .gc_args(gc)
+            .build_dir(build_dir.as_deref())
             .gc_dry_run(*gc_dry_run)
             .gc_debug(*gc_debug)
-            .preserve_cargo_binaries(gc.preserve_cargo_binaries())
             .gc_age_threshold_days(*gc_age_threshold_days)
             .gc_auto_max_target_size(*gc_auto_max_target_size)
             .verbose(verbose)
             .quiet(quiet)
             .working_dir(&current_dir)
+            .anchor_args(anchor)
+            .no_anchor(*no_anchor)
+            .no_gc(*no_gc)
             .build()?
-            .run(),
+            .run()
+            .map(|_| ()),
+        Commands::Batch {
+            manifest,
+            command,
+            jobs,
+            report,
+            anchor,
+            gc,
+            gc_dry_run,
+            gc_debug,
+            gc_age_threshold_days,
+            gc_auto_max_target_size,
+        } => Batch {
+            context: &context,
+            manifest,
+            voyage: command == "voyage",
+            jobs: *jobs,
+            report: report.as_deref(),
+            build_dir: cli.global_opts().build_dir(),
+            anchor,
+            gc,
+            gc_dry_run: *gc_dry_run,
+            gc_debug: *gc_debug,
+            gc_age_threshold_days: *gc_age_threshold_days,
+            gc_auto_max_target_size: *gc_auto_max_target_size,
+            verbose,
+            quiet,
+            working_dir: &current_dir,
+        }
+        .run(),
+        Commands::Embark {
+            gc,
+            gc_dry_run,
+            gc_debug,
+            gc_age_threshold_days,
+            gc_auto_max_target_size,
+        } => {
+            let heave = Heave::builder()
+                .context(&context)
+                .target_dir(&target_dir)
+                .gc_args(gc)
+                .build_dir(build_dir.as_deref())
+                .auto_max_target_size(*gc_auto_max_target_size)
+                .dry_run(*gc_dry_run)
+                .debug(*gc_debug)
+                .age_threshold_days(*gc_age_threshold_days)
+                .verbose(verbose)
+                .metadata_path(&metadata_path)
+                .quiet(quiet)
+                .build()?;
+            embark(
+                &context,
+                &metadata_path,
+                &target_dir,
+                verbose,
+                quiet,
+                &current_dir,
+                heave,
+            )
+        }
+        Commands::Dock => dock(
+            &context,
+            &metadata_path,
+            &target_dir,
+            verbose,
+            quiet,
+            &current_dir,
+        ),
+        Commands::ReportBuild {
+            status,
+            duration,
+            timings,
+        } => report_build(
+            &context,
+            &metadata_path,
+            &target_dir,
+            status,
+            duration.as_deref(),
+            timings.as_deref(),
+            verbose,
+            quiet,
+        ),
+        Commands::Plan { output, epoch } => {
+            let epoch = epoch
+                .or_else(|| std::env::var("SOURCE_DATE_EPOCH").ok()?.parse().ok())
+                .unwrap_or(1);
+            plan(
+                &context,
+                &metadata_path,
+                output,
+                epoch,
+                verbose,
+                quiet,
+                &current_dir,
+            )
+        }
+        Commands::CleanCrate { name, dry_run } => clean_crate(
+            &context,
+            &target_dir,
+            build_dir.as_deref(),
+            name,
+            *dry_run,
+            verbose,
+            quiet,
+        ),
+        Commands::Pin { patterns } => pin(&context, &metadata_path, patterns, verbose, quiet),
+        Commands::Unpin { patterns } => unpin(&context, &metadata_path, patterns, verbose, quiet),
+        Commands::ImportFrom { source, path } => import_from(
+            &context,
+            &metadata_path,
+            source,
+            &current_dir.join(path),
+            verbose,
+            quiet,
+        ),
+        Commands::Thaw => thaw(&context, &target_dir, verbose, quiet),
+        Commands::Seal => seal(&context, &target_dir, verbose, quiet),
+        Commands::Verify { full, sample } => {
+            verify(&context, &target_dir, *full, *sample, verbose, quiet)
+        }
+        Commands::Report { age_histogram } => {
+            report(&context, &target_dir, *age_histogram, verbose, quiet)
+        }
+        Commands::InstallHooks { force } => {
+            install_hooks(&context, verbose, quiet, &current_dir, *force)
+        }
+        Commands::SelfUpdate { check } => self_update(&context, *check, verbose, quiet),
+        Commands::Schema => schema(),
+        Commands::External(_) => unreachable!("plugins run before the command is set up"),
+    };
+
+    let warnings = context.warnings_emitted();
+    let summary = logging::Summary::take(
+        &context,
+        cli.command().name(),
+        started.elapsed(),
+        result.is_err(),
+    );
+    if summary_only {
+        println!("{summary}");
+    }
+    reporting::report(&summary, &log);
+    if let Some(path) = cli.global_opts().metrics_report()
+        && let Err(err) = reporting::gitlab::append_metrics_report(path, &summary)
+    {
+        log.warn(format!(
+            "Could not write the metrics report {}: {err}",
+            path.display()
+        ));
+    }
+    result?;
+
+    if update::check_enabled()
+        && !cli.global_opts().offline()
+        && !matches!(cli.command(), Commands::SelfUpdate { .. })
+    {
+        update::notify_if_outdated(&log);
+    }
+
+    if cli.global_opts().deny_warnings() && warnings > 0 {
+        return Err(HoldError::WarningsDenied(warnings));
+    }
+
+    Ok(())
+}
+
+/// Fail if any location given to the command is a remote URL.
+///
+/// Used in offline mode, which guarantees that cargo-hold never touches the
+/// network.
+#[cfg(feature = "cli")]
+fn ensure_local_paths(cli: &Cli) -> Result<()> {
+    let global = cli.global_opts();
+    let mut paths = vec![("--target-dir", global.target_dir())];
+    if let Some(path) = global.build_dir() {
+        paths.push(("--build-dir", path));
+    }
+    if let Some(path) = global.metadata_path() {
+        paths.push(("--metadata-path", path));
+    }
+    if let Some(path) = global.metrics_report() {
+        paths.push(("--metrics-report", path));
+    }
+    if let Some(path) = global.error_report() {
+        paths.push(("--error-report", path));
+    }
+    if let Some(path) = global.dedup_store() {
+        paths.push(("--dedup-store", path));
+    }
+    if let Some(path) = global.discovery_cache() {
+        paths.push(("--discovery-cache", path));
+    }
+    if let Some(path) = global.config() {
+        paths.push(("--config", path));
+    }
+    if let Some(path) = global.salvage_report() {
+        paths.push(("--salvage-report", path));
+    }
+
+    if let Commands::Heave { gc, .. }
+    | Commands::Voyage { gc, .. }
+    | Commands::Embark { gc, .. }
+    | Commands::Batch { gc, .. } = cli.command()
+    {
+        let options = [
+            ("--cargo-home", gc.cargo_home()),
+            ("--removal-list", gc.removal_list()),
+            ("--gc-audit-log", gc.gc_audit_log()),
+            ("--preserve-units-from", gc.preserve_units_from()),
+        ];
+        paths.extend(
+            options
+                .into_iter()
+                .filter_map(|(option, path)| Some((option, path?))),
+        );
+        paths.extend(
+            gc.lockfiles()
+                .iter()
+                .map(|path| ("--lockfile", path.as_path())),
+        );
+    }
+
+    if let Commands::Anchor { anchor }
+    | Commands::Voyage { anchor, .. }
+    | Commands::Batch { anchor, .. } = cli.command()
+        && let Some(path) = anchor.rebuild_report()
+    {
+        paths.push(("--rebuild-report", path));
+    }
+
+    if let Commands::Batch {
+        manifest, report, ..
+    } = cli.command()
+    {
+        paths.push(("--manifest", manifest));
+        if let Some(path) = report {
+            paths.push(("--report", path));
+        }
+    }
+
+    if let Commands::Plan { output, .. } = cli.command() {
+        paths.push(("--output", output));
+    }
+
+    if let Commands::Compare { old, new, .. } = cli.command() {
+        paths.extend([("OLD", old.as_path()), ("NEW", new.as_path())]);
+    }
+
+    if let Commands::ImportFrom { path, .. } = cli.command() {
+        paths.push(("PATH", path));
     }
+
+    if let Commands::ReportBuild {
+        timings: Some(path),
+        ..
+    } = cli.command()
+    {
+        paths.push(("--timings", path));
+    }
+
+    if let Commands::SelfUpdate { .. } = cli.command() {
+        paths.push(("self-update", Path::new(update::RELEASES_URL)));
+    }
+
+    match paths.into_iter().find(|(_, path)| is_remote(path)) {
+        Some((option, path)) => Err(HoldError::RemotePathOffline {
+            option: option.to_string(),
+            path: path.to_path_buf(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether a path is a URL such as `s3://bucket/key` or `https://host/path`
+#[cfg(feature = "cli")]
+fn is_remote(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
 }
@@ -0,0 +1,74 @@
+//! Pin and unpin command implementations.
+
+use std::path::Path;
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::gc::validate_pin_pattern;
+use crate::metadata::{load_metadata, save_metadata};
+
+/// Executes the pin command.
+///
+/// Records crate name patterns whose artifacts garbage collection never
+/// evicts. Without patterns, prints the pinned patterns one per line.
+pub fn pin(
+    context: &HoldContext,
+    metadata_path: &Path,
+    patterns: &[String],
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    let mut metadata = load_metadata(context, metadata_path)?;
+
+    if patterns.is_empty() {
+        if metadata.pinned_crates.is_empty() {
+            log.info("No crates are pinned");
+        }
+        for pattern in &metadata.pinned_crates {
+            println!("{pattern}");
+        }
+        log.summarize_count("pinned", metadata.pinned_crates.len());
+        return Ok(());
+    }
+
+    for pattern in patterns {
+        validate_pin_pattern(pattern)?;
+    }
+    for pattern in patterns {
+        if metadata.pin(pattern) {
+            log.info(format!("📌 Pinned {pattern}"));
+        } else {
+            log.verbose(1, format!("{pattern} is already pinned"));
+        }
+    }
+
+    log.summarize_count("pinned", metadata.pinned_crates.len());
+    save_metadata(context, &metadata, metadata_path)
+}
+
+/// Executes the unpin command.
+///
+/// Removes patterns recorded by [`pin`], letting garbage collection evict the
+/// matching artifacts again.
+pub fn unpin(
+    context: &HoldContext,
+    metadata_path: &Path,
+    patterns: &[String],
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    let mut metadata = load_metadata(context, metadata_path)?;
+
+    for pattern in patterns {
+        if metadata.unpin(pattern) {
+            log.info(format!("Unpinned {pattern}"));
+        } else {
+            log.warn(format!("{pattern} is not pinned"));
+        }
+    }
+
+    log.summarize_count("pinned", metadata.pinned_crates.len());
+    save_metadata(context, &metadata, metadata_path)
+}
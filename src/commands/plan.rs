@@ -0,0 +1,284 @@
+//! Plan command implementation.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::context::HoldContext;
+use crate::discovery::{TrackedFiles, discover_tracked_files};
+use crate::error::{HoldError, Result};
+use crate::hashing::{Salt, get_file_mtime_nanos, hash_tracked_file};
+use crate::metadata::save_metadata;
+use crate::state::{FileState, StateMetadata};
+use crate::timestamp::set_file_mtime;
+
+/// Files besides the manifests and lockfiles that decide how dependencies
+/// are built
+const BUILD_CONFIG_FILES: [&str; 4] = [
+    "rust-toolchain",
+    "rust-toolchain.toml",
+    ".cargo/config",
+    ".cargo/config.toml",
+];
+
+/// Contents of a stubbed library target
+const LIB_STUB: &str = "";
+
+/// Contents of every other stubbed target, including build scripts
+const MAIN_STUB: &str = "fn main() {}\n";
+
+/// Directories whose `*.rs` files and `*/main.rs` files cargo discovers as
+/// targets
+const TARGET_DIRS: [&str; 4] = ["src/bin", "tests", "benches", "examples"];
+
+/// Target tables of a manifest whose `path` is stubbed like a binary
+const TARGET_TABLES: [&str; 4] = ["bin", "test", "bench", "example"];
+
+/// A file of the recipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecipeFile {
+    /// Copied from the repository as is
+    Copy,
+    /// A target root replaced by stub contents
+    Stub(&'static str),
+}
+
+/// Executes the plan command.
+///
+/// Writes the recipe of a dependency build to `output`: every tracked
+/// manifest, lockfile, and toolchain or cargo configuration file, and a stub
+/// for every target root, all with the modification time `epoch` (seconds
+/// since the Unix epoch), so the recipe is identical whenever the manifests
+/// are. The recipe's metadata, at the same place relative to the recipe as
+/// `metadata_path` is to the repository, records these files, so anchoring
+/// the full sources on top of it keeps the timestamps of unchanged
+/// manifests and gives every real source a newer one.
+pub fn plan(
+    context: &HoldContext,
+    metadata_path: &Path,
+    output: &Path,
+    epoch: u64,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Planning dependency recipe...");
+
+    let is_empty = match fs::read_dir(output) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => true,
+        Err(source) => {
+            return Err(HoldError::IoError {
+                path: output.to_path_buf(),
+                source,
+            });
+        }
+    };
+    if !is_empty {
+        return Err(HoldError::PlanFailed {
+            path: output.to_path_buf(),
+            message: "the output directory is not empty".to_string(),
+        });
+    }
+
+    let TrackedFiles {
+        repo_root,
+        files: tracked_files,
+        lfs,
+        origin,
+        ..
+    } = discover_tracked_files(context, working_dir)?;
+    let salt = Salt::for_repo(context.hash_salt.as_deref(), origin.as_deref());
+    let recipe = recipe_files(&repo_root, &tracked_files)?;
+
+    let mtime = UNIX_EPOCH + Duration::from_secs(epoch);
+    let mut metadata = StateMetadata::new();
+    metadata.salt_id = salt.as_ref().map(Salt::id);
+    for (path, file) in &recipe {
+        let dest = output.join(path);
+        let written = match dest.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|()| match file {
+            RecipeFile::Copy => fs::copy(repo_root.join(path), &dest).map(drop),
+            RecipeFile::Stub(contents) => fs::write(&dest, contents),
+        });
+        written.map_err(|source| HoldError::IoError {
+            path: dest.clone(),
+            source,
+        })?;
+        set_file_mtime(&dest, mtime)?;
+
+        let is_lfs = *file == RecipeFile::Copy && lfs.contains(path);
+        let (size, hash) = hash_tracked_file(context, &dest, is_lfs, salt.as_ref())?;
+        log.verbose(2, format!("  {}", path.display()));
+        metadata.upsert(FileState {
+            path: path.clone(),
+            size,
+            hash,
+            mtime_nanos: get_file_mtime_nanos(&dest)?,
+        })?;
+    }
+    metadata.update_dir_hashes();
+
+    let recipe_metadata_path = output.join(recipe_relative_path(metadata_path, &repo_root));
+    save_metadata(context, &metadata, &recipe_metadata_path)?;
+
+    let stubs = recipe
+        .values()
+        .filter(|file| matches!(file, RecipeFile::Stub(_)))
+        .count();
+    log.summarize_count("files", recipe.len() - stubs);
+    log.summarize_count("stubs", stubs);
+    log.info(format!(
+        "📋 Planned {} file{} and {} stub target{} in {}",
+        recipe.len() - stubs,
+        if recipe.len() - stubs == 1 { "" } else { "s" },
+        stubs,
+        if stubs == 1 { "" } else { "s" },
+        output.display()
+    ));
+
+    Ok(())
+}
+
+/// The files of the recipe for the tracked files of a repository, by path
+/// relative to the repository root
+fn recipe_files(
+    repo_root: &Path,
+    tracked_files: &[PathBuf],
+) -> Result<BTreeMap<PathBuf, RecipeFile>> {
+    let tracked: HashSet<&Path> = tracked_files.iter().map(PathBuf::as_path).collect();
+    let mut recipe = BTreeMap::new();
+    let mut package_dirs = HashSet::new();
+
+    for path in tracked_files {
+        let is_build_file = BUILD_CONFIG_FILES.iter().any(|name| path.ends_with(name))
+            || path.file_name().is_some_and(|name| name == "Cargo.lock");
+        if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+            recipe.insert(path.clone(), RecipeFile::Copy);
+            let package_dir = path.parent().unwrap_or(Path::new(""));
+            if declared_targets(repo_root, path, &tracked, &mut recipe)? {
+                package_dirs.insert(package_dir.to_path_buf());
+            }
+        } else if is_build_file {
+            recipe.insert(path.clone(), RecipeFile::Copy);
+        }
+    }
+
+    // Targets cargo discovers by their location belong to the nearest package
+    for path in tracked_files {
+        let Some(package_dir) = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| package_dirs.contains(*dir))
+        else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix(package_dir) else {
+            continue;
+        };
+        if let Some(stub) = discovered_stub(relative) {
+            recipe.entry(path.clone()).or_insert(RecipeFile::Stub(stub));
+        }
+    }
+
+    Ok(recipe)
+}
+
+/// Add stubs for the tracked targets a manifest declares by path. Returns
+/// whether the manifest declares a package, rather than only a workspace.
+fn declared_targets(
+    repo_root: &Path,
+    manifest: &Path,
+    tracked: &HashSet<&Path>,
+    recipe: &mut BTreeMap<PathBuf, RecipeFile>,
+) -> Result<bool> {
+    let full_path = repo_root.join(manifest);
+    let contents = fs::read_to_string(&full_path).map_err(|source| HoldError::IoError {
+        path: full_path.clone(),
+        source,
+    })?;
+    let table: toml::Table =
+        contents
+            .parse()
+            .map_err(|err: toml::de::Error| HoldError::PlanFailed {
+                path: full_path.clone(),
+                message: err.message().to_string(),
+            })?;
+    let Some(package) = table.get("package").and_then(toml::Value::as_table) else {
+        return Ok(false);
+    };
+
+    let package_dir = manifest.parent().unwrap_or(Path::new(""));
+    let mut declared = Vec::new();
+    if let Some(build) = package.get("build").and_then(toml::Value::as_str) {
+        declared.push((build, MAIN_STUB));
+    }
+    if let Some(path) = table
+        .get("lib")
+        .and_then(|lib| lib.get("path"))
+        .and_then(toml::Value::as_str)
+    {
+        declared.push((path, LIB_STUB));
+    }
+    for kind in TARGET_TABLES {
+        let targets = table.get(kind).and_then(toml::Value::as_array);
+        for target in targets.into_iter().flatten() {
+            if let Some(path) = target.get("path").and_then(toml::Value::as_str) {
+                declared.push((path, MAIN_STUB));
+            }
+        }
+    }
+
+    for (path, stub) in declared {
+        // Normalized like the tracked paths, so `./src/cli.rs` matches
+        let path: PathBuf = package_dir
+            .join(path)
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        if tracked.contains(path.as_path()) {
+            recipe.insert(path, RecipeFile::Stub(stub));
+        }
+    }
+
+    Ok(true)
+}
+
+/// The stub of a file at `path` in a package, if cargo discovers it as a
+/// target root
+fn discovered_stub(path: &Path) -> Option<&'static str> {
+    if path == Path::new("src/lib.rs") {
+        return Some(LIB_STUB);
+    }
+    if path == Path::new("src/main.rs") || path == Path::new("build.rs") {
+        return Some(MAIN_STUB);
+    }
+    let is_target = TARGET_DIRS.iter().any(|dir| {
+        path.strip_prefix(dir).is_ok_and(|rest| {
+            let mut components = rest.components();
+            match (components.next(), components.next(), components.next()) {
+                (Some(file), None, None) => {
+                    Path::new(&file).extension().is_some_and(|ext| ext == "rs")
+                }
+                (Some(_), Some(file), None) => file.as_os_str() == "main.rs",
+                _ => false,
+            }
+        })
+    });
+    is_target.then_some(MAIN_STUB)
+}
+
+/// Where `path` goes in the recipe: its place relative to the repository
+/// root, or the default place in the target directory outside of it
+fn recipe_relative_path(path: &Path, repo_root: &Path) -> PathBuf {
+    std::path::absolute(path)
+        .ok()
+        .and_then(|path| path.strip_prefix(repo_root).ok().map(Path::to_path_buf))
+        .filter(|path| !path.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("target/cargo-hold.metadata"))
+}
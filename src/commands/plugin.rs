@@ -0,0 +1,127 @@
+//! External subcommands.
+//!
+//! Like cargo with `cargo-<name>`, cargo-hold runs an executable named
+//! `cargo-hold-<name>` found on `PATH` for a command `name` it does not know,
+//! passing it the remaining arguments. The global options are resolved first
+//! and handed to the plugin in the environment variables cargo-hold reads
+//! them from, so a plugin running `cargo hold` itself works on the same
+//! target directory and metadata:
+//!
+//! - `CARGO_HOLD`: path of the running cargo-hold executable
+//! - `CARGO_HOLD_TARGET_DIR`: the target directory
+//! - `CARGO_HOLD_METADATA_PATH`: the metadata file
+//! - `CARGO_HOLD_VERBOSITY`: the verbosity level, `0` when quiet
+//! - `CARGO_HOLD_QUIET`: `true` when output is silenced
+//! - `CARGO_HOLD_OFFLINE`: `true` with `--offline`
+//! - `CARGO_HOLD_CONFIG`: the configuration file, when given
+//!
+//! Plugins report their own output; no hooks run and no summary is recorded
+//! around them.
+
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::GlobalOpts;
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// Prefix of the executables providing external subcommands
+pub const PLUGIN_PREFIX: &str = "cargo-hold-";
+
+/// Context passed to a plugin.
+pub struct Plugin<'a> {
+    /// The command name followed by its arguments
+    pub args: &'a [OsString],
+    /// Resolved target directory
+    pub target_dir: &'a Path,
+    /// Resolved metadata file
+    pub metadata_path: &'a Path,
+    /// Global options, for the ones passed through as they are
+    pub global_opts: &'a GlobalOpts,
+    /// Verbosity level
+    pub verbose: u8,
+    /// Suppress all output
+    pub quiet: bool,
+    /// Working directory of the plugin
+    pub working_dir: &'a Path,
+}
+
+impl Plugin<'_> {
+    /// Runs the plugin found on `PATH`.
+    pub fn run(&self) -> Result<()> {
+        self.run_from(std::env::var_os("PATH").as_deref())
+    }
+
+    /// Runs the plugin found in the directories of `search_path`, a list
+    /// formatted like `PATH`.
+    pub fn run_from(&self, search_path: Option<&OsStr>) -> Result<()> {
+        let log = Logger::new(self.verbose, self.quiet);
+        let Some((name, args)) = self.args.split_first() else {
+            return Err(HoldError::UnknownCommand {
+                name: String::new(),
+            });
+        };
+        let name = name.to_string_lossy().into_owned();
+        let Some(program) = find_plugin(&name, search_path) else {
+            return Err(HoldError::UnknownCommand { name });
+        };
+        log.verbose(1, format!("Running plugin {}", program.display()));
+
+        let failed = |message: String| HoldError::PluginFailed {
+            name: name.clone(),
+            message,
+        };
+        let mut command = Command::new(&program);
+        command
+            .args(args)
+            .current_dir(self.working_dir)
+            .env("CARGO_HOLD_TARGET_DIR", self.target_dir)
+            .env("CARGO_HOLD_METADATA_PATH", self.metadata_path)
+            .env("CARGO_HOLD_VERBOSITY", self.verbose.to_string())
+            .env("CARGO_HOLD_QUIET", self.quiet.to_string())
+            .env("CARGO_HOLD_OFFLINE", self.global_opts.offline().to_string());
+        if let Ok(exe) = std::env::current_exe() {
+            command.env("CARGO_HOLD", exe);
+        }
+        if let Some(config) = self.global_opts.config() {
+            command.env("CARGO_HOLD_CONFIG", config);
+        }
+        let status = command
+            .status()
+            .map_err(|e| failed(format!("Failed to start {}: {e}", program.display())))?;
+        if !status.success() {
+            return Err(failed(format!(
+                "{} exited with {status}",
+                program.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Executable providing the external subcommand `name` in the directories of
+/// `search_path`, the first one found winning
+pub fn find_plugin(name: &str, search_path: Option<&OsStr>) -> Option<PathBuf> {
+    // A name is a single path component, never one reaching elsewhere
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    let file_name = format!("{PLUGIN_PREFIX}{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(search_path?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,231 @@
+//! Report command implementation.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::gc::{self, CrateArtifact, SizeMode, SizeUnits, format_age};
+
+/// Lower bounds of the age histogram's buckets, in days; each bucket ends
+/// where the next begins, and the last one is open
+const AGE_BUCKET_DAYS: [u64; 8] = [0, 1, 2, 3, 7, 14, 30, 60];
+
+/// Percentiles of the age distribution reported
+const PERCENTILES: [u64; 5] = [50, 75, 90, 95, 99];
+
+/// Width of the histogram's bars at the largest bucket
+const BAR_WIDTH: u64 = 30;
+
+/// Executes the report command.
+///
+/// Prints the selected reports on the target directory. With
+/// `age_histogram`, that is how its crate artifact groups are distributed by
+/// age: the number and size of the groups per age bucket, how much an age
+/// threshold at the start of each bucket would remove, and percentiles of
+/// the age weighted by size.
+pub fn report(
+    context: &HoldContext,
+    target_dir: &Path,
+    age_histogram: bool,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+
+    if age_histogram {
+        log.verbose(
+            1,
+            format!("Scanning artifacts in {}...", target_dir.display()),
+        );
+        let mut artifacts = Vec::new();
+        for profile_dir in gc::find_profile_directories(target_dir)? {
+            artifacts.extend(gc::collect_crate_artifacts(
+                &profile_dir,
+                SizeMode::default(),
+            )?);
+        }
+        let ages = AgeHistogram::new(&artifacts, SystemTime::now());
+        log.summarize_count("crates", ages.crates);
+        log.summarize_size("size", ages.size);
+        if ages.crates == 0 {
+            log.info(format!("No crate artifacts in {}", target_dir.display()));
+        } else {
+            for line in ages.render(log.units()) {
+                println!("{line}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Crate artifact groups bucketed by age
+struct AgeHistogram {
+    /// Number and size of the groups per bucket of [`AGE_BUCKET_DAYS`]
+    buckets: Vec<(usize, u64)>,
+    /// Age and size of every group, youngest first
+    ages: Vec<(Duration, u64)>,
+    crates: usize,
+    size: u64,
+}
+
+impl AgeHistogram {
+    fn new(artifacts: &[CrateArtifact], now: SystemTime) -> Self {
+        let mut ages: Vec<(Duration, u64)> = artifacts
+            .iter()
+            .map(|artifact| {
+                let age = now
+                    .duration_since(artifact.newest_mtime())
+                    .unwrap_or_default();
+                (age, artifact.total_size())
+            })
+            .collect();
+        ages.sort();
+
+        let mut buckets = vec![(0, 0); AGE_BUCKET_DAYS.len()];
+        for &(age, size) in &ages {
+            let days = age.as_secs() / (24 * 60 * 60);
+            let bucket = AGE_BUCKET_DAYS
+                .iter()
+                .rposition(|&start| days >= start)
+                .unwrap_or(0);
+            buckets[bucket].0 += 1;
+            buckets[bucket].1 += size;
+        }
+
+        Self {
+            crates: ages.len(),
+            size: ages.iter().map(|&(_, size)| size).sum(),
+            buckets,
+            ages,
+        }
+    }
+
+    /// Youngest age below which `percentile` percent of the bytes are
+    fn percentile(&self, percentile: u64) -> Duration {
+        let wanted = (u128::from(self.size) * u128::from(percentile)).div_ceil(100);
+        let mut seen = 0u128;
+        for &(age, size) in &self.ages {
+            seen += u128::from(size);
+            if seen >= wanted {
+                return age;
+            }
+        }
+        self.ages.last().map_or(Duration::ZERO, |&(age, _)| age)
+    }
+
+    /// The histogram, a line per bucket, followed by the percentiles, with
+    /// sizes in `units`
+    fn render(&self, units: SizeUnits) -> Vec<String> {
+        let largest = self
+            .buckets
+            .iter()
+            .map(|&(_, size)| size)
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![format!(
+            "{:<9} {:>7} {:>11} {:>16}",
+            "Age", "Crates", "Size", "Removed at start"
+        )];
+        // What an age threshold at the start of a bucket removes: that
+        // bucket and every older one
+        let mut older = self.size;
+        for (index, &(crates, size)) in self.buckets.iter().enumerate() {
+            let start = AGE_BUCKET_DAYS[index];
+            let label = match AGE_BUCKET_DAYS.get(index + 1) {
+                Some(end) if start == 0 => format!("< {end}d"),
+                Some(end) => format!("{start}d-{end}d"),
+                None => format!(">= {start}d"),
+            };
+            let bar = if largest == 0 {
+                0
+            } else {
+                (u128::from(size) * u128::from(BAR_WIDTH)).div_ceil(u128::from(largest)) as usize
+            };
+            lines.push(
+                format!(
+                    "{label:<9} {crates:>7} {:>11} {:>16}  {}",
+                    units.format(size),
+                    units.format(older),
+                    "#".repeat(bar)
+                )
+                .trim_end()
+                .to_string(),
+            );
+            older -= size;
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "Age percentiles by size: {}",
+            PERCENTILES
+                .iter()
+                .map(|&p| format!("p{p} {}", format_age(self.percentile(p))))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if let Some(&(oldest, _)) = self.ages.last() {
+            lines.push(format!(
+                "Oldest: {} ({} crates, {} in total)",
+                format_age(oldest),
+                self.crates,
+                units.format(self.size)
+            ));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn artifact(now: SystemTime, age: Duration, total_size: u64) -> CrateArtifact {
+        CrateArtifact {
+            name: "serde".to_string(),
+            hash: "1a2b3c4d5e6f7a8b".to_string(),
+            artifacts: Vec::new(),
+            total_size,
+            newest_mtime: now - age,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
+        }
+    }
+
+    #[test]
+    fn test_age_histogram_buckets_and_percentiles() {
+        let now = SystemTime::now();
+        let artifacts = [
+            artifact(now, DAY / 2, 100),
+            artifact(now, DAY * 5, 300),
+            artifact(now, DAY * 5, 100),
+            artifact(now, DAY * 90, 500),
+        ];
+        let ages = AgeHistogram::new(&artifacts, now);
+        assert_eq!(ages.crates, 4);
+        assert_eq!(ages.size, 1000);
+        assert_eq!(ages.buckets[0], (1, 100));
+        assert_eq!(ages.buckets[3], (2, 400));
+        assert_eq!(ages.buckets[7], (1, 500));
+
+        // Weighted by size: half of the bytes are at most 5 days old
+        assert_eq!(ages.percentile(50), DAY * 5);
+        assert_eq!(ages.percentile(51), DAY * 90);
+        assert_eq!(ages.percentile(10), DAY / 2);
+
+        let lines = ages.render(SizeUnits::Binary);
+        assert!(lines[1].starts_with("< 1d"));
+        assert!(lines[4].starts_with("3d-7d"));
+        assert!(lines[8].starts_with(">= 60d"));
+        assert!(lines.last().unwrap().starts_with("Oldest: 90d"));
+    }
+
+    #[test]
+    fn test_age_histogram_of_nothing() {
+        let ages = AgeHistogram::new(&[], SystemTime::now());
+        assert_eq!(ages.crates, 0);
+        assert_eq!(ages.percentile(90), Duration::ZERO);
+    }
+}
@@ -0,0 +1,157 @@
+//! Report-build command implementation.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::gc;
+use crate::lock::{LockMode, TargetDirLock};
+use crate::logging::Logger;
+use crate::metadata::{load_metadata, save_metadata};
+use crate::state::{BuildRecord, StateMetadata};
+
+/// Outcome of a build passed to `report-build`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStatus {
+    /// The build succeeded
+    Success,
+    /// The build failed
+    Failure,
+}
+
+impl FromStr for BuildStatus {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "success" => Ok(Self::Success),
+            "failure" => Ok(Self::Failure),
+            _ => Err(HoldError::InvalidBuildStatus(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BuildStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => f.write_str("success"),
+            Self::Failure => f.write_str("failure"),
+        }
+    }
+}
+
+/// Executes the report-build command.
+///
+/// Records the outcome and duration of the build that just finished in the
+/// GC metrics. For a successful build, where it started in every profile
+/// directory is recorded as well, like `dock` does; while the builds after
+/// it fail, GC keeps preserving its artifacts. The durations of successful
+/// builds size the auto-cap headroom by what a rebuild costs.
+///
+/// With `timings`, cargo's JSON messages of the build, how long each crate
+/// took to build replaces what was recorded for it, so that GC evicts the
+/// crates that are cheap to rebuild first.
+#[allow(clippy::too_many_arguments)]
+pub fn report_build(
+    context: &HoldContext,
+    metadata_path: &Path,
+    target_dir: &Path,
+    status: &str,
+    duration: Option<&str>,
+    timings: Option<&Path>,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    let status: BuildStatus = status.parse()?;
+    let duration = duration.map(gc::parse_duration).transpose()?;
+    let build_costs = timings
+        .map(|path| {
+            let contents = std::fs::read_to_string(path).map_err(|source| HoldError::IoError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            gc::BuildCosts::parse_timings(&contents)
+        })
+        .transpose()?;
+
+    let _lock = TargetDirLock::acquire(target_dir, LockMode::Shared, &log)?;
+    let mut metadata = load_metadata(context, metadata_path)?;
+    let profile_start_mtime_nanos = match status {
+        BuildStatus::Success => record_build_starts(&mut metadata, target_dir, &log)?,
+        BuildStatus::Failure => Default::default(),
+    };
+    metadata.gc_metrics.record_build(BuildRecord {
+        reported_nanos: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_nanos(),
+        success: status == BuildStatus::Success,
+        duration_secs: duration.map(|duration| duration.as_secs()),
+        profile_start_mtime_nanos,
+    });
+    if let Some(build_costs) = &build_costs {
+        metadata.build_costs.extend(build_costs.clone());
+        log.summarize_count("crates timed", build_costs.len());
+        log.verbose(
+            1,
+            format!("Recorded the build time of {} crates", build_costs.len()),
+        );
+    }
+    save_metadata(context, &metadata, metadata_path)?;
+
+    log.summarize("build", status);
+    if let Some(duration) = duration {
+        log.summarize("build time", gc::format_duration(duration));
+    }
+    log.info(match duration {
+        Some(duration) => format!(
+            "📋 Recorded a {status} build ({})",
+            gc::format_duration(duration)
+        ),
+        None => format!("📋 Recorded a {status} build"),
+    });
+
+    Ok(())
+}
+
+/// Record where the build that just finished started in every profile
+/// directory, returning the recorded timestamps by profile.
+///
+/// The build produced the artifacts newer than the last GC, which ran right
+/// before it, or than the previously recorded build when no GC ran; the
+/// oldest of them marks its start.
+pub(crate) fn record_build_starts(
+    metadata: &mut StateMetadata,
+    target_dir: &Path,
+    log: &Logger,
+) -> Result<HashMap<String, u128>> {
+    let mut starts = HashMap::new();
+    for (profile, mtimes) in gc::profile_artifact_mtimes(target_dir)? {
+        let previous = metadata.last_gc_mtime_nanos.or_else(|| {
+            metadata
+                .gc_metrics
+                .profile_build_mtime_nanos
+                .get(&profile)
+                .and_then(|history| history.last().copied())
+        });
+        let Some(start) = mtimes
+            .into_iter()
+            .filter(|mtime| previous.is_none_or(|previous| *mtime > previous))
+            .min()
+        else {
+            log.verbose(2, format!("Nothing was built in {profile}"));
+            continue;
+        };
+        log.verbose(2, format!("Recorded the last build of {profile}"));
+        metadata
+            .gc_metrics
+            .record_profile_build_mtime_nanos(&profile, start);
+        starts.insert(profile, start);
+    }
+    Ok(starts)
+}
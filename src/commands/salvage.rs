@@ -1,30 +1,80 @@
 //! Salvage command implementation.
 
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use rayon::prelude::*;
+use serde_json::Value;
 
-use crate::discovery::discover_tracked_files;
-use crate::error::Result;
-use crate::hashing::{get_file_size, hash_file};
+use crate::context::HoldContext;
+use crate::discovery::{TrackedFiles, discover_tracked_files};
+use crate::error::{HoldError, Result};
+use crate::hashing::{
+    Salt, get_file_size_and_mtime, hash_contents, hash_lfs_pointer, hash_tracked_file,
+};
 use crate::logging::Logger;
-use crate::metadata::load_metadata;
-use crate::state::{FileState, StateMetadata};
-use crate::timestamp::{generate_monotonic_timestamp, restore_timestamps};
+use crate::metadata::{DedupStore, load_metadata_versioned};
+use crate::schema::SCHEMA_VERSION;
+use crate::state::{FileState, METADATA_VERSION, StateMetadata};
+use crate::timestamp::{generate_monotonic_timestamp, restore_timestamps, system_time_to_nanos};
+
+/// How long before the metadata was saved a file's timestamp must be for the
+/// file to be trusted unchanged by its timestamp alone: a write within the
+/// same tick of a coarse filesystem clock keeps the timestamp (git's "racy
+/// clean" files)
+const RACY_MARGIN_NANOS: u64 = 2_000_000_000;
+
+/// Names of the files whose changes make cargo recompile dependencies
+const MANIFEST_NAMES: [&str; 2] = ["Cargo.toml", "Cargo.lock"];
 
 /// Executes the salvage command.
 ///
 /// Restores timestamps based on metadata content, assigning monotonic
 /// timestamps to new or modified files.
-pub fn salvage(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path) -> Result<()> {
-    let log = Logger::new(verbose, quiet);
+pub fn salvage(
+    context: &HoldContext,
+    metadata_path: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<()> {
+    salvage_files(context, metadata_path, verbose, quiet, working_dir).map(|_| ())
+}
+
+/// Files found to differ from the metadata during a salvage pass.
+#[derive(Debug, Default)]
+pub(crate) struct SalvageOutcome {
+    /// Repository root the paths below are relative to (None when the
+    /// metadata was empty and no discovery happened).
+    pub(crate) repo_root: Option<PathBuf>,
+    /// Files whose content changed since the metadata was stowed.
+    pub(crate) modified: Vec<PathBuf>,
+    /// Files that were not present in the metadata.
+    pub(crate) added: Vec<PathBuf>,
+    /// Whether the metadata records every tracked file as it is, in the
+    /// current format and with the current salt, so stowing would save it
+    /// unchanged.
+    pub(crate) up_to_date: bool,
+}
+
+/// Salvage timestamps and report which files were modified or added.
+pub(crate) fn salvage_files(
+    context: &HoldContext,
+    metadata_path: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<SalvageOutcome> {
+    let log = context.logger(verbose, quiet);
     log.verbose(1, "Salvaging timestamps from metadata...");
 
-    let metadata = load_metadata(metadata_path)?;
+    let (metadata, stored_version) = load_metadata_versioned(context, metadata_path)?;
 
     if metadata.is_empty() {
         log.verbose(1, "Metadata is empty, nothing to restore");
-        return Ok(());
+        return Ok(SalvageOutcome::default());
     }
 
     if !log.quiet() && log.level() > 0 {
@@ -38,81 +88,334 @@ pub fn salvage(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Pat
     }
 
     let new_mtime = generate_monotonic_timestamp(&metadata);
+    let clean_before = clean_before(metadata_path);
 
-    let (repo_root, tracked_files, symlink_count) = discover_tracked_files(working_dir)?;
+    let TrackedFiles {
+        repo_root,
+        files: tracked_files,
+        symlinks: symlink_count,
+        excluded,
+        conflicted,
+        conflicts_skipped,
+        lfs,
+        origin,
+    } = discover_tracked_files(context, working_dir)?;
 
-    if !log.quiet() && symlink_count > 0 {
-        eprintln!(
-            "Warning: Skipped {} symbolic link{} (timestamps not needed for symlinks)",
+    let salt = Salt::for_repo(context.hash_salt.as_deref(), origin.as_deref());
+    if metadata.salt_id != salt.as_ref().map(Salt::id) {
+        log.warn(
+            "Metadata hashes were recorded with another salt (another repository, or a different \
+             --hash-salt); ignoring them",
+        );
+        return Ok(SalvageOutcome::default());
+    }
+    let dedup = context
+        .dedup_store
+        .as_deref()
+        .map(|path| DedupStore::load(context, path, metadata.salt_id, &log));
+
+    if symlink_count > 0 {
+        log.warn(format!(
+            "Skipped {} symbolic link{} (timestamps not needed for symlinks)",
             symlink_count,
             if symlink_count == 1 { "" } else { "s" }
+        ));
+    }
+    if excluded > 0 {
+        log.verbose(
+            1,
+            format!(
+                "Skipped {excluded} tracked file(s) below the target directory or an excluded path"
+            ),
+        );
+    }
+    log_conflicts(&log, conflicted, conflicts_skipped);
+
+    // Analyzed and restored in batches so that only one batch of results is
+    // held in memory at a time
+    let mut unchanged = 0;
+    let mut inherited = 0;
+    let mut modified = Vec::new();
+    let mut added = Vec::new();
+    let mut errors = 0;
+    for batch in tracked_files.chunks(context.batch_size) {
+        let mut analysis = analyze_files(
+            context,
+            &repo_root,
+            batch,
+            &lfs,
+            &metadata,
+            salt.as_ref(),
+            clean_before,
+            &log,
         );
+        let known = match &dedup {
+            Some(store) => {
+                let (known, rest) = inherit_timestamps(
+                    context,
+                    &repo_root,
+                    analysis.added,
+                    &lfs,
+                    store,
+                    salt.as_ref(),
+                );
+                analysis.added = rest;
+                known
+            }
+            None => Vec::new(),
+        };
+        unchanged += analysis.unchanged.len() + analysis.clean;
+        inherited += known.len();
+        analysis.unchanged.extend(&known);
+        restore_timestamps(
+            &repo_root,
+            &analysis.unchanged,
+            &analysis.modified,
+            &analysis.added,
+            new_mtime,
+        )?;
+        modified.extend(analysis.modified.into_iter().map(Path::to_path_buf));
+        added.extend(analysis.added.into_iter().map(Path::to_path_buf));
+        errors += analysis.errors;
     }
 
-    let (unchanged, modified, added) =
-        analyze_files(&repo_root, &tracked_files, &metadata, verbose, quiet)?;
+    if errors > 0 {
+        log.warn(format!("Failed to analyze {errors} file(s)"));
+        if !log.quiet() && log.level() == 0 {
+            eprintln!("Run with -v for more details");
+        }
+    }
+    log.summarize_count("files", tracked_files.len());
+    log.summarize_count("modified", modified.len());
+    log.summarize_count("added", added.len());
+    if dedup.is_some() {
+        log.summarize_count("inherited", inherited);
+    }
+    log.summarize_count("errors", errors);
 
     if !log.quiet() && log.level() > 0 {
         eprintln!(
             "Found {} unchanged, {} modified, {} added files",
-            unchanged.len(),
+            unchanged,
             modified.len(),
             added.len()
         );
     }
 
-    let unchanged_refs: Vec<&FileState> = unchanged.iter().collect();
-    let modified_refs: Vec<&Path> = modified.iter().map(|p| p.as_path()).collect();
-    let added_refs: Vec<&Path> = added.iter().map(|p| p.as_path()).collect();
-
-    restore_timestamps(
-        &repo_root,
-        &unchanged_refs,
-        &modified_refs,
-        &added_refs,
-        new_mtime,
-    )?;
-
     if !log.quiet() {
         eprintln!("Timestamp restoration complete:");
         eprintln!("  Files analyzed: {}", tracked_files.len());
-        eprintln!(
-            "  Unchanged files (timestamps restored): {}",
-            unchanged.len()
-        );
+        eprintln!("  Unchanged files (timestamps restored): {unchanged}");
         eprintln!(
             "  Modified files (new timestamp applied): {}",
             modified.len()
         );
         eprintln!("  New files (new timestamp applied): {}", added.len());
+        if dedup.is_some() {
+            eprintln!("  Moved or copied files (timestamps inherited): {inherited}");
+        }
     }
 
-    Ok(())
+    let removed = removed_files(&metadata, &tracked_files, unchanged + modified.len());
+    let mut manifests: Vec<&Path> = modified
+        .iter()
+        .chain(&added)
+        .chain(&removed)
+        .map(PathBuf::as_path)
+        .filter(|path| is_manifest(path))
+        .collect();
+    manifests.sort_unstable();
+    if !manifests.is_empty() {
+        log.summarize_count("manifests changed", manifests.len());
+        log.notice(format!(
+            "Dependency manifests changed ({}); expect dependencies to be recompiled",
+            list_paths(&manifests, 3)
+        ));
+    }
+
+    if let Some(path) = &context.salvage_report {
+        let report = SalvageReport {
+            modified: &modified,
+            added: &added,
+            removed: &removed,
+            manifests: &manifests,
+        };
+        report.write(path)?;
+        log.verbose(1, format!("Wrote salvage report to {}", path.display()));
+    }
+
+    let up_to_date = stored_version == METADATA_VERSION
+        && modified.is_empty()
+        && added.is_empty()
+        && removed.is_empty()
+        && errors == 0;
+
+    Ok(SalvageOutcome {
+        repo_root: Some(repo_root),
+        modified,
+        added,
+        up_to_date,
+    })
 }
 
-/// Analyze files to categorize them as unchanged, modified, or added.
-fn analyze_files(
-    repo_root: &Path,
-    tracked_files: &[PathBuf],
+/// Recorded files that are no longer tracked, sorted, given the number of
+/// tracked files found in the metadata
+fn removed_files(
     metadata: &StateMetadata,
-    verbose: u8,
-    quiet: bool,
-) -> Result<(Vec<FileState>, Vec<PathBuf>, Vec<PathBuf>)> {
-    let log = Logger::new(verbose, quiet);
-    let mut unchanged = Vec::new();
-    let mut modified = Vec::new();
-    let mut added = Vec::new();
+    tracked_files: &[PathBuf],
+    recorded_tracked: usize,
+) -> Vec<PathBuf> {
+    if metadata.len() <= recorded_tracked {
+        return Vec::new();
+    }
+    let tracked: HashSet<&Path> = tracked_files.iter().map(PathBuf::as_path).collect();
+    let mut removed: Vec<PathBuf> = metadata
+        .files
+        .keys()
+        .map(PathBuf::from)
+        .filter(|path| !tracked.contains(path.as_path()))
+        .collect();
+    removed.sort_unstable();
+    removed
+}
+
+/// Whether changes to `path` make cargo recompile dependencies
+fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| MANIFEST_NAMES.contains(&name))
+}
+
+/// The first `limit` of `paths` separated by commas, followed by the number
+/// of the rest
+fn list_paths(paths: &[&Path], limit: usize) -> String {
+    let mut listed: Vec<String> = paths
+        .iter()
+        .take(limit)
+        .map(|path| path.display().to_string())
+        .collect();
+    if paths.len() > limit {
+        listed.push(format!("and {} more", paths.len() - limit));
+    }
+    listed.join(", ")
+}
+
+/// Files a salvage found changed, as written by `--salvage-report`
+pub(crate) struct SalvageReport<'a> {
+    pub(crate) modified: &'a [PathBuf],
+    pub(crate) added: &'a [PathBuf],
+    pub(crate) removed: &'a [PathBuf],
+    /// Changed `Cargo.toml` and `Cargo.lock` files, among the above
+    pub(crate) manifests: &'a [&'a Path],
+}
+
+impl SalvageReport<'_> {
+    /// The report as a JSON document
+    pub(crate) fn to_json(&self) -> Value {
+        let paths = |paths: &[PathBuf]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        };
+        let manifests: Vec<String> = self
+            .manifests
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "modified": paths(self.modified),
+            "added": paths(self.added),
+            "removed": paths(self.removed),
+            "manifests_changed": manifests,
+            "dependencies_changed": !self.manifests.is_empty(),
+        })
+    }
+
+    /// Write the report to `path`
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let io_error = |source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+        serde_json::to_writer_pretty(&mut out, &self.to_json())
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(out))
+            .and_then(|()| out.flush())
+            .map_err(io_error)
+    }
+}
 
-    let results: Vec<(PathBuf, FileCategory)> = tracked_files
+/// Timestamp before which files still holding their recorded timestamp and
+/// size are trusted unchanged, a little before the metadata at
+/// `metadata_path` was saved; `None` if the metadata has no timestamp
+pub(crate) fn clean_before(metadata_path: &Path) -> Option<u64> {
+    std::fs::metadata(metadata_path)
+        .and_then(|file| file.modified())
+        .ok()
+        .map(|saved| system_time_to_nanos(saved).saturating_sub(RACY_MARGIN_NANOS))
+}
+
+/// Files of a batch found unchanged, modified, or added, and the number of
+/// files that could not be analyzed
+#[derive(Default)]
+pub(crate) struct FileAnalysis<'a> {
+    pub(crate) unchanged: Vec<&'a FileState>,
+    /// Unchanged files that still hold their recorded timestamp, which were
+    /// neither hashed nor need restoring
+    pub(crate) clean: usize,
+    pub(crate) modified: Vec<&'a Path>,
+    pub(crate) added: Vec<&'a Path>,
+    pub(crate) errors: usize,
+}
+
+/// Analyze files to categorize them as unchanged, modified, or added, hashing
+/// them with the `salt` of the metadata.
+///
+/// Files recorded with a timestamp before `clean_before` that still hold it
+/// and their recorded size are taken as unchanged without being hashed, so
+/// subtrees untouched since the last stow cost a `stat` per file.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn analyze_files<'a>(
+    context: &HoldContext,
+    repo_root: &Path,
+    tracked_files: &'a [PathBuf],
+    lfs: &HashSet<PathBuf>,
+    metadata: &'a StateMetadata,
+    salt: Option<&Salt>,
+    clean_before: Option<u64>,
+    log: &Logger,
+) -> FileAnalysis<'a> {
+    let results: Vec<(&Path, FileCategory)> = tracked_files
         .par_iter()
         .map(|path| {
             let full_path = repo_root.join(path);
             let category = match metadata.get(path) {
-                Ok(Some(metadata_state)) => match get_file_size(&full_path) {
-                    Ok(size) if size != metadata_state.size => FileCategory::Modified,
-                    Ok(_) => match hash_file(&full_path) {
+                // Pointers are small, so there is no size to check first
+                Ok(Some(metadata_state)) if lfs.contains(path) => {
+                    match hash_lfs_pointer(&full_path, salt) {
+                        Ok((size, hash))
+                            if size != metadata_state.size || hash != metadata_state.hash =>
+                        {
+                            FileCategory::Modified
+                        }
+                        Ok(_) => FileCategory::Unchanged(metadata_state),
+                        Err(_) => FileCategory::Error,
+                    }
+                }
+                Ok(Some(metadata_state)) => match get_file_size_and_mtime(&full_path) {
+                    Ok((size, _)) if size != metadata_state.size => FileCategory::Modified,
+                    Ok((_, mtime))
+                        if mtime == metadata_state.mtime_nanos
+                            && clean_before.is_some_and(|before| mtime < before) =>
+                    {
+                        FileCategory::Clean
+                    }
+                    Ok((size, _)) => match hash_contents(context, &full_path, size, salt) {
                         Ok(hash) if hash != metadata_state.hash => FileCategory::Modified,
-                        Ok(_) => FileCategory::Unchanged(metadata_state.clone()),
+                        Ok(_) => FileCategory::Unchanged(metadata_state),
                         Err(_) => FileCategory::Error,
                     },
                     Err(_) => FileCategory::Error,
@@ -120,36 +423,88 @@ fn analyze_files(
                 Ok(None) => FileCategory::Added,
                 Err(_) => FileCategory::Error,
             };
-            (path.clone(), category)
+            (path.as_path(), category)
         })
         .collect();
 
-    let mut errors = Vec::new();
+    let mut analysis = FileAnalysis::default();
     for (path, category) in results {
         match category {
-            FileCategory::Unchanged(state) => unchanged.push(state),
-            FileCategory::Modified => modified.push(path),
-            FileCategory::Added => added.push(path),
+            FileCategory::Unchanged(state) => analysis.unchanged.push(state),
+            FileCategory::Clean => analysis.clean += 1,
+            FileCategory::Modified => analysis.modified.push(path),
+            FileCategory::Added => analysis.added.push(path),
             FileCategory::Error => {
-                errors.push(path.clone());
+                analysis.errors += 1;
                 log.verbose(2, format!("Warning: Could not analyze file {path:?}"));
             }
         }
     }
 
-    if !errors.is_empty() && !log.quiet() {
-        eprintln!("Warning: Failed to analyze {} file(s)", errors.len());
-        if log.level() == 0 {
-            eprintln!("Run with -v for more details");
+    analysis
+}
+
+/// Split `added` files into those whose contents the dedup `store` knows,
+/// as file states with the canonical timestamp of their contents, and the
+/// rest; files that cannot be hashed stay added
+pub(crate) fn inherit_timestamps<'a>(
+    context: &HoldContext,
+    repo_root: &Path,
+    added: Vec<&'a Path>,
+    lfs: &HashSet<PathBuf>,
+    store: &DedupStore,
+    salt: Option<&Salt>,
+) -> (Vec<FileState>, Vec<&'a Path>) {
+    let results: Vec<(&Path, Option<FileState>)> = added
+        .into_par_iter()
+        .map(|path| {
+            let state = hash_tracked_file(context, &repo_root.join(path), lfs.contains(path), salt)
+                .ok()
+                .and_then(|(size, hash)| {
+                    Some(FileState {
+                        path: path.to_path_buf(),
+                        size,
+                        hash,
+                        mtime_nanos: store.canonical_mtime(size, &hash)?,
+                    })
+                });
+            (path, state)
+        })
+        .collect();
+
+    let mut known = Vec::new();
+    let mut rest = Vec::new();
+    for (path, state) in results {
+        match state {
+            Some(state) => known.push(state),
+            None => rest.push(path),
         }
     }
-
-    Ok((unchanged, modified, added))
+    (known, rest)
 }
 
-enum FileCategory {
-    Unchanged(FileState),
+enum FileCategory<'a> {
+    Unchanged(&'a FileState),
+    Clean,
     Modified,
     Added,
     Error,
 }
+
+/// Report paths with unresolved merge conflicts, whose working tree versions
+/// are used
+pub(crate) fn log_conflicts(log: &Logger, conflicted: usize, skipped: usize) {
+    if conflicted == 0 {
+        return;
+    }
+    log.info(format!(
+        "{conflicted} path{} with merge conflicts: using the working tree version",
+        if conflicted == 1 { "" } else { "s" }
+    ));
+    if skipped > 0 {
+        log.info(format!(
+            "Skipped {skipped} conflicted path{} missing from the working tree",
+            if skipped == 1 { "" } else { "s" }
+        ));
+    }
+}
@@ -0,0 +1,22 @@
+//! Schema command implementation.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::error::{HoldError, Result};
+use crate::schema::SCHEMA;
+
+/// Executes the schema command.
+///
+/// Prints the JSON Schema of the JSON documents cargo-hold writes (the
+/// removal list, the seal manifest, and the input of `--gc-policy-cmd`) on
+/// stdout.
+pub fn schema() -> Result<()> {
+    io::stdout()
+        .lock()
+        .write_all(SCHEMA.as_bytes())
+        .map_err(|source| HoldError::IoError {
+            path: PathBuf::from("-"),
+            source,
+        })
+}
@@ -0,0 +1,32 @@
+//! Seal command implementation.
+
+use std::path::Path;
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::lock::{LockMode, TargetDirLock};
+use crate::seal::Seal;
+
+/// Executes the seal command.
+///
+/// Records the size and hash of every file in the target directory, so
+/// `verify` can detect a damaged copy after the directory is restored from a
+/// cache. Run it as the last step before the cache is saved.
+pub fn seal(context: &HoldContext, target_dir: &Path, verbose: u8, quiet: bool) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Sealing target directory...");
+
+    let _lock = TargetDirLock::acquire(target_dir, LockMode::Shared, &log)?;
+    let seal = Seal::create(target_dir)?;
+    seal.save(target_dir)?;
+    log.summarize_count("files sealed", seal.len());
+
+    log.info(format!(
+        "🔏 Sealed {} file{} ({})",
+        seal.len(),
+        if seal.len() == 1 { "" } else { "s" },
+        log.format_size(seal.total_size())
+    ));
+
+    Ok(())
+}
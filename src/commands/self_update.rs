@@ -0,0 +1,40 @@
+//! Self-update command implementation.
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::update::{self, Version};
+
+/// Executes the self-update command.
+///
+/// Looks up the latest release and, unless only checking, replaces the
+/// running binary with that release's binary for this platform after
+/// verifying the archive against the checksums published with it.
+pub fn self_update(context: &HoldContext, check: bool, verbose: u8, quiet: bool) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Looking up the latest cargo-hold release...");
+
+    let current = Version::current();
+    let latest = update::latest_version(60)?;
+    if latest <= current {
+        log.summarize("version", current);
+        log.info(format!("✅ cargo-hold {current} is up to date"));
+        return Ok(());
+    }
+
+    if check {
+        log.summarize("available", latest);
+        log.info(format!(
+            "📦 cargo-hold {latest} is available (running {current})"
+        ));
+        return Ok(());
+    }
+
+    let executable = std::env::current_exe().map_err(|err| {
+        HoldError::SelfUpdateFailed(format!("could not locate the running binary: {err}"))
+    })?;
+    update::install(latest, &executable, &log)?;
+    log.summarize("version", latest);
+    log.info(format!("⬆️ Updated cargo-hold {current} to {latest}"));
+
+    Ok(())
+}
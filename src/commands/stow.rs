@@ -1,24 +1,45 @@
 //! Stow command implementation.
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use rayon::prelude::*;
 
-use crate::discovery::discover_tracked_files;
+use crate::commands::salvage::log_conflicts;
+use crate::context::HoldContext;
+use crate::discovery::{TrackedFiles, discover_tracked_files};
 use crate::error::{HoldError, Result};
-use crate::hashing::{get_file_mtime_nanos, get_file_size, hash_file};
+use crate::hashing::{LargeFilePolicy, Salt, get_file_mtime_nanos, hash_tracked_file};
+use crate::hooks::{self, Hook};
 use crate::logging::Logger;
-use crate::metadata::{load_metadata, save_metadata};
-use crate::state::{FileState, StateMetadata};
+use crate::metadata::{DedupStore, load_metadata, save_metadata};
+use crate::state::{FileState, StateMetadata, changed_files};
 
 /// Executes the stow command.
 ///
-/// Scans all Git-tracked files, hashes them, and persists the state.
-pub fn stow(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path) -> Result<()> {
-    let log = Logger::new(verbose, quiet);
+/// Scans all Git-tracked files, hashes them, and persists the state, then
+/// runs the `post-stow` hook.
+pub fn stow(
+    context: &HoldContext,
+    metadata_path: &Path,
+    verbose: u8,
+    quiet: bool,
+    working_dir: &Path,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
     log.verbose(1, "Stowing files in cargo hold...");
 
-    let (repo_root, tracked_files, symlink_count) = discover_tracked_files(working_dir)?;
+    let TrackedFiles {
+        repo_root,
+        files: tracked_files,
+        symlinks: symlink_count,
+        excluded,
+        conflicted,
+        conflicts_skipped,
+        lfs,
+        origin,
+    } = discover_tracked_files(context, working_dir)?;
+    let salt = Salt::for_repo(context.hash_salt.as_deref(), origin.as_deref());
 
     log.verbose(1, format!("Found {} tracked files", tracked_files.len()));
 
@@ -29,41 +50,59 @@ pub fn stow(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path)
             if symlink_count == 1 { "" } else { "s" }
         );
     }
+    if !log.quiet() && excluded > 0 {
+        eprintln!(
+            "Note: Skipped {} tracked file{} below the target directory or an excluded path (not \
+             stored in metadata)",
+            excluded,
+            if excluded == 1 { "" } else { "s" }
+        );
+    }
+    log_conflicts(&log, conflicted, conflicts_skipped);
 
-    let file_states: Vec<Result<FileState>> = tracked_files
-        .par_iter()
-        .map(|path| build_file_state(&repo_root, path))
-        .collect();
-
+    // Hashed in batches so that only one batch of results is held in memory
+    // next to the new metadata
     let mut new_metadata = StateMetadata::new();
+    new_metadata.salt_id = salt.as_ref().map(Salt::id);
     let mut errors = 0;
-    for result in file_states {
-        match result {
-            Ok(state) => {
-                if let Err(e) = new_metadata.upsert(state) {
+    for batch in tracked_files.chunks(context.batch_size) {
+        let file_states: Vec<Result<FileState>> = batch
+            .par_iter()
+            .map(|path| {
+                build_file_state(context, &repo_root, path, lfs.contains(path), salt.as_ref())
+            })
+            .collect();
+
+        for result in file_states {
+            match result {
+                Ok(state) => {
+                    if let Err(e) = new_metadata.upsert(state) {
+                        errors += 1;
+                        if !log.quiet() {
+                            eprintln!("Warning: Failed to add file to metadata: {e:?}");
+                        }
+                    }
+                }
+                Err(e) => {
                     errors += 1;
                     if !log.quiet() {
-                        eprintln!("Warning: Failed to add file to metadata: {e:?}");
+                        eprintln!("Warning: Failed to analyze file: {e:?}");
                     }
                 }
             }
-            Err(e) => {
-                errors += 1;
-                if !log.quiet() {
-                    eprintln!("Warning: Failed to analyze file: {e:?}");
-                }
-            }
         }
     }
 
-    if errors > 0 && !log.quiet() {
-        eprintln!("Warning: Failed to analyze {errors} file(s)");
-        if log.level() == 0 {
+    log.summarize_count("files", tracked_files.len());
+    log.summarize_count("errors", errors);
+    if errors > 0 {
+        log.warn(format!("Failed to analyze {errors} file(s)"));
+        if !log.quiet() && log.level() == 0 {
             eprintln!("Run with -v for more details");
         }
     }
 
-    let existing_metadata = match load_metadata(metadata_path) {
+    let existing_metadata = match load_metadata(context, metadata_path) {
         Ok(metadata) => Some(metadata),
         Err(HoldError::DeserializationError { .. }) => None,
         Err(err) => return Err(err),
@@ -71,18 +110,53 @@ pub fn stow(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path)
 
     if let Some(existing) = existing_metadata.as_ref() {
         new_metadata.gc_metrics = existing.gc_metrics.clone();
+        new_metadata.last_gc_mtime_nanos = existing.last_gc_mtime_nanos;
+        new_metadata.recent_gc_mtime_nanos = existing.recent_gc_mtime_nanos.clone();
+        new_metadata.pinned_crates = existing.pinned_crates.clone();
+        new_metadata.build_costs = existing.build_costs.clone();
     }
 
-    new_metadata.last_gc_mtime_nanos = existing_metadata
+    let renames = match existing_metadata.as_ref() {
+        Some(existing) if existing.salt_id == new_metadata.salt_id => {
+            carry_forward_renames(context, existing, &mut new_metadata)
+        }
+        _ => Vec::new(),
+    };
+    for (from, to) in &renames {
+        log.verbose(2, format!("Renamed {} -> {}", from.display(), to.display()));
+    }
+    log.summarize_count("renamed", renames.len());
+    new_metadata.update_dir_hashes();
+    let files_changed = existing_metadata
         .as_ref()
-        .and_then(|existing| existing.last_gc_mtime_nanos);
+        .map(|existing| changed_files(existing, &new_metadata).len());
+
+    save_metadata(context, &new_metadata, metadata_path)?;
 
-    save_metadata(&new_metadata, metadata_path)?;
+    if let Some(path) = context.dedup_store.as_deref() {
+        let mut store = DedupStore::load(context, path, new_metadata.salt_id, &log);
+        store.record(context, &new_metadata);
+        store.save(context, path)?;
+        log.verbose(
+            1,
+            format!(
+                "Dedup store at {} knows {} contents",
+                path.display(),
+                store.len()
+            ),
+        );
+    }
 
     if !log.quiet() {
         eprintln!("File scan complete:");
         eprintln!("  Files tracked: {}", tracked_files.len());
         eprintln!("  Metadata entries: {}", new_metadata.len());
+        if !renames.is_empty() {
+            eprintln!(
+                "  Renamed files (timestamps carried forward): {}",
+                renames.len()
+            );
+        }
         if errors > 0 {
             eprintln!("  Files skipped: {errors} (errors)");
         }
@@ -93,13 +167,97 @@ pub fn stow(metadata_path: &Path, verbose: u8, quiet: bool, working_dir: &Path)
         }
     }
 
-    Ok(())
+    run_post_stow_hook(
+        context,
+        metadata_path,
+        files_changed.unwrap_or(new_metadata.len()),
+        files_changed.is_none_or(|count| count > 0),
+        &log,
+    )
+}
+
+/// Runs the `post-stow` hook for the metadata at `metadata_path`, with the
+/// number of files changed since the previous metadata and whether the
+/// metadata changed
+pub(crate) fn run_post_stow_hook(
+    context: &HoldContext,
+    metadata_path: &Path,
+    files_changed: usize,
+    metadata_changed: bool,
+    log: &Logger,
+) -> Result<()> {
+    hooks::run(
+        context,
+        Hook::PostStow,
+        &[
+            ("CARGO_HOLD_METADATA", metadata_path.display().to_string()),
+            ("CARGO_HOLD_FILES_CHANGED", files_changed.to_string()),
+            (
+                "CARGO_HOLD_METADATA_CHANGED",
+                u8::from(metadata_changed).to_string(),
+            ),
+        ],
+        log,
+    )
+}
+
+/// Detect files renamed since `old` was stowed, i.e. contents at a path
+/// missing from `old` that `old` recorded at a path now gone, and give them
+/// the timestamp recorded for their old path in `new`.
+///
+/// Each old path is carried forward to at most one new path, so of several
+/// copies only one counts as renamed. Files hashed in part or not at all
+/// under `--large-file-policy` are never matched. Returns the old and new
+/// paths of the renamed files.
+pub(crate) fn carry_forward_renames(
+    context: &HoldContext,
+    old: &StateMetadata,
+    new: &mut StateMetadata,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut gone: HashMap<(u64, [u8; 32]), Vec<&FileState>> = HashMap::new();
+    for (path, state) in &old.files {
+        if !new.files.contains_key(path)
+            && context.large_file_policy(state.size) == LargeFilePolicy::Full
+        {
+            gone.entry((state.size, state.hash))
+                .or_default()
+                .push(state);
+        }
+    }
+    if gone.is_empty() {
+        return Vec::new();
+    }
+    // Paired in path order, so that the same trees give the same renames
+    for states in gone.values_mut() {
+        states.sort_unstable_by(|a, b| b.path.cmp(&a.path));
+    }
+    let mut appeared: Vec<&mut FileState> = new
+        .files
+        .iter_mut()
+        .filter(|(path, _)| !old.files.contains_key(*path))
+        .map(|(_, state)| state)
+        .collect();
+    appeared.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    let mut renames = Vec::new();
+    for state in appeared {
+        if let Some(old_state) = gone.get_mut(&(state.size, state.hash)).and_then(Vec::pop) {
+            state.mtime_nanos = old_state.mtime_nanos;
+            renames.push((old_state.path.clone(), state.path.clone()));
+        }
+    }
+    renames
 }
 
-fn build_file_state(repo_root: &Path, path: &PathBuf) -> Result<FileState> {
+fn build_file_state(
+    context: &HoldContext,
+    repo_root: &Path,
+    path: &PathBuf,
+    lfs: bool,
+    salt: Option<&Salt>,
+) -> Result<FileState> {
     let full_path = repo_root.join(path);
-    let size = get_file_size(&full_path)?;
-    let hash = hash_file(&full_path)?;
+    let (size, hash) = hash_tracked_file(context, &full_path, lfs, salt)?;
     let mtime_nanos = get_file_mtime_nanos(&full_path)?;
 
     Ok(FileState {
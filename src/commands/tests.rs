@@ -1,15 +1,21 @@
+use std::collections::HashSet;
 use std::fs;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use tempfile::TempDir;
 
 use super::*;
+use crate::cli::{AnchorArgs, GcArgs, GlobalOpts};
+use crate::commands::anchor::{AnchorOutcome, anchor};
+use crate::commands::salvage::inherit_timestamps;
+use crate::gc::SizeUnits;
 use crate::gc::auto_cap::{
-    HARD_CEILING_MIN_FINALS, MAX_GROWTH_FACTOR_PER_RUN_PCT, MAX_SHRINK_FACTOR_PER_RUN_PCT,
-    MIN_HEADROOM_BYTES, suggest_max_target_size,
+    HARD_CEILING_MIN_FINALS, MAX_GROWTH_FACTOR_PER_RUN_PCT, MAX_REBUILD_COST_PCT,
+    MAX_SHRINK_FACTOR_PER_RUN_PCT, MIN_HEADROOM_BYTES, WARMUP_RUNS, suggest_max_target_size,
 };
-use crate::metadata::{load_metadata, save_metadata};
-use crate::state::{GcMetrics, METADATA_VERSION, StateMetadata};
+use crate::metadata::{self, DedupStore, load_metadata, save_metadata};
+use crate::state::{FileState, GcMetrics, METADATA_VERSION, StateMetadata};
 
 fn setup_git_repo() -> TempDir {
     let temp_dir = TempDir::new().unwrap();
@@ -33,12 +39,72 @@ fn test_stow_command() {
     let temp_dir = setup_git_repo();
     let metadata_path = temp_dir.path().join("test.metadata");
 
-    stow(&metadata_path, 0, false, temp_dir.path()).unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
     assert!(metadata_path.exists());
-    let metadata = load_metadata(&metadata_path).unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(metadata.len(), 1);
 }
 
+#[test]
+fn test_commands_run_concurrently_in_contexts_of_their_own() {
+    let excluding = setup_git_repo();
+    let signing = setup_git_repo();
+    let metadata_path = |repo: &TempDir| repo.path().join("test.metadata");
+    let options = [
+        (
+            &excluding,
+            GlobalOpts::builder()
+                .exclude("test.txt")
+                .quiet(true)
+                .build(),
+        ),
+        (
+            &signing,
+            GlobalOpts::builder()
+                .metadata_key(Some("secret"))
+                .quiet(true)
+                .build(),
+        ),
+    ];
+
+    std::thread::scope(|scope| {
+        for (repo, global) in &options {
+            scope.spawn(move || {
+                let context = HoldContext::new(global, repo.path()).unwrap();
+                stow(&context, &metadata_path(repo), 0, true, repo.path()).unwrap();
+            });
+        }
+    });
+
+    // Neither command saw the other's options
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path(&excluding))
+            .unwrap()
+            .len(),
+        0
+    );
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path(&signing))
+            .unwrap()
+            .len(),
+        1
+    );
+    let other_key = GlobalOpts::builder()
+        .metadata_key(Some("other"))
+        .quiet(true)
+        .build();
+    let other_key = HoldContext::new(&other_key, signing.path()).unwrap();
+    let reloaded = load_metadata(&other_key, &metadata_path(&signing)).unwrap();
+    assert_eq!(reloaded.len(), 0);
+}
+
 #[test]
 fn test_stow_from_subdirectory() {
     let temp_dir = setup_git_repo();
@@ -51,9 +117,9 @@ fn test_stow_from_subdirectory() {
     let metadata_path = temp_dir.path().join("test.metadata");
 
     // Run stow from subdirectory - it should find the parent git repo
-    stow(&metadata_path, 0, false, &subdir).unwrap();
+    stow(&HoldContext::default(), &metadata_path, 0, false, &subdir).unwrap();
     assert!(metadata_path.exists());
-    let metadata = load_metadata(&metadata_path).unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(metadata.len(), 1);
 }
 
@@ -68,10 +134,890 @@ fn test_salvage_from_subdirectory() {
     let metadata_path = temp_dir.path().join("test.metadata");
 
     // First stow from the root
-    stow(&metadata_path, 0, false, temp_dir.path()).unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
 
     // Now run salvage from subdirectory
-    salvage(&metadata_path, 0, false, &subdir).unwrap();
+    salvage(&HoldContext::default(), &metadata_path, 0, false, &subdir).unwrap();
+}
+
+#[test]
+fn test_moved_files_inherit_timestamps_from_dedup_store() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let store_path = temp_dir.path().join("test.dedup");
+    let log = Logger::new(0, true);
+    let original = SystemTime::now() - Duration::from_secs(3600);
+    fs::OpenOptions::new()
+        .write(true)
+        .open(temp_dir.path().join("test.txt"))
+        .unwrap()
+        .set_modified(original)
+        .unwrap();
+
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    let mut store = DedupStore::load(&HoldContext::default(), &store_path, metadata.salt_id, &log);
+    store.record(&HoldContext::default(), &metadata);
+
+    fs::write(temp_dir.path().join("moved.txt"), "test content").unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "new content").unwrap();
+    let added = [Path::new("moved.txt"), Path::new("new.txt")];
+    let (known, rest) = inherit_timestamps(
+        &HoldContext::default(),
+        temp_dir.path(),
+        added.to_vec(),
+        &HashSet::new(),
+        &store,
+        None,
+    );
+
+    assert_eq!(rest, [Path::new("new.txt")]);
+    assert_eq!(known.len(), 1);
+    assert_eq!(known[0].path, Path::new("moved.txt"));
+    assert_eq!(
+        known[0].mtime_nanos,
+        metadata
+            .get(Path::new("test.txt"))
+            .unwrap()
+            .unwrap()
+            .mtime_nanos
+    );
+}
+
+#[test]
+fn test_stow_carries_timestamps_of_renamed_files_forward() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let original = SystemTime::now() - Duration::from_secs(3600);
+    fs::OpenOptions::new()
+        .write(true)
+        .open(temp_dir.path().join("test.txt"))
+        .unwrap()
+        .set_modified(original)
+        .unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let before = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+
+    // git mv test.txt renamed.txt, which gives the file a new timestamp
+    fs::rename(
+        temp_dir.path().join("test.txt"),
+        temp_dir.path().join("renamed.txt"),
+    )
+    .unwrap();
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("test.txt")).unwrap();
+    index.add_path(Path::new("renamed.txt")).unwrap();
+    index.write().unwrap();
+    fs::OpenOptions::new()
+        .write(true)
+        .open(temp_dir.path().join("renamed.txt"))
+        .unwrap()
+        .set_modified(SystemTime::now())
+        .unwrap();
+
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let after = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(
+        after
+            .get(Path::new("renamed.txt"))
+            .unwrap()
+            .unwrap()
+            .mtime_nanos,
+        before
+            .get(Path::new("test.txt"))
+            .unwrap()
+            .unwrap()
+            .mtime_nanos
+    );
+}
+
+#[test]
+fn test_renames_pair_each_old_path_with_one_new_path() {
+    let state = |path: &str, contents: &str, mtime_nanos| FileState {
+        path: PathBuf::from(path),
+        size: contents.len() as u64,
+        hash: *blake3::hash(contents.as_bytes()).as_bytes(),
+        mtime_nanos,
+    };
+    let mut old = StateMetadata::new();
+    old.upsert(state("a.rs", "same", 1)).unwrap();
+    old.upsert(state("kept.rs", "kept", 2)).unwrap();
+    old.upsert(state("gone.rs", "gone", 3)).unwrap();
+    let mut new = StateMetadata::new();
+    new.upsert(state("b.rs", "same", 10)).unwrap();
+    new.upsert(state("c.rs", "same", 10)).unwrap();
+    new.upsert(state("kept.rs", "kept", 10)).unwrap();
+    new.upsert(state("other.rs", "other", 10)).unwrap();
+
+    let renames = stow::carry_forward_renames(&HoldContext::default(), &old, &mut new);
+
+    assert_eq!(renames, [(PathBuf::from("a.rs"), PathBuf::from("b.rs"))]);
+    let mtime = |path: &str| new.get(Path::new(path)).unwrap().unwrap().mtime_nanos;
+    assert_eq!(mtime("b.rs"), 1);
+    assert_eq!(mtime("c.rs"), 10, "A copy is not a rename");
+    assert_eq!(mtime("kept.rs"), 10);
+    assert_eq!(mtime("other.rs"), 10);
+}
+
+#[test]
+fn test_salvage_reports_changed_dependency_manifests() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let report_path = temp_dir.path().join("salvage.json");
+    fs::create_dir(temp_dir.path().join("app")).unwrap();
+    fs::write(temp_dir.path().join("app/Cargo.toml"), "[package]\n").unwrap();
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("app/Cargo.toml")).unwrap();
+    index.write().unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    fs::write(temp_dir.path().join("Cargo.lock"), "version = 4\n").unwrap();
+    index.add_path(Path::new("Cargo.lock")).unwrap();
+    index.remove_path(Path::new("app/Cargo.toml")).unwrap();
+    index.write().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+
+    let context = HoldContext {
+        salvage_report: Some(report_path.clone()),
+        ..HoldContext::default()
+    };
+    salvage(&context, &metadata_path, 0, true, temp_dir.path()).unwrap();
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&fs::read(&report_path).unwrap()).unwrap();
+    assert_eq!(report["modified"], serde_json::json!(["test.txt"]));
+    assert_eq!(report["added"], serde_json::json!(["Cargo.lock"]));
+    assert_eq!(report["removed"], serde_json::json!(["app/Cargo.toml"]));
+    assert_eq!(
+        report["manifests_changed"],
+        serde_json::json!(["Cargo.lock", "app/Cargo.toml"])
+    );
+    assert_eq!(report["dependencies_changed"], true);
+}
+
+#[test]
+fn test_salvage_trusts_files_that_kept_their_timestamp() {
+    let temp_dir = setup_git_repo();
+    let path = PathBuf::from("test.txt");
+    let full_path = temp_dir.path().join(&path);
+    let (size, mtime_nanos) = crate::hashing::get_file_size_and_mtime(&full_path).unwrap();
+    let mut metadata = StateMetadata::new();
+    // Recorded with a hash the contents no longer have, which only hashing
+    // notices
+    metadata
+        .upsert(FileState {
+            path: path.clone(),
+            size,
+            hash: [0; 32],
+            mtime_nanos,
+        })
+        .unwrap();
+    let tracked = [path];
+    let log = Logger::new(0, true);
+    let analyze = |clean_before| {
+        salvage::analyze_files(
+            &HoldContext::default(),
+            temp_dir.path(),
+            &tracked,
+            &HashSet::new(),
+            &metadata,
+            None,
+            clean_before,
+            &log,
+        )
+    };
+
+    let analysis = analyze(Some(mtime_nanos + 1));
+    assert_eq!(analysis.clean, 1);
+    assert!(analysis.modified.is_empty());
+
+    // Too close to when the metadata was saved to be trusted
+    let analysis = analyze(Some(mtime_nanos));
+    assert_eq!(analysis.clean, 0);
+    assert_eq!(analysis.modified.len(), 1);
+    assert_eq!(analyze(None).modified.len(), 1);
+}
+
+#[test]
+fn test_diff_lists_changes_since_stow() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    fs::write(temp_dir.path().join("src/lib.rs"), "").unwrap();
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("src/lib.rs")).unwrap();
+    index.write().unwrap();
+
+    let log = Logger::new(0, true);
+    let changes = diff::changes_since_stow(
+        &HoldContext::default(),
+        &metadata_path,
+        &log,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let listed: Vec<String> = changes
+        .iter()
+        .map(|change| format!("{} {}", change.kind, change.path.display()))
+        .collect();
+    assert_eq!(listed, ["A src/lib.rs", "M test.txt"]);
+
+    let dirs = diff::changed_dirs(&changes, 1);
+    assert_eq!(dirs[Path::new(".")], ["1 modified"]);
+    assert_eq!(dirs[Path::new("src")], ["1 added"]);
+}
+
+#[test]
+fn test_diff_compares_two_stowed_states() {
+    let temp_dir = setup_git_repo();
+    let before_path = temp_dir.path().join("before.metadata");
+    let after_path = temp_dir.path().join("after.metadata");
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    fs::create_dir_all(temp_dir.path().join("src/unchanged")).unwrap();
+    for file in ["src/old.rs", "src/unchanged/lib.rs"] {
+        fs::write(temp_dir.path().join(file), file).unwrap();
+        index.add_path(Path::new(file)).unwrap();
+    }
+    index.write().unwrap();
+    stow(
+        &HoldContext::default(),
+        &before_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+    fs::write(temp_dir.path().join("src/new.rs"), "").unwrap();
+    index.add_path(Path::new("src/new.rs")).unwrap();
+    index.remove_path(Path::new("src/old.rs")).unwrap();
+    index.write().unwrap();
+    stow(
+        &HoldContext::default(),
+        &after_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    let before = load_metadata(&HoldContext::default(), &before_path).unwrap();
+    let after = load_metadata(&HoldContext::default(), &after_path).unwrap();
+    // The rollups were saved with the file states
+    assert_eq!(
+        before.dir_hashes["src/unchanged"],
+        after.dir_hashes["src/unchanged"]
+    );
+    assert_ne!(before.dir_hashes["src"], after.dir_hashes["src"]);
+    let listed: Vec<String> = crate::state::changed_files(&before, &after)
+        .iter()
+        .map(|change| format!("{} {}", change.kind, change.path.display()))
+        .collect();
+    assert_eq!(listed, ["A src/new.rs", "D src/old.rs", "M test.txt"]);
+
+    // The diff command finds the same changes since the first stow
+    let context = HoldContext::default();
+    diff::diff(&context, &before_path, None, 0, true, temp_dir.path()).unwrap();
+    let summary = crate::logging::Summary::take(&context, "diff", Duration::ZERO, false);
+    let counts: Vec<(&str, &str)> = summary
+        .items
+        .iter()
+        .map(|item| (item.label, item.value.as_str()))
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            ("errors", "0"),
+            ("added", "1"),
+            ("modified", "1"),
+            ("removed", "1")
+        ]
+    );
+}
+
+#[test]
+fn test_compare_reports_file_and_metric_differences() {
+    let temp_dir = TempDir::new().unwrap();
+    let state = |path: &str, size, hash, mtime_nanos| FileState {
+        path: PathBuf::from(path),
+        size,
+        hash: [hash; 32],
+        mtime_nanos,
+    };
+    let mut old = StateMetadata::new();
+    let mut new = StateMetadata::new();
+    for file in [
+        state("Cargo.lock", 100, 1, 10),
+        state("src/lib.rs", 20, 2, 10),
+        state("src/main.rs", 30, 3, 10),
+        state("src/old.rs", 5, 4, 10),
+    ] {
+        old.upsert(file).unwrap();
+    }
+    for file in [
+        state("Cargo.lock", 100, 1, 10),
+        state("src/lib.rs", 25, 9, 10),
+        state("src/main.rs", 30, 3, 2_500_000_010),
+        state("src/new.rs", 5, 4, 10),
+    ] {
+        new.upsert(file).unwrap();
+    }
+    old.gc_metrics.runs = 3;
+    new.gc_metrics.runs = 5;
+    new.gc_metrics.last_suggested_cap = Some(1024);
+    let old_path = temp_dir.path().join("old.metadata");
+    let new_path = temp_dir.path().join("new.metadata");
+    save_metadata(&HoldContext::default(), &old, &old_path).unwrap();
+    save_metadata(&HoldContext::default(), &new, &new_path).unwrap();
+
+    let comparison = compare::Comparison::new(
+        &metadata::read_metadata(&old_path).unwrap(),
+        &metadata::read_metadata(&new_path).unwrap(),
+    );
+    assert_eq!(comparison.added, ["src/new.rs"]);
+    assert_eq!(comparison.removed, ["src/old.rs"]);
+    assert_eq!(comparison.hash_changes(), 1);
+    assert_eq!(
+        comparison.render(SizeUnits::Binary),
+        [
+            "M src/lib.rs (hash, size 20 -> 25)",
+            "T src/main.rs (mtime +2.500s)",
+            "A src/new.rs",
+            "D src/old.rs",
+            "",
+            "GC metrics:",
+            "  runs: 3 -> 5 (+2)",
+            "  last_suggested_cap: - -> 1.0 KiB",
+        ]
+    );
+
+    let report = comparison.to_json(&old_path, &new_path);
+    assert_eq!(report["salt_changed"], false);
+    assert_eq!(report["changed"][1]["path"], "src/main.rs");
+    assert_eq!(report["changed"][1]["hash_changed"], false);
+    assert_eq!(report["gc_metrics"][0]["name"], "runs");
+    assert_eq!(report["gc_metrics"][0]["delta"], 2);
+
+    // Neither file is reset, whatever it holds
+    fs::write(&old_path, "not metadata").unwrap();
+    assert!(metadata::read_metadata(&old_path).is_err());
+    assert!(metadata::read_metadata(&temp_dir.path().join("missing")).is_err());
+    assert!(old_path.exists());
+}
+
+#[test]
+fn test_import_from_seeds_the_gc_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+    let stamp = temp_dir.path().join("sweep.timestamp");
+    let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    let millis = hour_ago.duration_since(UNIX_EPOCH).unwrap().as_millis();
+    fs::write(&stamp, format!("{millis}")).unwrap();
+
+    import_from(
+        &HoldContext::default(),
+        &metadata_path,
+        "sweep",
+        &stamp,
+        0,
+        true,
+    )
+    .unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(metadata.gc_mtime_history(), [millis * 1_000_000]);
+
+    // sccache's stats stand for the build that ended when they were saved
+    let stats = temp_dir.path().join("stats.json");
+    fs::write(&stats, r#"{"stats": {"compile_requests": 12}}"#).unwrap();
+    import_from(
+        &HoldContext::default(),
+        &metadata_path,
+        "sccache",
+        &stats,
+        0,
+        true,
+    )
+    .unwrap();
+    let history = load_metadata(&HoldContext::default(), &metadata_path)
+        .unwrap()
+        .gc_mtime_history();
+    let modified = fs::metadata(&stats).unwrap().modified().unwrap();
+    assert_eq!(
+        history,
+        [
+            millis * 1_000_000,
+            modified.duration_since(UNIX_EPOCH).unwrap().as_nanos()
+        ]
+    );
+
+    // An older build than the last recorded GC adds nothing
+    import_from(
+        &HoldContext::default(),
+        &metadata_path,
+        "sweep",
+        &stamp,
+        0,
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .gc_mtime_history(),
+        history
+    );
+
+    for (contents, source) in [
+        ("yesterday", "sweep"),
+        ("99999999999999", "sweep"),
+        (r#"{"stats": {"compile_requests": 0}}"#, "sccache"),
+        ("{}", "sccache"),
+    ] {
+        fs::write(&stats, contents).unwrap();
+        let err = import_from(
+            &HoldContext::default(),
+            &metadata_path,
+            source,
+            &stats,
+            0,
+            true,
+        )
+        .unwrap_err();
+        assert!(matches!(err, HoldError::ImportFailed { .. }), "{err:?}");
+    }
+}
+
+#[test]
+fn test_plan_writes_recipe_that_anchors_under_full_sources() {
+    let temp_dir = setup_git_repo();
+    let files = [
+        ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+        ("Cargo.lock", "version = 4\n"),
+        (
+            "crates/app/Cargo.toml",
+            "[package]\nname = \"app\"\n\n[[bin]]\nname = \"tool\"\npath = \"./tool.rs\"\n",
+        ),
+        ("crates/app/src/main.rs", "fn main() { app::run() }\n"),
+        ("crates/app/src/lib.rs", "pub fn run() {}\n"),
+        ("crates/app/src/util.rs", "pub fn util() {}\n"),
+        ("crates/app/tool.rs", "fn main() { println!(\"tool\") }\n"),
+        ("crates/app/build.rs", "fn main() { println!(\"build\") }\n"),
+        ("crates/app/tests/smoke.rs", "#[test]\nfn smoke() {}\n"),
+    ];
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    for (path, contents) in files {
+        let full_path = temp_dir.path().join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(&full_path, contents).unwrap();
+        index.add_path(Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+
+    let metadata_path = temp_dir.path().join("target/cargo-hold.metadata");
+    let output = TempDir::new().unwrap();
+    plan(
+        &HoldContext::default(),
+        &metadata_path,
+        output.path(),
+        1_000,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    let mut written = Vec::new();
+    let mut dirs = vec![output.path().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                written.push(path.strip_prefix(output.path()).unwrap().to_path_buf());
+            }
+        }
+    }
+    written.sort();
+    let expected: Vec<PathBuf> = [
+        "Cargo.lock",
+        "Cargo.toml",
+        "crates/app/Cargo.toml",
+        "crates/app/build.rs",
+        "crates/app/src/lib.rs",
+        "crates/app/src/main.rs",
+        "crates/app/tests/smoke.rs",
+        "crates/app/tool.rs",
+        "target/cargo-hold.metadata",
+    ]
+    .into_iter()
+    .map(PathBuf::from)
+    .collect();
+    assert_eq!(written, expected);
+
+    let read = |path: &str| fs::read_to_string(output.path().join(path)).unwrap();
+    assert_eq!(read("Cargo.lock"), "version = 4\n");
+    assert_eq!(read("crates/app/src/lib.rs"), "");
+    assert_eq!(read("crates/app/src/main.rs"), "fn main() {}\n");
+    assert_eq!(read("crates/app/tool.rs"), "fn main() {}\n");
+    let epoch = UNIX_EPOCH + Duration::from_secs(1_000);
+    for path in &expected[..expected.len() - 1] {
+        let mtime = fs::metadata(output.path().join(path))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(mtime, epoch, "{}", path.display());
+    }
+
+    // The full sources anchored on the recipe's metadata keep the manifests
+    // at the epoch, while every real source is newer than the stub builds
+    fs::create_dir_all(temp_dir.path().join("target")).unwrap();
+    fs::copy(
+        output.path().join("target/cargo-hold.metadata"),
+        &metadata_path,
+    )
+    .unwrap();
+    salvage(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let mtime = |path: &str| {
+        fs::metadata(temp_dir.path().join(path))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    assert_eq!(mtime("Cargo.toml"), epoch);
+    assert_eq!(mtime("crates/app/Cargo.toml"), epoch);
+    for path in ["crates/app/src/main.rs", "crates/app/src/util.rs"] {
+        assert!(mtime(path) > epoch, "{path}");
+    }
+
+    let err = plan(
+        &HoldContext::default(),
+        &metadata_path,
+        output.path(),
+        1_000,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, HoldError::PlanFailed { .. }), "{err:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_stow_hook_tells_whether_metadata_changed() {
+    use clap::Parser;
+
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let hook_log = temp_dir.path().join("hook.log");
+    let config = serde_json::json!({
+        "hooks": {
+            "post-stow": format!(
+                "echo \"$CARGO_HOLD_HOOK $CARGO_HOLD_FILES_CHANGED $CARGO_HOLD_METADATA_CHANGED\" >> '{}'",
+                hook_log.display()
+            )
+        }
+    });
+    fs::write(
+        temp_dir.path().join(crate::hooks::CONFIG_FILENAME),
+        config.to_string(),
+    )
+    .unwrap();
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--quiet",
+        "--metadata-path",
+        metadata_path.to_str().unwrap(),
+        "stow",
+    ]);
+
+    execute_with_dir(&cli, Some(temp_dir.path())).unwrap();
+    // Rewriting the same contents changes the timestamp but not the content
+    fs::write(temp_dir.path().join("test.txt"), "test content").unwrap();
+    execute_with_dir(&cli, Some(temp_dir.path())).unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+    execute_with_dir(&cli, Some(temp_dir.path())).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&hook_log).unwrap(),
+        "post-stow 1 1\npost-stow 0 0\npost-stow 1 1\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_plugin_runs_with_resolved_global_options() {
+    use std::ffi::OsString;
+    use std::os::unix::fs::PermissionsExt;
+
+    use clap::Parser;
+
+    use crate::commands::plugin::find_plugin;
+
+    let temp_dir = TempDir::new().unwrap();
+    let bin_dir = temp_dir.path().join("bin");
+    fs::create_dir(&bin_dir).unwrap();
+    let plugin_log = temp_dir.path().join("plugin.log");
+    let plugin = bin_dir.join("cargo-hold-audit");
+    fs::write(
+        &plugin,
+        format!(
+            "#!/bin/sh\necho \"$* $CARGO_HOLD_TARGET_DIR $CARGO_HOLD_METADATA_PATH \
+             $CARGO_HOLD_VERBOSITY $CARGO_HOLD_QUIET\" > '{}'\nexit \"$1\"\n",
+            plugin_log.display()
+        ),
+    )
+    .unwrap();
+    let search_path = std::env::join_paths([temp_dir.path(), &bin_dir]).unwrap();
+    assert_eq!(find_plugin("audit", Some(&search_path)), None);
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(find_plugin("audit", Some(&search_path)), Some(plugin));
+    assert_eq!(
+        find_plugin("../bin/cargo-hold-audit", Some(&search_path)),
+        None
+    );
+
+    let target_dir = temp_dir.path().join("out");
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--target-dir",
+        target_dir.to_str().unwrap(),
+        "audit",
+        "0",
+    ]);
+    let Commands::External(args) = cli.command() else {
+        panic!("Expected External command");
+    };
+    let run = |args: &[OsString]| {
+        plugin::Plugin {
+            args,
+            target_dir: &cli.global_opts().get_target_dir(),
+            metadata_path: &cli.global_opts().get_metadata_path(),
+            global_opts: cli.global_opts(),
+            verbose: 2,
+            quiet: false,
+            working_dir: temp_dir.path(),
+        }
+        .run_from(Some(&search_path))
+    };
+    run(args).unwrap();
+    assert_eq!(
+        fs::read_to_string(&plugin_log).unwrap(),
+        format!(
+            "0 {} {} 2 false\n",
+            target_dir.display(),
+            target_dir.join("cargo-hold.metadata").display()
+        )
+    );
+
+    let failing = [OsString::from("audit"), OsString::from("3")];
+    assert!(matches!(
+        run(&failing),
+        Err(HoldError::PluginFailed { ref name, .. }) if name == "audit"
+    ));
+    let unknown = [OsString::from("charter")];
+    assert!(matches!(
+        run(&unknown),
+        Err(HoldError::UnknownCommand { ref name }) if name == "charter"
+    ));
+}
+
+#[test]
+fn test_failed_command_writes_error_report() {
+    use clap::Parser;
+
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let report_path = temp_dir.path().join("error.json");
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--quiet",
+        "--error-report",
+        report_path.to_str().unwrap(),
+        "--metadata-path",
+        metadata_path.to_str().unwrap(),
+        "stow",
+    ]);
+    execute_with_dir(&cli, Some(temp_dir.path())).unwrap();
+    assert!(!report_path.exists());
+
+    let config_path = temp_dir.path().join("hold.json");
+    fs::write(&config_path, r#"{"hooks": {"pre-anchor": "exit 3"}}"#).unwrap();
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--quiet",
+        "--error-report",
+        report_path.to_str().unwrap(),
+        "--config",
+        config_path.to_str().unwrap(),
+        "--metadata-path",
+        metadata_path.to_str().unwrap(),
+        "anchor",
+    ]);
+    let err = execute_with_dir(&cli, Some(temp_dir.path())).unwrap_err();
+    let report: serde_json::Value =
+        serde_json::from_slice(&fs::read(&report_path).unwrap()).unwrap();
+    assert_eq!(report["command"], "anchor");
+    assert_eq!(report["error"]["message"], err.to_string());
+    assert_eq!(report["error"]["code"], "cargo_hold::hooks::failed");
+    assert_eq!(
+        report["context"]["repo_root"],
+        temp_dir.path().canonicalize().unwrap().to_str().unwrap()
+    );
+    assert_eq!(report["context"]["metadata_version"], METADATA_VERSION);
+}
+
+#[test]
+fn test_failing_pre_anchor_hook_aborts_anchor() {
+    use clap::Parser;
+
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let config_path = temp_dir.path().join("hold.json");
+    fs::write(&config_path, r#"{"hooks": {"pre-anchor": "exit 3"}}"#).unwrap();
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--quiet",
+        "--config",
+        config_path.to_str().unwrap(),
+        "--metadata-path",
+        metadata_path.to_str().unwrap(),
+        "anchor",
+    ]);
+
+    let err = execute_with_dir(&cli, Some(temp_dir.path())).unwrap_err();
+    assert!(matches!(err, HoldError::HookFailed { ref hook, .. } if hook == "pre-anchor"));
+    assert!(!metadata_path.exists());
+
+    // A configuration file given explicitly must exist
+    fs::remove_file(&config_path).unwrap();
+    assert!(matches!(
+        execute_with_dir(&cli, Some(temp_dir.path())),
+        Err(HoldError::IoError { .. })
+    ));
+}
+
+#[test]
+fn test_verify_passes_a_sealed_target_dir_and_fails_a_tampered_one() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    fs::create_dir_all(target_dir.join("debug/deps")).unwrap();
+    let rlib = target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib");
+    let rmeta = target_dir.join("debug/deps/libfoo-0123456789abcdef.rmeta");
+    fs::write(&rlib, "rlib").unwrap();
+    fs::write(&rmeta, "rmeta").unwrap();
+    let verify = |full, sample| {
+        let context = HoldContext::default();
+        let result = verify(&context, &target_dir, full, sample, 0, true);
+        let summary = crate::logging::Summary::take(&context, "verify", Duration::ZERO, false);
+        let counts: Vec<(&str, String)> = summary
+            .items
+            .iter()
+            .map(|item| (item.label, item.value.clone()))
+            .collect();
+        (result, counts)
+    };
+
+    // Nothing to compare against before the target directory is sealed
+    let (result, counts) = verify(true, 0);
+    result.unwrap();
+    assert!(counts.is_empty());
+
+    seal(&HoldContext::default(), &target_dir, 0, true).unwrap();
+    // The artifacts and the profile's .cargo-lock
+    let (result, counts) = verify(true, 0);
+    result.unwrap();
+    assert_eq!(
+        counts,
+        [
+            ("files checked", "3".to_string()),
+            ("hashed", "3".to_string()),
+            ("corrupted", "0".to_string())
+        ]
+    );
+
+    // Same size, other contents: only hashing notices
+    fs::write(&rlib, "RLIB").unwrap();
+    verify(false, 0).0.unwrap();
+    let (result, counts) = verify(true, 0);
+    assert!(matches!(
+        result,
+        Err(HoldError::CorruptedArtifacts {
+            corrupted: 1,
+            checked: 3,
+            ..
+        })
+    ));
+    assert_eq!(counts[2], ("corrupted", "1".to_string()));
+
+    // A missing file is caught without hashing anything
+    fs::write(&rlib, "rlib").unwrap();
+    fs::remove_file(&rmeta).unwrap();
+    let (result, counts) = verify(false, 0);
+    assert!(matches!(
+        result,
+        Err(HoldError::CorruptedArtifacts { corrupted: 1, .. })
+    ));
+    assert_eq!(counts[1], ("hashed", "0".to_string()));
 }
 
 #[test]
@@ -80,11 +1026,18 @@ fn test_bilge_command() {
     let metadata_path = temp_dir.path().join("test.metadata");
 
     // Create metadata first
-    stow(&metadata_path, 0, false, temp_dir.path()).unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
     assert!(metadata_path.exists());
 
     // Bilge it
-    bilge(&metadata_path, 0, false).unwrap();
+    bilge(&HoldContext::default(), &metadata_path, 0, false).unwrap();
     assert!(!metadata_path.exists());
 }
 
@@ -92,16 +1045,500 @@ fn test_bilge_command() {
 fn test_anchor_command() {
     let temp_dir = setup_git_repo();
     let metadata_path = temp_dir.path().join("test.metadata");
+    let target_dir = temp_dir.path().join("target");
 
     // Run anchor
-    anchor(&metadata_path, 0, false, temp_dir.path()).unwrap();
+    anchor(
+        &HoldContext::default(),
+        &metadata_path,
+        &target_dir,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
 
     // Metadata should exist
     assert!(metadata_path.exists());
-    let metadata = load_metadata(&metadata_path).unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(metadata.len(), 1);
 }
 
+#[test]
+fn test_anchor_leaves_up_to_date_metadata_alone() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let target_dir = temp_dir.path().join("target");
+    let saved_at = || fs::metadata(&metadata_path).unwrap().modified().unwrap();
+    let backdate = || {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&metadata_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+        saved_at()
+    };
+
+    let anchored = anchor(
+        &HoldContext::default(),
+        &metadata_path,
+        &target_dir,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    assert_eq!(anchored, AnchorOutcome::Stowed);
+    let backdated = backdate();
+    // Rewriting the same contents changes the timestamp but not the content
+    fs::write(temp_dir.path().join("test.txt"), "test content").unwrap();
+    let anchored = anchor(
+        &HoldContext::default(),
+        &metadata_path,
+        &target_dir,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    assert_eq!(anchored, AnchorOutcome::Unchanged);
+    assert_eq!(saved_at(), backdated);
+
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+    let anchored = anchor(
+        &HoldContext::default(),
+        &metadata_path,
+        &target_dir,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    assert_eq!(anchored, AnchorOutcome::Stowed);
+    assert_ne!(saved_at(), backdated);
+
+    // Removing a tracked file changes the metadata too
+    let backdated = backdate();
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new("test.txt")).unwrap();
+    index.write().unwrap();
+    let anchored = anchor(
+        &HoldContext::default(),
+        &metadata_path,
+        &target_dir,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    assert_eq!(anchored, AnchorOutcome::Stowed);
+    assert_ne!(saved_at(), backdated);
+    assert!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+/// A directory holding a Git repository tracking a `main.rs` for each of
+/// `names`
+fn batch_workspace(names: &[&str]) -> TempDir {
+    let workspace = TempDir::new().unwrap();
+    for name in names {
+        let repo_dir = workspace.path().join(name);
+        let repo = git2::Repository::init(&repo_dir).unwrap();
+        fs::write(repo_dir.join("main.rs"), name).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("main.rs")).unwrap();
+        index.write().unwrap();
+    }
+    workspace
+}
+
+/// Writes the fingerprint and rlib of crate `name`, last built `age_days`
+/// ago, into `profile_dir`, returning the rlib
+fn crate_artifacts(profile_dir: &Path, name: &str, age_days: u64) -> PathBuf {
+    let built = filetime::FileTime::from_system_time(
+        SystemTime::now() - Duration::from_secs(age_days * 86_400),
+    );
+    let fingerprint = profile_dir.join(format!(".fingerprint/lib{name}-0123456789abcdef"));
+    let rlib = profile_dir.join(format!("deps/lib{name}-0123456789abcdef.rlib"));
+    fs::create_dir_all(&fingerprint).unwrap();
+    fs::create_dir_all(rlib.parent().unwrap()).unwrap();
+    for file in [fingerprint.join("dep-lib"), rlib.clone()] {
+        fs::write(&file, name).unwrap();
+        filetime::set_file_mtime(&file, built).unwrap();
+    }
+    filetime::set_file_mtime(&fingerprint, built).unwrap();
+    rlib
+}
+
+#[test]
+fn test_batch_anchors_every_repository_and_reports_failures() {
+    let workspace = batch_workspace(&["api", "worker"]);
+    let manifest = workspace.path().join("repos.toml");
+    fs::write(
+        &manifest,
+        r#"
+max-total-size = "3MiB"
+
+[[repo]]
+path = "api"
+
+[[repo]]
+path = "worker"
+target-dir = "build"
+max-target-size = "1MiB"
+
+[[repo]]
+path = "missing"
+"#,
+    )
+    .unwrap();
+    let report = workspace.path().join("batch.json");
+
+    let result = Batch {
+        manifest: Path::new("repos.toml"),
+        voyage: false,
+        jobs: NonZeroUsize::new(2).unwrap(),
+        report: Some(&report),
+        build_dir: None,
+        anchor: &AnchorArgs::default(),
+        gc: &GcArgs::default(),
+        gc_dry_run: false,
+        gc_debug: false,
+        gc_age_threshold_days: 7,
+        gc_auto_max_target_size: false,
+        verbose: 0,
+        quiet: true,
+        working_dir: workspace.path(),
+        context: &HoldContext::default(),
+    }
+    .run();
+    assert!(matches!(
+        result,
+        Err(HoldError::BatchFailed {
+            failed: 1,
+            total: 3
+        })
+    ));
+
+    // The failing repository did not stop the others
+    let api = load_metadata(
+        &HoldContext::default(),
+        &workspace.path().join("api/target/cargo-hold.metadata"),
+    )
+    .unwrap();
+    assert_eq!(api.len(), 1);
+    let worker = load_metadata(
+        &HoldContext::default(),
+        &workspace.path().join("worker/build/cargo-hold.metadata"),
+    )
+    .unwrap();
+    assert_eq!(worker.len(), 1);
+
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&report).unwrap()).unwrap();
+    assert_eq!(report["command"], "anchor");
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["totals"]["files"], 2);
+    let repos = report["repositories"].as_array().unwrap();
+    let names: Vec<&str> = repos
+        .iter()
+        .map(|repo| repo["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["api", "worker", "missing"]);
+    // The budget left by worker's own cap is shared by the other two
+    assert_eq!(repos[0]["max_target_size"], 1024 * 1024);
+    assert_eq!(repos[1]["max_target_size"], 1024 * 1024);
+    assert_eq!(repos[0]["metadata"], "stowed");
+    assert_eq!(repos[2]["failed"], true);
+    assert!(repos[2]["error"].is_string());
+    assert!(repos[2].get("metadata").is_none());
+}
+
+#[test]
+fn test_batch_writes_the_removal_list_of_every_repository() {
+    let workspace = batch_workspace(&["api", "worker"]);
+    for name in ["api", "worker"] {
+        crate_artifacts(&workspace.path().join(name).join("target/debug"), name, 10);
+    }
+    fs::write(
+        workspace.path().join("repos.toml"),
+        r#"
+[[repo]]
+path = "api"
+removal-list = "removed.json"
+
+[[repo]]
+path = "worker"
+removal-list = "removed.json"
+"#,
+    )
+    .unwrap();
+    fs::create_dir(workspace.path().join("cargo-home")).unwrap();
+    let gc = GcArgs::default().with_cargo_home(workspace.path().join("cargo-home"));
+    let batch = |gc: &GcArgs| {
+        Batch {
+            context: &HoldContext::default(),
+            manifest: Path::new("repos.toml"),
+            voyage: true,
+            jobs: NonZeroUsize::new(2).unwrap(),
+            report: None,
+            build_dir: None,
+            anchor: &AnchorArgs::default(),
+            gc,
+            gc_dry_run: true,
+            gc_debug: false,
+            gc_age_threshold_days: 7,
+            gc_auto_max_target_size: false,
+            verbose: 0,
+            quiet: true,
+            working_dir: workspace.path(),
+        }
+        .run()
+    };
+    batch(&gc).unwrap();
+
+    // Each repository listed its own stale crate
+    for name in ["api", "worker"] {
+        let list: serde_json::Value = serde_json::from_slice(
+            &fs::read(workspace.path().join(name).join("removed.json")).unwrap(),
+        )
+        .unwrap();
+        let paths: Vec<&str> = list
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["path"].as_str().unwrap())
+            .collect();
+        assert!(!paths.is_empty());
+        assert!(
+            paths
+                .iter()
+                .all(|path| path.contains(&format!("lib{name}-"))),
+            "{paths:?}"
+        );
+    }
+
+    // One list for every repository would be overwritten by each of them
+    let shared = gc
+        .clone()
+        .with_removal_list(workspace.path().join("removed.json"));
+    assert!(matches!(
+        batch(&shared),
+        Err(HoldError::PerRepositoryOption { option, .. }) if option == "--removal-list"
+    ));
+    let anchor = AnchorArgs::default().with_rebuild_report(workspace.path().join("rebuilds.json"));
+    let err = Batch {
+        context: &HoldContext::default(),
+        manifest: Path::new("repos.toml"),
+        voyage: false,
+        jobs: NonZeroUsize::new(1).unwrap(),
+        report: None,
+        build_dir: None,
+        anchor: &anchor,
+        gc: &gc,
+        gc_dry_run: true,
+        gc_debug: false,
+        gc_age_threshold_days: 7,
+        gc_auto_max_target_size: false,
+        verbose: 0,
+        quiet: true,
+        working_dir: workspace.path(),
+    }
+    .run()
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        HoldError::PerRepositoryOption { option, .. } if option == "--rebuild-report"
+    ));
+}
+
+#[test]
+fn test_batch_collects_the_build_dir_of_every_repository() {
+    let workspace = batch_workspace(&["api", "worker"]);
+    // --build-dir in api, the manifest's own build-dir in worker
+    let api_build = workspace.path().join("api/scratch/debug");
+    let worker_build = workspace.path().join("worker/build-dir/debug");
+    let api_rlib = crate_artifacts(&api_build, "api", 1);
+    let worker_rlib = crate_artifacts(&worker_build, "worker", 1);
+    let kept = crate_artifacts(&workspace.path().join("api/target/debug"), "kept", 1);
+    fs::write(
+        workspace.path().join("repos.toml"),
+        r#"
+[[repo]]
+path = "api"
+
+[[repo]]
+path = "worker"
+build-dir = "build-dir"
+"#,
+    )
+    .unwrap();
+    fs::create_dir(workspace.path().join("cargo-home")).unwrap();
+    let gc = GcArgs::default()
+        .with_cargo_home(workspace.path().join("cargo-home"))
+        .with_max_build_dir_size("1")
+        .with_force(true);
+    let batch = |build_dir: &Path| {
+        Batch {
+            context: &HoldContext::default(),
+            manifest: Path::new("repos.toml"),
+            voyage: true,
+            jobs: NonZeroUsize::new(2).unwrap(),
+            report: None,
+            build_dir: Some(build_dir),
+            anchor: &AnchorArgs::default(),
+            gc: &gc,
+            gc_dry_run: false,
+            gc_debug: false,
+            gc_age_threshold_days: 7,
+            gc_auto_max_target_size: false,
+            verbose: 0,
+            quiet: true,
+            working_dir: workspace.path(),
+        }
+        .run()
+    };
+    batch(Path::new("scratch")).unwrap();
+
+    // The build directories were held to --max-build-dir-size, and the
+    // target directory to nothing
+    assert!(!api_rlib.exists());
+    assert!(!worker_rlib.exists());
+    assert!(kept.exists());
+
+    // One build directory for every repository would be cleaned by each
+    assert!(matches!(
+        batch(&workspace.path().join("shared")),
+        Err(HoldError::PerRepositoryOption { key, .. }) if key == "build-dir"
+    ));
+}
+
+#[test]
+fn test_batch_counts_the_warnings_of_each_repository() {
+    let workspace = batch_workspace(&["api", "worker"]);
+    // A tracked symlink, which anchor skips with a warning
+    let worker = workspace.path().join("worker");
+    std::os::unix::fs::symlink("main.rs", worker.join("link.rs")).unwrap();
+    let repo = git2::Repository::open(&worker).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("link.rs")).unwrap();
+    index.write().unwrap();
+    fs::write(
+        workspace.path().join("repos.toml"),
+        r#"
+[[repo]]
+path = "api"
+
+[[repo]]
+path = "worker"
+"#,
+    )
+    .unwrap();
+    let report = workspace.path().join("batch.json");
+    let context = HoldContext::default();
+    let batch = || {
+        Batch {
+            context: &context,
+            manifest: Path::new("repos.toml"),
+            voyage: false,
+            jobs: NonZeroUsize::new(2).unwrap(),
+            report: Some(&report),
+            build_dir: None,
+            anchor: &AnchorArgs::default(),
+            gc: &GcArgs::default(),
+            gc_dry_run: false,
+            gc_debug: false,
+            gc_age_threshold_days: 7,
+            gc_auto_max_target_size: false,
+            verbose: 0,
+            quiet: true,
+            working_dir: workspace.path(),
+        }
+        .run()
+    };
+    batch().unwrap();
+    batch().unwrap();
+
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&report).unwrap()).unwrap();
+    assert_eq!(report["repositories"][0]["warnings"], 0);
+    assert_eq!(report["repositories"][1]["warnings"], 1);
+    // Nothing changed since the first run stowed the metadata
+    assert_eq!(report["repositories"][0]["metadata"], "unchanged");
+    // Each warning also counts for the batch, e.g. for --deny-warnings
+    assert_eq!(context.warnings_emitted(), 1);
+}
+
+#[test]
+fn test_dock_stows_and_records_where_the_build_started() {
+    let temp_dir = setup_git_repo();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let target_dir = temp_dir.path().join("target");
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+    // Garbage collected five days ago, before the build
+    let mut metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    metadata.last_gc_mtime_nanos = Some(
+        (SystemTime::now() - Duration::from_secs(5 * 86_400))
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos(),
+    );
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let debug = target_dir.join("debug");
+    crate_artifacts(&debug, "kept", 10);
+    let first_built = crate_artifacts(&debug, "first", 2);
+    crate_artifacts(&debug, "last", 1);
+    // Nothing was built in release since the last GC
+    crate_artifacts(&target_dir.join("release"), "stale", 10);
+    fs::write(temp_dir.path().join("test.txt"), "changed content").unwrap();
+
+    let context = HoldContext::default();
+    dock::dock(
+        &context,
+        &metadata_path,
+        &target_dir,
+        0,
+        true,
+        temp_dir.path(),
+    )
+    .unwrap();
+
+    let summary = crate::logging::Summary::take(&context, "dock", Duration::ZERO, false);
+    let recorded = summary
+        .items
+        .iter()
+        .find(|item| item.label == "profiles recorded")
+        .unwrap();
+    assert_eq!(recorded.value, "1");
+
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    // The tracked files were stowed
+    let state = metadata.get(Path::new("test.txt")).unwrap().unwrap();
+    assert_eq!(state.size, "changed content".len() as u64);
+    // The build started with the oldest artifact newer than the last GC
+    let started = fs::metadata(&first_built)
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let builds = &metadata.gc_metrics.profile_build_mtime_nanos;
+    assert_eq!(builds["debug"], [started]);
+    assert!(!builds.contains_key("release"));
+}
+
 #[test]
 fn test_stow_propagates_future_metadata_error() {
     let temp_dir = setup_git_repo();
@@ -110,10 +1547,17 @@ fn test_stow_propagates_future_metadata_error() {
     // Persist metadata with a future format version
     let mut metadata = StateMetadata::new();
     metadata.version = METADATA_VERSION + 1;
-    save_metadata(&metadata, &metadata_path).unwrap();
-
-    let err = stow(&metadata_path, 0, false, temp_dir.path()).unwrap_err();
-    assert!(matches!(err, HoldError::ConfigError(_)));
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let err = stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, HoldError::MetadataVersionTooNew { .. }));
 }
 
 #[test]
@@ -124,13 +1568,20 @@ fn test_stow_preserves_last_gc_timestamp_when_time_advances() {
     let expected_nanos = one_hour_ago.duration_since(UNIX_EPOCH).unwrap().as_nanos();
     let mut seed = StateMetadata::new();
     seed.last_gc_mtime_nanos = Some(expected_nanos);
-    save_metadata(&seed, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &seed, &metadata_path).unwrap();
 
     // Allow the wall clock to move forward before running stow again.
     std::thread::sleep(Duration::from_millis(10));
 
-    stow(&metadata_path, 0, false, temp_dir.path()).unwrap();
-    let second_metadata = load_metadata(&metadata_path).unwrap();
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let second_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     let second_preservation = second_metadata
         .last_gc_mtime_nanos
         .expect("stow should keep last_gc_mtime_nanos set");
@@ -157,15 +1608,107 @@ fn test_stow_preserves_gc_metrics() {
             observed_growth_pct: 5,
             clamp_reason: "deadband/hold".to_string(),
         }),
+        profile_build_mtime_nanos: std::collections::HashMap::from([(
+            "release".to_string(),
+            vec![1, 2],
+        )]),
+        directory_sizes: std::collections::HashMap::from([(
+            "release".to_string(),
+            crate::state::DirectorySize {
+                mtime_nanos: 7,
+                apparent: 100,
+                allocated: 4096,
+                ..Default::default()
+            },
+        )]),
+        recent_builds: vec![crate::state::BuildRecord {
+            reported_nanos: 3,
+            success: true,
+            duration_secs: Some(812),
+            ..Default::default()
+        }],
     };
-    save_metadata(&existing, &metadata_path).unwrap();
-
-    stow(&metadata_path, 0, false, temp_dir.path()).unwrap();
-    let reloaded = load_metadata(&metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &existing, &metadata_path).unwrap();
+
+    stow(
+        &HoldContext::default(),
+        &metadata_path,
+        0,
+        false,
+        temp_dir.path(),
+    )
+    .unwrap();
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
 
     assert_eq!(reloaded.gc_metrics, existing.gc_metrics);
 }
 
+#[test]
+fn test_install_hooks_writes_salvage_hooks() {
+    let temp_dir = setup_git_repo();
+
+    install_hooks(&HoldContext::default(), 0, true, temp_dir.path(), false).unwrap();
+
+    for name in install_hooks::HOOK_NAMES {
+        let hook = temp_dir.path().join(".git/hooks").join(name);
+        let contents = fs::read_to_string(&hook).unwrap();
+        assert!(contents.contains(install_hooks::HOOK_MARKER));
+        assert!(contents.contains("cargo hold salvage"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&hook).unwrap().permissions().mode();
+            assert_ne!(mode & 0o111, 0, "{name} hook should be executable");
+        }
+    }
+}
+
+#[test]
+fn test_install_hooks_respects_foreign_hooks() {
+    let temp_dir = setup_git_repo();
+    let hooks_dir = temp_dir.path().join(".git/hooks");
+    fs::create_dir_all(&hooks_dir).unwrap();
+    let post_merge = hooks_dir.join("post-merge");
+    fs::write(&post_merge, "#!/bin/sh\necho custom\n").unwrap();
+
+    install_hooks(&HoldContext::default(), 0, true, temp_dir.path(), false).unwrap();
+    assert_eq!(
+        fs::read_to_string(&post_merge).unwrap(),
+        "#!/bin/sh\necho custom\n"
+    );
+    assert!(hooks_dir.join("post-checkout").exists());
+
+    install_hooks(&HoldContext::default(), 0, true, temp_dir.path(), true).unwrap();
+    assert!(
+        fs::read_to_string(&post_merge)
+            .unwrap()
+            .contains(install_hooks::HOOK_MARKER)
+    );
+}
+
+#[test]
+fn test_install_hooks_from_linked_worktree() {
+    let temp_dir = setup_git_repo();
+    let repo = git2::Repository::open(temp_dir.path()).unwrap();
+    let tree = repo
+        .find_tree(repo.index().unwrap().write_tree().unwrap())
+        .unwrap();
+    let signature = git2::Signature::now("cargo-hold", "cargo-hold@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+    let other = TempDir::new().unwrap();
+    let worktree = other.path().join("feature");
+    repo.worktree("feature", &worktree, None).unwrap();
+
+    install_hooks(&HoldContext::default(), 0, true, &worktree, false).unwrap();
+
+    // Git runs the hooks of the main repository in every worktree
+    for name in install_hooks::HOOK_NAMES {
+        assert!(temp_dir.path().join(".git/hooks").join(name).exists());
+    }
+}
+
 fn make_profile(target: &Path) {
     let profile = target.join("debug");
     fs::create_dir_all(profile.join("build")).unwrap();
@@ -203,7 +1746,7 @@ fn test_heave_records_last_gc_timestamp() {
         .unwrap()
         .as_nanos();
 
-    let reloaded = load_metadata(&metadata_path).unwrap();
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     let recorded = reloaded
         .last_gc_mtime_nanos
         .expect("heave should record last_gc_mtime_nanos");
@@ -212,6 +1755,179 @@ fn test_heave_records_last_gc_timestamp() {
         recorded >= before && recorded <= after,
         "last_gc_mtime_nanos should reflect GC time"
     );
+    assert_eq!(reloaded.recent_gc_mtime_nanos, vec![recorded]);
+}
+
+#[test]
+fn test_heave_records_profile_build_timestamps() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+
+    let fingerprint = target_dir.join("debug/.fingerprint/foo-0123456789abcdef");
+    fs::create_dir_all(&fingerprint).unwrap();
+    let lib_file = fingerprint.join("lib-foo");
+    fs::write(&lib_file, "fingerprint").unwrap();
+    let built_at = fs::metadata(&lib_file)
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+
+    for _ in 0..2 {
+        Heave::builder()
+            .target_dir(&target_dir)
+            .auto_max_target_size(false)
+            .metadata_path(&metadata_path)
+            .quiet(true)
+            .build()
+            .unwrap()
+            .heave()
+            .unwrap();
+    }
+
+    // The same build seen twice is recorded once.
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(
+        reloaded.gc_metrics.profile_build_mtime_nanos.get("debug"),
+        Some(&vec![built_at])
+    );
+}
+
+#[test]
+fn test_heave_records_directory_size_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    fs::write(target_dir.join("debug/deps/libfoo.rlib"), [0u8; 100]).unwrap();
+    let settled = filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(60));
+    for dir in [
+        "debug/build",
+        "debug/deps",
+        "debug/.fingerprint",
+        "debug",
+        "",
+    ] {
+        filetime::set_file_mtime(target_dir.join(dir), settled).unwrap();
+    }
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+
+    Heave::builder()
+        .target_dir(&target_dir)
+        .auto_max_target_size(false)
+        .metadata_path(&metadata_path)
+        .quiet(true)
+        .build()
+        .unwrap()
+        .heave()
+        .unwrap();
+
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    let deps = reloaded
+        .gc_metrics
+        .directory_sizes
+        .get("debug/deps")
+        .expect("heave should index the deps directory");
+    assert_eq!(deps.apparent, 100);
+    assert!(reloaded.gc_metrics.directory_sizes.contains_key(""));
+}
+
+#[test]
+fn test_heave_suspends_size_limits_while_warming_up() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    let rlib = target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib");
+    let build = || {
+        fs::write(&rlib, [0u8; 10_000]).unwrap();
+        let two_days_ago = filetime::FileTime::from_system_time(
+            SystemTime::now() - Duration::from_secs(2 * 86400),
+        );
+        filetime::set_file_mtime(&rlib, two_days_ago).unwrap();
+    };
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+    let heave = |force| {
+        Heave::builder()
+            .target_dir(&target_dir)
+            .max_target_size(Some("1K"))
+            .auto_max_target_size(false)
+            .force(force)
+            .metadata_path(&metadata_path)
+            .age_threshold_days(7)
+            .quiet(true)
+            .build()
+            .unwrap()
+            .heave()
+            .unwrap();
+    };
+
+    // Too young for the age threshold, and the cap waits for the metrics
+    build();
+    heave(false);
+    assert!(rlib.exists());
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .gc_metrics
+            .runs,
+        1
+    );
+
+    // Without the recorded build, which would preserve the artifact
+    let mut metadata = StateMetadata::new();
+    metadata.gc_metrics.runs = WARMUP_RUNS;
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    heave(false);
+    assert!(!rlib.exists());
+
+    fs::remove_file(&metadata_path).unwrap();
+    build();
+    heave(true);
+    assert!(!rlib.exists());
+}
+
+#[test]
+fn test_heave_simulate_removes_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    let rlib = target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib");
+    fs::write(&rlib, [0u8; 100]).unwrap();
+    let old = filetime::FileTime::from_system_time(
+        SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60),
+    );
+    filetime::set_file_mtime(&rlib, old).unwrap();
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+    let policies = ["age=1d".to_string(), "cap=1G,costs=off".to_string()];
+
+    Heave::builder()
+        .target_dir(&target_dir)
+        .auto_max_target_size(false)
+        .metadata_path(&metadata_path)
+        .simulate(true)
+        .policies(&policies)
+        .quiet(true)
+        .build()
+        .unwrap()
+        .heave()
+        .unwrap();
+
+    assert!(rlib.exists());
+    assert!(!metadata_path.exists());
+
+    let invalid = ["order=largest".to_string()];
+    let result = Heave::builder()
+        .target_dir(&target_dir)
+        .simulate(true)
+        .policies(&invalid)
+        .quiet(true)
+        .build()
+        .unwrap()
+        .heave();
+    assert!(matches!(result, Err(HoldError::InvalidSimulatedPolicy(..))));
 }
 
 #[test]
@@ -225,12 +1941,13 @@ fn test_heave_auto_cap_records_metrics() {
     metadata.gc_metrics.seed_initial_size = Some(5 * 1024 * 1024);
     metadata.gc_metrics.recent_initial_sizes = vec![5 * 1024 * 1024, 6 * 1024 * 1024];
     metadata.gc_metrics.recent_bytes_freed = vec![0, 0];
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     Heave::builder()
         .target_dir(&target_dir)
         .max_target_size(None)
         .auto_max_target_size(true)
+        .force(true)
         .metadata_path(&metadata_path)
         .age_threshold_days(7)
         .verbose(0)
@@ -240,7 +1957,7 @@ fn test_heave_auto_cap_records_metrics() {
         .heave()
         .unwrap();
 
-    let reloaded = load_metadata(&metadata_path).unwrap();
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     let metrics = &reloaded.gc_metrics;
     assert_eq!(metrics.runs, 1);
     assert!(
@@ -251,6 +1968,92 @@ fn test_heave_auto_cap_records_metrics() {
     assert!(!metrics.recent_initial_sizes.is_empty());
 }
 
+#[test]
+fn test_heave_suggest_only_records_the_cap_without_enforcing_it() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    let rlib = target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib");
+    fs::write(&rlib, [0u8; 10_000]).unwrap();
+    let two_days_ago =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(2 * 86400));
+    filetime::set_file_mtime(&rlib, two_days_ago).unwrap();
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.gc_metrics.seed_initial_size = Some(5 * 1024 * 1024);
+    metadata.gc_metrics.recent_initial_sizes = vec![5 * 1024 * 1024, 6 * 1024 * 1024];
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    // Observed even while warming up, and next to an explicit cap, which is
+    // still enforced once forced
+    for (max_target_size, force) in [(None, false), (Some("1K"), true)] {
+        // Forget the recorded build, which would preserve the artifact
+        let mut metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+        metadata.gc_metrics.profile_build_mtime_nanos.clear();
+        save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+        Heave::builder()
+            .target_dir(&target_dir)
+            .max_target_size(max_target_size)
+            .suggest_only(true)
+            .force(force)
+            .metadata_path(&metadata_path)
+            .age_threshold_days(7)
+            .quiet(true)
+            .build()
+            .unwrap()
+            .heave()
+            .unwrap();
+        let metrics = load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .gc_metrics;
+        assert!(metrics.last_suggested_cap.is_some());
+        assert!(metrics.last_cap_trace.is_some());
+        assert_eq!(rlib.exists(), max_target_size.is_none());
+    }
+}
+
+#[test]
+fn test_heave_keeps_gc_metrics_for_the_configured_window() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    make_profile(&target_dir);
+    let metadata_path = temp_dir.path().join("cargo-hold.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.gc_metrics.recent_initial_sizes = vec![1, 2, 3];
+    metadata.gc_metrics.recent_bytes_freed = vec![0, 0, 0];
+    metadata.gc_metrics.recent_final_sizes = vec![1, 2, 3];
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let heave = |window| {
+        Heave::builder()
+            .target_dir(&target_dir)
+            .auto_max_target_size(false)
+            .gc_metrics_window(window)
+            .metadata_path(&metadata_path)
+            .quiet(true)
+            .build()
+            .unwrap()
+            .heave()
+            .unwrap();
+        load_metadata(&HoldContext::default(), &metadata_path).unwrap()
+    };
+
+    // A shorter window drops the oldest runs, and sticks without the flag
+    let metadata = heave(NonZeroU32::new(2));
+    assert_eq!(metadata.gc_metrics_window, Some(2));
+    assert_eq!(metadata.gc_metrics.recent_initial_sizes.len(), 2);
+    assert_eq!(metadata.gc_metrics.recent_final_sizes[0], 3);
+    let metadata = heave(None);
+    assert_eq!(metadata.gc_metrics.recent_bytes_freed.len(), 2);
+
+    let metadata = heave(NonZeroU32::new(52));
+    assert_eq!(metadata.metrics_window(), 52);
+    assert_eq!(metadata.gc_metrics.recent_initial_sizes.len(), 3);
+}
+
 #[test]
 fn test_heave_auto_cap_can_be_disabled() {
     let temp_dir = TempDir::new().unwrap();
@@ -260,7 +2063,7 @@ fn test_heave_auto_cap_can_be_disabled() {
 
     let mut metadata = StateMetadata::new();
     metadata.gc_metrics.seed_initial_size = Some(5 * 1024 * 1024);
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     Heave::builder()
         .target_dir(&target_dir)
@@ -275,7 +2078,7 @@ fn test_heave_auto_cap_can_be_disabled() {
         .heave()
         .unwrap();
 
-    let reloaded = load_metadata(&metadata_path).unwrap();
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert!(reloaded.gc_metrics.last_suggested_cap.is_none());
 }
 
@@ -290,6 +2093,40 @@ fn cold_start_from_current_skips_hard_ceiling() {
     assert_eq!(trace.clamp_reason, "cold-start");
 }
 
+#[test]
+fn slow_builds_widen_the_growth_budget() {
+    let seed = 1024 * 1024;
+    let build = |success, secs| crate::state::BuildRecord {
+        success,
+        duration_secs: Some(secs),
+        ..Default::default()
+    };
+    let mut metrics = GcMetrics::default();
+
+    // Failed builds say nothing about what a rebuild costs
+    metrics.record_build(build(false, 3600));
+    let (cap, _) = suggest_max_target_size(&metrics, Some(seed)).unwrap();
+    assert_eq!(cap, seed + MIN_HEADROOM_BYTES);
+
+    // A 14 minute build adds 20%
+    metrics.record_build(build(true, 14 * 60));
+    let (cap, trace) = suggest_max_target_size(&metrics, Some(seed)).unwrap();
+    assert_eq!(
+        trace.growth_budget,
+        MIN_HEADROOM_BYTES + MIN_HEADROOM_BYTES / 100 * 20
+    );
+    assert_eq!(cap, seed + trace.growth_budget);
+
+    // Very slow builds at most double it
+    metrics.record_build(build(true, 5 * 3600));
+    metrics.record_build(build(true, 5 * 3600));
+    let (_, trace) = suggest_max_target_size(&metrics, Some(seed)).unwrap();
+    assert_eq!(
+        trace.growth_budget,
+        MIN_HEADROOM_BYTES + MIN_HEADROOM_BYTES / 100 * MAX_REBUILD_COST_PCT
+    );
+}
+
 #[test]
 fn finals_without_initials_still_respect_hard_ceiling() {
     let gib = 1024 * 1024 * 1024;
@@ -338,6 +2175,7 @@ fn mk_metrics(initials: &[u64], freed: &[u64], last_cap: Option<u64>) -> GcMetri
         last_suggested_cap: last_cap,
         recent_final_sizes: Vec::new(),
         last_cap_trace: None,
+        ..GcMetrics::default()
     }
 }
 
@@ -355,6 +2193,7 @@ fn mk_metrics_with_finals(
         last_suggested_cap: last_cap,
         recent_final_sizes: finals.to_vec(),
         last_cap_trace: None,
+        ..GcMetrics::default()
     }
 }
 
@@ -532,3 +2371,60 @@ fn shrink_moves_down_slowly_not_below_baseline() {
         14 * 1024 * 1024 * 1024 - (14 * 1024 * 1024 * 1024 * MAX_SHRINK_FACTOR_PER_RUN_PCT) / 100;
     assert_eq!(cap, min_cap);
 }
+
+#[test]
+fn test_offline_rejects_remote_paths() {
+    use clap::Parser;
+
+    let temp_dir = TempDir::new().unwrap();
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--offline",
+        "heave",
+        "--removal-list",
+        "s3://bucket/removed.json",
+    ]);
+    let err = execute_with_dir(&cli, Some(temp_dir.path())).unwrap_err();
+    assert!(matches!(
+        err,
+        HoldError::RemotePathOffline { ref option, .. } if option == "--removal-list"
+    ));
+
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--offline",
+        "--metadata-path",
+        "https://cache.example.com/cargo-hold.metadata",
+        "bilge",
+    ]);
+    assert!(matches!(
+        execute_with_dir(&cli, Some(temp_dir.path())),
+        Err(HoldError::RemotePathOffline { .. })
+    ));
+
+    assert!(is_remote(Path::new("gs://bucket/target")));
+    assert!(!is_remote(Path::new("target")));
+    assert!(!is_remote(Path::new("C:\\target")));
+    assert!(!is_remote(Path::new("./weird://name")));
+}
+
+#[test]
+fn test_deny_warnings_fails_command_that_warned() {
+    use clap::Parser;
+
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    // Unpinning a crate that is not pinned is a warning
+    let cli = Cli::parse_from([
+        "cargo-hold",
+        "--deny-warnings",
+        "--quiet",
+        "--metadata-path",
+        metadata_path.to_str().unwrap(),
+        "unpin",
+        "rocksdb-sys",
+    ]);
+    let err = execute_with_dir(&cli, Some(temp_dir.path())).unwrap_err();
+    assert!(matches!(err, HoldError::WarningsDenied(count) if count >= 1));
+}
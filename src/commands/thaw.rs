@@ -0,0 +1,49 @@
+//! Thaw command implementation.
+
+use std::path::Path;
+
+use crate::context::HoldContext;
+use crate::error::Result;
+use crate::gc::ColdManifest;
+use crate::lock::{LockMode, TargetDirLock};
+
+/// Executes the thaw command.
+///
+/// Decompresses every artifact compressed by `--gc-compress-cold` back to its
+/// original path and modification time. Cargo's target directory lock is
+/// held while decompressing.
+pub fn thaw(context: &HoldContext, target_dir: &Path, verbose: u8, quiet: bool) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Thawing compressed artifacts...");
+
+    if ColdManifest::load(target_dir)?.is_empty() {
+        log.info("No compressed artifacts to thaw");
+        return Ok(());
+    }
+
+    // Read the manifest again under the lock, in case a GC just updated it
+    let _lock = TargetDirLock::acquire(target_dir, LockMode::Exclusive, &log)?;
+    let mut manifest = ColdManifest::load(target_dir)?;
+    let stats = manifest.thaw(target_dir)?;
+    manifest.save(target_dir)?;
+    log.summarize_count("thawed", stats.files_thawed);
+
+    log.info(format!(
+        "🧊 Thawed {} artifact{} ({})",
+        stats.files_thawed,
+        if stats.files_thawed == 1 { "" } else { "s" },
+        log.format_size(stats.bytes_restored)
+    ));
+    if stats.files_discarded > 0 {
+        log.verbose(
+            1,
+            format!(
+                "Discarded {} compressed artifact{} that cargo has since rebuilt",
+                stats.files_discarded,
+                if stats.files_discarded == 1 { "" } else { "s" }
+            ),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Verify command implementation.
+
+use std::path::Path;
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::lock::{LockMode, TargetDirLock};
+use crate::seal::{Seal, Verification};
+
+/// Executes the verify command.
+///
+/// Compares the target directory against the manifest written by `seal`,
+/// listing every missing or corrupted file. The size of every file is
+/// checked; unless `full` is set, only `sample` files are hashed.
+pub fn verify(
+    context: &HoldContext,
+    target_dir: &Path,
+    full: bool,
+    sample: usize,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    let log = context.logger(verbose, quiet);
+    log.verbose(1, "Verifying target directory...");
+
+    let _lock = TargetDirLock::acquire(target_dir, LockMode::Shared, &log)?;
+    let Some(seal) = Seal::load(target_dir)? else {
+        log.info("No seal found, nothing to verify");
+        return Ok(());
+    };
+
+    let verification = if full {
+        Verification::Full
+    } else {
+        Verification::Sample(sample)
+    };
+    let report = seal.verify(target_dir, verification);
+    log.summarize_count("files checked", report.files_checked);
+    log.summarize_count("hashed", report.files_hashed);
+    log.summarize_count("corrupted", report.corrupted.len());
+
+    if report.corrupted.is_empty() {
+        log.info(format!(
+            "✅ Verified {} sealed files ({} hashed)",
+            report.files_checked, report.files_hashed
+        ));
+        return Ok(());
+    }
+
+    for corruption in &report.corrupted {
+        log.info(format!("  Corrupted: {corruption}"));
+    }
+    Err(HoldError::CorruptedArtifacts {
+        target_dir: target_dir.to_path_buf(),
+        corrupted: report.corrupted.len(),
+        checked: report.files_checked,
+    })
+}
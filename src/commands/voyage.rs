@@ -1,21 +1,29 @@
 //! Voyage command (anchor + heave).
 
-use std::path::Path;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 
-use crate::commands::anchor::anchor;
+use crate::cli::{AnchorArgs, GcArgs};
+use crate::commands::anchor::{AnchorOutcome, anchor_with_args};
 use crate::commands::gc_options::{GcOptions, GcOptionsBuilder};
 use crate::commands::heave::Heave;
+use crate::context::HoldContext;
 use crate::error::{HoldError, Result};
-use crate::logging::Logger;
 
 pub struct Voyage<'a> {
     pub(crate) gc: GcOptions<'a>,
     pub(crate) working_dir: &'a Path,
+    pub(crate) anchor: AnchorArgs,
+    pub(crate) no_anchor: bool,
+    pub(crate) no_gc: bool,
 }
 
 pub struct VoyageBuilder<'a> {
     gc: GcOptionsBuilder<'a>,
     working_dir: Option<&'a Path>,
+    anchor: AnchorArgs,
+    no_anchor: bool,
+    no_gc: bool,
 }
 
 impl<'a> Voyage<'a> {
@@ -23,43 +31,47 @@ impl<'a> Voyage<'a> {
         VoyageBuilder::new()
     }
 
-    /// Execute the voyage (anchor + heave)
-    pub fn run(self) -> Result<()> {
-        let log = Logger::new(self.gc.verbose(), self.gc.quiet());
-        log.info("🚢 Setting sail on voyage (anchor + heave)...");
-
-        anchor(
-            self.gc
-                .metadata_path()
-                .ok_or_else(|| HoldError::ConfigError("metadata_path is required".to_string()))?,
-            self.gc.verbose(),
-            self.gc.quiet(),
-            self.working_dir,
-        )?;
-
-        log.info("🧹 Starting garbage collection...");
-
-        Heave::builder()
-            .target_dir(self.gc.target_dir())
-            .max_target_size(self.gc.max_target_size())
-            .auto_max_target_size(self.gc.auto_max_target_size())
-            .dry_run(self.gc.dry_run())
-            .debug(self.gc.debug())
-            .preserve_cargo_binaries(self.gc.preserve_cargo_binaries())
-            .age_threshold_days(self.gc.age_threshold_days())
-            .verbose(self.gc.verbose())
-            .metadata_path(
+    /// Execute the voyage (anchor + heave), skipping the steps turned off.
+    ///
+    /// Returns what anchor did with the metadata, or `None` under
+    /// `--no-anchor`.
+    pub fn run(self) -> Result<Option<AnchorOutcome>> {
+        let log = self.gc.logger();
+        let steps = match (self.no_anchor, self.no_gc) {
+            (false, false) => "anchor + heave",
+            (true, false) => "heave only",
+            (false, true) => "anchor only",
+            (true, true) => "nothing to do",
+        };
+        log.info(format!("🚢 Setting sail on voyage ({steps})..."));
+
+        let anchored = if self.no_anchor {
+            log.verbose(1, "Skipping anchor (--no-anchor)");
+            None
+        } else {
+            Some(anchor_with_args(
+                self.gc.context(),
                 self.gc.metadata_path().ok_or_else(|| {
                     HoldError::ConfigError("metadata_path is required".to_string())
                 })?,
-            )
-            .quiet(self.gc.quiet())
-            .build()?
-            .heave()?;
+                self.gc.target_dir(),
+                self.gc.verbose(),
+                self.gc.quiet(),
+                self.working_dir,
+                &self.anchor,
+            )?)
+        };
+
+        if self.no_gc {
+            log.verbose(1, "Skipping garbage collection (--no-gc)");
+        } else {
+            log.info("🧹 Starting garbage collection...");
+            Heave::from_options(self.gc).heave()?;
+        }
 
         log.info("🚢 Voyage completed successfully!");
 
-        Ok(())
+        Ok(anchored)
     }
 }
 
@@ -74,6 +86,9 @@ impl<'a> VoyageBuilder<'a> {
         Self {
             gc: GcOptionsBuilder::new(),
             working_dir: None,
+            anchor: AnchorArgs::default(),
+            no_anchor: false,
+            no_gc: false,
         }
     }
 
@@ -87,11 +102,37 @@ impl<'a> VoyageBuilder<'a> {
         self
     }
 
+    /// Set every option given by the shared GC arguments `gc`
+    pub fn gc_args(mut self, gc: &'a GcArgs) -> Self {
+        self.gc = self.gc.gc_args(gc);
+        self
+    }
+
     pub fn max_target_size(mut self, size: Option<&'a str>) -> Self {
         self.gc = self.gc.max_target_size(size);
         self
     }
 
+    pub fn max_target_files(mut self, files: Option<u64>) -> Self {
+        self.gc = self.gc.max_target_files(files);
+        self
+    }
+
+    pub fn build_dir(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.build_dir(path);
+        self
+    }
+
+    pub fn max_build_dir_size(mut self, size: Option<&'a str>) -> Self {
+        self.gc = self.gc.max_build_dir_size(size);
+        self
+    }
+
+    pub fn max_coverage_size(mut self, size: Option<&'a str>) -> Self {
+        self.gc = self.gc.max_coverage_size(size);
+        self
+    }
+
     pub fn gc_dry_run(mut self, dry_run: bool) -> Self {
         self.gc = self.gc.dry_run(dry_run);
         self
@@ -112,6 +153,126 @@ impl<'a> VoyageBuilder<'a> {
         self
     }
 
+    pub fn preserve_units_from(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.preserve_units_from(path);
+        self
+    }
+
+    pub fn preserve_last_builds(mut self, builds: usize) -> Self {
+        self.gc = self.gc.preserve_last_builds(builds);
+        self
+    }
+
+    pub fn preserve_window(mut self, window: Option<&'a str>) -> Self {
+        self.gc = self.gc.preserve_window(window);
+        self
+    }
+
+    pub fn preserve_buffer(mut self, buffer: Option<&'a str>) -> Self {
+        self.gc = self.gc.preserve_buffer(buffer);
+        self
+    }
+
+    pub fn size_mode(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.size_mode(mode);
+        self
+    }
+
+    pub fn gc_when_busy(mut self, policy: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_when_busy(policy);
+        self
+    }
+
+    pub fn gc_policy_cmd(mut self, command: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_policy_cmd(command);
+        self
+    }
+
+    pub fn global_cache(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.global_cache(mode);
+        self
+    }
+
+    pub fn gc_sandbox(mut self, mode: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_sandbox(mode);
+        self
+    }
+
+    pub fn gc_symlinks(mut self, policy: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_symlinks(policy);
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.gc = self.gc.force(force);
+        self
+    }
+
+    pub fn gc_metrics_window(mut self, window: Option<NonZeroU32>) -> Self {
+        self.gc = self.gc.gc_metrics_window(window);
+        self
+    }
+
+    pub fn cargo_home(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.cargo_home(path);
+        self
+    }
+
+    pub fn keep_cargo_home(mut self, keep: bool) -> Self {
+        self.gc = self.gc.keep_cargo_home(keep);
+        self
+    }
+
+    pub fn removal_list(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.removal_list(path);
+        self
+    }
+
+    pub fn removal_list_format(mut self, format: Option<&'a str>) -> Self {
+        self.gc = self.gc.removal_list_format(format);
+        self
+    }
+
+    pub fn gc_audit_log(mut self, path: Option<&'a Path>) -> Self {
+        self.gc = self.gc.gc_audit_log(path);
+        self
+    }
+
+    pub fn gc_audit_log_format(mut self, format: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_audit_log_format(format);
+        self
+    }
+
+    pub fn gc_max_duration(mut self, duration: Option<&'a str>) -> Self {
+        self.gc = self.gc.gc_max_duration(duration);
+        self
+    }
+
+    pub fn gc_compress_cold(mut self, enabled: bool) -> Self {
+        self.gc = self.gc.gc_compress_cold(enabled);
+        self
+    }
+
+    pub fn gc_dep_info(mut self, enabled: bool) -> Self {
+        self.gc = self.gc.gc_dep_info(enabled);
+        self
+    }
+
+    pub fn age_threshold(mut self, threshold: Option<&'a str>) -> Self {
+        self.gc = self.gc.age_threshold(threshold);
+        self
+    }
+
+    pub fn age_threshold_for(mut self, thresholds: &'a [String]) -> Self {
+        self.gc = self.gc.age_threshold_for(thresholds);
+        self
+    }
+
+    pub fn lockfiles(mut self, paths: &'a [PathBuf]) -> Self {
+        self.gc = self.gc.lockfiles(paths);
+        self
+    }
+
     pub fn gc_age_threshold_days(mut self, days: u32) -> Self {
         self.gc = self.gc.age_threshold_days(days);
         self
@@ -127,17 +288,42 @@ impl<'a> VoyageBuilder<'a> {
         self
     }
 
+    pub fn context(mut self, context: &HoldContext) -> Self {
+        self.gc = self.gc.context(context);
+        self
+    }
+
     pub fn working_dir(mut self, working_dir: &'a Path) -> Self {
         self.working_dir = Some(working_dir);
         self
     }
 
+    pub fn anchor_args(mut self, anchor: &AnchorArgs) -> Self {
+        self.anchor = anchor.clone();
+        self
+    }
+
+    /// Skip the anchor step.
+    pub fn no_anchor(mut self, skip: bool) -> Self {
+        self.no_anchor = skip;
+        self
+    }
+
+    /// Skip the garbage collection step.
+    pub fn no_gc(mut self, skip: bool) -> Self {
+        self.no_gc = skip;
+        self
+    }
+
     pub fn build(self) -> Result<Voyage<'a>> {
         Ok(Voyage {
             gc: self.gc.build()?,
             working_dir: self
                 .working_dir
                 .ok_or_else(|| HoldError::ConfigError("working_dir is required".to_string()))?,
+            anchor: self.anchor,
+            no_anchor: self.no_anchor,
+            no_gc: self.no_gc,
         })
     }
 }
@@ -0,0 +1,196 @@
+//! The context a command runs in.
+//!
+//! The options read deep down while a command runs, such as `--vcs` or
+//! `--metadata-key`, live in the [`HoldContext`] given to the command rather
+//! than in process-wide state, so several commands can run at once on
+//! different repositories with different options.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "cli")]
+use crate::cli::GlobalOpts;
+use crate::discovery::{Exclusions, Listings, Vcs, VcsBackend};
+use crate::error::Result;
+use crate::gc::{EphemeralMode, SizeUnits};
+use crate::hashing::{DEFAULT_BATCH_SIZE, LargeFilePolicy};
+#[cfg(feature = "cli")]
+use crate::hooks::CONFIG_FILENAME;
+use crate::hooks::Hooks;
+use crate::lfs::LfsMode;
+use crate::logging::{Logger, SummaryItems};
+use crate::profile::Profile;
+use crate::reporting::CiMode;
+
+/// Options of a command, with the caches and the warning count its steps
+/// share.
+///
+/// [`execute`](crate::commands::execute) runs every command in a context of
+/// its own, made from the command line's global options. The command
+/// functions take the context as their first argument; make one with
+/// [`HoldContext::new`], or use [`HoldContext::default`] for the defaults.
+/// Commands given different contexts share nothing, so they may run
+/// concurrently on different threads:
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// use cargo_hold::cli::GlobalOpts;
+/// use cargo_hold::commands::stow::stow;
+/// use cargo_hold::context::HoldContext;
+///
+/// std::thread::scope(|scope| {
+///     for repo in ["repos/a", "repos/b"] {
+///         scope.spawn(move || {
+///             let global = GlobalOpts::builder()
+///                 .target_dir(format!("{repo}/target"))
+///                 .build();
+///             let repo = Path::new(repo);
+///             let context = HoldContext::new(&global, repo)?;
+///             stow(&context, &global.get_metadata_path(), 0, false, repo)
+///         });
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct HoldContext {
+    /// CI system reported to
+    pub(crate) ci: CiMode,
+    pub(crate) size_units: SizeUnits,
+    pub(crate) vcs: VcsBackend,
+    pub(crate) lfs: LfsMode,
+    pub(crate) exclusions: Exclusions,
+    /// `--large-file-threshold` and the policy for files above it, if a
+    /// threshold was given
+    pub(crate) large_files: Option<(u64, LargeFilePolicy)>,
+    /// Files analyzed per batch by stow and salvage
+    pub(crate) batch_size: usize,
+    /// `--hash-salt`, if given; otherwise hashes are salted with the
+    /// repository's origin
+    pub(crate) hash_salt: Option<String>,
+    pub(crate) trusted_metadata: bool,
+    /// MAC key derived from `--metadata-key`
+    pub(crate) metadata_key: Option<[u8; 32]>,
+    pub(crate) dedup_store: Option<PathBuf>,
+    pub(crate) discovery_cache: Option<PathBuf>,
+    pub(crate) salvage_report: Option<PathBuf>,
+    pub(crate) profile: Profile,
+    pub(crate) ephemeral: EphemeralMode,
+    pub(crate) hooks: Hooks,
+    /// Warnings emitted so far, for `--deny-warnings`
+    pub(crate) warnings: Arc<AtomicUsize>,
+    /// Items of the command's one-line summary recorded so far
+    pub(crate) summary: Arc<SummaryItems>,
+    /// Listings of the Git indexes read so far
+    pub(crate) listings: Arc<Listings>,
+}
+
+impl Default for HoldContext {
+    fn default() -> Self {
+        Self {
+            ci: CiMode::Auto.resolve(),
+            size_units: SizeUnits::default(),
+            vcs: VcsBackend::default(),
+            lfs: LfsMode::default(),
+            exclusions: Exclusions::default(),
+            large_files: None,
+            batch_size: DEFAULT_BATCH_SIZE.get(),
+            hash_salt: None,
+            trusted_metadata: false,
+            metadata_key: None,
+            dedup_store: None,
+            discovery_cache: None,
+            salvage_report: None,
+            profile: Profile::default(),
+            ephemeral: EphemeralMode::default(),
+            hooks: Hooks::default(),
+            warnings: Arc::default(),
+            summary: Arc::default(),
+            listings: Arc::default(),
+        }
+    }
+}
+
+impl HoldContext {
+    /// The context of the command line's global options for a command run in
+    /// `working_dir`, loading the hooks of the configuration file
+    #[cfg(feature = "cli")]
+    pub fn new(global: &GlobalOpts, working_dir: &Path) -> Result<Self> {
+        let size_units: SizeUnits = global.size_units().parse()?;
+        let large_file_threshold = global
+            .large_file_threshold()
+            .map(|size| size_units.parse(size))
+            .transpose()?;
+        let large_file_policy = LargeFilePolicy::parse_in(global.large_file_policy(), size_units)?;
+        let hooks = match global.config() {
+            Some(path) => Hooks::load(path, true)?,
+            None => Hooks::load(&working_dir.join(CONFIG_FILENAME), false)?,
+        };
+        Ok(Self {
+            ci: global.ci().parse::<CiMode>()?.resolve(),
+            size_units,
+            vcs: global.vcs().parse()?,
+            lfs: global.lfs().parse()?,
+            exclusions: Exclusions::new(
+                (!global.hash_target_dir()).then_some(global.get_target_dir().as_path()),
+                global.exclude(),
+            ),
+            large_files: large_file_threshold.map(|threshold| (threshold, large_file_policy)),
+            batch_size: global.batch_size().get(),
+            hash_salt: global.hash_salt().map(str::to_string),
+            trusted_metadata: global.trusted_metadata(),
+            metadata_key: global.metadata_key().and_then(crate::metadata::derive_key),
+            dedup_store: global.dedup_store().map(Path::to_path_buf),
+            discovery_cache: global.discovery_cache().map(Path::to_path_buf),
+            salvage_report: global.salvage_report().map(Path::to_path_buf),
+            profile: global.profile().parse()?,
+            ephemeral: global.ephemeral().parse()?,
+            hooks,
+            warnings: Arc::default(),
+            summary: Arc::default(),
+            listings: Arc::default(),
+        })
+    }
+
+    /// The same context for a command run on `target_dir`, sharing its caches
+    /// but counting its warnings and summary items apart
+    pub(crate) fn for_target_dir(&self, target_dir: &Path) -> Self {
+        Self {
+            exclusions: self.exclusions.clone().with_target_dir(target_dir),
+            warnings: Arc::default(),
+            summary: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The working copy containing `path`, opened with the `--vcs` backend
+    pub(crate) fn open_vcs(&self, path: &Path) -> Result<Box<dyn Vcs>> {
+        self.vcs.open(path)
+    }
+
+    /// A logger printing at `verbose` unless `quiet`, counting its warnings
+    /// in this context
+    pub fn logger(&self, verbose: u8, quiet: bool) -> Logger {
+        Logger::with_context(verbose, quiet, self)
+    }
+
+    /// Number of warnings emitted in this context so far
+    pub fn warnings_emitted(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    /// Count a warning that was reported to the user
+    pub(crate) fn record_warning(&self) {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Policy for hashing a tracked file of `size` bytes; without a
+    /// `--large-file-threshold` every file is hashed in full
+    pub(crate) fn large_file_policy(&self, size: u64) -> LargeFilePolicy {
+        match self.large_files {
+            Some((threshold, policy)) if size > threshold => policy,
+            _ => LargeFilePolicy::Full,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Cache of the paths listed by a Git index.
+//!
+//! Listing the index of a large repository takes seconds, and `anchor` lists
+//! it twice: to salvage timestamps and to stow the new state. The listing is
+//! kept in the command's [`HoldContext`] and, with `--discovery-cache`, in a
+//! file for later invocations, keyed by the [`IndexStamp`] of the index it was
+//! read from, so it is only read again once the index changes.
+//!
+//! The file is signed like the metadata when the command has a metadata key.
+//! Being a cache, a file that cannot be read or written is ignored with a
+//! warning.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use rkyv::rancor::BoxedError;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::{TrackedPath, Vcs};
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::metadata::{read_signed, write_signed};
+
+/// Bytes at the end of the index stamped: its SHA-256 checksum, or its SHA-1
+/// checksum and the last bytes of its contents
+const CHECKSUM_LEN: u64 = 32;
+
+/// Listings read in a context, by working copy root
+pub(crate) type Listings = Mutex<HashMap<PathBuf, Listing>>;
+
+/// Identifies the contents of an index file: Git rewrites the whole index,
+/// ending with a checksum of its contents, whenever it changes
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexStamp {
+    size: u64,
+    mtime_nanos: u64,
+    checksum: Vec<u8>,
+}
+
+impl IndexStamp {
+    /// Stamp of the index file at `path`, if it can be read
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let mut file = File::open(path).ok()?;
+        let metadata = file.metadata().ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let size = metadata.len();
+        file.seek(SeekFrom::Start(size.saturating_sub(CHECKSUM_LEN)))
+            .ok()?;
+        let mut checksum = Vec::new();
+        file.read_to_end(&mut checksum).ok()?;
+        Some(Self {
+            size,
+            mtime_nanos: u64::try_from(mtime.as_nanos()).unwrap_or(u64::MAX),
+            checksum,
+        })
+    }
+}
+
+/// The paths listed by an index, as stored in the cache file
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Listing {
+    /// Root of the working copy
+    root: String,
+    stamp: IndexStamp,
+    /// Each path relative to the root, and whether it is a conflict stage
+    paths: Vec<(String, bool)>,
+}
+
+impl Listing {
+    fn paths(&self) -> Vec<TrackedPath> {
+        self.paths
+            .iter()
+            .map(|(path, conflicted)| TrackedPath {
+                path: PathBuf::from(path),
+                conflicted: *conflicted,
+            })
+            .collect()
+    }
+}
+
+/// [`Vcs::tracked_paths`], reusing the listing of an unchanged index read
+/// earlier in `context` or, with `--discovery-cache`, by an earlier
+/// invocation
+pub(crate) fn tracked_paths(context: &HoldContext, vcs: &dyn Vcs) -> Result<Vec<TrackedPath>> {
+    cached_tracked_paths(
+        context,
+        vcs,
+        &context.listings,
+        context.discovery_cache.as_deref(),
+    )
+}
+
+/// [`tracked_paths`] with the listings in `memory` and the cache `file`
+pub(super) fn cached_tracked_paths(
+    context: &HoldContext,
+    vcs: &dyn Vcs,
+    memory: &Listings,
+    file: Option<&Path>,
+) -> Result<Vec<TrackedPath>> {
+    // Stamped before listing, so an index changing meanwhile is listed again
+    // next time
+    let (Some(stamp), Some(root)) = (vcs.index_stamp(), vcs.root().to_str()) else {
+        return vcs.tracked_paths();
+    };
+    let matches = |listing: &Listing| listing.root == root && listing.stamp == stamp;
+
+    let remember = |listing: Listing| {
+        memory
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(vcs.root().to_path_buf(), listing);
+    };
+
+    if let Some(listing) = memory
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(vcs.root())
+        .filter(|listing| matches(listing))
+    {
+        return Ok(listing.paths());
+    }
+    if let Some(listing) = file.and_then(|file| load(context, file)).filter(matches) {
+        let paths = listing.paths();
+        remember(listing);
+        return Ok(paths);
+    }
+
+    let paths = vcs.tracked_paths()?;
+    let listing = Listing {
+        root: root.to_string(),
+        stamp,
+        paths: paths
+            .iter()
+            .map(|entry| (entry.path.to_string_lossy().into_owned(), entry.conflicted))
+            .collect(),
+    };
+    if let Some(file) = file
+        && let Err(e) = save(context, &listing, file)
+    {
+        warn(
+            context,
+            format!("Could not save discovery cache {}: {e}", file.display()),
+        );
+    }
+    remember(listing);
+    Ok(paths)
+}
+
+/// The listing in the cache file at `path`, if there is a readable one
+fn load(context: &HoldContext, path: &Path) -> Option<Listing> {
+    let listing = read_signed(path, context.metadata_key.as_ref(), |bytes| {
+        rkyv::from_bytes::<Listing, BoxedError>(bytes).map_err(HoldError::DeserializationError)
+    });
+    match listing {
+        Ok(listing) => listing,
+        Err(e) => {
+            warn(
+                context,
+                format!("Ignoring discovery cache {}: {e}", path.display()),
+            );
+            None
+        }
+    }
+}
+
+fn save(context: &HoldContext, listing: &Listing, path: &Path) -> Result<()> {
+    let bytes = rkyv::to_bytes::<BoxedError>(listing)
+        .map_err(|e| HoldError::SerializationError(Box::new(e)))?;
+    write_signed(path, &bytes, context.metadata_key.as_ref())
+}
+
+fn warn(context: &HoldContext, message: String) {
+    context.record_warning();
+    eprintln!("Warning: {message}");
+}
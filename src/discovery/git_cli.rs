@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::{
+    IndexStamp, TrackedPath, Vcs, index_path, listing, printed_root, printed_url, run,
+    run_with_input,
+};
+use crate::error::{HoldError, Result};
+
+/// A Git working copy read by running `git`
+pub(crate) struct GitCli {
+    root: PathBuf,
+}
+
+impl GitCli {
+    /// Open the repository at or above `path`
+    pub(crate) fn discover(path: &Path) -> Result<Self> {
+        // Fails outside a repository and in bare repositories, which have no
+        // files to track
+        let output = run("git", path, &["rev-parse", "--show-toplevel"])?;
+        if !output.status.success() {
+            return Err(HoldError::RepoNotFound(path.to_path_buf()));
+        }
+
+        Ok(Self {
+            root: printed_root(output)?,
+        })
+    }
+}
+
+impl Vcs for GitCli {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn tracked_paths(&self) -> Result<Vec<TrackedPath>> {
+        let output = run("git", &self.root, &["ls-files", "--stage", "-z"])?;
+        parse_ls_files(&listing("git ls-files", output)?)
+    }
+
+    fn index_stamp(&self) -> Option<IndexStamp> {
+        // Honors GIT_INDEX_FILE and linked worktrees
+        let output = run("git", &self.root, &["rev-parse", "--git-path", "index"])
+            .ok()
+            .filter(|output| output.status.success())?;
+        let index = printed_root(output).ok()?;
+        IndexStamp::read(&self.root.join(index))
+    }
+
+    fn lfs_paths(&self, paths: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(path.as_os_str().as_encoded_bytes());
+            input.push(0);
+        }
+        let output = run_with_input(
+            "git",
+            &self.root,
+            &["check-attr", "--stdin", "-z", "filter"],
+            &input,
+        )?;
+        parse_check_attr(&listing("git check-attr", output)?)
+    }
+
+    fn origin_url(&self) -> Option<String> {
+        printed_url(run("git", &self.root, &["remote", "get-url", "origin"]).ok()?)
+    }
+}
+
+/// Parse the output of `git ls-files --stage -z`: one
+/// `<mode> <object> <stage>\t<path>` record per entry, NUL terminated.
+///
+/// Submodules (mode 160000) are left out. Entries with a stage other than 0
+/// are conflict stages.
+pub(crate) fn parse_ls_files(output: &[u8]) -> Result<Vec<TrackedPath>> {
+    output
+        .split(|&b| b == 0)
+        .filter(|record| !record.is_empty())
+        .filter(|record| !record.starts_with(b"160000 "))
+        .map(|record| {
+            let unexpected = || {
+                HoldError::VcsCommandFailed(format!(
+                    "Unexpected git ls-files output: {}",
+                    String::from_utf8_lossy(record)
+                ))
+            };
+            let tab = record
+                .iter()
+                .position(|&b| b == b'\t')
+                .ok_or_else(unexpected)?;
+            let stage = record[..tab]
+                .rsplit(|&b| b == b' ')
+                .next()
+                .ok_or_else(unexpected)?;
+            Ok(TrackedPath {
+                path: index_path(&record[tab + 1..])?,
+                conflicted: stage != b"0",
+            })
+        })
+        .collect()
+}
+
+/// Parse the output of `git check-attr -z filter`: `<path>`, `filter`, and the
+/// attribute's value per path, each NUL terminated, into the paths whose
+/// filter is `lfs`
+pub(crate) fn parse_check_attr(output: &[u8]) -> Result<HashSet<PathBuf>> {
+    let unexpected = || {
+        HoldError::VcsCommandFailed(format!(
+            "Unexpected git check-attr output: {}",
+            String::from_utf8_lossy(output)
+        ))
+    };
+    if output.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let fields: Vec<&[u8]> = output
+        .strip_suffix(b"\0")
+        .ok_or_else(unexpected)?
+        .split(|&b| b == 0)
+        .collect();
+    if !fields.len().is_multiple_of(3) {
+        return Err(unexpected());
+    }
+
+    fields
+        .chunks_exact(3)
+        .filter(|record| record[2] == b"lfs")
+        .map(|record| index_path(record[0]))
+        .collect()
+}
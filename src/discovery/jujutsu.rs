@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use super::{TrackedPath, Vcs, index_path, listing, printed_root, run};
+use crate::error::{HoldError, Result};
+
+/// A Jujutsu working copy read by running `jj`, including working copies
+/// colocated with a Git repository
+pub(crate) struct Jujutsu {
+    root: PathBuf,
+}
+
+impl Jujutsu {
+    /// Open the repository at or above `path`
+    pub(crate) fn discover(path: &Path) -> Result<Self> {
+        let output = run("jj", path, &["root"])?;
+        if !output.status.success() {
+            return Err(HoldError::RepoNotFound(path.to_path_buf()));
+        }
+
+        Ok(Self {
+            root: printed_root(output)?,
+        })
+    }
+}
+
+impl Vcs for Jujutsu {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn tracked_paths(&self) -> Result<Vec<TrackedPath>> {
+        // Snapshots the working copy first, so new files that are not ignored
+        // are tracked, as in any other jj command. Run at the root, paths are
+        // printed relative to it, one per line.
+        let output = run("jj", &self.root, &["file", "list", "--color", "never"])?;
+        parse_file_list(&listing("jj file list", output)?)
+    }
+
+    fn origin_url(&self) -> Option<String> {
+        let output = run("jj", &self.root, &["git", "remote", "list"]).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_remote_list(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// The URL of `origin` in the output of `jj git remote list`, one
+/// `<name> <url>` line per remote
+pub(crate) fn parse_remote_list(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("origin "))
+        .map(|url| url.trim().to_string())
+}
+
+/// Parse the output of `jj file list`, one path per line
+pub(crate) fn parse_file_list(output: &[u8]) -> Result<Vec<TrackedPath>> {
+    output
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|path| !path.is_empty())
+        .map(|path| index_path(path).map(TrackedPath::resolved))
+        .collect()
+}
@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use git2::{AttrCheckFlags, Repository};
+
+use super::{IndexStamp, TrackedPath, Vcs, index_path};
+use crate::error::{HoldError, Result};
+
+/// Bits of an index entry's flags holding its merge stage, which is 0 unless
+/// the path has a conflict
+const INDEX_ENTRY_STAGE_MASK: u16 = 0x3000;
+
+/// A Git working copy read with libgit2
+pub(crate) struct Libgit2 {
+    repo: Repository,
+    root: PathBuf,
+}
+
+impl Libgit2 {
+    /// Open the repository at or above `path`
+    pub(crate) fn discover(path: &Path) -> Result<Self> {
+        let repo =
+            Repository::discover(path).map_err(|_| HoldError::RepoNotFound(path.to_path_buf()))?;
+        // Bare repositories have no files to track
+        let root = repo
+            .workdir()
+            .ok_or_else(|| HoldError::RepoNotFound(path.to_path_buf()))?
+            .to_path_buf();
+
+        Ok(Self { repo, root })
+    }
+}
+
+impl Vcs for Libgit2 {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn tracked_paths(&self) -> Result<Vec<TrackedPath>> {
+        let index = self.repo.index().map_err(HoldError::IndexError)?;
+
+        index
+            .iter()
+            // Skip submodules (mode 160000) - they appear as directories in the
+            // filesystem but are special entries in git that we can't set
+            // timestamps on
+            .filter(|entry| entry.mode != 0o160000)
+            .map(|entry| {
+                Ok(TrackedPath {
+                    path: index_path(&entry.path)?,
+                    conflicted: entry.flags & INDEX_ENTRY_STAGE_MASK != 0,
+                })
+            })
+            .collect()
+    }
+
+    fn index_stamp(&self) -> Option<IndexStamp> {
+        IndexStamp::read(&self.repo.path().join("index"))
+    }
+
+    fn lfs_paths(&self, paths: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+        let mut lfs = HashSet::new();
+        for path in paths {
+            let filter = self
+                .repo
+                .get_attr(path, "filter", AttrCheckFlags::FILE_THEN_INDEX)
+                .map_err(HoldError::IndexError)?;
+            if filter == Some("lfs") {
+                lfs.insert(path.clone());
+            }
+        }
+        Ok(lfs)
+    }
+
+    fn origin_url(&self) -> Option<String> {
+        let remote = self.repo.find_remote("origin").ok()?;
+        remote.url().map(str::to_string)
+    }
+}
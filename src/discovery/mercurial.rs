@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use super::{TrackedPath, Vcs, index_path, listing, printed_root, printed_url, run};
+use crate::error::{HoldError, Result};
+
+/// A Mercurial working copy read by running `hg`
+pub(crate) struct Mercurial {
+    root: PathBuf,
+}
+
+impl Mercurial {
+    /// Open the repository at or above `path`
+    pub(crate) fn discover(path: &Path) -> Result<Self> {
+        let output = run("hg", path, &["root"])?;
+        if !output.status.success() {
+            return Err(HoldError::RepoNotFound(path.to_path_buf()));
+        }
+
+        Ok(Self {
+            root: printed_root(output)?,
+        })
+    }
+}
+
+impl Vcs for Mercurial {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn tracked_paths(&self) -> Result<Vec<TrackedPath>> {
+        // Run at the root, `hg files` prints paths relative to it; files of
+        // subrepositories are only listed with --subrepos
+        let output = run("hg", &self.root, &["files", "--print0"])?;
+        parse_files(&listing("hg files", output)?)
+    }
+
+    fn origin_url(&self) -> Option<String> {
+        printed_url(run("hg", &self.root, &["paths", "default"]).ok()?)
+    }
+}
+
+/// Parse NUL terminated paths, as printed by `hg files --print0`
+pub(crate) fn parse_files(output: &[u8]) -> Result<Vec<TrackedPath>> {
+    output
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| index_path(path).map(TrackedPath::resolved))
+        .collect()
+}
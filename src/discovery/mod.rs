@@ -0,0 +1,484 @@
+//! Discovery of the files tracked by version control.
+//!
+//! Each version control system is a [`Vcs`] backend; the backend is selected
+//! with `--vcs` or detected from the working copy, and commands open it
+//! through their [`HoldContext`]. Tracked files below the
+//! target directory or an `--exclude` path are left out (see [`Exclusions`]).
+//! Git listings are cached until the index changes (see [`cache`]).
+
+mod cache;
+mod git_cli;
+mod jujutsu;
+#[cfg(feature = "git")]
+mod libgit2;
+mod mercurial;
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::str::FromStr;
+
+use cache::IndexStamp;
+pub(crate) use cache::Listings;
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::lfs::LfsMode;
+
+/// A working copy whose tracked files cargo-hold manages
+pub(crate) trait Vcs {
+    /// Absolute path of the root of the working copy
+    fn root(&self) -> &Path;
+
+    /// Tracked files relative to the root in index order, leaving out
+    /// submodules. A path with merge conflicts may be listed once per
+    /// conflict stage.
+    fn tracked_paths(&self) -> Result<Vec<TrackedPath>>;
+
+    /// Stamp of the index [`Vcs::tracked_paths`] reads, if its listing may
+    /// be cached until the stamp changes
+    fn index_stamp(&self) -> Option<IndexStamp> {
+        None
+    }
+
+    /// Those of `paths` whose `filter` attribute is `lfs`, i.e. that Git LFS
+    /// manages; only Git knows about Git LFS
+    fn lfs_paths(&self, _paths: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+        Ok(HashSet::new())
+    }
+
+    /// URL of the `origin` remote (Mercurial's `default` path), if the
+    /// working copy has one
+    fn origin_url(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A path listed by a [`Vcs`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TrackedPath {
+    /// Path relative to the root of the working copy
+    pub(crate) path: PathBuf,
+    /// Whether the entry is a stage of an unresolved merge conflict
+    pub(crate) conflicted: bool,
+}
+
+impl TrackedPath {
+    /// An entry without merge conflicts
+    pub(crate) fn resolved(path: PathBuf) -> Self {
+        Self {
+            path,
+            conflicted: false,
+        }
+    }
+}
+
+/// Tracked files found by [`discover_tracked_files`]
+#[derive(Debug)]
+pub(crate) struct TrackedFiles {
+    /// Absolute path of the repository root
+    pub(crate) repo_root: PathBuf,
+    /// Paths of the files relative to the repository root
+    pub(crate) files: Vec<PathBuf>,
+    /// Tracked symbolic links that were left out
+    pub(crate) symlinks: usize,
+    /// Tracked files left out for being below an excluded path
+    pub(crate) excluded: usize,
+    /// Paths with unresolved merge conflicts, listed once in `files` so that
+    /// their working tree contents are hashed
+    pub(crate) conflicted: usize,
+    /// Conflicted paths left out because the working tree has no file for
+    /// them, e.g. after a modify/delete conflict
+    pub(crate) conflicts_skipped: usize,
+    /// Files managed by Git LFS, to be hashed as pointers; empty unless
+    /// [`LfsMode::Pointer`] is used
+    pub(crate) lfs: HashSet<PathBuf>,
+    /// The repository's origin, normalized by [`normalize_origin_url`]
+    pub(crate) origin: Option<String>,
+}
+
+/// Tracked paths left out of discovery.
+///
+/// Files committed below the target directory, e.g. vendored build outputs,
+/// are left out by default: `heave` deletes and rewrites files there, so
+/// hashing them would have every stow record changes garbage collection made.
+/// `--hash-target-dir` keeps them, and `--exclude` leaves out further paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Exclusions {
+    /// Target directory whose tracked files are left out
+    target_dir: Option<PathBuf>,
+    /// Paths relative to the repository root left out with everything below
+    /// them
+    paths: Vec<PathBuf>,
+}
+
+impl Exclusions {
+    /// Leave out the tracked files below `target_dir`, if given, and below
+    /// each of `paths`, relative to the repository root
+    pub(crate) fn new(target_dir: Option<&Path>, paths: &[PathBuf]) -> Self {
+        Self {
+            target_dir: target_dir.map(Path::to_path_buf),
+            paths: paths
+                .iter()
+                .map(|path| {
+                    path.components()
+                        .filter(|component| *component != Component::CurDir)
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// The same exclusions for a command run on `target_dir`, unless tracked
+    /// files below the target directory are hashed
+    pub(crate) fn with_target_dir(mut self, target_dir: &Path) -> Self {
+        if self.target_dir.is_some() {
+            self.target_dir = Some(target_dir.to_path_buf());
+        }
+        self
+    }
+
+    /// Excluded paths relative to `repo_root`; the target directory only
+    /// counts when it lies inside the repository
+    fn prefixes(&self, repo_root: &Path) -> Vec<PathBuf> {
+        let target_dir = self
+            .target_dir
+            .as_deref()
+            .and_then(|dir| relative_to(dir, repo_root));
+        self.paths
+            .iter()
+            .cloned()
+            .chain(target_dir)
+            .filter(|prefix| !prefix.as_os_str().is_empty())
+            .collect()
+    }
+}
+
+/// `path` relative to `root`, comparing canonical paths when they differ
+/// only by symbolic links
+fn relative_to(path: &Path, root: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(root) {
+        return Some(relative.to_path_buf());
+    }
+    let path = path.canonicalize().ok()?;
+    let root = root.canonicalize().ok()?;
+    path.strip_prefix(root).ok().map(Path::to_path_buf)
+}
+
+/// How tracked files are discovered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VcsBackend {
+    /// Jujutsu when the working copy has a `.jj` directory, Mercurial when it
+    /// has a `.hg` directory, Git otherwise: libgit2 when cargo-hold is built
+    /// with the `git` feature, the `git` command otherwise
+    #[default]
+    Auto,
+    /// Read the Git index with libgit2
+    #[cfg(feature = "git")]
+    Libgit2,
+    /// Run `git ls-files`, which copes with partial clones and needs no
+    /// native library
+    GitCli,
+    /// Run `hg files`
+    Mercurial,
+    /// Run `jj file list`, also for Jujutsu working copies colocated with Git
+    Jujutsu,
+}
+
+impl VcsBackend {
+    /// Resolve [`VcsBackend::Auto`] to Jujutsu or Mercurial when the working
+    /// copy containing `path` is one; Git working copies stay `Auto`.
+    ///
+    /// The nearest directory with a `.jj`, `.hg`, or `.git` entry decides; a
+    /// Jujutsu working copy colocated with Git has both `.jj` and `.git`.
+    pub(crate) fn resolve(self, path: &Path) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        for dir in path.ancestors() {
+            if dir.join(".jj").is_dir() {
+                return Self::Jujutsu;
+            }
+            if dir.join(".hg").is_dir() {
+                return Self::Mercurial;
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        Self::Auto
+    }
+
+    /// Open the working copy containing `path`
+    pub(crate) fn open(self, path: &Path) -> Result<Box<dyn Vcs>> {
+        match self.resolve(path) {
+            #[cfg(feature = "git")]
+            Self::Auto | Self::Libgit2 => Ok(Box::new(libgit2::Libgit2::discover(path)?)),
+            #[cfg(not(feature = "git"))]
+            Self::Auto => Ok(Box::new(git_cli::GitCli::discover(path)?)),
+            Self::GitCli => Ok(Box::new(git_cli::GitCli::discover(path)?)),
+            Self::Mercurial => Ok(Box::new(mercurial::Mercurial::discover(path)?)),
+            Self::Jujutsu => Ok(Box::new(jujutsu::Jujutsu::discover(path)?)),
+        }
+    }
+}
+
+impl FromStr for VcsBackend {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            #[cfg(feature = "git")]
+            "libgit2" => Ok(Self::Libgit2),
+            "git" => Ok(Self::GitCli),
+            "hg" => Ok(Self::Mercurial),
+            "jj" => Ok(Self::Jujutsu),
+            _ => Err(HoldError::InvalidVcs(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for VcsBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            #[cfg(feature = "git")]
+            Self::Libgit2 => f.write_str("libgit2"),
+            Self::GitCli => f.write_str("git"),
+            Self::Mercurial => f.write_str("hg"),
+            Self::Jujutsu => f.write_str("jj"),
+        }
+    }
+}
+
+/// Discovers all tracked files in the repository containing `repo_path`.
+///
+/// Uses the backend of `context` (see [`HoldContext::open_vcs`]) to list the
+/// files tracked by version control, automatically respecting ignore rules.
+/// The returned paths are relative to the repository root. Symbolic links,
+/// files that cannot be accessed, and files below the exclusions of
+/// `context` are left out.
+///
+/// During a conflicted merge or rebase the index has an entry per conflict
+/// stage; each conflicted path is listed once, and its working tree version is
+/// what gets hashed.
+///
+/// With [`LfsMode::Pointer`] in `context`, the files managed by Git LFS are
+/// looked up as well.
+///
+/// # Arguments
+///
+/// * `context` - The context of the command, with its backend, exclusions, and
+///   caches
+/// * `repo_path` - A path within the repository (will search upward for the
+///   repo root)
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - No repository is found at or above the given path
+/// - The tracked files cannot be listed
+/// - Any file path contains invalid UTF-8
+pub(crate) fn discover_tracked_files(
+    context: &HoldContext,
+    repo_path: &Path,
+) -> Result<TrackedFiles> {
+    discover_tracked_files_in(context, context.open_vcs(repo_path)?.as_ref(), context.lfs)
+}
+
+/// Root of the working copy containing `path` with `backend`, if there is
+/// one
+pub(crate) fn repo_root(backend: VcsBackend, path: &Path) -> Option<PathBuf> {
+    let vcs = backend.open(path).ok()?;
+    // Collecting the components drops the trailing slash libgit2 reports
+    Some(vcs.root().components().collect())
+}
+
+/// [`discover_tracked_files`] with an explicit backend and LFS mode
+#[cfg(test)]
+pub(crate) fn discover_tracked_files_with(
+    context: &HoldContext,
+    backend: VcsBackend,
+    lfs: LfsMode,
+    repo_path: &Path,
+) -> Result<TrackedFiles> {
+    discover_tracked_files_in(context, backend.open(repo_path)?.as_ref(), lfs)
+}
+
+/// Tracked files of the working copy `vcs`, looking up the files managed by
+/// Git LFS with [`LfsMode::Pointer`]
+fn discover_tracked_files_in(
+    context: &HoldContext,
+    vcs: &dyn Vcs,
+    lfs: LfsMode,
+) -> Result<TrackedFiles> {
+    let mut tracked = TrackedFiles {
+        repo_root: vcs.root().to_path_buf(),
+        files: Vec::new(),
+        symlinks: 0,
+        excluded: 0,
+        conflicted: 0,
+        conflicts_skipped: 0,
+        lfs: HashSet::new(),
+        origin: vcs.origin_url().as_deref().map(normalize_origin_url),
+    };
+    let excluded = context.exclusions.prefixes(&tracked.repo_root);
+    let paths = merge_conflict_stages(cache::tracked_paths(context, vcs)?);
+    tracked.files.reserve(paths.len());
+
+    for TrackedPath { path, conflicted } in paths {
+        if excluded.iter().any(|prefix| path.starts_with(prefix)) {
+            tracked.excluded += 1;
+            continue;
+        }
+        tracked.conflicted += usize::from(conflicted);
+
+        // Check if the file is a symlink in the actual filesystem
+        let full_path = tracked.repo_root.join(&path);
+        match std::fs::symlink_metadata(&full_path) {
+            Ok(metadata) => {
+                if metadata.is_symlink() {
+                    tracked.symlinks += 1;
+                    continue; // Skip symlinks
+                }
+            }
+            Err(e) if conflicted && e.kind() == std::io::ErrorKind::NotFound => {
+                tracked.conflicts_skipped += 1;
+                continue; // Deleted on one side of the conflict
+            }
+            Err(e) => {
+                context.record_warning();
+                eprintln!(
+                    "Warning: Could not access file '{}': {}. Skipping.",
+                    full_path.display(),
+                    e
+                );
+                continue; // Skip files we can't access
+            }
+        }
+
+        tracked.files.push(path);
+    }
+
+    if lfs == LfsMode::Pointer && !tracked.files.is_empty() {
+        tracked.lfs = vcs.lfs_paths(&tracked.files)?;
+    }
+
+    Ok(tracked)
+}
+
+/// Collapse the conflict stages of a path, which the index lists next to each
+/// other, into a single conflicted entry
+pub(crate) fn merge_conflict_stages(paths: Vec<TrackedPath>) -> Vec<TrackedPath> {
+    let mut merged: Vec<TrackedPath> = Vec::with_capacity(paths.len());
+    for entry in paths {
+        match merged.last_mut() {
+            Some(last) if last.path == entry.path => last.conflicted |= entry.conflicted,
+            _ => merged.push(entry),
+        }
+    }
+    merged
+}
+
+/// Convert a path from the index to a `PathBuf`
+fn index_path(path: &[u8]) -> Result<PathBuf> {
+    let path = std::str::from_utf8(path).map_err(|e| HoldError::InvalidPath {
+        message: format!("Invalid UTF-8 in path: {e}"),
+    })?;
+    Ok(PathBuf::from(path))
+}
+
+/// Run a version control command in `dir`
+fn run(program: &str, dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        // Keeps user configuration from changing Mercurial's output
+        .env("HGPLAIN", "1")
+        .output()
+        .map_err(|e| HoldError::VcsCommandFailed(format!("Could not run {program}: {e}")))
+}
+
+/// Run a version control command in `dir`, writing `input` to its standard
+/// input
+fn run_with_input(program: &str, dir: &Path, args: &[&str], input: &[u8]) -> Result<Output> {
+    let failed = |e| HoldError::VcsCommandFailed(format!("Could not run {program}: {e}"));
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+
+    // Written from another thread so that a full stdout pipe cannot block the
+    // command while it waits for more input
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input));
+        let output = child.wait_with_output().map_err(failed)?;
+        match writer.join().expect("stdin writer panicked") {
+            // The command exited without reading everything; its status says why
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(failed(e)),
+            _ => Ok(output),
+        }
+    })
+}
+
+/// Standard output of a command listing the tracked files
+fn listing(command: &str, output: Output) -> Result<Vec<u8>> {
+    if !output.status.success() {
+        return Err(HoldError::VcsCommandFailed(format!(
+            "{command} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// The repository a remote URL points to as `host/path`, lowercase and
+/// without credentials or a `.git` suffix, so that HTTPS and SSH clones of a
+/// repository agree
+pub(crate) fn normalize_origin_url(url: &str) -> String {
+    let url = url.trim();
+    let (rest, scp_like) = match url.split_once("://") {
+        Some((_, rest)) => (rest, false),
+        None => (url, true),
+    };
+    let authority = &rest[..rest.find('/').unwrap_or(rest.len())];
+    let rest = match authority.rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    // `git@host:org/repo` names the path after a colon
+    let rest = if scp_like {
+        rest.replacen(':', "/", 1)
+    } else {
+        rest.to_string()
+    };
+    rest.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .trim_end_matches('/')
+        .to_lowercase()
+}
+
+/// A URL printed on a line of its own by a successful command
+fn printed_url(output: Output) -> Option<String> {
+    let url = String::from_utf8(output.stdout).ok()?;
+    let url = url.trim();
+    (output.status.success() && !url.is_empty()).then(|| url.to_string())
+}
+
+/// Working copy root printed on a line of its own
+fn printed_root(output: Output) -> Result<PathBuf> {
+    let root = String::from_utf8(output.stdout).map_err(|e| HoldError::InvalidPath {
+        message: format!("Invalid UTF-8 in repository root: {e}"),
+    })?;
+    Ok(PathBuf::from(root.trim_end_matches(['\r', '\n'])))
+}
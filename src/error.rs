@@ -7,7 +7,9 @@
 //! # Error Handling Strategy
 //!
 //! - All errors derive from [`HoldError`]
-//! - Each variant includes helpful error messages and diagnostic codes
+//! - Each variant has a stable diagnostic code (e.g.
+//!   `cargo_hold::metadata::version_too_new`) that CI tooling can match on, a
+//!   help suggestion, and a URL pointing at the variant's documentation
 //! - Context is preserved through the error chain
 //! - Errors are automatically converted to `miette::Result` for CLI output
 //!
@@ -37,13 +39,18 @@ use thiserror::Error;
 pub enum HoldError {
     /// Git repository not found in the current directory or any parent.
     ///
-    /// Raised when `git2::Repository::discover()` fails or when the repository
-    /// is bare (no working directory). cargo-hold requires a Git repository to
-    /// determine which files to track for timestamp management.
+    /// Raised when no repository is found above the working directory or when
+    /// the repository is bare (no working directory). cargo-hold requires a Git
+    /// repository to determine which files to track for timestamp
+    /// management.
     #[error("Git repository not found in '{0}' or any parent directories")]
     #[diagnostic(
         code(cargo_hold::git::repo_not_found),
-        help("Ensure 'cargo hold' is run from within a Git repository.")
+        help("Ensure 'cargo hold' is run from within a Git repository."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.RepoNotFound"
+        )
     )]
     RepoNotFound(
         /// The path where the Git repository was searched for
@@ -54,18 +61,58 @@ pub enum HoldError {
     ///
     /// Wraps errors from `repo.index()` when cargo-hold tries to read
     /// the list of files tracked by Git. The Git index contains the staged
-    /// and tracked files that cargo-hold needs to manage.
+    /// and tracked files that cargo-hold needs to manage. Only present with the
+    /// `git` feature.
+    #[cfg(feature = "git")]
     #[error("Failed to access Git index")]
-    #[diagnostic(code(cargo_hold::git::index_error))]
+    #[diagnostic(
+        code(cargo_hold::git::index_error),
+        help("Check that the Git repository is not corrupted, e.g. with 'git status'."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.IndexError"
+        )
+    )]
     IndexError(#[from] git2::Error),
 
+    /// The version control command listing the tracked files failed.
+    ///
+    /// Raised by the `git`, `hg`, and `jj` backends of `--vcs` when the
+    /// command cannot be run or fails inside a repository.
+    #[error("Failed to list tracked files: {0}")]
+    #[diagnostic(
+        code(cargo_hold::vcs::command_failed),
+        help(
+            "Check that the version control tool is installed and works in the repository, e.g. \
+             with 'git ls-files', 'hg files', or 'jj file list'."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.VcsCommandFailed"
+        )
+    )]
+    VcsCommandFailed(
+        /// Description of the failure
+        String,
+    ),
+
     /// File system I/O error during cargo-hold operations.
     ///
     /// Common causes: permission denied, file not found, disk full,
     /// or memory mapping failures. Used throughout for file operations,
     /// directory creation/removal, and metadata access.
     #[error("I/O error accessing '{path}'")]
-    #[diagnostic(code(cargo_hold::io_error))]
+    #[diagnostic(
+        code(cargo_hold::io_error),
+        help(
+            "Check that the path exists, that you have permission to access it, and that the \
+             disk is not full."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.IoError"
+        )
+    )]
     IoError {
         /// The path that caused the I/O error
         path: PathBuf,
@@ -85,6 +132,10 @@ pub enum HoldError {
         help(
             "An internal error occurred while trying to save the metadata. Try running 'cargo \
              hold bilge' to reset."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.SerializationError"
         )
     )]
     SerializationError(#[source] Box<dyn std::error::Error + Send + Sync>),
@@ -97,7 +148,11 @@ pub enum HoldError {
     #[error("Failed to deserialize metadata: {0}")]
     #[diagnostic(
         code(cargo_hold::metadata::deserialization_error),
-        help("The metadata file may be corrupted. Run 'cargo hold bilge' to reset it.")
+        help("The metadata file may be corrupted. Run 'cargo hold bilge' to reset it."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.DeserializationError"
+        )
     )]
     DeserializationError(
         /// The underlying deserialization error
@@ -111,7 +166,14 @@ pub enum HoldError {
     /// strings fails. All paths tracked by Git must be valid UTF-8 for
     /// cargo-hold to process them.
     #[error("Invalid path: {message}")]
-    #[diagnostic(code(cargo_hold::path::invalid))]
+    #[diagnostic(
+        code(cargo_hold::path::invalid),
+        help("File paths tracked by Git must be valid UTF-8."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidPath"
+        )
+    )]
     InvalidPath {
         /// Description of why the path is invalid
         message: String,
@@ -125,7 +187,11 @@ pub enum HoldError {
     #[error("Invalid file type for '{0}': {1}")]
     #[diagnostic(
         code(cargo_hold::file::invalid_type),
-        help("cargo-hold only processes regular files tracked by Git.")
+        help("cargo-hold only processes regular files tracked by Git."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidFileType"
+        )
     )]
     InvalidFileType(
         /// The path of the invalid file
@@ -142,7 +208,11 @@ pub enum HoldError {
     #[error("Failed to set file modification time for '{0}'")]
     #[diagnostic(
         code(cargo_hold::timestamp::set_error),
-        help("Ensure you have write permissions for the file.")
+        help("Ensure you have write permissions for the file."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.SetTimestampError"
+        )
     )]
     SetTimestampError(
         /// The file whose timestamp couldn't be set
@@ -160,7 +230,11 @@ pub enum HoldError {
     #[error("Failed to create metadata directory '{0}'")]
     #[diagnostic(
         code(cargo_hold::metadata::create_dir_error),
-        help("Ensure you have write permissions for the parent directory.")
+        help("Ensure you have write permissions for the parent directory."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.CreateMetadataDirError"
+        )
     )]
     CreateMetadataDirError(
         /// The directory path that couldn't be created
@@ -172,15 +246,21 @@ pub enum HoldError {
 
     /// Invalid size specification for --max-target-size.
     ///
-    /// Raised when parsing size strings like "5G" or "500M" fails.
+    /// Raised when parsing size strings like "5G", "500M" or "40%" fails.
     /// Valid suffixes are B (bytes), K (kilobytes), M (megabytes),
-    /// G (gigabytes), or T (terabytes). Numbers without suffix are bytes.
+    /// G (gigabytes), or T (terabytes). Numbers without suffix are bytes, and
+    /// a percentage is of the filesystem holding the target directory. Sizes
+    /// of zero and percentages above 100% are rejected.
     #[error("Invalid metadata size: '{0}' - {1}")]
     #[diagnostic(
         code(cargo_hold::gc::invalid_metadata_size),
         help(
             "Specify metadata size as a number with optional suffix (e.g., '5G', '500M', '1024K', \
-             or raw bytes)"
+             or raw bytes), or as a percentage of the filesystem (e.g., '40%')"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidMetadataSize"
         )
     )]
     InvalidMetadataSize(
@@ -190,6 +270,780 @@ pub enum HoldError {
         String,
     ),
 
+    /// Invalid duration specification for a preservation knob.
+    ///
+    /// Raised when parsing duration strings like "2h" or "30m" fails.
+    /// Valid suffixes are s (seconds), m (minutes), h (hours), d (days),
+    /// or w (weeks). Numbers without suffix are seconds.
+    #[error("Invalid duration: '{0}' - {1}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_duration),
+        help("Specify a duration as a number with optional suffix (e.g., '2h', '30m', '90s')"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidDuration"
+        )
+    )]
+    InvalidDuration(
+        /// The invalid duration value provided
+        String,
+        /// Description of the parsing error
+        String,
+    ),
+
+    /// Invalid per-area age threshold for --age-threshold-for.
+    ///
+    /// Raised when the value is not `AREA=DURATION` or names an unknown area.
+    #[error("Invalid age threshold: '{0}' - {1}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_age_threshold),
+        help(
+            "Use AREA=DURATION, AREA=evict, or (for tool outputs) AREA=keep with one of the \
+             areas deps, incremental, registry-cache, registry-src, git, bin, coverage, \
+             nextest, trybuild, snapshots, dx, wasm-bindgen, or trunk (e.g., 'deps=12h', \
+             'registry-cache=90d', 'dx=keep')"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidAgeThreshold"
+        )
+    )]
+    InvalidAgeThreshold(
+        /// The invalid value provided
+        String,
+        /// Description of the problem
+        String,
+    ),
+
+    /// Invalid candidate policy for heave --policy.
+    ///
+    /// Raised when the policy is not a list of `KEY=VALUE` settings, names
+    /// an unknown setting, or gives a setting an invalid value.
+    #[error("Invalid simulated policy: '{0}' - {1}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_simulated_policy),
+        help(
+            "Use comma-separated settings among cap=SIZE, files=N, age=DURATION, costs=on|off, \
+             and policy-cmd=COMMAND, the latter last (e.g., 'cap=5G,age=3d')"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidSimulatedPolicy"
+        )
+    )]
+    InvalidSimulatedPolicy(
+        /// The invalid policy provided
+        String,
+        /// Description of the problem
+        String,
+    ),
+
+    /// Invalid size mode for --size-mode.
+    ///
+    /// Raised when the value is neither `apparent` nor `allocated`.
+    #[error("Invalid size mode: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_size_mode),
+        help(
+            "Use 'allocated' to count disk blocks (default) or 'apparent' to count logical file \
+             lengths"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidSizeMode"
+        )
+    )]
+    InvalidSizeMode(
+        /// The invalid mode value provided
+        String,
+    ),
+
+    /// Invalid size units for --size-units.
+    ///
+    /// Raised when the value is neither `binary` nor `si`.
+    #[error("Invalid size units: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_size_units),
+        help(
+            "Use 'binary' to read KB, MB and GB as powers of 1024 (default) or 'si' to read them \
+             as powers of 1000"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidSizeUnits"
+        )
+    )]
+    InvalidSizeUnits(
+        /// The invalid units value provided
+        String,
+    ),
+
+    /// Invalid symbolic link policy for --gc-symlinks.
+    ///
+    /// Raised when the value is neither `skip` nor `follow`.
+    #[error("Invalid symbolic link policy: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_symlink_policy),
+        help(
+            "Use 'skip' to count each symbolic link as itself (default) or 'follow' to count \
+             what it points to"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidSymlinkPolicy"
+        )
+    )]
+    InvalidSymlinkPolicy(
+        /// The invalid policy value provided
+        String,
+    ),
+
+    /// Invalid glob pattern for --only.
+    ///
+    /// Raised when a path filter is empty or cannot be compiled.
+    #[error("Invalid path filter: '{0}' - {1}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_path_filter),
+        help(
+            "Use a glob starting with the target directory name, e.g. 'target/debug' or \
+             'target/**/build'"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidPathFilter"
+        )
+    )]
+    InvalidPathFilter(
+        /// The invalid pattern provided
+        String,
+        /// Description of the problem
+        String,
+    ),
+
+    /// Invalid crate pattern for `cargo hold pin`.
+    ///
+    /// Raised when a pattern is empty or contains characters that cannot
+    /// appear in a crate name.
+    #[error("Invalid pin pattern: '{0}' - {1}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_pin_pattern),
+        help("Use a crate name, optionally with '*' and '?' wildcards, e.g. 'librocksdb-sys'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidPinPattern"
+        )
+    )]
+    InvalidPinPattern(
+        /// The invalid pattern provided
+        String,
+        /// Description of the problem
+        String,
+    ),
+
+    /// Invalid policy for --gc-when-busy.
+    ///
+    /// Raised when the value is not one of `wait`, `skip`, or `fail`.
+    #[error("Invalid busy policy: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_busy_policy),
+        help("Use 'wait' (default), 'skip', or 'fail'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidBusyPolicy"
+        )
+    )]
+    InvalidBusyPolicy(
+        /// The invalid policy value provided
+        String,
+    ),
+
+    /// Invalid format for --removal-list-format.
+    ///
+    /// Raised when the value is neither `json` nor `nul`.
+    #[error("Invalid removal list format: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_removal_list_format),
+        help("Use 'json' (default) or 'nul' for NUL-delimited '<size>\\t<path>' records"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidRemovalListFormat"
+        )
+    )]
+    InvalidRemovalListFormat(
+        /// The invalid format value provided
+        String,
+    ),
+
+    /// Invalid format for --gc-audit-log-format.
+    ///
+    /// Raised when the value is neither `text` nor `jsonl`.
+    #[error("Invalid audit log format: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_audit_log_format),
+        help("Use 'text' (default) for tab-separated lines or 'jsonl' for a JSON object per line"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidAuditLogFormat"
+        )
+    )]
+    InvalidAuditLogFormat(
+        /// The invalid format value provided
+        String,
+    ),
+
+    /// Invalid mode for --global-cache.
+    ///
+    /// Raised when the value is neither `auto` nor `off`.
+    #[error("Invalid global cache mode: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_global_cache_mode),
+        help(
+            "Use 'auto' to follow cargo's global cache tracking database when present (default) \
+             or 'off' to rely on file modification times"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidGlobalCacheMode"
+        )
+    )]
+    InvalidGlobalCacheMode(
+        /// The invalid mode value provided
+        String,
+    ),
+
+    /// Invalid mode for --gc-sandbox.
+    ///
+    /// Raised when the value is not `off`, `auto`, or `on`.
+    #[error("Invalid GC sandbox mode: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_sandbox_mode),
+        help(
+            "Use 'off' to run garbage collection unconfined (default), 'auto' to confine it with \
+             Landlock where the kernel supports it, or 'on' to require confinement"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidSandboxMode"
+        )
+    )]
+    InvalidSandboxMode(
+        /// The invalid mode value provided
+        String,
+    ),
+
+    /// Invalid mode for --ephemeral.
+    ///
+    /// Raised when the value is not `auto`, `on`, or `off`.
+    #[error("Invalid ephemeral mode: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_ephemeral_mode),
+        help(
+            "Use 'auto' (default) to detect containers and hosted runners, 'on', or 'off'"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidEphemeralMode"
+        )
+    )]
+    InvalidEphemeralMode(
+        /// The invalid mode value provided
+        String,
+    ),
+
+    /// Invalid CI system for --ci.
+    ///
+    /// Raised when the value is not one of `auto`, `none`, `github`,
+    /// `gitlab`, or `buildkite`.
+    #[error("Invalid CI system: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_ci),
+        help("Use 'auto' (default), 'none', 'github', 'gitlab', or 'buildkite'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidCiMode"
+        )
+    )]
+    InvalidCiMode(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Invalid version control backend for --vcs.
+    ///
+    /// Raised when the value is not one of `auto`, `libgit2`, `git`, `hg`, or
+    /// `jj`; `libgit2` needs the `git` feature.
+    #[error("Invalid version control backend: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_vcs),
+        help("Use 'auto' (default), 'libgit2', 'git', 'hg', or 'jj'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidVcs"
+        )
+    )]
+    InvalidVcs(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Invalid Git LFS hashing mode for --lfs.
+    ///
+    /// Raised when the value is neither `content` nor `pointer`.
+    #[error("Invalid Git LFS mode: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_lfs_mode),
+        help("Use 'content' (default) or 'pointer'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidLfsMode"
+        )
+    )]
+    InvalidLfsMode(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Invalid preset for --profile.
+    ///
+    /// Raised when the value is neither `ci` nor `local`.
+    #[error("Invalid profile: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_profile),
+        help("Use 'ci' (default) or 'local'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidProfile"
+        )
+    )]
+    InvalidProfile(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Invalid policy for --large-file-policy.
+    ///
+    /// Raised when the value is not `full`, `skip`, `head`, or `head:<SIZE>`
+    /// with a non-zero size.
+    #[error("Invalid large file policy: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_large_file_policy),
+        help(
+            "Use 'full' (default), 'skip' to compare sizes only, or 'head:<SIZE>' (e.g. \
+             'head:16M') to hash the first bytes"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidLargeFilePolicy"
+        )
+    )]
+    InvalidLargeFilePolicy(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Invalid build status for report-build --status.
+    ///
+    /// Raised when the value is neither `success` nor `failure`.
+    #[error("Invalid build status: '{0}'")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_build_status),
+        help("Use 'success' or 'failure'"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidBuildStatus"
+        )
+    )]
+    InvalidBuildStatus(
+        /// The invalid value provided
+        String,
+    ),
+
+    /// Failed to read or update cargo's global cache tracking database.
+    ///
+    /// Raised when `$CARGO_HOME/.global-cache` cannot be opened, has an
+    /// unexpected schema, or cannot be written. Garbage collection of the
+    /// cargo home falls back to file modification times in that case. Only
+    /// present with the `gc` feature.
+    #[cfg(feature = "gc")]
+    #[error("Failed to access cargo's global cache database '{path}'")]
+    #[diagnostic(
+        code(cargo_hold::gc::global_cache),
+        help("Pass --global-cache off to ignore the database"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.GlobalCacheError"
+        )
+    )]
+    GlobalCacheError {
+        /// The database path
+        path: PathBuf,
+        /// The underlying SQLite error
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    /// Garbage collection could not be confined with --gc-sandbox on.
+    ///
+    /// Raised before anything is removed when the kernel does not support
+    /// Landlock (Linux 5.13+), it is disabled, or the platform is not Linux.
+    #[error("Cannot confine garbage collection with Landlock: {reason}")]
+    #[diagnostic(
+        code(cargo_hold::gc::sandbox_unavailable),
+        help(
+            "Pass --gc-sandbox auto to run unconfined where Landlock is unavailable, and check \
+             that the kernel lists 'landlock' in /sys/kernel/security/lsm"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.SandboxUnavailable"
+        )
+    )]
+    SandboxUnavailable {
+        /// Why Landlock could not be used
+        reason: String,
+    },
+
+    /// Garbage collection refused to remove a path outside of the
+    /// directories it cleans.
+    ///
+    /// Every path is checked before it is removed, both as written and with
+    /// the symbolic links of its parent directories resolved, so that a link
+    /// inside the target directory or the cargo home cannot redirect a
+    /// removal elsewhere. GC reports the path and carries on; `clean-crate`
+    /// stops.
+    #[error(
+        "Refusing to remove '{path}': it resolves to '{resolved}', outside of the cleaned \
+         directories"
+    )]
+    #[diagnostic(
+        code(cargo_hold::gc::unsafe_removal),
+        help(
+            "A symbolic link in the target directory or the cargo home points elsewhere; remove \
+             the link, and investigate it if nothing created it on purpose"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.UnsafeRemoval"
+        )
+    )]
+    UnsafeRemoval {
+        /// The path scheduled for removal
+        path: PathBuf,
+        /// Where it resolves to
+        resolved: PathBuf,
+    },
+
+    /// A cargo build is using the target directory.
+    ///
+    /// Raised before garbage collection when another process holds cargo's
+    /// `.cargo-lock` or has files in the target directory open, and the
+    /// `--gc-when-busy` policy is `fail` (or `wait` timed out).
+    #[error("Target directory '{path}' is in use: {reason}")]
+    #[diagnostic(
+        code(cargo_hold::gc::target_busy),
+        help(
+            "Wait for the running build to finish, or pass --gc-when-busy skip to leave the \
+             target directory alone"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.TargetBusy"
+        )
+    )]
+    TargetBusy {
+        /// The busy target directory
+        path: PathBuf,
+        /// What indicated the directory is in use
+        reason: String,
+    },
+
+    /// The --gc-policy-cmd command failed or replied with malformed output.
+    ///
+    /// Garbage collection is aborted rather than falling back to the
+    /// built-in rules, since the policy may exist to protect artifacts.
+    #[error("GC policy command '{command}' failed: {message}")]
+    #[diagnostic(
+        code(cargo_hold::gc::policy_command),
+        help(
+            "The command must exit successfully and print one verdict per input line: 'keep', \
+             'evict', 'default', or a numeric eviction score"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.PolicyCommandError"
+        )
+    )]
+    PolicyCommandError {
+        /// The policy command line
+        command: String,
+        /// What went wrong
+        message: String,
+    },
+
+    /// The configuration file is malformed.
+    ///
+    /// Raised when it is not a JSON object of known keys, or defines an
+    /// unknown hook or a hook command that is not a string.
+    #[error("Invalid configuration file {path}: {message}")]
+    #[diagnostic(
+        code(cargo_hold::config::invalid_file),
+        help(
+            "The configuration file must be a JSON object such as \
+             {{\"hooks\": {{\"post-stow\": \"./upload-cache.sh\"}}}}, with hooks named \
+             'pre-anchor', 'post-stow', or 'post-heave'"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidConfigFile"
+        )
+    )]
+    InvalidConfigFile {
+        /// The configuration file
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// A hook of the configuration file failed or exited non-zero.
+    ///
+    /// The command running the hook fails too, as a hook may exist to upload
+    /// a cache or guard a build.
+    #[error("The {hook} hook failed: {message}")]
+    #[diagnostic(
+        code(cargo_hold::hooks::failed),
+        help("Fix the hook command in the configuration file, or remove the hook"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.HookFailed"
+        )
+    )]
+    HookFailed {
+        /// Name of the hook
+        hook: String,
+        /// What went wrong
+        message: String,
+    },
+
+    /// The command is neither built in nor provided by a plugin.
+    ///
+    /// Plugins are executables named `cargo-hold-<name>` on `PATH`.
+    #[error("No such command: `{name}`")]
+    #[diagnostic(
+        code(cargo_hold::plugin::not_found),
+        help(
+            "Run 'cargo hold --help' for the built-in commands, or install an executable named \
+             'cargo-hold-{name}' on PATH"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.UnknownCommand"
+        )
+    )]
+    UnknownCommand {
+        /// Name of the command
+        name: String,
+    },
+
+    /// A plugin could not be started or exited non-zero.
+    #[error("The plugin cargo-hold-{name} failed: {message}")]
+    #[diagnostic(
+        code(cargo_hold::plugin::failed),
+        help("See the plugin's output above for what went wrong"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.PluginFailed"
+        )
+    )]
+    PluginFailed {
+        /// Name of the command the plugin provides
+        name: String,
+        /// What went wrong
+        message: String,
+    },
+
+    /// The manifest of `cargo hold batch` is malformed.
+    ///
+    /// Raised when it is not valid TOML, has unknown keys, lists no
+    /// repositories, or gives the repositories more than `max-total-size`.
+    #[error("Invalid batch manifest {path}: {message}")]
+    #[diagnostic(
+        code(cargo_hold::batch::invalid_manifest),
+        help(
+            "The manifest lists repositories as [[repo]] tables with a 'path' and optionally \
+             'target-dir', 'build-dir', 'metadata-path', 'max-target-size', 'priority', \
+             'removal-list', \
+             'preserve-units-from', and 'rebuild-report'; a top-level 'max-total-size' must \
+             exceed the sum of the repositories' own caps"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidBatchManifest"
+        )
+    )]
+    InvalidBatchManifest {
+        /// The manifest
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// Some repositories of a batch failed.
+    ///
+    /// The other repositories were still processed and the cargo home
+    /// cleaned; the report names the failures.
+    #[error("{failed} of {total} repositories failed")]
+    #[diagnostic(
+        code(cargo_hold::batch::failed),
+        help("The errors of the failed repositories are printed above and kept in the report"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.BatchFailed"
+        )
+    )]
+    BatchFailed {
+        /// Repositories that failed
+        failed: usize,
+        /// Repositories in the manifest
+        total: usize,
+    },
+
+    /// An option given to `cargo hold batch` names a file of a single
+    /// repository.
+    ///
+    /// Raised for `--removal-list`, `--preserve-units-from`,
+    /// `--rebuild-report`, and an absolute `--build-dir`: every repository
+    /// would overwrite, read, or clean the same path.
+    #[error("{option} cannot be given to batch, as every repository would use the same path")]
+    #[diagnostic(
+        code(cargo_hold::batch::per_repository_option),
+        help("Give each repository a '{key}' of its own in the batch manifest"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.PerRepositoryOption"
+        )
+    )]
+    PerRepositoryOption {
+        /// The option given
+        option: String,
+        /// The manifest key taking its place
+        key: String,
+    },
+
+    /// `cargo hold plan` cannot write the recipe.
+    ///
+    /// Raised when the output directory already holds files, which would end
+    /// up in the recipe layer, or when a tracked manifest cannot be parsed.
+    #[error("Cannot plan '{path}': {message}")]
+    #[diagnostic(
+        code(cargo_hold::plan::failed),
+        help(
+            "Plan into a new or empty directory, and check that every tracked Cargo.toml is \
+             valid TOML"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.PlanFailed"
+        )
+    )]
+    PlanFailed {
+        /// The output directory or manifest
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// `cargo hold import-from` cannot read the last build from a file
+    /// written by another tool.
+    #[error("Cannot import from '{path}': {message}")]
+    #[diagnostic(
+        code(cargo_hold::import::failed),
+        help(
+            "Pass the sweep.timestamp written by `cargo sweep --stamp`, or the output of \
+             `sccache --show-stats --stats-format=json` saved after a build"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.ImportFailed"
+        )
+    )]
+    ImportFailed {
+        /// The imported file
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// The manifest of artifacts compressed by --gc-compress-cold is
+    /// malformed.
+    ///
+    /// Without it the compressed artifacts cannot be thawed with their
+    /// original timestamps, so garbage collection and `thaw` stop instead of
+    /// guessing.
+    #[error("Invalid compressed artifact manifest '{path}': {message}")]
+    #[diagnostic(
+        code(cargo_hold::gc::cold_manifest),
+        help(
+            "Remove the manifest and the `.zst` files next to the build artifacts; cargo rebuilds \
+             the affected crates"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.ColdManifestError"
+        )
+    )]
+    ColdManifestError {
+        /// The manifest path
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// The seal manifest written by `cargo hold seal` is malformed.
+    ///
+    /// A damaged seal cannot tell intact artifacts from corrupted ones, so
+    /// `verify` stops instead of reporting a clean target directory.
+    #[error("Invalid seal manifest '{path}': {message}")]
+    #[diagnostic(
+        code(cargo_hold::seal::manifest),
+        help("Remove the seal manifest, or restore the target directory from the cache again"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.SealManifestError"
+        )
+    )]
+    SealManifestError {
+        /// The manifest path
+        path: PathBuf,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// Files of a restored target directory do not match its seal.
+    ///
+    /// Raised by `verify` when sealed artifacts are missing, have a
+    /// different size, or have different contents, which usually means the
+    /// cache was truncated or damaged while being stored or downloaded.
+    #[error("{corrupted} of {checked} sealed files in '{target_dir}' are missing or corrupted")]
+    #[diagnostic(
+        code(cargo_hold::seal::corrupted),
+        help(
+            "The cache was likely damaged in transit. Delete the target directory (or the listed \
+             files) so cargo rebuilds them, or restore the cache again"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.CorruptedArtifacts"
+        )
+    )]
+    CorruptedArtifacts {
+        /// The verified target directory
+        target_dir: PathBuf,
+        /// Number of files that do not match the seal
+        corrupted: usize,
+        /// Number of sealed files
+        checked: usize,
+    },
+
     /// Cannot determine home directory for cargo cache cleanup.
     ///
     /// Raised when `home::cargo_home()` returns None during garbage
@@ -198,28 +1052,249 @@ pub enum HoldError {
     #[error("Garbage collection error: {0}")]
     #[diagnostic(
         code(cargo_hold::gc::error),
-        help("Check permissions and disk space, then try again.")
+        help("Check permissions and disk space, then try again."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.GcError"
+        )
     )]
     GcError(
         /// Description of the garbage collection error
         String,
     ),
 
-    /// Metadata version is newer than supported or configuration invalid.
+    /// Garbage collection cannot remove entries from some directories.
+    ///
+    /// Raised before anything is removed, when creating and deleting a probe
+    /// file fails in a directory garbage collection would remove entries from,
+    /// such as a cache restored by another user or a read-only mount.
+    #[error("Garbage collection cannot remove files from {count} directories:{directories}")]
+    #[diagnostic(
+        code(cargo_hold::gc::permission_denied),
+        help(
+            "Fix the ownership or permissions of the listed directories, or leave the cargo home \
+             alone with --keep-cargo-home"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.GcPermissionDenied"
+        )
+    )]
+    GcPermissionDenied {
+        /// Number of directories that failed the check
+        count: usize,
+        /// The directories and why they failed, a line each
+        directories: String,
+    },
+
+    /// Required configuration is missing.
     ///
-    /// Raised when: 1) loaded metadata has version > METADATA_VERSION,
-    /// indicating it was created by a newer cargo-hold version, or
-    /// 2) required parameters are missing for the voyage command.
+    /// Raised when the builders for the heave and voyage commands are built
+    /// without a required parameter, or when no command is given.
     #[error("Configuration error: {0}")]
     #[diagnostic(
         code(cargo_hold::config::error),
-        help("Check the required configuration parameters.")
+        help("Check the required configuration parameters."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.ConfigError"
+        )
     )]
     ConfigError(
         /// Description of the configuration error
         String,
     ),
 
+    /// Metadata file was written by a newer version of cargo-hold.
+    ///
+    /// Raised when the loaded metadata has a version greater than
+    /// METADATA_VERSION. Its layout is unknown, so it is neither read nor
+    /// reset.
+    #[error("Metadata version {found} is newer than supported version {supported}")]
+    #[diagnostic(
+        code(cargo_hold::metadata::version_too_new),
+        help("Update cargo-hold, or run 'cargo hold bilge' to start over with fresh metadata."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.MetadataVersionTooNew"
+        )
+    )]
+    MetadataVersionTooNew {
+        /// The version of the metadata file
+        found: u32,
+        /// The newest version this cargo-hold understands
+        supported: u32,
+    },
+
+    /// Metadata file is not signed with the key given by `--metadata-key`.
+    ///
+    /// Raised while loading when the file has no signature or its signature
+    /// does not match, e.g. because a cache was poisoned. The metadata is
+    /// discarded rather than trusted.
+    #[error("Metadata file '{path}' {reason}")]
+    #[diagnostic(
+        code(cargo_hold::metadata::unauthenticated),
+        help(
+            "Metadata is only trusted when it was saved with the same CARGO_HOLD_METADATA_KEY; \
+             the next stow signs it"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.UnauthenticatedMetadata"
+        )
+    )]
+    UnauthenticatedMetadata {
+        /// Path to the metadata file
+        path: PathBuf,
+        /// What is wrong with its signature
+        reason: &'static str,
+    },
+
+    /// `cargo metadata` failed or printed output that cannot be parsed.
+    ///
+    /// Raised while reading the workspace members for
+    /// `--invalidate-fingerprints`.
+    #[error("Failed to read workspace metadata: {0}")]
+    #[diagnostic(
+        code(cargo_hold::workspace::cargo_metadata),
+        help(
+            "Run 'cargo metadata --no-deps --offline' in the repository to see the underlying \
+             error."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.CargoMetadataError"
+        )
+    )]
+    CargoMetadataError(
+        /// Description of the failure
+        String,
+    ),
+
+    /// The file given to --preserve-units-from is not recognized.
+    ///
+    /// Raised when it is neither a `--unit-graph` document nor a stream of
+    /// cargo JSON messages.
+    #[error("Invalid build units file: {0}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_build_units),
+        help(
+            "Pass the output of 'cargo build --unit-graph -Z unstable-options', \
+             '--message-format=json', or '--timings=json'."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidBuildUnits"
+        )
+    )]
+    InvalidBuildUnits(
+        /// Description of the problem
+        String,
+    ),
+
+    /// The file given to report-build --timings holds no build timings.
+    ///
+    /// Raised when the file has no `timing-info` messages, e.g. because
+    /// cargo ran without `--timings=json`.
+    #[error("Invalid build timings file: {0}")]
+    #[diagnostic(
+        code(cargo_hold::gc::invalid_build_timings),
+        help(
+            "Pass the JSON messages of 'cargo build --timings=json -Z unstable-options', as \
+             written to its standard output."
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidBuildTimings"
+        )
+    )]
+    InvalidBuildTimings(
+        /// Description of the problem
+        String,
+    ),
+
+    /// A remote location was configured while running with `--offline`.
+    ///
+    /// cargo-hold only reads and writes local files; offline mode rejects
+    /// URLs up front so a hermetic build never reaches for the network.
+    #[error("{option} '{path}' is a remote location, which --offline does not allow")]
+    #[diagnostic(
+        code(cargo_hold::config::offline),
+        help("Use a local path, or drop --offline (CARGO_HOLD_OFFLINE) to allow remote locations"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.RemotePathOffline"
+        )
+    )]
+    RemotePathOffline {
+        /// The option naming the location
+        option: String,
+        /// The remote location
+        path: PathBuf,
+    },
+
+    /// The command reported warnings while running with `--deny-warnings`.
+    ///
+    /// The command ran to completion; the warnings printed above it explain
+    /// what needs attention.
+    #[error("{0} warning(s) reported, which --deny-warnings does not allow")]
+    #[diagnostic(
+        code(cargo_hold::config::warnings_denied),
+        help("Resolve the warnings above, or drop --deny-warnings (CARGO_HOLD_DENY_WARNINGS)"),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.WarningsDenied"
+        )
+    )]
+    WarningsDenied(
+        /// Number of warnings reported
+        usize,
+    ),
+
+    /// Looking up, downloading or installing a release failed.
+    ///
+    /// Raised by `self-update` when `curl` or `tar` fail, the release has no
+    /// archive for this platform, or the new binary cannot be put in place.
+    #[error("Self-update failed: {0}")]
+    #[diagnostic(
+        code(cargo_hold::update::failed),
+        help(
+            "Check network access to github.com, or install the release manually with \
+             'cargo binstall cargo-hold'"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.SelfUpdateFailed"
+        )
+    )]
+    SelfUpdateFailed(
+        /// Description of the failure
+        String,
+    ),
+
+    /// A downloaded release archive does not match its published checksum.
+    ///
+    /// The archive is discarded and the installed binary left untouched.
+    #[error("{file} has SHA-256 {actual}, but the release lists {expected}")]
+    #[diagnostic(
+        code(cargo_hold::update::checksum_mismatch),
+        help(
+            "The download was corrupted or tampered with; retry, and report it if it persists"
+        ),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.UpdateChecksumMismatch"
+        )
+    )]
+    UpdateChecksumMismatch {
+        /// Name of the release archive
+        file: String,
+        /// Checksum published with the release
+        expected: String,
+        /// Checksum of the downloaded archive
+        actual: String,
+    },
+
     /// PathBuf cannot be converted to UTF-8 string for storage.
     ///
     /// Raised in StateMetadata operations when a PathBuf contains
@@ -228,7 +1303,11 @@ pub enum HoldError {
     #[error("Invalid UTF-8 in path: {0}")]
     #[diagnostic(
         code(cargo_hold::path::invalid_utf8),
-        help("File paths must be valid UTF-8. This is a requirement for Git-tracked files.")
+        help("File paths must be valid UTF-8. This is a requirement for Git-tracked files."),
+        url(
+            "https://docs.rs/cargo-hold/latest/cargo_hold/error/enum.HoldError.html\
+             #variant.InvalidUtf8Path"
+        )
     )]
     InvalidUtf8Path(
         /// The path containing invalid UTF-8
@@ -238,3 +1317,110 @@ pub enum HoldError {
 
 /// Type alias for Results in this crate
 pub type Result<T> = std::result::Result<T, HoldError>;
+
+#[cfg(test)]
+mod tests {
+    use miette::Diagnostic;
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_have_code_help_and_url() {
+        let errors = [
+            HoldError::RepoNotFound(PathBuf::from(".")),
+            HoldError::IoError {
+                path: PathBuf::from("target"),
+                source: std::io::Error::other("disk full"),
+            },
+            HoldError::InvalidPath {
+                message: "not UTF-8".to_string(),
+            },
+            HoldError::MetadataVersionTooNew {
+                found: 99,
+                supported: 1,
+            },
+            HoldError::UnauthenticatedMetadata {
+                path: PathBuf::from("target/cargo-hold.metadata"),
+                reason: "is not signed",
+            },
+            HoldError::CargoMetadataError("no packages".to_string()),
+            HoldError::VcsCommandFailed("git ls-files failed".to_string()),
+            HoldError::InvalidVcs("svn".to_string()),
+            HoldError::InvalidLfsMode("smudge".to_string()),
+            HoldError::InvalidProfile("laptop".to_string()),
+            HoldError::InvalidEphemeralMode("maybe".to_string()),
+            HoldError::InvalidLargeFilePolicy("sample".to_string()),
+            HoldError::InvalidBuildUnits("empty".to_string()),
+            HoldError::InvalidBuildTimings("empty".to_string()),
+            HoldError::InvalidAuditLogFormat("csv".to_string()),
+            HoldError::InvalidSandboxMode("strict".to_string()),
+            HoldError::InvalidSymlinkPolicy("always".to_string()),
+            HoldError::UnsafeRemoval {
+                path: PathBuf::from("target/debug/deps/escape/precious"),
+                resolved: PathBuf::from("/home/ci/precious"),
+            },
+            HoldError::SandboxUnavailable {
+                reason: "Function not implemented (os error 38)".to_string(),
+            },
+            HoldError::InvalidSimulatedPolicy("cap".to_string(), "not KEY=VALUE".to_string()),
+            HoldError::GcError("no home".to_string()),
+            HoldError::GcPermissionDenied {
+                count: 1,
+                directories: "\n  target/debug/deps: Permission denied".to_string(),
+            },
+            HoldError::InvalidConfigFile {
+                path: PathBuf::from(".cargo-hold.json"),
+                message: "unknown key 'hook'".to_string(),
+            },
+            HoldError::HookFailed {
+                hook: "post-stow".to_string(),
+                message: "'false' exited with exit status: 1".to_string(),
+            },
+            HoldError::UnknownCommand {
+                name: "audit".to_string(),
+            },
+            HoldError::PluginFailed {
+                name: "audit".to_string(),
+                message: "exited with exit status: 1".to_string(),
+            },
+            HoldError::InvalidBatchManifest {
+                path: PathBuf::from("repos.toml"),
+                message: "no repositories".to_string(),
+            },
+            HoldError::BatchFailed {
+                failed: 1,
+                total: 3,
+            },
+            HoldError::PerRepositoryOption {
+                option: "--removal-list".to_string(),
+                key: "removal-list".to_string(),
+            },
+            HoldError::PlanFailed {
+                path: PathBuf::from("recipe"),
+                message: "the output directory is not empty".to_string(),
+            },
+            HoldError::ImportFailed {
+                path: PathBuf::from("sweep.timestamp"),
+                message: "expected milliseconds since the Unix epoch".to_string(),
+            },
+        ];
+
+        for error in errors {
+            let name = format!("{error:?}");
+            let name = name.split(['(', ' ']).next().unwrap();
+            assert!(error.code().is_some(), "{name} has no code");
+            assert!(error.help().is_some(), "{name} has no help");
+            let url = error.url().unwrap().to_string();
+            assert!(url.ends_with(&format!("#variant.{name}")), "{url}");
+        }
+
+        let error = HoldError::MetadataVersionTooNew {
+            found: 99,
+            supported: 1,
+        };
+        assert_eq!(
+            error.code().unwrap().to_string(),
+            "cargo_hold::metadata::version_too_new"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+//! Entry points for the fuzz targets in `fuzz/`.
+//!
+//! Only built with the `fuzz` feature; not part of the public API.
+
+#[cfg(feature = "gc")]
+use std::path::Path;
+
+use rkyv::util::AlignedVec;
+
+use crate::error::Result;
+use crate::state::StateMetadata;
+
+/// Decodes and migrates metadata file contents, as loading a metadata file
+/// does
+pub fn parse_metadata(bytes: &[u8]) -> Result<StateMetadata> {
+    // Metadata files are memory mapped, so rkyv sees them page aligned
+    let mut aligned = AlignedVec::<16>::new();
+    aligned.extend_from_slice(bytes);
+    crate::metadata::parse_metadata(&aligned, false).map(|(metadata, _)| metadata)
+}
+
+/// Parses a size such as `--max-target-size` takes
+pub fn parse_size(s: &str) -> Result<u64> {
+    crate::gc::parse_size(s)
+}
+
+/// Splits a target directory file name into its crate name and hash
+#[cfg(feature = "gc")]
+pub fn parse_crate_artifact_name(path: &Path) -> Option<(String, String)> {
+    crate::gc::parse_crate_artifact_name(path)
+}
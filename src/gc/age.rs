@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::duration::parse_duration;
+use crate::error::{HoldError, Result};
+
+/// Default age threshold for target directory artifacts
+pub(crate) const DEFAULT_AGE_THRESHOLD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Default age threshold for cargo home areas: extracted registry sources,
+/// git checkouts and databases, and installed binaries
+pub(crate) const DEFAULT_CARGO_HOME_AGE_THRESHOLD: Duration =
+    Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Parts of the target directory and cargo home that can be given their own
+/// age threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GcArea {
+    /// Crate artifacts in `deps/`, `build/`, and `.fingerprint/` (default:
+    /// the general age threshold)
+    Deps,
+    /// Incremental compilation sessions (default: always removed)
+    Incremental,
+    /// Downloaded `.crate` files in `registry/cache` (default: the general
+    /// age threshold)
+    RegistryCache,
+    /// Extracted sources in `registry/src` (default: 30 days)
+    RegistrySrc,
+    /// `git/checkouts` and `git/db` (default: 30 days)
+    Git,
+    /// Installed binaries in `bin/` (default: 30 days)
+    Bin,
+    /// Coverage data in the target directory: `*.profraw` and `*.profdata`
+    /// files and the contents of `llvm-cov-target` (default: the general age
+    /// threshold)
+    Coverage,
+    /// The runs of `cargo nextest` in `nextest/` (default: the general age
+    /// threshold)
+    Nextest,
+    /// The test projects of `trybuild` in `tests/` (default: the general age
+    /// threshold)
+    Trybuild,
+    /// Snapshots `insta` left for review in the target directory,
+    /// `*.snap.new` and `*.pending-snap` (default: the general age threshold)
+    Snapshots,
+    /// The builds and bundles of the Dioxus CLI in `dx/` (default: the
+    /// general age threshold)
+    Dx,
+    /// The JavaScript bindings and wasm of `wasm-bindgen` in `wasm-bindgen/`
+    /// (default: the general age threshold)
+    WasmBindgen,
+    /// The staging directories of `trunk`, `trunk/` and `.stage` (default:
+    /// the general age threshold)
+    Trunk,
+}
+
+impl GcArea {
+    /// Every area, in the order they are documented
+    pub const ALL: [GcArea; 13] = [
+        Self::Deps,
+        Self::Incremental,
+        Self::RegistryCache,
+        Self::RegistrySrc,
+        Self::Git,
+        Self::Bin,
+        Self::Coverage,
+        Self::Nextest,
+        Self::Trybuild,
+        Self::Snapshots,
+        Self::Dx,
+        Self::WasmBindgen,
+        Self::Trunk,
+    ];
+
+    /// Whether the area holds the outputs of a tool, which can be kept
+    /// altogether (`AREA=keep`)
+    pub fn is_tool_output(self) -> bool {
+        matches!(
+            self,
+            Self::Coverage
+                | Self::Nextest
+                | Self::Trybuild
+                | Self::Snapshots
+                | Self::Dx
+                | Self::WasmBindgen
+                | Self::Trunk
+        )
+    }
+}
+
+impl FromStr for GcArea {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "deps" => Ok(Self::Deps),
+            "incremental" => Ok(Self::Incremental),
+            "registry-cache" => Ok(Self::RegistryCache),
+            "registry-src" => Ok(Self::RegistrySrc),
+            "git" => Ok(Self::Git),
+            "bin" => Ok(Self::Bin),
+            "coverage" => Ok(Self::Coverage),
+            "nextest" => Ok(Self::Nextest),
+            "trybuild" => Ok(Self::Trybuild),
+            "snapshots" => Ok(Self::Snapshots),
+            "dx" => Ok(Self::Dx),
+            "wasm-bindgen" => Ok(Self::WasmBindgen),
+            "trunk" => Ok(Self::Trunk),
+            _ => Err(HoldError::InvalidAgeThreshold(
+                s.to_string(),
+                format!(
+                    "Unknown area, expected one of: {}",
+                    Self::ALL.map(|area| area.to_string()).join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GcArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Deps => "deps",
+            Self::Incremental => "incremental",
+            Self::RegistryCache => "registry-cache",
+            Self::RegistrySrc => "registry-src",
+            Self::Git => "git",
+            Self::Bin => "bin",
+            Self::Coverage => "coverage",
+            Self::Nextest => "nextest",
+            Self::Trybuild => "trybuild",
+            Self::Snapshots => "snapshots",
+            Self::Dx => "dx",
+            Self::WasmBindgen => "wasm-bindgen",
+            Self::Trunk => "trunk",
+        })
+    }
+}
+
+/// Age threshold of an area whose outputs are never removed (`AREA=keep`)
+pub(crate) const KEEP: Duration = Duration::MAX;
+
+/// Parse an `AREA=DURATION` per-area age threshold like `registry-cache=90d`.
+/// `AREA=evict` removes everything in the area (a threshold of 0), and the
+/// outputs of a tool are never removed with `AREA=keep`.
+pub(crate) fn parse_area_threshold(s: &str) -> Result<(GcArea, Duration)> {
+    let Some((area, duration)) = s.split_once('=') else {
+        return Err(HoldError::InvalidAgeThreshold(
+            s.to_string(),
+            "Expected AREA=DURATION".to_string(),
+        ));
+    };
+
+    let area: GcArea = area.parse()?;
+    let threshold = match duration.trim() {
+        "evict" => Duration::ZERO,
+        "keep" if area.is_tool_output() => KEEP,
+        "keep" => {
+            return Err(HoldError::InvalidAgeThreshold(
+                s.to_string(),
+                format!("Only the outputs of tools can be kept, not {area}"),
+            ));
+        }
+        duration => parse_duration(duration)?,
+    };
+    Ok((area, threshold))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_area_threshold() {
+        assert_eq!(
+            parse_area_threshold("deps=12h").unwrap(),
+            (GcArea::Deps, Duration::from_secs(12 * 60 * 60))
+        );
+        assert_eq!(
+            parse_area_threshold("registry_cache=90d").unwrap(),
+            (
+                GcArea::RegistryCache,
+                Duration::from_secs(90 * 24 * 60 * 60)
+            )
+        );
+        assert_eq!(parse_area_threshold("bin=0").unwrap().1, Duration::ZERO);
+        assert_eq!(
+            parse_area_threshold("incremental=evict").unwrap().1,
+            Duration::ZERO
+        );
+        assert_eq!(
+            parse_area_threshold("wasm-bindgen=keep").unwrap(),
+            (GcArea::WasmBindgen, KEEP)
+        );
+        assert!(parse_area_threshold("deps=keep").is_err());
+
+        assert!(parse_area_threshold("deps").is_err());
+        assert!(parse_area_threshold("docs=1d").is_err());
+        assert!(parse_area_threshold("git=soon").is_err());
+    }
+
+    #[test]
+    fn test_area_names_round_trip() {
+        for area in GcArea::ALL {
+            assert_eq!(area.to_string().parse::<GcArea>().unwrap(), area);
+        }
+    }
+}
@@ -1,57 +1,215 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use regex::Regex;
 
-use super::size::format_size;
+use super::costs::weighted_ages;
+use super::duration::{format_age, format_duration};
+use super::filter::PathFilter;
+use super::size::{DiskUsage, SizeMode};
+use super::units::BuildUnits;
 use crate::error::{HoldError, Result};
 use crate::logging::Logger;
 use crate::timestamp::saturating_duration_from_nanos;
 
-/// Information about a single artifact
+/// A file or directory belonging to a crate artifact group
 #[derive(Debug, Clone)]
-pub(crate) struct ArtifactInfo {
+pub struct ArtifactInfo {
     pub(crate) path: PathBuf,
     pub(crate) size: u64,
-    pub(crate) _modified: SystemTime,
+    pub(crate) modified: SystemTime,
 }
 
-/// A crate artifact group (all related files for a single crate)
+impl ArtifactInfo {
+    /// Path of the file or directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the file in the size mode it was collected with, 0 for a
+    /// directory (its contents are listed separately) or a hard link counted
+    /// elsewhere
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Modification time of the file, the Unix epoch for a directory
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+}
+
+/// A crate artifact group: the fingerprint, `deps`, and `build` entries
+/// cargo wrote for one unit of a crate, identified by its name and metadata
+/// hash
 #[derive(Debug)]
-pub(crate) struct CrateArtifact {
+pub struct CrateArtifact {
     pub(crate) name: String,
     pub(crate) hash: String,
     pub(crate) artifacts: Vec<ArtifactInfo>,
     pub(crate) total_size: u64,
     pub(crate) newest_mtime: SystemTime,
+    /// Eviction priority assigned by a policy command; higher scores are
+    /// evicted first under size pressure, ties fall back to age
+    pub(crate) eviction_score: f64,
+    /// Seconds cargo took to build the crate, if recorded; crates costly to
+    /// rebuild for their size are evicted later than their age suggests
+    pub(crate) rebuild_secs: Option<f64>,
+    /// Whether the dep-info of the crate shows that none of its workspace
+    /// sources changed since it was built, so cargo would reuse it however
+    /// old it is; such crates are exempt from age-based removal
+    pub(crate) inputs_unchanged: bool,
+}
+
+impl CrateArtifact {
+    /// Name of the crate as it appears in file names, e.g. `serde` or
+    /// `libserde`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 16-digit hexadecimal metadata hash distinguishing builds of the crate
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Files and directories of the group, sorted by path
+    pub fn artifacts(&self) -> &[ArtifactInfo] {
+        &self.artifacts
+    }
+
+    /// Total size of the files in the size mode they were collected with
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// Modification time of the newest file, which garbage collection takes
+    /// as the last time the crate was built
+    pub fn newest_mtime(&self) -> SystemTime {
+        self.newest_mtime
+    }
+
+    /// Number of files and directories in the group
+    pub fn file_count(&self) -> u64 {
+        self.artifacts.len() as u64
+    }
+}
+
+/// How much the target directory holds and how much it may hold
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Budget {
+    /// Current size in bytes
+    pub(crate) size: u64,
+    /// Optional maximum size in bytes
+    pub(crate) max_size: Option<u64>,
+    /// Current number of files and directories
+    pub(crate) files: u64,
+    /// Optional maximum number of files and directories
+    pub(crate) max_files: Option<u64>,
+}
+
+/// Default slack subtracted from the previous build timestamp to account for
+/// clock drift and builds finishing before GC runs.
+pub(crate) const DEFAULT_PRESERVE_BUFFER: Duration = Duration::from_secs(5 * 60);
+
+/// How artifacts from the previous build are protected from eviction
+#[derive(Debug, Clone)]
+pub(crate) struct PreservePolicy {
+    /// Timestamps of the previous builds to preserve, oldest first
+    pub(crate) previous_builds_mtime_nanos: Vec<u128>,
+    /// Artifacts modified up to this long before the previous build are kept
+    pub(crate) buffer: Duration,
+    /// Previous build timestamps older than this are considered stale and
+    /// ignored (defaults to the age threshold)
+    pub(crate) window: Option<Duration>,
+}
+
+impl Default for PreservePolicy {
+    fn default() -> Self {
+        Self {
+            previous_builds_mtime_nanos: Vec::new(),
+            buffer: DEFAULT_PRESERVE_BUFFER,
+            window: None,
+        }
+    }
+}
+
+impl PreservePolicy {
+    /// Policy preserving the given previous build with default buffer and
+    /// window
+    #[cfg(test)]
+    pub(crate) fn previous_build(nanos: u128) -> Self {
+        Self {
+            previous_builds_mtime_nanos: vec![nanos],
+            ..Self::default()
+        }
+    }
 }
 
-/// Collect all crate artifacts from a profile directory
-pub(crate) fn collect_crate_artifacts(profile_dir: &Path) -> Result<Vec<CrateArtifact>> {
+/// Collect all crate artifacts from a profile directory, sizing files with
+/// `size_mode`.
+///
+/// Files in `deps` and `build` are grouped with the fingerprint of the same
+/// name and hash; files without a fingerprint form groups of their own. In
+/// cargo's new build directory layout, each `build/<package>/<hash>`
+/// directory forms a group. Hard-linked files are counted once across all
+/// groups. The groups are
+/// sorted by name and hash.
+///
+/// ```no_run
+/// use cargo_hold::gc::{self, SizeMode};
+///
+/// for profile_dir in gc::find_profile_directories("target".as_ref())? {
+///     for artifact in gc::collect_crate_artifacts(&profile_dir, SizeMode::Apparent)? {
+///         println!(
+///             "{}-{}: {}",
+///             artifact.name(),
+///             artifact.hash(),
+///             gc::format_size(artifact.total_size())
+///         );
+///     }
+/// }
+/// # Ok::<(), cargo_hold::error::HoldError>(())
+/// ```
+pub fn collect_crate_artifacts(
+    profile_dir: &Path,
+    size_mode: SizeMode,
+) -> Result<Vec<CrateArtifact>> {
     let fingerprint_dir = profile_dir.join(".fingerprint");
-    if !fingerprint_dir.exists() {
+    let unit_dirs = unit_directories(profile_dir)?;
+    if !fingerprint_dir.exists() && unit_dirs.is_empty() {
         return Ok(Vec::new());
     }
 
-    let mut crate_map: HashMap<(String, String), CrateArtifact> = HashMap::new();
+    // Ordered so that every later step sees the groups in the same order
+    let mut crate_map: BTreeMap<(String, String), CrateArtifact> = BTreeMap::new();
+    // Shared across all groups so hard links (e.g. between deps/ and build/)
+    // are only counted once.
+    let mut usage = DiskUsage::default();
 
     // Scan fingerprint directory to identify crates
-    let entries = fs::read_dir(&fingerprint_dir).map_err(|source| HoldError::IoError {
-        path: fingerprint_dir.clone(),
-        source,
-    })?;
+    let entries = match fs::read_dir(&fingerprint_dir) {
+        Ok(entries) => Some(entries),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(source) => {
+            return Err(HoldError::IoError {
+                path: fingerprint_dir,
+                source,
+            });
+        }
+    };
 
-    for entry in entries {
+    for entry in entries.into_iter().flatten() {
         let entry = entry.map_err(|source| HoldError::IoError {
             path: fingerprint_dir.clone(),
             source,
         })?;
         let path = entry.path();
 
-        if path.is_dir()
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir())
             && let Some((name, hash)) = parse_crate_artifact_name(&path)
         {
             let key = (name.clone(), hash.clone());
@@ -61,10 +219,13 @@ pub(crate) fn collect_crate_artifacts(profile_dir: &Path) -> Result<Vec<CrateArt
                 artifacts: Vec::new(),
                 total_size: 0,
                 newest_mtime: SystemTime::UNIX_EPOCH,
+                eviction_score: 0.0,
+                rebuild_secs: None,
+                inputs_unchanged: false,
             });
 
             // Add the fingerprint directory itself as an artifact
-            add_artifact_file(&path, crate_artifact)?;
+            add_artifact_file(&path, crate_artifact, &mut usage, size_mode)?;
         }
     }
 
@@ -91,7 +252,7 @@ pub(crate) fn collect_crate_artifacts(profile_dir: &Path) -> Result<Vec<CrateArt
             if let Some((name, hash)) = parse_crate_artifact_name(&path) {
                 let key = (name.clone(), hash.clone());
                 if let Some(crate_artifact) = crate_map.get_mut(&key) {
-                    add_artifact_file(&path, crate_artifact)?;
+                    add_artifact_file(&path, crate_artifact, &mut usage, size_mode)?;
                 } else {
                     // This file doesn't have a corresponding fingerprint entry
                     // Create a new crate artifact for orphaned files
@@ -101,19 +262,93 @@ pub(crate) fn collect_crate_artifacts(profile_dir: &Path) -> Result<Vec<CrateArt
                         artifacts: Vec::new(),
                         total_size: 0,
                         newest_mtime: SystemTime::UNIX_EPOCH,
+                        eviction_score: 0.0,
+                        rebuild_secs: None,
+                        inputs_unchanged: false,
                     };
-                    add_artifact_file(&path, &mut artifact)?;
+                    add_artifact_file(&path, &mut artifact, &mut usage, size_mode)?;
                     crate_map.insert(key, artifact);
                 }
             }
         }
     }
 
-    Ok(crate_map.into_values().collect())
+    // Every file of a unit of the new layout, its fingerprint and dep-info
+    // included, lives in the unit's directory
+    for (name, hash, unit_dir) in unit_dirs {
+        let crate_artifact = crate_map
+            .entry((name.clone(), hash.clone()))
+            .or_insert_with(|| CrateArtifact {
+                name,
+                hash,
+                artifacts: Vec::new(),
+                total_size: 0,
+                newest_mtime: SystemTime::UNIX_EPOCH,
+                eviction_score: 0.0,
+                rebuild_secs: None,
+                inputs_unchanged: false,
+            });
+        add_artifact_file(&unit_dir, crate_artifact, &mut usage, size_mode)?;
+    }
+
+    Ok(crate_map
+        .into_values()
+        .map(|mut crate_artifact| {
+            crate_artifact.artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+            crate_artifact
+        })
+        .collect())
 }
 
-/// Parse a crate artifact filename to extract name and hash
-pub(crate) fn parse_crate_artifact_name(path: &Path) -> Option<(String, String)> {
+/// The unit directories of cargo's new build directory layout
+/// (`-Zbuild-dir-new-layout`), `build/<package>/<hash>`, with their package
+/// name and hash, sorted.
+///
+/// The classic layout's `build/<package>-<hash>` directories are not
+/// included.
+pub(crate) fn unit_directories(profile_dir: &Path) -> Result<Vec<(String, String, PathBuf)>> {
+    let build_dir = profile_dir.join("build");
+    let packages = match fs::read_dir(&build_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(HoldError::IoError {
+                path: build_dir,
+                source,
+            });
+        }
+    };
+
+    let mut units = Vec::new();
+    for package in packages.filter_map(|entry| entry.ok()) {
+        let package_dir = package.path();
+        if !package_dir.is_dir() || parse_crate_artifact_name(&package_dir).is_some() {
+            continue;
+        }
+        let Ok(hashes) = fs::read_dir(&package_dir) else {
+            continue;
+        };
+        let name = package.file_name().to_string_lossy().into_owned();
+        for hash in hashes.filter_map(|entry| entry.ok()) {
+            let hash_name = hash.file_name().to_string_lossy().into_owned();
+            if hash_name.len() == 16
+                && hash_name
+                    .bytes()
+                    .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+                && hash.path().is_dir()
+            {
+                units.push((name.clone(), hash_name, hash.path()));
+            }
+        }
+    }
+    units.sort();
+    Ok(units)
+}
+
+/// Parse a crate artifact file name such as `libserde-0123456789abcdef.rlib`
+/// into its crate name and hash, or `None` if it does not end in a 16-digit
+/// hexadecimal hash
+pub fn parse_crate_artifact_name(path: &Path) -> Option<(String, String)> {
     static CRATE_ARTIFACT_RE: OnceLock<Regex> = OnceLock::new();
 
     let filename = path.file_name()?.to_str()?;
@@ -125,11 +360,21 @@ pub(crate) fn parse_crate_artifact_name(path: &Path) -> Option<(String, String)>
     Some((captures[1].to_string(), captures[2].to_string()))
 }
 
-/// Add artifact files to a crate artifact
-fn add_artifact_files(path: &Path, crate_artifact: &mut CrateArtifact) -> Result<()> {
-    if path.is_file() {
-        add_artifact_file(path, crate_artifact)?;
-    } else if path.is_dir() {
+/// Add artifact files to a crate artifact. Symbolic links are added as
+/// themselves and never followed, so a group never reaches outside its
+/// directory.
+fn add_artifact_files(
+    path: &Path,
+    crate_artifact: &mut CrateArtifact,
+    usage: &mut DiskUsage,
+    size_mode: SizeMode,
+) -> Result<()> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.is_dir() {
+        add_artifact_file(path, crate_artifact, usage, size_mode)?;
+    } else {
         let entries = fs::read_dir(path).map_err(|source| HoldError::IoError {
             path: path.to_path_buf(),
             source,
@@ -140,7 +385,7 @@ fn add_artifact_files(path: &Path, crate_artifact: &mut CrateArtifact) -> Result
                 path: path.to_path_buf(),
                 source,
             })?;
-            add_artifact_files(&entry.path(), crate_artifact)?;
+            add_artifact_files(&entry.path(), crate_artifact, usage, size_mode)?;
         }
     }
 
@@ -148,20 +393,25 @@ fn add_artifact_files(path: &Path, crate_artifact: &mut CrateArtifact) -> Result
 }
 
 /// Add a single artifact file to a crate artifact
-fn add_artifact_file(path: &Path, crate_artifact: &mut CrateArtifact) -> Result<()> {
-    let metadata = fs::metadata(path).map_err(|source| HoldError::IoError {
+fn add_artifact_file(
+    path: &Path,
+    crate_artifact: &mut CrateArtifact,
+    usage: &mut DiskUsage,
+    size_mode: SizeMode,
+) -> Result<()> {
+    let metadata = fs::symlink_metadata(path).map_err(|source| HoldError::IoError {
         path: path.to_path_buf(),
         source,
     })?;
 
     // If it's a directory, add all its contents but not the directory itself
     if metadata.is_dir() {
-        add_artifact_files(path, crate_artifact)?;
+        add_artifact_files(path, crate_artifact, usage, size_mode)?;
         // Also add the directory itself as an artifact to ensure it gets removed
         let artifact_info = ArtifactInfo {
             path: path.to_path_buf(),
-            size: 0,                           // Directories don't have meaningful size
-            _modified: SystemTime::UNIX_EPOCH, // Don't use directory mtime for age calculation
+            size: 0,                          // Directories don't have meaningful size
+            modified: SystemTime::UNIX_EPOCH, // Don't use directory mtime for age calculation
         };
         crate_artifact.artifacts.push(artifact_info);
     } else {
@@ -173,8 +423,8 @@ fn add_artifact_file(path: &Path, crate_artifact: &mut CrateArtifact) -> Result<
 
         let artifact_info = ArtifactInfo {
             path: path.to_path_buf(),
-            size: metadata.len(),
-            _modified: modified,
+            size: usage.measure(&metadata).get(size_mode),
+            modified,
         };
 
         crate_artifact.total_size += artifact_info.size;
@@ -191,8 +441,10 @@ fn add_artifact_file(path: &Path, crate_artifact: &mut CrateArtifact) -> Result<
 /// Select artifacts to remove based on both size and age constraints
 ///
 /// This function implements a two-phase cleanup strategy:
-/// 1. **Size enforcement**: If a size limit is specified and exceeded, removes
-///    oldest artifacts first until the target directory is under the limit
+/// 1. **Size enforcement**: If a size or file-count limit is specified and
+///    exceeded, removes oldest artifacts first until the target directory is
+///    under both limits, counting artifacts costly to rebuild as younger (see
+///    [`BuildCosts`](super::BuildCosts))
 /// 2. **Age cleanup**: After size compliance, removes any remaining artifacts
 ///    older than the specified age threshold
 ///
@@ -202,95 +454,147 @@ fn add_artifact_file(path: &Path, crate_artifact: &mut CrateArtifact) -> Result<
 /// # Arguments
 ///
 /// * `crate_artifacts` - List of crate artifacts to consider for removal
-/// * `current_size` - Current total size of all artifacts in bytes
-/// * `max_size` - Optional maximum size limit in bytes
-/// * `age_threshold_days` - Age threshold in days (artifacts older than this
-///   are removed)
-/// * `previous_build_mtime_nanos` - Optional timestamp of the previous build to
-///   preserve
-/// * `verbose` - Verbosity level for debug output
-/// * `quiet` - Suppress logging
+/// * `budget` - Current size and file count, and their optional limits
+/// * `age_threshold` - Age threshold (artifacts older than this are removed)
+/// * `preserve` - How artifacts from the previous build are preserved
+/// * `log` - Logger for the selection
 ///
 /// # Returns
 ///
 /// A vector of references to artifacts that should be removed
-pub(crate) fn select_artifacts_for_removal(
-    crate_artifacts: &[CrateArtifact],
-    current_size: u64,
-    max_size: Option<u64>,
-    age_threshold_days: u32,
-    previous_build_mtime_nanos: Option<u128>,
-    verbose: u8,
-    quiet: bool,
-) -> Vec<&CrateArtifact> {
+pub(crate) fn select_artifacts_for_removal<'a>(
+    crate_artifacts: &'a [CrateArtifact],
+    budget: &Budget,
+    age_threshold: Duration,
+    preserve: &PreservePolicy,
+    log: &Logger,
+) -> Vec<&'a CrateArtifact> {
     let remaining = preserve_previous_build_artifacts(
         crate_artifacts.iter().collect(),
-        previous_build_mtime_nanos,
-        age_threshold_days,
-        verbose,
-        quiet,
+        preserve,
+        age_threshold,
+        log,
     );
 
-    let (mut to_remove, remaining) = select_for_size(remaining, current_size, max_size, quiet);
-    let age_selected = select_for_age(remaining, age_threshold_days, verbose, quiet);
+    let (mut to_remove, remaining) = select_for_size(remaining, budget, log);
+    let age_selected = select_for_age(remaining, age_threshold, log);
     to_remove.extend(age_selected);
 
     to_remove
 }
 
-fn preserve_previous_build_artifacts(
-    artifacts: Vec<&CrateArtifact>,
-    previous_build_mtime_nanos: Option<u128>,
-    age_threshold_days: u32,
-    verbose: u8,
-    quiet: bool,
-) -> Vec<&CrateArtifact> {
-    let log = Logger::new(verbose, quiet);
-    if let Some(previous_mtime_nanos) = previous_build_mtime_nanos {
-        let (duration, saturated) = saturating_duration_from_nanos(previous_mtime_nanos);
-        if saturated && !log.quiet() {
-            eprintln!(
-                "Warning: previous_build_mtime_nanos ({previous_mtime_nanos}) exceeds \
-                 representable range; clamping to ~year 2554.",
-            );
-        }
+/// Narrow crate artifacts to the files inside the `--only` scope, dropping
+/// groups with nothing left.
+///
+/// Each group keeps its original newest mtime so age decisions still reflect
+/// when the crate as a whole was last built.
+pub(crate) fn scope_artifacts(
+    crate_artifacts: Vec<CrateArtifact>,
+    filter: Option<&PathFilter>,
+) -> Vec<CrateArtifact> {
+    let Some(filter) = filter else {
+        return crate_artifacts;
+    };
+
+    crate_artifacts
+        .into_iter()
+        .filter_map(|mut artifact| {
+            artifact.artifacts.retain(|info| filter.matches(&info.path));
+            artifact.total_size = artifact.artifacts.iter().map(|info| info.size).sum();
+            (!artifact.artifacts.is_empty()).then_some(artifact)
+        })
+        .collect()
+}
 
-        let mut previous_mtime = SystemTime::UNIX_EPOCH + duration;
-        let now = SystemTime::now();
-        if previous_mtime > now {
-            previous_mtime = now;
+/// Set aside artifacts used by the last build so they are never evicted,
+/// returning only the artifacts that remain eligible for removal.
+pub(crate) fn exclude_build_units(
+    crate_artifacts: Vec<CrateArtifact>,
+    build_units: Option<&BuildUnits>,
+    log: &Logger,
+) -> Vec<CrateArtifact> {
+    let Some(build_units) = build_units else {
+        return crate_artifacts;
+    };
+
+    let (preserved, eligible): (Vec<_>, Vec<_>) = crate_artifacts
+        .into_iter()
+        .partition(|artifact| build_units.contains(artifact));
+
+    if !log.quiet() && !preserved.is_empty() {
+        let preserved_size: u64 = preserved.iter().map(|a| a.total_size).sum();
+        eprintln!(
+            "  Preserving {} artifacts ({}) used by the last build",
+            preserved.len(),
+            log.format_size(preserved_size)
+        );
+        if log.level() > 1 {
+            for artifact in &preserved {
+                eprintln!("    Preserving: {}-{}", artifact.name, artifact.hash);
+            }
         }
+    }
 
-        if age_threshold_days == 0 {
+    eligible
+}
+
+fn preserve_previous_build_artifacts<'a>(
+    artifacts: Vec<&'a CrateArtifact>,
+    preserve: &PreservePolicy,
+    age_threshold: Duration,
+    log: &Logger,
+) -> Vec<&'a CrateArtifact> {
+    if preserve.previous_builds_mtime_nanos.is_empty() {
+        return artifacts;
+    }
+
+    let window = match preserve.window {
+        Some(window) => window,
+        None if age_threshold.is_zero() => {
             log.verbose(
                 2,
-                "  Skipping previous build preservation because age threshold is 0 days",
+                "  Skipping previous build preservation because age threshold is 0",
             );
             return artifacts;
         }
+        None => age_threshold,
+    };
+
+    let now = SystemTime::now();
+    let mut oldest_valid: Option<SystemTime> = None;
+    let mut valid_builds = 0;
+    for &previous_mtime_nanos in &preserve.previous_builds_mtime_nanos {
+        let (duration, saturated) = saturating_duration_from_nanos(previous_mtime_nanos);
+        if saturated {
+            log.warn(format!(
+                "previous_build_mtime_nanos ({previous_mtime_nanos}) exceeds representable range; \
+                 clamping to ~year 2554."
+            ));
+        }
 
-        let age_threshold =
-            std::time::Duration::from_secs(age_threshold_days as u64 * 24 * 60 * 60);
-        let elapsed_since_previous = now
-            .duration_since(previous_mtime)
-            .unwrap_or(std::time::Duration::ZERO);
+        let previous_mtime = (SystemTime::UNIX_EPOCH + duration).min(now);
+        let elapsed_since_previous = now.duration_since(previous_mtime).unwrap_or(Duration::ZERO);
 
-        if elapsed_since_previous > age_threshold {
+        if elapsed_since_previous > window {
             log.verbose(
                 1,
                 format!(
                     "  Previous build timestamp is {elapsed_since_previous:?} old; exceeding \
-                     threshold, skipping preservation"
+                     preservation window, skipping it"
                 ),
             );
-            return artifacts;
+            continue;
         }
 
-        // Add a generous buffer to account for clock drift and build finishing before
-        // GC.
-        let buffer = std::time::Duration::from_secs(5 * 60);
+        oldest_valid = Some(oldest_valid.map_or(previous_mtime, |t| t.min(previous_mtime)));
+        valid_builds += 1;
+    }
+
+    // Every build in the history shares one cutoff: anything written since the
+    // oldest preserved build (minus the buffer) is kept.
+    if let Some(previous_mtime) = oldest_valid {
         let cutoff_time = previous_mtime
-            .checked_sub(buffer)
+            .checked_sub(preserve.buffer)
             .unwrap_or(SystemTime::UNIX_EPOCH);
 
         let (preserved, eligible): (Vec<_>, Vec<_>) = artifacts
@@ -299,11 +603,19 @@ fn preserve_previous_build_artifacts(
 
         if !log.quiet() && !preserved.is_empty() {
             let preserved_size: u64 = preserved.iter().map(|a| a.total_size).sum();
-            eprintln!(
-                "  Preserving {} artifacts ({}) from previous build",
-                preserved.len(),
-                format_size(preserved_size)
-            );
+            if valid_builds == 1 {
+                eprintln!(
+                    "  Preserving {} artifacts ({}) from previous build",
+                    preserved.len(),
+                    log.format_size(preserved_size)
+                );
+            } else {
+                eprintln!(
+                    "  Preserving {} artifacts ({}) from previous {valid_builds} builds",
+                    preserved.len(),
+                    log.format_size(preserved_size)
+                );
+            }
             if log.level() > 1 {
                 for artifact in &preserved {
                     eprintln!("    Preserving: {}-{}", artifact.name, artifact.hash);
@@ -317,79 +629,112 @@ fn preserve_previous_build_artifacts(
     artifacts
 }
 
-fn select_for_size(
-    mut remaining_artifacts: Vec<&CrateArtifact>,
-    current_size: u64,
-    max_size: Option<u64>,
-    quiet: bool,
-) -> (Vec<&CrateArtifact>, Vec<&CrateArtifact>) {
+fn select_for_size<'a>(
+    mut remaining_artifacts: Vec<&'a CrateArtifact>,
+    budget: &Budget,
+    log: &Logger,
+) -> (Vec<&'a CrateArtifact>, Vec<&'a CrateArtifact>) {
     let mut to_remove = Vec::new();
-    let log = Logger::new(0, quiet);
 
-    if let Some(max_size) = max_size {
-        if !log.quiet() {
+    if budget.max_size.is_none() && budget.max_files.is_none() {
+        return (to_remove, remaining_artifacts);
+    }
+
+    if !log.quiet() {
+        if let Some(max_size) = budget.max_size {
             eprintln!(
                 "  Size-based cleanup: current={}, max={}",
-                format_size(current_size),
-                format_size(max_size)
+                log.format_size(budget.size),
+                log.format_size(max_size)
             );
         }
+        if let Some(max_files) = budget.max_files {
+            eprintln!(
+                "  File-count cleanup: current={}, max={}",
+                budget.files, max_files
+            );
+        }
+    }
 
-        if current_size > max_size {
-            let needed = current_size - max_size;
-            if !log.quiet() {
-                eprintln!("  Need to free: {}", format_size(needed));
-            }
-
-            // Sort by age (oldest first)
-            remaining_artifacts.sort_by_key(|a| a.newest_mtime);
+    let needed = budget
+        .max_size
+        .map_or(0, |max_size| budget.size.saturating_sub(max_size));
+    let needed_files = budget
+        .max_files
+        .map_or(0, |max_files| budget.files.saturating_sub(max_files));
 
-            let mut freed = 0u64;
-            let mut kept_artifacts = Vec::new();
+    if needed > 0 || needed_files > 0 {
+        if !log.quiet() {
+            if needed > 0 {
+                eprintln!("  Need to free: {}", log.format_size(needed));
+            }
+            if needed_files > 0 {
+                eprintln!("  Need to remove: {needed_files} files");
+            }
+        }
 
-            for artifact in remaining_artifacts {
-                if freed < needed {
-                    to_remove.push(artifact);
-                    freed += artifact.total_size;
-                } else {
-                    kept_artifacts.push(artifact);
-                }
+        // Sort by policy score (highest first), then by age weighted by
+        // rebuild cost (oldest first), breaking ties by name and hash so
+        // identical caches always evict the same artifacts
+        let ages = weighted_ages(&remaining_artifacts, SystemTime::now());
+        let mut candidates: Vec<(f64, &CrateArtifact)> =
+            ages.into_iter().zip(remaining_artifacts).collect();
+        candidates.sort_by(|(a_age, a), (b_age, b)| {
+            b.eviction_score
+                .total_cmp(&a.eviction_score)
+                .then(b_age.total_cmp(a_age))
+                .then(a.newest_mtime.cmp(&b.newest_mtime))
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.hash.cmp(&b.hash))
+        });
+
+        let mut freed = 0u64;
+        let mut files_freed = 0u64;
+        let mut kept_artifacts = Vec::new();
+
+        for (_, artifact) in candidates {
+            if freed < needed || files_freed < needed_files {
+                to_remove.push(artifact);
+                freed += artifact.total_size;
+                files_freed += artifact.file_count();
+            } else {
+                kept_artifacts.push(artifact);
             }
+        }
 
-            remaining_artifacts = kept_artifacts;
+        remaining_artifacts = kept_artifacts;
 
-            if !log.quiet() {
-                eprintln!(
-                    "  Size cleanup will remove {} crates, freeing {}",
-                    to_remove.len(),
-                    format_size(freed)
-                );
-            }
-        } else if !log.quiet() {
-            eprintln!("  Already within target size");
+        if !log.quiet() {
+            eprintln!(
+                "  Size cleanup will remove {} crates, freeing {} ({} files)",
+                to_remove.len(),
+                log.format_size(freed),
+                files_freed
+            );
         }
+    } else if !log.quiet() {
+        eprintln!("  Already within target size");
     }
 
     (to_remove, remaining_artifacts)
 }
 
-fn select_for_age(
-    remaining_artifacts: Vec<&CrateArtifact>,
-    age_threshold_days: u32,
-    verbose: u8,
-    quiet: bool,
-) -> Vec<&CrateArtifact> {
+fn select_for_age<'a>(
+    remaining_artifacts: Vec<&'a CrateArtifact>,
+    age_threshold: Duration,
+    log: &Logger,
+) -> Vec<&'a CrateArtifact> {
     let mut to_remove = Vec::new();
-    let log = Logger::new(verbose, quiet);
 
     if !log.quiet() {
-        eprintln!("  Age-based cleanup: removing artifacts older than {age_threshold_days} days");
+        eprintln!(
+            "  Age-based cleanup: removing artifacts older than {}",
+            format_duration(age_threshold)
+        );
     }
 
     let cutoff = SystemTime::now()
-        .checked_sub(std::time::Duration::from_secs(
-            age_threshold_days as u64 * 24 * 60 * 60,
-        ))
+        .checked_sub(age_threshold)
         .unwrap_or(SystemTime::UNIX_EPOCH);
 
     let now = SystemTime::now();
@@ -397,17 +742,26 @@ fn select_for_age(
     let mut age_removed_size = 0u64;
 
     for artifact in remaining_artifacts {
-        let age_days = now
+        let age = now
             .duration_since(artifact.newest_mtime)
-            .map(|d| d.as_secs() / (24 * 60 * 60))
-            .unwrap_or(0);
+            .unwrap_or_default();
 
-        if artifact.newest_mtime < cutoff {
+        if artifact.newest_mtime < cutoff && artifact.inputs_unchanged {
+            log.verbose(
+                2,
+                format!(
+                    "    Keeping old crate {} with unchanged sources: age={}",
+                    artifact.name,
+                    format_age(age)
+                ),
+            );
+        } else if artifact.newest_mtime < cutoff {
             log.verbose(
                 2,
                 format!(
-                    "    Removing old crate {}: age={} days",
-                    artifact.name, age_days
+                    "    Removing old crate {}: age={}",
+                    artifact.name,
+                    format_age(age)
                 ),
             );
             age_removed_count += 1;
@@ -420,30 +774,9 @@ fn select_for_age(
         eprintln!(
             "  Age cleanup will remove {} additional crates, freeing {}",
             age_removed_count,
-            format_size(age_removed_size)
+            log.format_size(age_removed_size)
         );
     }
 
     to_remove
 }
-
-/// Remove all artifacts for a crate
-pub(crate) fn remove_crate_artifacts(crate_artifact: &CrateArtifact) -> Result<()> {
-    for artifact in &crate_artifact.artifacts {
-        if artifact.path.exists() {
-            if artifact.path.is_dir() {
-                fs::remove_dir_all(&artifact.path).map_err(|source| HoldError::IoError {
-                    path: artifact.path.clone(),
-                    source,
-                })?;
-            } else {
-                fs::remove_file(&artifact.path).map_err(|source| HoldError::IoError {
-                    path: artifact.path.clone(),
-                    source,
-                })?;
-            }
-        }
-    }
-
-    Ok(())
-}
@@ -0,0 +1,211 @@
+//! Append-only log of the paths garbage collection removed, written by
+//! `--gc-audit-log`.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::removals::RemovedPath;
+use crate::error::{HoldError, Result};
+use crate::schema::SCHEMA_VERSION;
+
+/// Size past which the audit log is rotated before more is appended
+const ROTATE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Number of rotated audit logs kept next to the current one, as
+/// `<path>.1` (the most recent) to `<path>.4`
+const KEPT_ROTATIONS: u32 = 4;
+
+/// How entries of the audit log are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuditLogFormat {
+    /// `<unix time>\t<phase>\t<reason>\t<size>\t<path>` lines
+    #[default]
+    Text,
+    /// A JSON object per line
+    Jsonl,
+}
+
+impl FromStr for AuditLogFormat {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(HoldError::InvalidAuditLogFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for AuditLogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => f.write_str("text"),
+            Self::Jsonl => f.write_str("jsonl"),
+        }
+    }
+}
+
+/// Append an entry per path of `removed` to the audit log at `path`,
+/// rotating the log first once it grew past 16 MiB
+pub(crate) fn append_audit_log(
+    removed: &[RemovedPath],
+    format: AuditLogFormat,
+    path: &Path,
+) -> Result<()> {
+    if removed.is_empty() {
+        return Ok(());
+    }
+    let io_error = |source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut entries = Vec::new();
+    render(removed, format, time, &mut entries).map_err(io_error)?;
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() >= ROTATE_SIZE) {
+        rotate(path).map_err(io_error)?;
+    }
+    // A single write keeps the entries of concurrent runs from interleaving
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&entries))
+        .map_err(io_error)
+}
+
+fn render(
+    removed: &[RemovedPath],
+    format: AuditLogFormat,
+    time: u64,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    for entry in removed {
+        match format {
+            AuditLogFormat::Text => writeln!(
+                out,
+                "{time}\t{}\t{}\t{}\t{}",
+                entry.phase,
+                entry.reason,
+                entry.size,
+                entry.path.display()
+            )?,
+            AuditLogFormat::Jsonl => {
+                let line = serde_json::json!({
+                    "schema_version": SCHEMA_VERSION,
+                    "time": time,
+                    "phase": entry.phase.to_string(),
+                    "reason": entry.reason.to_string(),
+                    "size": entry.size,
+                    "path": entry.path.to_string_lossy(),
+                });
+                serde_json::to_writer(&mut *out, &line)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shift `<path>.1`, `<path>.2`, ... up by one, dropping the oldest, and
+/// move the log itself to `<path>.1`
+fn rotate(path: &Path) -> io::Result<()> {
+    let rotated = |n: u32| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    };
+    for n in (1..KEPT_ROTATIONS).rev() {
+        match fs::rename(rotated(n), rotated(n + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gc::{GcPhase, RemovalReason};
+
+    fn removed() -> Vec<RemovedPath> {
+        vec![RemovedPath {
+            path: PathBuf::from("target/debug/deps/libserde-1a2b.rlib"),
+            size: 4096,
+            phase: GcPhase::TargetDir,
+            reason: RemovalReason::Cap,
+        }]
+    }
+
+    #[test]
+    fn test_render_entries() {
+        let mut out = Vec::new();
+        render(&removed(), AuditLogFormat::Text, 1_700_000_000, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1700000000\ttarget-dir\tcap\t4096\ttarget/debug/deps/libserde-1a2b.rlib\n"
+        );
+
+        let mut out = Vec::new();
+        render(&removed(), AuditLogFormat::Jsonl, 1_700_000_000, &mut out).unwrap();
+        let line: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["phase"], "target-dir");
+        assert_eq!(line["reason"], "cap");
+        assert_eq!(line["size"], 4096);
+        assert_eq!(line["time"], 1_700_000_000);
+    }
+
+    #[test]
+    fn test_audit_log_appends_and_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gc-audit.log");
+        append_audit_log(&removed(), AuditLogFormat::Text, &path).unwrap();
+        append_audit_log(&removed(), AuditLogFormat::Text, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        // A full log moves to <path>.1, pushing the older rotations up
+        fs::write(dir.path().join("gc-audit.log.1"), "older").unwrap();
+        fs::File::create(&path)
+            .unwrap()
+            .set_len(ROTATE_SIZE)
+            .unwrap();
+        append_audit_log(&removed(), AuditLogFormat::Text, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert_eq!(
+            fs::metadata(dir.path().join("gc-audit.log.1"))
+                .unwrap()
+                .len(),
+            ROTATE_SIZE
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("gc-audit.log.2")).unwrap(),
+            "older"
+        );
+
+        append_audit_log(&[], AuditLogFormat::Text, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            "JSONL".parse::<AuditLogFormat>().unwrap(),
+            AuditLogFormat::Jsonl
+        );
+        assert_eq!(
+            "text".parse::<AuditLogFormat>().unwrap(),
+            AuditLogFormat::Text
+        );
+        assert!("json".parse::<AuditLogFormat>().is_err());
+    }
+}
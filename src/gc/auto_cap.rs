@@ -1,17 +1,22 @@
 use crate::state::{CapTrace, GcMetrics};
 
-pub(crate) const GC_METRICS_WINDOW: usize = 20;
 pub(crate) const MIN_HEADROOM_BYTES: u64 = 2 * 1024 * 1024 * 1024; // 2 GiB safety cushion
 pub(crate) const MIN_STEADY_HEADROOM_BYTES: u64 = 256 * 1024 * 1024; // 256 MiB cushion once a cap exists
 pub(crate) const MAX_GROWTH_FACTOR_PER_RUN_PCT: u64 = 10; // limit upward drift to +10% per run
 pub(crate) const MAX_SHRINK_FACTOR_PER_RUN_PCT: u64 = 10; // limit downward drift to -10% per run
 pub(crate) const GROWTH_DEADBAND_PCT: u64 = 5; // tolerate small oscillations without moving the cap
 pub(crate) const HARD_CEILING_MIN_FINALS: usize = 3; // require enough history before clamping
-
-pub(crate) fn push_bounded(vec: &mut Vec<u64>, value: u64) {
+pub(crate) const REBUILD_COST_STEP_SECS: u64 = 5 * 60; // each 5 minutes a rebuild takes...
+pub(crate) const REBUILD_COST_PCT_PER_STEP: u64 = 10; // ...adds 10% to the growth budget
+pub(crate) const MAX_REBUILD_COST_PCT: u64 = 100; // at most doubling it
+pub(crate) const WARMUP_RUNS: u32 = 3; // size limits wait for this many recorded runs
+
+/// Push `value`, keeping the last `window` values; a window shorter than
+/// before drops the oldest values beyond it
+pub(crate) fn push_bounded(vec: &mut Vec<u64>, value: u64, window: usize) {
     vec.push(value);
-    if vec.len() > GC_METRICS_WINDOW {
-        let overflow = vec.len() - GC_METRICS_WINDOW;
+    if vec.len() > window {
+        let overflow = vec.len() - window;
         vec.drain(0..overflow);
     }
 }
@@ -31,6 +36,10 @@ pub(crate) fn suggest_max_target_size(
     let baseline = baseline_from_finals(&finals);
     let has_prev_cap = metrics.last_suggested_cap.is_some();
     let growth_budget = growth_budget_from_growths(&growths, has_prev_cap);
+    // Evicting artifacts that a slow build has to recreate costs more, so
+    // leave more room when the reported builds are slow
+    let growth_budget =
+        growth_budget.saturating_add(growth_budget / 100 * rebuild_cost_pct(metrics));
 
     let mut proposed = baseline.saturating_add(growth_budget);
 
@@ -117,6 +126,14 @@ pub(crate) fn suggest_max_target_size(
     ))
 }
 
+/// Extra growth budget, in percent, for the median duration of recent
+/// successful builds
+fn rebuild_cost_pct(metrics: &GcMetrics) -> u64 {
+    metrics.median_build_secs().map_or(0, |secs| {
+        (secs / REBUILD_COST_STEP_SECS * REBUILD_COST_PCT_PER_STEP).min(MAX_REBUILD_COST_PCT)
+    })
+}
+
 pub(crate) fn percentile(sorted: &[u64], p: u32) -> u64 {
     if sorted.is_empty() {
         return 0;
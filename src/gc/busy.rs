@@ -0,0 +1,238 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::{fmt, fs, thread};
+
+use crate::error::{HoldError, Result};
+use crate::lock::{CARGO_LOCK_FILE, is_locked, lock_paths};
+use crate::logging::Logger;
+
+/// How long [`BusyPolicy::Wait`] waits for builds to finish before failing
+pub(crate) const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How often a busy target directory is re-checked while waiting
+const BUSY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What to do when a cargo build is using the target directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BusyPolicy {
+    /// Wait for the build to finish before collecting garbage
+    #[default]
+    Wait,
+    /// Skip garbage collection entirely
+    Skip,
+    /// Fail with an error
+    Fail,
+}
+
+impl FromStr for BusyPolicy {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "wait" => Ok(Self::Wait),
+            "skip" => Ok(Self::Skip),
+            "fail" => Ok(Self::Fail),
+            _ => Err(HoldError::InvalidBusyPolicy(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BusyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wait => f.write_str("wait"),
+            Self::Skip => f.write_str("skip"),
+            Self::Fail => f.write_str("fail"),
+        }
+    }
+}
+
+/// Evidence that a build is using the target directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BusyReason {
+    /// Another process holds cargo's lock on a profile directory
+    CargoLock(PathBuf),
+    /// Another process has a file in the target directory open
+    OpenFile {
+        /// Process holding the file open
+        pid: u32,
+        /// The open file
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for BusyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CargoLock(path) => write!(f, "{} is locked", path.display()),
+            Self::OpenFile { pid, path } => {
+                write!(f, "process {pid} has {} open", path.display())
+            }
+        }
+    }
+}
+
+/// Check whether a cargo build appears to be running against `target_dir`.
+///
+/// Cargo holds an exclusive lock on `<profile>/.cargo-lock` for the duration
+/// of a build, so a lock we cannot take is the primary signal. Because rustc
+/// and linkers invoked outside of cargo do not take that lock, open file
+/// handles inside the target directory are checked as well (Linux only).
+pub(crate) fn detect_busy(target_dir: &Path) -> Result<Option<BusyReason>> {
+    if !target_dir.exists() {
+        return Ok(None);
+    }
+
+    for lock_path in lock_paths(target_dir)? {
+        if is_locked(&lock_path) {
+            return Ok(Some(BusyReason::CargoLock(lock_path)));
+        }
+    }
+
+    Ok(find_open_file(target_dir))
+}
+
+/// Wait for `target_dir` to become idle according to `policy`.
+///
+/// Returns `Ok(true)` when garbage collection may proceed and `Ok(false)`
+/// when it should be skipped.
+pub(crate) fn wait_until_idle(
+    target_dir: &Path,
+    policy: BusyPolicy,
+    timeout: Duration,
+    log: &Logger,
+) -> Result<bool> {
+    let started = Instant::now();
+    let mut announced = false;
+
+    while let Some(reason) = detect_busy(target_dir)? {
+        match policy {
+            BusyPolicy::Skip => {
+                log.info(format!(
+                    "Target directory is in use ({reason}); skipping garbage collection"
+                ));
+                return Ok(false);
+            }
+            BusyPolicy::Fail => {
+                return Err(HoldError::TargetBusy {
+                    path: target_dir.to_path_buf(),
+                    reason: reason.to_string(),
+                });
+            }
+            BusyPolicy::Wait => {
+                if started.elapsed() >= timeout {
+                    return Err(HoldError::TargetBusy {
+                        path: target_dir.to_path_buf(),
+                        reason: format!("{reason} (gave up after {}s)", timeout.as_secs()),
+                    });
+                }
+                if !announced {
+                    log.info(format!(
+                        "Target directory is in use ({reason}); waiting for it to become idle..."
+                    ));
+                    announced = true;
+                }
+                thread::sleep(BUSY_POLL_INTERVAL);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Find a file inside `target_dir` held open by another process.
+///
+/// Processes we are not allowed to inspect are ignored.
+#[cfg(target_os = "linux")]
+fn find_open_file(target_dir: &Path) -> Option<BusyReason> {
+    let target_dir = fs::canonicalize(target_dir).ok()?;
+    let own_pid = std::process::id();
+
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == own_pid {
+            continue;
+        }
+
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Ok(path) = fs::read_link(fd.path())
+                && path.starts_with(&target_dir)
+                && !is_lock_file(&path)
+            {
+                return Some(BusyReason::OpenFile { pid, path });
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_open_file(_target_dir: &Path) -> Option<BusyReason> {
+    None
+}
+
+/// Lock files are held open by idle tools too, and are covered by the lock
+/// probe instead.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_lock_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == CARGO_LOCK_FILE || name == ".package-cache")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use fs4::FileExt;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_busy_policy() {
+        assert_eq!("wait".parse::<BusyPolicy>().unwrap(), BusyPolicy::Wait);
+        assert_eq!("Skip".parse::<BusyPolicy>().unwrap(), BusyPolicy::Skip);
+        assert_eq!("fail".parse::<BusyPolicy>().unwrap(), BusyPolicy::Fail);
+        assert!("later".parse::<BusyPolicy>().is_err());
+        assert_eq!(BusyPolicy::default().to_string(), "wait");
+    }
+
+    #[test]
+    fn test_detect_busy_honors_cargo_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let profile_dir = temp_dir.path().join("debug");
+        fs::create_dir_all(profile_dir.join("deps")).unwrap();
+        let lock_path = profile_dir.join(".cargo-lock");
+        File::create(&lock_path).unwrap();
+
+        assert_eq!(detect_busy(temp_dir.path()).unwrap(), None);
+
+        let holder = File::open(&lock_path).unwrap();
+        FileExt::lock(&holder).unwrap();
+        assert_eq!(
+            detect_busy(temp_dir.path()).unwrap(),
+            Some(BusyReason::CargoLock(lock_path.clone()))
+        );
+
+        let log = Logger::new(0, true);
+        assert!(!wait_until_idle(temp_dir.path(), BusyPolicy::Skip, Duration::ZERO, &log).unwrap());
+        assert!(matches!(
+            wait_until_idle(temp_dir.path(), BusyPolicy::Fail, Duration::ZERO, &log),
+            Err(HoldError::TargetBusy { .. })
+        ));
+
+        FileExt::unlock(&holder).unwrap();
+        assert!(wait_until_idle(temp_dir.path(), BusyPolicy::Fail, Duration::ZERO, &log).unwrap());
+    }
+}
@@ -1,11 +1,20 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use rayon::prelude::*;
 
+use super::age::GcArea;
 use super::config::Gc;
+use super::duration::format_duration;
+use super::global_cache::{GlobalCache, GlobalCacheMode, LastUses};
+use super::lockfile::GitRefs;
+use super::removals::{GcPhase, RemovalReason};
 use crate::error::{HoldError, Result};
+use crate::lock::PackageCacheLock;
+use crate::logging::Logger;
 
 #[derive(Debug, Default)]
 pub struct CargoRegistryStats {
@@ -20,24 +29,52 @@ pub(crate) fn clean_cargo_registry_with_home(
     verbose: u8,
 ) -> Result<CargoRegistryStats> {
     let mut stats = CargoRegistryStats::default();
-
-    // Clean old registry cache files
-    let registry_cache = cargo_home.join("registry").join("cache");
-    if registry_cache.exists() {
-        let cache_stats = clean_old_files(
-            config,
-            &registry_cache,
-            config.age_threshold_days(),
-            verbose,
-        )?;
-        stats.bytes_freed += cache_stats.bytes_freed;
-        stats.files_removed += cache_stats.files_removed;
+    let log = config.logger(verbose);
+
+    // Take the lock cargo's own GC takes so no cargo command reads or
+    // extracts packages while they are being removed
+    let _lock = if config.dry_run() || !cargo_home.exists() {
+        None
+    } else {
+        Some(PackageCacheLock::acquire(cargo_home, &log)?)
+    };
+    let mut global_cache = open_global_cache(config, cargo_home, &log);
+    let last_uses = global_cache.as_ref().map(|(_, last_uses)| last_uses);
+
+    // Git revisions pinned by lockfiles are kept regardless of age
+    let git_refs = GitRefs::from_lockfiles(config.lockfiles())?;
+    if !config.quiet() && verbose > 1 && !git_refs.is_empty() {
+        eprintln!(
+            "  Keeping git checkouts referenced by {} lockfile(s)",
+            config.lockfiles().len()
+        );
     }
 
+    // Clean old registry packages, keeping downloads and extracted sources in
+    // sync
+    let registry = cargo_home.join("registry");
+    let registry_stats = clean_registry_packages(
+        config,
+        &registry.join("cache"),
+        &registry.join("src"),
+        last_uses,
+        verbose,
+    )?;
+    stats.bytes_freed += registry_stats.bytes_freed;
+    stats.files_removed += registry_stats.files_removed;
+    stats.dirs_removed += registry_stats.dirs_removed;
+
     // Clean old git checkouts
     let git_checkouts = cargo_home.join("git").join("checkouts");
     if git_checkouts.exists() {
-        let git_stats = clean_old_directories(config, &git_checkouts, 30, verbose)?;
+        let git_stats = clean_git_checkouts(
+            config,
+            &git_checkouts,
+            config.age_threshold_for(GcArea::Git),
+            &git_refs,
+            last_uses,
+            verbose,
+        )?;
         stats.bytes_freed += git_stats.bytes_freed;
         stats.dirs_removed += git_stats.dirs_removed;
     }
@@ -45,18 +82,24 @@ pub(crate) fn clean_cargo_registry_with_home(
     // Clean old git db entries
     let git_db = cargo_home.join("git").join("db");
     if git_db.exists() {
-        let git_stats = clean_old_directories(config, &git_db, 30, verbose)?;
+        let git_stats = clean_old_directories(
+            config,
+            &git_db,
+            config.age_threshold_for(GcArea::Git),
+            last_uses,
+            verbose,
+            |db| git_refs.references_repository(db),
+        )?;
         stats.bytes_freed += git_stats.bytes_freed;
         stats.dirs_removed += git_stats.dirs_removed;
     }
 
-    // Clean old registry sources
-    let registry_src = cargo_home.join("registry").join("src");
-    if registry_src.exists() {
-        let src_stats = clean_old_directories(config, &registry_src, 30, verbose)?;
-        stats.bytes_freed += src_stats.bytes_freed;
-        stats.dirs_removed += src_stats.dirs_removed;
-        // 30 days for sources
+    // Drop the records of removed entries so cargo does not track files that
+    // no longer exist
+    if let Some((db, last_uses)) = &mut global_cache
+        && let Err(e) = db.forget(&last_uses.take_removed())
+    {
+        log.warn(error_chain(&e));
     }
 
     Ok(stats)
@@ -98,7 +141,8 @@ pub(crate) fn clean_cargo_bin_with_home(
         "cargo-hold", // Keep ourselves!
     ];
 
-    let cutoff = age_cutoff(30);
+    let threshold = config.age_threshold_for(GcArea::Bin);
+    let cutoff = age_cutoff(threshold);
 
     let entries: Vec<_> = fs::read_dir(&cargo_bin)
         .map_err(|source| HoldError::IoError {
@@ -122,18 +166,22 @@ pub(crate) fn clean_cargo_bin_with_home(
                         .any(|pattern| name.starts_with(pattern));
 
                 if !should_keep
+                    && !config.out_of_time()
                     && let Ok(metadata) = fs::metadata(path)
                     && let Ok(modified) = metadata.modified()
                     && modified < cutoff
                 {
-                    let size = metadata.len();
+                    let size = config.size_mode().measure(&metadata);
                     if !config.quiet() && verbose > 1 {
-                        eprintln!("  Removing old cargo binary: {name} (older than 30 days)");
+                        eprintln!(
+                            "  Removing old cargo binary: {name} (older than {})",
+                            format_duration(threshold)
+                        );
                     }
-                    if !config.dry_run() {
-                        let _ = fs::remove_file(path);
+                    if config.remove(path) {
+                        config.record_removal(path, size, GcPhase::CargoHome, RemovalReason::Age);
+                        return size;
                     }
-                    return size;
                 }
             }
             0
@@ -143,7 +191,67 @@ pub(crate) fn clean_cargo_bin_with_home(
     Ok(bytes_freed)
 }
 
-/// Clean old files in a directory using walkdir and rayon
+/// Open cargo's global cache tracking database and read its last-use records,
+/// unless disabled or missing.
+///
+/// A database that cannot be read is reported and ignored, which falls back
+/// to file modification times.
+fn open_global_cache(
+    config: &Gc,
+    cargo_home: &Path,
+    log: &Logger,
+) -> Option<(GlobalCache, LastUses)> {
+    if config.global_cache_mode() == GlobalCacheMode::Off {
+        return None;
+    }
+
+    let opened = GlobalCache::open(cargo_home).and_then(|db| {
+        db.map(|db| {
+            let last_uses = db.last_uses(cargo_home)?;
+            Ok((db, last_uses))
+        })
+        .transpose()
+    });
+
+    match opened {
+        Ok(Some(global_cache)) => {
+            log.verbose(1, "  Using cargo's global cache tracking database");
+            Some(global_cache)
+        }
+        Ok(None) => {
+            log.verbose(
+                2,
+                "  No global cache tracking database, using file modification times",
+            );
+            None
+        }
+        Err(e) => {
+            log.warn(format!(
+                "{}, using file modification times",
+                error_chain(&e)
+            ));
+            None
+        }
+    }
+}
+
+/// An error and its underlying cause on one line
+fn error_chain(error: &HoldError) -> String {
+    match std::error::Error::source(error) {
+        Some(source) => format!("{error}: {source}"),
+        None => error.to_string(),
+    }
+}
+
+/// When an entry was last used: cargo's record if it has one, otherwise the
+/// modification time
+fn last_used(last_uses: Option<&LastUses>, path: &Path, modified: SystemTime) -> SystemTime {
+    last_uses
+        .and_then(|last_uses| last_uses.last_use(path))
+        .unwrap_or(modified)
+}
+
+/// What a cargo home cleanup pass removed
 #[derive(Debug, Default)]
 struct CleanupStats {
     bytes_freed: u64,
@@ -151,50 +259,191 @@ struct CleanupStats {
     dirs_removed: usize,
 }
 
-fn clean_old_files(
+/// A registry package as stored by cargo: the downloaded
+/// `cache/<index>/<name>-<version>.crate` and the extracted
+/// `src/<index>/<name>-<version>/`, either of which may be missing
+#[derive(Debug, Default)]
+struct RegistryPackage {
+    crate_file: Option<(PathBuf, SystemTime)>,
+    src_dir: Option<(PathBuf, SystemTime)>,
+}
+
+/// Clean registry packages, deciding once per package so that a kept
+/// `.crate` keeps its extracted sources and an evicted one takes them along.
+///
+/// A package is kept while its `.crate` is younger than the `registry-cache`
+/// threshold or its sources are younger than the `registry-src` threshold.
+fn clean_registry_packages(
     config: &Gc,
-    dir: &Path,
-    age_threshold_days: u32,
+    cache_dir: &Path,
+    src_dir: &Path,
+    last_uses: Option<&LastUses>,
     verbose: u8,
 ) -> Result<CleanupStats> {
-    let cutoff = age_cutoff(age_threshold_days);
+    let cache_threshold = config.age_threshold_for(GcArea::RegistryCache);
+    let src_threshold = config.age_threshold_for(GcArea::RegistrySrc);
+    let (cache_cutoff, src_cutoff) = (age_cutoff(cache_threshold), age_cutoff(src_threshold));
 
     if !config.quiet() && verbose > 1 {
-        eprintln!("  Cleaning old files in {dir:?} (>{age_threshold_days} days)");
+        eprintln!(
+            "  Cleaning registry packages (cache >{}, src >{})",
+            format_duration(cache_threshold),
+            format_duration(src_threshold)
+        );
     }
 
-    // Collect all files that need to be checked
-    let files_to_check: Vec<_> = walkdir::WalkDir::new(dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .map(|e| e.path().to_path_buf())
-        .collect();
+    let mut packages: HashMap<(OsString, String), RegistryPackage> = HashMap::new();
+    for (index, path, modified) in registry_entries(cache_dir)? {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let name = name.strip_suffix(".crate").unwrap_or(name).to_string();
+        let modified = last_used(last_uses, &path, modified);
+        packages.entry((index, name)).or_default().crate_file = Some((path, modified));
+    }
+    for (index, path, modified) in registry_entries(src_dir)? {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let name = name.to_string();
+        let modified = last_used(last_uses, &path, modified);
+        packages.entry((index, name)).or_default().src_dir = Some((path, modified));
+    }
 
-    // Process files in parallel using rayon
-    let stats = files_to_check
+    let stats = packages
         .par_iter()
-        .map(|path| remove_file_if_older(config, path, cutoff))
+        .map(|(_, package)| {
+            let cache_fresh =
+                matches!(package.crate_file, Some((_, modified)) if modified >= cache_cutoff);
+            let src_fresh =
+                matches!(package.src_dir, Some((_, modified)) if modified >= src_cutoff);
+            if cache_fresh || src_fresh || config.out_of_time() {
+                return CleanupStats::default();
+            }
+
+            let mut stats = CleanupStats::default();
+            if let Some((path, _)) = &package.crate_file
+                && let Ok(metadata) = fs::metadata(path)
+            {
+                let size = config.size_mode().measure(&metadata);
+                if config.remove(path) {
+                    config.record_removal(path, size, GcPhase::CargoHome, RemovalReason::Age);
+                    stats.bytes_freed += size;
+                    stats.files_removed += 1;
+                    forget(config, last_uses, path);
+                }
+            }
+            if let Some((path, _)) = &package.src_dir
+                && let Ok(size) = super::cleanup::calculate_directory_size(path, config.size_mode())
+                && config.remove(path)
+            {
+                config.record_removal(path, size, GcPhase::CargoHome, RemovalReason::Age);
+                stats.bytes_freed += size;
+                stats.dirs_removed += 1;
+                forget(config, last_uses, path);
+            }
+            stats
+        })
         .reduce(CleanupStats::default, |mut acc, item| {
             acc.bytes_freed += item.bytes_freed;
             acc.files_removed += item.files_removed;
+            acc.dirs_removed += item.dirs_removed;
             acc
         });
 
     Ok(stats)
 }
 
-/// Clean old directories
+/// Entries two levels below a registry directory (`<index>/<entry>`), with
+/// their index directory name and modification time
+fn registry_entries(dir: &Path) -> Result<Vec<(OsString, PathBuf, SystemTime)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let indexes = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+
+    let mut entries = Vec::new();
+    for index in indexes.filter_map(|e| e.ok()) {
+        let Ok(children) = fs::read_dir(index.path()) else {
+            continue;
+        };
+        for child in children.filter_map(|e| e.ok()) {
+            if let Ok(modified) = child.metadata().and_then(|metadata| metadata.modified()) {
+                entries.push((index.file_name(), child.path(), modified));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Clean git checkouts, keeping revisions referenced by lockfiles.
+///
+/// Repositories that are still referenced are cleaned one revision at a time;
+/// all others are removed as a whole once they are old enough.
+fn clean_git_checkouts(
+    config: &Gc,
+    dir: &Path,
+    age_threshold: Duration,
+    git_refs: &GitRefs,
+    last_uses: Option<&LastUses>,
+    verbose: u8,
+) -> Result<CleanupStats> {
+    let mut stats = clean_old_directories(
+        config,
+        dir,
+        age_threshold,
+        last_uses,
+        verbose,
+        |repository| git_refs.references_repository(repository),
+    )?;
+    if git_refs.is_empty() {
+        return Ok(stats);
+    }
+
+    let repositories = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    for repository in repositories.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if !repository.is_dir() || !git_refs.references_repository(&repository) {
+            continue;
+        }
+        let checkout_stats = clean_old_directories(
+            config,
+            &repository,
+            age_threshold,
+            last_uses,
+            verbose,
+            |checkout| git_refs.references_checkout(checkout),
+        )?;
+        stats.bytes_freed += checkout_stats.bytes_freed;
+        stats.dirs_removed += checkout_stats.dirs_removed;
+    }
+
+    Ok(stats)
+}
+
+/// Clean old directories, except those `keep` returns true for
 fn clean_old_directories(
     config: &Gc,
     dir: &Path,
-    age_threshold_days: u32,
+    age_threshold: Duration,
+    last_uses: Option<&LastUses>,
     verbose: u8,
+    keep: impl Fn(&Path) -> bool + Sync,
 ) -> Result<CleanupStats> {
-    let cutoff = age_cutoff(age_threshold_days);
+    let cutoff = age_cutoff(age_threshold);
 
     if !config.quiet() && verbose > 1 {
-        eprintln!("  Cleaning old directories in {dir:?} (>{age_threshold_days} days)");
+        eprintln!(
+            "  Cleaning old directories in {dir:?} (>{})",
+            format_duration(age_threshold)
+        );
     }
 
     // Collect directories to check
@@ -205,13 +454,13 @@ fn clean_old_directories(
         })?
         .filter_map(|e| e.ok())
         .map(|e| e.path())
-        .filter(|p| p.is_dir())
+        .filter(|p| p.is_dir() && !keep(p))
         .collect();
 
     // Process directories in parallel
     let stats = entries
         .par_iter()
-        .map(|path| remove_dir_if_older(config, path, cutoff))
+        .map(|path| remove_dir_if_older(config, path, cutoff, last_uses))
         .reduce(CleanupStats::default, |mut acc, item| {
             acc.bytes_freed += item.bytes_freed;
             acc.dirs_removed += item.dirs_removed;
@@ -221,41 +470,39 @@ fn clean_old_directories(
     Ok(stats)
 }
 
-fn age_cutoff(age_threshold_days: u32) -> SystemTime {
+fn age_cutoff(age_threshold: Duration) -> SystemTime {
     SystemTime::now()
-        .checked_sub(std::time::Duration::from_secs(
-            age_threshold_days as u64 * 24 * 60 * 60,
-        ))
+        .checked_sub(age_threshold)
         .unwrap_or(SystemTime::UNIX_EPOCH)
 }
 
-fn remove_file_if_older(config: &Gc, path: &Path, cutoff: SystemTime) -> CleanupStats {
-    if let Ok(metadata) = fs::metadata(path)
-        && let Ok(modified) = metadata.modified()
-        && modified < cutoff
+/// Record a removed entry for the global cache database, if in use and not
+/// in a dry run
+fn forget(config: &Gc, last_uses: Option<&LastUses>, path: &Path) {
+    if let Some(last_uses) = last_uses
+        && !config.dry_run()
     {
-        let size = metadata.len();
-        if !config.dry_run() {
-            let _ = fs::remove_file(path);
-        }
-        return CleanupStats {
-            bytes_freed: size,
-            files_removed: 1,
-            dirs_removed: 0,
-        };
+        last_uses.removed(path);
     }
-    CleanupStats::default()
 }
 
-fn remove_dir_if_older(config: &Gc, path: &Path, cutoff: SystemTime) -> CleanupStats {
+fn remove_dir_if_older(
+    config: &Gc,
+    path: &Path,
+    cutoff: SystemTime,
+    last_uses: Option<&LastUses>,
+) -> CleanupStats {
     if let Ok(metadata) = fs::metadata(path)
         && let Ok(modified) = metadata.modified()
-        && modified < cutoff
-        && let Ok(size) = super::cleanup::calculate_directory_size(path)
+        && last_used(last_uses, path, modified) < cutoff
+        && !config.out_of_time()
+        && let Ok(size) = super::cleanup::calculate_directory_size(path, config.size_mode())
     {
-        if !config.dry_run() {
-            let _ = fs::remove_dir_all(path);
+        if !config.remove(path) {
+            return CleanupStats::default();
         }
+        config.record_removal(path, size, GcPhase::CargoHome, RemovalReason::Age);
+        forget(config, last_uses, path);
         return CleanupStats {
             bytes_freed: size,
             files_removed: 0,
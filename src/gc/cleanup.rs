@@ -1,166 +1,330 @@
+use std::cmp::Reverse;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
+use super::age::GcArea;
 use super::artifacts::{
-    collect_crate_artifacts, remove_crate_artifacts, select_artifacts_for_removal,
+    Budget, CrateArtifact, collect_crate_artifacts, exclude_build_units, scope_artifacts,
+    select_artifacts_for_removal,
 };
 use super::config::{Gc, GcStats};
-use super::size::format_size;
+use super::costs::weigh_build_costs;
+use super::depinfo::{DepInfo, split_stale};
+use super::duration::format_duration;
+use super::filter::PathFilter;
+use super::pins::exclude_pinned;
+use super::policy::apply_policy;
+use super::profiles::{find_profile_directories, profile_key};
+use super::removals::{GcPhase, RemovalReason};
+use super::size::{DiskSizes, DiskUsage, SizeMode, SymlinkPolicy};
 use crate::error::{HoldError, Result};
 use crate::logging::Logger;
 
-/// Find all profile directories in the target directory
-pub(crate) fn find_profile_directories(target_dir: &Path) -> Result<Vec<PathBuf>> {
-    let mut profile_dirs = Vec::new();
-
-    if !target_dir.exists() {
-        return Ok(profile_dirs);
-    }
-
-    // Check if target_dir itself is a profile directory
-    if is_profile_directory(target_dir) {
-        profile_dirs.push(target_dir.to_path_buf());
-        return Ok(profile_dirs);
-    }
-
-    // Look for profile directories in subdirectories
-    let entries = fs::read_dir(target_dir).map_err(|source| HoldError::IoError {
-        path: target_dir.to_path_buf(),
-        source,
-    })?;
-
-    for entry in entries {
-        let entry = entry.map_err(|source| HoldError::IoError {
-            path: target_dir.to_path_buf(),
-            source,
-        })?;
-        let path = entry.path();
-
-        if path.is_dir() {
-            // Skip special files
-            if let Some(name) = path.file_name() {
-                let name = name.to_string_lossy();
-                if name == "CACHEDIR.TAG" || name == ".rustc_info.json" {
-                    continue;
-                }
-            }
-
-            if is_profile_directory(&path) {
-                profile_dirs.push(path);
-            } else {
-                // Check subdirectories (for target triple directories)
-                if let Ok(subdirs) = find_profile_directories(&path) {
-                    profile_dirs.extend(subdirs);
-                }
-            }
+/// Newest mtime of every crate artifact group, per profile directory keyed by
+/// [`profile_key`]. Profiles without artifacts are left out.
+pub(crate) fn profile_artifact_mtimes(target_dir: &Path) -> Result<Vec<(String, Vec<u128>)>> {
+    let mut profiles = Vec::new();
+    for profile_dir in find_profile_directories(target_dir)? {
+        let mtimes: Vec<u128> = collect_crate_artifacts(&profile_dir, SizeMode::default())?
+            .iter()
+            .filter_map(newest_mtime_nanos)
+            .collect();
+        if !mtimes.is_empty() {
+            profiles.push((profile_key(target_dir, &profile_dir), mtimes));
         }
     }
-
-    Ok(profile_dirs)
+    Ok(profiles)
 }
 
-/// Check if a directory is a Cargo profile directory
-fn is_profile_directory(path: &Path) -> bool {
-    if !path.is_dir() {
-        return false;
-    }
+fn newest_mtime_nanos(artifact: &CrateArtifact) -> Option<u128> {
+    artifact
+        .newest_mtime
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|newest| newest.as_nanos())
+}
 
-    // Check for standard Cargo build artifacts
-    let artifact_dirs = ["build", "deps", ".fingerprint"];
-    artifact_dirs.iter().any(|&dir| path.join(dir).exists())
+/// The directory profile directories are cleaned in, the target directory or
+/// a build directory apart from it, with its caps
+pub(crate) struct ProfileRoot<'a> {
+    pub(crate) dir: &'a Path,
+    pub(crate) max_size: Option<u64>,
+    pub(crate) max_files: Option<u64>,
 }
 
-/// Clean a single profile directory
+/// Clean a single profile directory of `root`, whose initial size and what
+/// was freed in it so far are in `global_stats`
 pub(crate) fn clean_profile_directory(
     profile_dir: &Path,
+    root: &ProfileRoot,
     config: &Gc,
     verbose: u8,
     global_stats: &GcStats,
 ) -> Result<GcStats> {
-    let log = Logger::new(verbose, config.quiet());
+    let log = config.logger(verbose);
     let mut stats = GcStats::default();
 
     // First, preserve binaries
-    let binaries = preserve_binaries(profile_dir, verbose, config.quiet())?;
+    let binaries = preserve_binaries(profile_dir, &log)?;
     stats.binaries_preserved = binaries.len();
 
     // Remove incremental compilation data
     let incremental_dir = profile_dir.join("incremental");
-    if incremental_dir.exists() {
-        log.verbose(1, "  Removing incremental compilation data");
-        let size = calculate_directory_size(&incremental_dir)?;
-        if !config.dry_run() {
-            fs::remove_dir_all(&incremental_dir).map_err(|source| HoldError::IoError {
-                path: incremental_dir,
-                source,
-            })?;
+    let incremental_threshold = config.age_threshold_for(GcArea::Incremental);
+    if incremental_dir.exists()
+        && !config.keep_incremental()
+        && config.in_scope(&incremental_dir)
+        && !config.out_of_time()
+    {
+        if incremental_threshold.is_zero() {
+            log.verbose(1, "  Removing incremental compilation data");
+            let usage = calculate_disk_usage(&incremental_dir)?;
+            let size = usage.get(config.size_mode());
+            if config.remove(&incremental_dir) {
+                config.record_removal(
+                    &incremental_dir,
+                    size,
+                    GcPhase::TargetDir,
+                    RemovalReason::Age,
+                );
+                stats.bytes_freed += size;
+                stats.files_freed += usage.files;
+            }
+        } else {
+            log.verbose(
+                1,
+                format!(
+                    "  Removing incremental sessions older than {}",
+                    format_duration(incremental_threshold)
+                ),
+            );
+            let freed =
+                clean_old_incremental_sessions(&incremental_dir, incremental_threshold, config)?;
+            stats.bytes_freed += freed.get(config.size_mode());
+            stats.files_freed += freed.files;
         }
-        stats.bytes_freed += size;
     }
 
     // Collect and analyze crate artifacts
-    let crate_artifacts = collect_crate_artifacts(profile_dir)?;
+    let crate_artifacts = collect_crate_artifacts(profile_dir, config.size_mode())?;
 
     log.verbose(
         2,
         format!("  Found {} crate artifacts", crate_artifacts.len()),
     );
 
+    // Remember when this profile was last built so the next run can preserve
+    // its artifacts independently of other profiles.
+    let profile = profile_key(root.dir, profile_dir);
+    if let Some(newest) = crate_artifacts.iter().filter_map(newest_mtime_nanos).max() {
+        stats
+            .profile_build_mtime_nanos
+            .insert(profile.clone(), newest);
+    }
+
+    let crate_artifacts = scope_artifacts(crate_artifacts, config.path_filter());
+    let crate_artifacts = exclude_pinned(crate_artifacts, config.pinned_crates(), &log);
+    let crate_artifacts = exclude_build_units(crate_artifacts, config.build_units(), &log);
+    let crate_artifacts = weigh_build_costs(crate_artifacts, config.build_costs(), &log);
+    let (evicted, crate_artifacts) =
+        apply_policy(config.policy_command(), &profile, crate_artifacts, &log)?;
+    let dep_info = if config.dep_info() {
+        let workspace_root = config.target_dir().parent().unwrap_or(Path::new("."));
+        Some(DepInfo::load(profile_dir, workspace_root)?)
+    } else {
+        None
+    };
+    let (stale, crate_artifacts) = split_stale(dep_info.as_ref(), crate_artifacts, &log);
+    let evicted_size: u64 = evicted
+        .iter()
+        .chain(&stale)
+        .map(|artifact| artifact.total_size)
+        .sum();
+    let evicted_files: u64 = evicted
+        .iter()
+        .chain(&stale)
+        .map(CrateArtifact::file_count)
+        .sum();
+
     // Determine which crates to remove using combined logic
+
     // Calculate the current total size (initial - already freed globally)
     let current_total_size = global_stats
         .initial_size
-        .saturating_sub(global_stats.bytes_freed + stats.bytes_freed);
+        .saturating_sub(global_stats.bytes_freed + stats.bytes_freed + evicted_size);
+    let current_files = global_stats
+        .initial_files
+        .saturating_sub(global_stats.files_freed + stats.files_freed + evicted_files);
     if !log.quiet() && (log.level() > 1 || config.debug()) {
         eprintln!(
             "  Initial: {}, Freed globally: {}, Freed locally: {}, Current total: {}",
-            format_size(global_stats.initial_size),
-            format_size(global_stats.bytes_freed),
-            format_size(stats.bytes_freed),
-            format_size(current_total_size)
+            log.format_size(global_stats.initial_size),
+            log.format_size(global_stats.bytes_freed),
+            log.format_size(stats.bytes_freed),
+            log.format_size(current_total_size)
         );
     }
 
-    let to_remove = select_artifacts_for_removal(
+    let mut to_remove: Vec<&CrateArtifact> = evicted.iter().chain(&stale).collect();
+    to_remove.extend(select_artifacts_for_removal(
         &crate_artifacts,
-        current_total_size,
-        config.max_target_size(),
-        config.age_threshold_days(),
-        config.previous_build_mtime_nanos(),
-        verbose,
-        config.quiet(),
-    );
+        &Budget {
+            size: current_total_size,
+            max_size: root.max_size,
+            files: current_files,
+            max_files: root.max_files,
+        },
+        config.age_threshold_for(GcArea::Deps),
+        &config.preserve_policy(&profile),
+        &log,
+    ));
 
     if !log.quiet() && (log.level() > 1 || config.debug()) {
         eprintln!("  Selected {} crates for removal", to_remove.len());
     }
 
-    // Remove selected crates
-    for crate_artifact in to_remove {
+    // Remove selected crates, or compress those not compressed yet; the
+    // policy command's evictions come first and stale crates next, unless the
+    // time budget puts the largest ones first
+    let mut to_remove: Vec<_> = to_remove.into_iter().enumerate().collect();
+    if config.max_duration().is_some() {
+        to_remove.sort_by_key(|(_, crate_artifact)| Reverse(crate_artifact.total_size));
+    }
+    for (index, crate_artifact) in to_remove {
+        if config.out_of_time() {
+            stats.remaining_crates += 1;
+            stats.remaining_bytes += crate_artifact.total_size;
+            continue;
+        }
+        // The cold manifest only covers the target directory; compressing
+        // replaces each file, so none may resolve outside of it
+        if config.compress_cold()
+            && root.dir == config.target_dir()
+            && crate_artifact
+                .artifacts
+                .iter()
+                .all(|artifact| config.may_remove(&artifact.path))
+            && !config.with_cold_manifest(|manifest| {
+                manifest.is_cold(config.target_dir(), crate_artifact)
+            })
+        {
+            if !log.quiet() && log.level() > 1 {
+                eprintln!(
+                    "  Compressing {}-{} ({})",
+                    crate_artifact.name,
+                    crate_artifact.hash,
+                    log.format_size(crate_artifact.total_size)
+                );
+            }
+            if !config.dry_run() {
+                stats.bytes_freed += config.with_cold_manifest(|manifest| {
+                    manifest.compress(config.target_dir(), crate_artifact, config.size_mode())
+                })?;
+            }
+            stats.crates_compressed += 1;
+            continue;
+        }
+
         if !log.quiet() && log.level() > 1 {
             eprintln!(
                 "  Removing {}-{} ({})",
                 crate_artifact.name,
                 crate_artifact.hash,
-                format_size(crate_artifact.total_size)
+                log.format_size(crate_artifact.total_size)
             );
         }
 
+        let reason = if index < evicted.len() {
+            RemovalReason::Policy
+        } else if index < evicted.len() + stale.len() {
+            RemovalReason::Stale
+        } else if SystemTime::now()
+            .duration_since(crate_artifact.newest_mtime)
+            .is_ok_and(|age| age >= config.age_threshold_for(GcArea::Deps))
+        {
+            RemovalReason::Age
+        } else {
+            RemovalReason::Cap
+        };
+        let mut removed_all = true;
+        for artifact in &crate_artifact.artifacts {
+            // Gone already, e.g. along with a removed build script directory
+            if !config.dry_run() && !artifact.path.exists() {
+                continue;
+            }
+            if !config.remove(&artifact.path) {
+                removed_all = false;
+                continue;
+            }
+            config.record_removal(&artifact.path, artifact.size, GcPhase::TargetDir, reason);
+            stats.bytes_freed += artifact.size;
+            stats.files_freed += 1;
+            stats.artifacts_removed += 1;
+        }
+        if !removed_all {
+            continue;
+        }
         if !config.dry_run() {
-            remove_crate_artifacts(crate_artifact)?;
+            config.with_cold_manifest(|manifest| {
+                manifest.forget(config.target_dir(), crate_artifact);
+            });
         }
-
-        stats.bytes_freed += crate_artifact.total_size;
-        stats.artifacts_removed += crate_artifact.artifacts.len();
         stats.crates_cleaned += 1;
     }
 
     Ok(stats)
 }
 
+/// Remove incremental sessions (`incremental/<crate>-<hash>`) last written
+/// more than `threshold` ago, returning the space and files freed
+fn clean_old_incremental_sessions(
+    incremental_dir: &Path,
+    threshold: Duration,
+    config: &Gc,
+) -> Result<DiskSizes> {
+    let cutoff = SystemTime::now()
+        .checked_sub(threshold)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut freed = DiskSizes::default();
+
+    let entries = fs::read_dir(incremental_dir).map_err(|source| HoldError::IoError {
+        path: incremental_dir.to_path_buf(),
+        source,
+    })?;
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: incremental_dir.to_path_buf(),
+            source,
+        })?;
+        let path = entry.path();
+        let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+            continue;
+        };
+        if !path.is_dir() || modified >= cutoff || !config.in_scope(&path) {
+            continue;
+        }
+        if config.out_of_time() {
+            break;
+        }
+
+        let usage = calculate_disk_usage(&path)?;
+        if config.remove(&path) {
+            config.record_removal(
+                &path,
+                usage.get(config.size_mode()),
+                GcPhase::TargetDir,
+                RemovalReason::Age,
+            );
+            freed += usage;
+        }
+    }
+
+    Ok(freed)
+}
+
 /// Preserve binary files in the profile directory
-fn preserve_binaries(profile_dir: &Path, verbose: u8, quiet: bool) -> Result<Vec<PathBuf>> {
-    let log = Logger::new(verbose, quiet);
+fn preserve_binaries(profile_dir: &Path, log: &Logger) -> Result<Vec<PathBuf>> {
     let mut binaries = Vec::new();
 
     let entries = fs::read_dir(profile_dir).map_err(|source| HoldError::IoError {
@@ -212,65 +376,392 @@ fn preserve_binaries(profile_dir: &Path, verbose: u8, quiet: bool) -> Result<Vec
     Ok(binaries)
 }
 
-/// Clean miscellaneous directories (doc, package, tmp)
-pub(crate) fn clean_misc_directories(target_dir: &Path, config: &Gc, verbose: u8) -> Result<u64> {
-    let mut bytes_freed = 0;
-    let log = Logger::new(verbose, config.quiet());
+/// Remove every artifact of the crate `name` from all profile directories of
+/// the target directory and the build directory: deps, fingerprints, build
+/// script directories, and incremental data.
+///
+/// Names are compared the way cargo mangles them, so `foo-bar`, `foo_bar`,
+/// and the `libfoo_bar` file prefix all refer to the same crate. Paths are
+/// removed through [`Gc::remove`], so failures and refusals are counted in
+/// [`GcStats::errors`] and [`GcStats::refused_removals`] rather than freed.
+pub(crate) fn clean_named_crate(config: &Gc, name: &str, log: &Logger) -> Result<GcStats> {
+    let mut stats = GcStats::default();
+    let wanted = normalize_crate_name(name);
+    let size_mode = config.size_mode();
+
+    let mut profile_dirs = find_profile_directories(config.target_dir())?;
+    if let Some(build_dir) = config.build_dir()
+        && build_dir.exists()
+    {
+        profile_dirs.extend(find_profile_directories(build_dir)?);
+    }
+
+    for profile_dir in profile_dirs {
+        let mut removed_any = false;
+
+        for crate_artifact in collect_crate_artifacts(&profile_dir, size_mode)? {
+            if !crate_name_matches(&crate_artifact.name, &wanted) {
+                continue;
+            }
+            log.verbose(
+                1,
+                format!(
+                    "  Removing {}-{} from {} ({})",
+                    crate_artifact.name,
+                    crate_artifact.hash,
+                    profile_dir.display(),
+                    log.format_size(crate_artifact.total_size)
+                ),
+            );
+            for artifact in &crate_artifact.artifacts {
+                // Gone already, e.g. along with a removed build script directory
+                if !config.dry_run() && !artifact.path.exists() {
+                    continue;
+                }
+                if config.remove(&artifact.path) {
+                    stats.bytes_freed += artifact.size;
+                    stats.artifacts_removed += 1;
+                    removed_any = true;
+                }
+            }
+        }
+
+        let incremental_dir = profile_dir.join("incremental");
+        if incremental_dir.is_dir() {
+            let entries = fs::read_dir(&incremental_dir).map_err(|source| HoldError::IoError {
+                path: incremental_dir.clone(),
+                source,
+            })?;
+            for entry in entries {
+                let entry = entry.map_err(|source| HoldError::IoError {
+                    path: incremental_dir.clone(),
+                    source,
+                })?;
+                let path = entry.path();
+                // Incremental sessions are named `<crate>-<base32 hash>`
+                let file_name = entry.file_name();
+                let Some((crate_name, _hash)) =
+                    file_name.to_str().and_then(|name| name.rsplit_once('-'))
+                else {
+                    continue;
+                };
+                if !path.is_dir() || normalize_crate_name(crate_name) != wanted {
+                    continue;
+                }
+
+                log.verbose(1, format!("  Removing {}", path.display()));
+                let size = calculate_directory_size(&path, size_mode)?;
+                if config.remove(&path) {
+                    stats.bytes_freed += size;
+                    stats.artifacts_removed += 1;
+                    removed_any = true;
+                }
+            }
+        }
+
+        if removed_any {
+            stats.crates_cleaned += 1;
+        }
+    }
+
+    stats.errors = config.take_removal_errors();
+    stats.refused_removals = config.take_refused_removals();
+    Ok(stats)
+}
+
+fn normalize_crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Check an artifact group name (e.g. `foo-bar` or `libfoo_bar`) against a
+/// normalized crate name.
+fn crate_name_matches(artifact_name: &str, wanted: &str) -> bool {
+    let name = normalize_crate_name(artifact_name);
+    name == wanted || name.strip_prefix("lib") == Some(wanted)
+}
+
+/// Clean miscellaneous directories (doc, package, tmp), returning the space
+/// and files freed
+pub(crate) fn clean_misc_directories(
+    target_dir: &Path,
+    config: &Gc,
+    verbose: u8,
+) -> Result<DiskSizes> {
+    let mut freed = DiskSizes::default();
+    let log = config.logger(verbose);
 
     for dir_name in &["doc", "package", "tmp"] {
+        if *dir_name == "doc" && config.keep_docs() {
+            log.verbose(1, "Keeping generated documentation");
+            continue;
+        }
         let dir = target_dir.join(dir_name);
-        if dir.exists() {
+        if dir.exists() && config.in_scope(&dir) && !config.out_of_time() {
             log.verbose(1, format!("Removing directory: {}", dir.display()));
 
-            let size = calculate_directory_size(&dir)?;
-            if !config.dry_run() {
-                fs::remove_dir_all(&dir)
-                    .map_err(|source| HoldError::IoError { path: dir, source })?;
+            let usage = calculate_disk_usage(&dir)?;
+            if config.remove(&dir) {
+                config.record_removal(
+                    &dir,
+                    usage.get(config.size_mode()),
+                    GcPhase::TargetDir,
+                    RemovalReason::Disposable,
+                );
+                freed += usage;
             }
-            bytes_freed += size;
         }
     }
 
-    Ok(bytes_freed)
+    Ok(freed)
 }
 
-/// Calculate the total size of a directory
-pub(crate) fn calculate_directory_size(path: &Path) -> Result<u64> {
-    if !path.exists() {
-        return Ok(0);
-    }
+/// Calculate the total size of a directory in the given mode
+pub fn calculate_directory_size(path: &Path, mode: SizeMode) -> Result<u64> {
+    Ok(calculate_disk_usage(path)?.get(mode))
+}
 
-    let mut total_size = 0;
+/// Calculate both the apparent and allocated size of a directory, and count
+/// its files and directories
+///
+/// Hard-linked files are only counted once, and symbolic links are counted
+/// as themselves, like [`SymlinkPolicy::Skip`] does.
+pub fn calculate_disk_usage(path: &Path) -> Result<DiskSizes> {
+    calculate_disk_usage_with(path, &mut DiskUsage::default())
+}
+
+/// Calculate the size of `path`, following symbolic links as `symlinks` says
+pub(crate) fn calculate_disk_usage_following(
+    path: &Path,
+    symlinks: SymlinkPolicy,
+) -> Result<DiskSizes> {
+    calculate_disk_usage_with(path, &mut DiskUsage::new(symlinks))
+}
+
+/// Calculate the size of the subtrees of `path` that are in the `--only`
+/// scope
+pub(crate) fn calculate_scoped_disk_usage(
+    path: &Path,
+    filter: &PathFilter,
+    symlinks: SymlinkPolicy,
+) -> Result<DiskSizes> {
+    calculate_scoped_disk_usage_with(path, filter, &mut DiskUsage::new(symlinks))
+}
 
-    if path.is_file() {
-        let metadata = fs::metadata(path).map_err(|source| HoldError::IoError {
+fn calculate_scoped_disk_usage_with(
+    path: &Path,
+    filter: &PathFilter,
+    usage: &mut DiskUsage,
+) -> Result<DiskSizes> {
+    if filter.matches(path) {
+        return calculate_disk_usage_with(path, usage);
+    }
+
+    let mut total_size = DiskSizes::default();
+    if usage.symlinks().is_dir(path) {
+        let entries = fs::read_dir(path).map_err(|source| HoldError::IoError {
             path: path.to_path_buf(),
             source,
         })?;
-        return Ok(metadata.len());
+        for entry in entries {
+            let entry = entry.map_err(|source| HoldError::IoError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            total_size += calculate_scoped_disk_usage_with(&entry.path(), filter, usage)?;
+        }
     }
 
-    let entries = fs::read_dir(path).map_err(|source| HoldError::IoError {
-        path: path.to_path_buf(),
+    Ok(total_size)
+}
+
+/// Every `SIZE_SAMPLE_STRIDE`th file is measured to estimate a directory's
+/// size
+const SIZE_SAMPLE_STRIDE: usize = 16;
+
+/// Fewest sampled files an estimate is based on; smaller trees are always
+/// measured exactly
+const MIN_SIZE_SAMPLE: usize = 256;
+
+/// Size of a directory measured against a size budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct BudgetedUsage {
+    /// Measured or estimated sizes
+    pub(crate) sizes: DiskSizes,
+    /// Whether `sizes` was extrapolated from a sample
+    pub(crate) estimated: bool,
+}
+
+/// Calculate the size of `path` (limited to the `--only` scope, if any),
+/// stopping early once a sample shows it is well within `budget`.
+///
+/// Files are listed without being stat'ed, then every
+/// `SIZE_SAMPLE_STRIDE`th file is measured. If the extrapolated size in
+/// `mode` is at most half of `budget`, that estimate is returned; otherwise
+/// the remaining files are measured and the exact size is returned.
+pub(crate) fn calculate_disk_usage_within(
+    path: &Path,
+    filter: Option<&PathFilter>,
+    mode: SizeMode,
+    symlinks: SymlinkPolicy,
+    budget: u64,
+) -> Result<BudgetedUsage> {
+    if !symlinks.is_dir(path) {
+        let sizes = match filter {
+            Some(filter) => calculate_scoped_disk_usage(path, filter, symlinks)?,
+            None => calculate_disk_usage_following(path, symlinks)?,
+        };
+        return Ok(BudgetedUsage {
+            sizes,
+            estimated: false,
+        });
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = 0;
+    collect_files(
+        path,
+        filter.filter(|filter| !filter.matches(path)),
+        symlinks,
+        &mut files,
+        &mut dirs,
+    )?;
+
+    let mut usage = DiskUsage::new(symlinks);
+    let mut sizes = DiskSizes {
+        files: dirs,
+        ..DiskSizes::default()
+    };
+    let sampled = files.len().div_ceil(SIZE_SAMPLE_STRIDE);
+    for file in files.iter().step_by(SIZE_SAMPLE_STRIDE) {
+        sizes += measure_file(file, &mut usage)?;
+    }
+
+    if sampled >= MIN_SIZE_SAMPLE {
+        let scale = |size: u64| (u128::from(size) * files.len() as u128 / sampled as u128) as u64;
+        // The listing already gives the file count, short of hard links
+        let estimate = DiskSizes {
+            apparent: scale(sizes.apparent),
+            allocated: scale(sizes.allocated),
+            files: dirs + files.len() as u64,
+        };
+        if estimate.get(mode) <= budget / 2 {
+            return Ok(BudgetedUsage {
+                sizes: estimate,
+                estimated: true,
+            });
+        }
+    }
+
+    for (index, file) in files.iter().enumerate() {
+        if index % SIZE_SAMPLE_STRIDE != 0 {
+            sizes += measure_file(file, &mut usage)?;
+        }
+    }
+
+    Ok(BudgetedUsage {
+        sizes,
+        estimated: false,
+    })
+}
+
+/// List the files below `dir` and count the directories (including `dir`)
+/// using only directory entries, so nothing is stat'ed. With a `filter`, only
+/// files and directories in scope are included; `None` means everything
+/// below `dir` is in scope.
+fn collect_files(
+    dir: &Path,
+    filter: Option<&PathFilter>,
+    symlinks: SymlinkPolicy,
+    files: &mut Vec<PathBuf>,
+    dirs: &mut u64,
+) -> Result<()> {
+    if filter.is_none() {
+        *dirs += 1;
+    }
+
+    let entries = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
         source,
     })?;
 
     for entry in entries {
         let entry = entry.map_err(|source| HoldError::IoError {
-            path: path.to_path_buf(),
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|source| HoldError::IoError {
+            path: path.clone(),
             source,
         })?;
-        let entry_path = entry.path();
+        let filter = filter.filter(|filter| !filter.matches(&path));
+
+        // Symlinks are followed as the policy says, like the exact size
+        // calculation does
+        let follow = file_type.is_symlink() && symlinks == SymlinkPolicy::Follow;
+        if file_type.is_dir() || (follow && path.is_dir()) {
+            collect_files(&path, filter, symlinks, files, dirs)?;
+        } else if filter.is_none() {
+            files.push(path);
+        }
+    }
 
-        if entry_path.is_dir() {
-            total_size += calculate_directory_size(&entry_path)?;
-        } else if entry_path.is_file() {
-            let metadata = fs::metadata(&entry_path).map_err(|source| HoldError::IoError {
-                path: entry_path.clone(),
+    Ok(())
+}
+
+/// Sizes of a listed file, or zero if it vanished (or is a followed dangling
+/// symlink)
+fn measure_file(path: &Path, usage: &mut DiskUsage) -> Result<DiskSizes> {
+    match usage.symlinks().metadata(path) {
+        Ok(metadata) if metadata.is_file() || metadata.is_symlink() => Ok(usage.measure(&metadata)),
+        Ok(_) => Ok(DiskSizes::default()),
+        Err(source) if source.kind() == std::io::ErrorKind::NotFound => Ok(DiskSizes::default()),
+        Err(source) => Err(HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Calculate the size of `path`, sharing hard-link tracking with `usage`
+pub(crate) fn calculate_disk_usage_with(path: &Path, usage: &mut DiskUsage) -> Result<DiskSizes> {
+    let metadata = match usage.symlinks().metadata(path) {
+        Ok(metadata) => metadata,
+        Err(source) if source.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(DiskSizes::default());
+        }
+        Err(source) => {
+            return Err(HoldError::IoError {
+                path: path.to_path_buf(),
                 source,
-            })?;
-            total_size += metadata.len();
+            });
         }
+    };
+    if !metadata.is_dir() {
+        return Ok(if metadata.is_file() || metadata.is_symlink() {
+            usage.measure(&metadata)
+        } else {
+            DiskSizes::default()
+        });
+    }
+
+    // Count the directory itself
+    let mut total_size = DiskSizes {
+        files: 1,
+        ..DiskSizes::default()
+    };
+
+    let entries = fs::read_dir(path).map_err(|source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        total_size += calculate_disk_usage_with(&entry.path(), usage)?;
     }
 
     Ok(total_size)
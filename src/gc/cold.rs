@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use super::artifacts::CrateArtifact;
+use super::size::{DiskSizes, SizeMode, hard_link_id};
+use crate::error::{HoldError, Result};
+
+/// Manifest of compressed artifacts, kept in the target directory
+pub(crate) const COLD_MANIFEST_FILENAME: &str = "cargo-hold.cold.json";
+
+/// Extension appended to the name of a compressed artifact
+const COMPRESSED_EXTENSION: &str = "zst";
+
+/// Suffix of the temporary file an artifact is decompressed into before it
+/// replaces the compressed one
+const THAW_SUFFIX: &str = "cargo-hold-thaw";
+
+/// Artifacts compressed by `--gc-compress-cold`, so `thaw` can restore them
+/// with their original modification times.
+///
+/// A compressed artifact `<path>` is stored next to where it was as
+/// `<path>.zst`. Cargo sees the original as missing and rebuilds the crate
+/// unless it is thawed first.
+#[derive(Debug, Default)]
+pub(crate) struct ColdManifest {
+    /// Modification times in nanoseconds since UNIX_EPOCH, keyed by original
+    /// path relative to the target directory
+    files: BTreeMap<PathBuf, u64>,
+    /// Whether the manifest changed since it was loaded
+    changed: bool,
+}
+
+/// Statistics about thawing a target directory
+#[derive(Debug, Default)]
+pub(crate) struct ThawStats {
+    /// Artifacts decompressed to their original paths
+    pub(crate) files_thawed: usize,
+    /// Compressed artifacts discarded because cargo rebuilt the original
+    pub(crate) files_discarded: usize,
+    /// Apparent size of the thawed artifacts
+    pub(crate) bytes_restored: u64,
+}
+
+impl ColdManifest {
+    /// Load the manifest of `target_dir`, or an empty one if there is none
+    pub(crate) fn load(target_dir: &Path) -> Result<Self> {
+        let path = target_dir.join(COLD_MANIFEST_FILENAME);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(source) if source.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(source) => return Err(HoldError::IoError { path, source }),
+        };
+
+        let invalid = |message: &str| HoldError::ColdManifestError {
+            path: path.clone(),
+            message: message.to_string(),
+        };
+        let value: Value =
+            serde_json::from_slice(&contents).map_err(|err| invalid(&err.to_string()))?;
+        let entries = value
+            .get("files")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("missing \"files\" array"))?;
+
+        let mut files = BTreeMap::new();
+        for entry in entries {
+            let path = entry
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("entry without a \"path\""))?;
+            let mtime_nanos = entry
+                .get("mtime_nanos")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| invalid("entry without an \"mtime_nanos\""))?;
+            files.insert(PathBuf::from(path), mtime_nanos);
+        }
+
+        Ok(Self {
+            files,
+            changed: false,
+        })
+    }
+
+    /// Whether no artifacts are compressed
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Write the manifest back if it changed, removing it once empty
+    pub(crate) fn save(&mut self, target_dir: &Path) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let path = target_dir.join(COLD_MANIFEST_FILENAME);
+        let io_error = |source| HoldError::IoError {
+            path: path.clone(),
+            source,
+        };
+        if self.files.is_empty() {
+            match fs::remove_file(&path) {
+                Err(source) if source.kind() != io::ErrorKind::NotFound => {
+                    return Err(io_error(source));
+                }
+                _ => {}
+            }
+        } else {
+            let entries: Vec<_> = self
+                .files
+                .iter()
+                .map(|(path, mtime_nanos)| {
+                    serde_json::json!({
+                        "path": path.to_string_lossy(),
+                        "mtime_nanos": mtime_nanos,
+                    })
+                })
+                .collect();
+            let mut out = BufWriter::new(File::create(&path).map_err(io_error)?);
+            serde_json::to_writer_pretty(&mut out, &serde_json::json!({ "files": entries }))
+                .map_err(io::Error::from)
+                .and_then(|()| writeln!(out))
+                .and_then(|()| out.flush())
+                .map_err(io_error)?;
+        }
+
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Whether any file of the group is compressed
+    pub(crate) fn is_cold(&self, target_dir: &Path, crate_artifact: &CrateArtifact) -> bool {
+        crate_artifact.artifacts.iter().any(|artifact| {
+            original_path(&artifact.path)
+                .and_then(|original| relative_key(target_dir, &original))
+                .is_some_and(|key| self.files.contains_key(&key))
+        })
+    }
+
+    /// Compress every file of the group in place, returning the space saved
+    /// in `size_mode`.
+    ///
+    /// Files with other hard links are left alone, since compressing them
+    /// would not free anything.
+    pub(crate) fn compress(
+        &mut self,
+        target_dir: &Path,
+        crate_artifact: &CrateArtifact,
+        size_mode: SizeMode,
+    ) -> Result<u64> {
+        let mut saved = 0u64;
+        for artifact in &crate_artifact.artifacts {
+            let Some(key) = relative_key(target_dir, &artifact.path) else {
+                continue;
+            };
+            let metadata = match fs::metadata(&artifact.path) {
+                Ok(metadata) => metadata,
+                Err(source) if source.kind() == io::ErrorKind::NotFound => continue,
+                Err(source) => {
+                    return Err(HoldError::IoError {
+                        path: artifact.path.clone(),
+                        source,
+                    });
+                }
+            };
+            if !metadata.is_file() || hard_link_id(&metadata).is_some() {
+                continue;
+            }
+
+            let compressed = compress_file(&artifact.path, &metadata)?;
+            saved += artifact
+                .size
+                .saturating_sub(DiskSizes::of_file(&compressed).get(size_mode));
+            self.files.insert(key, mtime_nanos(&metadata));
+            self.changed = true;
+        }
+
+        Ok(saved)
+    }
+
+    /// Drop the entries of a group that was removed
+    pub(crate) fn forget(&mut self, target_dir: &Path, crate_artifact: &CrateArtifact) {
+        for artifact in &crate_artifact.artifacts {
+            if let Some(key) = original_path(&artifact.path)
+                .and_then(|original| relative_key(target_dir, &original))
+                && self.files.remove(&key).is_some()
+            {
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Decompress every artifact in the manifest back to its original path
+    /// and modification time.
+    ///
+    /// Compressed artifacts whose original was rebuilt in the meantime are
+    /// discarded, as are entries whose compressed file is gone.
+    pub(crate) fn thaw(&mut self, target_dir: &Path) -> Result<ThawStats> {
+        let mut stats = ThawStats::default();
+        for (key, mtime_nanos) in std::mem::take(&mut self.files) {
+            self.changed = true;
+            let original = target_dir.join(&key);
+            let compressed = with_suffix(&original, COMPRESSED_EXTENSION);
+            if !compressed.exists() {
+                continue;
+            }
+
+            if original.exists() {
+                fs::remove_file(&compressed).map_err(|source| HoldError::IoError {
+                    path: compressed,
+                    source,
+                })?;
+                stats.files_discarded += 1;
+                continue;
+            }
+
+            stats.bytes_restored += thaw_file(&compressed, &original, mtime_nanos)?;
+            stats.files_thawed += 1;
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Compress `path` to `<path>.zst` with the same permissions and mtime, then
+/// remove `path`; returns the metadata of the compressed file
+fn compress_file(path: &Path, metadata: &fs::Metadata) -> Result<fs::Metadata> {
+    let compressed = with_suffix(path, COMPRESSED_EXTENSION);
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| HoldError::IoError { path, source }
+    };
+
+    let mut source = File::open(path).map_err(io_error(path))?;
+    let mut out = File::create(&compressed).map_err(io_error(&compressed))?;
+    let written = zstd::stream::copy_encode(&mut source, &mut out, 0)
+        .and_then(|()| out.set_permissions(metadata.permissions()))
+        .and_then(|()| out.set_modified(metadata.modified()?))
+        .and_then(|()| out.metadata());
+    let compressed_metadata = match written {
+        Ok(compressed_metadata) => compressed_metadata,
+        Err(source) => {
+            let _ = fs::remove_file(&compressed);
+            return Err(HoldError::IoError {
+                path: compressed,
+                source,
+            });
+        }
+    };
+
+    fs::remove_file(path).map_err(io_error(path))?;
+    Ok(compressed_metadata)
+}
+
+/// Decompress `compressed` to `original` with the recorded mtime, then
+/// remove `compressed`; returns the decompressed size
+fn thaw_file(compressed: &Path, original: &Path, mtime_nanos: u64) -> Result<u64> {
+    // Decompress next to the original and rename it into place, so an
+    // interrupted thaw never leaves a truncated artifact with a fresh mtime
+    let temp = with_suffix(original, THAW_SUFFIX);
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| HoldError::IoError { path, source }
+    };
+
+    let mut source = File::open(compressed).map_err(io_error(compressed))?;
+    let permissions = source
+        .metadata()
+        .map_err(io_error(compressed))?
+        .permissions();
+    let mut out = File::create(&temp).map_err(io_error(&temp))?;
+    let written = zstd::stream::copy_decode(&mut source, &mut out)
+        .and_then(|()| out.set_permissions(permissions))
+        .and_then(|()| out.set_modified(UNIX_EPOCH + Duration::from_nanos(mtime_nanos)))
+        .and_then(|()| out.metadata());
+    let size = match written {
+        Ok(metadata) => metadata.len(),
+        Err(source) => {
+            let _ = fs::remove_file(&temp);
+            return Err(HoldError::IoError {
+                path: compressed.to_path_buf(),
+                source,
+            });
+        }
+    };
+
+    fs::rename(&temp, original).map_err(io_error(original))?;
+    fs::remove_file(compressed).map_err(io_error(compressed))?;
+    Ok(size)
+}
+
+/// `path` with `.suffix` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// The path a compressed artifact was compressed from
+fn original_path(path: &Path) -> Option<PathBuf> {
+    (path.extension()? == COMPRESSED_EXTENSION).then(|| path.with_extension(""))
+}
+
+/// Manifest key of a path inside the target directory
+fn relative_key(target_dir: &Path, path: &Path) -> Option<PathBuf> {
+    path.strip_prefix(target_dir)
+        .ok()
+        .filter(|relative| relative.to_str().is_some())
+        .map(Path::to_path_buf)
+}
+
+fn mtime_nanos(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::gc::artifacts::ArtifactInfo;
+
+    fn group(paths: &[PathBuf]) -> CrateArtifact {
+        CrateArtifact {
+            name: "libfoo".to_string(),
+            hash: "0123456789abcdef".to_string(),
+            artifacts: paths
+                .iter()
+                .map(|path| ArtifactInfo {
+                    path: path.clone(),
+                    size: fs::metadata(path)
+                        .map(|metadata| metadata.len())
+                        .unwrap_or(0),
+                    modified: SystemTime::UNIX_EPOCH,
+                })
+                .collect(),
+            total_size: 0,
+            newest_mtime: SystemTime::UNIX_EPOCH,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
+        }
+    }
+
+    #[test]
+    fn test_compressed_group_is_cold_after_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let rlib = temp_dir
+            .path()
+            .join("debug/deps/libfoo-0123456789abcdef.rlib");
+        fs::create_dir_all(rlib.parent().unwrap()).unwrap();
+        fs::write(&rlib, [0u8; 4096]).unwrap();
+
+        let mut manifest = ColdManifest::default();
+        let saved = manifest
+            .compress(
+                temp_dir.path(),
+                &group(std::slice::from_ref(&rlib)),
+                SizeMode::Apparent,
+            )
+            .unwrap();
+        manifest.save(temp_dir.path()).unwrap();
+        assert!(saved > 0);
+
+        let compressed = with_suffix(&rlib, COMPRESSED_EXTENSION);
+        let manifest = ColdManifest::load(temp_dir.path()).unwrap();
+        assert!(manifest.is_cold(temp_dir.path(), &group(&[compressed])));
+        assert!(!manifest.is_cold(temp_dir.path(), &group(&[rlib])));
+    }
+
+    #[test]
+    fn test_thaw_discards_compressed_copies_of_rebuilt_artifacts() {
+        let temp_dir = TempDir::new().unwrap();
+        let rlib = temp_dir.path().join("libfoo-0123456789abcdef.rlib");
+        fs::write(&rlib, b"old").unwrap();
+
+        let mut manifest = ColdManifest::default();
+        manifest
+            .compress(
+                temp_dir.path(),
+                &group(std::slice::from_ref(&rlib)),
+                SizeMode::Apparent,
+            )
+            .unwrap();
+        fs::write(&rlib, b"rebuilt").unwrap();
+
+        let stats = manifest.thaw(temp_dir.path()).unwrap();
+        assert_eq!(stats.files_thawed, 0);
+        assert_eq!(stats.files_discarded, 1);
+        assert_eq!(fs::read(&rlib).unwrap(), b"rebuilt");
+        assert!(!with_suffix(&rlib, COMPRESSED_EXTENSION).exists());
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(COLD_MANIFEST_FILENAME),
+            r#"{"files": [{"path": "debug/deps/libfoo.rlib"}]}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ColdManifest::load(temp_dir.path()),
+            Err(HoldError::ColdManifestError { .. })
+        ));
+    }
+}
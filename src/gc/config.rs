@@ -1,13 +1,39 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
-use super::cargo;
+use super::age::{DEFAULT_AGE_THRESHOLD, DEFAULT_CARGO_HOME_AGE_THRESHOLD, GcArea, KEEP};
+use super::artifacts::{DEFAULT_PRESERVE_BUFFER, PreservePolicy};
+use super::busy::{BusyPolicy, DEFAULT_BUSY_TIMEOUT, wait_until_idle};
 use super::cleanup::{
-    calculate_directory_size, clean_misc_directories, clean_profile_directory,
-    find_profile_directories,
+    BudgetedUsage, ProfileRoot, calculate_disk_usage_following, calculate_disk_usage_within,
+    calculate_scoped_disk_usage, clean_misc_directories, clean_profile_directory,
 };
-use super::size::format_size;
+use super::cold::ColdManifest;
+use super::containment::RemovalRoots;
+use super::costs::BuildCosts;
+use super::duration::format_duration;
+use super::filter::PathFilter;
+use super::global_cache::{GLOBAL_CACHE_DB, GlobalCacheMode};
+use super::outputs::clean_tool_outputs;
+use super::pins::PinnedCrates;
+use super::policy::PolicyCommand;
+use super::profiles::find_named_profile_directories;
+use super::removals::{GcPhase, RemovalReason, RemovedPath};
+use super::runner::{self, Runner};
+use super::sandbox::{SandboxMode, SandboxPaths, run_confined};
+use super::size::{DiskSizes, SizeMode, SymlinkPolicy};
+use super::size_index::SizeIndex;
+use super::units::BuildUnits;
+use super::{cargo, preflight};
+use crate::context::HoldContext;
 use crate::error::{HoldError, Result};
+use crate::lock::{LockMode, TargetDirLock, create_package_cache_lock_files};
 use crate::logging::Logger;
+use crate::state::DirectorySize;
 
 /// Garbage collection
 #[derive(Debug)]
@@ -16,18 +42,109 @@ pub struct Gc {
     target_dir: PathBuf,
     /// Maximum target directory size in bytes (if None, use age-based cleanup)
     max_target_size: Option<u64>,
+    /// Maximum number of files and directories in the target directory
+    max_target_files: Option<u64>,
+    /// Cargo's build directory, when it is apart from the target directory
+    build_dir: Option<PathBuf>,
+    /// Maximum build directory size in bytes
+    max_build_dir_size: Option<u64>,
+    /// Maximum size of the coverage data in the target directory in bytes
+    max_coverage_size: Option<u64>,
     /// Dry run mode - don't actually delete anything
     dry_run: bool,
     /// Enable debug output
     debug: bool,
     /// Age threshold for cleanup (default: 7 days)
-    age_threshold_days: u32,
+    age_threshold: Duration,
+    /// Age thresholds overriding the defaults for individual areas
+    area_age_thresholds: HashMap<GcArea, Duration>,
     /// Additional binaries to preserve in ~/.cargo/bin (on top of defaults)
     preserve_binaries: Vec<String>,
-    /// Timestamp of the previous build to preserve artifacts from
-    previous_build_mtime_nanos: Option<u128>,
+    /// Timestamps of recent builds to preserve artifacts from, oldest first
+    recent_build_mtime_nanos: Vec<u128>,
+    /// Last-build timestamps per profile directory (relative to the target
+    /// directory), oldest first; used instead of `recent_build_mtime_nanos`
+    /// for profiles that have an entry
+    profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+    /// Number of most recent builds whose artifacts are preserved
+    preserve_last_builds: usize,
+    /// Slack before the previous build timestamp within which artifacts are
+    /// still preserved
+    preserve_buffer: Duration,
+    /// Maximum age of the previous build timestamp for it to be honored
+    /// (defaults to the age threshold)
+    preserve_window: Option<Duration>,
+    /// Units used by the last build, which are never evicted
+    build_units: Option<BuildUnits>,
+    /// Crates pinned by the user, which are never evicted
+    pinned_crates: Option<PinnedCrates>,
+    /// Recorded build durations weighting eviction under size pressure
+    build_costs: Option<BuildCosts>,
+    /// How file sizes are measured for the size cap and reporting
+    size_mode: SizeMode,
+    /// Restricts GC to matching subtrees of the target directory
+    path_filter: Option<PathFilter>,
+    /// External command deciding which artifacts to keep or evict
+    policy_command: Option<PolicyCommand>,
+    /// Lockfiles whose git dependency revisions are never removed
+    lockfiles: Vec<PathBuf>,
+    /// Cargo home to clean (if None, `$CARGO_HOME` or `~/.cargo`)
+    cargo_home: Option<PathBuf>,
+    /// Whether cargo's global cache tracking database is used for cargo home
+    /// cleanup
+    global_cache_mode: GlobalCacheMode,
+    /// Whether the collection is confined to the directories it cleans
+    sandbox: SandboxMode,
+    /// Whether measuring sizes descends into symbolic links to directories
+    symlinks: SymlinkPolicy,
+    /// What to do when a build is using the target directory
+    when_busy: BusyPolicy,
+    /// How long to wait for a busy target directory with
+    /// [`BusyPolicy::Wait`]
+    busy_timeout: Duration,
+    /// How long removing may take before the rest is left for the next run
+    max_duration: Option<Duration>,
+    /// When the time budget runs out, once the collection started
+    deadline: Mutex<Option<Instant>>,
+    /// Whether work was skipped because the time budget ran out
+    stopped_early: AtomicBool,
+    /// Paths removed so far, when removals are recorded
+    removals: Option<Mutex<Vec<RemovedPath>>>,
+    /// Paths that could not be removed so far
+    removal_errors: AtomicUsize,
+    /// The directories removals are confined to, resolved on the first
+    /// removal
+    removal_roots: OnceLock<RemovalRoots>,
+    /// Paths refused so far for lying outside of the removal roots
+    refused_removals: AtomicUsize,
+    /// Directory sizes from previous scans, when an index is kept
+    size_index: Option<Mutex<SizeIndex>>,
+    /// Compress artifact groups selected for eviction instead of removing
+    /// them, unless they are already compressed
+    compress_cold: bool,
+    /// Evict crates whose workspace sources are newer than their build, and
+    /// keep those whose sources are not from age-based removal
+    dep_info: bool,
+    /// Names of the profile directories declared by the workspace, found at
+    /// any depth of the target and build directories
+    profile_names: Vec<String>,
+    /// Artifacts compressed in the target directory, loaded during GC
+    cold_manifest: Mutex<ColdManifest>,
+    /// Leave the registry, git checkouts, and binaries of the cargo home
+    /// alone
+    keep_cargo_home: bool,
+    /// Keep incremental compilation data regardless of its age
+    keep_incremental: bool,
+    /// Keep the generated documentation
+    keep_docs: bool,
+    /// Ephemeral environment GC runs in, which skips cleanup that is wasted
+    /// there
+    runner: Option<Runner>,
     /// Suppress informational logging when true
     quiet: bool,
+    /// Context of the command collecting, whose size units and warning count
+    /// the output uses
+    context: HoldContext,
 }
 
 impl Gc {
@@ -46,19 +163,87 @@ impl Gc {
         self.max_target_size
     }
 
+    /// Get the maximum number of files and directories in the target
+    /// directory
+    pub fn max_target_files(&self) -> Option<u64> {
+        self.max_target_files
+    }
+
+    /// Get cargo's build directory, if it is apart from the target directory
+    pub fn build_dir(&self) -> Option<&Path> {
+        self.build_dir.as_deref()
+    }
+
+    /// Get the maximum build directory size
+    pub fn max_build_dir_size(&self) -> Option<u64> {
+        self.max_build_dir_size
+    }
+
+    /// Get the maximum size of the coverage data
+    pub fn max_coverage_size(&self) -> Option<u64> {
+        self.max_coverage_size
+    }
+
     /// Check if dry run mode is enabled
     pub fn dry_run(&self) -> bool {
         self.dry_run
     }
 
+    /// Check if cold artifact groups are compressed before they are evicted
+    pub fn compress_cold(&self) -> bool {
+        self.compress_cold
+    }
+
+    /// Check if dep-info decides which crates are stale or fresh
+    pub fn dep_info(&self) -> bool {
+        self.dep_info
+    }
+
+    /// Get the names of the profile directories declared by the workspace
+    pub fn profile_names(&self) -> &[String] {
+        &self.profile_names
+    }
+
+    /// Find the profile directories of `dir`, including those named after a
+    /// declared profile wherever they nest
+    fn profile_directories(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        find_named_profile_directories(dir, &self.profile_names)
+    }
+
     /// Check if debug mode is enabled
     pub fn debug(&self) -> bool {
         self.debug
     }
 
-    /// Get the age threshold in days
+    /// Get the age threshold in whole days
     pub fn age_threshold_days(&self) -> u32 {
-        self.age_threshold_days
+        (self.age_threshold.as_secs() / (24 * 60 * 60)) as u32
+    }
+
+    /// Get the general age threshold
+    pub fn age_threshold(&self) -> Duration {
+        self.age_threshold
+    }
+
+    /// Get the age threshold for an area, honoring per-area overrides
+    pub fn age_threshold_for(&self, area: GcArea) -> Duration {
+        if let Some(threshold) = self.area_age_thresholds.get(&area) {
+            return *threshold;
+        }
+
+        match area {
+            GcArea::Deps
+            | GcArea::RegistryCache
+            | GcArea::Coverage
+            | GcArea::Nextest
+            | GcArea::Trybuild
+            | GcArea::Snapshots
+            | GcArea::Dx
+            | GcArea::WasmBindgen
+            | GcArea::Trunk => self.age_threshold,
+            GcArea::Incremental => Duration::ZERO,
+            GcArea::RegistrySrc | GcArea::Git | GcArea::Bin => DEFAULT_CARGO_HOME_AGE_THRESHOLD,
+        }
     }
 
     /// Get the list of binaries to preserve
@@ -68,7 +253,304 @@ impl Gc {
 
     /// Get the previous build mtime in nanoseconds
     pub fn previous_build_mtime_nanos(&self) -> Option<u128> {
-        self.previous_build_mtime_nanos
+        self.recent_build_mtime_nanos.last().copied()
+    }
+
+    /// Get the recent build mtimes in nanoseconds, oldest first
+    pub fn recent_build_mtime_nanos(&self) -> &[u128] {
+        &self.recent_build_mtime_nanos
+    }
+
+    /// Get the last-build timestamps recorded per profile directory
+    pub fn profile_build_mtime_nanos(&self) -> &HashMap<String, Vec<u128>> {
+        &self.profile_build_mtime_nanos
+    }
+
+    /// Get the number of most recent builds whose artifacts are preserved
+    pub fn preserve_last_builds(&self) -> usize {
+        self.preserve_last_builds
+    }
+
+    /// Get the previous build preservation buffer
+    pub fn preserve_buffer(&self) -> Duration {
+        self.preserve_buffer
+    }
+
+    /// Get the previous build preservation window, if overridden
+    pub fn preserve_window(&self) -> Option<Duration> {
+        self.preserve_window
+    }
+
+    /// Get how file sizes are measured
+    pub fn size_mode(&self) -> SizeMode {
+        self.size_mode
+    }
+
+    /// Get the path filter restricting GC to parts of the target directory
+    pub fn path_filter(&self) -> Option<&PathFilter> {
+        self.path_filter.as_ref()
+    }
+
+    /// Get the external command deciding which artifacts to keep or evict
+    pub fn policy_command(&self) -> Option<&PolicyCommand> {
+        self.policy_command.as_ref()
+    }
+
+    /// Get the lockfiles whose git dependency revisions are kept
+    pub fn lockfiles(&self) -> &[PathBuf] {
+        &self.lockfiles
+    }
+
+    /// Get whether cargo's global cache tracking database is used
+    pub fn global_cache_mode(&self) -> GlobalCacheMode {
+        self.global_cache_mode
+    }
+
+    /// Get whether the collection is confined with Landlock
+    pub fn sandbox(&self) -> SandboxMode {
+        self.sandbox
+    }
+
+    /// Get whether measuring sizes follows symbolic links
+    pub fn symlinks(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+
+    /// Record a removed (or, in a dry run, removable) path, if removals are
+    /// recorded
+    pub(crate) fn record_removal(
+        &self,
+        path: &Path,
+        size: u64,
+        phase: GcPhase,
+        reason: RemovalReason,
+    ) {
+        if let Some(removals) = &self.removals {
+            removals
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(RemovedPath {
+                    path: path.to_path_buf(),
+                    size,
+                    phase,
+                    reason,
+                });
+        }
+    }
+
+    /// Remove a file or directory unless in a dry run, returning whether it
+    /// is gone (or would be).
+    ///
+    /// A failure is reported as a warning and counted in [`GcStats::errors`]
+    /// rather than failing the collection, so callers only count what was
+    /// removed as freed.
+    pub(crate) fn remove(&self, path: &Path) -> bool {
+        if !self.may_remove(path) {
+            return false;
+        }
+        if self.dry_run() {
+            return true;
+        }
+        // A symbolic link is removed itself, never what it points to
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        let removed = if is_dir {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match removed {
+            Ok(()) => true,
+            // Removed already, e.g. along with a directory above it
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => {
+                self.removal_errors.fetch_add(1, Ordering::Relaxed);
+                self.logger(0)
+                    .warn(format!("Could not remove {}: {e}", path.display()));
+                false
+            }
+        }
+    }
+
+    /// Check that `path` lies within the target directory, the build
+    /// directory, or the cleaned parts of the cargo home, even with symbolic
+    /// links resolved. A path outside of them is reported as a warning and
+    /// counted in [`GcStats::refused_removals`].
+    pub(crate) fn may_remove(&self, path: &Path) -> bool {
+        let roots = self.removal_roots.get_or_init(|| {
+            let mut dirs = vec![self.target_dir().to_path_buf()];
+            dirs.extend(self.build_dir().map(Path::to_path_buf));
+            if let Ok(cargo_home) = self.cargo_home() {
+                dirs.extend(["registry", "git", "bin"].map(|dir| cargo_home.join(dir)));
+            }
+            RemovalRoots::new(dirs)
+        });
+        match roots.check(path) {
+            Ok(()) => true,
+            Err(e) => {
+                self.refused_removals.fetch_add(1, Ordering::Relaxed);
+                self.logger(0).warn(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Take the number of paths that could not be removed so far
+    pub(super) fn take_removal_errors(&self) -> usize {
+        self.removal_errors.swap(0, Ordering::Relaxed)
+    }
+
+    /// Take the number of paths refused so far
+    pub(super) fn take_refused_removals(&self) -> usize {
+        self.refused_removals.swap(0, Ordering::Relaxed)
+    }
+
+    /// Take the paths recorded as removed so far, sorted; empty unless
+    /// removals are recorded
+    pub fn take_removals(&self) -> Vec<RemovedPath> {
+        let Some(removals) = &self.removals else {
+            return Vec::new();
+        };
+        let mut removed = std::mem::take(
+            &mut *removals
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        removed.sort();
+        removed
+    }
+
+    /// Run `f` on the manifest of compressed artifacts
+    pub(crate) fn with_cold_manifest<T>(&self, f: impl FnOnce(&mut ColdManifest) -> T) -> T {
+        f(&mut self
+            .cold_manifest
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// Check whether a path inside the target directory may be cleaned
+    pub(crate) fn in_scope(&self, path: &Path) -> bool {
+        self.path_filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(path))
+    }
+
+    /// Measure the target directory, or only the in-scope parts of it when a
+    /// path filter is set.
+    ///
+    /// With a directory size index, unchanged directories are not measured
+    /// again. Otherwise, or for the `initial` measurement while the index is
+    /// still empty, a size cap lets the result be estimated from a sample
+    /// when the directory is clearly within the cap.
+    fn measure_target_dir(&self, initial: bool) -> Result<BudgetedUsage> {
+        if self.path_filter.is_none()
+            && let Some(index) = &self.size_index
+        {
+            let mut index = index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Filling an empty index takes a full walk, which the final
+            // measurement does anyway
+            if !(initial && index.is_empty() && self.max_target_size().is_some()) {
+                return Ok(BudgetedUsage {
+                    sizes: index.measure(self.target_dir(), self.symlinks())?,
+                    estimated: false,
+                });
+            }
+        }
+
+        if let Some(max_size) = self.max_target_size() {
+            return calculate_disk_usage_within(
+                self.target_dir(),
+                self.path_filter(),
+                self.size_mode(),
+                self.symlinks(),
+                max_size,
+            );
+        }
+
+        let sizes = match &self.path_filter {
+            Some(filter) => {
+                calculate_scoped_disk_usage(self.target_dir(), filter, self.symlinks())?
+            }
+            None => calculate_disk_usage_following(self.target_dir(), self.symlinks())?,
+        };
+        Ok(BudgetedUsage {
+            sizes,
+            estimated: false,
+        })
+    }
+
+    /// Get the policy applied when a build is using the target directory
+    pub fn when_busy(&self) -> BusyPolicy {
+        self.when_busy
+    }
+
+    /// Get how long to wait for a busy target directory
+    pub fn busy_timeout(&self) -> Duration {
+        self.busy_timeout
+    }
+
+    /// Get how long the collection may take, if limited
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+
+    /// Start the time budget, if any
+    fn start_clock(&self) {
+        *self
+            .deadline
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+            self.max_duration.map(|budget| Instant::now() + budget);
+        self.stopped_early.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the time budget ran out, in which case the caller skips the
+    /// work it was about to do
+    pub(crate) fn out_of_time(&self) -> bool {
+        let deadline = *self
+            .deadline
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let out_of_time = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if out_of_time {
+            self.stopped_early.store(true, Ordering::Relaxed);
+        }
+        out_of_time
+    }
+
+    /// Preservation policy for a profile directory, identified by its path
+    /// relative to the target directory.
+    ///
+    /// Profiles with recorded build timestamps use their own history; others
+    /// fall back to the global GC timestamp history.
+    pub(crate) fn preserve_policy(&self, profile: &str) -> PreservePolicy {
+        let history = self
+            .profile_build_mtime_nanos
+            .get(profile)
+            .filter(|history| !history.is_empty())
+            .unwrap_or(&self.recent_build_mtime_nanos);
+        let keep_from = history.len().saturating_sub(self.preserve_last_builds);
+        PreservePolicy {
+            previous_builds_mtime_nanos: history[keep_from..].to_vec(),
+            buffer: self.preserve_buffer,
+            window: self.preserve_window,
+        }
+    }
+
+    /// Get the units used by the last build
+    pub fn build_units(&self) -> Option<&BuildUnits> {
+        self.build_units.as_ref()
+    }
+
+    /// Get the crates that are never evicted
+    pub fn pinned_crates(&self) -> Option<&PinnedCrates> {
+        self.pinned_crates.as_ref()
+    }
+
+    /// Get the recorded build durations weighting eviction
+    pub fn build_costs(&self) -> Option<&BuildCosts> {
+        self.build_costs.as_ref()
     }
 
     /// Check if quiet mode is enabled
@@ -76,15 +558,62 @@ impl Gc {
         self.quiet
     }
 
+    /// A logger for the collection printing at `verbose`
+    pub(crate) fn logger(&self, verbose: u8) -> Logger {
+        self.context.logger(verbose, self.quiet)
+    }
+
+    /// Check if the cargo home is left alone
+    pub fn keep_cargo_home(&self) -> bool {
+        self.keep_cargo_home
+    }
+
+    /// Check if incremental compilation data is kept
+    pub fn keep_incremental(&self) -> bool {
+        self.keep_incremental
+    }
+
+    /// Check if the generated documentation is kept
+    pub fn keep_docs(&self) -> bool {
+        self.keep_docs
+    }
+
+    /// Get the ephemeral environment GC runs in, if any
+    pub fn runner(&self) -> Option<Runner> {
+        self.runner
+    }
+
+    /// Run GC as a quiet dry run to find out what it would remove
+    ///
+    /// The directory sizes measured on the way are kept for the GC that
+    /// follows.
+    pub fn preview(&mut self) -> Result<GcStats> {
+        let (dry_run, quiet) = (self.dry_run, self.quiet);
+        self.dry_run = true;
+        self.quiet = true;
+        let stats = self.perform_gc(0);
+        self.dry_run = dry_run;
+        self.quiet = quiet;
+
+        let mut stats = stats?;
+        if let Some(index) = &self.size_index {
+            *index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+                SizeIndex::new(std::mem::take(&mut stats.directory_sizes));
+        }
+        Ok(stats)
+    }
+
     /// Main entry point for garbage collection
     ///
     /// Performs comprehensive garbage collection on build artifacts using a
     /// combined size and age-based strategy:
     ///
-    /// 1. **Size enforcement**: If max_target_size is specified and exceeded,
-    ///    removes oldest artifacts first until the target directory is under
-    ///    the limit
-    /// 2. **Age cleanup**: Removes all artifacts older than age_threshold_days
+    /// 1. **Size enforcement**: If max_target_size or max_target_files is
+    ///    specified and exceeded, removes oldest artifacts first until the
+    ///    target directory is under both limits
+    /// 2. **Age cleanup**: Removes all artifacts older than the age threshold
     ///
     /// Both conditions are always applied together, ensuring consistent cleanup
     /// behavior. The function also cleans cargo registry cache, git checkouts,
@@ -99,81 +628,404 @@ impl Gc {
     ///
     /// Statistics about the garbage collection operation
     pub fn perform_gc(&self, verbose: u8) -> Result<GcStats> {
+        let log = self.logger(verbose);
+        let paths = if self.sandbox() == SandboxMode::Off {
+            SandboxPaths::default()
+        } else {
+            self.sandbox_paths()?
+        };
+        run_confined(self.sandbox(), &paths, log, || self.collect(verbose))
+    }
+
+    /// What a confined collection may write to: the directories it cleans,
+    /// and the files in the cargo home root it updates
+    fn sandbox_paths(&self) -> Result<SandboxPaths> {
+        let mut paths = SandboxPaths {
+            dirs: vec![self.target_dir().to_path_buf()],
+            // Opened for writing by policy commands run without input
+            files: vec![PathBuf::from("/dev/null")],
+        };
+        paths.dirs.extend(self.build_dir().map(Path::to_path_buf));
+        if self.path_filter.is_none() && !self.keep_cargo_home() {
+            let cargo_home = self.cargo_home()?;
+            for dir in ["registry", "git", "bin"] {
+                paths.dirs.push(cargo_home.join(dir));
+            }
+            for suffix in ["", "-journal", "-wal", "-shm"] {
+                paths
+                    .files
+                    .push(cargo_home.join(format!("{GLOBAL_CACHE_DB}{suffix}")));
+            }
+            // Created up front, as a confined collection cannot create files
+            // in the cargo home root
+            if !self.dry_run() && cargo_home.is_dir() {
+                paths
+                    .files
+                    .extend(create_package_cache_lock_files(&cargo_home)?);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn collect(&self, verbose: u8) -> Result<GcStats> {
         let mut stats = GcStats::default();
-        let log = Logger::new(verbose, self.quiet());
+        let log = self.logger(verbose);
 
         if !log.quiet() && (log.level() > 0 || self.debug()) {
             eprintln!("Starting garbage collection in {:?}", self.target_dir());
             eprintln!("Cleanup criteria:");
             if let Some(max_size) = self.max_target_size() {
-                eprintln!("  - Target directory size: {}", format_size(max_size));
+                eprintln!("  - Target directory size: {}", log.format_size(max_size));
+            }
+            if let Some(max_files) = self.max_target_files() {
+                eprintln!("  - Target directory files: {max_files}");
+            }
+            if let Some(build_dir) = self.build_dir() {
+                eprintln!("  - Build directory: {}", build_dir.display());
+                if let Some(max_size) = self.max_build_dir_size() {
+                    eprintln!("  - Build directory size: {}", log.format_size(max_size));
+                }
+            }
+            if let Some(max_size) = self.max_coverage_size() {
+                eprintln!("  - Coverage data size: {}", log.format_size(max_size));
             }
             eprintln!(
-                "  - Remove artifacts older than {} days",
-                self.age_threshold_days()
+                "  - Remove artifacts older than {}",
+                format_duration(self.age_threshold_for(GcArea::Deps))
             );
+            for area in GcArea::ALL {
+                if area != GcArea::Deps
+                    && let Some(threshold) = self.area_age_thresholds.get(&area)
+                {
+                    if *threshold == KEEP {
+                        eprintln!("  - Keep {area}");
+                        continue;
+                    }
+                    eprintln!(
+                        "  - Remove {area} older than {}",
+                        format_duration(*threshold)
+                    );
+                }
+            }
+            if let Some(filter) = self.path_filter() {
+                eprintln!(
+                    "  - Only within: {}",
+                    filter.patterns().collect::<Vec<_>>().join(", ")
+                );
+            }
+            if let Some(policy) = self.policy_command() {
+                eprintln!("  - Policy command: {}", policy.command());
+            }
+            if let Some(pins) = self.pinned_crates() {
+                eprintln!(
+                    "  - Never evict: {}",
+                    pins.patterns().collect::<Vec<_>>().join(", ")
+                );
+            }
+            if self.global_cache_mode() == GlobalCacheMode::Off {
+                eprintln!("  - Ignoring cargo's global cache database");
+            }
+            if self.compress_cold() {
+                eprintln!("  - Compress cold artifacts before removing them");
+            }
+        }
+
+        // Never delete artifacts out from under a running build
+        if !self.dry_run()
+            && !wait_until_idle(
+                self.target_dir(),
+                self.when_busy(),
+                self.busy_timeout(),
+                &log,
+            )?
+        {
+            stats.skipped_busy = true;
+            return Ok(stats);
+        }
+
+        // Hold cargo's own lock while deleting so a build started meanwhile
+        // waits for us instead of racing the cleanup
+        let _lock = if self.dry_run() {
+            None
+        } else {
+            Some(TargetDirLock::acquire(
+                self.target_dir(),
+                LockMode::Exclusive,
+                &log,
+            )?)
+        };
+
+        // Waiting for a build does not count against the time budget
+        self.start_clock();
+
+        // Fail before removing anything rather than part way through
+        let cleans_cargo_home = self.path_filter.is_none() && !self.keep_cargo_home();
+        if !self.dry_run() {
+            let cargo_home = if cleans_cargo_home {
+                Some(self.cargo_home()?)
+            } else {
+                None
+            };
+            preflight::check_removable(self, true, cargo_home.as_deref())?;
         }
 
         // Calculate initial size (return 0 if directory doesn't exist)
-        stats.initial_size = if self.target_dir().exists() {
-            calculate_directory_size(self.target_dir())?
+        let initial = if self.target_dir().exists() {
+            self.measure_target_dir(true)?
         } else {
-            0
+            BudgetedUsage::default()
         };
+        stats.initial_usage = initial.sizes;
+        stats.initial_size = stats.initial_usage.get(self.size_mode());
+        stats.initial_size_estimated = initial.estimated;
+        stats.initial_files = stats.initial_usage.files;
+
+        // Groups removed by this run are dropped from the manifest even when
+        // nothing new is compressed
+        self.with_cold_manifest(|manifest| -> Result<()> {
+            *manifest = ColdManifest::load(self.target_dir())?;
+            Ok(())
+        })?;
 
         if !log.quiet() {
             // Always provide feedback about the operation
             eprintln!("Cleanup status:");
-            eprintln!("  Current size: {}", format_size(stats.initial_size));
+            eprintln!(
+                "  Current size: {}{} (apparent {}, allocated {})",
+                if stats.initial_size_estimated {
+                    "~"
+                } else {
+                    ""
+                },
+                log.format_size(stats.initial_size),
+                log.format_size(stats.initial_usage.apparent),
+                log.format_size(stats.initial_usage.allocated)
+            );
 
             if let Some(max_size) = self.max_target_size() {
-                eprintln!("  Target size: {}", format_size(max_size));
+                eprintln!("  Target size: {}", log.format_size(max_size));
                 if stats.initial_size > max_size {
                     eprintln!(
                         "  Need to free: {} (for size limit)",
-                        format_size(stats.initial_size - max_size)
+                        log.format_size(stats.initial_size - max_size)
                     );
                 } else {
                     eprintln!("  Already within target size");
                 }
             }
+            eprintln!("  Current files: {}", stats.initial_files);
+            if let Some(max_files) = self.max_target_files() {
+                eprintln!("  Target files: {max_files}");
+                if stats.initial_files > max_files {
+                    eprintln!(
+                        "  Need to remove: {} files (for file limit)",
+                        stats.initial_files - max_files
+                    );
+                } else {
+                    eprintln!("  Already within file limit");
+                }
+            }
 
-            eprintln!("  Age threshold: {} days", self.age_threshold_days());
+            eprintln!(
+                "  Age threshold: {}",
+                format_duration(self.age_threshold_for(GcArea::Deps))
+            );
         }
 
+        // Tool outputs go first, as a whole, so the profile directories they
+        // held are not cleaned crate by crate afterwards
+        let (outputs_freed, outputs_removed) =
+            clean_tool_outputs(self.target_dir(), self, verbose)?;
+        stats.bytes_freed += outputs_freed.get(self.size_mode());
+        stats.files_freed += outputs_freed.files;
+
         // Clean profile directories
-        let profile_dirs = find_profile_directories(self.target_dir())?;
+        let root = ProfileRoot {
+            dir: self.target_dir(),
+            max_size: self.max_target_size(),
+            max_files: self.max_target_files(),
+        };
+        let mut profile_dirs = self.profile_directories(self.target_dir())?;
+        profile_dirs.retain(|dir| {
+            !outputs_removed
+                .iter()
+                .any(|removed| dir.starts_with(removed))
+        });
         for profile_dir in profile_dirs {
+            if self.out_of_time() {
+                break;
+            }
             log.verbose(1, format!("Cleaning profile directory: {profile_dir:?}"));
-            let profile_stats = clean_profile_directory(&profile_dir, self, verbose, &stats)?;
-            stats.bytes_freed += profile_stats.bytes_freed;
-            stats.artifacts_removed += profile_stats.artifacts_removed;
-            stats.crates_cleaned += profile_stats.crates_cleaned;
-            stats.binaries_preserved += profile_stats.binaries_preserved;
+            let profile_stats =
+                clean_profile_directory(&profile_dir, &root, self, verbose, &stats)?;
+            stats.add_profile(profile_stats);
+            if !self.dry_run() {
+                self.with_cold_manifest(|manifest| manifest.save(self.target_dir()))?;
+            }
         }
 
-        // Clean other directories (doc, package, tmp)
-        stats.bytes_freed += clean_misc_directories(self.target_dir(), self, verbose)?;
+        if let Some(build_dir) = self.build_dir()
+            && build_dir.exists()
+            && !self.out_of_time()
+        {
+            self.clean_build_dir(build_dir, &mut stats, verbose)?;
+        }
 
-        // Clean cargo registry and downloads
-        log.verbose(1, "Cleaning cargo registry...");
-        let registry_stats = self.clean_cargo_registry(verbose)?;
-        stats.bytes_freed += registry_stats.bytes_freed;
-        stats.registry_bytes_freed = registry_stats.bytes_freed;
-        stats.registry_files_removed = registry_stats.files_removed;
-        stats.registry_dirs_removed = registry_stats.dirs_removed;
+        // Clean other directories (doc, package, tmp)
+        let misc_freed = clean_misc_directories(self.target_dir(), self, verbose)?;
+        stats.bytes_freed += misc_freed.get(self.size_mode());
+        stats.files_freed += misc_freed.files;
 
-        // Clean cargo binaries
-        log.verbose(1, "Cleaning cargo binaries...");
-        stats.bytes_freed += self.clean_cargo_bin(verbose)?;
+        // The cargo home is outside of any --only scope
+        if cleans_cargo_home && !self.out_of_time() {
+            self.sweep_cargo_home(&mut stats, verbose)?;
+        }
 
         // Calculate final size
-        stats.final_size = calculate_directory_size(self.target_dir())?;
+        let final_usage = self.measure_target_dir(false)?;
+        stats.final_usage = final_usage.sizes;
+        stats.final_size = stats.final_usage.get(self.size_mode());
+        stats.final_size_estimated = final_usage.estimated;
+        stats.final_files = stats.final_usage.files;
+
+        stats.removed_paths = self.take_removals();
+        stats.errors = self.take_removal_errors();
+        stats.refused_removals = self.take_refused_removals();
+        stats.out_of_time = self.stopped_early.load(Ordering::Relaxed);
+        if let Some(index) = &self.size_index {
+            stats.directory_sizes = index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .take_entries();
+        }
 
         Ok(stats)
     }
 
-    /// Clean the cargo registry cache (~/.cargo/registry).
+    /// Clean the profile directories of a build directory apart from the
+    /// target directory under its own size cap, adding what was freed to
+    /// `stats`
+    fn clean_build_dir(&self, build_dir: &Path, stats: &mut GcStats, verbose: u8) -> Result<()> {
+        let log = self.logger(verbose);
+        let initial = calculate_disk_usage_following(build_dir, self.symlinks())?;
+        // The build directory's own budget, tracked apart from the target
+        // directory's
+        let mut budget = GcStats {
+            initial_size: initial.get(self.size_mode()),
+            initial_files: initial.files,
+            ..GcStats::default()
+        };
+        log.verbose(
+            1,
+            format!(
+                "Build directory {}: {}",
+                build_dir.display(),
+                log.format_size(budget.initial_size)
+            ),
+        );
+
+        let root = ProfileRoot {
+            dir: build_dir,
+            max_size: self.max_build_dir_size(),
+            max_files: None,
+        };
+        for profile_dir in self.profile_directories(build_dir)? {
+            if self.out_of_time() {
+                break;
+            }
+            log.verbose(1, format!("Cleaning profile directory: {profile_dir:?}"));
+            let profile_stats =
+                clean_profile_directory(&profile_dir, &root, self, verbose, &budget)?;
+            budget.bytes_freed += profile_stats.bytes_freed;
+            budget.files_freed += profile_stats.files_freed;
+            stats.add_profile(profile_stats);
+        }
+
+        stats.build_dir_initial_size = budget.initial_size;
+        stats.build_dir_final_size =
+            calculate_disk_usage_following(build_dir, self.symlinks())?.get(self.size_mode());
+        Ok(())
+    }
+
+    /// Clean the cargo home: the registry and downloads, then the installed
+    /// binaries, adding what was freed to `stats`.
+    ///
+    /// A cargo home baked into the runner's image is left alone, as is the
+    /// `bin` directory on a runner (unless `--age-threshold-for bin=...`).
+    ///
+    /// Unless in a dry run, fails without removing anything when an entry of
+    /// the cargo home could not be removed.
+    pub fn clean_cargo_home(&self, stats: &mut GcStats, verbose: u8) -> Result<()> {
+        if !self.dry_run() {
+            preflight::check_removable(self, false, Some(&self.cargo_home()?))?;
+        }
+        self.start_clock();
+        self.sweep_cargo_home(stats, verbose)?;
+        stats.errors += self.take_removal_errors();
+        stats.refused_removals += self.take_refused_removals();
+        stats.out_of_time |= self.stopped_early.load(Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn sweep_cargo_home(&self, stats: &mut GcStats, verbose: u8) -> Result<()> {
+        let log = self.logger(verbose);
+
+        // A cargo home baked into the image is restored in full by the
+        // next container
+        let cargo_home = self.cargo_home()?;
+        if let Some(runner) = self.runner
+            && !self.cleans_cargo_registry(&cargo_home)
+        {
+            log.verbose(
+                1,
+                format!(
+                    "Running in {runner} whose image holds {}; skipping cargo registry cleanup",
+                    cargo_home.display()
+                ),
+            );
+        } else {
+            // Clean cargo registry and downloads
+            log.verbose(1, "Cleaning cargo registry...");
+            let registry_stats = self.clean_cargo_registry(verbose)?;
+            stats.bytes_freed += registry_stats.bytes_freed;
+            stats.registry_bytes_freed = registry_stats.bytes_freed;
+            stats.registry_files_removed = registry_stats.files_removed;
+            stats.registry_dirs_removed = registry_stats.dirs_removed;
+        }
+
+        // Binaries installed on a fresh machine never reach the default
+        // age threshold
+        if let Some(runner) = self.runner
+            && !self.cleans_cargo_bin()
+        {
+            log.verbose(
+                1,
+                format!("Running on {runner}; skipping cargo binaries cleanup"),
+            );
+        } else if self.out_of_time() {
+            log.verbose(1, "Out of time; skipping cargo binaries cleanup");
+        } else {
+            // Clean cargo binaries
+            log.verbose(1, "Cleaning cargo binaries...");
+            stats.bytes_freed += self.clean_cargo_bin(verbose)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the cargo home cleanup cleans the registry and git checkouts
+    /// of `cargo_home`
+    pub(crate) fn cleans_cargo_registry(&self, cargo_home: &Path) -> bool {
+        !self
+            .runner
+            .is_some_and(|runner| runner.is_container() && runner::baked_into_image(cargo_home))
+    }
+
+    /// Whether the cargo home cleanup cleans the installed binaries
+    pub(crate) fn cleans_cargo_bin(&self) -> bool {
+        self.runner.is_none() || self.area_age_thresholds.contains_key(&GcArea::Bin)
+    }
+
+    /// Clean the cargo registry cache (`<cargo home>/registry`).
     ///
     /// Removes old cached crates and git checkouts based on age threshold.
     ///
@@ -199,9 +1051,10 @@ impl Gc {
         cargo::clean_cargo_registry_with_home(self, cargo_home, verbose)
     }
 
-    /// Clean old binaries from ~/.cargo/bin.
+    /// Clean old binaries from `<cargo home>/bin`.
     ///
-    /// Removes binaries older than 30 days, except for preserved binaries
+    /// Removes binaries older than the `bin` age threshold (default: 30 days),
+    /// except for preserved binaries
     /// and those in the default preservation list.
     ///
     /// # Arguments
@@ -234,7 +1087,12 @@ impl Gc {
         cargo::clean_cargo_bin_with_home(self, cargo_home, verbose)
     }
 
-    fn cargo_home(&self) -> Result<PathBuf> {
+    /// Get the cargo home whose registry, git checkouts, and binaries are
+    /// cleaned: the configured one, else `$CARGO_HOME`, else `~/.cargo`
+    pub fn cargo_home(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.cargo_home {
+            return Ok(path.clone());
+        }
         if let Some(path) = std::env::var_os("CARGO_HOME") {
             return Ok(PathBuf::from(path));
         }
@@ -250,27 +1108,99 @@ impl Default for Gc {
         Self {
             target_dir: PathBuf::from("target"),
             max_target_size: None,
+            max_target_files: None,
+            build_dir: None,
+            max_build_dir_size: None,
+            max_coverage_size: None,
             dry_run: false,
             debug: false,
-            age_threshold_days: 7,
+            age_threshold: DEFAULT_AGE_THRESHOLD,
+            area_age_thresholds: HashMap::new(),
             preserve_binaries: Vec::new(),
-            previous_build_mtime_nanos: None,
+            recent_build_mtime_nanos: Vec::new(),
+            profile_build_mtime_nanos: HashMap::new(),
+            preserve_last_builds: 1,
+            preserve_buffer: DEFAULT_PRESERVE_BUFFER,
+            preserve_window: None,
+            build_units: None,
+            pinned_crates: None,
+            build_costs: None,
+            size_mode: SizeMode::default(),
+            path_filter: None,
+            policy_command: None,
+            lockfiles: Vec::new(),
+            cargo_home: None,
+            global_cache_mode: GlobalCacheMode::default(),
+            sandbox: SandboxMode::default(),
+            symlinks: SymlinkPolicy::default(),
+            when_busy: BusyPolicy::default(),
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            max_duration: None,
+            deadline: Mutex::new(None),
+            stopped_early: AtomicBool::new(false),
+            removals: None,
+            removal_errors: AtomicUsize::new(0),
+            removal_roots: OnceLock::new(),
+            refused_removals: AtomicUsize::new(0),
+            size_index: None,
+            compress_cold: false,
+            dep_info: false,
+            profile_names: Vec::new(),
+            cold_manifest: Mutex::default(),
+            keep_cargo_home: false,
+            keep_incremental: false,
+            keep_docs: false,
+            runner: None,
             quiet: false,
+            context: HoldContext::default(),
         }
     }
 }
 
 /// Builder for [`Gc`]
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct GcBuilder {
     target_dir: Option<PathBuf>,
     max_target_size: Option<u64>,
+    max_target_files: Option<u64>,
+    build_dir: Option<PathBuf>,
+    max_build_dir_size: Option<u64>,
+    max_coverage_size: Option<u64>,
     dry_run: bool,
     debug: bool,
-    age_threshold_days: Option<u32>,
+    age_threshold: Option<Duration>,
+    area_age_thresholds: HashMap<GcArea, Duration>,
     preserve_binaries: Vec<String>,
-    previous_build_mtime_nanos: Option<u128>,
+    recent_build_mtime_nanos: Vec<u128>,
+    profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+    preserve_last_builds: Option<usize>,
+    preserve_buffer: Option<Duration>,
+    preserve_window: Option<Duration>,
+    build_units: Option<BuildUnits>,
+    pinned_crates: Option<PinnedCrates>,
+    build_costs: Option<BuildCosts>,
+    size_mode: SizeMode,
+    path_filter: Option<PathFilter>,
+    policy_command: Option<PolicyCommand>,
+    lockfiles: Vec<PathBuf>,
+    cargo_home: Option<PathBuf>,
+    global_cache_mode: GlobalCacheMode,
+    sandbox: SandboxMode,
+    symlinks: SymlinkPolicy,
+    when_busy: BusyPolicy,
+    busy_timeout: Option<Duration>,
+    max_duration: Option<Duration>,
+    record_removals: bool,
+    directory_sizes: Option<HashMap<String, DirectorySize>>,
+    compress_cold: bool,
+    dep_info: bool,
+    profile_names: Vec<String>,
+    keep_cargo_home: bool,
+    keep_incremental: bool,
+    keep_docs: bool,
+    runner: Option<Runner>,
     quiet: bool,
+    context: HoldContext,
 }
 
 impl GcBuilder {
@@ -286,6 +1216,37 @@ impl GcBuilder {
         self
     }
 
+    /// Set the maximum number of files and directories in the target
+    /// directory
+    pub fn max_target_files(mut self, files: u64) -> Self {
+        self.max_target_files = Some(files);
+        self
+    }
+
+    /// Set cargo's build directory (`build.build-dir`), when it keeps the
+    /// intermediate artifacts apart from the target directory. Its profile
+    /// directories are cleaned after those of the target directory, under
+    /// [`max_build_dir_size`](Self::max_build_dir_size) rather than the
+    /// target directory's caps
+    pub fn build_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.build_dir = Some(dir.into());
+        self
+    }
+
+    /// Set the maximum build directory size
+    pub fn max_build_dir_size(mut self, size: u64) -> Self {
+        self.max_build_dir_size = Some(size);
+        self
+    }
+
+    /// Set the maximum size of the coverage data in the target directory
+    /// (`*.profraw`, `*.profdata`, and `llvm-cov-target`). The oldest files
+    /// and `llvm-cov-target` entries are removed as a whole until it fits
+    pub fn max_coverage_size(mut self, size: u64) -> Self {
+        self.max_coverage_size = Some(size);
+        self
+    }
+
     /// Enable dry run mode
     pub fn dry_run(mut self, enabled: bool) -> Self {
         self.dry_run = enabled;
@@ -300,7 +1261,20 @@ impl GcBuilder {
 
     /// Set the age threshold in days
     pub fn age_threshold_days(mut self, days: u32) -> Self {
-        self.age_threshold_days = Some(days);
+        self.age_threshold = Some(Duration::from_secs(days as u64 * 24 * 60 * 60));
+        self
+    }
+
+    /// Set the age threshold (default: 7 days)
+    pub fn age_threshold(mut self, threshold: Duration) -> Self {
+        self.age_threshold = Some(threshold);
+        self
+    }
+
+    /// Override the age threshold for a single area. [`Duration::MAX`] keeps
+    /// the outputs of a tool ([`GcArea::is_tool_output`]) altogether
+    pub fn area_age_threshold(mut self, area: GcArea, threshold: Duration) -> Self {
+        self.area_age_thresholds.insert(area, threshold);
         self
     }
 
@@ -317,8 +1291,211 @@ impl GcBuilder {
     }
 
     /// Set the previous build mtime in nanoseconds
+    ///
+    /// Replaces any history set with
+    /// [`recent_build_mtime_nanos`](Self::recent_build_mtime_nanos).
     pub fn previous_build_mtime_nanos(mut self, nanos: u128) -> Self {
-        self.previous_build_mtime_nanos = Some(nanos);
+        self.recent_build_mtime_nanos = vec![nanos];
+        self
+    }
+
+    /// Set the recent build mtimes in nanoseconds, oldest first
+    pub fn recent_build_mtime_nanos(mut self, nanos: Vec<u128>) -> Self {
+        self.recent_build_mtime_nanos = nanos;
+        self
+    }
+
+    /// Set the last-build timestamps per profile directory, oldest first
+    ///
+    /// Keys are profile directories relative to the target directory (e.g.
+    /// `debug` or `x86_64-unknown-linux-gnu/release`).
+    pub fn profile_build_mtime_nanos(mut self, profiles: HashMap<String, Vec<u128>>) -> Self {
+        self.profile_build_mtime_nanos = profiles;
+        self
+    }
+
+    /// Set how many of the most recent builds have their artifacts preserved
+    /// (default: 1)
+    pub fn preserve_last_builds(mut self, builds: usize) -> Self {
+        self.preserve_last_builds = Some(builds);
+        self
+    }
+
+    /// Set how long before the previous build timestamp artifacts are still
+    /// preserved (default: 5 minutes)
+    pub fn preserve_buffer(mut self, buffer: Duration) -> Self {
+        self.preserve_buffer = Some(buffer);
+        self
+    }
+
+    /// Set how old the previous build timestamp may be before preservation is
+    /// skipped (default: the age threshold)
+    pub fn preserve_window(mut self, window: Duration) -> Self {
+        self.preserve_window = Some(window);
+        self
+    }
+
+    /// Set the units used by the last build, which are never evicted
+    pub fn build_units(mut self, units: BuildUnits) -> Self {
+        self.build_units = Some(units);
+        self
+    }
+
+    /// Set crates whose artifacts are never evicted
+    pub fn pinned_crates(mut self, pins: PinnedCrates) -> Self {
+        self.pinned_crates = Some(pins);
+        self
+    }
+
+    /// Set the recorded build durations, so that crates costly to rebuild
+    /// are evicted after cheaper ones of the same age
+    pub fn build_costs(mut self, costs: BuildCosts) -> Self {
+        self.build_costs = Some(costs);
+        self
+    }
+
+    /// Set how file sizes are measured (default: allocated blocks)
+    pub fn size_mode(mut self, mode: SizeMode) -> Self {
+        self.size_mode = mode;
+        self
+    }
+
+    /// Restrict GC to the subtrees of the target directory matching a filter;
+    /// the cargo registry and `~/.cargo/bin` are left alone
+    pub fn path_filter(mut self, filter: PathFilter) -> Self {
+        self.path_filter = Some(filter);
+        self
+    }
+
+    /// Let an external command keep, evict, or rank artifacts before the
+    /// built-in age and size rules run
+    pub fn policy_command(mut self, command: PolicyCommand) -> Self {
+        self.policy_command = Some(command);
+        self
+    }
+
+    /// Keep git checkouts for revisions referenced by a `Cargo.lock`,
+    /// regardless of age
+    pub fn lockfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lockfiles.push(path.into());
+        self
+    }
+
+    /// Set the cargo home to clean instead of `$CARGO_HOME` or `~/.cargo`
+    pub fn cargo_home(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cargo_home = Some(path.into());
+        self
+    }
+
+    /// Set whether cargo's global cache tracking database is used for cargo
+    /// home cleanup (default: when present)
+    pub fn global_cache_mode(mut self, mode: GlobalCacheMode) -> Self {
+        self.global_cache_mode = mode;
+        self
+    }
+
+    /// Set whether the collection is confined with Landlock to writing below
+    /// the target directory, the build directory, and the cleaned parts of
+    /// the cargo home (default: off)
+    pub fn sandbox(mut self, mode: SandboxMode) -> Self {
+        self.sandbox = mode;
+        self
+    }
+
+    /// Set whether measuring sizes descends into symbolic links to
+    /// directories (default: skip, counting each link as itself). Artifacts
+    /// are grouped and removed as links either way.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Set what to do when a build is using the target directory (default:
+    /// wait)
+    pub fn when_busy(mut self, policy: BusyPolicy) -> Self {
+        self.when_busy = policy;
+        self
+    }
+
+    /// Set how long to wait for a busy target directory (default: 10 minutes)
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long the collection may take; once it runs out the remaining
+    /// evictions are left for the next run, and the largest artifact groups
+    /// are evicted first so the budget goes where it frees the most
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Record every removed path in [`GcStats::removed_paths`] (default:
+    /// off)
+    pub fn record_removals(mut self, record: bool) -> Self {
+        self.record_removals = record;
+        self
+    }
+
+    /// Reuse the directory sizes recorded by a previous GC when measuring
+    /// the target directory; the updated index is returned in
+    /// [`GcStats::directory_sizes`]
+    pub(crate) fn directory_sizes(mut self, sizes: HashMap<String, DirectorySize>) -> Self {
+        self.directory_sizes = Some(sizes);
+        self
+    }
+
+    /// Compress artifact groups selected for eviction in place, and only
+    /// remove groups that are already compressed (default: off)
+    pub fn compress_cold(mut self, enabled: bool) -> Self {
+        self.compress_cold = enabled;
+        self
+    }
+
+    /// Read the rustc dep-info of every crate: crates with a workspace source
+    /// newer than their build are evicted, as cargo would rebuild them, and
+    /// crates whose workspace sources all predate their build are exempt from
+    /// the age threshold, though not from the size cap (default: off)
+    pub fn dep_info(mut self, enabled: bool) -> Self {
+        self.dep_info = enabled;
+        self
+    }
+
+    /// Set the names of the profile directories declared by the workspace
+    /// (`debug`, `release`, `release-lto`, ...). Directories with these names
+    /// that hold build artifacts are cleaned as profiles however deep they
+    /// nest in the target directory, e.g. `llvm-cov-target/<triple>/coverage`
+    pub fn profile_names(mut self, names: Vec<String>) -> Self {
+        self.profile_names = names;
+        self
+    }
+
+    /// Leave the registry, git checkouts, and binaries of the cargo home
+    /// alone (default: off)
+    pub fn keep_cargo_home(mut self, keep: bool) -> Self {
+        self.keep_cargo_home = keep;
+        self
+    }
+
+    /// Keep incremental compilation data regardless of its age (default:
+    /// off)
+    pub fn keep_incremental(mut self, keep: bool) -> Self {
+        self.keep_incremental = keep;
+        self
+    }
+
+    /// Keep the generated documentation in `target/doc` (default: off)
+    pub fn keep_docs(mut self, keep: bool) -> Self {
+        self.keep_docs = keep;
+        self
+    }
+
+    /// Set the ephemeral environment GC runs in: cargo binaries are not
+    /// cleaned unless their age threshold is set, nor a cargo home baked into
+    /// a container image
+    pub fn runner(mut self, runner: Runner) -> Self {
+        self.runner = Some(runner);
         self
     }
 
@@ -328,17 +1505,70 @@ impl GcBuilder {
         self
     }
 
+    /// Set the context of the command collecting (default:
+    /// [`HoldContext::default`])
+    pub fn context(mut self, context: &HoldContext) -> Self {
+        self.context = context.clone();
+        self
+    }
+
     /// Build the [`Gc`]
     pub fn build(self) -> Gc {
+        let build_dir = self.build_dir.filter(|dir| {
+            self.target_dir
+                .as_deref()
+                .is_none_or(|target_dir| dir != target_dir)
+        });
         Gc {
             target_dir: self.target_dir.unwrap_or_else(|| PathBuf::from("target")),
             max_target_size: self.max_target_size,
+            max_target_files: self.max_target_files,
+            build_dir,
+            max_build_dir_size: self.max_build_dir_size,
+            max_coverage_size: self.max_coverage_size,
             dry_run: self.dry_run,
             debug: self.debug,
-            age_threshold_days: self.age_threshold_days.unwrap_or(7),
+            age_threshold: self.age_threshold.unwrap_or(DEFAULT_AGE_THRESHOLD),
+            area_age_thresholds: self.area_age_thresholds,
             preserve_binaries: self.preserve_binaries,
-            previous_build_mtime_nanos: self.previous_build_mtime_nanos,
+            recent_build_mtime_nanos: self.recent_build_mtime_nanos,
+            profile_build_mtime_nanos: self.profile_build_mtime_nanos,
+            preserve_last_builds: self.preserve_last_builds.unwrap_or(1),
+            preserve_buffer: self.preserve_buffer.unwrap_or(DEFAULT_PRESERVE_BUFFER),
+            preserve_window: self.preserve_window,
+            build_units: self.build_units,
+            pinned_crates: self.pinned_crates,
+            build_costs: self.build_costs,
+            size_mode: self.size_mode,
+            path_filter: self.path_filter,
+            policy_command: self.policy_command,
+            lockfiles: self.lockfiles,
+            cargo_home: self.cargo_home,
+            global_cache_mode: self.global_cache_mode,
+            sandbox: self.sandbox,
+            symlinks: self.symlinks,
+            when_busy: self.when_busy,
+            busy_timeout: self.busy_timeout.unwrap_or(DEFAULT_BUSY_TIMEOUT),
+            max_duration: self.max_duration,
+            deadline: Mutex::new(None),
+            stopped_early: AtomicBool::new(false),
+            removals: self.record_removals.then(Mutex::default),
+            removal_errors: AtomicUsize::new(0),
+            removal_roots: OnceLock::new(),
+            refused_removals: AtomicUsize::new(0),
+            size_index: self
+                .directory_sizes
+                .map(|sizes| Mutex::new(SizeIndex::new(sizes))),
+            compress_cold: self.compress_cold,
+            dep_info: self.dep_info,
+            profile_names: self.profile_names,
+            cold_manifest: Mutex::default(),
+            keep_cargo_home: self.keep_cargo_home,
+            keep_incremental: self.keep_incremental,
+            keep_docs: self.keep_docs,
+            runner: self.runner,
             quiet: self.quiet,
+            context: self.context,
         }
     }
 }
@@ -358,10 +1588,77 @@ pub struct GcStats {
     pub artifacts_removed: usize,
     /// Number of crates cleaned
     pub crates_cleaned: usize,
-    /// Initial target directory size
+    /// Number of crates compressed instead of removed
+    pub crates_compressed: usize,
+    /// Initial target directory size, in the configured size mode
     pub initial_size: u64,
-    /// Final target directory size
+    /// Final target directory size, in the configured size mode
     pub final_size: u64,
+    /// Initial target directory size, measured both ways
+    pub initial_usage: DiskSizes,
+    /// Final target directory size, measured both ways
+    pub final_usage: DiskSizes,
+    /// Initial number of files and directories in the target directory
+    pub initial_files: u64,
+    /// Final number of files and directories in the target directory
+    pub final_files: u64,
+    /// Files and directories removed from the target directory (or, in a
+    /// dry run, that would be removed)
+    pub files_freed: u64,
+    /// Whether the initial size was estimated from a sample because the
+    /// target directory was well within the size cap
+    pub initial_size_estimated: bool,
+    /// Whether the final size was estimated from a sample because the
+    /// target directory was well within the size cap
+    pub final_size_estimated: bool,
     /// Number of binaries preserved
     pub binaries_preserved: usize,
+    /// Number of files and directories that could not be removed; what they
+    /// hold is not counted as freed
+    pub errors: usize,
+    /// Number of files and directories refused for resolving outside of the
+    /// cleaned directories, e.g. through a symbolic link
+    pub refused_removals: usize,
+    /// Whether work was left for the next run because the time budget ran
+    /// out
+    pub out_of_time: bool,
+    /// Artifact groups selected for eviction but left because the time
+    /// budget ran out
+    pub remaining_crates: usize,
+    /// Size of the artifact groups left because the time budget ran out
+    pub remaining_bytes: u64,
+    /// Whether garbage collection was skipped because the target directory
+    /// was in use
+    pub skipped_busy: bool,
+    /// Initial size of the build directory, when it is apart from the target
+    /// directory, in the configured size mode
+    pub build_dir_initial_size: u64,
+    /// Final size of the build directory, when it is apart from the target
+    /// directory, in the configured size mode
+    pub build_dir_final_size: u64,
+    /// Newest artifact mtime seen per profile directory (relative to the
+    /// target directory), in nanoseconds since UNIX_EPOCH
+    pub profile_build_mtime_nanos: HashMap<String, u128>,
+    /// Every path removed (or, in a dry run, that would be removed), sorted;
+    /// only filled in when removals are recorded
+    pub removed_paths: Vec<RemovedPath>,
+    /// Directory size index after the final measurement, when one was given
+    /// to [`GcBuilder::directory_sizes`]
+    pub(crate) directory_sizes: HashMap<String, DirectorySize>,
+}
+
+impl GcStats {
+    /// Add what cleaning a profile directory did
+    fn add_profile(&mut self, profile: GcStats) {
+        self.bytes_freed += profile.bytes_freed;
+        self.files_freed += profile.files_freed;
+        self.artifacts_removed += profile.artifacts_removed;
+        self.crates_cleaned += profile.crates_cleaned;
+        self.crates_compressed += profile.crates_compressed;
+        self.binaries_preserved += profile.binaries_preserved;
+        self.remaining_crates += profile.remaining_crates;
+        self.remaining_bytes += profile.remaining_bytes;
+        self.profile_build_mtime_nanos
+            .extend(profile.profile_build_mtime_nanos);
+    }
 }
@@ -0,0 +1,140 @@
+//! Checks that every path about to be removed lies within the directories
+//! garbage collection cleans, so a symbolic link planted in the target
+//! directory or the cargo home cannot redirect a removal elsewhere.
+
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+use crate::error::{HoldError, Result};
+
+/// The directories removals are confined to
+#[derive(Debug, Default)]
+pub(crate) struct RemovalRoots {
+    /// Each directory as written, made absolute, and with its symbolic links
+    /// resolved
+    roots: Vec<(PathBuf, PathBuf)>,
+}
+
+impl RemovalRoots {
+    /// Confine removals to `dirs`; those that do not exist are left out, as
+    /// there is nothing to remove in them
+    pub(crate) fn new(dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        let roots = dirs
+            .into_iter()
+            .filter_map(|dir| {
+                let canonical = fs::canonicalize(&dir).ok()?;
+                Some((std::path::absolute(&dir).ok()?, canonical))
+            })
+            .collect();
+        Self { roots }
+    }
+
+    /// Check that `path` lies below one of the directories, both as written
+    /// and with the symbolic links of its parent directories resolved.
+    ///
+    /// The path itself may be a symbolic link: removing it deletes the link
+    /// rather than what it points to. A path whose parent directory is gone,
+    /// as when it was removed along with it, is only checked as written:
+    /// there is no link left to pass through.
+    pub(crate) fn check(&self, path: &Path) -> Result<()> {
+        let lexical = std::path::absolute(path).map_err(|source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let escape = |resolved: PathBuf| HoldError::UnsafeRemoval {
+            path: path.to_path_buf(),
+            resolved,
+        };
+        if lexical
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(escape(lexical));
+        }
+        let (Some(parent), Some(name)) = (lexical.parent(), lexical.file_name()) else {
+            return Err(escape(lexical));
+        };
+        let below = |path: &Path, root: &Path| path.starts_with(root) && path != root;
+        let resolved = match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(name),
+            Err(source) if source.kind() == io::ErrorKind::NotFound => {
+                return if self.roots.iter().any(|(root, _)| below(&lexical, root)) {
+                    Ok(())
+                } else {
+                    Err(escape(lexical))
+                };
+            }
+            Err(source) => {
+                return Err(HoldError::IoError {
+                    path: parent.to_path_buf(),
+                    source,
+                });
+            }
+        };
+
+        let contained = self.roots.iter().any(|(root, canonical_root)| {
+            below(&lexical, root) && below(&resolved, canonical_root)
+        });
+        if contained {
+            Ok(())
+        } else {
+            Err(escape(resolved))
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_removals_stay_within_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        let outside = temp_dir.path().join("home");
+        fs::create_dir_all(target.join("debug/deps")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(target.join("debug/deps/libfoo.rlib"), "").unwrap();
+        fs::write(outside.join("precious"), "").unwrap();
+        let roots = RemovalRoots::new([target.clone(), temp_dir.path().join("missing")]);
+
+        roots.check(&target.join("debug/deps/libfoo.rlib")).unwrap();
+        roots.check(&target.join("debug")).unwrap();
+
+        // The roots themselves, and anything lexically outside of them
+        for path in [
+            target.clone(),
+            outside.join("precious"),
+            target.join("debug/../../home/precious"),
+        ] {
+            let err = roots.check(&path).unwrap_err();
+            assert!(matches!(err, HoldError::UnsafeRemoval { .. }), "{err:?}");
+        }
+
+        // A symbolic link may be removed itself, but not be passed through
+        symlink(&outside, target.join("debug/escape")).unwrap();
+        roots.check(&target.join("debug/escape")).unwrap();
+        let err = roots
+            .check(&target.join("debug/escape/precious"))
+            .unwrap_err();
+        let HoldError::UnsafeRemoval { resolved, .. } = err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(
+            resolved,
+            fs::canonicalize(&outside).unwrap().join("precious")
+        );
+
+        // Below a directory removed already, there is nothing to escape
+        // through, but the path must still lie within the roots
+        roots
+            .check(&target.join("release/deps/libfoo.rlib"))
+            .unwrap();
+        let err = roots.check(&outside.join("gone/precious")).unwrap_err();
+        assert!(matches!(err, HoldError::UnsafeRemoval { .. }), "{err:?}");
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+
+use super::artifacts::CrateArtifact;
+use super::units::{normalize_name, package_name_from_id};
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// Weights of the costliest and cheapest crates to rebuild relative to the
+/// median one are capped at this factor, so that age still counts
+const MAX_COST_WEIGHT: f64 = 16.0;
+
+/// How long cargo took to build each crate, recorded in the metadata.
+///
+/// Under size pressure, artifacts are evicted oldest first with their age
+/// divided by how costly they are to rebuild per byte relative to the median
+/// crate: a crate taking ten times longer per MiB than the median is evicted
+/// as if it were ten times younger. Crates without a recorded cost weigh as
+/// much as the median.
+#[derive(Debug, Clone, Default)]
+pub struct BuildCosts {
+    /// Build duration in milliseconds by normalized crate name
+    millis: HashMap<String, u64>,
+}
+
+impl BuildCosts {
+    /// Create the costs from the build durations recorded in the metadata
+    pub fn new(millis: &HashMap<String, u64>) -> Self {
+        Self {
+            millis: millis.clone(),
+        }
+    }
+
+    /// Build durations in milliseconds by normalized crate name, read from
+    /// the `timing-info` messages of a cargo JSON message stream
+    /// (`cargo build --timings=json -Z unstable-options`).
+    ///
+    /// The units of a package, such as its library and build script, add up.
+    pub fn parse_timings(contents: &str) -> Result<HashMap<String, u64>> {
+        let mut millis: HashMap<String, u64> = HashMap::new();
+        let mut saw_timing = false;
+        for line in contents.lines() {
+            let Ok(message) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            if message.get("reason").and_then(Value::as_str) != Some("timing-info") {
+                continue;
+            }
+            saw_timing = true;
+            let name = message
+                .get("package_id")
+                .and_then(Value::as_str)
+                .and_then(package_name_from_id)
+                .or_else(|| {
+                    message
+                        .get("target")
+                        .and_then(|target| target.get("name"))
+                        .and_then(Value::as_str)
+                });
+            let duration = message
+                .get("duration")
+                .and_then(Value::as_f64)
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+            if let (Some(name), Some(duration)) = (name, duration) {
+                *millis.entry(normalize_name(name)).or_default() += duration.as_millis() as u64;
+            }
+        }
+
+        if !saw_timing {
+            return Err(HoldError::InvalidBuildTimings(
+                "No timing-info messages found".to_string(),
+            ));
+        }
+        Ok(millis)
+    }
+
+    /// Seconds cargo took to build the crate of an artifact group, if
+    /// recorded
+    pub(crate) fn rebuild_secs(&self, artifact: &CrateArtifact) -> Option<f64> {
+        let name = normalize_name(&artifact.name);
+        self.millis
+            .get(&name)
+            .or_else(|| {
+                name.strip_prefix("lib")
+                    .and_then(|stripped| self.millis.get(stripped))
+            })
+            .map(|&millis| millis as f64 / 1000.0)
+    }
+}
+
+/// Record the rebuild cost of every artifact group whose crate has one
+pub(crate) fn weigh_build_costs(
+    mut crate_artifacts: Vec<CrateArtifact>,
+    costs: Option<&BuildCosts>,
+    log: &Logger,
+) -> Vec<CrateArtifact> {
+    let Some(costs) = costs else {
+        return crate_artifacts;
+    };
+
+    let mut weighed = 0;
+    for artifact in &mut crate_artifacts {
+        artifact.rebuild_secs = costs.rebuild_secs(artifact);
+        weighed += usize::from(artifact.rebuild_secs.is_some());
+    }
+    log.verbose(
+        2,
+        format!(
+            "  Weighing eviction of {weighed} of {} artifacts by build cost",
+            crate_artifacts.len()
+        ),
+    );
+
+    crate_artifacts
+}
+
+/// Age of each artifact divided by its rebuild cost weight, in seconds
+pub(crate) fn weighted_ages(artifacts: &[&CrateArtifact], now: SystemTime) -> Vec<f64> {
+    // Rebuild seconds per MiB freed
+    let cost_per_mib = |artifact: &CrateArtifact| {
+        let mib = (artifact.total_size.max(1) as f64) / (1024.0 * 1024.0);
+        artifact.rebuild_secs.map(|secs| secs / mib)
+    };
+    let mut known: Vec<f64> = artifacts
+        .iter()
+        .filter_map(|artifact| cost_per_mib(artifact))
+        .collect();
+    known.sort_by(f64::total_cmp);
+    let median = known.get(known.len() / 2).copied().filter(|m| *m > 0.0);
+
+    artifacts
+        .iter()
+        .map(|artifact| {
+            let age = now
+                .duration_since(artifact.newest_mtime)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let weight = match (cost_per_mib(artifact), median) {
+                (Some(cost), Some(median)) => {
+                    (cost / median).clamp(1.0 / MAX_COST_WEIGHT, MAX_COST_WEIGHT)
+                }
+                _ => 1.0,
+            };
+            age / weight
+        })
+        .collect()
+}
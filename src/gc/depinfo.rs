@@ -0,0 +1,489 @@
+//! Rustc dep-info of the units of a profile directory, tying sources to the
+//! artifacts built from them.
+//!
+//! rustc writes the sources of every unit it compiles to
+//! `deps/<crate>-<hash>.d`, a makefile-style file listing workspace sources
+//! relative to the workspace root and everything else, such as registry
+//! sources, by absolute path. Cargo considers the unit stale when one of
+//! those sources is newer than the `dep-*` file in the unit's `.fingerprint`
+//! directory, so comparing the two tells, without building, which artifacts
+//! cargo will replace and which it will reuse.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::artifacts::{CrateArtifact, parse_crate_artifact_name, unit_directories};
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// The sources of the units of a profile directory.
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// use cargo_hold::gc::DepInfo;
+///
+/// let dep_info = DepInfo::load(Path::new("target/debug"), Path::new("."))?;
+/// for hash in dep_info.units_using(Path::new("src/lib.rs")) {
+///     println!("built from src/lib.rs: {hash}");
+/// }
+/// # Ok::<(), cargo_hold::error::HoldError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct DepInfo {
+    workspace_root: PathBuf,
+    /// Units by hash
+    units: HashMap<String, Unit>,
+    /// Hashes of the units built from each source
+    users: HashMap<PathBuf, Vec<String>>,
+}
+
+#[derive(Debug)]
+struct Unit {
+    /// Sources, relative to the workspace root for those inside it
+    sources: Vec<PathBuf>,
+    /// Package name and the mtime cargo compares the sources with, from the
+    /// unit's `.fingerprint` directory
+    fingerprint: Option<(String, SystemTime)>,
+}
+
+/// How the sources of a unit compare with its fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum InputState {
+    /// Neither dep-info nor fingerprint, the unit has no workspace sources,
+    /// or one of them is missing (for instance because the workspace root
+    /// is not where the unit was built from)
+    Unknown,
+    /// Every workspace source is older than the fingerprint: cargo reuses
+    /// the unit however old it is
+    Unchanged,
+    /// This workspace source, the newest, is newer than the fingerprint by
+    /// this much: cargo rebuilds the unit
+    Newer(PathBuf, Duration),
+}
+
+impl DepInfo {
+    /// Read the dep-info and fingerprints of `profile_dir`, resolving
+    /// relative sources against `workspace_root`.
+    ///
+    /// Unreadable dep-info files are skipped.
+    pub fn load(profile_dir: &Path, workspace_root: &Path) -> Result<Self> {
+        let mut dep_info = Self {
+            workspace_root: workspace_root.to_path_buf(),
+            ..Self::default()
+        };
+        let fingerprints = fingerprint_mtimes(&profile_dir.join(".fingerprint"))?;
+        let deps = profile_dir.join("deps");
+        let entries = match fs::read_dir(&deps) {
+            Ok(entries) => Some(entries),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(source) => return Err(HoldError::IoError { path: deps, source }),
+        };
+
+        for entry in entries.into_iter().flatten().filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "d") {
+                continue;
+            }
+            let Some((_, hash)) = parse_crate_artifact_name(&path) else {
+                continue;
+            };
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let fingerprint = fingerprints.get(&hash).cloned();
+            dep_info.insert(hash, parse_dep_info(&contents), fingerprint);
+        }
+
+        // The new build directory layout keeps the dep-info in the unit's
+        // `out` directory and its fingerprint in `fingerprint`
+        for (name, hash, unit_dir) in unit_directories(profile_dir)? {
+            let Ok(outputs) = fs::read_dir(unit_dir.join("out")) else {
+                continue;
+            };
+            let mut sources = Vec::new();
+            for output in outputs.filter_map(|entry| entry.ok()) {
+                let path = output.path();
+                if path.extension().is_some_and(|extension| extension == "d")
+                    && let Ok(contents) = fs::read_to_string(&path)
+                {
+                    sources.extend(parse_dep_info(&contents));
+                }
+            }
+            if sources.is_empty() {
+                continue;
+            }
+            sources.sort_unstable();
+            sources.dedup();
+            let fingerprint =
+                oldest_dep_file(&unit_dir.join("fingerprint")).map(|mtime| (name, mtime));
+            dep_info.insert(hash, sources, fingerprint);
+        }
+        Ok(dep_info)
+    }
+
+    fn insert(
+        &mut self,
+        hash: String,
+        sources: Vec<String>,
+        fingerprint: Option<(String, SystemTime)>,
+    ) {
+        let sources: Vec<PathBuf> = sources
+            .into_iter()
+            .map(|source| {
+                let source = PathBuf::from(source);
+                match source.strip_prefix(&self.workspace_root) {
+                    Ok(relative) => relative.to_path_buf(),
+                    Err(_) => source,
+                }
+            })
+            .collect();
+        for source in &sources {
+            self.users
+                .entry(source.clone())
+                .or_default()
+                .push(hash.clone());
+        }
+        self.units.insert(
+            hash,
+            Unit {
+                sources,
+                fingerprint,
+            },
+        );
+    }
+
+    /// Sources of the unit with `hash`, relative to the workspace root for
+    /// those inside it, or `None` without dep-info for the unit
+    pub fn sources(&self, hash: &str) -> Option<&[PathBuf]> {
+        self.units.get(hash).map(|unit| unit.sources.as_slice())
+    }
+
+    /// Hashes of the units built from `source`, given relative to the
+    /// workspace root for a workspace source
+    pub fn units_using(&self, source: &Path) -> impl Iterator<Item = &str> {
+        self.users
+            .get(source)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Hashes of the units with both dep-info and a fingerprint, with the
+    /// package name of the fingerprint
+    pub(crate) fn fingerprinted_units(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.units.iter().filter_map(|(hash, unit)| {
+            let (package, _) = unit.fingerprint.as_ref()?;
+            Some((hash.as_str(), package.as_str()))
+        })
+    }
+
+    /// How the workspace sources of the unit with `hash` compare with its
+    /// fingerprint
+    pub(crate) fn input_state(&self, hash: &str) -> InputState {
+        let Some(unit) = self.units.get(hash) else {
+            return InputState::Unknown;
+        };
+        let Some((_, fingerprint)) = unit.fingerprint else {
+            return InputState::Unknown;
+        };
+
+        let mut workspace_sources = 0;
+        let mut newest: Option<(&Path, Duration)> = None;
+        for source in unit.sources.iter().filter(|source| source.is_relative()) {
+            workspace_sources += 1;
+            let Ok(mtime) = fs::metadata(self.workspace_root.join(source))
+                .and_then(|metadata| metadata.modified())
+            else {
+                return InputState::Unknown;
+            };
+            let newer_by = mtime.duration_since(fingerprint).unwrap_or_default();
+            if !newer_by.is_zero() && newest.is_none_or(|(_, newest)| newer_by > newest) {
+                newest = Some((source, newer_by));
+            }
+        }
+        match newest {
+            Some((source, newer_by)) => InputState::Newer(source.to_path_buf(), newer_by),
+            None if workspace_sources > 0 => InputState::Unchanged,
+            None => InputState::Unknown,
+        }
+    }
+}
+
+/// Split off the crates cargo would rebuild because a workspace source is
+/// newer than their build, and mark the rest whose workspace sources are all
+/// older as having unchanged inputs.
+///
+/// Returns the stale crates and the remaining ones.
+pub(crate) fn split_stale(
+    dep_info: Option<&DepInfo>,
+    crate_artifacts: Vec<CrateArtifact>,
+    log: &Logger,
+) -> (Vec<CrateArtifact>, Vec<CrateArtifact>) {
+    let Some(dep_info) = dep_info else {
+        return (Vec::new(), crate_artifacts);
+    };
+
+    let mut stale = Vec::new();
+    let mut remaining = Vec::new();
+    let mut unchanged = 0;
+    for mut artifact in crate_artifacts {
+        match dep_info.input_state(&artifact.hash) {
+            InputState::Newer(source, _) => {
+                log.verbose(
+                    2,
+                    format!(
+                        "    Stale: {}-{} ({} changed)",
+                        artifact.name,
+                        artifact.hash,
+                        source.display()
+                    ),
+                );
+                stale.push(artifact);
+            }
+            InputState::Unchanged => {
+                artifact.inputs_unchanged = true;
+                unchanged += 1;
+                remaining.push(artifact);
+            }
+            InputState::Unknown => remaining.push(artifact),
+        }
+    }
+
+    log.verbose(
+        1,
+        format!(
+            "  Dep-info: {} crates stale, {unchanged} with unchanged sources",
+            stale.len()
+        ),
+    );
+    (stale, remaining)
+}
+
+/// Package name and the mtime of the `dep-*` file cargo compares sources
+/// against, of every fingerprint directory in `fingerprint_dir`, by hash
+fn fingerprint_mtimes(fingerprint_dir: &Path) -> Result<HashMap<String, (String, SystemTime)>> {
+    let mut fingerprints = HashMap::new();
+    let entries = match fs::read_dir(fingerprint_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(fingerprints),
+        Err(source) => {
+            return Err(HoldError::IoError {
+                path: fingerprint_dir.to_path_buf(),
+                source,
+            });
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Some((name, hash)) = parse_crate_artifact_name(&path) else {
+            continue;
+        };
+        if let Some(mtime) = oldest_dep_file(&path) {
+            fingerprints.insert(hash, (name, mtime));
+        }
+    }
+    Ok(fingerprints)
+}
+
+/// The mtime of the oldest `dep-*` file of a unit's fingerprint directory
+fn oldest_dep_file(fingerprint: &Path) -> Option<SystemTime> {
+    fs::read_dir(fingerprint)
+        .ok()?
+        .filter_map(|file| file.ok())
+        .filter(|file| file.file_name().to_string_lossy().starts_with("dep-"))
+        .filter_map(|file| {
+            file.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .min()
+}
+
+/// The prerequisites of the rules of a makefile-style dep-info file, with
+/// escaped spaces unescaped, sorted and deduplicated
+fn parse_dep_info(contents: &str) -> Vec<String> {
+    let mut sources = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        // The target ends at the first colon followed by a space or the end
+        // of the line, so drive letters and escaped colons stay in paths
+        let Some(colon) = line
+            .match_indices(':')
+            .map(|(index, _)| index)
+            .find(|&index| line[index + 1..].is_empty() || line[index + 1..].starts_with(' '))
+        else {
+            continue;
+        };
+        let mut source = String::new();
+        let mut chars = line[colon + 1..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // Only spaces are escaped; Windows paths keep their
+                // backslashes
+                '\\' if chars.as_str().starts_with(' ') => {
+                    source.push(' ');
+                    chars.next();
+                }
+                ' ' => {
+                    if !source.is_empty() {
+                        sources.push(std::mem::take(&mut source));
+                    }
+                }
+                _ => source.push(c),
+            }
+        }
+        if !source.is_empty() {
+            sources.push(source);
+        }
+    }
+    sources.sort_unstable();
+    sources.dedup();
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use filetime::FileTime;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef";
+
+    #[test]
+    fn test_parse_dep_info() {
+        let contents = [
+            "/t/debug/deps/foo-0123456789abcdef.d: src/lib.rs src/my\\ mod.rs \
+             /cargo/registry/src/serde/lib.rs",
+            "",
+            "src/lib.rs:",
+            "src/my\\ mod.rs:",
+            "",
+            "# env-dep:CARGO_PKG_NAME=foo",
+        ]
+        .join("\n");
+        assert_eq!(
+            parse_dep_info(&contents),
+            [
+                "/cargo/registry/src/serde/lib.rs",
+                "src/lib.rs",
+                "src/my mod.rs"
+            ]
+        );
+        assert_eq!(
+            parse_dep_info("C:\\t\\foo.d: C:\\ws\\src\\lib.rs\n"),
+            ["C:\\ws\\src\\lib.rs"]
+        );
+    }
+
+    #[test]
+    fn test_dep_info_maps_sources_and_compares_them_with_fingerprints() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path();
+        let profile = workspace.join("target/debug");
+        fs::create_dir_all(profile.join("deps")).unwrap();
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        let at = |secs: i64| FileTime::from_unix_time(1_700_000_000 + secs, 0);
+        for (name, secs) in [("lib.rs", 50), ("util.rs", 160), ("main.rs", 150)] {
+            fs::write(workspace.join("src").join(name), "").unwrap();
+            filetime::set_file_mtime(workspace.join("src").join(name), at(secs)).unwrap();
+        }
+        let unit = |name: &str, hash: &str, built_at: i64, sources: &str| {
+            let fingerprint = profile.join(format!(".fingerprint/{name}-{hash}"));
+            fs::create_dir_all(&fingerprint).unwrap();
+            let dep = fingerprint.join(format!("dep-lib-{name}"));
+            fs::write(&dep, "").unwrap();
+            filetime::set_file_mtime(&dep, at(built_at)).unwrap();
+            let d = profile.join(format!("deps/{name}-{hash}.d"));
+            fs::write(d, format!("x: {sources}\n")).unwrap();
+        };
+        // foo was built before util.rs changed, bar after main.rs did, and
+        // baz only uses registry sources
+        unit("foo", HASH, 100, "src/lib.rs src/util.rs");
+        let absolute_main = workspace.join("src/main.rs");
+        unit(
+            "bar",
+            "fedcba9876543210",
+            200,
+            &format!("src/lib.rs {}", absolute_main.display()),
+        );
+        unit("baz", "1111111111111111", 0, "/cargo/registry/baz/lib.rs");
+        unit("qux", "2222222222222222", 0, "src/gone.rs");
+
+        let dep_info = DepInfo::load(&profile, workspace).unwrap();
+        assert_eq!(
+            dep_info.input_state(HASH),
+            InputState::Newer(PathBuf::from("src/util.rs"), Duration::from_secs(60))
+        );
+        assert_eq!(
+            dep_info.input_state("fedcba9876543210"),
+            InputState::Unchanged
+        );
+        assert_eq!(
+            dep_info.input_state("1111111111111111"),
+            InputState::Unknown
+        );
+        assert_eq!(
+            dep_info.input_state("2222222222222222"),
+            InputState::Unknown
+        );
+        assert_eq!(
+            dep_info.input_state("3333333333333333"),
+            InputState::Unknown
+        );
+
+        // Absolute paths inside the workspace are made relative
+        let mut users: Vec<_> = dep_info.units_using(Path::new("src/lib.rs")).collect();
+        users.sort_unstable();
+        assert_eq!(users, ["0123456789abcdef", "fedcba9876543210"]);
+        assert_eq!(
+            dep_info
+                .units_using(Path::new("src/main.rs"))
+                .collect::<Vec<_>>(),
+            ["fedcba9876543210"]
+        );
+        assert_eq!(
+            dep_info.sources(HASH).unwrap(),
+            [PathBuf::from("src/lib.rs"), PathBuf::from("src/util.rs")]
+        );
+    }
+
+    #[test]
+    fn test_dep_info_of_the_new_build_dir_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path();
+        let profile = workspace.join("build-dir/debug");
+        let unit = profile.join(format!("build/foo/{HASH}"));
+        fs::create_dir_all(unit.join("fingerprint")).unwrap();
+        fs::create_dir_all(unit.join("out")).unwrap();
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/lib.rs"), "").unwrap();
+        fs::write(unit.join("fingerprint/dep-lib-foo"), "").unwrap();
+        fs::write(unit.join("out/foo.d"), "x: src/lib.rs\n").unwrap();
+        filetime::set_file_mtime(
+            workspace.join("src/lib.rs"),
+            FileTime::from_unix_time(1_700_000_100, 0),
+        )
+        .unwrap();
+        filetime::set_file_mtime(
+            unit.join("fingerprint/dep-lib-foo"),
+            FileTime::from_unix_time(1_700_000_000, 0),
+        )
+        .unwrap();
+
+        let dep_info = DepInfo::load(&profile, workspace).unwrap();
+        assert_eq!(
+            dep_info.input_state(HASH),
+            InputState::Newer(PathBuf::from("src/lib.rs"), Duration::from_secs(100))
+        );
+        assert_eq!(
+            dep_info.fingerprinted_units().collect::<Vec<_>>(),
+            [(HASH, "foo")]
+        );
+    }
+}
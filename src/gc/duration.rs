@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use crate::error::{HoldError, Result};
+
+/// Parse a duration string like "90s", "30m", "2h", "1d" into a [`Duration`]
+pub(crate) fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+
+    // Try to parse as raw seconds first
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let split_pos = s
+        .char_indices()
+        .find(|(_, ch)| ch.is_alphabetic())
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    let (num_part, suffix) = s.split_at(split_pos);
+    if num_part.is_empty() {
+        return Err(HoldError::InvalidDuration(
+            s.to_string(),
+            "No number found".to_string(),
+        ));
+    }
+
+    let multiplier = match suffix.to_lowercase().as_str() {
+        "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => 60,
+        "h" | "hr" | "hrs" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(HoldError::InvalidDuration(
+                s.to_string(),
+                format!("Unknown duration suffix: {suffix}"),
+            ));
+        }
+    };
+
+    let base: f64 = num_part.trim().parse().map_err(|_| {
+        HoldError::InvalidDuration(s.to_string(), "Invalid number format".to_string())
+    })?;
+    if !base.is_finite() || base < 0.0 {
+        return Err(HoldError::InvalidDuration(
+            s.to_string(),
+            "Duration must be a non-negative number".to_string(),
+        ));
+    }
+
+    Ok(Duration::from_secs_f64(base * multiplier as f64))
+}
+
+/// Format a duration in a compact human-readable form
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        s if s >= 24 * 60 * 60 && s % (24 * 60 * 60) == 0 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 && s % (60 * 60) == 0 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
+/// Format an age in its coarsest whole unit, e.g. "3d" for 3 days and 5 hours
+pub(crate) fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    for unit in [24 * 60 * 60, 60 * 60, 60] {
+        if secs >= unit {
+            return format_duration(Duration::from_secs(secs / unit * unit));
+        }
+    }
+    format_duration(age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("3d").unwrap(), Duration::from_secs(259_200));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(604_800));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5y").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(300)), "5m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(172_800)), "2d");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "90m");
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(45)), "45s");
+        assert_eq!(format_age(Duration::from_secs(5400)), "1h");
+        assert_eq!(format_age(Duration::from_secs(3 * 86_400 + 5 * 3600)), "3d");
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::{HoldError, Result};
+
+/// Restricts garbage collection to subtrees of the target directory matching
+/// one of a set of glob patterns.
+///
+/// Relative patterns start with the target directory's own name (e.g.
+/// `target/debug` or `target/**/build`); absolute patterns are matched against
+/// absolute paths. A path is in scope when it, or any of its ancestors inside
+/// the target directory, matches a pattern.
+///
+/// Supported syntax: `*` (any characters except `/`), `?` (one character
+/// except `/`), and `**` (any number of directories).
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    target_dir: PathBuf,
+    patterns: Vec<(String, Regex)>,
+}
+
+impl PathFilter {
+    /// Create a filter for `target_dir` from glob patterns.
+    pub fn new(target_dir: &Path, globs: &[String]) -> Result<Self> {
+        let patterns = globs
+            .iter()
+            .map(|glob| Ok((glob.clone(), glob_to_regex(glob)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            target_dir: target_dir.to_path_buf(),
+            patterns,
+        })
+    }
+
+    /// The glob patterns this filter was built from
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|(glob, _)| glob.as_str())
+    }
+
+    /// Check whether `path` (inside the target directory) is in scope.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.target_dir) else {
+            return false;
+        };
+        let target_name = self
+            .target_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut relative_key = target_name;
+        let mut absolute = self.target_dir.clone();
+        if self.is_match(&relative_key, &absolute) {
+            return true;
+        }
+
+        for component in relative.components() {
+            let Component::Normal(part) = component else {
+                continue;
+            };
+            relative_key.push('/');
+            relative_key.push_str(&part.to_string_lossy());
+            absolute.push(part);
+            if self.is_match(&relative_key, &absolute) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn is_match(&self, relative_key: &str, absolute: &Path) -> bool {
+        let absolute_key = absolute.to_string_lossy().replace('\\', "/");
+        self.patterns.iter().any(|(glob, regex)| {
+            if Path::new(glob).is_absolute() {
+                regex.is_match(&absolute_key)
+            } else {
+                regex.is_match(relative_key)
+            }
+        })
+    }
+}
+
+/// Translate a glob into an anchored regular expression.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let trimmed = glob.trim().trim_start_matches("./").trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Err(HoldError::InvalidPathFilter(
+            glob.to_string(),
+            "Pattern is empty".to_string(),
+        ));
+    }
+
+    let mut pattern = String::from("^");
+    let mut chars = trimmed.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // `**/` matches zero or more leading directories
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            other => pattern.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    Regex::new(&pattern).map_err(|e| HoldError::InvalidPathFilter(glob.to_string(), e.to_string()))
+}
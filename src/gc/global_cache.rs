@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OpenFlags, params};
+
+use crate::error::{HoldError, Result};
+
+/// Name of cargo's global cache tracking database in the cargo home
+pub(crate) const GLOBAL_CACHE_DB: &str = ".global-cache";
+
+/// How long to wait for cargo to release the tracking database
+const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether cargo's global cache tracking database is used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GlobalCacheMode {
+    /// Use the database when it exists (cargo 1.78+), fall back to file
+    /// modification times otherwise
+    #[default]
+    Auto,
+    /// Ignore the database and always use file modification times
+    Off,
+}
+
+impl FromStr for GlobalCacheMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "off" => Ok(Self::Off),
+            _ => Err(HoldError::InvalidGlobalCacheMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for GlobalCacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Off => f.write_str("off"),
+        }
+    }
+}
+
+/// Cargo's global cache tracking database (`$CARGO_HOME/.global-cache`).
+///
+/// Since 1.78 cargo records when each registry index, downloaded `.crate`,
+/// extracted source directory, git database, and git checkout was last used,
+/// and its own automatic GC works from those records. cargo-hold reads the
+/// same records instead of guessing from file modification times (which
+/// cache restores and extraction tend to reset), and deletes the rows of
+/// whatever it removes so cargo's bookkeeping matches the disk.
+pub(crate) struct GlobalCache {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl GlobalCache {
+    /// Open the database in `cargo_home`, or `None` if cargo has not created
+    /// one (cargo older than 1.78)
+    pub(crate) fn open(cargo_home: &Path) -> Result<Option<Self>> {
+        let path = cargo_home.join(GLOBAL_CACHE_DB);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .and_then(|conn| {
+                conn.busy_timeout(DB_BUSY_TIMEOUT)?;
+                // Cargo relies on cascading deletes, which SQLite only
+                // performs with foreign keys enabled
+                conn.pragma_update(None, "foreign_keys", true)?;
+                Ok(conn)
+            })
+            .map_err(|source| HoldError::GlobalCacheError {
+                path: path.clone(),
+                source,
+            })?;
+
+        Ok(Some(Self { path, conn }))
+    }
+
+    /// Read every last-use record
+    pub(crate) fn last_uses(&self, cargo_home: &Path) -> Result<LastUses> {
+        let mut last_uses = LastUses {
+            cargo_home: cargo_home.to_path_buf(),
+            ..LastUses::default()
+        };
+
+        self.query(
+            "SELECT registry_index.name, registry_crate.name, registry_crate.timestamp
+             FROM registry_crate
+             JOIN registry_index ON registry_crate.registry_id = registry_index.id",
+            |parent, name, timestamp| {
+                last_uses
+                    .registry_crates
+                    .insert((parent.unwrap_or_default(), name), timestamp);
+            },
+        )?;
+        self.query(
+            "SELECT registry_index.name, registry_src.name, registry_src.timestamp
+             FROM registry_src
+             JOIN registry_index ON registry_src.registry_id = registry_index.id",
+            |parent, name, timestamp| {
+                last_uses
+                    .registry_srcs
+                    .insert((parent.unwrap_or_default(), name), timestamp);
+            },
+        )?;
+        self.query(
+            "SELECT NULL, name, timestamp FROM git_db",
+            |_, name, timestamp| {
+                last_uses.git_dbs.insert(name, timestamp);
+            },
+        )?;
+        self.query(
+            "SELECT git_db.name, git_checkout.name, git_checkout.timestamp
+             FROM git_checkout
+             JOIN git_db ON git_checkout.git_id = git_db.id",
+            |parent, name, timestamp| {
+                last_uses
+                    .git_checkouts
+                    .insert((parent.unwrap_or_default(), name), timestamp);
+            },
+        )?;
+
+        Ok(last_uses)
+    }
+
+    /// Delete the records of removed cache entries
+    pub(crate) fn forget(&mut self, removed: &[CacheEntry]) -> Result<()> {
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.path.clone();
+        let to_error = |source| HoldError::GlobalCacheError {
+            path: path.clone(),
+            source,
+        };
+
+        let tx = self.conn.transaction().map_err(to_error)?;
+        for entry in removed {
+            match entry {
+                CacheEntry::RegistryCrate { index, name } => tx.execute(
+                    "DELETE FROM registry_crate WHERE name = ?2 AND registry_id = (SELECT id FROM \
+                     registry_index WHERE name = ?1)",
+                    params![index, name],
+                ),
+                CacheEntry::RegistrySrc { index, name } => tx.execute(
+                    "DELETE FROM registry_src WHERE name = ?2 AND registry_id = (SELECT id FROM \
+                     registry_index WHERE name = ?1)",
+                    params![index, name],
+                ),
+                // Its checkouts go with it (ON DELETE CASCADE)
+                CacheEntry::GitDb { name } => {
+                    tx.execute("DELETE FROM git_db WHERE name = ?1", params![name])
+                }
+                CacheEntry::GitCheckout { repository, name } => tx.execute(
+                    "DELETE FROM git_checkout WHERE name = ?2 AND git_id = (SELECT id FROM git_db \
+                     WHERE name = ?1)",
+                    params![repository, name],
+                ),
+                CacheEntry::GitRepository { repository } => tx.execute(
+                    "DELETE FROM git_checkout WHERE git_id = (SELECT id FROM git_db WHERE name = \
+                     ?1)",
+                    params![repository],
+                ),
+            }
+            .map_err(to_error)?;
+        }
+        tx.commit().map_err(to_error)
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        mut row: impl FnMut(Option<String>, String, SystemTime),
+    ) -> Result<()> {
+        let to_error = |source| HoldError::GlobalCacheError {
+            path: self.path.clone(),
+            source,
+        };
+
+        let mut statement = self.conn.prepare(sql).map_err(to_error)?;
+        let mut rows = statement.query([]).map_err(to_error)?;
+        while let Some(record) = rows.next().map_err(to_error)? {
+            let parent: Option<String> = record.get(0).map_err(to_error)?;
+            let name: String = record.get(1).map_err(to_error)?;
+            let timestamp: i64 = record.get(2).map_err(to_error)?;
+            row(
+                parent,
+                name,
+                UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A cargo home entry tracked by the global cache database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CacheEntry {
+    /// `registry/cache/<index>/<name>.crate`
+    RegistryCrate { index: String, name: String },
+    /// `registry/src/<index>/<name>`
+    RegistrySrc { index: String, name: String },
+    /// `git/db/<name>`
+    GitDb { name: String },
+    /// `git/checkouts/<repository>/<name>`
+    GitCheckout { repository: String, name: String },
+    /// `git/checkouts/<repository>`, with all of its checkouts
+    GitRepository { repository: String },
+}
+
+impl CacheEntry {
+    /// Identify a cargo home path, or `None` if it is not a tracked entry
+    pub(crate) fn from_path(cargo_home: &Path, path: &Path) -> Option<Self> {
+        let relative = path.strip_prefix(cargo_home).ok()?;
+        let parts = relative
+            .components()
+            .map(|component| match component {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let entry = match parts.as_slice() {
+            ["registry", "cache", index, name] => Self::RegistryCrate {
+                index: index.to_string(),
+                name: name.to_string(),
+            },
+            ["registry", "src", index, name] => Self::RegistrySrc {
+                index: index.to_string(),
+                name: name.to_string(),
+            },
+            ["git", "db", name] => Self::GitDb {
+                name: name.to_string(),
+            },
+            ["git", "checkouts", repository, name] => Self::GitCheckout {
+                repository: repository.to_string(),
+                name: name.to_string(),
+            },
+            ["git", "checkouts", repository] => Self::GitRepository {
+                repository: repository.to_string(),
+            },
+            _ => return None,
+        };
+        Some(entry)
+    }
+}
+
+/// Last-use times read from the global cache database, plus the entries
+/// removed during this run
+#[derive(Debug, Default)]
+pub(crate) struct LastUses {
+    cargo_home: PathBuf,
+    registry_crates: HashMap<(String, String), SystemTime>,
+    registry_srcs: HashMap<(String, String), SystemTime>,
+    git_dbs: HashMap<String, SystemTime>,
+    git_checkouts: HashMap<(String, String), SystemTime>,
+    removed: Mutex<Vec<CacheEntry>>,
+}
+
+impl LastUses {
+    /// When cargo last used the entry at `path`, if it is tracked.
+    ///
+    /// A git checkouts directory was last used when its most recent checkout
+    /// was.
+    pub(crate) fn last_use(&self, path: &Path) -> Option<SystemTime> {
+        match CacheEntry::from_path(&self.cargo_home, path)? {
+            CacheEntry::RegistryCrate { index, name } => {
+                self.registry_crates.get(&(index, name)).copied()
+            }
+            CacheEntry::RegistrySrc { index, name } => {
+                self.registry_srcs.get(&(index, name)).copied()
+            }
+            CacheEntry::GitDb { name } => self.git_dbs.get(&name).copied(),
+            CacheEntry::GitCheckout { repository, name } => {
+                self.git_checkouts.get(&(repository, name)).copied()
+            }
+            CacheEntry::GitRepository { repository } => self
+                .git_checkouts
+                .iter()
+                .filter(|((parent, _), _)| *parent == repository)
+                .map(|(_, timestamp)| *timestamp)
+                .max(),
+        }
+    }
+
+    /// Record that the entry at `path` was removed
+    pub(crate) fn removed(&self, path: &Path) {
+        if let Some(entry) = CacheEntry::from_path(&self.cargo_home, path) {
+            self.removed
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(entry);
+        }
+    }
+
+    /// Entries removed so far
+    pub(crate) fn take_removed(&self) -> Vec<CacheEntry> {
+        std::mem::take(
+            &mut *self
+                .removed
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// The tables of cargo's tracking database that cargo-hold uses
+    const SCHEMA: &str = "
+        CREATE TABLE registry_index (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT \
+                          NULL, timestamp INTEGER NOT NULL);
+        CREATE TABLE registry_crate (registry_id INTEGER NOT NULL, name TEXT NOT NULL, size \
+                          INTEGER NOT NULL, timestamp INTEGER NOT NULL, PRIMARY KEY (registry_id, \
+                          name), FOREIGN KEY (registry_id) REFERENCES registry_index (id) ON \
+                          DELETE CASCADE);
+        CREATE TABLE registry_src (registry_id INTEGER NOT NULL, name TEXT NOT NULL, size INTEGER, \
+                          timestamp INTEGER NOT NULL, PRIMARY KEY (registry_id, name), FOREIGN \
+                          KEY (registry_id) REFERENCES registry_index (id) ON DELETE CASCADE);
+        CREATE TABLE git_db (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, \
+                          timestamp INTEGER NOT NULL);
+        CREATE TABLE git_checkout (git_id INTEGER NOT NULL, name TEXT NOT NULL, size INTEGER, \
+                          timestamp INTEGER NOT NULL, PRIMARY KEY (git_id, name), FOREIGN KEY \
+                          (git_id) REFERENCES git_db (id) ON DELETE CASCADE);
+        INSERT INTO registry_index VALUES (1, 'index.crates.io-1949cf8c6b5b557f', 300);
+        INSERT INTO registry_crate VALUES (1, 'serde-1.0.200.crate', 10, 100);
+        INSERT INTO registry_src VALUES (1, 'serde-1.0.200', 20, 200);
+        INSERT INTO git_db VALUES (1, 'anchor-4f2d1c0b9a8e7d6c', 400);
+        INSERT INTO git_checkout VALUES (1, '8a5f1e3', 30, 500);
+        INSERT INTO git_checkout VALUES (1, '0123abc', 30, 600);
+    ";
+
+    fn cargo_home_with_db() -> TempDir {
+        let cargo_home = TempDir::new().unwrap();
+        Connection::open(cargo_home.path().join(GLOBAL_CACHE_DB))
+            .unwrap()
+            .execute_batch(SCHEMA)
+            .unwrap();
+        cargo_home
+    }
+
+    fn secs(secs: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    fn count(cargo_home: &Path, table: &str) -> i64 {
+        Connection::open(cargo_home.join(GLOBAL_CACHE_DB))
+            .unwrap()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_missing_database() {
+        let cargo_home = TempDir::new().unwrap();
+        assert!(GlobalCache::open(cargo_home.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_last_uses_by_path() {
+        let cargo_home = cargo_home_with_db();
+        let home = cargo_home.path();
+        let db = GlobalCache::open(home).unwrap().unwrap();
+        let last_uses = db.last_uses(home).unwrap();
+
+        let registry = home.join("registry");
+        let index = "index.crates.io-1949cf8c6b5b557f";
+        assert_eq!(
+            last_uses.last_use(
+                &registry
+                    .join("cache")
+                    .join(index)
+                    .join("serde-1.0.200.crate")
+            ),
+            secs(100)
+        );
+        assert_eq!(
+            last_uses.last_use(&registry.join("src").join(index).join("serde-1.0.200")),
+            secs(200)
+        );
+        assert_eq!(
+            last_uses.last_use(&registry.join("src").join(index).join("untracked-1.0.0")),
+            None
+        );
+
+        let git = home.join("git");
+        let repository = "anchor-4f2d1c0b9a8e7d6c";
+        assert_eq!(
+            last_uses.last_use(&git.join("db").join(repository)),
+            secs(400)
+        );
+        assert_eq!(
+            last_uses.last_use(&git.join("checkouts").join(repository).join("8a5f1e3")),
+            secs(500)
+        );
+        // A repository's checkouts directory is as recent as its newest
+        // checkout
+        assert_eq!(
+            last_uses.last_use(&git.join("checkouts").join(repository)),
+            secs(600)
+        );
+
+        assert_eq!(
+            last_uses.last_use(&home.join("bin").join("cargo-hold")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_forget_removed_entries() {
+        let cargo_home = cargo_home_with_db();
+        let home = cargo_home.path();
+        let mut db = GlobalCache::open(home).unwrap().unwrap();
+        let last_uses = db.last_uses(home).unwrap();
+
+        let index = "index.crates.io-1949cf8c6b5b557f";
+        last_uses.removed(
+            &home
+                .join("registry")
+                .join("cache")
+                .join(index)
+                .join("serde-1.0.200.crate"),
+        );
+        last_uses.removed(&home.join("git").join("db").join("anchor-4f2d1c0b9a8e7d6c"));
+        db.forget(&last_uses.take_removed()).unwrap();
+
+        assert_eq!(count(home, "registry_crate"), 0);
+        assert_eq!(count(home, "registry_src"), 1);
+        assert_eq!(count(home, "registry_index"), 1);
+        assert_eq!(count(home, "git_db"), 0);
+        assert_eq!(count(home, "git_checkout"), 0);
+        assert!(last_uses.take_removed().is_empty());
+    }
+
+    #[test]
+    fn test_parse_global_cache_mode() {
+        assert_eq!(
+            "auto".parse::<GlobalCacheMode>().unwrap(),
+            GlobalCacheMode::Auto
+        );
+        assert_eq!(
+            " OFF ".parse::<GlobalCacheMode>().unwrap(),
+            GlobalCacheMode::Off
+        );
+        assert!("on".parse::<GlobalCacheMode>().is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{HoldError, Result};
+
+/// Git dependency revisions referenced by a set of `Cargo.lock` files.
+///
+/// Cargo stores git dependencies under `git/db/<name>-<url hash>` and
+/// `git/checkouts/<name>-<url hash>/<short revision>`, where `<name>` is the
+/// last path segment of the repository URL. The URL hash is an internal cargo
+/// detail, so repositories are matched by name, and checkouts by revision
+/// prefix.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct GitRefs {
+    /// Full commit hashes keyed by repository name
+    revisions: HashMap<String, HashSet<String>>,
+}
+
+impl GitRefs {
+    /// Collect git revisions from every lockfile in `paths`
+    pub(crate) fn from_lockfiles(paths: &[PathBuf]) -> Result<Self> {
+        let mut refs = Self::default();
+        for path in paths {
+            let content = fs::read_to_string(path).map_err(|source| HoldError::IoError {
+                path: path.clone(),
+                source,
+            })?;
+            refs.extend_from_lockfile(&content);
+        }
+        Ok(refs)
+    }
+
+    /// Add the git sources of one `Cargo.lock` document
+    pub(crate) fn extend_from_lockfile(&mut self, content: &str) {
+        for line in content.lines() {
+            let Some(source) = line
+                .trim()
+                .strip_prefix("source = \"git+")
+                .and_then(|rest| rest.strip_suffix('"'))
+            else {
+                continue;
+            };
+            let Some((url, revision)) = source.rsplit_once('#') else {
+                continue;
+            };
+
+            self.revisions
+                .entry(repository_name(url))
+                .or_default()
+                .insert(revision.to_lowercase());
+        }
+    }
+
+    /// Whether no git dependencies were found
+    pub(crate) fn is_empty(&self) -> bool {
+        self.revisions.is_empty()
+    }
+
+    /// Whether a `git/db` or `git/checkouts` entry (`<name>-<url hash>`)
+    /// belongs to a referenced repository
+    pub(crate) fn references_repository(&self, dir: &Path) -> bool {
+        self.revisions_for(dir).is_some()
+    }
+
+    /// Whether a checkout directory (`<name>-<url hash>/<short revision>`)
+    /// holds a referenced revision
+    pub(crate) fn references_checkout(&self, checkout: &Path) -> bool {
+        let (Some(repository), Some(short_id)) = (
+            checkout.parent(),
+            checkout.file_name().and_then(|name| name.to_str()),
+        ) else {
+            return false;
+        };
+        let short_id = short_id.to_lowercase();
+
+        self.revisions_for(repository).is_some_and(|revisions| {
+            revisions
+                .iter()
+                .any(|revision| revision.starts_with(&short_id))
+        })
+    }
+
+    fn revisions_for(&self, dir: &Path) -> Option<&HashSet<String>> {
+        let name = dir.file_name()?.to_str()?;
+        let (repository, _url_hash) = name.rsplit_once('-')?;
+        self.revisions.get(repository)
+    }
+}
+
+/// Cargo's name for a git repository: the last path segment of its URL
+/// without a `.git` suffix
+fn repository_name(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    // Drop the scheme and host so only the URL path is considered
+    let path = url.split_once("://").map_or(url, |(_, rest)| {
+        rest.split_once('/').map_or("", |(_, path)| path)
+    });
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git");
+
+    if name.is_empty() {
+        "_empty".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKFILE: &str = r#"
+version = 4
+
+[[package]]
+name = "anchor-lang"
+version = "0.30.1"
+source = "git+https://github.com/coral-xyz/anchor?branch=master#8a5f1e3b9c0d2e4f6a7b8c9d0e1f2a3b4c5d6e7f"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "solana-program"
+version = "2.0.0"
+source = "git+https://github.com/anza-xyz/agave.git?rev=0123abc#0123abcdef0123abcdef0123abcdef0123abcdef"
+"#;
+
+    #[test]
+    fn test_git_refs_from_lockfile() {
+        let mut refs = GitRefs::default();
+        refs.extend_from_lockfile(LOCKFILE);
+
+        assert!(refs.references_repository(Path::new("db/anchor-4f2d1c0b9a8e7d6c")));
+        assert!(refs.references_repository(Path::new("db/agave-0011223344556677")));
+        assert!(!refs.references_repository(Path::new("db/serde-0011223344556677")));
+
+        assert!(refs.references_checkout(Path::new("checkouts/anchor-4f2d1c0b9a8e7d6c/8a5f1e3")));
+        assert!(!refs.references_checkout(Path::new("checkouts/anchor-4f2d1c0b9a8e7d6c/1111111")));
+        assert!(refs.references_checkout(Path::new("checkouts/agave-0011223344556677/0123abc")));
+    }
+
+    #[test]
+    fn test_repository_name() {
+        assert_eq!(repository_name("https://github.com/foo/bar"), "bar");
+        assert_eq!(repository_name("https://github.com/foo/bar.git"), "bar");
+        assert_eq!(
+            repository_name("https://github.com/foo/bar/?branch=x"),
+            "bar"
+        );
+        assert_eq!(repository_name("file:///"), "_empty");
+        assert_eq!(repository_name("https://example.com"), "_empty");
+    }
+}
@@ -3,15 +3,43 @@
 //! This module provides functionality to clean up old build artifacts from:
 //! - Target directories (build artifacts)
 //! - `~/.cargo/registry/cache` (downloaded crates)
-//! - `~/.cargo/git/checkouts` (git dependencies)
+//! - `~/.cargo/git/checkouts` (git dependencies, keeping revisions pinned by
+//!   lockfiles)
 //! - `~/.cargo/bin` (installed binaries)
 //!
+//! Cargo home cleanup holds cargo's package cache lock and, with cargo 1.78+,
+//! follows the last-use records of cargo's global cache tracking database,
+//! removing the records of whatever it deletes.
+//!
+//! The scanning and measuring behind garbage collection is public for tools
+//! that inspect target directories: [`find_profile_directories`] locates the
+//! profile directories, `collect_crate_artifacts` groups their contents by
+//! crate into `CrateArtifact`s, and `calculate_disk_usage`, [`format_size`],
+//! and [`parse_size`] measure and print sizes.
+//!
+//! [`find_named_profile_directories`] also finds custom profiles, such as
+//! `coverage` or `release-lto`, nested at any depth.
+//!
+//! Only the size types and helpers and the profile directory lookups are
+//! available without the `gc` cargo feature.
+//!
 //! # Features
 //!
 //! - Size-based cleanup: Remove artifacts when directory exceeds size limit
-//! - Age-based cleanup: Remove artifacts older than threshold
+//! - Age-based cleanup: Remove artifacts older than threshold, configurable per
+//!   area (deps, incremental, registry, git, bin)
 //! - Smart grouping: Removes all related artifacts together (by crate)
 //! - Preservation rules: Always keeps important files and recent artifacts
+//! - Policy hook: An external command can keep, evict, or rank artifacts
+//! - Dep-info: Crates built from since changed sources are evicted, and those
+//!   whose sources are unchanged outlive the age threshold
+//! - Simulation: Candidate policies can be compared in dry runs side by side
+//! - Pins: Crates pinned by name or glob are never evicted
+//! - Tool outputs: Coverage data, `cargo nextest` runs, `trybuild` projects,
+//!   and pending `insta` snapshots are removed whole under their own age
+//!   thresholds
+//! - Cold tier: Evicted groups can be compressed in place first and thawed
+//!   later
 //! - Parallel processing: Uses rayon for efficient directory scanning
 //!
 //! # Example
@@ -35,14 +63,116 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+#[cfg(feature = "gc")]
+mod age;
+#[cfg(feature = "gc")]
 mod artifacts;
+#[cfg(feature = "gc")]
+mod audit;
+#[cfg(feature = "gc")]
 pub(crate) mod auto_cap;
+#[cfg(feature = "gc")]
+mod busy;
+#[cfg(feature = "gc")]
 mod cargo;
+#[cfg(feature = "gc")]
 mod cleanup;
+#[cfg(feature = "gc")]
+mod cold;
+#[cfg(feature = "gc")]
 pub mod config;
+#[cfg(feature = "gc")]
+mod containment;
+#[cfg(feature = "gc")]
+mod costs;
+#[cfg(feature = "gc")]
+mod depinfo;
+#[cfg_attr(not(feature = "gc"), allow(dead_code))]
+mod duration;
+#[cfg(feature = "gc")]
+mod filter;
+#[cfg(feature = "gc")]
+mod global_cache;
+#[cfg(feature = "gc")]
+mod lockfile;
+#[cfg(feature = "gc")]
+mod outputs;
+#[cfg(feature = "gc")]
+mod pins;
+#[cfg(feature = "gc")]
+mod policy;
+#[cfg(feature = "gc")]
+mod preflight;
+mod profiles;
+#[cfg(feature = "gc")]
+mod removals;
+#[cfg_attr(not(feature = "gc"), allow(dead_code))]
+mod runner;
+#[cfg(feature = "gc")]
+mod sandbox;
+#[cfg(feature = "gc")]
+mod simulate;
+#[cfg_attr(not(feature = "gc"), allow(dead_code))]
 mod size;
-#[cfg(test)]
+#[cfg(feature = "gc")]
+mod size_index;
+#[cfg(all(test, feature = "gc"))]
 mod tests;
+#[cfg(feature = "gc")]
+pub mod units;
 
-pub(crate) use cleanup::calculate_directory_size;
-pub(crate) use size::{format_size, parse_size};
+#[cfg(feature = "gc")]
+pub use age::GcArea;
+#[cfg(feature = "gc")]
+pub(crate) use age::parse_area_threshold;
+#[cfg(feature = "gc")]
+pub use artifacts::{
+    ArtifactInfo, CrateArtifact, collect_crate_artifacts, parse_crate_artifact_name,
+};
+#[cfg(all(feature = "gc", feature = "bench"))]
+pub(crate) use artifacts::{Budget, PreservePolicy, select_artifacts_for_removal};
+#[cfg(feature = "gc")]
+pub use audit::AuditLogFormat;
+#[cfg(feature = "gc")]
+pub(crate) use audit::append_audit_log;
+#[cfg(feature = "gc")]
+pub use busy::BusyPolicy;
+#[cfg(feature = "gc")]
+pub use cleanup::{calculate_directory_size, calculate_disk_usage};
+#[cfg(feature = "gc")]
+pub(crate) use cleanup::{clean_named_crate, profile_artifact_mtimes};
+#[cfg(feature = "gc")]
+pub(crate) use cold::ColdManifest;
+#[cfg(feature = "gc")]
+pub use costs::BuildCosts;
+#[cfg(feature = "gc")]
+pub use depinfo::DepInfo;
+#[cfg(feature = "gc")]
+pub(crate) use depinfo::InputState;
+pub(crate) use duration::{format_age, format_duration, parse_duration};
+#[cfg(feature = "gc")]
+pub use filter::PathFilter;
+#[cfg(feature = "gc")]
+pub use global_cache::GlobalCacheMode;
+#[cfg(feature = "gc")]
+pub use pins::PinnedCrates;
+#[cfg(feature = "gc")]
+pub(crate) use pins::validate_pin_pattern;
+#[cfg(feature = "gc")]
+pub use policy::PolicyCommand;
+#[cfg(all(test, feature = "gc"))]
+pub(crate) use policy::policy_input_line;
+#[cfg(feature = "gc")]
+pub(crate) use profiles::declared_profile_names;
+pub use profiles::{find_named_profile_directories, find_profile_directories};
+#[cfg(feature = "gc")]
+pub(crate) use removals::write_removal_list;
+#[cfg(feature = "gc")]
+pub use removals::{GcPhase, RemovalListFormat, RemovalReason, RemovedPath};
+pub use runner::{EphemeralMode, Runner};
+#[cfg(feature = "gc")]
+pub use sandbox::SandboxMode;
+#[cfg(feature = "gc")]
+pub use simulate::SimulatedPolicy;
+pub use size::{DiskSizes, SizeMode, SizeUnits, SymlinkPolicy, format_size, parse_size};
+pub(crate) use size::{compressing_filesystem, parse_max_target_size};
@@ -0,0 +1,372 @@
+//! Outputs of tools that run in the target directory: coverage data
+//! (`*.profraw`, `*.profdata`, and `cargo llvm-cov`'s target directory),
+//! `cargo nextest` run stores, `trybuild` test projects, stray `insta`
+//! snapshots, and the web builds of `dx`, `wasm-bindgen`, and `trunk`.
+//!
+//! These match none of the crate grouping rules, so they are cleaned as whole
+//! files and directories, each tool under the age threshold of its own area
+//! (or kept with `AREA=keep`); coverage data also under
+//! `--max-coverage-size`.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{fmt, fs};
+
+use super::age::{GcArea, KEEP};
+use super::cleanup::calculate_disk_usage;
+use super::config::Gc;
+use super::removals::{GcPhase, RemovalReason};
+use super::size::DiskSizes;
+use crate::error::{HoldError, Result};
+
+/// Directories with many files and never any tool output
+const SKIPPED_DIRS: [&str; 3] = ["deps", ".fingerprint", "incremental"];
+
+/// A tool whose outputs are cleaned apart from the crate artifacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolOutput {
+    /// `*.profraw` and `*.profdata` files, and the entries of
+    /// `llvm-cov-target`
+    Coverage,
+    /// The runs of each profile in `nextest/<profile>/`
+    Nextest,
+    /// The test projects of `trybuild` in `tests/trybuild/` (or `tests/` for
+    /// older versions)
+    Trybuild,
+    /// Snapshots `insta` left for review: `*.snap.new` and `*.pending-snap`
+    Snapshots,
+    /// The builds and bundles of each app in `dx/<app>/`
+    Dx,
+    /// The bindings of each profile in `wasm-bindgen/<profile>/`
+    WasmBindgen,
+    /// The entries of `trunk/` and the `.stage` directories `trunk` builds
+    /// its dist in
+    Trunk,
+}
+
+impl ToolOutput {
+    const ALL: [ToolOutput; 7] = [
+        Self::Coverage,
+        Self::Nextest,
+        Self::Trybuild,
+        Self::Snapshots,
+        Self::Dx,
+        Self::WasmBindgen,
+        Self::Trunk,
+    ];
+
+    fn area(self) -> GcArea {
+        match self {
+            Self::Coverage => GcArea::Coverage,
+            Self::Nextest => GcArea::Nextest,
+            Self::Trybuild => GcArea::Trybuild,
+            Self::Snapshots => GcArea::Snapshots,
+            Self::Dx => GcArea::Dx,
+            Self::WasmBindgen => GcArea::WasmBindgen,
+            Self::Trunk => GcArea::Trunk,
+        }
+    }
+
+    fn max_size(self, config: &Gc) -> Option<u64> {
+        match self {
+            Self::Coverage => config.max_coverage_size(),
+            Self::Nextest
+            | Self::Trybuild
+            | Self::Snapshots
+            | Self::Dx
+            | Self::WasmBindgen
+            | Self::Trunk => None,
+        }
+    }
+
+    /// Whether a file, or a directory, anywhere in the target directory is
+    /// this tool's output
+    fn matches(self, name: &str, is_dir: bool) -> bool {
+        match self {
+            Self::Coverage => {
+                !is_dir && (name.ends_with(".profraw") || name.ends_with(".profdata"))
+            }
+            Self::Snapshots => {
+                !is_dir && (name.ends_with(".snap.new") || name.ends_with(".pending-snap"))
+            }
+            Self::Trunk => is_dir && name == ".stage",
+            Self::Nextest | Self::Trybuild | Self::Dx | Self::WasmBindgen => false,
+        }
+    }
+
+    /// The directories whose entries are this tool's outputs, each removed
+    /// as a whole
+    fn output_dirs(self, target_dir: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::Coverage => vec![target_dir.join("llvm-cov-target")],
+            Self::Nextest => with_subdirectories(target_dir.join("nextest")),
+            Self::Dx => with_subdirectories(target_dir.join("dx")),
+            Self::WasmBindgen => with_subdirectories(target_dir.join("wasm-bindgen")),
+            Self::Trunk => vec![target_dir.join("trunk")],
+            Self::Trybuild => {
+                let tests_dir = target_dir.join("tests");
+                vec![tests_dir.clone(), tests_dir.join("trybuild")]
+            }
+            Self::Snapshots => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for ToolOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Coverage => "coverage data",
+            Self::Nextest => "nextest runs",
+            Self::Trybuild => "trybuild projects",
+            Self::Snapshots => "pending snapshots",
+            Self::Dx => "dx builds",
+            Self::WasmBindgen => "wasm-bindgen output",
+            Self::Trunk => "trunk staging",
+        })
+    }
+}
+
+/// A file or directory of a tool's output, removed as a whole
+#[derive(Debug)]
+struct OutputItem {
+    path: PathBuf,
+    usage: DiskSizes,
+    newest_mtime: SystemTime,
+}
+
+/// Find the outputs of `tool` in the target directory, oldest first
+fn find_outputs(tool: ToolOutput, target_dir: &Path, files: &[PathBuf]) -> Result<Vec<OutputItem>> {
+    let mut paths: Vec<PathBuf> = files
+        .iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| tool.matches(name, path.is_dir()))
+        })
+        .cloned()
+        .collect();
+    let output_dirs = tool.output_dirs(target_dir);
+    for dir in &output_dirs {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in read_dir(dir)? {
+            // Output directories nested in one another are not outputs
+            let path = entry?.path();
+            if !output_dirs.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut items = paths
+        .into_iter()
+        .map(|path| {
+            Ok(OutputItem {
+                usage: calculate_disk_usage(&path)?,
+                newest_mtime: newest_mtime(&path),
+                path,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    items.sort_by(|a, b| {
+        a.newest_mtime
+            .cmp(&b.newest_mtime)
+            .then(a.path.cmp(&b.path))
+    });
+
+    Ok(items)
+}
+
+/// Remove the outputs of every tool older than the age threshold of its area,
+/// then the oldest of the rest until they fit the tool's size cap. Returns
+/// what was freed and the paths removed.
+pub(crate) fn clean_tool_outputs(
+    target_dir: &Path,
+    config: &Gc,
+    verbose: u8,
+) -> Result<(DiskSizes, Vec<PathBuf>)> {
+    let mut freed = DiskSizes::default();
+    let mut removed = Vec::new();
+    if !target_dir.is_dir() {
+        return Ok((freed, removed));
+    }
+
+    // The files of every tool are found in a single walk
+    let output_dirs: Vec<PathBuf> = ToolOutput::ALL
+        .iter()
+        .flat_map(|tool| tool.output_dirs(target_dir))
+        .collect();
+    let mut files = Vec::new();
+    collect_output_files(target_dir, &output_dirs, &mut files)?;
+
+    for tool in ToolOutput::ALL {
+        if config.out_of_time() {
+            break;
+        }
+        let items = find_outputs(tool, target_dir, &files)?;
+        clean_outputs(tool, items, config, verbose, &mut freed, &mut removed);
+    }
+
+    Ok((freed, removed))
+}
+
+fn clean_outputs(
+    tool: ToolOutput,
+    items: Vec<OutputItem>,
+    config: &Gc,
+    verbose: u8,
+    freed: &mut DiskSizes,
+    removed: &mut Vec<PathBuf>,
+) {
+    if items.is_empty() {
+        return;
+    }
+    let log = config.logger(verbose);
+    let mode = config.size_mode();
+    let mut total: u64 = items.iter().map(|item| item.usage.get(mode)).sum();
+    log.verbose(
+        1,
+        format!(
+            "Found {tool}: {} in {} entries",
+            log.format_size(total),
+            items.len()
+        ),
+    );
+
+    let threshold = config.age_threshold_for(tool.area());
+    if threshold == KEEP {
+        log.verbose(1, format!("  Keeping {tool} ({}=keep)", tool.area()));
+        return;
+    }
+    let cutoff = SystemTime::now()
+        .checked_sub(threshold)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    for item in items {
+        if config.out_of_time() {
+            break;
+        }
+        let reason = if item.newest_mtime < cutoff {
+            RemovalReason::Age
+        } else if tool
+            .max_size(config)
+            .is_some_and(|max_size| total > max_size)
+        {
+            RemovalReason::Cap
+        } else {
+            continue;
+        };
+        if !config.in_scope(&item.path) {
+            continue;
+        }
+
+        log.verbose(
+            1,
+            format!(
+                "  Removing {tool} {} ({}, {reason})",
+                item.path.display(),
+                log.format_size(item.usage.get(mode))
+            ),
+        );
+        if config.remove(&item.path) {
+            config.record_removal(&item.path, item.usage.get(mode), GcPhase::TargetDir, reason);
+            total = total.saturating_sub(item.usage.get(mode));
+            *freed += item.usage;
+            removed.push(item.path);
+        }
+    }
+}
+
+/// Collect the files and directories of the target directory that some tool
+/// matches, outside the output directories, which are cleaned entry by entry
+fn collect_output_files(
+    dir: &Path,
+    output_dirs: &[PathBuf],
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if !file_type.is_dir() && !file_type.is_file() {
+            continue;
+        }
+        let name = entry.file_name();
+        let matched = name.to_str().is_some_and(|name| {
+            ToolOutput::ALL
+                .iter()
+                .any(|tool| tool.matches(name, file_type.is_dir()))
+        });
+        if matched {
+            files.push(path);
+        } else if file_type.is_dir()
+            && !output_dirs.contains(&path)
+            && !SKIPPED_DIRS.iter().any(|dir| name == *dir)
+        {
+            collect_output_files(&path, output_dirs, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `dir` and its subdirectories, whose entries are a tool's outputs
+fn with_subdirectories(dir: PathBuf) -> Vec<PathBuf> {
+    let subdirs: Vec<PathBuf> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    let mut dirs = vec![dir];
+    dirs.extend(subdirs);
+    dirs
+}
+
+/// Newest modification time of a file or of the files below a directory.
+/// Directory mtimes change as GC removes their entries, so they only count
+/// for a directory without files; cargo's lock files are touched by GC
+/// itself, so they never count.
+fn newest_mtime(path: &Path) -> SystemTime {
+    newest_file_mtime(path).unwrap_or_else(|| {
+        fs::symlink_metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    })
+}
+
+fn newest_file_mtime(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if !metadata.is_dir() {
+        let is_lock = path
+            .file_name()
+            .is_some_and(|name| name == ".cargo-lock" || name == ".cargo-artifact-lock");
+        return if is_lock {
+            None
+        } else {
+            metadata.modified().ok()
+        };
+    }
+    fs::read_dir(path)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| newest_file_mtime(&entry.path()))
+        .max()
+}
+
+fn read_dir(dir: &Path) -> Result<impl Iterator<Item = Result<fs::DirEntry>>> {
+    let entries = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let dir = dir.to_path_buf();
+    Ok(entries.map(move |entry| {
+        entry.map_err(|source| HoldError::IoError {
+            path: dir.clone(),
+            source,
+        })
+    }))
+}
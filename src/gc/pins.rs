@@ -0,0 +1,103 @@
+use regex::Regex;
+
+use super::artifacts::CrateArtifact;
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// Crates pinned with `cargo hold pin`, whose artifacts are never evicted.
+///
+/// Each pattern is a crate name or a glob where `*` matches any characters
+/// and `?` a single one. Dashes and underscores are interchangeable, and the
+/// `lib` prefix of library artifacts is ignored, so `rocksdb-sys` matches
+/// `librocksdb_sys-<hash>.rlib` as well as the `rocksdb-sys-<hash>` build
+/// script output.
+#[derive(Debug, Clone)]
+pub struct PinnedCrates {
+    patterns: Vec<(String, Regex)>,
+}
+
+impl PinnedCrates {
+    /// Create the pins from crate name patterns
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Ok((pattern.clone(), pattern_to_regex(pattern)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { patterns })
+    }
+
+    /// The patterns the pins were created from
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|(pattern, _)| pattern.as_str())
+    }
+
+    /// Check whether an artifact group belongs to a pinned crate
+    pub(crate) fn contains(&self, artifact: &CrateArtifact) -> bool {
+        let name = normalize(&artifact.name);
+        let unprefixed = name.strip_prefix("lib");
+        self.patterns.iter().any(|(_, regex)| {
+            regex.is_match(&name) || unprefixed.is_some_and(|name| regex.is_match(name))
+        })
+    }
+}
+
+/// Check that a pin pattern only uses crate name characters and wildcards
+pub(crate) fn validate_pin_pattern(pattern: &str) -> Result<()> {
+    pattern_to_regex(pattern).map(|_| ())
+}
+
+/// Drop artifacts of pinned crates from eviction candidates
+pub(crate) fn exclude_pinned(
+    crate_artifacts: Vec<CrateArtifact>,
+    pins: Option<&PinnedCrates>,
+    log: &Logger,
+) -> Vec<CrateArtifact> {
+    let Some(pins) = pins else {
+        return crate_artifacts;
+    };
+
+    let (pinned, eligible): (Vec<_>, Vec<_>) = crate_artifacts
+        .into_iter()
+        .partition(|artifact| pins.contains(artifact));
+
+    if !log.quiet() && !pinned.is_empty() {
+        let pinned_size: u64 = pinned.iter().map(|a| a.total_size).sum();
+        eprintln!(
+            "  Preserving {} pinned artifacts ({})",
+            pinned.len(),
+            log.format_size(pinned_size)
+        );
+        if log.level() > 1 {
+            for artifact in &pinned {
+                eprintln!("    Pinned: {}-{}", artifact.name, artifact.hash);
+            }
+        }
+    }
+
+    eligible
+}
+
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+fn pattern_to_regex(pattern: &str) -> Result<Regex> {
+    let invalid = |reason: &str| HoldError::InvalidPinPattern(pattern.to_string(), reason.into());
+    if pattern.is_empty() {
+        return Err(invalid("pattern is empty"));
+    }
+
+    let mut regex = String::from("^");
+    for c in normalize(pattern).chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c if c.is_ascii_alphanumeric() || c == '_' => regex.push(c),
+            c => return Err(invalid(&format!("unexpected character '{c}'"))),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).map_err(|err| invalid(&err.to_string()))
+}
@@ -0,0 +1,301 @@
+use std::io::Write;
+use std::process::Stdio;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::artifacts::CrateArtifact;
+use crate::error::{HoldError, Result};
+use crate::hooks::shell;
+use crate::logging::Logger;
+use crate::schema::SCHEMA_VERSION;
+
+/// A user-provided command that decides which crate artifacts to keep or
+/// evict.
+///
+/// The command is run through the shell once per profile directory. It
+/// receives one JSON object per candidate artifact on stdin, one per line:
+///
+/// ```json
+/// {"schema_version":1,"profile":"debug","name":"serde","hash":"1a2b3c4d5e6f7a8b","size":1048576,"files":4,"newest_mtime":1700000000,"age_secs":86400}
+/// ```
+///
+/// and must print exactly one verdict per input line, in the same order:
+///
+/// - `keep`: never evict the artifact in this run
+/// - `evict`: evict the artifact regardless of age or size limits
+/// - `default`: leave the decision to the built-in age and size rules
+/// - a number: an eviction score for the size cap; higher scores are evicted
+///   first, and artifacts with equal scores (including `default`, which scores
+///   0) fall back to oldest first
+///
+/// The profile directory is also exported as `CARGO_HOLD_PROFILE`. A command
+/// that fails, exits non-zero, or prints malformed output aborts garbage
+/// collection rather than guessing.
+#[derive(Debug, Clone)]
+pub struct PolicyCommand {
+    command: String,
+}
+
+impl PolicyCommand {
+    /// Create a policy from a shell command line
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+
+    /// The shell command line
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Ask the command for a verdict on each artifact of a profile
+    pub(crate) fn evaluate(
+        &self,
+        profile: &str,
+        artifacts: &[CrateArtifact],
+    ) -> Result<Vec<Verdict>> {
+        if artifacts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = SystemTime::now();
+        let mut input = String::new();
+        for artifact in artifacts {
+            let newest_mtime = artifact
+                .newest_mtime
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let age_secs = now
+                .duration_since(artifact.newest_mtime)
+                .unwrap_or_default()
+                .as_secs();
+            let line = policy_input_line(
+                profile,
+                &artifact.name,
+                &artifact.hash,
+                artifact.total_size,
+                artifact.artifacts.len(),
+                newest_mtime,
+                age_secs,
+            );
+            input.push_str(&line.to_string());
+            input.push('\n');
+        }
+
+        let mut child = shell(&self.command)
+            .env("CARGO_HOLD_PROFILE", profile)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| self.error(format!("Failed to start: {e}")))?;
+
+        // Feed stdin from a separate thread so a command that replies while
+        // still reading cannot deadlock against us
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| self.error(format!("Failed to wait for command: {e}")))?;
+        // A command may legitimately stop reading early (e.g. `yes keep`)
+        let _ = writer.join();
+
+        if !output.status.success() {
+            return Err(self.error(format!("Exited with {}", output.status)));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let verdicts = stdout
+            .lines()
+            .take(artifacts.len())
+            .map(|line| {
+                line.parse::<Verdict>()
+                    .map_err(|()| self.error(format!("Unrecognized verdict '{}'", line.trim())))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if verdicts.len() != artifacts.len() {
+            return Err(self.error(format!(
+                "Expected {} verdicts, got {}",
+                artifacts.len(),
+                verdicts.len()
+            )));
+        }
+
+        Ok(verdicts)
+    }
+
+    fn error(&self, message: String) -> HoldError {
+        HoldError::PolicyCommandError {
+            command: self.command.clone(),
+            message,
+        }
+    }
+}
+
+/// The line of policy command input describing one artifact, as described
+/// by the `policyInput` definition of the JSON schema
+pub(crate) fn policy_input_line(
+    profile: &str,
+    name: &str,
+    hash: &str,
+    size: u64,
+    files: usize,
+    newest_mtime: u64,
+    age_secs: u64,
+) -> serde_json::Value {
+    serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "profile": profile,
+        "name": name,
+        "hash": hash,
+        "size": size,
+        "files": files,
+        "newest_mtime": newest_mtime,
+        "age_secs": age_secs,
+    })
+}
+
+/// A policy command's decision for one artifact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Verdict {
+    /// Use the built-in rules
+    Default,
+    /// Never evict
+    Keep,
+    /// Always evict
+    Evict,
+    /// Use the built-in rules with this eviction score
+    Score(f64),
+}
+
+impl std::str::FromStr for Verdict {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, ()> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "default" => Ok(Self::Default),
+            "keep" => Ok(Self::Keep),
+            "evict" => Ok(Self::Evict),
+            other => other
+                .parse::<f64>()
+                .ok()
+                .filter(|score| score.is_finite())
+                .map(Self::Score)
+                .ok_or(()),
+        }
+    }
+}
+
+/// Apply a policy command to a profile's artifacts.
+///
+/// Returns the artifacts the policy evicts outright, followed by the ones
+/// left to the built-in rules (with their scores set). Artifacts the policy
+/// keeps are dropped from both.
+pub(crate) fn apply_policy(
+    policy: Option<&PolicyCommand>,
+    profile: &str,
+    crate_artifacts: Vec<CrateArtifact>,
+    log: &Logger,
+) -> Result<(Vec<CrateArtifact>, Vec<CrateArtifact>)> {
+    let Some(policy) = policy else {
+        return Ok((Vec::new(), crate_artifacts));
+    };
+
+    let verdicts = policy.evaluate(profile, &crate_artifacts)?;
+    let mut evicted = Vec::new();
+    let mut remaining = Vec::new();
+    let mut kept = 0;
+
+    for (mut artifact, verdict) in crate_artifacts.into_iter().zip(verdicts) {
+        match verdict {
+            Verdict::Keep => {
+                log.verbose(
+                    2,
+                    format!("    Policy keeps: {}-{}", artifact.name, artifact.hash),
+                );
+                kept += 1;
+            }
+            Verdict::Evict => evicted.push(artifact),
+            Verdict::Default => remaining.push(artifact),
+            Verdict::Score(score) => {
+                artifact.eviction_score = score;
+                remaining.push(artifact);
+            }
+        }
+    }
+
+    log.verbose(
+        1,
+        format!(
+            "  Policy command kept {kept} and evicted {} artifacts",
+            evicted.len()
+        ),
+    );
+
+    Ok((evicted, remaining))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(name: &str) -> CrateArtifact {
+        CrateArtifact {
+            name: name.to_string(),
+            hash: "0123456789abcdef".to_string(),
+            artifacts: vec![],
+            total_size: 1024,
+            newest_mtime: SystemTime::now(),
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        assert_eq!("keep".parse::<Verdict>(), Ok(Verdict::Keep));
+        assert_eq!(" EVICT ".parse::<Verdict>(), Ok(Verdict::Evict));
+        assert_eq!("default".parse::<Verdict>(), Ok(Verdict::Default));
+        assert_eq!("".parse::<Verdict>(), Ok(Verdict::Default));
+        assert_eq!("2.5".parse::<Verdict>(), Ok(Verdict::Score(2.5)));
+        assert!("maybe".parse::<Verdict>().is_err());
+        assert!("NaN".parse::<Verdict>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_policy_partitions_artifacts() {
+        // Keep `alpha`, evict `beta`, score everything else by its line number
+        let policy = PolicyCommand::new(
+            r#"n=0; while read -r line; do n=$((n+1)); case "$line" in *'"name":"alpha"'*) echo keep;; *'"name":"beta"'*) echo evict;; *) echo $n;; esac; done"#,
+        );
+        let log = Logger::new(0, true);
+        let artifacts = vec![artifact("alpha"), artifact("beta"), artifact("gamma")];
+
+        let (evicted, remaining) = apply_policy(Some(&policy), "debug", artifacts, &log).unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].name, "beta");
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "gamma");
+        assert_eq!(remaining[0].eviction_score, 3.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_policy_command_failures_are_errors() {
+        let artifacts = vec![artifact("alpha"), artifact("beta")];
+
+        for command in ["exit 3", "echo keep", "echo keep; echo perhaps"] {
+            let result = PolicyCommand::new(command).evaluate("debug", &artifacts);
+            assert!(
+                matches!(result, Err(HoldError::PolicyCommandError { .. })),
+                "{command} should fail"
+            );
+        }
+    }
+}
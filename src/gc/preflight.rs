@@ -0,0 +1,157 @@
+//! Check that garbage collection may remove what it is about to, before it
+//! removes anything.
+//!
+//! A target directory or cargo home restored by another user, or mounted
+//! read-only, would otherwise fail part way through: some entries removed,
+//! others not, and the freed bytes wrong.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::config::Gc;
+use super::profiles::find_profile_directories;
+use crate::error::{HoldError, Result};
+
+/// Subdirectories of a profile directory that entries are removed from
+const PROFILE_SUBDIRS: [&str; 4] = ["deps", ".fingerprint", "build", "incremental"];
+
+/// Check the directories the target directory cleanup (with `target_dir`)
+/// and the cleanup of `cargo_home` remove entries from, reporting all that
+/// fail at once
+pub(crate) fn check_removable(
+    config: &Gc,
+    target_dir: bool,
+    cargo_home: Option<&Path>,
+) -> Result<()> {
+    let mut directories = Vec::new();
+    if target_dir && config.target_dir().exists() {
+        let target_dir = config.target_dir();
+        directories.push(target_dir.to_path_buf());
+        for profile_dir in find_profile_directories(target_dir)? {
+            directories.extend(PROFILE_SUBDIRS.iter().map(|name| profile_dir.join(name)));
+            directories.push(profile_dir);
+        }
+    }
+    if let Some(cargo_home) = cargo_home {
+        directories.extend(cargo_home_directories(config, cargo_home));
+    }
+
+    let failures: Vec<_> = directories
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .filter_map(|dir| probe(dir).err().map(|e| (dir, e)))
+        .collect();
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(HoldError::GcPermissionDenied {
+        count: failures.len(),
+        directories: failures
+            .iter()
+            .map(|(dir, e)| format!("\n  {}: {e}", dir.display()))
+            .collect(),
+    })
+}
+
+/// Directories of the cargo home cleanup: each registry's downloads and
+/// sources, the git checkouts and databases, and the installed binaries
+fn cargo_home_directories(config: &Gc, cargo_home: &Path) -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+    if config.cleans_cargo_registry(cargo_home) {
+        let registry = cargo_home.join("registry");
+        for area in ["cache", "src"] {
+            if let Ok(entries) = fs::read_dir(registry.join(area)) {
+                directories.extend(entries.filter_map(|e| e.ok()).map(|e| e.path()));
+            }
+        }
+        let git = cargo_home.join("git");
+        directories.push(git.join("checkouts"));
+        directories.push(git.join("db"));
+    }
+    if config.cleans_cargo_bin() {
+        directories.push(cargo_home.join("bin"));
+    }
+    directories
+}
+
+/// Create and delete a file in `dir`, which needs the same permissions as
+/// removing any of its entries
+fn probe(dir: &Path) -> io::Result<()> {
+    let mtime = fs::metadata(dir).and_then(|metadata| metadata.modified());
+    let path = dir.join(format!(".cargo-hold-preflight-{}", std::process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(_) => {}
+        // Left behind by a run that was killed; removing it is the check
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    fs::remove_file(&path)?;
+
+    // The size index and cargo's freshness checks read directory mtimes,
+    // which the probe must not move
+    if let Ok(mtime) = mtime {
+        let _ = File::open(dir).and_then(|dir| dir.set_modified(mtime));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_removable_passes_on_writable_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let target_dir = dir.path().join("target");
+        fs::create_dir_all(target_dir.join("debug").join("deps")).unwrap();
+        fs::create_dir_all(target_dir.join("debug").join(".fingerprint")).unwrap();
+        let cargo_home = dir.path().join("cargo");
+        fs::create_dir_all(cargo_home.join("registry").join("cache").join("index-1a2b")).unwrap();
+
+        let config = Gc::builder().target_dir(&target_dir).build();
+        check_removable(&config, true, Some(&cargo_home)).unwrap();
+        // The probes are gone
+        assert_eq!(
+            fs::read_dir(target_dir.join("debug").join("deps"))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_removable_reports_every_read_only_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target_dir = dir.path().join("target");
+        let deps = target_dir.join("debug").join("deps");
+        let build = target_dir.join("debug").join("build");
+        fs::create_dir_all(&deps).unwrap();
+        fs::create_dir_all(&build).unwrap();
+        for path in [&deps, &build] {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o555)).unwrap();
+        }
+        // Permissions do not bind root
+        let enforced = probe(&deps).is_err();
+
+        let config = Gc::builder().target_dir(&target_dir).build();
+        let result = check_removable(&config, true, None);
+        for path in [&deps, &build] {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        if !enforced {
+            return;
+        }
+        match result {
+            Err(HoldError::GcPermissionDenied { count, directories }) => {
+                assert_eq!(count, 2);
+                assert!(directories.contains(&deps.display().to_string()));
+                assert!(directories.contains(&build.display().to_string()));
+            }
+            other => panic!("expected a permission error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Locating cargo profile directories (`debug`, `release`, `<triple>/debug`)
+//! in a target directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{HoldError, Result};
+
+/// Find all profile directories (`debug`, `release`, `<triple>/debug`, ...)
+/// in the target directory, sorted by path; the target directory itself if
+/// it is one
+pub fn find_profile_directories(target_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut profile_dirs = Vec::new();
+
+    if !target_dir.exists() {
+        return Ok(profile_dirs);
+    }
+
+    // Check if target_dir itself is a profile directory
+    if is_profile_directory(target_dir) {
+        profile_dirs.push(target_dir.to_path_buf());
+        return Ok(profile_dirs);
+    }
+
+    // Look for profile directories in subdirectories
+    let entries = fs::read_dir(target_dir).map_err(|source| HoldError::IoError {
+        path: target_dir.to_path_buf(),
+        source,
+    })?;
+
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: target_dir.to_path_buf(),
+            source,
+        })?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            // Skip special files
+            if let Some(name) = path.file_name() {
+                let name = name.to_string_lossy();
+                if name == "CACHEDIR.TAG" || name == ".rustc_info.json" {
+                    continue;
+                }
+            }
+
+            if is_profile_directory(&path) {
+                profile_dirs.push(path);
+            } else {
+                // Check subdirectories (for target triple directories)
+                if let Ok(subdirs) = find_profile_directories(&path) {
+                    profile_dirs.extend(subdirs);
+                }
+            }
+        }
+    }
+
+    // Profiles share the size budget, so clean them in a stable order
+    profile_dirs.sort();
+
+    Ok(profile_dirs)
+}
+
+/// How deep below the target directory [`find_named_profile_directories`]
+/// looks for named profiles, e.g. `<tool>/<triple>/<profile>` is 3
+const MAX_NAMED_PROFILE_DEPTH: usize = 4;
+
+/// The directories cargo builds into within a profile directory
+const PROFILE_ARTIFACT_DIRS: [&str; 4] = ["build", "deps", ".fingerprint", "incremental"];
+
+/// Find the profile directories of [`find_profile_directories`] and, in
+/// addition, every directory named after one of `names` (up to a few levels
+/// deep) that holds build artifacts, even next to or below a directory that
+/// looks like a profile, or with only an `incremental` directory.
+///
+/// Custom profiles (`release-lto`, `coverage`) and the target directories of
+/// tools like `cargo llvm-cov` nest under target triples and tool
+/// directories, where the shape alone does not tell a profile apart.
+pub fn find_named_profile_directories(target_dir: &Path, names: &[String]) -> Result<Vec<PathBuf>> {
+    let mut profile_dirs = find_profile_directories(target_dir)?;
+    if names.is_empty() || !target_dir.is_dir() {
+        return Ok(profile_dirs);
+    }
+
+    let mut named = Vec::new();
+    collect_named_profiles(target_dir, names, &profile_dirs, 1, &mut named)?;
+    profile_dirs.extend(named);
+    profile_dirs.sort();
+    profile_dirs.dedup();
+
+    Ok(profile_dirs)
+}
+
+fn collect_named_profiles(
+    dir: &Path,
+    names: &[String],
+    found: &[PathBuf],
+    depth: usize,
+    named: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+
+    // A profile may still nest named ones, just not within its own artifacts
+    let in_profile = found
+        .iter()
+        .chain(named.iter())
+        .any(|profile| profile == dir);
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        let path = entry.path();
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        let name = entry.file_name();
+        if in_profile && PROFILE_ARTIFACT_DIRS.iter().any(|dir| name == *dir) {
+            continue;
+        }
+
+        let is_named = name
+            .to_str()
+            .is_some_and(|name| names.iter().any(|profile| profile == name));
+        if is_named && holds_build_artifacts(&path) {
+            named.push(path.clone());
+        }
+        if depth < MAX_NAMED_PROFILE_DEPTH {
+            collect_named_profiles(&path, names, found, depth + 1, named)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a named profile directory holds anything cargo builds into it
+fn holds_build_artifacts(path: &Path) -> bool {
+    PROFILE_ARTIFACT_DIRS
+        .iter()
+        .any(|dir| path.join(dir).is_dir())
+}
+
+/// Read the names of the profile directories declared by the workspace at
+/// `workspace_root`: the `[profile.<name>]` tables of its `Cargo.toml`, of
+/// the `.cargo/config.toml` files of it and its ancestors, and of the cargo
+/// home's config (`$CARGO_HOME` or `~/.cargo` unless given). `dev` and `test`
+/// build into `debug`, `bench` into `release`; `debug` and `release` are
+/// always included.
+#[cfg(feature = "gc")]
+pub(crate) fn declared_profile_names(
+    workspace_root: &Path,
+    cargo_home: Option<&Path>,
+) -> Vec<String> {
+    let mut sources = vec![workspace_root.join("Cargo.toml")];
+    for dir in workspace_root.ancestors() {
+        sources.push(dir.join(".cargo").join("config.toml"));
+        sources.push(dir.join(".cargo").join("config"));
+    }
+    let cargo_home = cargo_home
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os("CARGO_HOME").map(PathBuf::from))
+        .or_else(|| home::home_dir().map(|home| home.join(".cargo")));
+    if let Some(cargo_home) = cargo_home {
+        sources.push(cargo_home.join("config.toml"));
+        sources.push(cargo_home.join("config"));
+    }
+
+    let mut names = vec!["debug".to_string(), "release".to_string()];
+    for source in sources {
+        // Unreadable or malformed files are cargo's to report
+        let Ok(contents) = fs::read_to_string(&source) else {
+            continue;
+        };
+        let Ok(table) = contents.parse::<toml::Table>() else {
+            continue;
+        };
+        let Some(toml::Value::Table(profiles)) = table.get("profile") else {
+            continue;
+        };
+        for profile in profiles.keys() {
+            let dir_name = match profile.as_str() {
+                "dev" | "test" => "debug",
+                "bench" => "release",
+                other => other,
+            };
+            if !names.iter().any(|name| name == dir_name) {
+                names.push(dir_name.to_string());
+            }
+        }
+    }
+
+    names
+}
+
+/// Identify a profile directory by its path relative to the target directory,
+/// using `/` separators on every platform.
+pub(crate) fn profile_key(target_dir: &Path, profile_dir: &Path) -> String {
+    let relative = profile_dir.strip_prefix(target_dir).unwrap_or(profile_dir);
+    let key = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    if key.is_empty() { ".".to_string() } else { key }
+}
+
+/// Check if a directory is a Cargo profile directory
+fn is_profile_directory(path: &Path) -> bool {
+    if !path.is_dir() {
+        return false;
+    }
+
+    // Check for standard Cargo build artifacts
+    let artifact_dirs = ["build", "deps", ".fingerprint"];
+    artifact_dirs.iter().any(|&dir| path.join(dir).exists())
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+
+/// A file or directory removed by garbage collection (or, in a dry run, one
+/// that would be removed)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RemovedPath {
+    /// The removed path
+    pub path: PathBuf,
+    /// Its size in the configured size mode (the whole tree for directories)
+    pub size: u64,
+    /// The part of garbage collection that removed it
+    pub phase: GcPhase,
+    /// Why it was removed
+    pub reason: RemovalReason,
+}
+
+/// Part of garbage collection a path is removed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GcPhase {
+    /// Cleaning the target directory
+    TargetDir,
+    /// Cleaning the cargo registry, git checkouts, and binaries of the cargo
+    /// home
+    CargoHome,
+}
+
+impl fmt::Display for GcPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TargetDir => f.write_str("target-dir"),
+            Self::CargoHome => f.write_str("cargo-home"),
+        }
+    }
+}
+
+/// Why garbage collection removed a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RemovalReason {
+    /// It was not used for longer than the age threshold of its area
+    Age,
+    /// Evicted to bring the target directory under its size or file cap
+    Cap,
+    /// Evicted by the `--gc-policy-cmd` command
+    Policy,
+    /// Built from a workspace source changed since, so cargo would rebuild
+    /// it anyway (`--gc-dep-info`)
+    Stale,
+    /// Generated output removed on every run: documentation, packages, and
+    /// temporary files
+    Disposable,
+}
+
+impl fmt::Display for RemovalReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Age => f.write_str("age"),
+            Self::Cap => f.write_str("cap"),
+            Self::Policy => f.write_str("policy"),
+            Self::Stale => f.write_str("stale"),
+            Self::Disposable => f.write_str("disposable"),
+        }
+    }
+}
+
+/// How a list of removed paths is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovalListFormat {
+    /// A JSON array of `{"path": ..., "size": ...}` objects
+    #[default]
+    Json,
+    /// `<size>\t<path>` records terminated by NUL bytes, like `du --null`
+    Nul,
+}
+
+impl FromStr for RemovalListFormat {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "nul" | "null" => Ok(Self::Nul),
+            _ => Err(HoldError::InvalidRemovalListFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for RemovalListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => f.write_str("json"),
+            Self::Nul => f.write_str("nul"),
+        }
+    }
+}
+
+/// Write `removed` to `destination` (`-` for stdout) in `format`
+pub(crate) fn write_removal_list(
+    removed: &[RemovedPath],
+    format: RemovalListFormat,
+    destination: &Path,
+) -> Result<()> {
+    let io_error = |source| HoldError::IoError {
+        path: destination.to_path_buf(),
+        source,
+    };
+
+    if destination == Path::new("-") {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        render(removed, format, &mut out)
+            .and_then(|()| out.flush())
+            .map_err(io_error)
+    } else {
+        let mut out = BufWriter::new(File::create(destination).map_err(io_error)?);
+        render(removed, format, &mut out)
+            .and_then(|()| out.flush())
+            .map_err(io_error)
+    }
+}
+
+fn render(
+    removed: &[RemovedPath],
+    format: RemovalListFormat,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        RemovalListFormat::Json => {
+            let entries: Vec<_> = removed
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "path": entry.path.to_string_lossy(),
+                        "size": entry.size,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *out, &entries)?;
+            writeln!(out)
+        }
+        RemovalListFormat::Nul => {
+            for entry in removed {
+                write!(out, "{}\t", entry.size)?;
+                out.write_all(path_bytes(&entry.path).as_ref())?;
+                out.write_all(b"\0")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The raw bytes of a path, so NUL-delimited output survives non-UTF-8 names
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    std::borrow::Cow::Owned(path.to_string_lossy().into_owned().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removed() -> Vec<RemovedPath> {
+        vec![
+            RemovedPath {
+                path: PathBuf::from("target/debug/deps/libserde-1a2b.rlib"),
+                size: 4096,
+                phase: GcPhase::TargetDir,
+                reason: RemovalReason::Cap,
+            },
+            RemovedPath {
+                path: PathBuf::from("target/debug/incremental"),
+                size: 8192,
+                phase: GcPhase::TargetDir,
+                reason: RemovalReason::Age,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_json() {
+        let mut out = Vec::new();
+        render(&removed(), RemovalListFormat::Json, &mut out).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!([
+                {"path": "target/debug/deps/libserde-1a2b.rlib", "size": 4096},
+                {"path": "target/debug/incremental", "size": 8192},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_nul() {
+        let mut out = Vec::new();
+        render(&removed(), RemovalListFormat::Nul, &mut out).unwrap();
+
+        assert_eq!(
+            out,
+            b"4096\ttarget/debug/deps/libserde-1a2b.rlib\x008192\ttarget/debug/incremental\x00"
+        );
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            "JSON".parse::<RemovalListFormat>().unwrap(),
+            RemovalListFormat::Json
+        );
+        assert_eq!(
+            "nul".parse::<RemovalListFormat>().unwrap(),
+            RemovalListFormat::Nul
+        );
+        assert!("csv".parse::<RemovalListFormat>().is_err());
+    }
+}
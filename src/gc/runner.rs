@@ -0,0 +1,197 @@
+//! Ephemeral environments.
+//!
+//! Containers and hosted CI runners start from a fresh image on every job, so
+//! some of the cleanup meant for long-lived machines is wasted: nothing in
+//! `~/.cargo/bin` is ever old enough for the bin age threshold, and a cargo
+//! home baked into a container image comes back in full on the next job, so
+//! deleting from it only costs time.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+
+/// Whether GC treats the machine as ephemeral
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EphemeralMode {
+    /// Detect containers and hosted runners from the environment
+    #[default]
+    Auto,
+    /// Treat the machine as ephemeral even when nothing is detected
+    On,
+    /// Clean as on a long-lived machine
+    Off,
+}
+
+impl EphemeralMode {
+    /// The ephemeral environment cargo-hold runs in according to this mode,
+    /// if any
+    pub(crate) fn runner(self) -> Option<Runner> {
+        match self {
+            Self::Auto => Runner::detect(),
+            Self::On => Some(Runner::detect().unwrap_or(Runner::Other)),
+            Self::Off => None,
+        }
+    }
+}
+
+impl FromStr for EphemeralMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "on" => Ok(Self::On),
+            "off" => Ok(Self::Off),
+            _ => Err(HoldError::InvalidEphemeralMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for EphemeralMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::On => f.write_str("on"),
+            Self::Off => f.write_str("off"),
+        }
+    }
+}
+
+/// An ephemeral environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runner {
+    /// A Kubernetes pod
+    Kubernetes,
+    /// A Docker or Podman container
+    Docker,
+    /// A GitHub-hosted Actions runner, a fresh virtual machine per job
+    GitHubHosted,
+    /// Declared ephemeral with `--ephemeral on`
+    Other,
+}
+
+impl Runner {
+    /// The ephemeral environment of this process, if any
+    pub(crate) fn detect() -> Option<Self> {
+        Self::detect_in(|name| std::env::var(name).ok(), Path::new("/"))
+    }
+
+    /// The ephemeral environment given its environment variables and root
+    /// directory
+    pub(crate) fn detect_in(var: impl Fn(&str) -> Option<String>, root: &Path) -> Option<Self> {
+        if var("KUBERNETES_SERVICE_HOST").is_some_and(|host| !host.is_empty()) {
+            Some(Self::Kubernetes)
+        } else if root.join(".dockerenv").exists() || root.join("run/.containerenv").exists() {
+            Some(Self::Docker)
+        } else if var("RUNNER_ENVIRONMENT").as_deref() == Some("github-hosted") {
+            Some(Self::GitHubHosted)
+        } else {
+            None
+        }
+    }
+
+    /// Whether this is a container, whose files come from an image unless
+    /// mounted into it
+    pub(crate) fn is_container(self) -> bool {
+        matches!(self, Self::Kubernetes | Self::Docker)
+    }
+}
+
+impl fmt::Display for Runner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kubernetes => f.write_str("Kubernetes"),
+            Self::Docker => f.write_str("a container"),
+            Self::GitHubHosted => f.write_str("a GitHub-hosted runner"),
+            Self::Other => f.write_str("an ephemeral machine"),
+        }
+    }
+}
+
+/// Whether `path` lies on the container's root filesystem rather than on a
+/// volume mounted into it, i.e. was baked into the image
+#[cfg(unix)]
+pub(crate) fn baked_into_image(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(path), std::fs::metadata("/")) {
+        (Ok(path), Ok(root)) => path.dev() == root.dev(),
+        _ => false,
+    }
+}
+
+/// Whether `path` lies on the container's root filesystem rather than on a
+/// volume mounted into it, i.e. was baked into the image
+#[cfg(not(unix))]
+pub(crate) fn baked_into_image(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)], root: &Path) -> Option<Runner> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Runner::detect_in(|name| vars.get(name).cloned(), root)
+    }
+
+    #[test]
+    fn test_runner_detection() {
+        let root = TempDir::new().unwrap();
+        assert_eq!(detect(&[], root.path()), None);
+        assert_eq!(
+            detect(&[("RUNNER_ENVIRONMENT", "self-hosted")], root.path()),
+            None
+        );
+        assert_eq!(
+            detect(&[("RUNNER_ENVIRONMENT", "github-hosted")], root.path()),
+            Some(Runner::GitHubHosted)
+        );
+        assert_eq!(
+            detect(&[("KUBERNETES_SERVICE_HOST", "10.0.0.1")], root.path()),
+            Some(Runner::Kubernetes)
+        );
+
+        std::fs::write(root.path().join(".dockerenv"), "").unwrap();
+        assert_eq!(
+            detect(&[("RUNNER_ENVIRONMENT", "github-hosted")], root.path()),
+            Some(Runner::Docker),
+            "A job container is a container first"
+        );
+        assert!(Runner::Docker.is_container());
+        assert!(!Runner::GitHubHosted.is_container());
+    }
+
+    #[test]
+    fn test_ephemeral_mode_parsing() {
+        assert_eq!(
+            "auto".parse::<EphemeralMode>().unwrap(),
+            EphemeralMode::Auto
+        );
+        assert_eq!(" ON ".parse::<EphemeralMode>().unwrap(), EphemeralMode::On);
+        assert!(matches!(
+            "maybe".parse::<EphemeralMode>(),
+            Err(HoldError::InvalidEphemeralMode(_))
+        ));
+        assert_eq!(EphemeralMode::Off.to_string(), "off");
+        assert_eq!(EphemeralMode::Off.runner(), None);
+        assert!(EphemeralMode::On.runner().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_root_is_baked_into_image() {
+        assert!(baked_into_image(Path::new("/")));
+        assert!(!baked_into_image(Path::new("/nonexistent/cargo-home")));
+    }
+}
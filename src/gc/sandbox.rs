@@ -0,0 +1,302 @@
+//! Confinement of garbage collection with Landlock, selected by
+//! `--gc-sandbox`, so that a wrong path can never make GC delete anything
+//! outside the directories it cleans.
+//!
+//! The rules only restrict writing, creating, and removing files: GC, and a
+//! policy command it runs, can still read everything. Landlock confines a
+//! thread and the threads and processes it starts from then on, so GC runs
+//! on a thread of its own with a thread pool of its own, and the rest of the
+//! command (saving the metadata, the removal list, and the audit log) is not
+//! confined.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// Whether garbage collection is confined with Landlock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Never confine GC (default)
+    #[default]
+    Off,
+    /// Confine GC where the kernel supports Landlock (Linux 5.13+), run it
+    /// unconfined elsewhere
+    Auto,
+    /// Confine GC, failing where Landlock is unavailable
+    On,
+}
+
+impl FromStr for SandboxMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "auto" => Ok(Self::Auto),
+            "on" => Ok(Self::On),
+            _ => Err(HoldError::InvalidSandboxMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SandboxMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Auto => "auto",
+            Self::On => "on",
+        })
+    }
+}
+
+/// What a confined GC may write to
+#[derive(Debug, Default)]
+pub(crate) struct SandboxPaths {
+    /// Directories below which files may be written, created, and removed
+    pub(crate) dirs: Vec<PathBuf>,
+    /// Existing files that may be written, but neither created nor removed
+    pub(crate) files: Vec<PathBuf>,
+}
+
+/// Run `f` on a thread confined to `paths` as `mode` says.
+///
+/// Paths that do not exist are left out, as there is nothing to clean in
+/// them. With [`SandboxMode::Auto`], `f` still runs when Landlock is
+/// unavailable.
+pub(crate) fn run_confined<T: Send>(
+    mode: SandboxMode,
+    paths: &SandboxPaths,
+    log: Logger,
+    f: impl FnOnce() -> Result<T> + Send,
+) -> Result<T> {
+    if mode == SandboxMode::Off {
+        return f();
+    }
+
+    std::thread::scope(|scope| {
+        let confined = scope.spawn(|| {
+            match imp::restrict(paths) {
+                Ok(()) => log.verbose(
+                    1,
+                    format!(
+                        "Confined garbage collection to {} directories with Landlock",
+                        paths.dirs.iter().filter(|dir| dir.exists()).count()
+                    ),
+                ),
+                Err(err) if mode == SandboxMode::Auto => {
+                    log.verbose(1, format!("Running garbage collection unconfined: {err}"))
+                }
+                Err(err) => {
+                    return Err(HoldError::SandboxUnavailable {
+                        reason: err.to_string(),
+                    });
+                }
+            }
+            // Started from the confined thread, so its threads are confined
+            // too, unlike those of the global pool
+            let pool = rayon::ThreadPoolBuilder::new()
+                .thread_name(|index| format!("cargo-hold-gc-{index}"))
+                .build()
+                .map_err(|err| HoldError::GcError(format!("Failed to start GC threads: {err}")))?;
+            pool.install(f)
+        });
+        confined
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    use super::SandboxPaths;
+
+    const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+    const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+    const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+    /// Creating character devices, directories, regular files, sockets,
+    /// FIFOs, block devices, and symbolic links
+    const ACCESS_FS_MAKE: u64 = 0b111_1111 << 6;
+    /// Linking or renaming a file into another directory (ABI 2)
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+    /// Truncating a file (ABI 3)
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+    /// `struct landlock_ruleset_attr` as of ABI 1; later kernels accept the
+    /// shorter struct
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    /// `struct landlock_path_beneath_attr`
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    pub(super) fn restrict(paths: &SandboxPaths) -> io::Result<()> {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut handled =
+            ACCESS_FS_WRITE_FILE | ACCESS_FS_REMOVE_DIR | ACCESS_FS_REMOVE_FILE | ACCESS_FS_MAKE;
+        if abi >= 2 {
+            handled |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= ACCESS_FS_TRUNCATE;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: handled,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr,
+                size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        // Files only take the rights that apply to files
+        let file_access = handled & (ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE);
+        let rules = paths
+            .dirs
+            .iter()
+            .map(|dir| (dir, handled))
+            .chain(paths.files.iter().map(|file| (file, file_access)));
+        for (path, allowed_access) in rules {
+            let Ok(parent) = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+                .open(path)
+            else {
+                continue;
+            };
+            let rule = PathBeneathAttr {
+                allowed_access,
+                parent_fd: parent.as_raw_fd(),
+            };
+            let added = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset.as_raw_fd(),
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &rule,
+                    0,
+                )
+            };
+            if added != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // Required to confine a thread without CAP_SYS_ADMIN
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+
+    use super::SandboxPaths;
+
+    pub(super) fn restrict(_paths: &SandboxPaths) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Landlock is only available on Linux",
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_confined_gc_only_writes_below_allowed_paths() {
+        let allowed = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::write(allowed.path().join("artifact"), "a").unwrap();
+        fs::write(outside.path().join("precious"), "p").unwrap();
+        fs::write(outside.path().join("lock"), "").unwrap();
+        let paths = SandboxPaths {
+            dirs: vec![allowed.path().to_path_buf()],
+            files: vec![outside.path().join("lock")],
+        };
+
+        let log = Logger::new(0, true);
+        let supported = imp_supported();
+        let result = run_confined(SandboxMode::Auto, &paths, log, || {
+            fs::remove_file(allowed.path().join("artifact")).unwrap();
+            fs::write(outside.path().join("lock"), "locked").unwrap();
+            let removed = fs::remove_file(outside.path().join("precious"));
+            // Worker threads of the pool are confined as well
+            let created = rayon::join(|| fs::write(outside.path().join("new"), "n"), || ()).0;
+            Ok((removed.is_ok(), created.is_ok()))
+        })
+        .unwrap();
+
+        assert!(!allowed.path().join("artifact").exists());
+        assert_eq!(
+            fs::read_to_string(outside.path().join("lock")).unwrap(),
+            "locked"
+        );
+        if supported {
+            assert_eq!(result, (false, false));
+            assert!(outside.path().join("precious").exists());
+        }
+
+        // This thread was never confined
+        fs::write(outside.path().join("after"), "a").unwrap();
+    }
+
+    /// Whether the kernel running the tests supports Landlock
+    fn imp_supported() -> bool {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<u64>(),
+                0usize,
+                1 as libc::c_uint,
+            )
+        };
+        abi >= 1
+    }
+}
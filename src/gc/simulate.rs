@@ -0,0 +1,158 @@
+//! Candidate policies evaluated side by side by `heave --simulate`.
+
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::config::GcBuilder;
+use super::costs::BuildCosts;
+use super::duration::parse_duration;
+use super::policy::PolicyCommand;
+use super::size::{SizeUnits, parse_max_target_size};
+use crate::error::{HoldError, Result};
+
+/// A candidate GC policy: settings overriding those of the run simulating
+/// it, written as comma-separated `KEY=VALUE` pairs.
+///
+/// - `cap=<SIZE>`: the size cap, like `--max-target-size` (e.g. `5G` or `40%`)
+/// - `files=<N>`: the file count cap, like `--max-target-files`
+/// - `age=<DURATION>`: the age threshold, like `--age-threshold`
+/// - `costs=on|off`: whether recorded build costs weigh the eviction order
+/// - `policy-cmd=<COMMAND>`: a policy command ranking the artifacts, like
+///   `--gc-policy-cmd`; it takes the rest of the policy, commas included
+///
+/// ```
+/// use cargo_hold::gc::SimulatedPolicy;
+///
+/// let policy: SimulatedPolicy = "cap=5G,age=3d".parse()?;
+/// assert_eq!(policy.spec(), "cap=5G,age=3d");
+/// # Ok::<(), cargo_hold::error::HoldError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SimulatedPolicy {
+    spec: String,
+    max_target_size: Option<String>,
+    max_target_files: Option<u64>,
+    age_threshold: Option<Duration>,
+    build_costs: Option<bool>,
+    policy_command: Option<PolicyCommand>,
+}
+
+impl FromStr for SimulatedPolicy {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: String| HoldError::InvalidSimulatedPolicy(s.to_string(), reason);
+        let mut policy = Self {
+            spec: s.trim().to_string(),
+            max_target_size: None,
+            max_target_files: None,
+            age_threshold: None,
+            build_costs: None,
+            policy_command: None,
+        };
+
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let Some((key, value)) = rest.split_once('=') else {
+                return Err(invalid(format!("'{rest}' is not KEY=VALUE")));
+            };
+            let key = key.trim();
+            if key == "policy-cmd" {
+                policy.policy_command = Some(PolicyCommand::new(value.trim()));
+                break;
+            }
+            let (value, next) = value.split_once(',').unwrap_or((value, ""));
+            let value = value.trim();
+            match key {
+                "cap" => policy.max_target_size = Some(value.to_string()),
+                "files" => {
+                    let files = value
+                        .parse()
+                        .map_err(|_| invalid(format!("'{value}' is not a number of files")))?;
+                    policy.max_target_files = Some(files);
+                }
+                "age" => policy.age_threshold = Some(parse_duration(value)?),
+                "costs" => {
+                    policy.build_costs = Some(match value {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(invalid(format!("costs is '{value}', not on or off"))),
+                    });
+                }
+                _ => return Err(invalid(format!("unknown setting '{key}'"))),
+            }
+            rest = next.trim_start();
+        }
+        Ok(policy)
+    }
+}
+
+impl SimulatedPolicy {
+    /// The policy as written
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    /// Override the settings of `builder` that the policy sets, resolving a
+    /// percentage cap against `target_dir` and sizes in `units`
+    pub(crate) fn apply(
+        &self,
+        mut builder: GcBuilder,
+        target_dir: &Path,
+        units: SizeUnits,
+    ) -> Result<GcBuilder> {
+        if let Some(size) = &self.max_target_size {
+            builder = builder.max_target_size(parse_max_target_size(size, target_dir, units)?);
+        }
+        if let Some(files) = self.max_target_files {
+            builder = builder.max_target_files(files);
+        }
+        if let Some(threshold) = self.age_threshold {
+            builder = builder.age_threshold(threshold);
+        }
+        if self.build_costs == Some(false) {
+            // Without recorded costs every crate weighs as much as the median
+            builder = builder.build_costs(BuildCosts::default());
+        }
+        if let Some(command) = &self.policy_command {
+            builder = builder.policy_command(command.clone());
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simulated_policy() {
+        let policy: SimulatedPolicy = "cap=5G, files=20000,age=36h,costs=off".parse().unwrap();
+        assert_eq!(policy.max_target_size.as_deref(), Some("5G"));
+        assert_eq!(policy.max_target_files, Some(20_000));
+        assert_eq!(
+            policy.age_threshold,
+            Some(Duration::from_secs(36 * 60 * 60))
+        );
+        assert_eq!(policy.build_costs, Some(false));
+        assert!(policy.policy_command.is_none());
+
+        // The command keeps its commas
+        let policy: SimulatedPolicy = "age=3d,policy-cmd=jq -c '[.a,.b]'".parse().unwrap();
+        assert_eq!(
+            policy.age_threshold,
+            Some(Duration::from_secs(3 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            policy.policy_command.as_ref().map(PolicyCommand::command),
+            Some("jq -c '[.a,.b]'")
+        );
+
+        assert!("cap".parse::<SimulatedPolicy>().is_err());
+        assert!("order=largest".parse::<SimulatedPolicy>().is_err());
+        assert!("files=many".parse::<SimulatedPolicy>().is_err());
+        assert!("costs=maybe".parse::<SimulatedPolicy>().is_err());
+        assert!("age=soon".parse::<SimulatedPolicy>().is_err());
+    }
+}
@@ -1,8 +1,327 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::ops::AddAssign;
+use std::path::Path;
+use std::str::FromStr;
+
 use crate::error::{HoldError, Result};
 
-/// Parse a size string like "5G", "500M", "1024K" into bytes
-pub(crate) fn parse_size(s: &str) -> Result<u64> {
+/// How file sizes are measured for reporting and the size cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeMode {
+    /// Logical file length, as shown by `ls -l` or `du --apparent-size`
+    Apparent,
+    /// Blocks actually allocated on disk, as shown by `du`
+    ///
+    /// This reflects sparse files and transparent compression on filesystems
+    /// that report compressed block counts (e.g. ZFS).
+    #[default]
+    Allocated,
+}
+
+impl SizeMode {
+    /// Size of a single file in this mode
+    pub(crate) fn measure(self, metadata: &Metadata) -> u64 {
+        match self {
+            Self::Apparent => metadata.len(),
+            Self::Allocated => allocated_size(metadata),
+        }
+    }
+}
+
+impl FromStr for SizeMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "apparent" => Ok(Self::Apparent),
+            "allocated" => Ok(Self::Allocated),
+            _ => Err(HoldError::InvalidSizeMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SizeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Apparent => f.write_str("apparent"),
+            Self::Allocated => f.write_str("allocated"),
+        }
+    }
+}
+
+/// Whether measuring sizes descends into symbolic links to directories.
+///
+/// Artifacts are grouped and removed as links either way: removing a link
+/// never touches what it points to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Count each symbolic link as itself (default), so a cache directory
+    /// linked into the target directory is not counted against it
+    #[default]
+    Skip,
+    /// Count what each symbolic link points to, as earlier releases did; a
+    /// directory reached through several links is counted once per link
+    Follow,
+}
+
+impl SymlinkPolicy {
+    /// Metadata of `path`, or of what it points to when links are followed
+    pub(crate) fn metadata(self, path: &Path) -> std::io::Result<Metadata> {
+        match self {
+            Self::Skip => fs::symlink_metadata(path),
+            Self::Follow => fs::metadata(path),
+        }
+    }
+
+    /// Whether `path` is a directory to descend into
+    pub(crate) fn is_dir(self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "follow" => Ok(Self::Follow),
+            _ => Err(HoldError::InvalidSymlinkPolicy(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Skip => f.write_str("skip"),
+            Self::Follow => f.write_str("follow"),
+        }
+    }
+}
+
+/// How the KB, MB, GB and TB suffixes are read and how sizes are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// KB, MB, GB and TB are powers of 1024, as in earlier releases, and
+    /// sizes are printed in KiB, MiB, GiB and TiB
+    #[default]
+    Binary,
+    /// KB, MB, GB and TB are powers of 1000, like cloud disk sizes, and sizes
+    /// are printed in kB, MB, GB and TB. KiB, MiB, GiB and TiB stay binary.
+    Si,
+}
+
+impl SizeUnits {
+    /// Parse a size string like "5G", "500MB", "1024K" into bytes, reading
+    /// KB, MB, GB and TB in these units. The single-letter suffixes and KiB,
+    /// MiB, GiB and TiB are always binary.
+    pub fn parse(self, s: &str) -> Result<u64> {
+        parse_size_in(s, self)
+    }
+
+    /// Format a size in human-readable format, in these units
+    pub fn format(self, bytes: u64) -> String {
+        format_size_in(bytes, self)
+    }
+}
+
+impl FromStr for SizeUnits {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "binary" => Ok(Self::Binary),
+            "si" => Ok(Self::Si),
+            _ => Err(HoldError::InvalidSizeUnits(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SizeUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Binary => f.write_str("binary"),
+            Self::Si => f.write_str("si"),
+        }
+    }
+}
+
+/// Size of a set of files measured both ways
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskSizes {
+    /// Sum of logical file lengths
+    pub apparent: u64,
+    /// Sum of allocated blocks
+    pub allocated: u64,
+    /// Number of files and directories (inodes)
+    pub files: u64,
+}
+
+impl DiskSizes {
+    /// Get the size for a given mode
+    pub fn get(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.apparent,
+            SizeMode::Allocated => self.allocated,
+        }
+    }
+
+    /// Sizes of a single file, regardless of other links to it
+    pub(crate) fn of_file(metadata: &Metadata) -> Self {
+        Self {
+            apparent: metadata.len(),
+            allocated: allocated_size(metadata),
+            files: 1,
+        }
+    }
+}
+
+impl AddAssign for DiskSizes {
+    fn add_assign(&mut self, other: Self) {
+        self.apparent += other.apparent;
+        self.allocated += other.allocated;
+        self.files += other.files;
+    }
+}
+
+/// Disk space actually allocated to a file.
+///
+/// On Unix this is `st_blocks * 512`, which is smaller than the logical length
+/// for sparse files (e.g. debuginfo) and reflects what a disk-space cap cares
+/// about. Other platforms fall back to the logical length.
+fn allocated_size(metadata: &Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.blocks().saturating_mul(512)
+    }
+
+    #[cfg(not(unix))]
+    {
+        metadata.len()
+    }
+}
+
+/// Measures disk usage across many files, counting each hard-linked inode
+/// only once.
+#[derive(Debug, Default)]
+pub(crate) struct DiskUsage {
+    /// (device, inode) pairs of multiply-linked files already counted
+    seen: HashSet<(u64, u64)>,
+    /// Whether symbolic links are measured as what they point to
+    symlinks: SymlinkPolicy,
+}
+
+impl DiskUsage {
+    /// Measure following symbolic links as `symlinks` says
+    pub(crate) fn new(symlinks: SymlinkPolicy) -> Self {
+        Self {
+            seen: HashSet::new(),
+            symlinks,
+        }
+    }
+
+    /// Whether symbolic links are measured as what they point to
+    pub(crate) fn symlinks(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+
+    /// Sizes contributed by a file, or zero if another link to the same inode
+    /// was already counted.
+    pub(crate) fn measure(&mut self, metadata: &Metadata) -> DiskSizes {
+        let sizes = DiskSizes::of_file(metadata);
+        match hard_link_id(metadata) {
+            Some(id) => self.measure_linked(id, sizes),
+            None => sizes,
+        }
+    }
+
+    /// `sizes` of a multiply-linked file identified by (device, inode), or
+    /// zero if another link to it was already counted.
+    pub(crate) fn measure_linked(&mut self, id: (u64, u64), sizes: DiskSizes) -> DiskSizes {
+        if self.seen.insert(id) {
+            sizes
+        } else {
+            DiskSizes::default()
+        }
+    }
+}
+
+/// (device, inode) of a file with more than one hard link
+pub(crate) fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Filesystems whose block counts diverge from logical sizes because of
+/// transparent compression or copy-on-write sharing.
+const COMPRESSING_FILESYSTEMS: &[&str] = &["btrfs", "zfs", "bcachefs"];
+
+/// Name of the filesystem holding `path` if it may transparently compress
+/// data, so callers can explain why apparent and allocated sizes differ.
+///
+/// Only implemented on Linux (via `/proc/self/mounts`); returns `None`
+/// elsewhere or when the mount table cannot be read.
+pub(crate) fn compressing_filesystem(path: &Path) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let path = fs::canonicalize(path).ok()?;
+        let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+        let fs_type = mount_fs_type(&mounts, &path)?;
+        COMPRESSING_FILESYSTEMS
+            .contains(&fs_type)
+            .then(|| fs_type.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Find the filesystem type of the deepest mount point containing `path` in
+/// a `/proc/mounts`-formatted table.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_fs_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (Path::new(&mount_point).components().count(), fs_type))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Parse a size string like "5G", "500M", "1024K" into bytes, reading KB,
+/// MB, GB and TB as [`SizeUnits::Binary`]
+pub fn parse_size(s: &str) -> Result<u64> {
+    SizeUnits::Binary.parse(s)
+}
+
+fn parse_size_in(s: &str, units: SizeUnits) -> Result<u64> {
     let s = s.trim();
+    let kilo: u64 = match units {
+        SizeUnits::Binary => 1024,
+        SizeUnits::Si => 1000,
+    };
 
     // Try to parse as raw number first
     if let Ok(bytes) = s.parse::<u64>() {
@@ -13,10 +332,14 @@ pub(crate) fn parse_size(s: &str) -> Result<u64> {
     let (num_part, suffix) = split_number_suffix(s)?;
     let multiplier = match suffix.to_uppercase().as_str() {
         "B" | "" => 1,
-        "K" | "KB" | "KIB" => 1024,
-        "M" | "MB" | "MIB" => 1024 * 1024,
-        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
-        "T" | "TB" | "TIB" => 1024_u64.pow(4),
+        "K" | "KIB" => 1024,
+        "M" | "MIB" => 1024 * 1024,
+        "G" | "GIB" => 1024 * 1024 * 1024,
+        "T" | "TIB" => 1024_u64.pow(4),
+        "KB" => kilo,
+        "MB" => kilo.pow(2),
+        "GB" => kilo.pow(3),
+        "TB" => kilo.pow(4),
         _ => {
             return Err(HoldError::InvalidMetadataSize(
                 s.to_string(),
@@ -28,8 +351,56 @@ pub(crate) fn parse_size(s: &str) -> Result<u64> {
     let base: f64 = num_part.parse().map_err(|_| {
         HoldError::InvalidMetadataSize(s.to_string(), "Invalid number format".to_string())
     })?;
+    let bytes = base * multiplier as f64;
+    if !bytes.is_finite() || bytes < 0.0 || bytes >= u64::MAX as f64 {
+        return Err(HoldError::InvalidMetadataSize(
+            s.to_string(),
+            "Size out of range".to_string(),
+        ));
+    }
+
+    Ok(bytes as u64)
+}
+
+/// Parse a `--max-target-size` value into bytes: a size in `units`, or a
+/// percentage of the filesystem holding `target_dir` (e.g. "40%"), so one
+/// setting fits runners with different disks.
+pub(crate) fn parse_max_target_size(s: &str, target_dir: &Path, units: SizeUnits) -> Result<u64> {
+    let invalid =
+        |reason: &str| HoldError::InvalidMetadataSize(s.trim().to_string(), reason.into());
+
+    let size = match s.trim().strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| invalid("Invalid percentage"))?;
+            if !(percent > 0.0 && percent <= 100.0) {
+                return Err(invalid("Percentage must be above 0% and at most 100%"));
+            }
+            let total = filesystem_size(target_dir)?;
+            (total as f64 * percent / 100.0) as u64
+        }
+        None => units.parse(s)?,
+    };
+
+    if size == 0 {
+        return Err(invalid("A size of zero would evict everything"));
+    }
+    Ok(size)
+}
 
-    Ok((base * multiplier as f64) as u64)
+/// Total size of the filesystem holding `path`. The target directory may
+/// not exist yet, so the nearest existing ancestor is measured.
+fn filesystem_size(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    fs4::total_space(existing).map_err(|source| HoldError::IoError {
+        path: existing.to_path_buf(),
+        source,
+    })
 }
 
 /// Split a size string into number and suffix parts
@@ -53,21 +424,28 @@ fn split_number_suffix(s: &str) -> Result<(&str, &str)> {
     Ok((num, suffix))
 }
 
-/// Format size in human-readable format
-pub(crate) fn format_size(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+/// Format size in human-readable format, in [`SizeUnits::Binary`]
+pub fn format_size(bytes: u64) -> String {
+    SizeUnits::Binary.format(bytes)
+}
+
+fn format_size_in(bytes: u64, units: SizeUnits) -> String {
+    let (base, units): (f64, &[&str]) = match units {
+        SizeUnits::Binary => (1024.0, &["B", "KiB", "MiB", "GiB", "TiB"]),
+        SizeUnits::Si => (1000.0, &["B", "kB", "MB", "GB", "TB"]),
+    };
     let mut size = bytes as f64;
     let mut unit_idx = 0;
 
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
+    while size >= base && unit_idx < units.len() - 1 {
+        size /= base;
         unit_idx += 1;
     }
 
     if unit_idx == 0 {
-        format!("{} {}", bytes, UNITS[0])
+        format!("{} {}", bytes, units[0])
     } else {
-        format!("{:.1} {}", size, UNITS[unit_idx])
+        format!("{:.1} {}", size, units[unit_idx])
     }
 }
 
@@ -75,6 +453,33 @@ pub(crate) fn format_size(bytes: u64) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_size_mode() {
+        assert_eq!("apparent".parse::<SizeMode>().unwrap(), SizeMode::Apparent);
+        assert_eq!(
+            "Allocated".parse::<SizeMode>().unwrap(),
+            SizeMode::Allocated
+        );
+        assert!("logical".parse::<SizeMode>().is_err());
+        assert_eq!(SizeMode::default().to_string(), "allocated");
+    }
+
+    #[test]
+    fn test_mount_fs_type_picks_deepest_mount() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 /srv/ci btrfs rw,compress=zstd 0 \
+                      0\ntmpfs /srv/ci\\040cache tmpfs rw 0 0\n";
+
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/srv/ci/target")),
+            Some("btrfs")
+        );
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/srv/ci cache/target")),
+            Some("tmpfs")
+        );
+        assert_eq!(mount_fs_type(mounts, Path::new("/home/user")), Some("ext4"));
+    }
+
     #[test]
     fn test_parse_size() {
         assert_eq!(parse_size("100").unwrap(), 100);
@@ -98,6 +503,61 @@ mod tests {
         assert!(parse_size("100X").is_err());
     }
 
+    #[test]
+    fn test_parse_size_rejects_out_of_range_sizes() {
+        assert!(parse_size("-5G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn test_parse_max_target_size_percentage() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let target_dir = temp_dir.path().join("not-built-yet/target");
+        let total = fs4::total_space(temp_dir.path()).unwrap();
+
+        assert_eq!(
+            parse_max_target_size("40%", &target_dir, SizeUnits::Binary).unwrap(),
+            (total as f64 * 0.4) as u64
+        );
+        assert_eq!(
+            parse_max_target_size(" 100 % ", &target_dir, SizeUnits::Binary).unwrap(),
+            total
+        );
+        assert_eq!(
+            parse_max_target_size("5G", &target_dir, SizeUnits::Binary).unwrap(),
+            5 << 30
+        );
+
+        for invalid in ["0", "0%", "150%", "-10%", "abc%", "%"] {
+            assert!(
+                matches!(
+                    parse_max_target_size(invalid, &target_dir, SizeUnits::Binary),
+                    Err(HoldError::InvalidMetadataSize(..))
+                ),
+                "{invalid} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_si_units_read_and_print_powers_of_1000() {
+        assert_eq!(SizeUnits::Binary.parse("3GB").unwrap(), 3 << 30);
+        assert_eq!(SizeUnits::Si.parse("3GB").unwrap(), 3_000_000_000);
+        assert_eq!(SizeUnits::Si.parse("2kb").unwrap(), 2000);
+        assert_eq!(SizeUnits::Si.parse("1.5TB").unwrap(), 1_500_000_000_000);
+        assert_eq!(SizeUnits::Si.parse("3GiB").unwrap(), 3 << 30);
+        assert_eq!(SizeUnits::Si.parse("3G").unwrap(), 3 << 30);
+
+        assert_eq!(SizeUnits::Si.format(999), "999 B");
+        assert_eq!(SizeUnits::Si.format(1500), "1.5 kB");
+        assert_eq!(SizeUnits::Si.format(250_000_000_000), "250.0 GB");
+        assert_eq!(SizeUnits::Binary.format(1536), "1.5 KiB");
+
+        assert_eq!("SI".parse::<SizeUnits>().unwrap(), SizeUnits::Si);
+        assert_eq!(SizeUnits::default().to_string(), "binary");
+        assert!("decimal".parse::<SizeUnits>().is_err());
+    }
+
     #[test]
     fn test_format_size() {
         assert_eq!(format_size(0), "0 B");
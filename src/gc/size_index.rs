@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::cleanup::{calculate_disk_usage_following, calculate_disk_usage_with};
+use super::size::{DiskSizes, DiskUsage, SymlinkPolicy, hard_link_id};
+use crate::error::{HoldError, Result};
+use crate::state::{DirectorySize, LinkedFileSize};
+
+/// Directories modified this close to a scan may change again within the
+/// same mtime tick, so they are scanned again next time instead of trusted
+const RACY_MTIME_WINDOW: Duration = Duration::from_secs(2);
+
+/// Index of directory sizes that lets repeated scans of a target directory
+/// skip the files of directories that have not changed.
+///
+/// A directory's mtime changes whenever an entry is added, removed or
+/// renamed in it, which is how cargo and rustc replace build outputs. Files
+/// rewritten in place keep their old size in the index until their directory
+/// changes.
+#[derive(Debug, Default)]
+pub(crate) struct SizeIndex {
+    /// Entries keyed by directory relative to the scanned root (`""` for the
+    /// root itself)
+    entries: HashMap<String, DirectorySize>,
+}
+
+impl SizeIndex {
+    /// Index starting from the entries recorded by a previous scan
+    pub(crate) fn new(entries: HashMap<String, DirectorySize>) -> Self {
+        Self { entries }
+    }
+
+    /// Whether no directories are indexed yet
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Take the entries recorded by the last scan
+    pub(crate) fn take_entries(&mut self) -> HashMap<String, DirectorySize> {
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Measure `root`, reusing the entries of directories whose mtime is
+    /// unchanged and scanning the rest.
+    ///
+    /// Afterwards the index holds exactly the directories found below `root`.
+    pub(crate) fn measure(&mut self, root: &Path, symlinks: SymlinkPolicy) -> Result<DiskSizes> {
+        if !symlinks.is_dir(root) {
+            self.entries.clear();
+            return calculate_disk_usage_following(root, symlinks);
+        }
+
+        let mut scan = Scan {
+            previous: std::mem::take(&mut self.entries),
+            entries: HashMap::new(),
+            usage: DiskUsage::new(symlinks),
+            racy_cutoff: mtime_nanos(SystemTime::now() - RACY_MTIME_WINDOW),
+        };
+        let sizes = scan.measure_dir(root, String::new())?;
+        self.entries = scan.entries;
+
+        Ok(sizes)
+    }
+}
+
+/// State of a single scan over a directory tree
+struct Scan {
+    /// Entries from the previous scan, removed as they are visited
+    previous: HashMap<String, DirectorySize>,
+    /// Entries recorded by this scan
+    entries: HashMap<String, DirectorySize>,
+    /// Hard links counted so far
+    usage: DiskUsage,
+    /// Directories with an mtime at or after this are not recorded
+    racy_cutoff: u128,
+}
+
+impl Scan {
+    fn measure_dir(&mut self, dir: &Path, key: String) -> Result<DiskSizes> {
+        let mtime = match self.usage.symlinks().metadata(dir) {
+            // A link indexed as a directory while links were followed
+            Ok(metadata) if !metadata.is_dir() => return Ok(self.usage.measure(&metadata)),
+            Ok(metadata) => metadata.modified().ok().map(mtime_nanos),
+            // Removed since its parent was listed
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(DiskSizes::default());
+            }
+            Err(source) => {
+                return Err(HoldError::IoError {
+                    path: dir.to_path_buf(),
+                    source,
+                });
+            }
+        };
+
+        let (entry, unindexed_subdirs) = match self.previous.remove(&key) {
+            Some(entry) if mtime == Some(entry.mtime_nanos) => (entry, Vec::new()),
+            _ => scan_dir(dir, mtime.unwrap_or_default(), self.usage.symlinks())?,
+        };
+
+        let mut sizes = DiskSizes {
+            apparent: entry.apparent,
+            allocated: entry.allocated,
+            // The directory itself counts too
+            files: entry.files + 1,
+        };
+        for file in &entry.linked_files {
+            sizes += self.usage.measure_linked(
+                (file.dev, file.ino),
+                DiskSizes {
+                    apparent: file.apparent,
+                    allocated: file.allocated,
+                    files: 1,
+                },
+            );
+        }
+        for name in &entry.subdirs {
+            let child_key = if key.is_empty() {
+                name.clone()
+            } else {
+                format!("{key}/{name}")
+            };
+            sizes += self.measure_dir(&dir.join(name), child_key)?;
+        }
+        for subdir in &unindexed_subdirs {
+            sizes += calculate_disk_usage_with(subdir, &mut self.usage)?;
+        }
+
+        // Directories with names the index cannot hold are always rescanned
+        if unindexed_subdirs.is_empty() && mtime.is_some_and(|mtime| mtime < self.racy_cutoff) {
+            self.entries.insert(key, entry);
+        }
+
+        Ok(sizes)
+    }
+}
+
+/// Measure the files directly inside `dir`, returning its index entry and
+/// any subdirectories whose names are not valid UTF-8
+fn scan_dir(
+    dir: &Path,
+    mtime_nanos: u128,
+    symlinks: SymlinkPolicy,
+) -> Result<(DirectorySize, Vec<PathBuf>)> {
+    let mut entry = DirectorySize {
+        mtime_nanos,
+        ..DirectorySize::default()
+    };
+    let mut unindexed_subdirs = Vec::new();
+
+    let entries = fs::read_dir(dir).map_err(|source| HoldError::IoError {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    for dir_entry in entries {
+        let dir_entry = dir_entry.map_err(|source| HoldError::IoError {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        let path = dir_entry.path();
+        let Ok(file_type) = dir_entry.file_type() else {
+            continue;
+        };
+        let follow = file_type.is_symlink() && symlinks == SymlinkPolicy::Follow;
+
+        if file_type.is_dir() || (follow && path.is_dir()) {
+            match dir_entry.file_name().into_string() {
+                Ok(name) => entry.subdirs.push(name),
+                Err(_) => unindexed_subdirs.push(path),
+            }
+        } else if file_type.is_file() || file_type.is_symlink() {
+            let metadata = match symlinks.metadata(&path) {
+                Ok(metadata) => metadata,
+                // Removed since listed, or a dangling link being followed
+                Err(source) if source.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(HoldError::IoError { path, source }),
+            };
+            let sizes = DiskSizes::of_file(&metadata);
+            match hard_link_id(&metadata) {
+                Some((dev, ino)) => entry.linked_files.push(LinkedFileSize {
+                    dev,
+                    ino,
+                    apparent: sizes.apparent,
+                    allocated: sizes.allocated,
+                }),
+                None => {
+                    entry.apparent += sizes.apparent;
+                    entry.allocated += sizes.allocated;
+                    entry.files += 1;
+                }
+            }
+        }
+    }
+
+    entry.subdirs.sort();
+    Ok((entry, unindexed_subdirs))
+}
+
+fn mtime_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use filetime::FileTime;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::gc::calculate_disk_usage;
+
+    /// An mtime well before any scan in these tests
+    const SETTLED: i64 = 1_700_000_000;
+
+    /// Backdate the mtime of `dir` to `unix_secs` so scans trust it
+    fn settle(dir: &Path, unix_secs: i64) {
+        filetime::set_file_mtime(dir, FileTime::from_unix_time(unix_secs, 0)).unwrap();
+    }
+
+    fn target() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let deps = temp_dir.path().join("debug/deps");
+        fs::create_dir_all(&deps).unwrap();
+        fs::write(deps.join("libfoo.rlib"), [0u8; 1000]).unwrap();
+        fs::write(temp_dir.path().join("debug/foo.d"), [0u8; 10]).unwrap();
+        for dir in ["debug/deps", "debug", ""] {
+            settle(&temp_dir.path().join(dir), SETTLED);
+        }
+        temp_dir
+    }
+
+    #[test]
+    fn test_measure_matches_full_walk_and_records_directories() {
+        let temp_dir = target();
+        let mut index = SizeIndex::default();
+
+        let sizes = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+        assert_eq!(sizes, calculate_disk_usage(temp_dir.path()).unwrap());
+
+        let mut keys: Vec<_> = index.entries.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["", "debug", "debug/deps"]);
+    }
+
+    #[test]
+    fn test_measure_reuses_unchanged_directories() {
+        let temp_dir = target();
+        let deps = temp_dir.path().join("debug/deps");
+        let mut index = SizeIndex::default();
+        let before = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+
+        // Rewriting a file in place leaves the directory mtime alone, so the
+        // recorded size is reused
+        fs::write(deps.join("libfoo.rlib"), [0u8; 5000]).unwrap();
+        settle(&deps, SETTLED);
+        assert_eq!(
+            index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap(),
+            before
+        );
+
+        // Adding a file changes the directory mtime, so it is scanned again
+        fs::write(deps.join("libbar.rlib"), [0u8; 100]).unwrap();
+        settle(&deps, SETTLED + 1);
+        let after = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+        assert_eq!(after.apparent, 5000 + 100 + 10);
+    }
+
+    #[test]
+    fn test_measure_drops_removed_directories() {
+        let temp_dir = target();
+        let mut index = SizeIndex::default();
+        index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+
+        fs::remove_dir_all(temp_dir.path().join("debug/deps")).unwrap();
+        let sizes = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+
+        assert_eq!(sizes.apparent, 10);
+        assert!(!index.entries.contains_key("debug/deps"));
+    }
+
+    #[test]
+    fn test_measure_does_not_record_recently_modified_directories() {
+        let temp_dir = target();
+        fs::write(temp_dir.path().join("debug/bar.d"), [0u8; 10]).unwrap();
+        let mut index = SizeIndex::default();
+        index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+
+        assert!(!index.entries.contains_key("debug"));
+        assert!(index.entries.contains_key("debug/deps"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_measure_counts_hard_links_once_across_reused_directories() {
+        let temp_dir = target();
+        fs::hard_link(
+            temp_dir.path().join("debug/deps/libfoo.rlib"),
+            temp_dir.path().join("debug/libfoo.rlib"),
+        )
+        .unwrap();
+        for dir in ["debug/deps", "debug"] {
+            settle(&temp_dir.path().join(dir), SETTLED);
+        }
+        let mut index = SizeIndex::default();
+
+        let first = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+        let second = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+
+        assert_eq!(first.apparent, 1000 + 10);
+        assert_eq!(second, first);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_measure_follows_symlinks_as_the_policy_says() {
+        let temp_dir = target();
+        let cache = TempDir::new().unwrap();
+        fs::write(cache.path().join("blob"), [0u8; 4000]).unwrap();
+        std::os::unix::fs::symlink(cache.path(), temp_dir.path().join("debug/cache")).unwrap();
+        settle(&temp_dir.path().join("debug"), SETTLED);
+        settle(cache.path(), SETTLED);
+
+        let mut index = SizeIndex::default();
+        let skipped = index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap();
+        assert_eq!(skipped, calculate_disk_usage(temp_dir.path()).unwrap());
+        assert!(skipped.apparent < 4000);
+
+        // Entries recorded while skipping links are trusted; a fresh index
+        // descends into the link
+        let mut index = SizeIndex::default();
+        let followed = index
+            .measure(temp_dir.path(), SymlinkPolicy::Follow)
+            .unwrap();
+        assert_eq!(followed.apparent, 1000 + 10 + 4000);
+        assert!(index.entries.contains_key("debug/cache"));
+
+        // Switching back measures the link as itself, not as the directory
+        // it was indexed as
+        assert_eq!(
+            index.measure(temp_dir.path(), SymlinkPolicy::Skip).unwrap(),
+            skipped
+        );
+    }
+}
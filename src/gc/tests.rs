@@ -4,9 +4,21 @@ use std::time::{Duration, SystemTime};
 use proptest::prelude::*;
 
 use super::artifacts::{
-    ArtifactInfo, CrateArtifact, parse_crate_artifact_name, select_artifacts_for_removal,
+    ArtifactInfo, Budget, CrateArtifact, PreservePolicy, exclude_build_units,
+    parse_crate_artifact_name, select_artifacts_for_removal,
 };
-use super::size::{format_size, parse_size};
+use super::cleanup::{
+    calculate_disk_usage, calculate_disk_usage_following, calculate_disk_usage_within,
+    clean_named_crate,
+};
+use super::config::Gc;
+use super::costs::{BuildCosts, weigh_build_costs};
+use super::filter::PathFilter;
+use super::pins::{PinnedCrates, exclude_pinned, validate_pin_pattern};
+use super::profiles::profile_key;
+use super::size::{SizeMode, SymlinkPolicy, format_size, parse_size};
+use super::units::BuildUnits;
+use crate::logging::Logger;
 
 // Property test strategies
 
@@ -94,6 +106,10 @@ proptest! {
 
 // Helper functions
 
+fn days(days: u64) -> Duration {
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
 fn create_test_artifact(name: &str, hash: &str, size: u64, age_days: u64) -> CrateArtifact {
     let mtime = SystemTime::now()
         .checked_sub(Duration::from_secs(age_days * 24 * 60 * 60))
@@ -105,10 +121,28 @@ fn create_test_artifact(name: &str, hash: &str, size: u64, age_days: u64) -> Cra
         artifacts: vec![ArtifactInfo {
             path: PathBuf::from(format!("target/debug/deps/lib{name}-{hash}.rlib")),
             size,
-            _modified: mtime,
+            modified: mtime,
         }],
         total_size: size,
         newest_mtime: mtime,
+        eviction_score: 0.0,
+        rebuild_secs: None,
+        inputs_unchanged: false,
+    }
+}
+
+fn size_budget(size: u64, max_size: Option<u64>) -> Budget {
+    Budget {
+        size,
+        max_size,
+        ..Budget::default()
+    }
+}
+
+fn artifact_with_mtime(name: &str, hash: &str, size: u64, mtime: SystemTime) -> CrateArtifact {
+    CrateArtifact {
+        newest_mtime: mtime,
+        ..create_test_artifact(name, hash, size, 0)
     }
 }
 
@@ -128,7 +162,13 @@ fn test_combined_selection_size_and_age() {
     // Set max size to 6KB (need to free 4.5KB)
     // Set age threshold to 10 days (should remove artifacts older than 10 days)
 
-    let selected = select_artifacts_for_removal(&artifacts, 10500, Some(6000), 10, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(10500, Some(6000)),
+        days(10),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should remove:
     // 1. old_large (5KB) to get under size limit (leaves 5.5KB)
@@ -151,7 +191,13 @@ fn test_combined_selection_only_age() {
     // Total size: 4KB, max size: 10KB (no size pressure)
     // Age threshold: 10 days
 
-    let selected = select_artifacts_for_removal(&artifacts, 4000, Some(10000), 10, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(4000, Some(10000)),
+        days(10),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should only remove artifacts older than 10 days
     assert_eq!(selected.len(), 2);
@@ -172,7 +218,13 @@ fn test_combined_selection_only_size() {
     // Total size: 10.5KB, max size: 5KB
     // Age threshold: 30 days (nothing is old enough)
 
-    let selected = select_artifacts_for_removal(&artifacts, 10500, Some(5000), 30, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(10500, Some(5000)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should remove oldest first until under size limit
     // Removes: small1 (3 days), large1 (2 days) = 6KB freed (enough to get under
@@ -190,7 +242,13 @@ fn test_combined_selection_no_size_limit() {
         create_test_artifact("new", "2234567890abcdef", 10000, 5),
     ];
 
-    let selected = select_artifacts_for_removal(&artifacts, 20000, None, 10, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(20000, None),
+        days(10),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should only remove the old artifact
     assert_eq!(selected.len(), 1);
@@ -207,7 +265,13 @@ fn test_combined_selection_everything_removed() {
     ];
 
     // Total: 15KB, max size: 0KB, age threshold: 30 days
-    let selected = select_artifacts_for_removal(&artifacts, 15000, Some(0), 30, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(15000, Some(0)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // All artifacts should be selected for removal
     assert_eq!(selected.len(), 3);
@@ -223,7 +287,13 @@ fn test_combined_selection_exact_size_limit() {
     ];
 
     // Total: 6KB, max size: 6KB exactly
-    let selected = select_artifacts_for_removal(&artifacts, 6000, Some(6000), 10, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(6000, Some(6000)),
+        days(10),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should only remove artifacts older than 10 days
     assert_eq!(selected.len(), 2);
@@ -241,7 +311,13 @@ fn test_combined_selection_zero_age_threshold() {
     ];
 
     // Total: 6KB, max size: 10KB (no size pressure), age threshold: 0 days
-    let selected = select_artifacts_for_removal(&artifacts, 6000, Some(10000), 0, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(6000, Some(10000)),
+        days(0),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // All artifacts should be removed (all are >= 0 days old)
     assert_eq!(selected.len(), 3);
@@ -266,7 +342,13 @@ fn test_combined_selection_same_timestamps() {
     }
 
     // Total: 6KB, max size: 4KB, age threshold: 10 days
-    let selected = select_artifacts_for_removal(&artifacts, 6000, Some(4000), 10, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(6000, Some(4000)),
+        days(10),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
 
     // Should remove enough for size (at least 2KB) and all are old enough
     // Since they have same timestamp, the order might be implementation-dependent
@@ -277,11 +359,156 @@ fn test_combined_selection_same_timestamps() {
     assert!(removed_size >= 2000); // Need to free at least 2KB
 }
 
+#[test]
+fn test_combined_selection_prefers_higher_eviction_scores() {
+    let mut artifacts = vec![
+        create_test_artifact("oldest", "1234567890abcdef", 1000, 5),
+        create_test_artifact("scored", "2234567890abcdef", 1000, 1),
+        create_test_artifact("older", "3234567890abcdef", 1000, 4),
+    ];
+    artifacts[1].eviction_score = 10.0;
+
+    // Need to free 2KB: the scored artifact goes first despite being newest,
+    // then age breaks the tie between the unscored ones
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(3000, Some(1000)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, true),
+    );
+
+    let names: Vec<_> = selected.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["scored", "oldest"]);
+}
+
+#[test]
+fn test_size_selection_breaks_mtime_ties_by_name_and_hash() {
+    let mtime = SystemTime::now() - days(1);
+    let artifacts = vec![
+        artifact_with_mtime("zeta", "1234567890abcdef", 1000, mtime),
+        artifact_with_mtime("alpha", "ffff567890abcdef", 1000, mtime),
+        artifact_with_mtime("alpha", "0000567890abcdef", 1000, mtime),
+    ];
+
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(3000, Some(1000)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, true),
+    );
+
+    let keys: Vec<_> = selected
+        .iter()
+        .map(|a| (a.name.as_str(), a.hash.as_str()))
+        .collect();
+    assert_eq!(
+        keys,
+        [("alpha", "0000567890abcdef"), ("alpha", "ffff567890abcdef")]
+    );
+}
+
+#[test]
+fn test_size_selection_enforces_file_limit() {
+    let mut many_files = create_test_artifact("fingerprints", "1234567890abcdef", 100, 3);
+    many_files.artifacts = (0..50)
+        .map(|index| ArtifactInfo {
+            path: PathBuf::from(format!("target/debug/.fingerprint/f-{index}")),
+            size: 2,
+            modified: many_files.newest_mtime,
+        })
+        .collect();
+    let artifacts = vec![
+        create_test_artifact("oldest", "abcdef1234567890", 1000, 5),
+        many_files,
+        create_test_artifact("newest", "fedcba0987654321", 1000, 1),
+    ];
+
+    // Plenty of room for bytes, but 10 files over the file limit
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &Budget {
+            size: 2100,
+            max_size: Some(10_000),
+            files: 52,
+            max_files: Some(42),
+        },
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, true),
+    );
+
+    let names: Vec<_> = selected.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["oldest", "fingerprints"]);
+}
+
+/// Name and hash, then size (in KB) and age (in hours) of a generated artifact
+type ArtifactSpec = ((String, String), (u64, u64));
+
+/// Unique artifacts with only a few distinct sizes and ages, so ties are
+/// common, paired with a shuffled copy
+fn tied_specs_strategy() -> impl Strategy<Value = (Vec<ArtifactSpec>, Vec<ArtifactSpec>)> {
+    prop::collection::btree_map(
+        (crate_name_strategy(), hash_strategy()),
+        (1u64..4, 0u64..3),
+        1..24,
+    )
+    .prop_flat_map(|specs| {
+        let specs: Vec<_> = specs.into_iter().collect();
+        (Just(specs.clone()), Just(specs).prop_shuffle())
+    })
+}
+
+fn artifacts_from_specs(specs: &[ArtifactSpec], base: SystemTime) -> Vec<CrateArtifact> {
+    specs
+        .iter()
+        .map(|((name, hash), (size, age_hours))| {
+            let mtime = base - Duration::from_secs(age_hours * 60 * 60);
+            artifact_with_mtime(name, hash, size * 1000, mtime)
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn test_selection_is_independent_of_input_order(
+        (specs, shuffled) in tied_specs_strategy(),
+        max_fraction in 0u64..=100,
+    ) {
+        let base = SystemTime::now() - days(1);
+        let artifacts = artifacts_from_specs(&specs, base);
+        let shuffled = artifacts_from_specs(&shuffled, base);
+        let total: u64 = artifacts.iter().map(|a| a.total_size).sum();
+        let max_size = total * max_fraction / 100;
+        let select = |artifacts: &[CrateArtifact]| -> Vec<(String, String)> {
+            select_artifacts_for_removal(
+                artifacts,
+                &size_budget(total, Some(max_size)),
+                days(7),
+                &PreservePolicy::default(),
+                &Logger::new(0, true),
+            )
+            .into_iter()
+            .map(|a| (a.name.clone(), a.hash.clone()))
+            .collect()
+        };
+
+        prop_assert_eq!(select(&artifacts), select(&shuffled));
+    }
+}
+
 #[test]
 fn test_combined_selection_empty_list() {
     // Test with empty artifact list
     let artifacts = vec![];
-    let selected = select_artifacts_for_removal(&artifacts, 0, Some(1000), 7, None, 0, false);
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(0, Some(1000)),
+        days(7),
+        &PreservePolicy::default(),
+        &Logger::new(0, false),
+    );
     assert_eq!(selected.len(), 0);
 }
 
@@ -320,12 +547,13 @@ fn test_combined_selection_preserves_previous_build_artifacts() {
 
     let selected = select_artifacts_for_removal(
         &artifacts,
-        14000,
-        Some(6000),
-        30, // High age threshold so it doesn't interfere
-        Some(previous_build_nanos),
-        2, // verbose
-        false,
+        &size_budget(14000, Some(6000)),
+        days(30), // High age threshold so it doesn't interfere
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(
+            2, // verbose
+            false,
+        ),
     );
 
     // Should only remove the old artifact (5KB), not enough to meet size limit
@@ -365,12 +593,10 @@ fn test_combined_selection_timestamp_buffer_edge_case() {
 
     let selected = select_artifacts_for_removal(
         &artifacts,
-        4000,
-        Some(2000), // Need to remove 2KB
-        30,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(4000, Some(2000)), // Need to remove 2KB
+        days(30),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
 
     // With the 5-minute buffer, artifacts near the cutoff should be preserved; only
@@ -408,12 +634,10 @@ fn test_combined_selection_exceeds_size_for_preservation() {
     // But recent artifacts (15KB) should be preserved
     let selected = select_artifacts_for_removal(
         &artifacts,
-        19000,
-        Some(5000),
-        30,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(19000, Some(5000)),
+        days(30),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
 
     // Should only select old artifacts
@@ -434,12 +658,13 @@ fn test_combined_selection_no_previous_build_timestamp() {
     // Total: 12KB, max size: 6KB
     let selected = select_artifacts_for_removal(
         &artifacts,
-        12000,
-        Some(6000),
-        30,
-        None, // No previous build timestamp
-        0,
-        false,
+        &size_budget(12000, Some(6000)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(
+            // No previous build timestamp
+            0, false,
+        ),
     );
 
     // Should remove oldest first until under size limit
@@ -474,12 +699,10 @@ fn test_combined_selection_all_artifacts_are_recent() {
     // Total: 15KB, max size: 5KB
     let selected = select_artifacts_for_removal(
         &artifacts,
-        15000,
-        Some(5000),
-        30,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(15000, Some(5000)),
+        days(30),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
 
     // Nothing should be selected - all artifacts are preserved
@@ -522,12 +745,10 @@ fn test_combined_selection_mixed_ages_with_preservation() {
     // Total: 15KB, max size: 8KB, age threshold: 5 days
     let selected = select_artifacts_for_removal(
         &artifacts,
-        15000,
-        Some(8000),
-        5,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(15000, Some(8000)),
+        days(5),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
 
     // Should remove:
@@ -579,6 +800,117 @@ fn test_format_size() {
     assert_eq!(format_size(1024_u64.pow(4)), "1.0 TiB");
 }
 
+#[cfg(unix)]
+#[test]
+fn test_directory_size_counts_hard_links_once() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let original = temp_dir
+        .path()
+        .join("deps")
+        .join("libfoo-0123456789abcdef.rlib");
+    std::fs::create_dir_all(original.parent().unwrap()).unwrap();
+    std::fs::write(&original, vec![1u8; 64 * 1024]).unwrap();
+    let single = calculate_disk_usage(temp_dir.path()).unwrap();
+    assert_eq!(single.apparent, 64 * 1024);
+    assert!(single.allocated > 0);
+
+    std::fs::hard_link(&original, temp_dir.path().join("libfoo.rlib")).unwrap();
+    assert_eq!(calculate_disk_usage(temp_dir.path()).unwrap(), single);
+}
+
+#[test]
+fn test_directory_usage_counts_files_and_directories() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let deps = temp_dir.path().join("debug").join("deps");
+    std::fs::create_dir_all(&deps).unwrap();
+    std::fs::write(deps.join("libfoo.rlib"), [0u8; 10]).unwrap();
+    std::fs::write(deps.join("libfoo.rmeta"), [0u8; 10]).unwrap();
+
+    // The root, debug and deps directories plus two files
+    assert_eq!(calculate_disk_usage(temp_dir.path()).unwrap().files, 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_directory_size_uses_allocated_blocks_for_sparse_files() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let sparse = temp_dir.path().join("sparse.bin");
+    let file = std::fs::File::create(&sparse).unwrap();
+    file.set_len(64 * 1024 * 1024).unwrap();
+
+    let usage = calculate_disk_usage(temp_dir.path()).unwrap();
+    assert_eq!(usage.get(SizeMode::Apparent), 64 * 1024 * 1024);
+    assert!(usage.get(SizeMode::Allocated) < 64 * 1024 * 1024);
+}
+
+/// A target directory with enough files for the size scan to sample
+fn populate_uniform_files(dir: &Path) -> u64 {
+    let mut total = 0;
+    for crate_index in 0..64 {
+        let deps = dir
+            .join("debug")
+            .join("deps")
+            .join(format!("c{crate_index}"));
+        std::fs::create_dir_all(&deps).unwrap();
+        for file_index in 0..80 {
+            std::fs::write(deps.join(format!("f{file_index}")), [0u8; 100]).unwrap();
+            total += 100;
+        }
+    }
+    total
+}
+
+#[test]
+fn test_budgeted_size_estimates_when_well_under_budget() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let total = populate_uniform_files(temp_dir.path());
+
+    let usage = calculate_disk_usage_within(
+        temp_dir.path(),
+        None,
+        SizeMode::Apparent,
+        SymlinkPolicy::Skip,
+        total * 10,
+    )
+    .unwrap();
+    assert!(usage.estimated);
+    assert_eq!(usage.sizes.apparent, total);
+}
+
+#[test]
+fn test_budgeted_size_is_exact_near_budget() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let total = populate_uniform_files(temp_dir.path());
+
+    let usage = calculate_disk_usage_within(
+        temp_dir.path(),
+        None,
+        SizeMode::Apparent,
+        SymlinkPolicy::Skip,
+        total,
+    )
+    .unwrap();
+    assert!(!usage.estimated);
+    assert_eq!(usage.sizes, calculate_disk_usage(temp_dir.path()).unwrap());
+}
+
+#[test]
+fn test_budgeted_size_is_exact_for_small_trees() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("lib.rlib"), [0u8; 100]).unwrap();
+
+    let usage = calculate_disk_usage_within(
+        temp_dir.path(),
+        None,
+        SizeMode::Apparent,
+        SymlinkPolicy::Skip,
+        u64::MAX,
+    )
+    .unwrap();
+    assert!(!usage.estimated);
+    assert_eq!(usage.sizes.apparent, 100);
+}
+
 #[test]
 fn test_parse_crate_artifact_name_legacy_cases() {
     let path = Path::new("libfoo-123456789abcdef0");
@@ -618,6 +950,9 @@ fn test_select_artifacts_with_previous_build_timestamp() {
             artifacts: vec![],
             total_size: 1024 * 1024, // 1MB
             newest_mtime: five_minutes_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "previous-build-crate".to_string(),
@@ -625,6 +960,9 @@ fn test_select_artifacts_with_previous_build_timestamp() {
             artifacts: vec![],
             total_size: 2 * 1024 * 1024, // 2MB
             newest_mtime: ten_minutes_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "old-crate".to_string(),
@@ -632,6 +970,9 @@ fn test_select_artifacts_with_previous_build_timestamp() {
             artifacts: vec![],
             total_size: 3 * 1024 * 1024, // 3MB
             newest_mtime: one_hour_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "very-old-crate".to_string(),
@@ -639,6 +980,9 @@ fn test_select_artifacts_with_previous_build_timestamp() {
             artifacts: vec![],
             total_size: 4 * 1024 * 1024, // 4MB
             newest_mtime: two_days_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
     ];
 
@@ -651,12 +995,13 @@ fn test_select_artifacts_with_previous_build_timestamp() {
     // Test 1: With previous build timestamp, recent artifacts should be preserved
     let to_remove = select_artifacts_for_removal(
         &artifacts,
-        10 * 1024 * 1024,      // 10MB total
-        Some(5 * 1024 * 1024), // 5MB max
-        1,                     // 1 day age threshold
-        Some(previous_build_nanos),
-        0, // verbose
-        false,
+        &size_budget(10 * 1024 * 1024, Some(5 * 1024 * 1024)), // 10MB total, 5MB max
+        days(1),                                               // 1 day age threshold
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(
+            0, // verbose
+            false,
+        ),
     );
 
     // Should preserve artifacts from ten_minutes_ago and five_minutes_ago
@@ -668,12 +1013,14 @@ fn test_select_artifacts_with_previous_build_timestamp() {
     // Test 2: Without previous build timestamp, all old artifacts can be removed
     let to_remove_no_preserve = select_artifacts_for_removal(
         &artifacts,
-        10 * 1024 * 1024,      // 10MB total
-        Some(5 * 1024 * 1024), // 5MB max
-        1,                     // 1 day age threshold
-        None,                  // No previous build timestamp
-        0,                     // verbose
-        false,
+        &size_budget(10 * 1024 * 1024, Some(5 * 1024 * 1024)), // 10MB total, 5MB max
+        days(1),                                               // 1 day age threshold
+        &PreservePolicy::default(),
+        &Logger::new(
+            // No previous build timestamp
+            0, // verbose
+            false,
+        ),
     );
 
     // Should remove very-old-crate (age) and others for size
@@ -704,6 +1051,9 @@ fn test_select_artifacts_skips_stale_previous_timestamp() {
             artifacts: vec![],
             total_size: 2 * 1024 * 1024,
             newest_mtime: ten_days_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "recent-crate".to_string(),
@@ -711,17 +1061,18 @@ fn test_select_artifacts_skips_stale_previous_timestamp() {
             artifacts: vec![],
             total_size: 2 * 1024 * 1024,
             newest_mtime: two_days_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
     ];
 
     let to_remove = select_artifacts_for_removal(
         &artifacts,
-        4 * 1024 * 1024,
-        None,
-        7,
-        Some(stale_nanos),
-        0,
-        false,
+        &size_budget(4 * 1024 * 1024, None),
+        days(7),
+        &PreservePolicy::previous_build(stale_nanos),
+        &Logger::new(0, false),
     );
 
     assert_eq!(to_remove.len(), 1);
@@ -746,6 +1097,9 @@ fn test_select_artifacts_preserves_recent_previous_timestamp_with_buffer() {
             artifacts: vec![],
             total_size: 3 * 1024 * 1024,
             newest_mtime: two_minutes_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "older-build".to_string(),
@@ -753,17 +1107,18 @@ fn test_select_artifacts_preserves_recent_previous_timestamp_with_buffer() {
             artifacts: vec![],
             total_size: 3 * 1024 * 1024,
             newest_mtime: eight_days_ago,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
     ];
 
     let to_remove = select_artifacts_for_removal(
         &artifacts,
-        6 * 1024 * 1024,
-        Some(1024 * 1024),
-        7,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(6 * 1024 * 1024, Some(1024 * 1024)),
+        days(7),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
 
     assert_eq!(to_remove.len(), 1);
@@ -782,6 +1137,9 @@ fn test_size_cleanup_after_previous_build_expires() {
             artifacts: vec![],
             total_size: 3 * 1024 * 1024,
             newest_mtime: fresh,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
         CrateArtifact {
             name: "fresh-b".to_string(),
@@ -789,6 +1147,9 @@ fn test_size_cleanup_after_previous_build_expires() {
             artifacts: vec![],
             total_size: 3 * 1024 * 1024,
             newest_mtime: fresh,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
         },
     ];
 
@@ -803,12 +1164,10 @@ fn test_size_cleanup_after_previous_build_expires() {
         .as_nanos();
     let preserved = select_artifacts_for_removal(
         &artifacts,
-        current_size,
-        Some(cap),
-        age_threshold_days,
-        Some(previous_build_nanos),
-        0,
-        false,
+        &size_budget(current_size, Some(cap)),
+        days(age_threshold_days),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, false),
     );
     assert!(preserved.is_empty());
 
@@ -821,12 +1180,10 @@ fn test_size_cleanup_after_previous_build_expires() {
 
     let evicted = select_artifacts_for_removal(
         &artifacts,
-        current_size,
-        Some(cap),
-        age_threshold_days,
-        Some(stale_previous_nanos),
-        0,
-        false,
+        &size_budget(current_size, Some(cap)),
+        days(age_threshold_days),
+        &PreservePolicy::previous_build(stale_previous_nanos),
+        &Logger::new(0, false),
     );
 
     // With preservation skipped, size-based cleanup should evict to meet the cap.
@@ -834,3 +1191,478 @@ fn test_size_cleanup_after_previous_build_expires() {
     let freed: u64 = evicted.iter().map(|a| a.total_size).sum();
     assert!(freed >= current_size - cap);
 }
+
+// Build unit preservation tests
+
+#[test]
+fn test_build_units_from_message_stream_pin_exact_hashes() {
+    let stream = concat!(
+        r#"{"reason":"compiler-artifact","package_id":"serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)","target":{"name":"serde"},"filenames":["/ws/target/debug/deps/libserde-1111111111111111.rlib","/ws/target/debug/deps/libserde-1111111111111111.rmeta"]}"#,
+        "\n",
+        r#"{"reason":"build-script-executed","package_id":"ring 0.17.0","out_dir":"/ws/target/debug/build/ring-2222222222222222/out"}"#,
+        "\n",
+        "not json\n",
+        r#"{"reason":"build-finished","success":true}"#,
+        "\n",
+    );
+
+    let units = BuildUnits::parse(stream).unwrap();
+    assert!(!units.is_empty());
+
+    let artifacts = vec![
+        create_test_artifact("serde", "1111111111111111", 1000, 30),
+        create_test_artifact("serde", "9999999999999999", 1000, 30),
+        create_test_artifact("ring", "2222222222222222", 1000, 30),
+    ];
+    let eligible = exclude_build_units(artifacts, Some(&units), &Logger::new(0, true));
+
+    assert_eq!(eligible.len(), 1);
+    assert_eq!(eligible[0].hash, "9999999999999999");
+}
+
+#[test]
+fn test_build_units_from_unit_graph_match_by_name() {
+    let graph = r#"{
+        "version": 1,
+        "units": [
+            {"pkg_id": "path+file:///ws/my-app#0.1.0", "target": {"name": "my-app"}},
+            {"pkg_id": "registry+https://github.com/rust-lang/crates.io-index#proc-macro2@1.0.0", "target": {"name": "proc_macro2"}}
+        ],
+        "roots": [0]
+    }"#;
+
+    let units = BuildUnits::parse(graph).unwrap();
+
+    let artifacts = vec![
+        create_test_artifact("my-app", "1111111111111111", 1000, 30),
+        create_test_artifact("libproc_macro2", "2222222222222222", 1000, 30),
+        create_test_artifact("unrelated", "3333333333333333", 1000, 30),
+    ];
+    let eligible = exclude_build_units(artifacts, Some(&units), &Logger::new(0, true));
+
+    assert_eq!(eligible.len(), 1);
+    assert_eq!(eligible[0].name, "unrelated");
+}
+
+#[test]
+fn test_exclude_pinned_matches_names_and_globs() {
+    let pins = PinnedCrates::new(&["rocksdb-sys".to_string(), "serde*".to_string()]).unwrap();
+
+    let artifacts = vec![
+        create_test_artifact("librocksdb_sys", "1111111111111111", 1000, 30),
+        create_test_artifact("rocksdb-sys", "2222222222222222", 1000, 30),
+        create_test_artifact("serde_json", "3333333333333333", 1000, 30),
+        create_test_artifact("librocksdb", "4444444444444444", 1000, 30),
+    ];
+    let eligible = exclude_pinned(artifacts, Some(&pins), &Logger::new(0, true));
+
+    assert_eq!(eligible.len(), 1);
+    assert_eq!(eligible[0].name, "librocksdb");
+}
+
+#[test]
+fn test_pinned_crates_rejects_invalid_patterns() {
+    assert!(validate_pin_pattern("rocksdb-sys").is_ok());
+    assert!(validate_pin_pattern("*-sys").is_ok());
+    assert!(validate_pin_pattern("").is_err());
+    assert!(validate_pin_pattern("../rocksdb").is_err());
+    assert!(PinnedCrates::new(&["foo bar".to_string()]).is_err());
+}
+
+#[test]
+fn test_build_costs_sum_timings_per_package() {
+    let stream = concat!(
+        r#"{"reason":"timing-info","package_id":"registry+https://github.com/rust-lang/crates.io-index#ring@0.17.0","target":{"name":"build-script-build"},"mode":"run-custom-build","duration":12.5}"#,
+        "\n",
+        r#"{"reason":"timing-info","package_id":"registry+https://github.com/rust-lang/crates.io-index#ring@0.17.0","target":{"name":"ring"},"mode":"build","duration":287.5}"#,
+        "\n",
+        r#"{"reason":"timing-info","package_id":"path+file:///ws/my-app#0.1.0","target":{"name":"my-app"},"mode":"build","duration":1.25}"#,
+        "\n",
+        r#"{"reason":"build-finished","success":true}"#,
+        "\n",
+    );
+
+    let millis = BuildCosts::parse_timings(stream).unwrap();
+    assert_eq!(millis.len(), 2);
+    assert_eq!(millis["ring"], 300_000);
+    assert_eq!(millis["my_app"], 1250);
+
+    let costs = BuildCosts::new(&millis);
+    let artifacts = weigh_build_costs(
+        vec![
+            create_test_artifact("libring", "1111111111111111", 1000, 30),
+            create_test_artifact("my-app", "2222222222222222", 1000, 30),
+            create_test_artifact("serde", "3333333333333333", 1000, 30),
+        ],
+        Some(&costs),
+        &Logger::new(0, true),
+    );
+    let secs: Vec<_> = artifacts.iter().map(|a| a.rebuild_secs).collect();
+    assert_eq!(secs, [Some(300.0), Some(1.25), None]);
+
+    assert!(BuildCosts::parse_timings(r#"{"reason":"build-finished"}"#).is_err());
+}
+
+#[test]
+fn test_size_selection_evicts_cheap_rebuilds_first() {
+    let mut artifacts = vec![
+        create_test_artifact("ring", "1111111111111111", 40 << 20, 3),
+        create_test_artifact("itoa", "2222222222222222", 40 << 20, 2),
+        create_test_artifact("serde", "3333333333333333", 40 << 20, 2),
+    ];
+    artifacts[0].rebuild_secs = Some(300.0);
+    artifacts[1].rebuild_secs = Some(2.0);
+    artifacts[2].rebuild_secs = Some(10.0);
+
+    // The oldest crate takes 30 times longer to rebuild than the median one,
+    // so the two younger crates go first
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(120 << 20, Some(50 << 20)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, true),
+    );
+    let names: Vec<_> = selected.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["itoa", "serde"]);
+
+    // Without recorded costs, age alone decides
+    for artifact in &mut artifacts {
+        artifact.rebuild_secs = None;
+    }
+    let selected = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(120 << 20, Some(50 << 20)),
+        days(30),
+        &PreservePolicy::default(),
+        &Logger::new(0, true),
+    );
+    let names: Vec<_> = selected.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, ["ring", "itoa"]);
+}
+
+#[test]
+fn test_build_units_rejects_unrecognized_input() {
+    assert!(BuildUnits::parse("<html></html>").is_err());
+    assert!(BuildUnits::parse("").is_err());
+}
+
+#[test]
+fn test_select_artifacts_honors_custom_preserve_buffer_and_window() {
+    let now = SystemTime::now();
+    let previous_build = now - Duration::from_secs(3 * 24 * 60 * 60);
+    let long_build_start = previous_build - Duration::from_secs(2 * 60 * 60);
+
+    let previous_build_nanos = previous_build
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+
+    let artifacts = vec![CrateArtifact {
+        name: "early-in-long-build".to_string(),
+        hash: "cccccccccccccccc".to_string(),
+        artifacts: vec![],
+        total_size: 1024,
+        newest_mtime: long_build_start,
+        eviction_score: 0.0,
+        rebuild_secs: None,
+        inputs_unchanged: false,
+    }];
+
+    // Defaults: the 1 day age threshold doubles as the window, so the 3 day old
+    // timestamp is stale and the artifact is evicted.
+    let evicted = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(1024, None),
+        days(1),
+        &PreservePolicy::previous_build(previous_build_nanos),
+        &Logger::new(0, true),
+    );
+    assert_eq!(evicted.len(), 1);
+
+    // A wider window honors the timestamp, but the default 5 minute buffer does
+    // not reach back to the start of a 2 hour build.
+    let policy = PreservePolicy {
+        window: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        ..PreservePolicy::previous_build(previous_build_nanos)
+    };
+    let evicted = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(1024, None),
+        days(1),
+        &policy,
+        &Logger::new(0, true),
+    );
+    assert_eq!(evicted.len(), 1);
+
+    // Widening the buffer as well keeps the artifact.
+    let policy = PreservePolicy {
+        buffer: Duration::from_secs(3 * 60 * 60),
+        ..policy
+    };
+    let evicted = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(1024, None),
+        days(1),
+        &policy,
+        &Logger::new(0, true),
+    );
+    assert!(evicted.is_empty());
+}
+
+#[test]
+fn test_select_artifacts_preserves_last_n_builds() {
+    let now = SystemTime::now();
+    let to_nanos = |time: SystemTime| {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    };
+
+    // Release build six hours ago, debug build one hour ago.
+    let release_build = now - Duration::from_secs(6 * 60 * 60);
+    let debug_build = now - Duration::from_secs(60 * 60);
+
+    let artifacts = vec![
+        CrateArtifact {
+            name: "release-crate".to_string(),
+            hash: "dddddddddddddddd".to_string(),
+            artifacts: vec![],
+            total_size: 1024,
+            newest_mtime: release_build,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
+        },
+        CrateArtifact {
+            name: "debug-crate".to_string(),
+            hash: "eeeeeeeeeeeeeeee".to_string(),
+            artifacts: vec![],
+            total_size: 1024,
+            newest_mtime: debug_build,
+            eviction_score: 0.0,
+            rebuild_secs: None,
+            inputs_unchanged: false,
+        },
+    ];
+
+    let policy = PreservePolicy {
+        previous_builds_mtime_nanos: vec![to_nanos(debug_build)],
+        ..PreservePolicy::default()
+    };
+    let evicted = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(2048, Some(1024)),
+        days(7),
+        &policy,
+        &Logger::new(0, true),
+    );
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].name, "release-crate");
+
+    let policy = PreservePolicy {
+        previous_builds_mtime_nanos: vec![to_nanos(release_build), to_nanos(debug_build)],
+        ..PreservePolicy::default()
+    };
+    let evicted = select_artifacts_for_removal(
+        &artifacts,
+        &size_budget(2048, Some(1024)),
+        days(7),
+        &policy,
+        &Logger::new(0, true),
+    );
+    assert!(evicted.is_empty());
+}
+
+#[test]
+fn test_profile_key_is_relative_to_target_dir() {
+    let target = Path::new("/ws/target");
+    assert_eq!(profile_key(target, Path::new("/ws/target/debug")), "debug");
+    assert_eq!(
+        profile_key(
+            target,
+            Path::new("/ws/target/x86_64-unknown-linux-gnu/release")
+        ),
+        "x86_64-unknown-linux-gnu/release"
+    );
+    assert_eq!(profile_key(target, target), ".");
+}
+
+#[test]
+fn test_declared_profile_names_map_to_directories() {
+    use super::profiles::declared_profile_names;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let workspace = temp_dir.path().join("ws");
+    let cargo_home = temp_dir.path().join("cargo-home");
+    std::fs::create_dir_all(workspace.join(".cargo")).unwrap();
+    std::fs::create_dir_all(&cargo_home).unwrap();
+    std::fs::write(
+        workspace.join("Cargo.toml"),
+        "[package]\nname = \"ws\"\n\n[profile.dev]\nopt-level = \
+         1\n\n[profile.release-lto]\ninherits = \"release\"\nlto = true\n",
+    )
+    .unwrap();
+    std::fs::write(
+        workspace.join(".cargo/config.toml"),
+        "[profile.coverage]\ninherits = \"test\"\n[profile.bench]\ndebug = true\n",
+    )
+    .unwrap();
+    std::fs::write(
+        cargo_home.join("config.toml"),
+        "[profile.ci]\ninherits = \"dev\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        declared_profile_names(&workspace, Some(&cargo_home)),
+        ["debug", "release", "release-lto", "coverage", "ci"]
+    );
+}
+
+#[test]
+fn test_preserve_policy_prefers_profile_history() {
+    let gc = Gc::builder()
+        .recent_build_mtime_nanos(vec![10, 20, 30])
+        .profile_build_mtime_nanos(
+            [
+                ("debug".to_string(), vec![5, 15]),
+                ("release".to_string(), vec![]),
+            ]
+            .into_iter()
+            .collect(),
+        )
+        .preserve_last_builds(2)
+        .build();
+
+    assert_eq!(
+        gc.preserve_policy("debug").previous_builds_mtime_nanos,
+        vec![5, 15]
+    );
+    // Empty or missing profile histories fall back to the global history.
+    assert_eq!(
+        gc.preserve_policy("release").previous_builds_mtime_nanos,
+        vec![20, 30]
+    );
+    assert_eq!(
+        gc.preserve_policy("custom").previous_builds_mtime_nanos,
+        vec![20, 30]
+    );
+}
+
+#[test]
+fn test_path_filter_matches_subtrees() {
+    let target_dir = Path::new("/work/target");
+    let filter = PathFilter::new(
+        target_dir,
+        &["target/debug".to_string(), "target/**/build".to_string()],
+    )
+    .unwrap();
+
+    assert!(filter.matches(&target_dir.join("debug")));
+    assert!(filter.matches(&target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib")));
+    assert!(filter.matches(&target_dir.join("release/build/foo-0123456789abcdef/out")));
+    assert!(filter.matches(&target_dir.join("x86_64-unknown-linux-gnu/release/build")));
+    assert!(!filter.matches(&target_dir.join("release/deps/libfoo-0123456789abcdef.rlib")));
+    assert!(!filter.matches(&target_dir.join("debugger")));
+    assert!(!filter.matches(Path::new("/elsewhere/target/debug")));
+
+    let absolute =
+        PathFilter::new(target_dir, &["/work/target/*/incremental".to_string()]).unwrap();
+    assert!(absolute.matches(&target_dir.join("release/incremental/foo-1abc")));
+    assert!(!absolute.matches(&target_dir.join("release/deps")));
+
+    assert!(PathFilter::new(target_dir, &["".to_string()]).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sizes_count_symlinked_directories_as_the_policy_says() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let cache = tempfile::TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("lib.rlib"), [0u8; 100]).unwrap();
+    std::fs::write(cache.path().join("blob"), [0u8; 4000]).unwrap();
+    std::os::unix::fs::symlink(cache.path(), temp_dir.path().join("cache")).unwrap();
+
+    let skipped = calculate_disk_usage(temp_dir.path()).unwrap();
+    assert!(skipped.apparent < 4000);
+    assert_eq!(skipped.files, 3);
+    assert_eq!(
+        calculate_disk_usage_following(temp_dir.path(), SymlinkPolicy::Skip).unwrap(),
+        skipped
+    );
+    let followed = calculate_disk_usage_following(temp_dir.path(), SymlinkPolicy::Follow).unwrap();
+    assert_eq!(followed.apparent, 100 + 4000);
+    assert_eq!(followed.files, 4);
+
+    for (symlinks, expected) in [
+        (SymlinkPolicy::Skip, skipped),
+        (SymlinkPolicy::Follow, followed),
+    ] {
+        let usage = calculate_disk_usage_within(
+            temp_dir.path(),
+            None,
+            SizeMode::Apparent,
+            symlinks,
+            u64::MAX,
+        )
+        .unwrap();
+        assert_eq!(usage.sizes, expected);
+    }
+
+    assert_eq!(
+        "Follow".parse::<SymlinkPolicy>().unwrap(),
+        SymlinkPolicy::Follow
+    );
+    assert_eq!(SymlinkPolicy::default().to_string(), "skip");
+    assert!("always".parse::<SymlinkPolicy>().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clean_named_crate_removes_through_gc() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    let build_dir = temp_dir.path().join("build");
+    let outside = temp_dir.path().join("elsewhere");
+    for profile_dir in [target_dir.join("debug"), build_dir.join("debug")] {
+        std::fs::create_dir_all(profile_dir.join(".fingerprint")).unwrap();
+        std::fs::create_dir_all(profile_dir.join("deps")).unwrap();
+    }
+    let in_build_dir = build_dir.join("debug/deps/libfoo-0123456789abcdef.rlib");
+    std::fs::write(&in_build_dir, b"rlib").unwrap();
+    let escaped = outside.join("libfoo-fedcba9876543210.rlib");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(&escaped, b"rlib").unwrap();
+    let link = target_dir.join("debug/deps/libfoo-fedcba9876543210.rlib");
+    std::os::unix::fs::symlink(&escaped, &link).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(&target_dir)
+        .build_dir(&build_dir)
+        .quiet(true)
+        .build();
+    let stats = clean_named_crate(&config, "foo", &Logger::new(0, true)).unwrap();
+
+    assert!(!in_build_dir.exists());
+    // The link is removed, never what it points to
+    assert!(link.symlink_metadata().is_err());
+    assert!(escaped.exists());
+    assert_eq!(stats.artifacts_removed, 2);
+    assert_eq!(stats.errors, 0);
+}
+
+#[test]
+fn test_remove_skips_paths_removed_already() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let target_dir = temp_dir.path().join("target");
+    std::fs::create_dir_all(target_dir.join("debug/deps")).unwrap();
+
+    let config = Gc::builder().target_dir(&target_dir).quiet(true).build();
+    // Gone along with a build script directory removed before it
+    assert!(!config.remove(&target_dir.join("debug/build/foo-0123456789abcdef/out")));
+    assert!(!config.remove(&target_dir.join("debug/deps/libfoo-0123456789abcdef.rlib")));
+
+    assert_eq!(config.take_refused_removals(), 0);
+    assert_eq!(config.take_removal_errors(), 0);
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::artifacts::{CrateArtifact, parse_crate_artifact_name};
+use crate::error::{HoldError, Result};
+
+/// Units used by the most recent build, learned from cargo's own output.
+///
+/// Two kinds of input are understood:
+/// - A unit graph document (`cargo build --unit-graph -Z unstable-options`),
+///   which only names the units, so every artifact of those crates is kept.
+/// - A JSON message stream (`cargo build --message-format=json` or
+///   `--timings=json`). `compiler-artifact` and `build-script-executed`
+///   messages carry hashed paths and pin exact artifacts; `timing-info`
+///   messages only carry names.
+#[derive(Debug, Clone, Default)]
+pub struct BuildUnits {
+    /// Metadata hashes of exact artifacts used by the build.
+    hashes: HashSet<String>,
+    /// Normalized crate names used by the build.
+    names: HashSet<String>,
+}
+
+impl BuildUnits {
+    /// Load build units from a file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::parse(&contents)
+    }
+
+    /// Parse a unit graph document or a JSON message stream.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut units = Self::default();
+
+        if let Ok(value) = serde_json::from_str::<Value>(contents)
+            && let Some(graph_units) = value.get("units").and_then(Value::as_array)
+        {
+            for unit in graph_units {
+                units.add_unit_names(unit);
+            }
+            return Ok(units);
+        }
+
+        let mut saw_message = false;
+        for line in contents.lines() {
+            let Ok(message) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            saw_message = true;
+
+            match message.get("reason").and_then(Value::as_str) {
+                Some("compiler-artifact") => {
+                    for filename in message
+                        .get("filenames")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_str)
+                    {
+                        units.add_hash_from_path(Path::new(filename));
+                    }
+                }
+                Some("build-script-executed") => {
+                    // out_dir is `build/<name>-<hash>/out`
+                    if let Some(out_dir) = message.get("out_dir").and_then(Value::as_str)
+                        && let Some(parent) = Path::new(out_dir).parent()
+                    {
+                        units.add_hash_from_path(parent);
+                    }
+                }
+                Some("timing-info") => units.add_unit_names(&message),
+                _ => {}
+            }
+        }
+
+        if !saw_message {
+            return Err(HoldError::InvalidBuildUnits(
+                "Build units file is neither a unit graph nor a cargo JSON message stream"
+                    .to_string(),
+            ));
+        }
+
+        Ok(units)
+    }
+
+    /// Check whether no units were found.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty() && self.names.is_empty()
+    }
+
+    /// Check whether a crate artifact group was used by the build.
+    pub(crate) fn contains(&self, artifact: &CrateArtifact) -> bool {
+        if self.hashes.contains(&artifact.hash) {
+            return true;
+        }
+
+        let name = normalize_name(&artifact.name);
+        self.names.contains(&name)
+            || name
+                .strip_prefix("lib")
+                .is_some_and(|stripped| self.names.contains(stripped))
+    }
+
+    fn add_hash_from_path(&mut self, path: &Path) {
+        if let Some((_, hash)) = parse_crate_artifact_name(path) {
+            self.hashes.insert(hash);
+        }
+    }
+
+    fn add_unit_names(&mut self, unit: &Value) {
+        if let Some(name) = unit
+            .get("target")
+            .and_then(|target| target.get("name"))
+            .and_then(Value::as_str)
+        {
+            self.names.insert(normalize_name(name));
+        }
+        if let Some(name) = unit
+            .get("pkg_id")
+            .or_else(|| unit.get("package_id"))
+            .and_then(Value::as_str)
+            .and_then(package_name_from_id)
+        {
+            self.names.insert(normalize_name(name));
+        }
+    }
+}
+
+/// Extract the package name from a cargo package id.
+///
+/// Handles both the legacy `name version (source)` form and the package id
+/// spec form `source#name@version` (or `source#version` when the name matches
+/// the last path segment of the source).
+pub(super) fn package_name_from_id(id: &str) -> Option<&str> {
+    if let Some((source, spec)) = id.rsplit_once('#') {
+        return match spec.split_once('@') {
+            Some((name, _)) => Some(name),
+            None => source.rsplit('/').next(),
+        }
+        .filter(|name| !name.is_empty());
+    }
+
+    id.split_whitespace()
+        .next()
+        .filter(|name| !name.contains('/'))
+}
+
+pub(super) fn normalize_name(name: &str) -> String {
+    name.replace('-', "_")
+}
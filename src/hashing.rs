@@ -1,11 +1,135 @@
+use std::fmt;
 use std::fs::File;
+use std::io::Read;
+use std::num::NonZeroUsize;
 use std::path::Path;
+use std::str::FromStr;
 use std::time::UNIX_EPOCH;
 
 use blake3::Hasher;
 use memmap2::Mmap;
 
+use crate::context::HoldContext;
 use crate::error::HoldError;
+use crate::gc::SizeUnits;
+use crate::lfs::{MAX_POINTER_SIZE, Pointer};
+use crate::timestamp::clamp_file_mtime_nanos;
+
+/// Files analyzed per batch when no `--batch-size` is given
+pub(crate) const DEFAULT_BATCH_SIZE: NonZeroUsize = NonZeroUsize::new(16_384).unwrap();
+
+/// Bytes hashed by [`LargeFilePolicy::Head`] when no size is given
+const DEFAULT_HEAD_BYTES: u64 = 1024 * 1024;
+
+/// BLAKE3 key derivation context of the hash recorded for files compared by
+/// size alone
+const SIZE_ONLY_CONTEXT: &str = "cargo-hold 2025-01 size-only large file";
+
+/// BLAKE3 key derivation context of the hashes of large file heads, which
+/// keeps them from equalling the full hash of any file
+const HEAD_CONTEXT: &str = "cargo-hold 2025-01 large file head";
+
+/// BLAKE3 key derivation context of the salt derived from a repository's
+/// origin or from `--hash-salt`
+const SALT_CONTEXT: &str = "cargo-hold 2025-01 repository salt";
+
+/// BLAKE3 key derivation context of salt ids, which name a salt in the
+/// metadata without giving it away
+const SALT_ID_CONTEXT: &str = "cargo-hold 2025-01 salt id";
+
+/// Key of the hashes recorded for a repository, so that metadata of
+/// different repositories sharing a cache never matches and the recorded
+/// hashes say nothing about file contents to anyone without the salt
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Salt([u8; 32]);
+
+impl Salt {
+    /// The salt derived from `value`
+    pub(crate) fn new(value: &str) -> Self {
+        Self(blake3::derive_key(SALT_CONTEXT, value.as_bytes()))
+    }
+
+    /// The salt of the repository with the (normalized) `origin`, given
+    /// `--hash-salt`: an empty salt or `none` records unsalted hashes
+    pub(crate) fn for_repo(hash_salt: Option<&str>, origin: Option<&str>) -> Option<Self> {
+        match hash_salt {
+            Some("" | "none") => None,
+            Some(value) => Some(Self::new(value)),
+            None => origin.map(Self::new),
+        }
+    }
+
+    /// Identifies the salt in the metadata
+    pub(crate) fn id(&self) -> [u8; 16] {
+        let id = blake3::derive_key(SALT_ID_CONTEXT, &self.0);
+        id[..16].try_into().expect("16 bytes")
+    }
+}
+
+impl fmt::Debug for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Salt").field(&"..").finish()
+    }
+}
+
+/// `hash` keyed with `salt`: the keyed BLAKE3 hash of the unsalted hash, so
+/// that every large file policy and LFS pointers are salted alike
+fn salted(hash: [u8; 32], salt: Option<&Salt>) -> [u8; 32] {
+    match salt {
+        Some(Salt(key)) => *blake3::keyed_hash(key, &hash).as_bytes(),
+        None => hash,
+    }
+}
+
+/// How files larger than `--large-file-threshold` are hashed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LargeFilePolicy {
+    /// Hash the whole file, like any other
+    #[default]
+    Full,
+    /// Hash only this many leading bytes and trust the size for the rest
+    Head(u64),
+    /// Compare the size alone
+    Skip,
+}
+
+impl LargeFilePolicy {
+    /// Parse a `--large-file-policy` value, reading the size of `head:<size>`
+    /// in `units`
+    pub(crate) fn parse_in(s: &str, units: SizeUnits) -> Result<Self, HoldError> {
+        let invalid = || HoldError::InvalidLargeFilePolicy(s.to_string());
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "skip" => Ok(Self::Skip),
+            "head" => Ok(Self::Head(DEFAULT_HEAD_BYTES)),
+            policy => {
+                let bytes = policy.strip_prefix("head:").ok_or_else(invalid)?;
+                match units.parse(bytes) {
+                    Ok(0) | Err(_) => Err(invalid()),
+                    Ok(bytes) => Ok(Self::Head(bytes)),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for LargeFilePolicy {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self, HoldError> {
+        Self::parse_in(s, SizeUnits::Binary)
+    }
+}
+
+impl fmt::Display for LargeFilePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => f.write_str("full"),
+            Self::Head(bytes) => write!(f, "head:{bytes}"),
+            Self::Skip => f.write_str("skip"),
+        }
+    }
+}
 
 /// Computes the BLAKE3 hash of a file using memory mapping and parallel
 /// processing.
@@ -20,7 +144,7 @@ use crate::error::HoldError;
 ///
 /// # Returns
 ///
-/// A hex-encoded string of the file's BLAKE3 hash.
+/// The file's BLAKE3 hash.
 ///
 /// # Errors
 ///
@@ -28,13 +152,12 @@ use crate::error::HoldError;
 /// - The file cannot be read
 /// - The path points to a symbolic link
 /// - Memory mapping fails
-pub fn hash_file(path: &Path) -> Result<String, HoldError> {
+pub(crate) fn digest_file(path: &Path) -> Result<blake3::Hash, HoldError> {
     let metadata = checked_metadata(path)?;
 
     // Handle empty files without memory mapping
     if metadata.len() == 0 {
-        let hasher = Hasher::new();
-        return Ok(hasher.finalize().to_hex().to_string());
+        return Ok(Hasher::new().finalize());
     }
 
     // Open the file
@@ -53,7 +176,120 @@ pub fn hash_file(path: &Path) -> Result<String, HoldError> {
     let mut hasher = Hasher::new();
     hasher.update_rayon(&mmap);
 
-    Ok(hasher.finalize().to_hex().to_string())
+    Ok(hasher.finalize())
+}
+
+/// Computes the hex-encoded BLAKE3 hash of a file, as [`digest_file`] does,
+/// for listings people read.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, is a symbolic link, or memory
+/// mapping fails.
+pub fn hash_file(path: &Path) -> Result<String, HoldError> {
+    Ok(digest_file(path)?.to_hex().to_string())
+}
+
+/// Computes the hash of a tracked file of `size` bytes, applying the
+/// [`LargeFilePolicy`] of `context` for files above
+/// `--large-file-threshold`.
+///
+/// Files hashed in full get their BLAKE3 hash; the other policies record
+/// values that never equal a full hash, so changing the policy marks large
+/// files as modified once rather than hiding changes. The hash is keyed with
+/// the repository's `salt`, if any.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, is a symbolic link, or memory
+/// mapping fails.
+pub(crate) fn hash_contents(
+    context: &HoldContext,
+    path: &Path,
+    size: u64,
+    salt: Option<&Salt>,
+) -> Result<[u8; 32], HoldError> {
+    hash_with_policy(path, context.large_file_policy(size)).map(|hash| salted(hash, salt))
+}
+
+fn hash_with_policy(path: &Path, policy: LargeFilePolicy) -> Result<[u8; 32], HoldError> {
+    match policy {
+        LargeFilePolicy::Full => Ok(*digest_file(path)?.as_bytes()),
+        LargeFilePolicy::Skip => Ok(blake3::derive_key(SIZE_ONLY_CONTEXT, &[])),
+        LargeFilePolicy::Head(bytes) => {
+            checked_metadata(path)?;
+            let io_error = |source| HoldError::IoError {
+                path: path.to_path_buf(),
+                source,
+            };
+            let file = File::open(path).map_err(io_error)?;
+            let mut hasher = Hasher::new_derive_key(HEAD_CONTEXT);
+            hasher.update(&bytes.to_le_bytes());
+            std::io::copy(&mut file.take(bytes), &mut hasher).map_err(io_error)?;
+            Ok(*hasher.finalize().as_bytes())
+        }
+    }
+}
+
+/// Computes the size and BLAKE3 hash of the Git LFS pointer for a file managed
+/// by Git LFS.
+///
+/// The file may be checked out as its pointer or, smudged by git-lfs, as its
+/// content; both give the size and hash of the pointer in canonical form, so
+/// the result does not depend on whether git-lfs ran. The hash is keyed with
+/// the repository's `salt`, if any.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The file cannot be read
+/// - The path points to a symbolic link
+/// - Memory mapping fails
+pub(crate) fn hash_lfs_pointer(
+    path: &Path,
+    salt: Option<&Salt>,
+) -> Result<(u64, [u8; 32]), HoldError> {
+    let metadata = checked_metadata(path)?;
+    let io_error = |source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let pointer = if metadata.len() <= MAX_POINTER_SIZE {
+        let data = std::fs::read(path).map_err(io_error)?;
+        Pointer::parse(&data).unwrap_or_else(|| Pointer::for_content(&data))
+    } else {
+        let file = File::open(path).map_err(io_error)?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+        Pointer::for_content(&mmap)
+    };
+
+    let canonical = pointer.canonical();
+    Ok((
+        canonical.len() as u64,
+        salted(*blake3::hash(canonical.as_bytes()).as_bytes(), salt),
+    ))
+}
+
+/// Computes the size and hash a tracked file is recorded with: those of its
+/// LFS pointer if `lfs`, else its own, hashed by [`hash_contents`].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, is a symbolic link, or memory
+/// mapping fails.
+pub(crate) fn hash_tracked_file(
+    context: &HoldContext,
+    path: &Path,
+    lfs: bool,
+    salt: Option<&Salt>,
+) -> Result<(u64, [u8; 32]), HoldError> {
+    if lfs {
+        hash_lfs_pointer(path, salt)
+    } else {
+        let size = get_file_size(path)?;
+        Ok((size, hash_contents(context, path, size, salt)?))
+    }
 }
 
 /// Gets the size of a file in bytes, checking for symbolic links.
@@ -78,9 +314,20 @@ pub fn get_file_size(path: &Path) -> Result<u64, HoldError> {
     Ok(checked_metadata(path)?.len())
 }
 
-/// Gets the file's modification time as nanoseconds since UNIX_EPOCH.
-pub fn get_file_mtime_nanos(path: &Path) -> Result<u128, HoldError> {
+/// Gets the file's modification time as nanoseconds since UNIX_EPOCH,
+/// clamped to `u64` like the timestamps stored in the metadata.
+pub fn get_file_mtime_nanos(path: &Path) -> Result<u64, HoldError> {
+    mtime_nanos(path, &checked_metadata(path)?)
+}
+
+/// Gets the file's size and modification time, as [`get_file_size`] and
+/// [`get_file_mtime_nanos`] do, with a single `stat`.
+pub(crate) fn get_file_size_and_mtime(path: &Path) -> Result<(u64, u64), HoldError> {
     let metadata = checked_metadata(path)?;
+    Ok((metadata.len(), mtime_nanos(path, &metadata)?))
+}
+
+fn mtime_nanos(path: &Path, metadata: &std::fs::Metadata) -> Result<u64, HoldError> {
     let mtime = metadata.modified().map_err(|source| HoldError::IoError {
         path: path.to_path_buf(),
         source,
@@ -94,7 +341,7 @@ pub fn get_file_mtime_nanos(path: &Path) -> Result<u128, HoldError> {
         })?
         .as_nanos();
 
-    Ok(nanos)
+    Ok(clamp_file_mtime_nanos(nanos))
 }
 
 fn checked_metadata(path: &Path) -> Result<std::fs::Metadata, HoldError> {
@@ -167,6 +414,123 @@ mod tests {
         assert_eq!(size, content.len() as u64);
     }
 
+    #[test]
+    fn test_lfs_pointer_and_smudged_content_hash_the_same() {
+        let temp_dir = TempDir::new().unwrap();
+        for content in [b"foo".to_vec(), vec![7; 4096]] {
+            let smudged = temp_dir.path().join("smudged.bin");
+            let pointer = temp_dir.path().join("pointer.bin");
+            fs::write(&smudged, &content).unwrap();
+            fs::write(&pointer, Pointer::for_content(&content).canonical()).unwrap();
+
+            let expected = hash_lfs_pointer(&pointer, None).unwrap();
+            assert_eq!(hash_lfs_pointer(&smudged, None).unwrap(), expected);
+            assert_eq!(expected.0, get_file_size(&pointer).unwrap());
+            assert_eq!(expected.1, *digest_file(&pointer).unwrap().as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_large_file_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("model.bin");
+        fs::write(&file, "hello world").unwrap();
+
+        let full = hash_with_policy(&file, LargeFilePolicy::Full).unwrap();
+        assert_eq!(
+            hash_file(&file).unwrap(),
+            blake3::Hash::from(full).to_hex().as_str()
+        );
+        let size_only = hash_with_policy(&file, LargeFilePolicy::Skip).unwrap();
+        assert_ne!(size_only, full);
+
+        // Only the head counts, and it never passes for a full hash
+        let head = hash_with_policy(&file, LargeFilePolicy::Head(5)).unwrap();
+        fs::write(&file, "hello there").unwrap();
+        assert_eq!(
+            hash_with_policy(&file, LargeFilePolicy::Head(5)).unwrap(),
+            head
+        );
+        assert_eq!(
+            hash_with_policy(&file, LargeFilePolicy::Skip).unwrap(),
+            size_only
+        );
+        assert_ne!(
+            hash_with_policy(&file, LargeFilePolicy::Head(6)).unwrap(),
+            head
+        );
+        assert_ne!(
+            hash_with_policy(&file, LargeFilePolicy::Head(1024)).unwrap(),
+            full
+        );
+    }
+
+    #[test]
+    fn test_salted_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("lib.rs");
+        fs::write(&file, "hello world").unwrap();
+        let unsalted = hash_with_policy(&file, LargeFilePolicy::Full).unwrap();
+
+        let salt = Salt::new("github.com/ellipsis-labs/cargo-hold");
+        let other = Salt::new("github.com/ellipsis-labs/phoenix-v1");
+        let hash = salted(unsalted, Some(&salt));
+        assert_eq!(salted(unsalted, None), unsalted);
+        assert_ne!(hash, unsalted);
+        assert_ne!(hash, salted(unsalted, Some(&other)));
+        assert_eq!(hash, salted(unsalted, Some(&salt.clone())));
+        assert_ne!(salt.id(), other.id());
+
+        let (_, pointer) = hash_lfs_pointer(&file, None).unwrap();
+        assert_eq!(
+            hash_lfs_pointer(&file, Some(&salt)).unwrap().1,
+            salted(pointer, Some(&salt))
+        );
+    }
+
+    #[test]
+    fn test_salt_resolution() {
+        let origin = Some("github.com/ellipsis-labs/cargo-hold");
+        assert_eq!(Salt::for_repo(None, None), None);
+        assert_eq!(
+            Salt::for_repo(None, origin),
+            Some(Salt::new("github.com/ellipsis-labs/cargo-hold"))
+        );
+        assert_eq!(
+            Salt::for_repo(Some("team-secret"), origin),
+            Some(Salt::new("team-secret"))
+        );
+        assert_eq!(Salt::for_repo(Some("none"), origin), None);
+        assert_eq!(Salt::for_repo(Some(""), origin), None);
+    }
+
+    #[test]
+    fn test_large_file_policy_parsing() {
+        assert_eq!(
+            "full".parse::<LargeFilePolicy>().unwrap(),
+            LargeFilePolicy::Full
+        );
+        assert_eq!(
+            "Skip".parse::<LargeFilePolicy>().unwrap(),
+            LargeFilePolicy::Skip
+        );
+        assert_eq!(
+            "head".parse::<LargeFilePolicy>().unwrap(),
+            LargeFilePolicy::Head(DEFAULT_HEAD_BYTES)
+        );
+        assert_eq!(
+            "head:64M".parse::<LargeFilePolicy>().unwrap(),
+            LargeFilePolicy::Head(64 * 1024 * 1024)
+        );
+        for invalid in ["sample", "head:", "head:0", "head:lots"] {
+            assert!(matches!(
+                invalid.parse::<LargeFilePolicy>(),
+                Err(HoldError::InvalidLargeFilePolicy(_))
+            ));
+        }
+        assert_eq!(LargeFilePolicy::Head(4096).to_string(), "head:4096");
+    }
+
     #[test]
     fn test_hash_nonexistent_file() {
         let result = hash_file(Path::new("/nonexistent/file"));
@@ -0,0 +1,234 @@
+//! User commands run around cargo-hold commands.
+//!
+//! Hooks are defined in the configuration file, `.cargo-hold.json` in the
+//! working directory unless `--config` names another:
+//!
+//! ```json
+//! {
+//!   "hooks": {
+//!     "post-stow": "test \"$CARGO_HOLD_METADATA_CHANGED\" = 1 && ./upload-cache.sh"
+//!   }
+//! }
+//! ```
+//!
+//! Each hook is a command line run through the shell, with `CARGO_HOLD_HOOK`
+//! set to the hook's name and further context in environment variables (see
+//! [`Hook`]). A hook that fails or exits non-zero fails the command running
+//! it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// Name of the configuration file looked for in the working directory
+pub(crate) const CONFIG_FILENAME: &str = ".cargo-hold.json";
+
+/// Point in a command at which a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Hook {
+    /// Before `anchor` restores timestamps; gets `CARGO_HOLD_METADATA`, the
+    /// metadata path
+    PreAnchor,
+    /// After `stow` saved the metadata, or `anchor` found it up to date;
+    /// gets `CARGO_HOLD_METADATA`, `CARGO_HOLD_FILES_CHANGED`, the number of
+    /// files added, modified, or removed since the previous metadata, and
+    /// `CARGO_HOLD_METADATA_CHANGED`, `1` if the tracked files differ from the
+    /// previous metadata (or there was none) and `0` otherwise
+    PostStow,
+    /// After `heave` collected garbage, except in dry runs; gets
+    /// `CARGO_HOLD_BYTES_FREED` and `CARGO_HOLD_FILES_FREED`
+    PostHeave,
+}
+
+impl Hook {
+    const ALL: [Self; 3] = [Self::PreAnchor, Self::PostStow, Self::PostHeave];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::PreAnchor => "pre-anchor",
+            Self::PostStow => "post-stow",
+            Self::PostHeave => "post-heave",
+        }
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Hook {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, ()> {
+        Self::ALL
+            .into_iter()
+            .find(|hook| hook.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Commands of the hooks defined in a configuration file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Hooks {
+    commands: BTreeMap<Hook, String>,
+}
+
+impl Hooks {
+    /// Reads the hooks of the configuration file at `path`; a missing file
+    /// defines none unless `required`
+    pub(crate) fn load(path: &Path, required: bool) -> Result<Self> {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default());
+            }
+            Err(source) => {
+                return Err(HoldError::IoError {
+                    path: path.to_path_buf(),
+                    source,
+                });
+            }
+        };
+        Self::parse(&contents).map_err(|message| HoldError::InvalidConfigFile {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    fn parse(contents: &[u8]) -> std::result::Result<Self, String> {
+        let value: Value = serde_json::from_slice(contents).map_err(|e| e.to_string())?;
+        let Value::Object(config) = value else {
+            return Err("expected a JSON object".to_string());
+        };
+        let mut hooks = Self::default();
+        for (key, value) in config {
+            if key != "hooks" {
+                return Err(format!("unknown key '{key}'"));
+            }
+            let Value::Object(entries) = value else {
+                return Err("'hooks' must map hook names to commands".to_string());
+            };
+            for (name, command) in entries {
+                let hook = name.parse::<Hook>().map_err(|()| {
+                    format!("unknown hook '{name}' (expected pre-anchor, post-stow, or post-heave)")
+                })?;
+                let Value::String(command) = command else {
+                    return Err(format!("the command of hook '{name}' must be a string"));
+                };
+                hooks.commands.insert(hook, command);
+            }
+        }
+        Ok(hooks)
+    }
+
+    /// Command of `hook`, if defined
+    pub(crate) fn command(&self, hook: Hook) -> Option<&str> {
+        self.commands.get(&hook).map(String::as_str)
+    }
+}
+
+/// Runs the command of `hook` in `context`, if any, with the context
+/// variables `env`
+pub(crate) fn run(
+    context: &HoldContext,
+    hook: Hook,
+    env: &[(&str, String)],
+    log: &Logger,
+) -> Result<()> {
+    let Some(command) = context.hooks.command(hook) else {
+        return Ok(());
+    };
+    log.verbose(1, format!("Running {hook} hook: {command}"));
+
+    let failed = |message: String| HoldError::HookFailed {
+        hook: hook.to_string(),
+        message,
+    };
+    let status = shell(command)
+        .env("CARGO_HOLD_HOOK", hook.name())
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .status()
+        .map_err(|e| failed(format!("Failed to start '{command}': {e}")))?;
+    if !status.success() {
+        return Err(failed(format!("'{command}' exited with {status}")));
+    }
+    Ok(())
+}
+
+/// A command running `command` through the platform's shell
+#[cfg(unix)]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+pub(crate) fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hooks() {
+        let hooks = Hooks::parse(
+            br#"{"hooks": {"pre-anchor": "echo before", "post-stow": "./upload.sh"}}"#,
+        )
+        .unwrap();
+        assert_eq!(hooks.command(Hook::PreAnchor), Some("echo before"));
+        assert_eq!(hooks.command(Hook::PostStow), Some("./upload.sh"));
+        assert_eq!(hooks.command(Hook::PostHeave), None);
+
+        assert_eq!(Hooks::parse(b"{}").unwrap(), Hooks::default());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_config() {
+        for config in [
+            &b"not json"[..],
+            br#"["hooks"]"#,
+            br#"{"hook": {}}"#,
+            br#"{"hooks": "echo"}"#,
+            br#"{"hooks": {"post-anchor": "echo"}}"#,
+            br#"{"hooks": {"post-stow": ["echo"]}}"#,
+        ] {
+            assert!(
+                Hooks::parse(config).is_err(),
+                "{}",
+                String::from_utf8_lossy(config)
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_config_defines_no_hooks_unless_required() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILENAME);
+        assert_eq!(Hooks::load(&path, false).unwrap(), Hooks::default());
+        assert!(matches!(
+            Hooks::load(&path, true),
+            Err(HoldError::IoError { .. })
+        ));
+
+        std::fs::write(&path, r#"{"hooks": {"post-heave": 1}}"#).unwrap();
+        assert!(matches!(
+            Hooks::load(&path, false),
+            Err(HoldError::InvalidConfigFile { .. })
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! Git LFS pointer files.
+//!
+//! A file managed by Git LFS is checked out either as a small pointer file or,
+//! when git-lfs is installed, as its content. With `--lfs pointer` both are
+//! hashed as the pointer, whose object ID is computed from the content when
+//! needed, so runners with and without git-lfs agree on the hash.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+use crate::sha256;
+
+/// First line of every pointer file
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Pointer files are never larger than this
+pub(crate) const MAX_POINTER_SIZE: u64 = 1024;
+
+/// How files managed by Git LFS are hashed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LfsMode {
+    /// Hash whatever is checked out, the pointer or the content, like any
+    /// other file
+    #[default]
+    Content,
+    /// Hash the pointer, computing it from the content when the file is
+    /// checked out smudged
+    Pointer,
+}
+
+impl FromStr for LfsMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "content" => Ok(Self::Content),
+            "pointer" => Ok(Self::Pointer),
+            _ => Err(HoldError::InvalidLfsMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for LfsMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Content => f.write_str("content"),
+            Self::Pointer => f.write_str("pointer"),
+        }
+    }
+}
+
+/// The object a Git LFS pointer refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pointer {
+    /// SHA-256 of the content, in hex
+    oid: String,
+    /// Size of the content in bytes
+    size: u64,
+}
+
+impl Pointer {
+    /// Parse a pointer file, `None` if `data` is not one
+    pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ')? {
+                ("oid", value) => {
+                    let hex = value.strip_prefix("sha256:")?;
+                    let valid = hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit());
+                    oid = Some(hex.to_ascii_lowercase()).filter(|_| valid);
+                }
+                ("size", value) => size = value.parse().ok(),
+                // Extensions and keys added by later versions
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// The pointer git-lfs writes for `content`
+    pub(crate) fn for_content(content: &[u8]) -> Self {
+        Self {
+            oid: sha256::hex_digest(content),
+            size: content.len() as u64,
+        }
+    }
+
+    /// The pointer file in canonical form, as git-lfs writes it
+    pub(crate) fn canonical(&self) -> String {
+        format!(
+            "{POINTER_VERSION}\noid sha256:{}\nsize {}\n",
+            self.oid, self.size
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT_OID: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn test_pointer_of_content_matches_parsed_pointer() {
+        let pointer = format!("{POINTER_VERSION}\noid sha256:{CONTENT_OID}\nsize 3\n");
+
+        let parsed = Pointer::parse(pointer.as_bytes()).unwrap();
+        assert_eq!(parsed, Pointer::for_content(b"foo"));
+        assert_eq!(parsed.canonical(), pointer);
+    }
+
+    #[test]
+    fn test_pointer_parsing_is_tolerant_but_strict_about_the_format() {
+        let with_extension = format!(
+            "{POINTER_VERSION}\next-0-foo sha256:{CONTENT_OID}\noid sha256:{}\nsize 3\n",
+            CONTENT_OID.to_uppercase()
+        );
+        assert_eq!(
+            Pointer::parse(with_extension.as_bytes()),
+            Some(Pointer::for_content(b"foo"))
+        );
+
+        for not_a_pointer in [
+            "foo".to_string(),
+            format!("{POINTER_VERSION}\nsize 3\n"),
+            format!("{POINTER_VERSION}\noid sha256:abc\nsize 3\n"),
+            format!("{POINTER_VERSION}\noid sha256:{CONTENT_OID}\nsize three\n"),
+            format!("version 1\noid sha256:{CONTENT_OID}\nsize 3\n"),
+        ] {
+            assert_eq!(Pointer::parse(not_a_pointer.as_bytes()), None);
+        }
+    }
+
+    #[test]
+    fn test_lfs_mode_parsing() {
+        assert_eq!("content".parse::<LfsMode>().unwrap(), LfsMode::Content);
+        assert_eq!("Pointer".parse::<LfsMode>().unwrap(), LfsMode::Pointer);
+        assert!(matches!(
+            "smudge".parse::<LfsMode>(),
+            Err(HoldError::InvalidLfsMode(_))
+        ));
+        assert_eq!(LfsMode::Pointer.to_string(), "pointer");
+    }
+}
@@ -27,15 +27,20 @@
 //!
 //! - [`cli`]: Command-line interface definitions using clap
 //! - [`commands`]: Implementation of all cargo-hold subcommands
+//! - [`context`]: The paths and options a command runs with, so commands can
+//!   run concurrently
 //! - [`error`]: Error types and handling with thiserror + miette
 //! - [`gc`]: Garbage collection for build artifacts and cargo cache
 //!
 //! Internal modules (not part of the public API):
 //! - `state`: Core build state management with content tracking
 //! - `metadata`: Persistence layer for build state
-//! - `discovery`: Git integration for file discovery
+//! - `discovery`: Version control backends for file discovery
 //! - `timestamp`: Monotonic timestamp generation
 //! - `hashing`: BLAKE3-based file hashing utilities
+//! - `hooks`: User commands run around commands, from the configuration file
+//! - `workspace`: Workspace-aware fingerprint invalidation
+//! - `lock`: Cargo's target directory lock, shared by commands and gc
 //!
 //! ## Usage in CI
 //!
@@ -61,14 +66,16 @@
 //! as a library for integration into other tools:
 //!
 //! ```no_run
-//! use cargo_hold::cli::{Cli, Commands};
+//! use cargo_hold::cli::{AnchorArgs, Cli, Commands};
 //! use cargo_hold::commands;
 //!
 //! // Create CLI instance programmatically using the builder
 //! let cli = Cli::builder()
 //!     .target_dir("target")
 //!     .verbose(1)
-//!     .command(Commands::Anchor)
+//!     .command(Commands::Anchor {
+//!         anchor: AnchorArgs::default(),
+//!     })
 //!     .build()?;
 //!
 //! // Execute the command
@@ -76,6 +83,16 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //!
+//! ## Cargo Features
+//!
+//! All enabled by default:
+//!
+//! - `cli`: the [`cli`] module, [`commands::execute`], and fancy diagnostics;
+//!   implies `git` and `gc`
+//! - `git`: the libgit2 backend for discovering Git-tracked files, and
+//!   `install-hooks`; without it, tracked files are listed with `git ls-files`
+//! - `gc`: garbage collection of the target directory and the cargo home
+//!
 //! ## Performance
 //!
 //! cargo-hold is designed for speed:
@@ -92,16 +109,42 @@
 //!
 //! All public functions return `Result` types with descriptive error variants.
 
+// Without the command-line interface, which drives everything, the internal
+// helpers of the subsystems left out go unused
+#![cfg_attr(not(feature = "cli"), allow(dead_code, unused_imports))]
+
 // Re-export public modules for library usage
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod commands;
+pub mod context;
 pub mod error;
 pub mod gc;
 
 // Internal modules
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
 mod discovery;
+#[cfg(feature = "fuzz")]
+#[doc(hidden)]
+pub mod fuzz;
 mod hashing;
+mod hooks;
+mod lfs;
+mod lock;
 mod logging;
 mod metadata;
+mod priority;
+mod profile;
+#[cfg(feature = "cli")]
+mod rebuilds;
+mod reporting;
+mod schema;
+mod seal;
+mod sha256;
 mod state;
 mod timestamp;
+mod update;
+#[cfg(feature = "cli")]
+mod workspace;
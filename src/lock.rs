@@ -0,0 +1,278 @@
+//! Integration with cargo's target directory lock.
+//!
+//! Cargo takes an exclusive advisory lock on `<profile>/.cargo-lock` for the
+//! duration of a build. cargo-hold takes the same locks so that it never
+//! restores timestamps or deletes artifacts underneath a running build, and so
+//! that a build started while cargo-hold is working blocks until it is done
+//! (cargo reports "Blocking waiting for file lock on build directory").
+//!
+//! Likewise, cargo guards the registry and git caches in the cargo home with
+//! `.package-cache` and `.package-cache-mutate`, which cargo-hold takes before
+//! removing anything there.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use fs4::{FileExt, TryLockError};
+
+use crate::error::{HoldError, Result};
+use crate::gc::find_profile_directories;
+use crate::logging::Logger;
+
+/// Name of the lock file cargo keeps in every profile directory
+pub(crate) const CARGO_LOCK_FILE: &str = ".cargo-lock";
+
+/// How a target directory lock is held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockMode {
+    /// Compatible with other cargo-hold readers, excludes cargo builds
+    Shared,
+    /// Excludes cargo builds and every other cargo-hold process
+    Exclusive,
+}
+
+/// Cargo's locks on every profile directory of a target directory.
+///
+/// The locks are released when this value is dropped.
+#[derive(Debug)]
+pub(crate) struct TargetDirLock {
+    _files: Vec<File>,
+}
+
+impl TargetDirLock {
+    /// Lock every profile directory under `target_dir`, waiting for running
+    /// builds to release their locks.
+    ///
+    /// Profile directories are locked in a stable order so that concurrent
+    /// cargo-hold processes cannot deadlock. A missing target directory is
+    /// not an error; the returned lock is simply empty.
+    pub(crate) fn acquire(target_dir: &Path, mode: LockMode, log: &Logger) -> Result<Self> {
+        let mut files = Vec::new();
+
+        for lock_path in lock_paths(target_dir)? {
+            files.push(acquire_file(&lock_path, mode, log)?);
+        }
+
+        Ok(Self { _files: files })
+    }
+}
+
+/// Lock file cargo holds while downloading into the cargo home
+const PACKAGE_CACHE_LOCK_FILE: &str = ".package-cache";
+
+/// Lock file cargo holds (shared) while reading the cargo home caches
+const PACKAGE_CACHE_MUTATE_LOCK_FILE: &str = ".package-cache-mutate";
+
+/// Cargo's exclusive lock on the package caches of a cargo home, as taken by
+/// `cargo clean gc`.
+///
+/// The lock is released when this value is dropped.
+#[derive(Debug)]
+pub(crate) struct PackageCacheLock {
+    _files: Vec<File>,
+}
+
+impl PackageCacheLock {
+    /// Lock the package caches of `cargo_home`, waiting for running cargo
+    /// commands to release them.
+    ///
+    /// Follows cargo's order: the download lock first, then the mutate lock.
+    pub(crate) fn acquire(cargo_home: &Path, log: &Logger) -> Result<Self> {
+        let mut files = Vec::new();
+        for name in [PACKAGE_CACHE_LOCK_FILE, PACKAGE_CACHE_MUTATE_LOCK_FILE] {
+            files.push(acquire_file(
+                &cargo_home.join(name),
+                LockMode::Exclusive,
+                log,
+            )?);
+        }
+
+        Ok(Self { _files: files })
+    }
+}
+
+/// Create the lock files of the package caches of `cargo_home` that do not
+/// exist yet, returning the paths of both
+pub(crate) fn create_package_cache_lock_files(cargo_home: &Path) -> Result<Vec<PathBuf>> {
+    [PACKAGE_CACHE_LOCK_FILE, PACKAGE_CACHE_MUTATE_LOCK_FILE]
+        .into_iter()
+        .map(|name| {
+            let path = cargo_home.join(name);
+            open_lock_file(&path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Lock the file at `lock_path`, reporting when another process makes us wait
+fn acquire_file(lock_path: &Path, mode: LockMode, log: &Logger) -> Result<File> {
+    let file = open_lock_file(lock_path)?;
+
+    match try_lock(&file, mode) {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            log.info(format!(
+                "Blocking waiting for file lock on {}",
+                lock_path.display()
+            ));
+            lock(&file, mode).map_err(|source| HoldError::IoError {
+                path: lock_path.to_path_buf(),
+                source,
+            })?;
+        }
+        Err(TryLockError::Error(source)) => {
+            return Err(HoldError::IoError {
+                path: lock_path.to_path_buf(),
+                source,
+            });
+        }
+    }
+
+    log.verbose(2, format!("Locked {}", lock_path.display()));
+    Ok(file)
+}
+
+/// Check whether another process holds the lock file at `path`.
+///
+/// Missing or unreadable lock files count as unlocked.
+pub(crate) fn is_locked(path: &Path) -> bool {
+    let Ok(file) = OpenOptions::new().read(true).write(true).open(path) else {
+        return false;
+    };
+
+    match try_lock(&file, LockMode::Exclusive) {
+        Ok(()) => {
+            let _ = FileExt::unlock(&file);
+            false
+        }
+        Err(TryLockError::WouldBlock) => true,
+        Err(TryLockError::Error(_)) => false,
+    }
+}
+
+/// Lock file paths for every profile directory, sorted
+pub(crate) fn lock_paths(target_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !target_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = find_profile_directories(target_dir)?
+        .into_iter()
+        .map(|profile_dir| profile_dir.join(CARGO_LOCK_FILE))
+        .collect();
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
+}
+
+fn open_lock_file(path: &Path) -> Result<File> {
+    // Cargo creates the file the same way, so creating it here is harmless.
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+// Explicit trait calls below: the inherent `File` locking methods only exist
+// from Rust 1.89.
+
+fn try_lock(file: &File, mode: LockMode) -> std::result::Result<(), TryLockError> {
+    match mode {
+        LockMode::Shared => FileExt::try_lock_shared(file),
+        LockMode::Exclusive => FileExt::try_lock(file),
+    }
+}
+
+fn lock(file: &File, mode: LockMode) -> std::io::Result<()> {
+    match mode {
+        LockMode::Shared => FileExt::lock_shared(file),
+        LockMode::Exclusive => FileExt::lock(file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn target_with_profiles(profiles: &[&str]) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for profile in profiles {
+            fs::create_dir_all(temp_dir.path().join(profile).join("deps")).unwrap();
+        }
+        temp_dir
+    }
+
+    #[test]
+    fn test_acquire_locks_every_profile() {
+        let temp_dir = target_with_profiles(&["debug", "release"]);
+        let log = Logger::new(0, true);
+
+        let lock = TargetDirLock::acquire(temp_dir.path(), LockMode::Exclusive, &log).unwrap();
+        for profile in ["debug", "release"] {
+            assert!(is_locked(
+                &temp_dir.path().join(profile).join(CARGO_LOCK_FILE)
+            ));
+        }
+
+        drop(lock);
+        assert!(!is_locked(
+            &temp_dir.path().join("debug").join(CARGO_LOCK_FILE)
+        ));
+    }
+
+    #[test]
+    fn test_shared_locks_coexist() {
+        let temp_dir = target_with_profiles(&["debug"]);
+        let log = Logger::new(0, true);
+
+        let first = TargetDirLock::acquire(temp_dir.path(), LockMode::Shared, &log).unwrap();
+        let second = TargetDirLock::acquire(temp_dir.path(), LockMode::Shared, &log).unwrap();
+
+        let lock_path = temp_dir.path().join("debug").join(CARGO_LOCK_FILE);
+        let probe = open_lock_file(&lock_path).unwrap();
+        assert!(matches!(
+            try_lock(&probe, LockMode::Exclusive),
+            Err(TryLockError::WouldBlock)
+        ));
+
+        drop((first, second));
+        assert!(try_lock(&probe, LockMode::Exclusive).is_ok());
+    }
+
+    #[test]
+    fn test_missing_target_dir_is_not_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = Logger::new(0, true);
+
+        let missing = temp_dir.path().join("target");
+        assert!(lock_paths(&missing).unwrap().is_empty());
+        TargetDirLock::acquire(&missing, LockMode::Exclusive, &log).unwrap();
+    }
+
+    #[test]
+    fn test_package_cache_lock() {
+        let cargo_home = TempDir::new().unwrap();
+        let log = Logger::new(0, true);
+
+        let lock = PackageCacheLock::acquire(cargo_home.path(), &log).unwrap();
+        for name in [PACKAGE_CACHE_LOCK_FILE, PACKAGE_CACHE_MUTATE_LOCK_FILE] {
+            assert!(is_locked(&cargo_home.path().join(name)));
+        }
+
+        drop(lock);
+        assert!(!is_locked(
+            &cargo_home.path().join(PACKAGE_CACHE_MUTATE_LOCK_FILE)
+        ));
+    }
+}
@@ -1,14 +1,167 @@
 use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[derive(Clone, Copy, Debug)]
+use crate::context::HoldContext;
+use crate::gc::SizeUnits;
+use crate::reporting::{CiMode, github};
+
+/// An item of a command's summary, such as `12 modified`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryItem {
+    /// What the value counts or measures
+    pub label: &'static str,
+    /// The value as displayed
+    pub value: String,
+    /// The value as a number, for metrics reports
+    pub metric: Option<Metric>,
+}
+
+/// Numeric value of a summary item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// A number of things
+    Count(usize),
+    /// A size in bytes
+    Bytes(u64),
+}
+
+/// Items of the one-line summary printed by --summary-only, in the order
+/// they were first recorded
+#[derive(Debug, Default)]
+pub(crate) struct SummaryItems(Mutex<Vec<SummaryItem>>);
+
+impl SummaryItems {
+    /// Record `item`, replacing the value of an item with the same label, so
+    /// a command running several steps reports the last step's figure in the
+    /// first step's position
+    fn record(&self, item: SummaryItem) {
+        let mut items = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match items
+            .iter_mut()
+            .find(|existing| existing.label == item.label)
+        {
+            Some(existing) => *existing = item,
+            None => items.push(item),
+        }
+    }
+
+    /// Take the items recorded so far
+    fn take(&self) -> Vec<SummaryItem> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Summary of a finished command, displayed as a single line, e.g.
+/// `anchor: 58,213 files · 12 modified · 3 added · 0 errors · 4.2s`
+#[derive(Debug)]
+pub struct Summary {
+    /// Name of the command
+    pub command: &'static str,
+    /// Items recorded with [`Logger::summarize`] and friends
+    pub items: Vec<SummaryItem>,
+    /// Warnings reported by the command
+    pub warnings: usize,
+    /// How long the command took
+    pub elapsed: Duration,
+    /// Whether the command failed
+    pub failed: bool,
+}
+
+impl Summary {
+    /// Take the items recorded in `context` so far into the summary of
+    /// `command`, with the warnings emitted in it
+    pub fn take(
+        context: &HoldContext,
+        command: &'static str,
+        elapsed: Duration,
+        failed: bool,
+    ) -> Self {
+        Self {
+            command,
+            items: context.summary.take(),
+            warnings: context.warnings_emitted(),
+            elapsed,
+            failed,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| format!("{} {}", item.value, item.label))
+            .collect();
+        if self.warnings > 0 {
+            parts.push(format!(
+                "{} warning{}",
+                format_count(self.warnings),
+                if self.warnings == 1 { "" } else { "s" }
+            ));
+        }
+        if self.failed {
+            parts.push("failed".to_string());
+        }
+        parts.push(format!("{:.1}s", self.elapsed.as_secs_f64()));
+
+        write!(f, "{}: {}", self.command, parts.join(" · "))
+    }
+}
+
+/// Format a count with thousands separators, e.g. `58,213`
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+#[derive(Clone, Debug)]
 pub struct Logger {
     verbose: u8,
     quiet: bool,
+    /// CI system annotations are written for
+    ci: CiMode,
+    /// Units sizes are printed in
+    units: SizeUnits,
+    /// Warnings reported so far, shared with the context the logger was
+    /// made for
+    warnings: Arc<AtomicUsize>,
+    /// Items of the command's summary, shared with the context the logger
+    /// was made for
+    summary: Arc<SummaryItems>,
 }
 
 impl Logger {
+    /// A logger of its own, outside of any [`HoldContext`]
     pub fn new(verbose: u8, quiet: bool) -> Self {
-        Self { verbose, quiet }
+        Self {
+            verbose,
+            quiet,
+            ci: CiMode::Auto.resolve(),
+            units: SizeUnits::default(),
+            warnings: Arc::default(),
+            summary: Arc::default(),
+        }
+    }
+
+    pub(crate) fn with_context(verbose: u8, quiet: bool, context: &HoldContext) -> Self {
+        Self {
+            verbose,
+            quiet,
+            ci: context.ci,
+            units: context.size_units,
+            warnings: context.warnings.clone(),
+            summary: context.summary.clone(),
+        }
     }
 
     pub fn info(&self, message: impl Display) {
@@ -17,6 +170,76 @@ impl Logger {
         }
     }
 
+    /// Report a warning, which fails the command under --deny-warnings.
+    ///
+    /// In GitHub Actions the warning becomes an annotation.
+    pub fn warn(&self, message: impl Display) {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
+        if self.quiet {
+            return;
+        }
+        if self.ci == CiMode::GitHub {
+            println!(
+                "{}",
+                github::annotation("warning", None, &message.to_string())
+            );
+        } else {
+            eprintln!("Warning: {message}");
+        }
+    }
+
+    /// Report something worth noticing that is not a warning, so it does not
+    /// fail the command under --deny-warnings.
+    ///
+    /// In GitHub Actions the notice becomes an annotation.
+    pub fn notice(&self, message: impl Display) {
+        if self.quiet {
+            return;
+        }
+        if self.ci == CiMode::GitHub {
+            println!(
+                "{}",
+                github::annotation("notice", None, &message.to_string())
+            );
+        } else {
+            eprintln!("Notice: {message}");
+        }
+    }
+
+    /// Record an item of the command's one-line summary, such as
+    /// `12 modified`.
+    ///
+    /// Recording a label again replaces its value, so a command running
+    /// several steps reports the last step's figure in the first step's
+    /// position.
+    pub fn summarize(&self, label: &'static str, value: impl Display) {
+        self.summary.record(SummaryItem {
+            label,
+            value: value.to_string(),
+            metric: None,
+        });
+    }
+
+    /// Record a count in the command's one-line summary, with thousands
+    /// separators
+    pub fn summarize_count(&self, label: &'static str, count: usize) {
+        self.summary.record(SummaryItem {
+            label,
+            value: format_count(count),
+            metric: Some(Metric::Count(count)),
+        });
+    }
+
+    /// Record a size in the command's one-line summary, printed in
+    /// [`Logger::units`]
+    pub fn summarize_size(&self, label: &'static str, bytes: u64) {
+        self.summary.record(SummaryItem {
+            label,
+            value: self.units.format(bytes),
+            metric: Some(Metric::Bytes(bytes)),
+        });
+    }
+
     pub fn verbose(&self, level: u8, message: impl Display) {
         if !self.quiet && self.verbose >= level {
             eprintln!("{message}");
@@ -30,4 +253,61 @@ impl Logger {
     pub fn level(&self) -> u8 {
         self.verbose
     }
+
+    /// Number of warnings reported so far
+    pub fn warnings(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    /// CI system the command reports to
+    pub(crate) fn ci(&self) -> CiMode {
+        self.ci
+    }
+
+    /// Units sizes are printed in
+    pub fn units(&self) -> SizeUnits {
+        self.units
+    }
+
+    /// Format a size in human-readable format, in [`Logger::units`]
+    pub fn format_size(&self, bytes: u64) -> String {
+        self.units.format(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(58213), "58,213");
+        assert_eq!(format_count(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn test_summary_formats_one_line() {
+        let context = HoldContext {
+            size_units: SizeUnits::Binary,
+            ..HoldContext::default()
+        };
+        let log = context.logger(0, true);
+        log.summarize_count("files", 58213);
+        log.summarize_count("modified", 12);
+        log.summarize_count("files", 58214);
+        log.summarize_size("freed", 1536 * 1024 * 1024);
+        log.warn("something");
+
+        let summary = Summary::take(&context, "anchor", Duration::from_millis(4230), false);
+        assert_eq!(
+            summary.to_string(),
+            "anchor: 58,214 files · 12 modified · 1.5 GiB freed · 1 warning · 4.2s"
+        );
+
+        let summary = Summary::take(&HoldContext::default(), "heave", Duration::ZERO, true);
+        assert_eq!(summary.to_string(), "heave: failed · 0.0s");
+    }
 }
@@ -20,6 +20,12 @@
 //! - **bilge**: Clears metadata for a fresh start
 //! - **heave**: Garbage collection for build artifacts
 //! - **voyage**: Combined anchor + heave for complete CI workflow
+//! - **embark** / **dock**: A voyage split around the build (salvage + heave
+//!   before, stow + build record after)
+//! - **report-build**: Records a build's outcome and duration for GC
+//! - **install-hooks**: Installs git hooks that salvage timestamps locally
+//! - **self-update**: Updates cargo-hold to the latest release
+//! - **schema**: Prints the JSON Schema of cargo-hold's JSON output
 //!
 //! ## Quick Start
 //!
@@ -0,0 +1,152 @@
+//! Content-addressed store of canonical timestamps.
+//!
+//! With `--dedup-store`, `stow` records the timestamp of every file's
+//! contents in a file of their own, and `salvage` gives files missing from
+//! the metadata the timestamp recorded for their contents. Files that were
+//! moved or copied thus keep their timestamp instead of counting as added,
+//! even when their contents left the repository for a few commits in
+//! between. Contents no stow has seen for [`RETAINED_STOWS`] stows are
+//! forgotten, so the store does not grow without bound.
+//!
+//! The store is signed like the metadata when the command has a metadata key.
+//! Being optional, a store that cannot be read is ignored with a warning.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+use rkyv::rancor::BoxedError;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::{signature, write_atomically};
+use crate::context::HoldContext;
+use crate::error::{HoldError, Result};
+use crate::hashing::LargeFilePolicy;
+use crate::logging::Logger;
+use crate::state::StateMetadata;
+
+/// Stows an entry survives without its contents being seen
+pub(crate) const RETAINED_STOWS: u32 = 16;
+
+/// Canonical timestamps of file contents, by hash
+#[derive(Archive, Deserialize, Serialize, Debug, Default, PartialEq)]
+pub(crate) struct DedupStore {
+    /// Identifies the salt of the hashes, as [`StateMetadata::salt_id`] does
+    salt_id: Option<[u8; 16]>,
+    /// Number of stows recorded so far
+    stows: u32,
+    entries: HashMap<[u8; 32], DedupEntry>,
+}
+
+/// Timestamp of a file's contents
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+struct DedupEntry {
+    size: u64,
+    mtime_nanos: u64,
+    /// Value of [`DedupStore::stows`] when the contents were last seen
+    last_seen: u32,
+}
+
+impl DedupStore {
+    /// Loads the store at `path` for hashes keyed with the salt `salt_id`;
+    /// the store is empty if missing, unreadable, or recorded with another
+    /// salt
+    pub(crate) fn load(
+        context: &HoldContext,
+        path: &Path,
+        salt_id: Option<[u8; 16]>,
+        log: &Logger,
+    ) -> Self {
+        let empty = Self {
+            salt_id,
+            ..Self::default()
+        };
+        match Self::read(path, context.metadata_key.as_ref()) {
+            Ok(Some(store)) if store.salt_id == salt_id => store,
+            Ok(Some(_)) => {
+                log.verbose(
+                    1,
+                    "Dedup store was recorded with another salt, starting afresh",
+                );
+                empty
+            }
+            Ok(None) => empty,
+            Err(e) => {
+                log.warn(format!("Ignoring dedup store {}: {e}", path.display()));
+                empty
+            }
+        }
+    }
+
+    fn read(path: &Path, key: Option<&[u8; 32]>) -> Result<Option<Self>> {
+        let io_error = |source| HoldError::IoError {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_error(e)),
+        };
+        if file.metadata().map_err(io_error)?.len() == 0 {
+            return Ok(None);
+        }
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+        let bytes = signature::verify(&mmap, key, path)?;
+        rkyv::from_bytes::<Self, BoxedError>(bytes)
+            .map(Some)
+            .map_err(HoldError::DeserializationError)
+    }
+
+    /// Saves the store to `path` atomically, signed with the metadata key of
+    /// `context`
+    pub(crate) fn save(&self, context: &HoldContext, path: &Path) -> Result<()> {
+        write_atomically(path, context.metadata_key.as_ref(), |out, temp_path| {
+            let bytes = rkyv::to_bytes::<BoxedError>(self)
+                .map_err(|e| HoldError::SerializationError(Box::new(e)))?;
+            std::io::Write::write_all(out, &bytes).map_err(|source| HoldError::IoError {
+                path: temp_path.to_path_buf(),
+                source,
+            })
+        })
+    }
+
+    /// Canonical timestamp of contents of `size` bytes hashing to `hash`
+    pub(crate) fn canonical_mtime(&self, size: u64, hash: &[u8; 32]) -> Option<u64> {
+        self.entries
+            .get(hash)
+            .filter(|entry| entry.size == size)
+            .map(|entry| entry.mtime_nanos)
+    }
+
+    /// Records the timestamps of the files of `metadata` as one stow
+    ///
+    /// Contents already in the store keep their canonical timestamp. Files
+    /// hashed in part or not at all under the `--large-file-policy` of
+    /// `context` are left out, as their hashes do not identify their contents.
+    pub(crate) fn record(&mut self, context: &HoldContext, metadata: &StateMetadata) {
+        self.stows = self.stows.wrapping_add(1);
+        let stows = self.stows;
+        for state in metadata.files.values() {
+            if context.large_file_policy(state.size) != LargeFilePolicy::Full {
+                continue;
+            }
+            self.entries
+                .entry(state.hash)
+                .and_modify(|entry| entry.last_seen = stows)
+                .or_insert(DedupEntry {
+                    size: state.size,
+                    mtime_nanos: state.mtime_nanos,
+                    last_seen: stows,
+                });
+        }
+        self.entries
+            .retain(|_, entry| stows.wrapping_sub(entry.last_seen) < RETAINED_STOWS);
+    }
+
+    /// Number of contents in the store
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -1,22 +1,96 @@
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 
 use memmap2::Mmap;
 use rkyv::{Archive, Deserialize, Serialize};
 
+use crate::context::HoldContext;
 use crate::error::{HoldError, Result};
-use crate::state::{FileState, GcMetrics, METADATA_VERSION, StateMetadata};
+use crate::state::{
+    CapTrace, DirectorySize, FileState, GcMetrics, LinkedFileSize, METADATA_VERSION, StateMetadata,
+};
+use crate::timestamp::clamp_file_mtime_nanos;
 
+mod dedup;
+mod shards;
+mod signature;
 #[cfg(test)]
 mod tests;
 
+pub(crate) use dedup::DedupStore;
+use signature::Signer;
+pub(crate) use signature::derive_key;
+
+/// Legacy file state of v1 to v11 metadata files (timestamps as `u128`
+/// nanoseconds, hashes hex-encoded).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct FileStateV11 {
+    #[rkyv(with = rkyv::with::AsString)]
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: String,
+    pub mtime_nanos: u128,
+}
+
+impl From<FileStateV11> for FileState {
+    fn from(v11: FileStateV11) -> Self {
+        FileState {
+            path: v11.path,
+            size: v11.size,
+            hash: legacy_hash(&v11.hash),
+            mtime_nanos: clamp_file_mtime_nanos(v11.mtime_nanos),
+        }
+    }
+}
+
+/// Legacy file state of v12 metadata files (hashes hex-encoded).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+struct FileStateV12 {
+    #[rkyv(with = rkyv::with::AsString)]
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: String,
+    pub mtime_nanos: u64,
+}
+
+impl From<FileStateV12> for FileState {
+    fn from(v12: FileStateV12) -> Self {
+        FileState {
+            path: v12.path,
+            size: v12.size,
+            hash: legacy_hash(&v12.hash),
+            mtime_nanos: v12.mtime_nanos,
+        }
+    }
+}
+
+/// Decodes a hex-encoded legacy hash.
+///
+/// Hashes of large files tracked by `--large-file-policy head` or `skip` were
+/// not plain hex; they become a value no file's hash equals, so those files
+/// are hashed again once.
+fn legacy_hash(hex: &str) -> [u8; 32] {
+    match blake3::Hash::from_hex(hex) {
+        Ok(hash) => *hash.as_bytes(),
+        Err(_) => blake3::derive_key("cargo-hold 2025-01 legacy hash", hex.as_bytes()),
+    }
+}
+
+/// Converts legacy file states, clamping their timestamps to `u64`
+fn upgrade_file_states(files: HashMap<String, FileStateV11>) -> HashMap<String, FileState> {
+    files
+        .into_iter()
+        .map(|(key, state)| (key, state.into()))
+        .collect()
+}
+
 /// Legacy layout for v2 metadata files (without GC metrics).
 #[derive(Archive, Deserialize, Serialize, Debug, Clone)]
 struct StateMetadataV2 {
     pub version: u32,
-    pub files: HashMap<String, FileState>,
+    pub files: HashMap<String, FileStateV11>,
     pub last_gc_mtime_nanos: Option<u128>,
 }
 
@@ -24,9 +98,15 @@ impl From<StateMetadataV2> for StateMetadata {
     fn from(v2: StateMetadataV2) -> Self {
         StateMetadata {
             version: v2.version,
-            files: v2.files,
+            files: upgrade_file_states(v2.files),
             last_gc_mtime_nanos: v2.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: Vec::new(),
             gc_metrics: GcMetrics::default(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
         }
     }
 }
@@ -35,7 +115,7 @@ impl From<StateMetadataV2> for StateMetadata {
 #[derive(Archive, Deserialize, Serialize, Debug, Clone)]
 struct StateMetadataV3 {
     pub version: u32,
-    pub files: HashMap<String, FileState>,
+    pub files: HashMap<String, FileStateV11>,
     pub last_gc_mtime_nanos: Option<u128>,
     pub gc_metrics: GcMetricsV3,
 }
@@ -53,8 +133,9 @@ impl From<StateMetadataV3> for StateMetadata {
     fn from(v3: StateMetadataV3) -> Self {
         StateMetadata {
             version: v3.version,
-            files: v3.files,
+            files: upgrade_file_states(v3.files),
             last_gc_mtime_nanos: v3.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: Vec::new(),
             gc_metrics: GcMetrics {
                 runs: v3.gc_metrics.runs,
                 seed_initial_size: v3.gc_metrics.seed_initial_size,
@@ -63,7 +144,465 @@ impl From<StateMetadataV3> for StateMetadata {
                 last_suggested_cap: v3.gc_metrics.last_suggested_cap,
                 recent_final_sizes: Vec::new(),
                 last_cap_trace: None,
+                profile_build_mtime_nanos: HashMap::new(),
+                directory_sizes: HashMap::new(),
+                recent_builds: Vec::new(),
             },
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy GC metrics layout shared by v4 and v5 metadata files (without
+/// per-profile build timestamps).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+struct GcMetricsV4 {
+    pub runs: u32,
+    pub seed_initial_size: Option<u64>,
+    pub recent_initial_sizes: Vec<u64>,
+    pub recent_bytes_freed: Vec<u64>,
+    pub last_suggested_cap: Option<u64>,
+    pub recent_final_sizes: Vec<u64>,
+    pub last_cap_trace: Option<CapTrace>,
+}
+
+impl From<GcMetricsV4> for GcMetrics {
+    fn from(v4: GcMetricsV4) -> Self {
+        GcMetrics {
+            runs: v4.runs,
+            seed_initial_size: v4.seed_initial_size,
+            recent_initial_sizes: v4.recent_initial_sizes,
+            recent_bytes_freed: v4.recent_bytes_freed,
+            last_suggested_cap: v4.last_suggested_cap,
+            recent_final_sizes: v4.recent_final_sizes,
+            last_cap_trace: v4.last_cap_trace,
+            profile_build_mtime_nanos: HashMap::new(),
+            directory_sizes: HashMap::new(),
+            recent_builds: Vec::new(),
+        }
+    }
+}
+
+/// Legacy layout for v4 metadata files (single previous GC timestamp).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV4 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub gc_metrics: GcMetricsV4,
+}
+
+impl From<StateMetadataV4> for StateMetadata {
+    fn from(v4: StateMetadataV4) -> Self {
+        StateMetadata {
+            version: v4.version,
+            files: upgrade_file_states(v4.files),
+            last_gc_mtime_nanos: v4.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: Vec::new(),
+            gc_metrics: v4.gc_metrics.into(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout for v5 metadata files (GC timestamp history, global only).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV5 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetricsV4,
+}
+
+impl From<StateMetadataV5> for StateMetadata {
+    fn from(v5: StateMetadataV5) -> Self {
+        StateMetadata {
+            version: v5.version,
+            files: upgrade_file_states(v5.files),
+            last_gc_mtime_nanos: v5.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v5.recent_gc_mtime_nanos,
+            gc_metrics: v5.gc_metrics.into(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy GC metrics layout for v6 metadata files (without the directory
+/// size index).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+struct GcMetricsV6 {
+    pub runs: u32,
+    pub seed_initial_size: Option<u64>,
+    pub recent_initial_sizes: Vec<u64>,
+    pub recent_bytes_freed: Vec<u64>,
+    pub last_suggested_cap: Option<u64>,
+    pub recent_final_sizes: Vec<u64>,
+    pub last_cap_trace: Option<CapTrace>,
+    pub profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+}
+
+impl From<GcMetricsV6> for GcMetrics {
+    fn from(v6: GcMetricsV6) -> Self {
+        GcMetrics {
+            runs: v6.runs,
+            seed_initial_size: v6.seed_initial_size,
+            recent_initial_sizes: v6.recent_initial_sizes,
+            recent_bytes_freed: v6.recent_bytes_freed,
+            last_suggested_cap: v6.last_suggested_cap,
+            recent_final_sizes: v6.recent_final_sizes,
+            last_cap_trace: v6.last_cap_trace,
+            profile_build_mtime_nanos: v6.profile_build_mtime_nanos,
+            directory_sizes: HashMap::new(),
+            recent_builds: Vec::new(),
+        }
+    }
+}
+
+/// Legacy layout for v6 metadata files (per-profile build timestamps).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV6 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetricsV6,
+}
+
+impl From<StateMetadataV6> for StateMetadata {
+    fn from(v6: StateMetadataV6) -> Self {
+        StateMetadata {
+            version: v6.version,
+            files: upgrade_file_states(v6.files),
+            last_gc_mtime_nanos: v6.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v6.recent_gc_mtime_nanos,
+            gc_metrics: v6.gc_metrics.into(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy directory size index entry of v7 metadata files (without file
+/// counts).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+struct DirectorySizeV7 {
+    pub mtime_nanos: u128,
+    pub apparent: u64,
+    pub allocated: u64,
+    pub linked_files: Vec<LinkedFileSize>,
+    pub subdirs: Vec<String>,
+}
+
+/// Legacy GC metrics layout for v7 metadata files.
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+struct GcMetricsV7 {
+    pub runs: u32,
+    pub seed_initial_size: Option<u64>,
+    pub recent_initial_sizes: Vec<u64>,
+    pub recent_bytes_freed: Vec<u64>,
+    pub last_suggested_cap: Option<u64>,
+    pub recent_final_sizes: Vec<u64>,
+    pub last_cap_trace: Option<CapTrace>,
+    pub profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+    pub directory_sizes: HashMap<String, DirectorySizeV7>,
+}
+
+impl From<GcMetricsV7> for GcMetrics {
+    fn from(v7: GcMetricsV7) -> Self {
+        GcMetrics {
+            runs: v7.runs,
+            seed_initial_size: v7.seed_initial_size,
+            recent_initial_sizes: v7.recent_initial_sizes,
+            recent_bytes_freed: v7.recent_bytes_freed,
+            last_suggested_cap: v7.last_suggested_cap,
+            recent_final_sizes: v7.recent_final_sizes,
+            last_cap_trace: v7.last_cap_trace,
+            profile_build_mtime_nanos: v7.profile_build_mtime_nanos,
+            // Entries without file counts are rebuilt by the next GC
+            directory_sizes: HashMap::new(),
+            recent_builds: Vec::new(),
+        }
+    }
+}
+
+/// Legacy layout for v7 metadata files (directory size index without file
+/// counts).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV7 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetricsV7,
+}
+
+impl From<StateMetadataV7> for StateMetadata {
+    fn from(v7: StateMetadataV7) -> Self {
+        StateMetadata {
+            version: v7.version,
+            files: upgrade_file_states(v7.files),
+            last_gc_mtime_nanos: v7.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v7.recent_gc_mtime_nanos,
+            gc_metrics: v7.gc_metrics.into(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy GC metrics layout for v8 and v9 metadata files (without reported
+/// builds).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+struct GcMetricsV9 {
+    pub runs: u32,
+    pub seed_initial_size: Option<u64>,
+    pub recent_initial_sizes: Vec<u64>,
+    pub recent_bytes_freed: Vec<u64>,
+    pub last_suggested_cap: Option<u64>,
+    pub recent_final_sizes: Vec<u64>,
+    pub last_cap_trace: Option<CapTrace>,
+    pub profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+    pub directory_sizes: HashMap<String, DirectorySize>,
+}
+
+impl From<GcMetricsV9> for GcMetrics {
+    fn from(v9: GcMetricsV9) -> Self {
+        GcMetrics {
+            runs: v9.runs,
+            seed_initial_size: v9.seed_initial_size,
+            recent_initial_sizes: v9.recent_initial_sizes,
+            recent_bytes_freed: v9.recent_bytes_freed,
+            last_suggested_cap: v9.last_suggested_cap,
+            recent_final_sizes: v9.recent_final_sizes,
+            last_cap_trace: v9.last_cap_trace,
+            profile_build_mtime_nanos: v9.profile_build_mtime_nanos,
+            directory_sizes: v9.directory_sizes,
+            recent_builds: Vec::new(),
+        }
+    }
+}
+
+/// Legacy layout for v8 metadata files (without pinned crates).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV8 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetricsV9,
+}
+
+impl From<StateMetadataV8> for StateMetadata {
+    fn from(v8: StateMetadataV8) -> Self {
+        StateMetadata {
+            version: v8.version,
+            files: upgrade_file_states(v8.files),
+            last_gc_mtime_nanos: v8.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v8.recent_gc_mtime_nanos,
+            gc_metrics: v8.gc_metrics.into(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout for v9 metadata files (without reported builds).
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV9 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetricsV9,
+    pub pinned_crates: Vec<String>,
+}
+
+impl From<StateMetadataV9> for StateMetadata {
+    fn from(v9: StateMetadataV9) -> Self {
+        StateMetadata {
+            version: v9.version,
+            files: upgrade_file_states(v9.files),
+            last_gc_mtime_nanos: v9.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v9.recent_gc_mtime_nanos,
+            gc_metrics: v9.gc_metrics.into(),
+            pinned_crates: v9.pinned_crates,
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout for v10 and v11 metadata files (timestamps as `u128`
+/// nanoseconds); v11 files store it in shards.
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV11 {
+    pub version: u32,
+    pub files: HashMap<String, FileStateV11>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetrics,
+    pub pinned_crates: Vec<String>,
+}
+
+impl From<StateMetadataV11> for StateMetadata {
+    fn from(v11: StateMetadataV11) -> Self {
+        StateMetadata {
+            version: v11.version,
+            files: upgrade_file_states(v11.files),
+            last_gc_mtime_nanos: v11.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v11.recent_gc_mtime_nanos,
+            gc_metrics: v11.gc_metrics,
+            pinned_crates: v11.pinned_crates,
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout of the header of sharded v11 to v14 metadata files, before
+/// the salt id was recorded
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV14 {
+    pub version: u32,
+    pub files: HashMap<String, FileState>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetrics,
+    pub pinned_crates: Vec<String>,
+}
+
+impl From<StateMetadataV14> for StateMetadata {
+    fn from(v14: StateMetadataV14) -> Self {
+        StateMetadata {
+            version: v14.version,
+            files: v14.files,
+            last_gc_mtime_nanos: v14.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v14.recent_gc_mtime_nanos,
+            gc_metrics: v14.gc_metrics,
+            pinned_crates: v14.pinned_crates,
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout of the header of sharded v15 metadata files, before the
+/// rollup hashes of directories were recorded
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV15 {
+    pub version: u32,
+    pub files: HashMap<String, FileState>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetrics,
+    pub pinned_crates: Vec<String>,
+    pub salt_id: Option<[u8; 16]>,
+}
+
+impl From<StateMetadataV15> for StateMetadata {
+    fn from(v15: StateMetadataV15) -> Self {
+        StateMetadata {
+            version: v15.version,
+            files: v15.files,
+            last_gc_mtime_nanos: v15.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v15.recent_gc_mtime_nanos,
+            gc_metrics: v15.gc_metrics,
+            pinned_crates: v15.pinned_crates,
+            salt_id: v15.salt_id,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout of the header of sharded v16 metadata files, before the
+/// build costs of crates were recorded
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV16 {
+    pub version: u32,
+    pub files: HashMap<String, FileState>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetrics,
+    pub pinned_crates: Vec<String>,
+    pub salt_id: Option<[u8; 16]>,
+    pub dir_hashes: HashMap<String, [u8; 32]>,
+}
+
+impl From<StateMetadataV16> for StateMetadata {
+    fn from(v16: StateMetadataV16) -> Self {
+        StateMetadata {
+            version: v16.version,
+            files: v16.files,
+            last_gc_mtime_nanos: v16.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v16.recent_gc_mtime_nanos,
+            gc_metrics: v16.gc_metrics,
+            pinned_crates: v16.pinned_crates,
+            salt_id: v16.salt_id,
+            dir_hashes: v16.dir_hashes,
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+}
+
+/// Legacy layout of the header of sharded v17 metadata files, before the
+/// window of the GC metrics was recorded
+#[derive(Archive, Deserialize, Serialize, Debug, Clone)]
+struct StateMetadataV17 {
+    pub version: u32,
+    pub files: HashMap<String, FileState>,
+    pub last_gc_mtime_nanos: Option<u128>,
+    pub recent_gc_mtime_nanos: Vec<u128>,
+    pub gc_metrics: GcMetrics,
+    pub pinned_crates: Vec<String>,
+    pub salt_id: Option<[u8; 16]>,
+    pub dir_hashes: HashMap<String, [u8; 32]>,
+    pub build_costs: HashMap<String, u64>,
+}
+
+impl From<StateMetadataV17> for StateMetadata {
+    fn from(v17: StateMetadataV17) -> Self {
+        StateMetadata {
+            version: v17.version,
+            files: v17.files,
+            last_gc_mtime_nanos: v17.last_gc_mtime_nanos,
+            recent_gc_mtime_nanos: v17.recent_gc_mtime_nanos,
+            gc_metrics: v17.gc_metrics,
+            pinned_crates: v17.pinned_crates,
+            salt_id: v17.salt_id,
+            dir_hashes: v17.dir_hashes,
+            build_costs: v17.build_costs,
+            gc_metrics_window: None,
         }
     }
 }
@@ -72,19 +611,54 @@ impl From<StateMetadataV3> for StateMetadata {
 ///
 /// This function uses memory-mapped I/O and rkyv for extremely fast loading.
 /// If the metadata file doesn't exist, returns empty metadata.
-/// If the metadata file is from an incompatible format, automatically resets
-/// it.
+/// If the metadata file is from an incompatible format, or is not signed with
+/// the metadata key of `context`, automatically resets it.
+///
+/// With `--trusted-metadata` in `context`, the file states are not validated
+/// before they are accessed; see [`parse_metadata`].
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// - The metadata file exists but cannot be read due to I/O issues
 /// - The metadata version is newer than the current supported version
-pub fn load_metadata(metadata_path: &Path) -> Result<StateMetadata> {
-    match load_metadata_inner(metadata_path) {
+pub fn load_metadata(context: &HoldContext, metadata_path: &Path) -> Result<StateMetadata> {
+    load_metadata_versioned(context, metadata_path).map(|(metadata, _)| metadata)
+}
+
+/// Reads the metadata file at `metadata_path` for inspection, e.g. one
+/// copied from another runner.
+///
+/// Unlike [`load_metadata`], the file must exist and is never reset: a file
+/// that cannot be read is an error. Any signature is ignored, and the
+/// contents are always validated.
+pub(crate) fn read_metadata(metadata_path: &Path) -> Result<StateMetadata> {
+    let io_error = |source| HoldError::IoError {
+        path: metadata_path.to_path_buf(),
+        source,
+    };
+    let file = File::open(metadata_path).map_err(io_error)?;
+    let mmap = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+    let bytes = signature::verify(&mmap, None, metadata_path)?;
+    parse_metadata(bytes, false).map(|(metadata, _)| metadata)
+}
+
+/// Loads metadata as [`load_metadata`] does, along with the version it was
+/// stored with; metadata that is missing or was reset counts as stored with
+/// the current version
+pub(crate) fn load_metadata_versioned(
+    context: &HoldContext,
+    metadata_path: &Path,
+) -> Result<(StateMetadata, u32)> {
+    match load_metadata_inner(
+        metadata_path,
+        context.metadata_key.as_ref(),
+        context.trusted_metadata,
+    ) {
         Ok(metadata) => Ok(metadata),
         Err(HoldError::DeserializationError { .. }) => {
             // Any deserialization error is treated as format incompatibility
+            context.record_warning();
             eprintln!("⚠️  Detected incompatible metadata format from previous cargo-hold version");
             eprintln!("   Automatically resetting metadata to use new format...");
 
@@ -94,17 +668,33 @@ pub fn load_metadata(metadata_path: &Path) -> Result<StateMetadata> {
             }
 
             // Return a fresh metadata instance
-            Ok(StateMetadata::new())
+            Ok((StateMetadata::new(), METADATA_VERSION))
+        }
+        Err(error @ HoldError::UnauthenticatedMetadata { .. }) => {
+            // Possibly tampered with, so none of it is used
+            context.record_warning();
+            eprintln!("⚠️  {error}; ignoring it");
+
+            if let Err(remove_err) = fs::remove_file(metadata_path) {
+                eprintln!("   Warning: Could not remove untrusted metadata file: {remove_err}");
+            }
+
+            Ok((StateMetadata::new(), METADATA_VERSION))
         }
         Err(other) => Err(other),
     }
 }
 
-/// Internal function that loads metadata without automatic recovery.
-fn load_metadata_inner(metadata_path: &Path) -> Result<StateMetadata> {
+/// Internal function that loads metadata without automatic recovery,
+/// returning it with the version it was stored with.
+fn load_metadata_inner(
+    metadata_path: &Path,
+    key: Option<&[u8; 32]>,
+    trusted: bool,
+) -> Result<(StateMetadata, u32)> {
     // Check if file exists
     if !metadata_path.exists() {
-        return Ok(StateMetadata::new());
+        return Ok((StateMetadata::new(), METADATA_VERSION));
     }
 
     // Open the file
@@ -120,7 +710,7 @@ fn load_metadata_inner(metadata_path: &Path) -> Result<StateMetadata> {
     })?;
 
     if file_metadata.len() == 0 {
-        return Ok(StateMetadata::new());
+        return Ok((StateMetadata::new(), METADATA_VERSION));
     }
 
     // Memory map the file
@@ -129,29 +719,46 @@ fn load_metadata_inner(metadata_path: &Path) -> Result<StateMetadata> {
         source,
     })?;
 
-    // Deserialize using rkyv, with fallback to the v2 layout that didn't
-    // include GC metrics. This ensures older v2 metadata can still be loaded
-    // and migrated forward without being treated as incompatible.
-    let metadata = deserialize_metadata(&mmap[..])?;
+    let bytes = signature::verify(&mmap, key, metadata_path)?;
+    parse_metadata(bytes, trusted)
+}
+
+/// Decodes metadata file contents and migrates them to the current version,
+/// returning them with the version they were stored with.
+///
+/// The bytes must be aligned like a memory map for rkyv to access them.
+/// Everything is validated before it is deserialized unless `trusted`, in
+/// which case the file states, which make up nearly all of the file, are
+/// not; a corrupted file is then undefined behavior.
+pub(crate) fn parse_metadata(bytes: &[u8], trusted: bool) -> Result<(StateMetadata, u32)> {
+    // Files written before v11 hold a single rkyv buffer; fall back to the
+    // legacy layouts so that older metadata can still be loaded and migrated
+    // forward without being treated as incompatible.
+    let metadata = if shards::is_sharded(bytes) {
+        shards::read(bytes, trusted)?
+    } else {
+        deserialize_metadata(bytes)?
+    };
 
     // Check version compatibility
     if metadata.version > METADATA_VERSION {
-        return Err(HoldError::ConfigError(format!(
-            "Metadata version {} is newer than supported version {}. Please update cargo-hold.",
-            metadata.version, METADATA_VERSION
-        )));
+        return Err(HoldError::MetadataVersionTooNew {
+            found: metadata.version,
+            supported: METADATA_VERSION,
+        });
     }
 
     // Handle migration from older versions
     // Note: Migration happens in memory only. The file format is upgraded
     // to the current version when save_metadata() is next called.
-    let metadata = if metadata.version < METADATA_VERSION {
+    let version = metadata.version;
+    let metadata = if version < METADATA_VERSION {
         migrate_metadata(metadata)?
     } else {
         metadata
     };
 
-    Ok(metadata)
+    Ok((metadata, version))
 }
 
 /// Migrates metadata from older versions to the current version.
@@ -160,6 +767,25 @@ fn load_metadata_inner(metadata_path: &Path) -> Result<StateMetadata> {
 /// Currently handles:
 /// - v1 -> v2: Adds the last_gc_mtime_nanos field (defaults to None)
 /// - v2 -> v3: Adds gc_metrics with defaults
+/// - v3 -> v4: Adds recent_final_sizes and last_cap_trace
+/// - v4 -> v5: Seeds the GC timestamp history from last_gc_mtime_nanos
+/// - v5 -> v6: Adds per-profile build timestamps (empty until the next GC)
+/// - v6 -> v7: Adds the directory size index (empty until the next GC)
+/// - v7 -> v8: Adds file counts to the directory size index (rebuilt by the
+///   next GC)
+/// - v8 -> v9: Adds pinned crates (none pinned)
+/// - v9 -> v10: Adds reported build outcomes (none reported)
+/// - v10 -> v11: Stores the file states in shards (layout only)
+/// - v11 -> v12: Stores file timestamps as `u64` nanoseconds, clamping those
+///   after 2554 (converted while reading)
+/// - v12 -> v13: Stores file hashes as 32 bytes instead of hex (converted while
+///   reading)
+/// - v13 -> v14: Interns the directories of paths in shards (layout only)
+/// - v14 -> v15: Records the salt id of the hashes (unsalted)
+/// - v15 -> v16: Records the rollup hashes of directories (computed from the
+///   file states)
+/// - v16 -> v17: Records the build costs of crates (none recorded)
+/// - v17 -> v18: Records the window of the GC metrics (the default)
 ///
 /// # Arguments
 ///
@@ -190,13 +816,117 @@ fn migrate_metadata(mut metadata: StateMetadata) -> Result<StateMetadata> {
         metadata.version = 4;
     }
 
+    // Migration from v4 to v5: seed the GC timestamp history
+    if metadata.version == 4 {
+        metadata.recent_gc_mtime_nanos = metadata.last_gc_mtime_nanos.into_iter().collect();
+        metadata.version = 5;
+    }
+
+    // Migration from v5 to v6: per-profile build timestamps start empty
+    if metadata.version == 5 {
+        metadata.gc_metrics.profile_build_mtime_nanos.clear();
+        metadata.version = 6;
+    }
+
+    // Migration from v6 to v7: the directory size index starts empty
+    if metadata.version == 6 {
+        metadata.gc_metrics.directory_sizes.clear();
+        metadata.version = 7;
+    }
+
+    // Migration from v7 to v8: the directory size index is rebuilt with file
+    // counts
+    if metadata.version == 7 {
+        metadata.gc_metrics.directory_sizes.clear();
+        metadata.version = 8;
+    }
+
+    // Migration from v8 to v9: no crates are pinned yet
+    if metadata.version == 8 {
+        metadata.pinned_crates.clear();
+        metadata.version = 9;
+    }
+
+    // Migration from v9 to v10: no builds are reported yet
+    if metadata.version == 9 {
+        metadata.gc_metrics.recent_builds.clear();
+        metadata.version = 10;
+    }
+
+    // Migration from v10 to v11: the sharded layout is written on the next
+    // save
+    if metadata.version == 10 {
+        metadata.version = 11;
+    }
+
+    // Migration from v11 to v12: the timestamps were clamped to u64 while
+    // reading the legacy file states
+    if metadata.version == 11 {
+        metadata.version = 12;
+    }
+
+    // Migration from v12 to v13: the hashes were decoded from hex while
+    // reading the legacy file states
+    if metadata.version == 12 {
+        metadata.version = 13;
+    }
+
+    // Migration from v13 to v14: the paths are interned on the next save
+    if metadata.version == 13 {
+        metadata.version = 14;
+    }
+
+    // Migration from v14 to v15: earlier hashes were never salted
+    if metadata.version == 14 {
+        metadata.salt_id = None;
+        metadata.version = 15;
+    }
+
+    // Migration from v15 to v16
+    if metadata.version == 15 {
+        metadata.update_dir_hashes();
+        metadata.version = 16;
+    }
+
+    // Migration from v16 to v17: no build costs are recorded yet
+    if metadata.version == 16 {
+        metadata.build_costs.clear();
+        metadata.version = 17;
+    }
+
+    // Migration from v17 to v18: the GC metrics keep the default window
+    if metadata.version == 17 {
+        metadata.gc_metrics_window = None;
+        metadata.version = 18;
+    }
+
     Ok(metadata)
 }
 
+/// Deserializes metadata written as a single rkyv buffer, which only releases
+/// before v11 wrote, newest layout first
 fn deserialize_metadata(bytes: &[u8]) -> Result<StateMetadata> {
-    match rkyv::from_bytes::<StateMetadata, rkyv::rancor::BoxedError>(bytes) {
-        Ok(metadata) => Ok(metadata),
+    match rkyv::from_bytes::<StateMetadataV11, rkyv::rancor::BoxedError>(bytes) {
+        Ok(v11) => Ok(StateMetadata::from(v11)),
         Err(primary_err) => {
+            if let Ok(v9) = rkyv::from_bytes::<StateMetadataV9, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v9));
+            }
+            if let Ok(v8) = rkyv::from_bytes::<StateMetadataV8, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v8));
+            }
+            if let Ok(v7) = rkyv::from_bytes::<StateMetadataV7, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v7));
+            }
+            if let Ok(v6) = rkyv::from_bytes::<StateMetadataV6, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v6));
+            }
+            if let Ok(v5) = rkyv::from_bytes::<StateMetadataV5, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v5));
+            }
+            if let Ok(v4) = rkyv::from_bytes::<StateMetadataV4, rkyv::rancor::BoxedError>(bytes) {
+                return Ok(StateMetadata::from(v4));
+            }
             if let Ok(v3) = rkyv::from_bytes::<StateMetadataV3, rkyv::rancor::BoxedError>(bytes) {
                 return Ok(StateMetadata::from(v3));
             }
@@ -212,10 +942,11 @@ fn deserialize_metadata(bytes: &[u8]) -> Result<StateMetadata> {
 ///
 /// This function writes to a temporary file first, then atomically renames it
 /// to the final location. This ensures the metadata file is never left in a
-/// partially written state.
+/// partially written state. The file states are serialized in parallel shards,
+/// which bounds the memory used while saving large repositories.
 ///
 /// Creates the parent directory if it doesn't exist - this is needed for
-/// save/sync operations.
+/// save/sync operations. The file is signed when `context` has a metadata key.
 ///
 /// # Errors
 ///
@@ -223,32 +954,47 @@ fn deserialize_metadata(bytes: &[u8]) -> Result<StateMetadata> {
 /// - The parent directory cannot be created
 /// - The metadata cannot be serialized
 /// - The file cannot be written to disk
-pub fn save_metadata(metadata: &StateMetadata, metadata_path: &Path) -> Result<()> {
+pub fn save_metadata(
+    context: &HoldContext,
+    metadata: &StateMetadata,
+    metadata_path: &Path,
+) -> Result<()> {
+    write_atomically(
+        metadata_path,
+        context.metadata_key.as_ref(),
+        |out, temp_path| shards::write(metadata, out, temp_path),
+    )
+}
+
+/// Writes the file at `path` through a temporary file renamed over it once
+/// complete, creating its parent directory, and signs it with `key`;
+/// `write` receives the temporary file and its path
+fn write_atomically(
+    path: &Path,
+    key: Option<&[u8; 32]>,
+    write: impl FnOnce(&mut Signer<BufWriter<&File>>, &Path) -> Result<()>,
+) -> Result<()> {
     // Ensure the parent directory exists - create it for save operations
-    if let Some(parent) = metadata_path.parent() {
+    if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .map_err(|source| HoldError::CreateMetadataDirError(parent.to_path_buf(), source))?;
     }
 
-    // Serialize to bytes using rkyv
-    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(metadata)
-        .map_err(|e| HoldError::SerializationError(Box::new(e)))?;
-
     // Create a temporary file path
-    let temp_path = metadata_path.with_extension("tmp");
+    let temp_path = path.with_extension("tmp");
 
     // Write to temporary file
-    let mut temp_file = File::create(&temp_path).map_err(|source| HoldError::IoError {
+    let temp_file = File::create(&temp_path).map_err(|source| HoldError::IoError {
         path: temp_path.clone(),
         source,
     })?;
 
-    temp_file
-        .write_all(&bytes)
-        .map_err(|source| HoldError::IoError {
-            path: temp_path.clone(),
-            source,
-        })?;
+    let mut out = Signer::new(BufWriter::new(&temp_file), key);
+    write(&mut out, &temp_path)?;
+    out.finish().map_err(|source| HoldError::IoError {
+        path: temp_path.clone(),
+        source,
+    })?;
 
     temp_file.sync_all().map_err(|source| HoldError::IoError {
         path: temp_path.clone(),
@@ -256,14 +1002,48 @@ pub fn save_metadata(metadata: &StateMetadata, metadata_path: &Path) -> Result<(
     })?;
 
     // Atomically rename to final location
-    fs::rename(&temp_path, metadata_path).map_err(|source| HoldError::IoError {
-        path: metadata_path.to_path_buf(),
+    fs::rename(&temp_path, path).map_err(|source| HoldError::IoError {
+        path: path.to_path_buf(),
         source,
     })?;
 
     Ok(())
 }
 
+/// Writes `bytes` to `path` atomically, signed with `key`
+pub(crate) fn write_signed(path: &Path, bytes: &[u8], key: Option<&[u8; 32]>) -> Result<()> {
+    write_atomically(path, key, |out, temp_path| {
+        std::io::Write::write_all(out, bytes).map_err(|source| HoldError::IoError {
+            path: temp_path.to_path_buf(),
+            source,
+        })
+    })
+}
+
+/// Reads the file at `path` written by [`write_signed`], verifying its
+/// signature with `key`, and passes its contents to `read`; `None` if the
+/// file is missing or empty
+pub(crate) fn read_signed<T>(
+    path: &Path,
+    key: Option<&[u8; 32]>,
+    read: impl FnOnce(&[u8]) -> Result<T>,
+) -> Result<Option<T>> {
+    let io_error = |source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(e)),
+    };
+    if file.metadata().map_err(io_error)?.len() == 0 {
+        return Ok(None);
+    }
+    let mmap = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+    read(signature::verify(&mmap, key, path)?).map(Some)
+}
+
 /// Removes the metadata file from disk.
 ///
 /// This function is idempotent - it succeeds even if the metadata file
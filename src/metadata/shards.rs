@@ -0,0 +1,351 @@
+//! Sharded metadata layout.
+//!
+//! The file states are serialized in shards of [`SHARD_FILES`] entries, one
+//! batch of shards per rayon thread at a time, so saving never holds more
+//! than a batch of serialized shards next to the metadata. Loading
+//! deserializes the shards in parallel.
+//!
+//! A sharded file holds, in order:
+//! - [`MAGIC`]
+//! - the metadata without its file states, then each [`Shard`], each serialized
+//!   with rkyv and aligned to [`ALIGN`] bytes
+//! - the [`ShardIndex`] locating them, also aligned
+//! - the offset and length of the index as little-endian `u64`s
+//!
+//! Shards hold paths as a directory and a file name, listing each directory
+//! once as its parent and its own name, so the long directory prefixes of
+//! deep trees are not repeated for every file. Files are sorted by path, so
+//! the files of a directory share a shard.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use rayon::prelude::*;
+use rkyv::rancor::{BoxedError, Source};
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::{
+    FileStateV11, FileStateV12, StateMetadataV14, StateMetadataV15, StateMetadataV16,
+    StateMetadataV17,
+};
+use crate::error::{HoldError, Result};
+use crate::state::{FileState, StateMetadata};
+
+/// First bytes of a sharded metadata file
+const MAGIC: &[u8; 8] = b"HOLDSHRD";
+
+/// Alignment of the serialized segments, enough for rkyv's archived `u128`
+const ALIGN: u64 = 16;
+
+/// File states per shard
+pub(super) const SHARD_FILES: usize = 8192;
+
+/// Length of the footer locating the index
+const FOOTER_LEN: usize = 16;
+
+/// Bytes of a serialized segment within the file
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, Copy)]
+struct Segment {
+    offset: u64,
+    len: u64,
+}
+
+/// Locations of the serialized metadata and shards
+#[derive(Archive, Deserialize, Serialize, Debug, Default)]
+struct ShardIndex {
+    header: Option<Segment>,
+    shards: Vec<Segment>,
+}
+
+/// File states whose paths have their directories interned
+#[derive(Archive, Deserialize, Serialize, Debug, Default)]
+struct Shard {
+    /// Directories of the paths, each after its parent
+    dirs: Vec<InternedName>,
+    files: Vec<InternedFile>,
+}
+
+/// A path component within an interned directory
+#[derive(Archive, Deserialize, Serialize, Debug)]
+struct InternedName {
+    /// Index of the directory in [`Shard::dirs`], `None` at the top level
+    parent: Option<u32>,
+    name: String,
+}
+
+/// A [`FileState`] whose directory is interned
+#[derive(Archive, Deserialize, Serialize, Debug)]
+struct InternedFile {
+    path: InternedName,
+    size: u64,
+    hash: [u8; 32],
+    mtime_nanos: u64,
+}
+
+impl Shard {
+    /// Interns the directories of `states`
+    fn new(states: &[&FileState]) -> Result<Self> {
+        let mut shard = Self::default();
+        let mut dirs = HashMap::new();
+        for state in states {
+            let path = state.path.to_str().ok_or_else(|| HoldError::InvalidPath {
+                message: format!("Invalid UTF-8 in path: {}", state.path.display()),
+            })?;
+            let path = shard.intern(&mut dirs, path);
+            shard.files.push(InternedFile {
+                path,
+                size: state.size,
+                hash: state.hash,
+                mtime_nanos: state.mtime_nanos,
+            });
+        }
+        Ok(shard)
+    }
+
+    /// The name of `path` within its interned directory, interning the
+    /// directory and its parents as needed
+    fn intern<'a>(&mut self, dirs: &mut HashMap<&'a str, u32>, path: &'a str) -> InternedName {
+        let Some((dir, name)) = path.rsplit_once('/') else {
+            return InternedName {
+                parent: None,
+                name: path.to_string(),
+            };
+        };
+        let parent = match dirs.get(dir) {
+            Some(&index) => index,
+            None => {
+                let interned = self.intern(dirs, dir);
+                let index = self.dirs.len() as u32;
+                self.dirs.push(interned);
+                dirs.insert(dir, index);
+                index
+            }
+        };
+        InternedName {
+            parent: Some(parent),
+            name: name.to_string(),
+        }
+    }
+
+    /// The file states with their full paths
+    fn into_states(self) -> std::result::Result<Vec<FileState>, CorruptShards> {
+        let mut dirs: Vec<String> = Vec::with_capacity(self.dirs.len());
+        for dir in self.dirs {
+            let path = join(&dirs, dir)?;
+            dirs.push(path);
+        }
+        self.files
+            .into_iter()
+            .map(|file| {
+                Ok(FileState {
+                    path: join(&dirs, file.path)?.into(),
+                    size: file.size,
+                    hash: file.hash,
+                    mtime_nanos: file.mtime_nanos,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Full path of `name` given the full paths of the directories interned
+/// before it
+fn join(dirs: &[String], name: InternedName) -> std::result::Result<String, CorruptShards> {
+    match name.parent {
+        None => Ok(name.name),
+        Some(parent) => {
+            let dir = dirs
+                .get(parent as usize)
+                .ok_or(CorruptShards("directory out of order"))?;
+            Ok(format!("{dir}/{}", name.name))
+        }
+    }
+}
+
+/// The sharded layout is inconsistent, e.g. after a truncated copy
+#[derive(Debug, thiserror::Error)]
+#[error("Corrupted sharded metadata: {0}")]
+struct CorruptShards(&'static str);
+
+fn corrupt(what: &'static str) -> HoldError {
+    HoldError::DeserializationError(BoxedError::new(CorruptShards(what)))
+}
+
+/// Whether `bytes` hold metadata in the sharded layout
+pub(super) fn is_sharded(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Writes `metadata` to `out` in the sharded layout; `path` names `out` in
+/// errors
+pub(super) fn write(metadata: &StateMetadata, out: impl Write, path: &Path) -> Result<()> {
+    let io_error = |source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let serialize = |e: BoxedError| HoldError::SerializationError(Box::new(e));
+
+    let mut writer = SegmentWriter { out, position: 0 };
+    writer.out.write_all(MAGIC).map_err(io_error)?;
+    writer.position = MAGIC.len() as u64;
+
+    let header = StateMetadata {
+        version: metadata.version,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: metadata.last_gc_mtime_nanos,
+        recent_gc_mtime_nanos: metadata.recent_gc_mtime_nanos.clone(),
+        gc_metrics: metadata.gc_metrics.clone(),
+        pinned_crates: metadata.pinned_crates.clone(),
+        salt_id: metadata.salt_id,
+        dir_hashes: metadata.dir_hashes.clone(),
+        build_costs: metadata.build_costs.clone(),
+        gc_metrics_window: metadata.gc_metrics_window,
+    };
+    let mut index = ShardIndex::default();
+    let header = rkyv::to_bytes::<BoxedError>(&header).map_err(serialize)?;
+    index.header = Some(writer.segment(&header).map_err(io_error)?);
+
+    // Sorted so that saving the same states writes the same file
+    let mut states: Vec<&FileState> = metadata.files.values().collect();
+    states.par_sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    let batch = SHARD_FILES * rayon::current_num_threads();
+    for batch in states.chunks(batch) {
+        let shards = batch
+            .par_chunks(SHARD_FILES)
+            .map(|states| rkyv::to_bytes::<BoxedError>(&Shard::new(states)?).map_err(serialize))
+            .collect::<Result<Vec<_>>>()?;
+        for shard in shards {
+            index.shards.push(writer.segment(&shard).map_err(io_error)?);
+        }
+    }
+
+    let index = rkyv::to_bytes::<BoxedError>(&index).map_err(serialize)?;
+    let Segment { offset, len } = writer.segment(&index).map_err(io_error)?;
+    let mut footer = [0; FOOTER_LEN];
+    footer[..8].copy_from_slice(&offset.to_le_bytes());
+    footer[8..].copy_from_slice(&len.to_le_bytes());
+    writer.out.write_all(&footer).map_err(io_error)?;
+    writer.out.flush().map_err(io_error)
+}
+
+/// Reads metadata in the sharded layout, validating the shards unless
+/// `trusted`
+///
+/// The footer, index, and header are always validated, so the shards at
+/// least lie within `bytes` and are aligned.
+pub(super) fn read(bytes: &[u8], trusted: bool) -> Result<StateMetadata> {
+    let footer = bytes
+        .len()
+        .checked_sub(FOOTER_LEN)
+        .filter(|&at| at >= MAGIC.len())
+        .map(|at| &bytes[at..])
+        .ok_or_else(|| corrupt("missing footer"))?;
+    let index = Segment {
+        offset: u64::from_le_bytes(footer[..8].try_into().expect("8 bytes")),
+        len: u64::from_le_bytes(footer[8..].try_into().expect("8 bytes")),
+    };
+    let index = rkyv::from_bytes::<ShardIndex, BoxedError>(segment(bytes, index)?)
+        .map_err(HoldError::DeserializationError)?;
+
+    let header = segment(
+        bytes,
+        index.header.ok_or_else(|| corrupt("missing header"))?,
+    )?;
+    let mut metadata = match rkyv::from_bytes::<StateMetadata, BoxedError>(header) {
+        Ok(metadata) if metadata.version > 17 => metadata,
+        current => match legacy_header(header) {
+            Ok(metadata) => metadata,
+            Err(e) => current.map_err(|_| HoldError::DeserializationError(e))?,
+        },
+    };
+
+    // Shards were introduced by v11, whose file states hold timestamps as
+    // u128 nanoseconds; v11 and v12 file states hold hex-encoded hashes, and
+    // paths are interned since v14
+    let version = metadata.version;
+    let shards = index
+        .shards
+        .par_iter()
+        .map(|&shard| {
+            let bytes = segment(bytes, shard)?;
+            match version {
+                11 => rkyv::from_bytes::<Vec<FileStateV11>, BoxedError>(bytes)
+                    .map(|states| states.into_iter().map(FileState::from).collect()),
+                12 => rkyv::from_bytes::<Vec<FileStateV12>, BoxedError>(bytes)
+                    .map(|states| states.into_iter().map(FileState::from).collect()),
+                13 => rkyv::from_bytes::<Vec<FileState>, BoxedError>(bytes),
+                // SAFETY: the caller vouches for the metadata, so the shard is
+                // a valid archive; `segment` checked its bounds and alignment
+                _ if trusted => unsafe { rkyv::from_bytes_unchecked::<Shard, BoxedError>(bytes) }
+                    .and_then(|shard| shard.into_states().map_err(BoxedError::new)),
+                _ => rkyv::from_bytes::<Shard, BoxedError>(bytes)
+                    .and_then(|shard| shard.into_states().map_err(BoxedError::new)),
+            }
+            .map_err(HoldError::DeserializationError)
+        })
+        .collect::<Result<Vec<Vec<FileState>>>>()?;
+    metadata
+        .files
+        .reserve(shards.iter().map(Vec::len).sum::<usize>());
+    for state in shards.into_iter().flatten() {
+        metadata.upsert(state)?;
+    }
+
+    Ok(metadata)
+}
+
+/// Metadata of a header written before v18, without the window of the GC
+/// metrics; headers before v17 have no build costs of crates, headers before
+/// v16 no rollup hashes of directories, and headers before v15 no salt id
+/// either
+fn legacy_header(header: &[u8]) -> std::result::Result<StateMetadata, BoxedError> {
+    if let Ok(v17) = rkyv::from_bytes::<StateMetadataV17, BoxedError>(header)
+        && v17.version == 17
+    {
+        return Ok(v17.into());
+    }
+    if let Ok(v16) = rkyv::from_bytes::<StateMetadataV16, BoxedError>(header)
+        && v16.version == 16
+    {
+        return Ok(v16.into());
+    }
+    match rkyv::from_bytes::<StateMetadataV15, BoxedError>(header) {
+        Ok(v15) if v15.version == 15 => Ok(v15.into()),
+        _ => rkyv::from_bytes::<StateMetadataV14, BoxedError>(header).map(StateMetadata::from),
+    }
+}
+
+/// Bytes of `segment`, which must lie within `bytes` and be aligned
+fn segment(bytes: &[u8], segment: Segment) -> Result<&[u8]> {
+    let Segment { offset, len } = segment;
+    if !offset.is_multiple_of(ALIGN) {
+        return Err(corrupt("misaligned segment"));
+    }
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+        .ok_or_else(|| corrupt("segment out of bounds"))
+}
+
+/// Writes segments aligned to [`ALIGN`] bytes, keeping track of their offsets
+struct SegmentWriter<W> {
+    out: W,
+    position: u64,
+}
+
+impl<W: Write> SegmentWriter<W> {
+    fn segment(&mut self, bytes: &[u8]) -> std::io::Result<Segment> {
+        let padding = self.position.next_multiple_of(ALIGN) - self.position;
+        self.out
+            .write_all(&[0; ALIGN as usize][..padding as usize])?;
+        let offset = self.position + padding;
+        self.out.write_all(bytes)?;
+        self.position = offset + bytes.len() as u64;
+        Ok(Segment {
+            offset,
+            len: bytes.len() as u64,
+        })
+    }
+}
@@ -0,0 +1,94 @@
+//! Authentication of metadata files with a secret key.
+//!
+//! With `--metadata-key` (`CARGO_HOLD_METADATA_KEY`), saving appends
+//! [`MAGIC`] and a BLAKE3 keyed hash, a MAC like HMAC, of everything before
+//! it. Loading rejects files whose tag is missing or does not match, so a
+//! poisoned cache cannot inject hashes and timestamps.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use blake3::Hasher;
+
+use crate::error::{HoldError, Result};
+
+/// Marks the signature at the end of a signed metadata file
+const MAGIC: &[u8; 8] = b"HOLDMAC1";
+
+/// Length of [`MAGIC`] and the tag following it
+const TRAILER_LEN: usize = MAGIC.len() + blake3::OUT_LEN;
+
+/// BLAKE3 key derivation context turning `--metadata-key` into a MAC key
+const KEY_CONTEXT: &str = "cargo-hold 2025-01 metadata signing key";
+
+/// MAC key for `--metadata-key`, `None` if it is empty, such as a secret CI
+/// withholds from forks, so that nothing is signed
+pub(crate) fn derive_key(key: &str) -> Option<[u8; 32]> {
+    (!key.is_empty()).then(|| blake3::derive_key(KEY_CONTEXT, key.as_bytes()))
+}
+
+/// The signed contents of the metadata file at `path`, without the
+/// signature; with no `key`, any signature is ignored
+pub(super) fn verify<'a>(bytes: &'a [u8], key: Option<&[u8; 32]>, path: &Path) -> Result<&'a [u8]> {
+    let signed = bytes
+        .len()
+        .checked_sub(TRAILER_LEN)
+        .filter(|&at| bytes[at..].starts_with(MAGIC));
+    let Some(key) = key else {
+        return Ok(signed.map_or(bytes, |at| &bytes[..at]));
+    };
+    let unauthenticated = |reason| HoldError::UnauthenticatedMetadata {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let at = signed.ok_or_else(|| unauthenticated("is not signed"))?;
+    let tag: [u8; blake3::OUT_LEN] = bytes[at + MAGIC.len()..]
+        .try_into()
+        .expect("tag after the magic");
+    let mut hasher = Hasher::new_keyed(key);
+    hasher.update_rayon(&bytes[..at]);
+    // Hash comparisons are constant time
+    if hasher.finalize() != blake3::Hash::from(tag) {
+        return Err(unauthenticated("has an invalid signature"));
+    }
+    Ok(&bytes[..at])
+}
+
+/// Writes metadata, signing it when a key is given
+pub(super) struct Signer<W> {
+    out: W,
+    hasher: Option<Hasher>,
+}
+
+impl<W: Write> Signer<W> {
+    pub(super) fn new(out: W, key: Option<&[u8; 32]>) -> Self {
+        Self {
+            out,
+            hasher: key.map(Hasher::new_keyed),
+        }
+    }
+
+    /// Appends the signature, if signing
+    pub(super) fn finish(mut self) -> io::Result<()> {
+        if let Some(hasher) = self.hasher.take() {
+            self.out.write_all(MAGIC)?;
+            self.out.write_all(hasher.finalize().as_bytes())?;
+        }
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Write for Signer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.out.write(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
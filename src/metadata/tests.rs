@@ -3,13 +3,20 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+use proptest::prelude::*;
 use tempfile::TempDir;
 
+use crate::context::HoldContext;
 use crate::error::HoldError;
+use crate::logging::Logger;
+use crate::metadata::dedup::RETAINED_STOWS;
 use crate::metadata::{
-    StateMetadataV2, clean_metadata, load_metadata, migrate_metadata, save_metadata,
+    DedupStore, DirectorySizeV7, FileStateV11, FileStateV12, GcMetricsV4, GcMetricsV6, GcMetricsV7,
+    StateMetadataV2, StateMetadataV4, StateMetadataV5, StateMetadataV6, StateMetadataV7,
+    StateMetadataV8, StateMetadataV9, StateMetadataV11, clean_metadata, load_metadata,
+    load_metadata_inner, migrate_metadata, parse_metadata, save_metadata, shards, signature,
 };
-use crate::state::{FileState, METADATA_VERSION, StateMetadata};
+use crate::state::{FileState, GC_METRICS_WINDOW, METADATA_VERSION, StateMetadata};
 
 #[test]
 fn test_save_and_load_metadata() {
@@ -22,20 +29,20 @@ fn test_save_and_load_metadata() {
         .upsert(FileState {
             path: PathBuf::from("test.rs"),
             size: 1234,
-            hash: "abcdef".to_string(),
+            hash: [0xab; 32],
             mtime_nanos: SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
-                .as_nanos(),
+                .as_nanos() as u64,
         })
         .unwrap();
 
     // Save it
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
     assert!(metadata_path.exists());
 
     // Load it back
-    let loaded_metadata = load_metadata(&metadata_path).unwrap();
+    let loaded_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(loaded_metadata.len(), 1);
     assert!(loaded_metadata.contains(&PathBuf::from("test.rs")).unwrap());
 }
@@ -45,7 +52,7 @@ fn test_load_nonexistent_metadata() {
     let temp_dir = TempDir::new().unwrap();
     let metadata_path = temp_dir.path().join("nonexistent.metadata");
 
-    let metadata = load_metadata(&metadata_path).unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert!(metadata.is_empty());
 }
 
@@ -56,7 +63,7 @@ fn test_clean_metadata() {
 
     // Create a metadata file
     let metadata = StateMetadata::new();
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
     assert!(metadata_path.exists());
 
     // Clean it
@@ -73,7 +80,7 @@ fn test_atomic_save() {
     let metadata_path = temp_dir.path().join("test.metadata");
 
     let metadata = StateMetadata::new();
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     // Temporary file should not exist
     let temp_path = metadata_path.with_extension("tmp");
@@ -92,14 +99,14 @@ fn test_metadata_version() {
         .upsert(FileState {
             path: PathBuf::from("test.rs"),
             size: 100,
-            hash: "hash".to_string(),
+            hash: [7; 32],
             mtime_nanos: 123456789,
         })
         .unwrap();
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     // Load and check version
-    let loaded_metadata = load_metadata(&metadata_path).unwrap();
+    let loaded_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(loaded_metadata.version, METADATA_VERSION);
     assert_eq!(loaded_metadata.len(), 1);
 }
@@ -118,11 +125,570 @@ fn test_metadata_migration_v2_to_v3_adds_gc_metrics() {
     let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v2).unwrap();
     std::fs::write(&metadata_path, bytes).unwrap();
 
-    let loaded = load_metadata(&metadata_path).unwrap();
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(loaded.version, METADATA_VERSION);
     assert_eq!(loaded.gc_metrics.runs, 0);
 }
 
+#[test]
+fn test_metadata_migration_v4_seeds_gc_timestamp_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    // Simulate v4 metadata on disk (single previous GC timestamp).
+    let v4 = StateMetadataV4 {
+        version: 4,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(42),
+        gc_metrics: GcMetricsV4 {
+            runs: 3,
+            ..GcMetricsV4::default()
+        },
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v4).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.last_gc_mtime_nanos, Some(42));
+    assert_eq!(loaded.recent_gc_mtime_nanos, vec![42]);
+    assert_eq!(loaded.gc_metrics.runs, 3);
+}
+
+#[test]
+fn test_metadata_migration_v5_adds_profile_build_timestamps() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let v5 = StateMetadataV5 {
+        version: 5,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(2),
+        recent_gc_mtime_nanos: vec![1, 2],
+        gc_metrics: GcMetricsV4 {
+            runs: 5,
+            recent_final_sizes: vec![100],
+            ..GcMetricsV4::default()
+        },
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v5).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.recent_gc_mtime_nanos, vec![1, 2]);
+    assert_eq!(loaded.gc_metrics.runs, 5);
+    assert_eq!(loaded.gc_metrics.recent_final_sizes, vec![100]);
+    assert!(loaded.gc_metrics.profile_build_mtime_nanos.is_empty());
+}
+
+#[test]
+fn test_metadata_migration_v6_adds_directory_size_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let v6 = StateMetadataV6 {
+        version: 6,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(2),
+        recent_gc_mtime_nanos: vec![1, 2],
+        gc_metrics: GcMetricsV6 {
+            runs: 6,
+            profile_build_mtime_nanos: HashMap::from([("debug".to_string(), vec![3])]),
+            ..GcMetricsV6::default()
+        },
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v6).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.gc_metrics.runs, 6);
+    assert_eq!(
+        loaded.gc_metrics.profile_build_mtime_nanos.get("debug"),
+        Some(&vec![3])
+    );
+    assert!(loaded.gc_metrics.directory_sizes.is_empty());
+}
+
+#[test]
+fn test_metadata_migration_v7_rebuilds_directory_size_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let v7 = StateMetadataV7 {
+        version: 7,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(2),
+        recent_gc_mtime_nanos: vec![1, 2],
+        gc_metrics: GcMetricsV7 {
+            runs: 7,
+            directory_sizes: HashMap::from([(
+                "debug".to_string(),
+                DirectorySizeV7 {
+                    mtime_nanos: 1,
+                    apparent: 100,
+                    allocated: 4096,
+                    linked_files: Vec::new(),
+                    subdirs: vec!["deps".to_string()],
+                },
+            )]),
+            ..GcMetricsV7::default()
+        },
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v7).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.gc_metrics.runs, 7);
+    assert!(loaded.gc_metrics.directory_sizes.is_empty());
+}
+
+#[test]
+fn test_metadata_migration_v8_adds_pinned_crates() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut v8 = StateMetadataV8 {
+        version: 8,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(3),
+        recent_gc_mtime_nanos: vec![3],
+        gc_metrics: Default::default(),
+    };
+    v8.gc_metrics.runs = 8;
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v8).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.last_gc_mtime_nanos, Some(3));
+    assert_eq!(loaded.gc_metrics.runs, 8);
+    assert!(loaded.pinned_crates.is_empty());
+}
+
+#[test]
+fn test_metadata_migration_v9_adds_reported_builds() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut v9 = StateMetadataV9 {
+        version: 9,
+        files: HashMap::new(),
+        last_gc_mtime_nanos: Some(4),
+        recent_gc_mtime_nanos: vec![4],
+        gc_metrics: Default::default(),
+        pinned_crates: vec!["rocksdb-sys".to_string()],
+    };
+    v9.gc_metrics.runs = 9;
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v9).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.gc_metrics.runs, 9);
+    assert_eq!(loaded.pinned_crates, ["rocksdb-sys"]);
+    assert!(loaded.gc_metrics.recent_builds.is_empty());
+}
+
+#[test]
+fn test_metadata_migration_v10_reads_single_buffer_layout() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let v10 = StateMetadataV11 {
+        version: 10,
+        files: HashMap::from([(
+            "src/lib.rs".to_string(),
+            FileStateV11 {
+                path: PathBuf::from("src/lib.rs"),
+                size: 7,
+                hash: "hash".to_string(),
+                mtime_nanos: 1_700_000_000_000_000_000,
+            },
+        )]),
+        last_gc_mtime_nanos: None,
+        recent_gc_mtime_nanos: Vec::new(),
+        gc_metrics: Default::default(),
+        pinned_crates: vec!["rocksdb-sys".to_string()],
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&v10).unwrap();
+    std::fs::write(&metadata_path, bytes).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(
+        loaded
+            .get(Path::new("src/lib.rs"))
+            .unwrap()
+            .unwrap()
+            .mtime_nanos,
+        1_700_000_000_000_000_000
+    );
+    assert_eq!(loaded.pinned_crates, ["rocksdb-sys"]);
+
+    // The next save switches to the sharded layout
+    save_metadata(&HoldContext::default(), &loaded, &metadata_path).unwrap();
+    assert!(shards::is_sharded(&fs::read(&metadata_path).unwrap()));
+}
+
+#[test]
+fn test_metadata_migration_v12_clamps_timestamps_to_u64() {
+    let legacy = |mtime_nanos| FileStateV11 {
+        path: PathBuf::from("far_future.rs"),
+        size: 1,
+        hash: "hash".to_string(),
+        mtime_nanos,
+    };
+    assert_eq!(FileState::from(legacy(42)).mtime_nanos, 42);
+    assert_eq!(
+        FileState::from(legacy(u64::MAX as u128)).mtime_nanos,
+        u64::MAX
+    );
+    assert_eq!(FileState::from(legacy(u128::MAX)).mtime_nanos, u64::MAX);
+}
+
+#[test]
+fn test_metadata_migration_v13_decodes_hex_hashes() {
+    let legacy = |hash: &str| FileStateV12 {
+        path: PathBuf::from("src/lib.rs"),
+        size: 11,
+        hash: hash.to_string(),
+        mtime_nanos: 42,
+    };
+    let digest = blake3::hash(b"hello world");
+    let state = FileState::from(legacy(digest.to_hex().as_str()));
+    assert_eq!(state.hash, *digest.as_bytes());
+    assert_eq!(state.mtime_nanos, 42);
+
+    // Hashes of large files under the old head and size-only policies were
+    // not hex; they must not pass for the hash of any file
+    let size_only = FileState::from(legacy("size-only")).hash;
+    let head = FileState::from(legacy(&format!("head-5:{}", digest.to_hex()))).hash;
+    assert_ne!(size_only, head);
+    assert_ne!(head, *digest.as_bytes());
+    assert_eq!(size_only, FileState::from(legacy("size-only")).hash);
+}
+
+#[test]
+fn test_u64_timestamps_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    for (i, mtime_nanos) in [0, 1, 1_700_000_000_123_456_789, u64::MAX]
+        .into_iter()
+        .enumerate()
+    {
+        metadata
+            .upsert(FileState {
+                mtime_nanos,
+                ..file_state(i)
+            })
+            .unwrap();
+    }
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.files, metadata.files);
+    assert_eq!(loaded.max_mtime_nanos(), Some(u64::MAX));
+}
+
+fn file_state(i: usize) -> FileState {
+    FileState {
+        path: PathBuf::from(format!("src/module_{i}.rs")),
+        size: i as u64,
+        hash: *blake3::hash(&i.to_le_bytes()).as_bytes(),
+        mtime_nanos: i as u64 * 1_000_000_007,
+    }
+}
+
+#[test]
+fn test_sharded_metadata_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    for i in 0..2 * shards::SHARD_FILES + 5 {
+        metadata.upsert(file_state(i)).unwrap();
+    }
+    metadata.record_gc_mtime_nanos(42);
+    metadata.gc_metrics.runs = 3;
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    let bytes = fs::read(&metadata_path).unwrap();
+    assert!(shards::is_sharded(&bytes));
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.files, metadata.files);
+    assert_eq!(loaded.recent_gc_mtime_nanos, [42]);
+    assert_eq!(loaded.gc_metrics, metadata.gc_metrics);
+
+    // Saving the same states writes the same file
+    save_metadata(&HoldContext::default(), &loaded, &metadata_path).unwrap();
+    assert_eq!(fs::read(&metadata_path).unwrap(), bytes);
+}
+
+#[test]
+fn test_interned_paths_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    for (i, path) in [
+        "Cargo.toml",
+        "services/foo/bar/src/lib.rs",
+        "services/foo/bar/src/main.rs",
+        "services/foo/baz/src/lib.rs",
+        "services/foo/bar.rs",
+        "double//slash.rs",
+        "trailing/",
+        "/rooted.rs",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        metadata
+            .upsert(FileState {
+                path: PathBuf::from(path),
+                ..file_state(i)
+            })
+            .unwrap();
+    }
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    let mut paths: Vec<_> = loaded.files.values().map(|state| &state.path).collect();
+    paths.sort();
+    let mut expected: Vec<_> = metadata.files.values().map(|state| &state.path).collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    assert_eq!(loaded.files, metadata.files);
+}
+
+#[test]
+fn test_interned_paths_shrink_deep_trees() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    for i in 0..10_000 {
+        metadata
+            .upsert(FileState {
+                path: PathBuf::from(format!(
+                    "services/payments/ledger-service/crates/ledger_core_{}/src/handlers/\
+                     module_{i}.rs",
+                    i % 20
+                )),
+                ..file_state(i)
+            })
+            .unwrap();
+    }
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    // The v13 layout stored every path in full
+    let flat: Vec<FileState> = metadata.files.values().cloned().collect();
+    let flat = rkyv::to_bytes::<rkyv::rancor::BoxedError>(&flat).unwrap();
+    let interned = fs::metadata(&metadata_path).unwrap().len();
+    assert!(
+        interned < flat.len() as u64 * 2 / 3,
+        "{interned} bytes interned, {} flat",
+        flat.len()
+    );
+}
+
+#[test]
+fn test_truncated_sharded_metadata_is_reset() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    let bytes = fs::read(&metadata_path).unwrap();
+    fs::write(&metadata_path, &bytes[..bytes.len() - 1]).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert!(loaded.is_empty());
+    assert!(!metadata_path.exists());
+}
+
+#[test]
+fn test_dir_hashes_round_trip_and_are_computed_for_older_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    metadata.upsert(file_state(2)).unwrap();
+    metadata.update_dir_hashes();
+    assert!(metadata.dir_hashes.contains_key("src"));
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .dir_hashes,
+        metadata.dir_hashes
+    );
+
+    let mut v15 = metadata.clone();
+    v15.version = 15;
+    v15.dir_hashes.clear();
+    save_metadata(&HoldContext::default(), &v15, &metadata_path).unwrap();
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.dir_hashes, metadata.dir_hashes);
+}
+
+#[test]
+fn test_build_costs_round_trip_and_are_empty_for_older_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    metadata.build_costs.insert("ring".to_string(), 300_000);
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .build_costs,
+        metadata.build_costs
+    );
+
+    let mut v16 = metadata.clone();
+    v16.version = 16;
+    save_metadata(&HoldContext::default(), &v16, &metadata_path).unwrap();
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert!(loaded.build_costs.is_empty());
+}
+
+#[test]
+fn test_gc_metrics_window_round_trips_and_is_default_for_older_metadata() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    assert_eq!(metadata.metrics_window(), GC_METRICS_WINDOW);
+    metadata.gc_metrics_window = Some(52);
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    assert_eq!(
+        load_metadata(&HoldContext::default(), &metadata_path)
+            .unwrap()
+            .metrics_window(),
+        52
+    );
+
+    let mut v17 = metadata.clone();
+    v17.version = 17;
+    save_metadata(&HoldContext::default(), &v17, &metadata_path).unwrap();
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.version, METADATA_VERSION);
+    assert_eq!(loaded.gc_metrics_window, None);
+}
+
+#[test]
+fn test_salt_id_round_trips() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+
+    let mut metadata = StateMetadata::new();
+    assert_eq!(metadata.salt_id, None);
+    metadata.salt_id = Some([3; 16]);
+    metadata.upsert(file_state(1)).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.salt_id, Some([3; 16]));
+    assert_eq!(loaded.len(), 1);
+}
+
+#[test]
+fn test_dedup_store_keeps_the_first_timestamp_of_contents() {
+    let temp_dir = TempDir::new().unwrap();
+    let store_path = temp_dir.path().join("dedup");
+    let log = Logger::new(0, true);
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    let mut store = DedupStore::load(&HoldContext::default(), &store_path, None, &log);
+    store.record(&HoldContext::default(), &metadata);
+    store.save(&HoldContext::default(), &store_path).unwrap();
+
+    // The contents move to another path and get a new timestamp
+    let original = file_state(1);
+    let mut moved = file_state(1);
+    moved.path = PathBuf::from("src/moved.rs");
+    moved.mtime_nanos += 1;
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(moved).unwrap();
+    let mut store = DedupStore::load(&HoldContext::default(), &store_path, None, &log);
+    store.record(&HoldContext::default(), &metadata);
+
+    assert_eq!(
+        store.canonical_mtime(original.size, &original.hash),
+        Some(original.mtime_nanos)
+    );
+    assert_eq!(
+        store.canonical_mtime(original.size + 1, &original.hash),
+        None
+    );
+    assert_eq!(store.canonical_mtime(2, &file_state(2).hash), None);
+}
+
+#[test]
+fn test_dedup_store_forgets_contents_not_seen_recently() {
+    let temp_dir = TempDir::new().unwrap();
+    let log = Logger::new(0, true);
+    let mut store = DedupStore::load(
+        &HoldContext::default(),
+        &temp_dir.path().join("dedup"),
+        None,
+        &log,
+    );
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    store.record(&HoldContext::default(), &metadata);
+    let empty = StateMetadata::new();
+    for _ in 1..RETAINED_STOWS {
+        store.record(&HoldContext::default(), &empty);
+    }
+    assert_eq!(store.len(), 1);
+    store.record(&HoldContext::default(), &empty);
+    assert_eq!(store.len(), 0);
+}
+
+#[test]
+fn test_dedup_store_is_ignored_for_another_salt_or_when_corrupted() {
+    let temp_dir = TempDir::new().unwrap();
+    let store_path = temp_dir.path().join("dedup");
+    let log = Logger::new(0, true);
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    let mut store = DedupStore::load(&HoldContext::default(), &store_path, Some([1; 16]), &log);
+    store.record(&HoldContext::default(), &metadata);
+    store.save(&HoldContext::default(), &store_path).unwrap();
+    assert_eq!(
+        DedupStore::load(&HoldContext::default(), &store_path, Some([1; 16]), &log),
+        store
+    );
+    assert_eq!(
+        DedupStore::load(&HoldContext::default(), &store_path, Some([2; 16]), &log).len(),
+        0
+    );
+
+    fs::write(&store_path, b"not a dedup store").unwrap();
+    assert_eq!(
+        DedupStore::load(&HoldContext::default(), &store_path, Some([1; 16]), &log).len(),
+        0
+    );
+}
+
 #[test]
 fn test_metadata_migration_v1_to_v3() {
     let temp_dir = TempDir::new().unwrap();
@@ -135,16 +701,16 @@ fn test_metadata_migration_v1_to_v3() {
         .upsert(FileState {
             path: PathBuf::from("test.rs"),
             size: 100,
-            hash: "hash".to_string(),
+            hash: [7; 32],
             mtime_nanos: 123456789,
         })
         .unwrap();
 
     // Save with v1
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     // Load should migrate to latest
-    let loaded_metadata = load_metadata(&metadata_path).unwrap();
+    let loaded_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(loaded_metadata.version, METADATA_VERSION);
     assert_eq!(loaded_metadata.len(), 1);
     assert!(loaded_metadata.last_gc_mtime_nanos.is_none()); // Should be None after migration
@@ -162,7 +728,7 @@ fn test_last_gc_mtime_nanos_preservation() {
         .upsert(FileState {
             path: PathBuf::from("file1.rs"),
             size: 100,
-            hash: "hash1".to_string(),
+            hash: [1; 32],
             mtime_nanos: 1000000000,
         })
         .unwrap();
@@ -170,7 +736,7 @@ fn test_last_gc_mtime_nanos_preservation() {
         .upsert(FileState {
             path: PathBuf::from("file2.rs"),
             size: 200,
-            hash: "hash2".to_string(),
+            hash: [2; 32],
             mtime_nanos: 2000000000,
         })
         .unwrap();
@@ -178,25 +744,25 @@ fn test_last_gc_mtime_nanos_preservation() {
     assert_eq!(metadata.max_mtime_nanos(), Some(2000000000));
 
     // Save and load again
-    save_metadata(&metadata, &metadata_path).unwrap();
-    let loaded = load_metadata(&metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
 
     // Create new metadata and set last_gc_mtime_nanos
     let mut new_metadata = StateMetadata::new();
-    new_metadata.last_gc_mtime_nanos = loaded.max_mtime_nanos();
+    new_metadata.last_gc_mtime_nanos = loaded.max_mtime_nanos().map(u128::from);
     new_metadata
         .upsert(FileState {
             path: PathBuf::from("file3.rs"),
             size: 300,
-            hash: "hash3".to_string(),
+            hash: [3; 32],
             mtime_nanos: 3000000000,
         })
         .unwrap();
 
-    save_metadata(&new_metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &new_metadata, &metadata_path).unwrap();
 
     // Load and verify
-    let final_metadata = load_metadata(&metadata_path).unwrap();
+    let final_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(final_metadata.last_gc_mtime_nanos, Some(2000000000));
     assert_eq!(final_metadata.max_mtime_nanos(), Some(3000000000));
 }
@@ -218,7 +784,7 @@ fn test_format_incompatibility_recovery() {
 
     // Attempt to load metadata - should recover gracefully and return fresh
     // metadata
-    let result = load_metadata(&metadata_path);
+    let result = load_metadata(&HoldContext::default(), &metadata_path);
     assert!(result.is_ok());
 
     let metadata = result.unwrap();
@@ -242,7 +808,7 @@ fn test_format_incompatibility_with_subsequent_save() {
     fs::write(&metadata_path, invalid_data).unwrap();
 
     // Load should recover gracefully
-    let mut metadata = load_metadata(&metadata_path).unwrap();
+    let mut metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(metadata.version, METADATA_VERSION);
     assert_eq!(metadata.len(), 0);
 
@@ -251,15 +817,15 @@ fn test_format_incompatibility_with_subsequent_save() {
         .upsert(FileState {
             path: PathBuf::from("test.rs"),
             size: 100,
-            hash: "testhash".to_string(),
+            hash: [0x7e; 32],
             mtime_nanos: 1234567890,
         })
         .unwrap();
 
-    save_metadata(&metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
 
     // Should be able to load the new format without issues
-    let reloaded = load_metadata(&metadata_path).unwrap();
+    let reloaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(reloaded.version, METADATA_VERSION);
     assert_eq!(reloaded.len(), 1);
     assert!(reloaded.get(Path::new("test.rs")).unwrap().is_some());
@@ -278,7 +844,7 @@ fn test_version_migration_logic() {
         .upsert(FileState {
             path: PathBuf::from("legacy.rs"),
             size: 200,
-            hash: "legacyhash".to_string(),
+            hash: [0x1e; 32],
             mtime_nanos: 9876543210,
         })
         .unwrap();
@@ -307,18 +873,18 @@ fn test_future_version_handling() {
     let mut future_metadata = StateMetadata::new();
     future_metadata.version = METADATA_VERSION + 1; // Future version
 
-    save_metadata(&future_metadata, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &future_metadata, &metadata_path).unwrap();
 
-    // Should return a ConfigError for future versions
-    let result = load_metadata(&metadata_path);
+    // Should return a MetadataVersionTooNew error for future versions
+    let result = load_metadata(&HoldContext::default(), &metadata_path);
     assert!(result.is_err());
 
     match result.unwrap_err() {
-        HoldError::ConfigError(message) => {
-            assert!(message.contains("newer than supported"));
-            assert!(message.contains(&(METADATA_VERSION + 1).to_string()));
+        HoldError::MetadataVersionTooNew { found, supported } => {
+            assert_eq!(found, METADATA_VERSION + 1);
+            assert_eq!(supported, METADATA_VERSION);
         }
-        other => panic!("Expected ConfigError, got: {other:?}"),
+        other => panic!("Expected MetadataVersionTooNew, got: {other:?}"),
     }
 }
 
@@ -339,7 +905,7 @@ fn test_real_world_incompatible_format_scenario() {
     fs::write(&metadata_path, problematic_data).unwrap();
 
     // Load should detect incompatibility and recover gracefully
-    let metadata = load_metadata(&metadata_path).unwrap();
+    let metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
 
     // Verify recovery worked
     assert_eq!(metadata.version, METADATA_VERSION);
@@ -355,16 +921,16 @@ fn test_real_world_incompatible_format_scenario() {
         .upsert(FileState {
             path: PathBuf::from("recovered.rs"),
             size: 42,
-            hash: "recovered".to_string(),
+            hash: [0x2e; 32],
             mtime_nanos: 12345,
         })
         .unwrap();
 
     // Save should work normally after recovery
-    save_metadata(&recovered, &metadata_path).unwrap();
+    save_metadata(&HoldContext::default(), &recovered, &metadata_path).unwrap();
 
     // And subsequent loads should work fine
-    let final_metadata = load_metadata(&metadata_path).unwrap();
+    let final_metadata = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
     assert_eq!(final_metadata.len(), 1);
     assert!(
         final_metadata
@@ -373,3 +939,132 @@ fn test_real_world_incompatible_format_scenario() {
             .is_some()
     );
 }
+
+/// Contents of a saved metadata file with a few files and GC history
+fn saved_metadata_bytes() -> Vec<u8> {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let mut metadata = StateMetadata::new();
+    for i in 0..5 {
+        metadata.upsert(file_state(i)).unwrap();
+    }
+    metadata.record_gc_mtime_nanos(42);
+    save_metadata(&HoldContext::default(), &metadata, &metadata_path).unwrap();
+    fs::read(&metadata_path).unwrap()
+}
+
+/// Parses `bytes` from an aligned buffer, as when memory mapped
+fn parse_aligned(bytes: &[u8]) -> crate::error::Result<StateMetadata> {
+    parse_aligned_trusting(bytes, false)
+}
+
+fn parse_aligned_trusting(bytes: &[u8], trusted: bool) -> crate::error::Result<StateMetadata> {
+    let mut aligned = rkyv::util::AlignedVec::<16>::new();
+    aligned.extend_from_slice(bytes);
+    parse_metadata(&aligned, trusted).map(|(metadata, _)| metadata)
+}
+
+#[test]
+fn test_trusted_metadata_loads_the_same_states() {
+    let bytes = saved_metadata_bytes();
+    let validated = parse_aligned_trusting(&bytes, false).unwrap();
+    let trusted = parse_aligned_trusting(&bytes, true).unwrap();
+    assert_eq!(trusted.files.len(), 5);
+    assert_eq!(trusted.files, validated.files);
+    assert_eq!(
+        trusted.recent_gc_mtime_nanos,
+        validated.recent_gc_mtime_nanos
+    );
+
+    // The layout around the file states is still validated
+    assert!(matches!(
+        parse_aligned_trusting(&bytes[..bytes.len() - 1], true),
+        Err(HoldError::DeserializationError(_))
+    ));
+}
+
+proptest! {
+    #[test]
+    fn prop_arbitrary_metadata_bytes_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..512)) {
+        let _ = parse_aligned(&bytes);
+        let mut sharded = b"HOLDSHRD".to_vec();
+        sharded.extend_from_slice(&bytes);
+        let _ = parse_aligned(&sharded);
+    }
+
+    #[test]
+    fn prop_corrupted_metadata_never_panics(
+        flips in proptest::collection::vec((any::<prop::sample::Index>(), 1..=255u8), 1..8),
+        truncate in any::<prop::sample::Index>(),
+    ) {
+        let mut bytes = saved_metadata_bytes();
+        for (at, mask) in flips {
+            let at = at.index(bytes.len());
+            bytes[at] ^= mask;
+        }
+        let _ = parse_aligned(&bytes);
+        let _ = parse_aligned(&bytes[..truncate.index(bytes.len())]);
+    }
+}
+
+/// A context signing metadata with the key derived from `key`
+fn keyed(key: &str) -> HoldContext {
+    HoldContext {
+        metadata_key: signature::derive_key(key),
+        ..HoldContext::default()
+    }
+}
+
+#[test]
+fn test_signed_metadata_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let context = keyed("s3cret");
+
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+    save_metadata(&context, &metadata, &metadata_path).unwrap();
+
+    let loaded = load_metadata(&context, &metadata_path).unwrap();
+    assert_eq!(loaded.files, metadata.files);
+
+    // Without a key the signature is ignored
+    let loaded = load_metadata(&HoldContext::default(), &metadata_path).unwrap();
+    assert_eq!(loaded.files, metadata.files);
+    assert!(metadata_path.exists());
+}
+
+#[test]
+fn test_unauthenticated_metadata_is_ignored() {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_path = temp_dir.path().join("test.metadata");
+    let context = keyed("s3cret");
+    let mut metadata = StateMetadata::new();
+    metadata.upsert(file_state(1)).unwrap();
+
+    let unsigned = |path: &Path| save_metadata(&HoldContext::default(), &metadata, path).unwrap();
+    let other_key = |path: &Path| save_metadata(&keyed("guess"), &metadata, path).unwrap();
+    let tampered = |path: &Path| {
+        save_metadata(&context, &metadata, path).unwrap();
+        let mut bytes = fs::read(path).unwrap();
+        bytes[20] ^= 1;
+        fs::write(path, bytes).unwrap();
+    };
+    for save in [&unsigned as &dyn Fn(&Path), &other_key, &tampered] {
+        save(&metadata_path);
+        assert!(matches!(
+            load_metadata_inner(&metadata_path, context.metadata_key.as_ref(), false),
+            Err(HoldError::UnauthenticatedMetadata { .. })
+        ));
+
+        let loaded = load_metadata(&context, &metadata_path).unwrap();
+        assert!(loaded.is_empty());
+        assert!(!metadata_path.exists());
+    }
+}
+
+#[test]
+fn test_empty_metadata_key_signs_nothing() {
+    assert_eq!(signature::derive_key(""), None);
+    assert_ne!(signature::derive_key("a"), signature::derive_key("b"));
+}
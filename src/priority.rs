@@ -0,0 +1,90 @@
+//! Background priority, selected by `--background`, so that cargo-hold
+//! running next to a build or a test suite does not take CPU time or disk
+//! bandwidth from it.
+
+use std::io;
+
+/// Lower the CPU and I/O priority of this process, and of the threads and
+/// processes it starts from now on.
+///
+/// On Linux priorities belong to threads, which inherit them from the thread
+/// starting them, so this is called before any worker thread is started.
+pub(crate) fn enter_background() -> io::Result<()> {
+    imp::enter_background()
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+
+    pub(super) fn enter_background() -> io::Result<()> {
+        // The lowest CPU priority, like `nice -n 19`
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        #[cfg(target_os = "linux")]
+        idle_io()?;
+        Ok(())
+    }
+
+    /// Switch to the idle I/O scheduling class, like `ionice -c 3`: the disk
+    /// is only used when no other process wants it
+    #[cfg(target_os = "linux")]
+    fn idle_io() -> io::Result<()> {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+        let priority = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, PROCESS_MODE_BACKGROUND_BEGIN, SetPriorityClass,
+    };
+
+    pub(super) fn enter_background() -> io::Result<()> {
+        // Background processing mode lowers the CPU, I/O, and memory
+        // priorities at once
+        if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::io;
+
+    pub(super) fn enter_background() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "background priority is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_background_lowers_cpu_priority() {
+        // In a thread of its own, so that the other tests keep their priority
+        std::thread::spawn(|| {
+            enter_background().unwrap();
+            assert_eq!(unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }, 19);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! Presets for where cargo-hold runs.
+//!
+//! The defaults suit CI, where the target directory and cargo home are
+//! caches that can always be rebuilt. On a developer machine the same
+//! cleanup throws away docs being read, incremental data that makes the next
+//! edit-build cycle fast, and crates shared with every other project, so
+//! `--profile local` leaves those alone and asks before a large eviction.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+
+/// Evictions freeing more than this ask for confirmation with
+/// [`Profile::Local`]
+pub(crate) const LARGE_EVICTION: u64 = 1024 * 1024 * 1024;
+
+/// Preset of GC defaults for where cargo-hold runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Clean aggressively: the target directory and cargo home are caches
+    #[default]
+    Ci,
+    /// Keep the cargo home, incremental data, and docs, and confirm large
+    /// evictions
+    Local,
+}
+
+impl Profile {
+    /// Whether GC cleans the registry, git checkouts, and binaries of the
+    /// cargo home
+    pub(crate) fn cleans_cargo_home(self) -> bool {
+        self == Self::Ci
+    }
+
+    /// Whether GC keeps incremental compilation data regardless of its age
+    pub(crate) fn keeps_incremental(self) -> bool {
+        self == Self::Local
+    }
+
+    /// Whether GC keeps the generated documentation
+    pub(crate) fn keeps_docs(self) -> bool {
+        self == Self::Local
+    }
+
+    /// Size of an eviction above which GC asks before going ahead
+    pub(crate) fn confirm_evictions_above(self) -> Option<u64> {
+        match self {
+            Self::Ci => None,
+            Self::Local => Some(LARGE_EVICTION),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ci" => Ok(Self::Ci),
+            "local" => Ok(Self::Local),
+            _ => Err(HoldError::InvalidProfile(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ci => f.write_str("ci"),
+            Self::Local => f.write_str("local"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_parsing() {
+        assert_eq!("ci".parse::<Profile>().unwrap(), Profile::Ci);
+        assert_eq!(" Local ".parse::<Profile>().unwrap(), Profile::Local);
+        assert!(matches!(
+            "laptop".parse::<Profile>(),
+            Err(HoldError::InvalidProfile(_))
+        ));
+        assert_eq!(Profile::Local.to_string(), "local");
+    }
+
+    #[test]
+    fn test_ci_profile_keeps_todays_behavior() {
+        let ci = Profile::default();
+        assert!(ci.cleans_cargo_home());
+        assert!(!ci.keeps_incremental());
+        assert!(!ci.keeps_docs());
+        assert_eq!(ci.confirm_evictions_above(), None);
+
+        let local = Profile::Local;
+        assert!(!local.cleans_cargo_home());
+        assert!(local.keeps_incremental());
+        assert!(local.keeps_docs());
+        assert_eq!(local.confirm_evictions_above(), Some(LARGE_EVICTION));
+    }
+}
@@ -0,0 +1,175 @@
+//! Anchor-time detection of the units cargo will rebuild because a source
+//! is newer than the build that compiled it.
+//!
+//! Cargo considers a unit stale when any file listed in its dep-info is
+//! newer than the `dep-*` file in its `.fingerprint` directory. After
+//! timestamps are restored, a restored (or fresh) source newer than a cached
+//! build means a rebuild, and a confusing one when the source did not
+//! change. This module compares, through [`DepInfo`], the mtimes of the
+//! sources inside the repository with the fingerprint of every unit, and
+//! reports the units that will rebuild, so "why did cargo rebuild X" can be
+//! answered before the build.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::error::{HoldError, Result};
+use crate::gc::{DepInfo, InputState, find_profile_directories};
+use crate::schema::SCHEMA_VERSION;
+
+/// A unit whose fingerprint is older than one of its sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StaleUnit {
+    /// Profile directory, relative to the target directory (e.g. `debug`)
+    pub(crate) profile: String,
+    /// Package name, as in the fingerprint directory name
+    pub(crate) name: String,
+    /// 16-digit hash of the unit
+    pub(crate) hash: String,
+    /// The newest of the sources newer than the fingerprint, relative to the
+    /// repository root
+    pub(crate) source: PathBuf,
+    /// How much newer that source is than the fingerprint
+    pub(crate) newer_by: Duration,
+}
+
+/// Find the units of every profile directory of `target_dir` that depend on
+/// a source under `repo_root` newer than their fingerprint, sorted by profile,
+/// name, and hash.
+///
+/// Units without a fingerprint or dep-info are left out: cargo rebuilds those
+/// regardless of timestamps.
+pub(crate) fn find_stale_units(target_dir: &Path, repo_root: &Path) -> Result<Vec<StaleUnit>> {
+    let mut stale = Vec::new();
+    for profile_dir in find_profile_directories(target_dir)? {
+        let profile = profile_dir
+            .strip_prefix(target_dir)
+            .unwrap_or(&profile_dir)
+            .to_string_lossy()
+            .into_owned();
+        let dep_info = DepInfo::load(&profile_dir, repo_root)?;
+        for (hash, name) in dep_info.fingerprinted_units() {
+            if let InputState::Newer(source, newer_by) = dep_info.input_state(hash) {
+                stale.push(StaleUnit {
+                    profile: profile.clone(),
+                    name: name.to_string(),
+                    hash: hash.to_string(),
+                    source,
+                    newer_by,
+                });
+            }
+        }
+    }
+    stale.sort_by(|a, b| (&a.profile, &a.name, &a.hash).cmp(&(&b.profile, &b.name, &b.hash)));
+    Ok(stale)
+}
+
+/// Units expected to rebuild, as written by `--rebuild-report`
+pub(crate) fn rebuild_report_json(units: &[StaleUnit]) -> Value {
+    let units: Vec<Value> = units
+        .iter()
+        .map(|unit| {
+            serde_json::json!({
+                "profile": unit.profile,
+                "name": unit.name,
+                "hash": unit.hash,
+                "source": unit.source.to_string_lossy(),
+                "newer_by_secs": unit.newer_by.as_secs_f64(),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "units": units,
+    })
+}
+
+/// Write the report of `units` to `path`
+pub(crate) fn write_rebuild_report(units: &[StaleUnit], path: &Path) -> Result<()> {
+    let io_error = |source| HoldError::IoError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+    serde_json::to_writer_pretty(&mut out, &rebuild_report_json(units))
+        .map_err(std::io::Error::from)
+        .and_then(|()| writeln!(out))
+        .and_then(|()| out.flush())
+        .map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use filetime::FileTime;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef";
+
+    #[test]
+    fn test_find_stale_units_reports_sources_newer_than_the_fingerprint() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        let target = repo.join("target");
+        let deps = target.join("debug/deps");
+        fs::create_dir_all(&deps).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        for name in ["lib.rs", "util.rs", "main.rs"] {
+            fs::write(repo.join("src").join(name), "").unwrap();
+        }
+        let outside = temp_dir.path().with_extension("outside.rs");
+        fs::write(&outside, "").unwrap();
+        let at = |secs: i64| FileTime::from_unix_time(1_700_000_000 + secs, 0);
+
+        // foo was built at +100, after lib.rs but before util.rs and the
+        // file outside the repository
+        let fingerprint = target.join(format!("debug/.fingerprint/foo-{HASH}"));
+        fs::create_dir_all(&fingerprint).unwrap();
+        fs::write(fingerprint.join("dep-lib-foo"), "").unwrap();
+        filetime::set_file_mtime(fingerprint.join("dep-lib-foo"), at(100)).unwrap();
+        fs::write(
+            deps.join(format!("foo-{HASH}.d")),
+            format!(
+                "deps/foo-{HASH}.d: src/lib.rs src/util.rs {}\n",
+                outside.display()
+            ),
+        )
+        .unwrap();
+        filetime::set_file_mtime(repo.join("src/lib.rs"), at(50)).unwrap();
+        filetime::set_file_mtime(repo.join("src/util.rs"), at(160)).unwrap();
+        filetime::set_file_mtime(&outside, at(500)).unwrap();
+
+        // bar is up to date, baz has no fingerprint
+        let bar_hash = "fedcba9876543210";
+        let fingerprint = target.join(format!("debug/.fingerprint/bar-{bar_hash}"));
+        fs::create_dir_all(&fingerprint).unwrap();
+        fs::write(fingerprint.join("dep-bin-bar"), "").unwrap();
+        filetime::set_file_mtime(fingerprint.join("dep-bin-bar"), at(200)).unwrap();
+        fs::write(deps.join(format!("bar-{bar_hash}.d")), "x: src/main.rs\n").unwrap();
+        filetime::set_file_mtime(repo.join("src/main.rs"), at(150)).unwrap();
+        fs::write(deps.join("baz-1111111111111111.d"), "x: src/util.rs\n").unwrap();
+
+        let stale = find_stale_units(&target, repo).unwrap();
+        assert_eq!(
+            stale,
+            [StaleUnit {
+                profile: "debug".to_string(),
+                name: "foo".to_string(),
+                hash: HASH.to_string(),
+                source: PathBuf::from("src/util.rs"),
+                newer_by: Duration::from_secs(60),
+            }]
+        );
+
+        let report = rebuild_report_json(&stale);
+        assert_eq!(report["units"][0]["source"], "src/util.rs");
+        assert_eq!(report["units"][0]["newer_by_secs"], 60.0);
+    }
+}
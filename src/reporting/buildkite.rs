@@ -0,0 +1,78 @@
+//! Buildkite annotations.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::markdown;
+use crate::logging::{Logger, Summary};
+
+/// Annotate the build with the command's summary through `buildkite-agent`
+pub(crate) fn report(summary: &Summary, log: &Logger) {
+    let result = Command::new("buildkite-agent")
+        .args(annotate_args(summary))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(markdown(summary).as_bytes())?;
+            }
+            child.wait()
+        });
+
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => log.warn(format!("buildkite-agent annotate failed ({status})")),
+        Err(err) => log.warn(format!("Could not run buildkite-agent: {err}")),
+    }
+}
+
+/// Arguments of `buildkite-agent`; annotations of the same command are
+/// appended to one another
+fn annotate_args(summary: &Summary) -> Vec<String> {
+    let style = if summary.failed {
+        "error"
+    } else if summary.warnings > 0 {
+        "warning"
+    } else {
+        "info"
+    };
+
+    vec![
+        "annotate".to_string(),
+        "--style".to_string(),
+        style.to_string(),
+        "--context".to_string(),
+        format!("cargo-hold-{}", summary.command),
+        "--append".to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporting::tests::summary;
+
+    #[test]
+    fn test_annotate_args_style_follows_outcome() {
+        let mut summary = summary();
+        assert_eq!(
+            annotate_args(&summary),
+            [
+                "annotate",
+                "--style",
+                "warning",
+                "--context",
+                "cargo-hold-heave",
+                "--append"
+            ]
+        );
+
+        summary.failed = true;
+        assert_eq!(annotate_args(&summary)[2], "error");
+
+        summary.failed = false;
+        summary.warnings = 0;
+        assert_eq!(annotate_args(&summary)[2], "info");
+    }
+}
@@ -0,0 +1,72 @@
+//! JSON report of a failed command, written by `--error-report` for upload
+//! as a CI artifact.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use miette::Diagnostic;
+use serde_json::Value;
+
+use crate::discovery::{self, VcsBackend};
+use crate::error::HoldError;
+use crate::schema::SCHEMA_VERSION;
+use crate::state::METADATA_VERSION;
+
+/// A failed command and the context it failed in
+pub(crate) struct ErrorReport<'a> {
+    /// Name of the command, as typed on the command line
+    pub(crate) command: &'static str,
+    pub(crate) error: &'a HoldError,
+    pub(crate) working_dir: &'a Path,
+    pub(crate) target_dir: &'a Path,
+    pub(crate) metadata_path: &'a Path,
+    /// Backend the command listed tracked files with
+    pub(crate) vcs: VcsBackend,
+}
+
+impl ErrorReport<'_> {
+    /// The report as a JSON document
+    pub(crate) fn to_json(&self) -> Value {
+        let error = self.error;
+        let mut causes = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+
+        serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "command": self.command,
+            "cargo_hold_version": env!("CARGO_PKG_VERSION"),
+            "error": {
+                "message": error.to_string(),
+                "code": error.code().map(|code| code.to_string()),
+                "help": error.help().map(|help| help.to_string()),
+                "url": error.url().map(|url| url.to_string()),
+                "causes": causes,
+            },
+            "context": {
+                "working_dir": path(self.working_dir),
+                "repo_root": discovery::repo_root(self.vcs, self.working_dir).as_deref().map(path),
+                "target_dir": path(self.target_dir),
+                "metadata_path": path(self.metadata_path),
+                "metadata_exists": self.metadata_path.exists(),
+                "metadata_version": METADATA_VERSION,
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+            },
+        })
+    }
+
+    /// Write the report to `path`
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &self.to_json())?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
@@ -0,0 +1,87 @@
+//! GitHub Actions annotations and job summaries.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::markdown;
+use crate::logging::{Logger, Summary};
+
+/// Format a workflow command that annotates the job, e.g.
+/// `::notice title=cargo hold heave::1.2 GiB freed`
+pub(crate) fn annotation(level: &str, title: Option<&str>, message: &str) -> String {
+    match title {
+        Some(title) => format!(
+            "::{level} title={}::{}",
+            escape_property(title),
+            escape_data(message)
+        ),
+        None => format!("::{level}::{}", escape_data(message)),
+    }
+}
+
+/// Annotate the job with the command's summary and add it to the job
+/// summary page
+pub(crate) fn report(summary: &Summary, log: &Logger) {
+    if !log.quiet() {
+        let level = if summary.failed { "error" } else { "notice" };
+        let title = format!("cargo hold {}", summary.command);
+        println!("{}", annotation(level, Some(&title), &summary.to_string()));
+    }
+
+    if let Some(path) = std::env::var_os("GITHUB_STEP_SUMMARY")
+        && let Err(err) = append_step_summary(Path::new(&path), summary)
+    {
+        log.warn(format!("Could not write the job summary: {err}"));
+    }
+}
+
+/// Append the summary as a Markdown table to the job summary file
+fn append_step_summary(path: &Path, summary: &Summary) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(markdown(summary).as_bytes())
+}
+
+/// Escape the message of a workflow command
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::reporting::tests::summary;
+
+    #[test]
+    fn test_annotation_escapes_data_and_properties() {
+        assert_eq!(
+            annotation("warning", None, "100% done\nnext"),
+            "::warning::100%25 done%0Anext"
+        );
+        assert_eq!(
+            annotation("notice", Some("cargo hold: heave, dry"), "ok"),
+            "::notice title=cargo hold%3A heave%2C dry::ok"
+        );
+    }
+
+    #[test]
+    fn test_step_summary_is_appended() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("summary.md");
+
+        append_step_summary(&path, &summary()).unwrap();
+        append_step_summary(&path, &summary()).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, markdown(&summary()).repeat(2));
+    }
+}
@@ -0,0 +1,125 @@
+//! GitLab CI collapsible log sections and metrics reports.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::logging::{Logger, Metric, Summary};
+
+/// Marker opening a collapsed section of the job log
+pub(crate) fn section_start(command: &str) -> String {
+    format!(
+        "\x1b[0Ksection_start:{}:{}[collapsed=true]\r\x1b[0K⚓ cargo hold {command}",
+        unix_secs(),
+        section_name(command)
+    )
+}
+
+/// Marker closing the section opened by [`section_start`]
+fn section_end(command: &str) -> String {
+    format!(
+        "\x1b[0Ksection_end:{}:{}\r\x1b[0K",
+        unix_secs(),
+        section_name(command)
+    )
+}
+
+/// Close the command's log section and print its summary below it, so the
+/// summary stays visible while the details are collapsed
+pub(crate) fn report(summary: &Summary, log: &Logger) {
+    if !log.quiet() {
+        eprintln!("{}", section_end(summary.command));
+        eprintln!("{summary}");
+    }
+}
+
+/// Append the numeric summary items to a metrics report in the OpenMetrics
+/// text format read by GitLab's `artifacts:reports:metrics`
+pub(crate) fn append_metrics_report(path: &Path, summary: &Summary) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(metrics(summary).as_bytes())
+}
+
+/// Render the summary as metrics named `cargo_hold_<command>_<label>`
+fn metrics(summary: &Summary) -> String {
+    let prefix = format!("cargo_hold_{}", metric_name(summary.command));
+    let mut out = String::new();
+    for item in &summary.items {
+        match item.metric {
+            Some(Metric::Count(count)) => {
+                out.push_str(&format!("{prefix}_{} {count}\n", metric_name(item.label)));
+            }
+            Some(Metric::Bytes(bytes)) => {
+                out.push_str(&format!(
+                    "{prefix}_{}_bytes {bytes}\n",
+                    metric_name(item.label)
+                ));
+            }
+            None => {}
+        }
+    }
+    out.push_str(&format!("{prefix}_warnings {}\n", summary.warnings));
+    out.push_str(&format!("{prefix}_failed {}\n", u8::from(summary.failed)));
+    out.push_str(&format!(
+        "{prefix}_duration_seconds {:.3}\n",
+        summary.elapsed.as_secs_f64()
+    ));
+    out
+}
+
+/// Turn a label into a metric name component, e.g. `crates cleaned` into
+/// `crates_cleaned`
+fn metric_name(label: &str) -> String {
+    let mut name = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    name.trim_end_matches('_').to_string()
+}
+
+fn section_name(command: &str) -> String {
+    format!("cargo_hold_{}", metric_name(command))
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::reporting::tests::summary;
+
+    #[test]
+    fn test_sections_are_named_after_the_command() {
+        let start = section_start("clean-crate");
+        assert!(start.contains(":cargo_hold_clean_crate[collapsed=true]\r"));
+        assert!(start.ends_with("⚓ cargo hold clean-crate"));
+        assert!(section_end("clean-crate").contains(":cargo_hold_clean_crate\r"));
+    }
+
+    #[test]
+    fn test_metrics_report_lists_numeric_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("metrics.txt");
+
+        append_metrics_report(&path, &summary()).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "cargo_hold_heave_freed_bytes 1288490188\ncargo_hold_heave_crates_cleaned \
+             42\ncargo_hold_heave_warnings 1\ncargo_hold_heave_failed \
+             0\ncargo_hold_heave_duration_seconds 2.500\n"
+        );
+    }
+}
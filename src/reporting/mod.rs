@@ -0,0 +1,192 @@
+//! Reporting of command results to CI systems.
+//!
+//! The CI system is selected with `--ci` or detected from the environment.
+//! Reporting is best effort: failing to write a report never fails the
+//! command.
+
+pub(crate) mod buildkite;
+pub(crate) mod error_report;
+pub(crate) mod github;
+pub(crate) mod gitlab;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{HoldError, Result};
+use crate::logging::{Logger, Summary};
+
+/// Which CI system cargo-hold reports to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CiMode {
+    /// Detect the CI system from its environment variables
+    #[default]
+    Auto,
+    /// Report to no CI system
+    None,
+    /// GitHub Actions annotations and job summary
+    GitHub,
+    /// GitLab CI collapsible log sections
+    GitLab,
+    /// Buildkite annotations
+    Buildkite,
+}
+
+impl CiMode {
+    /// Resolve [`CiMode::Auto`] to the CI system running cargo-hold
+    pub(crate) fn resolve(self) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        let env_is_true = |name| std::env::var_os(name).is_some_and(|value| value == "true");
+        if env_is_true("GITHUB_ACTIONS") {
+            Self::GitHub
+        } else if env_is_true("GITLAB_CI") {
+            Self::GitLab
+        } else if env_is_true("BUILDKITE") {
+            Self::Buildkite
+        } else {
+            Self::None
+        }
+    }
+}
+
+impl FromStr for CiMode {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "none" => Ok(Self::None),
+            "github" => Ok(Self::GitHub),
+            "gitlab" => Ok(Self::GitLab),
+            "buildkite" => Ok(Self::Buildkite),
+            _ => Err(HoldError::InvalidCiMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CiMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::None => f.write_str("none"),
+            Self::GitHub => f.write_str("github"),
+            Self::GitLab => f.write_str("gitlab"),
+            Self::Buildkite => f.write_str("buildkite"),
+        }
+    }
+}
+
+/// Mark the start of a command in the CI log
+pub(crate) fn begin(command: &str, log: &Logger) {
+    if log.ci() == CiMode::GitLab && !log.quiet() {
+        eprintln!("{}", gitlab::section_start(command));
+    }
+}
+
+/// Report the summary of a finished command to the CI system of `log`
+pub(crate) fn report(summary: &Summary, log: &Logger) {
+    match log.ci() {
+        CiMode::GitHub => github::report(summary, log),
+        CiMode::GitLab => gitlab::report(summary, log),
+        CiMode::Buildkite => buildkite::report(summary, log),
+        CiMode::Auto | CiMode::None => {}
+    }
+}
+
+/// Render the summary as a Markdown section
+pub(crate) fn markdown(summary: &Summary) -> String {
+    let mut rows: Vec<(String, String)> = summary
+        .items
+        .iter()
+        .map(|item| (capitalize(item.label), item.value.clone()))
+        .collect();
+    if summary.warnings > 0 {
+        rows.push(("Warnings".to_string(), summary.warnings.to_string()));
+    }
+    rows.push((
+        "Result".to_string(),
+        if summary.failed {
+            "❌ Failed"
+        } else {
+            "✅ Succeeded"
+        }
+        .to_string(),
+    ));
+    rows.push((
+        "Duration".to_string(),
+        format!("{:.1}s", summary.elapsed.as_secs_f64()),
+    ));
+
+    let mut out = format!(
+        "### ⚓ cargo hold {}\n\n| | |\n| --- | --- |\n",
+        summary.command
+    );
+    for (label, value) in rows {
+        out.push_str(&format!("| {label} | {value} |\n"));
+    }
+    out.push('\n');
+    out
+}
+
+fn capitalize(label: &str) -> String {
+    let mut chars = label.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::logging::{Metric, SummaryItem};
+
+    pub(crate) fn summary() -> Summary {
+        Summary {
+            command: "heave",
+            items: vec![
+                SummaryItem {
+                    label: "freed",
+                    value: "1.2 GiB".to_string(),
+                    metric: Some(Metric::Bytes(1288490188)),
+                },
+                SummaryItem {
+                    label: "crates cleaned",
+                    value: "42".to_string(),
+                    metric: Some(Metric::Count(42)),
+                },
+                SummaryItem {
+                    label: "(target busy)",
+                    value: "skipped".to_string(),
+                    metric: None,
+                },
+            ],
+            warnings: 1,
+            elapsed: Duration::from_millis(2500),
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn test_ci_mode_parsing() {
+        assert_eq!("auto".parse::<CiMode>().unwrap(), CiMode::Auto);
+        assert_eq!("GitLab".parse::<CiMode>().unwrap(), CiMode::GitLab);
+        assert_eq!("buildkite".parse::<CiMode>().unwrap(), CiMode::Buildkite);
+        assert_eq!(CiMode::GitHub.to_string(), "github");
+        assert!("jenkins".parse::<CiMode>().is_err());
+        assert_eq!(CiMode::GitLab.resolve(), CiMode::GitLab);
+    }
+
+    #[test]
+    fn test_markdown_renders_a_table() {
+        let markdown = markdown(&summary());
+
+        assert!(markdown.starts_with("### ⚓ cargo hold heave\n"));
+        assert!(markdown.contains("| Freed | 1.2 GiB |\n| Crates cleaned | 42 |\n"));
+        assert!(markdown.contains("| Warnings | 1 |\n| Result | ✅ Succeeded |\n"));
+        assert!(markdown.contains("| Duration | 2.5s |\n"));
+    }
+}
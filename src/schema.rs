@@ -0,0 +1,248 @@
+//! JSON Schema of the JSON documents cargo-hold writes.
+//!
+//! The schema is embedded in the binary and printed by `cargo hold schema`.
+//! It only ever grows: new properties are optional, and existing ones keep
+//! their name and type. Object documents carry the `schema_version` they
+//! were written with, so parsers can tell which properties to expect.
+
+/// Version of the schema, bumped whenever a property is added
+pub(crate) const SCHEMA_VERSION: u64 = 11;
+
+/// The schema document
+pub(crate) const SCHEMA: &str = include_str!("schema.json");
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::PathBuf;
+    #[cfg(feature = "cli")]
+    use std::time::Duration;
+
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use super::*;
+    #[cfg(feature = "cli")]
+    use crate::commands::anchor::AnchorOutcome;
+    #[cfg(feature = "cli")]
+    use crate::commands::batch::{BatchRepo, BatchReport, RepoOutcome};
+    #[cfg(feature = "cli")]
+    use crate::commands::compare::Comparison;
+    #[cfg(feature = "gc")]
+    use crate::commands::salvage::SalvageReport;
+    #[cfg(feature = "cli")]
+    use crate::context::HoldContext;
+    #[cfg(feature = "cli")]
+    use crate::discovery::VcsBackend;
+    #[cfg(feature = "gc")]
+    use crate::gc::{
+        AuditLogFormat, GcPhase, RemovalListFormat, RemovalReason, RemovedPath, append_audit_log,
+        write_removal_list,
+    };
+    #[cfg(feature = "cli")]
+    use crate::logging::{self, Metric};
+    use crate::reporting::error_report::ErrorReport;
+    use crate::seal::Seal;
+    #[cfg(feature = "cli")]
+    use crate::state::{FileState, StateMetadata};
+
+    fn schema() -> Value {
+        serde_json::from_str(SCHEMA).unwrap()
+    }
+
+    /// Properties declared for objects of definition `name`, at `pointer`
+    /// below it
+    fn declared(name: &str, pointer: &str) -> BTreeSet<String> {
+        let schema = schema();
+        let properties = schema
+            .pointer(&format!("/$defs/{name}{pointer}/properties"))
+            .and_then(Value::as_object)
+            .unwrap();
+        properties.keys().cloned().collect()
+    }
+
+    fn keys(value: &Value) -> BTreeSet<String> {
+        value.as_object().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_schema_version_matches() {
+        assert_eq!(schema()["schema_version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    #[cfg(feature = "gc")]
+    fn test_written_documents_only_use_declared_properties() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let removal_list = temp_dir.path().join("removed.json");
+        let removed = [RemovedPath {
+            path: PathBuf::from("debug/deps/libfoo-1234.rlib"),
+            size: 10,
+            phase: GcPhase::TargetDir,
+            reason: RemovalReason::Age,
+        }];
+        write_removal_list(&removed, RemovalListFormat::Json, &removal_list).unwrap();
+        let document: Value = serde_json::from_slice(&fs::read(&removal_list).unwrap()).unwrap();
+        assert_eq!(keys(&document[0]), declared("removalList", "/items"));
+
+        let audit_log = temp_dir.path().join("gc-audit.jsonl");
+        append_audit_log(&removed, AuditLogFormat::Jsonl, &audit_log).unwrap();
+        let line: Value = serde_json::from_slice(&fs::read(&audit_log).unwrap()).unwrap();
+        assert_eq!(keys(&line), declared("auditLogEntry", ""));
+        assert_eq!(line["schema_version"], SCHEMA_VERSION);
+
+        let target = temp_dir.path().join("target");
+        fs::create_dir_all(target.join("debug")).unwrap();
+        fs::write(target.join("debug/foo"), b"foo").unwrap();
+        Seal::create(&target).unwrap().save(&target).unwrap();
+        let document: Value =
+            serde_json::from_slice(&fs::read(target.join(crate::seal::SEAL_FILENAME)).unwrap())
+                .unwrap();
+        assert_eq!(keys(&document), declared("sealManifest", ""));
+        assert_eq!(document["schema_version"], SCHEMA_VERSION);
+        assert_eq!(
+            keys(&document["files"][0]),
+            declared("sealManifest", "/properties/files/items")
+        );
+
+        let line = crate::gc::policy_input_line("debug", "foo", "1234", 10, 2, 1_700_000_000, 60);
+        assert_eq!(keys(&line), declared("policyInput", ""));
+        assert_eq!(line["schema_version"], SCHEMA_VERSION);
+
+        let changed = [PathBuf::from("Cargo.lock")];
+        let report = SalvageReport {
+            modified: &changed,
+            added: &[],
+            removed: &[],
+            manifests: &[changed[0].as_path()],
+        }
+        .to_json();
+        assert_eq!(keys(&report), declared("salvageReport", ""));
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+
+        #[cfg(feature = "cli")]
+        {
+            let report = crate::rebuilds::rebuild_report_json(&[crate::rebuilds::StaleUnit {
+                profile: "debug".to_string(),
+                name: "foo".to_string(),
+                hash: "0123456789abcdef".to_string(),
+                source: PathBuf::from("src/lib.rs"),
+                newer_by: Duration::from_secs(60),
+            }]);
+            assert_eq!(keys(&report), declared("rebuildReport", ""));
+            assert_eq!(report["schema_version"], SCHEMA_VERSION);
+            assert_eq!(
+                keys(&report["units"][0]),
+                declared("rebuildReport", "/properties/units/items")
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_compare_report_only_uses_declared_properties() {
+        let mut old = StateMetadata::new();
+        old.upsert(FileState {
+            path: PathBuf::from("src/lib.rs"),
+            size: 10,
+            hash: [1; 32],
+            mtime_nanos: 1,
+        })
+        .unwrap();
+        let mut new = old.clone();
+        new.upsert(FileState {
+            path: PathBuf::from("src/lib.rs"),
+            size: 10,
+            hash: [1; 32],
+            mtime_nanos: 2,
+        })
+        .unwrap();
+        let report = Comparison::new(&old, &new).to_json(
+            &PathBuf::from("old.metadata"),
+            &PathBuf::from("new.metadata"),
+        );
+        assert_eq!(keys(&report), declared("compareReport", ""));
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(
+            keys(&report["changed"][0]),
+            declared("compareReport", "/properties/changed/items")
+        );
+        assert_eq!(
+            keys(&report["gc_metrics"][0]),
+            declared("compareReport", "/properties/gc_metrics/items")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_batch_report_only_uses_declared_properties() {
+        let repos = [BatchRepo {
+            name: "api".to_string(),
+            path: PathBuf::from("/ci/api"),
+            target_dir: PathBuf::from("/ci/api/target"),
+            build_dir: None,
+            metadata_path: PathBuf::from("/ci/api/target/cargo-hold.metadata"),
+            max_target_size: Some(1024),
+            priority: 1,
+            removal_list: None,
+            preserve_units_from: None,
+            rebuild_report: None,
+        }];
+        let context = HoldContext::default();
+        context.logger(0, true).summarize_count("files", 3);
+        let outcomes = [RepoOutcome {
+            summary: logging::Summary::take(&context, "voyage", Duration::from_secs(1), true),
+            anchored: Some(AnchorOutcome::Unchanged),
+            error: Some("boom".to_string()),
+        }];
+        let report = BatchReport {
+            command: "voyage",
+            repos: &repos,
+            outcomes: &outcomes,
+            totals: &[("files", Metric::Count(3))],
+            cargo_home_freed: Some(10),
+            cargo_home_size: Some(20),
+        }
+        .to_json();
+        assert_eq!(keys(&report), declared("batchReport", ""));
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(
+            keys(&report["repositories"][0]),
+            declared("batchReport", "/properties/repositories/items")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_error_report_only_uses_declared_properties() {
+        let temp_dir = TempDir::new().unwrap();
+        let error = crate::error::HoldError::IoError {
+            path: PathBuf::from("target/cargo-hold.metadata"),
+            source: std::io::Error::other("disk full"),
+        };
+        let report = ErrorReport {
+            command: "stow",
+            error: &error,
+            working_dir: temp_dir.path(),
+            target_dir: &temp_dir.path().join("target"),
+            metadata_path: &temp_dir.path().join("target/cargo-hold.metadata"),
+            vcs: VcsBackend::Auto,
+        }
+        .to_json();
+        assert_eq!(keys(&report), declared("errorReport", ""));
+        assert_eq!(report["schema_version"], SCHEMA_VERSION);
+        assert_eq!(
+            keys(&report["error"]),
+            declared("errorReport", "/properties/error")
+        );
+        assert_eq!(
+            keys(&report["context"]),
+            declared("errorReport", "/properties/context")
+        );
+        assert_eq!(report["error"]["code"], "cargo_hold::io_error");
+        assert_eq!(report["error"]["causes"][0], "disk full");
+        assert_eq!(report["context"]["repo_root"], Value::Null);
+    }
+}
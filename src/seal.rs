@@ -0,0 +1,378 @@
+//! Integrity manifest of a target directory.
+//!
+//! `cargo hold seal` records the size and BLAKE3 hash of every file in the
+//! target directory right before it is cached. After the cache is restored,
+//! `cargo hold verify` compares the files against the manifest, so a
+//! truncated or damaged cache download is reported up front instead of as an
+//! inscrutable rustc metadata error halfway through the build.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::error::{HoldError, Result};
+use crate::hashing::hash_file;
+use crate::schema::SCHEMA_VERSION;
+
+/// Seal manifest, kept in the target directory
+pub(crate) const SEAL_FILENAME: &str = "cargo-hold.seal.json";
+
+/// Files in the root of the target directory with this prefix belong to
+/// cargo-hold and change after sealing, so they are not sealed
+const OWN_FILE_PREFIX: &str = "cargo-hold.";
+
+/// Size and hash of a sealed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SealedFile {
+    size: u64,
+    hash: String,
+}
+
+/// Sizes and hashes of the files of a target directory, keyed by path
+/// relative to it
+#[derive(Debug, Default)]
+pub(crate) struct Seal {
+    files: BTreeMap<PathBuf, SealedFile>,
+}
+
+/// How thoroughly `verify` checks the sealed files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verification {
+    /// Check the size of every file and hash at most this many of them
+    Sample(usize),
+    /// Check the size and hash of every file
+    Full,
+}
+
+/// A sealed file that does not match the seal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Corruption {
+    /// The file is gone
+    Missing(PathBuf),
+    /// The file has a different size
+    Size {
+        path: PathBuf,
+        expected: u64,
+        actual: u64,
+    },
+    /// The file has the same size but different contents
+    Contents(PathBuf),
+}
+
+impl std::fmt::Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Corruption::Missing(path) => write!(f, "{}: missing", path.display()),
+            Corruption::Size {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: {actual} bytes, sealed with {expected}",
+                path.display()
+            ),
+            Corruption::Contents(path) => write!(f, "{}: contents changed", path.display()),
+        }
+    }
+}
+
+/// Result of verifying a target directory against its seal
+#[derive(Debug, Default)]
+pub(crate) struct VerifyReport {
+    /// Sealed files whose size was checked
+    pub(crate) files_checked: usize,
+    /// Sealed files whose contents were hashed
+    pub(crate) files_hashed: usize,
+    /// Files that do not match the seal
+    pub(crate) corrupted: Vec<Corruption>,
+}
+
+impl Seal {
+    /// Hash every file in `target_dir`
+    pub(crate) fn create(target_dir: &Path) -> Result<Self> {
+        let mut paths = Vec::new();
+        collect_files(target_dir, Path::new(""), &mut paths)?;
+
+        let files = paths
+            .into_par_iter()
+            .map(|relative| {
+                let path = target_dir.join(&relative);
+                let size = fs::metadata(&path)
+                    .map_err(|source| HoldError::IoError {
+                        path: path.clone(),
+                        source,
+                    })?
+                    .len();
+                let hash = hash_file(&path)?;
+                Ok((relative, SealedFile { size, hash }))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { files })
+    }
+
+    /// Load the seal of `target_dir`, or `None` if it was never sealed
+    pub(crate) fn load(target_dir: &Path) -> Result<Option<Self>> {
+        let path = target_dir.join(SEAL_FILENAME);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(source) if source.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(HoldError::IoError { path, source }),
+        };
+
+        let invalid = |message: &str| HoldError::SealManifestError {
+            path: path.clone(),
+            message: message.to_string(),
+        };
+        let value: Value =
+            serde_json::from_slice(&contents).map_err(|err| invalid(&err.to_string()))?;
+        let entries = value
+            .get("files")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("missing \"files\" array"))?;
+
+        let mut files = BTreeMap::new();
+        for entry in entries {
+            let path = entry
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("entry without a \"path\""))?;
+            let size = entry
+                .get("size")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| invalid("entry without a \"size\""))?;
+            let hash = entry
+                .get("hash")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("entry without a \"hash\""))?;
+            files.insert(
+                PathBuf::from(path),
+                SealedFile {
+                    size,
+                    hash: hash.to_string(),
+                },
+            );
+        }
+
+        Ok(Some(Self { files }))
+    }
+
+    /// Write the seal into `target_dir`
+    pub(crate) fn save(&self, target_dir: &Path) -> Result<()> {
+        let path = target_dir.join(SEAL_FILENAME);
+        let io_error = |source| HoldError::IoError {
+            path: path.clone(),
+            source,
+        };
+
+        let entries: Vec<_> = self
+            .files
+            .iter()
+            .map(|(path, file)| {
+                serde_json::json!({
+                    "path": path.to_string_lossy(),
+                    "size": file.size,
+                    "hash": file.hash,
+                })
+            })
+            .collect();
+        let mut out = BufWriter::new(File::create(&path).map_err(io_error)?);
+        let document = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "files": entries,
+        });
+        serde_json::to_writer(&mut out, &document)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(out))
+            .and_then(|()| out.flush())
+            .map_err(io_error)
+    }
+
+    /// Number of sealed files
+    pub(crate) fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Total size of the sealed files
+    pub(crate) fn total_size(&self) -> u64 {
+        self.files.values().map(|file| file.size).sum()
+    }
+
+    /// Compare the files in `target_dir` against the seal.
+    ///
+    /// Every file's size is checked, which catches truncated downloads
+    /// cheaply. Which files are hashed when sampling changes from run to run,
+    /// so repeated runs cover more of the directory.
+    pub(crate) fn verify(&self, target_dir: &Path, verification: Verification) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        let mut same_size = Vec::new();
+
+        for (relative, sealed) in &self.files {
+            report.files_checked += 1;
+            match fs::metadata(target_dir.join(relative)) {
+                Ok(metadata) if metadata.len() == sealed.size => {
+                    same_size.push((relative, sealed));
+                }
+                Ok(metadata) => report.corrupted.push(Corruption::Size {
+                    path: relative.clone(),
+                    expected: sealed.size,
+                    actual: metadata.len(),
+                }),
+                Err(_) => report.corrupted.push(Corruption::Missing(relative.clone())),
+            }
+        }
+
+        if let Verification::Sample(count) = verification
+            && count < same_size.len()
+        {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default()
+                .to_le_bytes();
+            same_size.sort_by_cached_key(|(relative, _)| {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&seed);
+                hasher.update(relative.as_os_str().as_encoded_bytes());
+                *hasher.finalize().as_bytes()
+            });
+            same_size.truncate(count);
+        }
+
+        report.files_hashed = same_size.len();
+        let mut changed: Vec<_> = same_size
+            .into_par_iter()
+            .filter(|(relative, sealed)| {
+                hash_file(&target_dir.join(relative)).map_or(true, |hash| hash != sealed.hash)
+            })
+            .map(|(relative, _)| Corruption::Contents(relative.clone()))
+            .collect();
+        changed.sort_by_cached_key(ToString::to_string);
+        report.corrupted.extend(changed);
+
+        report
+    }
+}
+
+/// Collect the regular files below `dir`, relative to the target directory.
+/// Symbolic links are skipped, as cargo recreates them.
+fn collect_files(target_dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let dir = target_dir.join(relative);
+    let entries = fs::read_dir(&dir).map_err(|source| HoldError::IoError {
+        path: dir.clone(),
+        source,
+    })?;
+
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: dir.clone(),
+            source,
+        })?;
+        let file_type = entry.file_type().map_err(|source| HoldError::IoError {
+            path: entry.path(),
+            source,
+        })?;
+        let child = relative.join(entry.file_name());
+
+        if file_type.is_dir() {
+            collect_files(target_dir, &child, files)?;
+        } else if file_type.is_file() {
+            let own_file = relative.as_os_str().is_empty()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(OWN_FILE_PREFIX);
+            if !own_file {
+                files.push(child);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn target() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let deps = temp_dir.path().join("debug/deps");
+        fs::create_dir_all(&deps).unwrap();
+        fs::write(deps.join("libfoo-1234567890abcdef.rlib"), [1u8; 1000]).unwrap();
+        fs::write(deps.join("libbar-1234567890abcdef.rlib"), [2u8; 500]).unwrap();
+        fs::write(temp_dir.path().join("cargo-hold.metadata"), b"state").unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_seal_round_trips_and_skips_own_files() {
+        let temp_dir = target();
+
+        let seal = Seal::create(temp_dir.path()).unwrap();
+        seal.save(temp_dir.path()).unwrap();
+        let loaded = Seal::load(temp_dir.path()).unwrap().unwrap();
+
+        assert_eq!(loaded.files, seal.files);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.total_size(), 1500);
+    }
+
+    #[test]
+    fn test_verify_reports_truncated_missing_and_changed_files() {
+        let temp_dir = target();
+        let deps = temp_dir.path().join("debug/deps");
+        let seal = Seal::create(temp_dir.path()).unwrap();
+        assert!(
+            seal.verify(temp_dir.path(), Verification::Full)
+                .corrupted
+                .is_empty()
+        );
+
+        fs::write(deps.join("libfoo-1234567890abcdef.rlib"), [1u8; 10]).unwrap();
+        fs::write(deps.join("libbar-1234567890abcdef.rlib"), [3u8; 500]).unwrap();
+        let report = seal.verify(temp_dir.path(), Verification::Full);
+        assert_eq!(
+            report.corrupted,
+            [
+                Corruption::Size {
+                    path: PathBuf::from("debug/deps/libfoo-1234567890abcdef.rlib"),
+                    expected: 1000,
+                    actual: 10,
+                },
+                Corruption::Contents(PathBuf::from("debug/deps/libbar-1234567890abcdef.rlib")),
+            ]
+        );
+
+        fs::remove_file(deps.join("libfoo-1234567890abcdef.rlib")).unwrap();
+        let report = seal.verify(temp_dir.path(), Verification::Sample(0));
+        assert_eq!(report.files_checked, 2);
+        assert_eq!(report.files_hashed, 0);
+        assert_eq!(
+            report.corrupted,
+            [Corruption::Missing(PathBuf::from(
+                "debug/deps/libfoo-1234567890abcdef.rlib"
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_seal() {
+        let temp_dir = target();
+        assert!(Seal::load(temp_dir.path()).unwrap().is_none());
+
+        fs::write(temp_dir.path().join(SEAL_FILENAME), b"{\"files\": 1}").unwrap();
+        assert!(matches!(
+            Seal::load(temp_dir.path()),
+            Err(HoldError::SealManifestError { .. })
+        ));
+    }
+}
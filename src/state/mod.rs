@@ -5,15 +5,28 @@ use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::error::{HoldError, Result};
 
+mod rollup;
 #[cfg(test)]
 mod tests;
 
+pub use rollup::{ChangeKind, FileChange, changed_files, rollup_hashes};
+
 /// Current version of the metadata format.
 ///
 /// This version is incremented when incompatible changes are made to the
 /// metadata format. The tool will refuse to load metadata with a version higher
 /// than this constant.
-pub const METADATA_VERSION: u32 = 4;
+pub const METADATA_VERSION: u32 = 18;
+
+/// Maximum number of GC timestamps kept in [`StateMetadata`].
+pub const MAX_RECORDED_GC_TIMESTAMPS: usize = 16;
+
+/// Maximum number of build outcomes kept in [`GcMetrics`].
+pub const MAX_RECORDED_BUILDS: usize = 20;
+
+/// Number of GC runs the bounded windows of [`GcMetrics`] keep unless
+/// [`StateMetadata::gc_metrics_window`] says otherwise.
+pub const GC_METRICS_WINDOW: usize = 20;
 
 /// Represents the state of a single file at a point in time.
 ///
@@ -34,18 +47,20 @@ pub struct FileState {
     /// we know the file has changed without needing to read its contents.
     pub size: u64,
 
-    /// Hex-encoded BLAKE3 hash of the file's contents.
+    /// BLAKE3 hash of the file's contents.
     ///
     /// This provides a cryptographically strong guarantee that the file's
-    /// contents haven't changed.
-    pub hash: String,
+    /// contents haven't changed. Files above `--large-file-threshold` may hold
+    /// a hash of their head only, or of nothing when compared by size alone.
+    pub hash: [u8; 32],
 
     /// The monotonically-increasing timestamp last set on this file by
     /// cargo-hold.
     ///
     /// Stored as nanoseconds since UNIX_EPOCH to ensure precision across
-    /// different filesystems and platforms.
-    pub mtime_nanos: u128,
+    /// different filesystems and platforms. `u64` covers timestamps until the
+    /// year 2554; later ones are clamped to `u64::MAX`.
+    pub mtime_nanos: u64,
 }
 
 /// The metadata containing all tracked file states.
@@ -76,8 +91,51 @@ pub struct StateMetadata {
     /// metadata that was migrated.
     pub last_gc_mtime_nanos: Option<u128>,
 
+    /// Bounded history of recent GC timestamps, oldest first.
+    ///
+    /// The last entry matches `last_gc_mtime_nanos`. Keeping several entries
+    /// lets the garbage collector preserve artifacts from the last N builds
+    /// (e.g. alternating debug and release builds) rather than only the most
+    /// recent one.
+    pub recent_gc_mtime_nanos: Vec<u128>,
+
     /// Rolling garbage-collection telemetry used to auto-tune cache sizing.
     pub gc_metrics: GcMetrics,
+
+    /// Crate name patterns pinned with `cargo hold pin`, in the order they
+    /// were added.
+    ///
+    /// Artifacts of matching crates are never evicted by garbage collection.
+    pub pinned_crates: Vec<String>,
+
+    /// Identifies the salt the file hashes are keyed with, `None` when they
+    /// are unsalted.
+    ///
+    /// Hashes recorded with another salt, e.g. by another repository sharing
+    /// the cache, never match and are ignored.
+    pub salt_id: Option<[u8; 16]>,
+
+    /// Rollup hashes of the directories holding the files, keyed by path
+    /// relative to the repository root (the root itself by `""`).
+    ///
+    /// Derived from `files` by [`rollup_hashes`] whenever they are stowed, so
+    /// comparisons can skip the subtrees whose rollups match.
+    pub dir_hashes: HashMap<String, [u8; 32]>,
+
+    /// Milliseconds cargo last took to build each crate, keyed by crate name
+    /// with dashes as underscores.
+    ///
+    /// Recorded from cargo's timing data by `cargo hold report-build
+    /// --timings`; garbage collection evicts crates that are cheap to rebuild
+    /// before expensive ones.
+    pub build_costs: HashMap<String, u64>,
+
+    /// Number of GC runs the bounded windows of `gc_metrics` keep, set with
+    /// `--gc-metrics-window`; `None` keeps [`GC_METRICS_WINDOW`].
+    ///
+    /// Pipelines that run rarely, e.g. weekly, need a longer memory than
+    /// those running on every pull request.
+    pub gc_metrics_window: Option<u32>,
 }
 
 impl StateMetadata {
@@ -87,7 +145,64 @@ impl StateMetadata {
             version: METADATA_VERSION,
             files: HashMap::new(),
             last_gc_mtime_nanos: None,
+            recent_gc_mtime_nanos: Vec::new(),
             gc_metrics: GcMetrics::default(),
+            pinned_crates: Vec::new(),
+            salt_id: None,
+            dir_hashes: HashMap::new(),
+            build_costs: HashMap::new(),
+            gc_metrics_window: None,
+        }
+    }
+
+    /// Recomputes [`StateMetadata::dir_hashes`] from the file states.
+    pub fn update_dir_hashes(&mut self) {
+        self.dir_hashes = rollup_hashes(self.files.values());
+    }
+
+    /// Pins a crate name pattern, returning false if it was already pinned.
+    pub fn pin(&mut self, pattern: &str) -> bool {
+        if self.pinned_crates.iter().any(|pinned| pinned == pattern) {
+            return false;
+        }
+        self.pinned_crates.push(pattern.to_string());
+        true
+    }
+
+    /// Unpins a crate name pattern, returning false if it was not pinned.
+    pub fn unpin(&mut self, pattern: &str) -> bool {
+        let before = self.pinned_crates.len();
+        self.pinned_crates.retain(|pinned| pinned != pattern);
+        self.pinned_crates.len() != before
+    }
+
+    /// Records a GC timestamp, keeping at most [`MAX_RECORDED_GC_TIMESTAMPS`]
+    /// entries in the history.
+    pub fn record_gc_mtime_nanos(&mut self, nanos: u128) {
+        self.last_gc_mtime_nanos = Some(nanos);
+        self.recent_gc_mtime_nanos.push(nanos);
+        if self.recent_gc_mtime_nanos.len() > MAX_RECORDED_GC_TIMESTAMPS {
+            let overflow = self.recent_gc_mtime_nanos.len() - MAX_RECORDED_GC_TIMESTAMPS;
+            self.recent_gc_mtime_nanos.drain(0..overflow);
+        }
+    }
+
+    /// Returns the number of GC runs the bounded windows of the GC metrics
+    /// keep.
+    pub fn metrics_window(&self) -> usize {
+        self.gc_metrics_window
+            .map_or(GC_METRICS_WINDOW, |window| window as usize)
+    }
+
+    /// Returns the recorded GC timestamps, oldest first.
+    ///
+    /// Falls back to `last_gc_mtime_nanos` for metadata written before the
+    /// history was tracked.
+    pub fn gc_mtime_history(&self) -> Vec<u128> {
+        if self.recent_gc_mtime_nanos.is_empty() {
+            self.last_gc_mtime_nanos.into_iter().collect()
+        } else {
+            self.recent_gc_mtime_nanos.clone()
         }
     }
 
@@ -95,7 +210,7 @@ impl StateMetadata {
     ///
     /// Returns `None` if the metadata is empty. The timestamp is in nanoseconds
     /// since UNIX_EPOCH.
-    pub fn max_mtime_nanos(&self) -> Option<u128> {
+    pub fn max_mtime_nanos(&self) -> Option<u64> {
         self.files.values().map(|state| state.mtime_nanos).max()
     }
 
@@ -189,6 +304,145 @@ pub struct GcMetrics {
     pub recent_final_sizes: Vec<u64>,
     /// Last recorded cap computation trace for observability/debugging.
     pub last_cap_trace: Option<CapTrace>,
+    /// Bounded history of last-build mtimes per profile directory, oldest
+    /// first.
+    ///
+    /// Keys are profile directories relative to the target directory (e.g.
+    /// `debug`, `release`, `x86_64-unknown-linux-gnu/release`). Each entry is
+    /// the newest artifact mtime seen in that profile during a GC run, so
+    /// profiles built on different schedules are preserved independently.
+    pub profile_build_mtime_nanos: HashMap<String, Vec<u128>>,
+    /// Sizes of the target directory's subdirectories from the last GC scan.
+    ///
+    /// Keys are directories relative to the target directory (`""` for the
+    /// target directory itself). Entries whose directory mtime is unchanged
+    /// are reused instead of measuring every file again.
+    pub directory_sizes: HashMap<String, DirectorySize>,
+    /// Bounded history of builds reported with `cargo hold report-build`,
+    /// oldest first.
+    pub recent_builds: Vec<BuildRecord>,
+}
+
+impl GcMetrics {
+    /// Records the outcome of a build, keeping at most
+    /// [`MAX_RECORDED_BUILDS`] entries.
+    pub fn record_build(&mut self, build: BuildRecord) {
+        self.recent_builds.push(build);
+        if self.recent_builds.len() > MAX_RECORDED_BUILDS {
+            let overflow = self.recent_builds.len() - MAX_RECORDED_BUILDS;
+            self.recent_builds.drain(0..overflow);
+        }
+    }
+
+    /// Returns the last successful build when the builds reported after it
+    /// failed.
+    ///
+    /// The artifacts of such a build are worth more than those of the failed
+    /// builds that followed, so garbage collection keeps preserving them.
+    pub fn successful_build_before_failures(&self) -> Option<&BuildRecord> {
+        if self.recent_builds.last()?.success {
+            return None;
+        }
+        self.recent_builds.iter().rev().find(|build| build.success)
+    }
+
+    /// Returns the per-profile build timestamps garbage collection preserves
+    /// artifacts from.
+    ///
+    /// While the builds reported after a successful one failed, the history
+    /// of each profile that build covered is rolled back to its start, so
+    /// its artifacts stay preserved along with those of the failed builds.
+    pub fn preserved_build_mtime_nanos(&self) -> HashMap<String, Vec<u128>> {
+        let mut histories = self.profile_build_mtime_nanos.clone();
+        if let Some(build) = self.successful_build_before_failures() {
+            for (profile, start) in &build.profile_start_mtime_nanos {
+                let history = histories.entry(profile.clone()).or_default();
+                history.retain(|nanos| nanos <= start);
+                if history.last() != Some(start) {
+                    history.push(*start);
+                }
+            }
+        }
+        histories
+    }
+
+    /// Returns the median duration of the recent successful builds, in
+    /// seconds, as an estimate of what a full rebuild costs.
+    pub fn median_build_secs(&self) -> Option<u64> {
+        let mut durations: Vec<u64> = self
+            .recent_builds
+            .iter()
+            .filter(|build| build.success)
+            .filter_map(|build| build.duration_secs)
+            .collect();
+        durations.sort_unstable();
+        durations.get(durations.len() / 2).copied()
+    }
+
+    /// Records the last-build mtime observed for a profile directory.
+    ///
+    /// Repeated observations of the same build are ignored, and at most
+    /// [`MAX_RECORDED_GC_TIMESTAMPS`] entries are kept per profile.
+    pub fn record_profile_build_mtime_nanos(&mut self, profile: &str, nanos: u128) {
+        let history = self
+            .profile_build_mtime_nanos
+            .entry(profile.to_string())
+            .or_default();
+        if history.last() == Some(&nanos) {
+            return;
+        }
+        history.push(nanos);
+        if history.len() > MAX_RECORDED_GC_TIMESTAMPS {
+            let overflow = history.len() - MAX_RECORDED_GC_TIMESTAMPS;
+            history.drain(0..overflow);
+        }
+    }
+}
+
+/// Sizes of the files directly inside a directory, as recorded by a GC scan.
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct DirectorySize {
+    /// Directory mtime when it was scanned, in nanoseconds since UNIX_EPOCH.
+    pub mtime_nanos: u128,
+    /// Total apparent size of the files with a single link (bytes).
+    pub apparent: u64,
+    /// Total allocated size of the files with a single link (bytes).
+    pub allocated: u64,
+    /// Number of files with a single link.
+    pub files: u64,
+    /// Files with several hard links, kept apart so that each inode is still
+    /// counted only once across directories.
+    pub linked_files: Vec<LinkedFileSize>,
+    /// Names of the subdirectories.
+    pub subdirs: Vec<String>,
+}
+
+/// Outcome of a build reported with `cargo hold report-build`.
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct BuildRecord {
+    /// When the build was reported, in nanoseconds since UNIX_EPOCH.
+    pub reported_nanos: u128,
+    /// Whether the build succeeded.
+    pub success: bool,
+    /// How long the build took, in seconds, when reported.
+    pub duration_secs: Option<u64>,
+    /// Where the build started in each profile directory it built, keyed
+    /// like [`GcMetrics::profile_build_mtime_nanos`]. Only recorded for
+    /// successful builds.
+    pub profile_start_mtime_nanos: HashMap<String, u128>,
+}
+
+/// Size of a file with several hard links, identified by device and inode.
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkedFileSize {
+    /// Device holding the file.
+    pub dev: u64,
+    /// Inode number of the file.
+    pub ino: u64,
+    /// Apparent size (bytes).
+    pub apparent: u64,
+    /// Allocated size (bytes).
+    pub allocated: u64,
 }
 
 /// Diagnostic trace of the most recent auto-cap computation.
@@ -0,0 +1,215 @@
+//! Directory rollup hashes.
+//!
+//! The rollup hash of a directory is a Merkle hash over the names, sizes,
+//! and hashes of its files and the names and rollup hashes of its
+//! subdirectories. Two sets of file states whose rollups match at a
+//! directory hold the same files below it, so comparing them only descends
+//! into the directories whose rollups differ.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use blake3::Hasher;
+
+use super::{FileState, StateMetadata};
+
+/// BLAKE3 key derivation context of rollup hashes
+const ROLLUP_CONTEXT: &str = "cargo-hold 2025-01 directory rollup";
+
+/// Directory and file name of a metadata path, the directory being empty at
+/// the top level
+fn split(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Rollup hashes of the directories holding `files`, keyed by path relative
+/// to the repository root; the root itself is keyed by the empty string
+pub fn rollup_hashes<'a>(
+    files: impl IntoIterator<Item = &'a FileState>,
+) -> HashMap<String, [u8; 32]> {
+    let listing = Listing::new(
+        files
+            .into_iter()
+            .filter_map(|state| Some((state.path.to_str()?, state))),
+    );
+
+    // Deepest directories first, so subdirectories are hashed before their
+    // parents
+    let mut dirs: Vec<&str> = listing.dirs.keys().copied().collect();
+    dirs.sort_unstable_by_key(|dir| std::cmp::Reverse(depth(dir)));
+
+    let mut hashes: HashMap<String, [u8; 32]> = HashMap::with_capacity(dirs.len());
+    for dir in dirs {
+        let entries = &listing.dirs[dir];
+        let mut files: Vec<(&str, &FileState)> = entries
+            .files
+            .iter()
+            .map(|(path, state)| (split(path).1, *state))
+            .collect();
+        files.sort_unstable_by_key(|(name, _)| *name);
+        let mut subdirs: Vec<&str> = entries.subdirs.clone();
+        subdirs.sort_unstable();
+
+        let mut hasher = Hasher::new_derive_key(ROLLUP_CONTEXT);
+        for (name, state) in files {
+            hasher.update(&[0]);
+            hasher.update(&(name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(&state.size.to_le_bytes());
+            hasher.update(&state.hash);
+        }
+        for subdir in subdirs {
+            let name = split(subdir).1;
+            hasher.update(&[1]);
+            hasher.update(&(name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(&hashes[subdir]);
+        }
+        hashes.insert(dir.to_string(), *hasher.finalize().as_bytes());
+    }
+    hashes
+}
+
+fn depth(dir: &str) -> usize {
+    if dir.is_empty() {
+        0
+    } else {
+        dir.matches('/').count() + 1
+    }
+}
+
+/// Files and subdirectories of every directory holding files
+#[derive(Default)]
+struct Listing<'a> {
+    dirs: HashMap<&'a str, DirEntries<'a>>,
+}
+
+#[derive(Default)]
+struct DirEntries<'a> {
+    /// Full paths and states of the files
+    files: Vec<(&'a str, &'a FileState)>,
+    /// Full paths of the subdirectories
+    subdirs: Vec<&'a str>,
+}
+
+impl<'a> Listing<'a> {
+    fn new(files: impl IntoIterator<Item = (&'a str, &'a FileState)>) -> Self {
+        let mut listing = Self::default();
+        listing.dirs.insert("", DirEntries::default());
+        for (path, state) in files {
+            let (mut dir, _) = split(path);
+            let known = listing.dirs.contains_key(dir);
+            listing
+                .dirs
+                .entry(dir)
+                .or_default()
+                .files
+                .push((path, state));
+            if known {
+                continue;
+            }
+            // Registers the new directory with its parents, up to the first
+            // one already registered
+            while !dir.is_empty() {
+                let (parent, _) = split(dir);
+                let known = listing.dirs.contains_key(parent);
+                listing.dirs.entry(parent).or_default().subdirs.push(dir);
+                if known {
+                    break;
+                }
+                dir = parent;
+            }
+        }
+        listing
+    }
+}
+
+/// How a file differs between two sets of file states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    /// Only the newer set has the file
+    Added,
+    /// The file's size or hash differs
+    Modified,
+    /// Only the older set has the file
+    Removed,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added => f.write_str("A"),
+            Self::Modified => f.write_str("M"),
+            Self::Removed => f.write_str("D"),
+        }
+    }
+}
+
+/// A file that differs between two sets of file states
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileChange {
+    /// Repository-relative path of the file
+    pub path: PathBuf,
+    /// How the file differs
+    pub kind: ChangeKind,
+}
+
+/// Files that differ between `old` and `new`, sorted by path.
+///
+/// Directories whose rollup hashes match are skipped without looking at
+/// their files; directories without rollup hashes are always compared file
+/// by file.
+pub fn changed_files(old: &StateMetadata, new: &StateMetadata) -> Vec<FileChange> {
+    let old_listing = Listing::new(old.files.iter().map(|(path, state)| (path.as_str(), state)));
+    let new_listing = Listing::new(new.files.iter().map(|(path, state)| (path.as_str(), state)));
+    let empty = DirEntries::default();
+
+    let mut changes = Vec::new();
+    let mut pending = vec![""];
+    while let Some(dir) = pending.pop() {
+        if let (Some(old_hash), Some(new_hash)) = (old.dir_hashes.get(dir), new.dir_hashes.get(dir))
+            && old_hash == new_hash
+        {
+            continue;
+        }
+        let old_entries = old_listing.dirs.get(dir).unwrap_or(&empty);
+        let new_entries = new_listing.dirs.get(dir).unwrap_or(&empty);
+
+        for (path, old_state) in &old_entries.files {
+            let kind = match new.files.get(*path) {
+                None => ChangeKind::Removed,
+                Some(new_state)
+                    if new_state.size != old_state.size || new_state.hash != old_state.hash =>
+                {
+                    ChangeKind::Modified
+                }
+                Some(_) => continue,
+            };
+            changes.push(FileChange {
+                path: PathBuf::from(path),
+                kind,
+            });
+        }
+        changes.extend(
+            new_entries
+                .files
+                .iter()
+                .filter(|(path, _)| !old.files.contains_key(*path))
+                .map(|(path, _)| FileChange {
+                    path: PathBuf::from(path),
+                    kind: ChangeKind::Added,
+                }),
+        );
+
+        pending.extend(&old_entries.subdirs);
+        pending.extend(
+            new_entries
+                .subdirs
+                .iter()
+                .filter(|subdir| !old_listing.dirs.contains_key(*subdir)),
+        );
+    }
+    changes.sort_unstable();
+    changes
+}
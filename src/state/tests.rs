@@ -1,6 +1,26 @@
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
-use crate::state::{FileState, StateMetadata};
+use crate::state::{
+    BuildRecord, ChangeKind, FileChange, FileState, GcMetrics, MAX_RECORDED_BUILDS,
+    MAX_RECORDED_GC_TIMESTAMPS, StateMetadata, changed_files, rollup_hashes,
+};
+
+fn tree(files: &[(&str, &str)]) -> StateMetadata {
+    let mut metadata = StateMetadata::new();
+    for (path, contents) in files {
+        metadata
+            .upsert(FileState {
+                path: PathBuf::from(path),
+                size: contents.len() as u64,
+                hash: *blake3::hash(contents.as_bytes()).as_bytes(),
+                mtime_nanos: 1,
+            })
+            .unwrap();
+    }
+    metadata.update_dir_hashes();
+    metadata
+}
 
 #[test]
 fn test_state_metadata_operations() {
@@ -10,11 +30,11 @@ fn test_state_metadata_operations() {
     let state = FileState {
         path: PathBuf::from("src/main.rs"),
         size: 1234,
-        hash: "abcdef".to_string(),
+        hash: [0xab; 32],
         mtime_nanos: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_nanos(),
+            .as_nanos() as u64,
     };
 
     metadata.upsert(state.clone()).unwrap();
@@ -23,7 +43,7 @@ fn test_state_metadata_operations() {
 
     let retrieved = metadata.get(&state.path).unwrap().unwrap();
     assert_eq!(retrieved.size, 1234);
-    assert_eq!(retrieved.hash, "abcdef");
+    assert_eq!(retrieved.hash, [0xab; 32]);
 
     metadata.remove(&state.path).unwrap();
     assert!(metadata.is_empty());
@@ -37,14 +57,14 @@ fn test_max_mtime_nanos() {
     let now_nanos = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
-        .as_nanos();
+        .as_nanos() as u64;
     let earlier_nanos = now_nanos - 10_000_000_000; // 10 seconds earlier
 
     metadata
         .upsert(FileState {
             path: PathBuf::from("file1.rs"),
             size: 100,
-            hash: "hash1".to_string(),
+            hash: [1; 32],
             mtime_nanos: earlier_nanos,
         })
         .unwrap();
@@ -53,10 +73,186 @@ fn test_max_mtime_nanos() {
         .upsert(FileState {
             path: PathBuf::from("file2.rs"),
             size: 200,
-            hash: "hash2".to_string(),
+            hash: [2; 32],
             mtime_nanos: now_nanos,
         })
         .unwrap();
 
     assert_eq!(metadata.max_mtime_nanos(), Some(now_nanos));
 }
+
+#[test]
+fn test_record_gc_mtime_nanos_is_bounded() {
+    let mut metadata = StateMetadata::new();
+    assert!(metadata.gc_mtime_history().is_empty());
+
+    // Metadata written before the history existed falls back to the single
+    // timestamp.
+    metadata.last_gc_mtime_nanos = Some(7);
+    assert_eq!(metadata.gc_mtime_history(), vec![7]);
+
+    for nanos in 0..(MAX_RECORDED_GC_TIMESTAMPS as u128 + 4) {
+        metadata.record_gc_mtime_nanos(nanos);
+    }
+
+    let history = metadata.gc_mtime_history();
+    assert_eq!(history.len(), MAX_RECORDED_GC_TIMESTAMPS);
+    assert_eq!(history.first().copied(), Some(4));
+    assert_eq!(
+        metadata.last_gc_mtime_nanos,
+        Some(MAX_RECORDED_GC_TIMESTAMPS as u128 + 3)
+    );
+}
+
+#[test]
+fn test_record_profile_build_mtime_nanos_skips_repeats() {
+    let mut metrics = GcMetrics::default();
+    metrics.record_profile_build_mtime_nanos("debug", 1);
+    metrics.record_profile_build_mtime_nanos("debug", 1);
+    metrics.record_profile_build_mtime_nanos("debug", 2);
+    metrics.record_profile_build_mtime_nanos("release", 3);
+
+    assert_eq!(metrics.profile_build_mtime_nanos["debug"], vec![1, 2]);
+    assert_eq!(metrics.profile_build_mtime_nanos["release"], vec![3]);
+
+    for nanos in 10..(10 + MAX_RECORDED_GC_TIMESTAMPS as u128 + 1) {
+        metrics.record_profile_build_mtime_nanos("debug", nanos);
+    }
+    assert_eq!(
+        metrics.profile_build_mtime_nanos["debug"].len(),
+        MAX_RECORDED_GC_TIMESTAMPS
+    );
+}
+
+#[test]
+fn test_pin_and_unpin() {
+    let mut metadata = StateMetadata::new();
+
+    assert!(metadata.pin("rocksdb-sys"));
+    assert!(!metadata.pin("rocksdb-sys"));
+    assert!(metadata.pin("*-sys"));
+    assert_eq!(metadata.pinned_crates, ["rocksdb-sys", "*-sys"]);
+
+    assert!(metadata.unpin("rocksdb-sys"));
+    assert!(!metadata.unpin("rocksdb-sys"));
+    assert_eq!(metadata.pinned_crates, ["*-sys"]);
+}
+
+#[test]
+fn test_failed_builds_roll_preservation_back_to_last_success() {
+    let mut metrics = GcMetrics::default();
+    metrics.record_profile_build_mtime_nanos("debug", 100);
+    metrics.record_build(BuildRecord {
+        success: true,
+        duration_secs: Some(600),
+        profile_start_mtime_nanos: [("debug".to_string(), 200)].into(),
+        ..Default::default()
+    });
+    metrics.record_profile_build_mtime_nanos("debug", 200);
+    metrics.record_profile_build_mtime_nanos("debug", 300);
+    assert!(metrics.successful_build_before_failures().is_none());
+    assert_eq!(
+        metrics.preserved_build_mtime_nanos()["debug"],
+        [100, 200, 300]
+    );
+
+    metrics.record_build(BuildRecord {
+        success: false,
+        duration_secs: Some(60),
+        ..Default::default()
+    });
+    assert!(metrics.successful_build_before_failures().is_some());
+    assert_eq!(metrics.preserved_build_mtime_nanos()["debug"], [100, 200]);
+    assert_eq!(metrics.median_build_secs(), Some(600));
+
+    for _ in 0..MAX_RECORDED_BUILDS {
+        metrics.record_build(BuildRecord::default());
+    }
+    assert_eq!(metrics.recent_builds.len(), MAX_RECORDED_BUILDS);
+    assert!(metrics.successful_build_before_failures().is_none());
+    assert_eq!(metrics.median_build_secs(), None);
+}
+
+#[test]
+fn test_rollup_hashes_change_along_the_path_of_a_change() {
+    let files = [
+        ("Cargo.toml", "[package]"),
+        ("src/lib.rs", "mod gc;"),
+        ("src/gc/mod.rs", "fn gc() {}"),
+        ("tests/it.rs", "#[test]"),
+    ];
+    let old = tree(&files);
+    assert_eq!(
+        old.dir_hashes
+            .keys()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>(),
+        BTreeSet::from(["", "src", "src/gc", "tests"])
+    );
+    assert_eq!(
+        tree(&files).dir_hashes,
+        old.dir_hashes,
+        "Rollups are deterministic"
+    );
+
+    let mut changed = files;
+    changed[2].1 = "fn gc() { todo!() }";
+    let new = tree(&changed);
+    for dir in ["", "src", "src/gc"] {
+        assert_ne!(old.dir_hashes[dir], new.dir_hashes[dir], "{dir}");
+    }
+    assert_eq!(old.dir_hashes["tests"], new.dir_hashes["tests"]);
+
+    // Moving a file changes the rollups of both directories
+    let moved = tree(&[
+        ("Cargo.toml", "[package]"),
+        ("src/lib.rs", "mod gc;"),
+        ("src/gc.rs", "fn gc() {}"),
+        ("tests/it.rs", "#[test]"),
+    ]);
+    assert_ne!(old.dir_hashes["src"], moved.dir_hashes["src"]);
+    assert!(!moved.dir_hashes.contains_key("src/gc"));
+    assert_eq!(rollup_hashes(moved.files.values()), moved.dir_hashes);
+}
+
+#[test]
+fn test_changed_files_descends_only_into_changed_directories() {
+    let old = tree(&[
+        ("Cargo.toml", "[package]"),
+        ("src/lib.rs", "mod gc;"),
+        ("src/gc/mod.rs", "fn gc() {}"),
+        ("src/gc/size.rs", "fn size() {}"),
+        ("tests/it.rs", "#[test]"),
+    ]);
+    let new = tree(&[
+        ("Cargo.toml", "[package]"),
+        ("src/lib.rs", "mod gc; mod size;"),
+        ("src/gc/mod.rs", "fn gc() {}"),
+        ("src/size.rs", "fn size() {}"),
+        ("benches/gc.rs", "fn main() {}"),
+        ("tests/it.rs", "#[test]"),
+    ]);
+    let change = |path: &str, kind| FileChange {
+        path: PathBuf::from(path),
+        kind,
+    };
+    assert_eq!(
+        changed_files(&old, &new),
+        [
+            change("benches/gc.rs", ChangeKind::Added),
+            change("src/gc/size.rs", ChangeKind::Removed),
+            change("src/lib.rs", ChangeKind::Modified),
+            change("src/size.rs", ChangeKind::Added),
+        ]
+    );
+    assert!(changed_files(&old, &old).is_empty());
+
+    // Matching rollups are trusted without looking at the files below them
+    let mut stale = new.clone();
+    stale.dir_hashes = old.dir_hashes.clone();
+    assert!(changed_files(&old, &stale).is_empty());
+    // Without rollups, every file is compared
+    let mut bare = new.clone();
+    bare.dir_hashes.clear();
+    assert_eq!(changed_files(&old, &bare), changed_files(&old, &new));
+}
@@ -22,29 +22,30 @@ pub fn saturating_duration_from_nanos(nanos: u128) -> (Duration, bool) {
     (Duration::new(seconds as u64, nanos_remainder), false)
 }
 
-/// Compute a [`SystemTime`] from nanoseconds with saturation at
-/// `UNIX_EPOCH + Duration::MAX`.
-///
-/// Returns the saturated timestamp along with a flag indicating whether the
-/// input exceeded the representable range.
-pub fn saturating_system_time_from_nanos(nanos: u128) -> (SystemTime, bool) {
-    let (duration, saturated) = saturating_duration_from_nanos(nanos);
-    (UNIX_EPOCH + duration, saturated)
-}
-
 use crate::error::{HoldError, Result};
 use crate::state::{FileState, StateMetadata};
 
+/// Clamp nanoseconds since UNIX_EPOCH to the `u64` file states store them in.
+///
+/// `u64` nanoseconds reach into the year 2554; later timestamps are clamped to
+/// `u64::MAX` rather than wrapped, so they still sort after every other one.
+pub(crate) fn clamp_file_mtime_nanos(nanos: u128) -> u64 {
+    u64::try_from(nanos).unwrap_or(u64::MAX)
+}
+
 /// Convert nanoseconds since UNIX_EPOCH to SystemTime
-fn nanos_to_system_time(nanos: u128) -> SystemTime {
-    saturating_system_time_from_nanos(nanos).0
+fn nanos_to_system_time(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos)
 }
 
-/// Convert SystemTime to nanoseconds since UNIX_EPOCH
-fn system_time_to_nanos(time: SystemTime) -> u128 {
-    time.duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::ZERO)
-        .as_nanos()
+/// Convert SystemTime to nanoseconds since UNIX_EPOCH, clamped like
+/// [`clamp_file_mtime_nanos`]
+pub(crate) fn system_time_to_nanos(time: SystemTime) -> u64 {
+    clamp_file_mtime_nanos(
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_nanos(),
+    )
 }
 
 /// Generates a monotonic timestamp that is guaranteed to be newer than any
@@ -63,6 +64,7 @@ fn system_time_to_nanos(time: SystemTime) -> u128 {
 ///
 /// A `SystemTime` that is guaranteed to be at least 1 nanosecond newer than any
 /// timestamp in the metadata, or the current system time, whichever is later.
+/// Only metadata holding the clamped `u64::MAX` gets that timestamp back.
 pub fn generate_monotonic_timestamp(metadata: &StateMetadata) -> SystemTime {
     // Get the maximum timestamp from metadata in nanos
     let max_metadata_nanos = metadata.max_mtime_nanos().unwrap_or(0);
@@ -71,7 +73,7 @@ pub fn generate_monotonic_timestamp(metadata: &StateMetadata) -> SystemTime {
     let now_nanos = system_time_to_nanos(SystemTime::now());
 
     // Return the maximum of now and max_metadata_nanos + 1
-    let monotonic_nanos = max(now_nanos, max_metadata_nanos + 1);
+    let monotonic_nanos = max(now_nanos, max_metadata_nanos.saturating_add(1));
 
     nanos_to_system_time(monotonic_nanos)
 }
@@ -1,12 +1,13 @@
 use std::fs;
 use std::path::PathBuf;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use tempfile::TempDir;
 
 use crate::state::{FileState, StateMetadata};
 use crate::timestamp::{
-    generate_monotonic_timestamp, restore_timestamps, set_file_mtime, system_time_to_nanos,
+    clamp_file_mtime_nanos, generate_monotonic_timestamp, restore_timestamps, set_file_mtime,
+    system_time_to_nanos,
 };
 
 #[test]
@@ -23,7 +24,7 @@ fn test_generate_monotonic_timestamp() {
         .upsert(FileState {
             path: PathBuf::from("test.rs"),
             size: 100,
-            hash: "hash".to_string(),
+            hash: [7; 32],
             mtime_nanos: system_time_to_nanos(future_time),
         })
         .unwrap();
@@ -33,6 +34,23 @@ fn test_generate_monotonic_timestamp() {
     assert!(ts2 > future_time);
 }
 
+#[test]
+fn test_monotonic_timestamp_saturates_at_clamped_timestamps() {
+    let mut metadata = StateMetadata::new();
+    metadata
+        .upsert(FileState {
+            path: PathBuf::from("test.rs"),
+            size: 100,
+            hash: [7; 32],
+            mtime_nanos: clamp_file_mtime_nanos(u128::MAX),
+        })
+        .unwrap();
+
+    let latest = UNIX_EPOCH + Duration::from_nanos(u64::MAX);
+    assert_eq!(generate_monotonic_timestamp(&metadata), latest);
+    assert_eq!(system_time_to_nanos(latest), u64::MAX);
+}
+
 #[test]
 fn test_set_file_mtime() {
     let temp_dir = TempDir::new().unwrap();
@@ -70,7 +88,7 @@ fn test_restore_timestamps() {
     let unchanged_state = FileState {
         path: PathBuf::from("unchanged.txt"),
         size: 9,
-        hash: "hash1".to_string(),
+        hash: [1; 32],
         mtime_nanos: system_time_to_nanos(old_time),
     };
 
@@ -0,0 +1,341 @@
+//! Release lookup and installation for `cargo hold self-update`.
+//!
+//! Releases are the GitHub releases of this repository: a `.tar.gz` archive
+//! per target holding the `cargo-hold` binary, and a `sha256sum` listing of
+//! every archive. Downloads go through `curl` and archives are unpacked with
+//! `tar`, which every supported runner image ships.
+
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fmt, fs};
+
+use serde_json::Value;
+
+use crate::error::{HoldError, Result};
+use crate::logging::Logger;
+
+/// GitHub repository the releases are published in
+const REPOSITORY: &str = "Ellipsis-Labs/cargo-hold";
+
+/// Checksums published with every release
+const CHECKSUMS_FILENAME: &str = "cargo-hold-checksums-sha256.txt";
+
+/// Environment variable opting in to the "new version available" check
+/// after every command
+pub(crate) const UPDATE_CHECK_ENV: &str = "CARGO_HOLD_UPDATE_CHECK";
+
+/// Where the last passive check is remembered, in the cargo home directory
+const CHECK_CACHE_FILENAME: &str = ".cargo-hold-update-check";
+
+/// How long a passive check's answer is reused, so a fleet of runners does
+/// not exhaust GitHub's API rate limit
+const CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Page listing the releases, for error messages and `--offline`
+pub(crate) const RELEASES_URL: &str = "https://github.com/Ellipsis-Labs/cargo-hold/releases";
+
+/// A release version. Pre-release and build suffixes are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl Version {
+    /// Version of the running binary
+    pub(crate) fn current() -> Self {
+        env!("CARGO_PKG_VERSION").parse().unwrap_or(Self {
+            major: 0,
+            minor: 0,
+            patch: 0,
+        })
+    }
+}
+
+impl FromStr for Version {
+    type Err = HoldError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || HoldError::SelfUpdateFailed(format!("'{s}' is not a release version"));
+        let core = s.trim().trim_start_matches('v');
+        let core = core.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = core.split('.').map(|part| part.parse::<u64>());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok(Self {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Rust target of the release archive matching the running platform
+fn release_target() -> Option<&'static str> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-musl"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-musl"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        _ => None,
+    }
+}
+
+/// Version of the latest release
+pub(crate) fn latest_version(timeout_secs: u64) -> Result<Version> {
+    let url = format!("https://api.github.com/repos/{REPOSITORY}/releases/latest");
+    let body = download(&url, timeout_secs)?;
+    let release: Value = serde_json::from_slice(&body).map_err(|err| {
+        HoldError::SelfUpdateFailed(format!("unexpected response from {url}: {err}"))
+    })?;
+    release
+        .get("tag_name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| HoldError::SelfUpdateFailed(format!("no release tag in {url}")))?
+        .parse()
+}
+
+/// Download the release archive of `version` for this platform, check it
+/// against the release's checksums, and replace `executable` with the binary
+/// it holds.
+pub(crate) fn install(version: Version, executable: &Path, log: &Logger) -> Result<()> {
+    let target = release_target().ok_or_else(|| {
+        HoldError::SelfUpdateFailed(format!(
+            "no release is built for {}-{}",
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ))
+    })?;
+    let archive_name = format!("cargo-hold-{version}-{target}.tar.gz");
+    let base_url = format!("https://github.com/{REPOSITORY}/releases/download/v{version}");
+
+    let checksums = download(&format!("{base_url}/{CHECKSUMS_FILENAME}"), 60)?;
+    let expected = published_checksum(&String::from_utf8_lossy(&checksums), &archive_name)
+        .ok_or_else(|| {
+            HoldError::SelfUpdateFailed(format!(
+                "{CHECKSUMS_FILENAME} does not list {archive_name}"
+            ))
+        })?;
+
+    log.verbose(1, format!("Downloading {archive_name}..."));
+    let archive = download(&format!("{base_url}/{archive_name}"), 300)?;
+    let actual = crate::sha256::hex_digest(&archive);
+    if actual != expected {
+        return Err(HoldError::UpdateChecksumMismatch {
+            file: archive_name,
+            expected,
+            actual,
+        });
+    }
+    log.verbose(1, format!("Verified the SHA-256 of {archive_name}"));
+
+    // Unpack next to the executable, so the final rename stays on one
+    // filesystem and replaces the binary atomically
+    let staging = executable.with_file_name(format!(".cargo-hold-update-{}", std::process::id()));
+    let result = unpack_and_replace(&archive, &staging, executable);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn unpack_and_replace(archive: &[u8], staging: &Path, executable: &Path) -> Result<()> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| HoldError::IoError { path, source }
+    };
+
+    fs::create_dir_all(staging).map_err(io_error(staging))?;
+    let archive_path = staging.join("cargo-hold.tar.gz");
+    fs::write(&archive_path, archive).map_err(io_error(&archive_path))?;
+
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(staging)
+        .arg("cargo-hold")
+        .status()
+        .map_err(|err| HoldError::SelfUpdateFailed(format!("could not run tar: {err}")))?;
+    if !status.success() {
+        return Err(HoldError::SelfUpdateFailed(format!(
+            "tar could not unpack the release archive ({status})"
+        )));
+    }
+
+    let binary = staging.join("cargo-hold");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))
+            .map_err(io_error(&binary))?;
+    }
+    fs::rename(&binary, executable).map_err(io_error(executable))
+}
+
+/// Checksum listed for `file` in `sha256sum` output
+fn published_checksum(checksums: &str, file: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        // `sha256sum` marks files hashed in binary mode with a `*`
+        let name = name.trim_start().trim_start_matches('*');
+        (name == file).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// Fetch `url` with `curl`
+fn download(url: &str, timeout_secs: u64) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .arg("--max-time")
+        .arg(timeout_secs.to_string())
+        .arg(url)
+        .output()
+        .map_err(|err| HoldError::SelfUpdateFailed(format!("could not run curl: {err}")))?;
+    if !output.status.success() {
+        return Err(HoldError::SelfUpdateFailed(format!(
+            "could not download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Whether the passive update check is enabled through [`UPDATE_CHECK_ENV`]
+pub(crate) fn check_enabled() -> bool {
+    std::env::var(UPDATE_CHECK_ENV).is_ok_and(|value| {
+        matches!(
+            value.trim().to_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    })
+}
+
+/// Tell the user when a newer release is available. Best effort: the
+/// answer is cached for a day in the cargo home directory, and failures are
+/// only reported verbosely.
+pub(crate) fn notify_if_outdated(log: &Logger) {
+    let current = Version::current();
+    match cached_or_latest_version() {
+        Ok(latest) if latest > current => log.info(format!(
+            "📦 cargo-hold {latest} is available (running {current}); run `cargo hold \
+             self-update` to install it"
+        )),
+        Ok(_) => {}
+        Err(err) => log.verbose(1, format!("Could not check for a newer cargo-hold: {err}")),
+    }
+}
+
+fn cached_or_latest_version() -> Result<Version> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let cache = home::cargo_home()
+        .ok()
+        .map(|home| home.join(CHECK_CACHE_FILENAME));
+
+    let cached = cache
+        .as_deref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| {
+            let (checked, version) = contents.trim().split_once(' ')?;
+            let checked: u64 = checked.parse().ok()?;
+            let version: Version = version.parse().ok()?;
+            (now.saturating_sub(checked) < CHECK_INTERVAL_SECS).then_some(version)
+        });
+    if let Some(version) = cached {
+        return Ok(version);
+    }
+
+    let latest = latest_version(5)?;
+    if let Some(path) = cache {
+        let _ = fs::write(path, format!("{now} {latest}\n"));
+    }
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_parse_and_order() {
+        let version: Version = "v1.3.1".parse().unwrap();
+        assert_eq!(version.to_string(), "1.3.1");
+        assert_eq!(
+            "1.4.0-rc.1".parse::<Version>().unwrap().to_string(),
+            "1.4.0"
+        );
+        assert!("1.10.0".parse::<Version>().unwrap() > version);
+        assert!("2.0.0".parse::<Version>().unwrap() > "1.99.99".parse().unwrap());
+
+        for invalid in ["", "1.2", "1.2.3.4", "latest"] {
+            assert!(invalid.parse::<Version>().is_err(), "{invalid}");
+        }
+        assert_eq!(Version::current().to_string(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_unpack_and_replace_swaps_the_binary() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let release = temp_dir.path().join("release");
+        fs::create_dir(&release).unwrap();
+        fs::write(release.join("cargo-hold"), b"new binary").unwrap();
+        let archive_path = temp_dir.path().join("release.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive_path)
+            .arg("-C")
+            .arg(&release)
+            .arg("cargo-hold")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let executable = temp_dir.path().join("cargo-hold");
+        fs::write(&executable, b"old binary").unwrap();
+        let staging = temp_dir.path().join(".staging");
+        let archive = fs::read(&archive_path).unwrap();
+        unpack_and_replace(&archive, &staging, &executable).unwrap();
+
+        assert_eq!(fs::read(&executable).unwrap(), b"new binary");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&executable).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[test]
+    fn test_published_checksum_reads_sha256sum_output() {
+        let checksums = "\
+ABCDEF  cargo-hold-1.4.0-x86_64-unknown-linux-musl.tar.gz
+012345 *cargo-hold-1.4.0-aarch64-apple-darwin.tar.gz
+";
+        assert_eq!(
+            published_checksum(
+                checksums,
+                "cargo-hold-1.4.0-x86_64-unknown-linux-musl.tar.gz"
+            ),
+            Some("abcdef".to_string())
+        );
+        assert_eq!(
+            published_checksum(checksums, "cargo-hold-1.4.0-aarch64-apple-darwin.tar.gz"),
+            Some("012345".to_string())
+        );
+        assert_eq!(
+            published_checksum(checksums, "cargo-hold-1.4.0.tar.gz"),
+            None
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Workspace-aware fingerprint invalidation.
+//!
+//! cargo-hold restores mtimes for unchanged files, which occasionally leaves
+//! cargo's own dirty detection disagreeing with our content analysis (for
+//! example when a proc-macro's input changed but its mtimes were restored).
+//! This module maps changed files to workspace members via `cargo metadata`,
+//! expands the set to every member that depends on them, and removes the
+//! matching `.fingerprint` entries so cargo is forced to rebuild those units.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::Value;
+
+use crate::error::{HoldError, Result};
+use crate::gc::{find_profile_directories, parse_crate_artifact_name};
+use crate::logging::Logger;
+
+/// A workspace member as reported by `cargo metadata --no-deps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WorkspaceMember {
+    pub(crate) name: String,
+    pub(crate) dir: PathBuf,
+    /// Directories of path dependencies declared by this member.
+    pub(crate) path_deps: Vec<PathBuf>,
+}
+
+/// Summary of a fingerprint invalidation pass.
+#[derive(Debug, Default)]
+pub(crate) struct InvalidationStats {
+    /// Packages whose fingerprints were invalidated.
+    pub(crate) packages: BTreeSet<String>,
+    /// Number of fingerprint directories removed across all profiles.
+    pub(crate) fingerprints_removed: usize,
+}
+
+/// Invalidate fingerprints for the workspace members owning `changed_files`
+/// and all of their workspace dependents.
+///
+/// `changed_files` are relative to `repo_root`. Failures to run
+/// `cargo metadata` are reported as warnings and treated as a no-op, since
+/// the repository may not be a cargo workspace at all.
+pub(crate) fn invalidate_changed_members(
+    repo_root: &Path,
+    changed_files: &[PathBuf],
+    target_dir: &Path,
+    log: &Logger,
+) -> Result<InvalidationStats> {
+    let mut stats = InvalidationStats::default();
+
+    if changed_files.is_empty() {
+        return Ok(stats);
+    }
+
+    let members = match load_workspace_members(repo_root) {
+        Ok(members) => members,
+        Err(err) => {
+            log.warn(format!(
+                "Could not read workspace metadata, skipping fingerprint invalidation: {err}"
+            ));
+            return Ok(stats);
+        }
+    };
+
+    let absolute: Vec<PathBuf> = changed_files.iter().map(|p| repo_root.join(p)).collect();
+    stats.packages = affected_members(&members, &absolute);
+
+    if stats.packages.is_empty() {
+        log.verbose(1, "No workspace members affected by changed files");
+        return Ok(stats);
+    }
+
+    log.verbose(
+        1,
+        format!(
+            "Invalidating fingerprints for: {}",
+            stats
+                .packages
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    );
+
+    for profile_dir in find_profile_directories(target_dir)? {
+        stats.fingerprints_removed +=
+            remove_fingerprints(&profile_dir.join(".fingerprint"), &stats.packages)?;
+    }
+
+    Ok(stats)
+}
+
+/// Run `cargo metadata --no-deps` in `dir` and parse the workspace members.
+fn load_workspace_members(dir: &Path) -> Result<Vec<WorkspaceMember>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let output = Command::new(cargo)
+        .args([
+            "metadata",
+            "--no-deps",
+            "--format-version",
+            "1",
+            "--offline",
+        ])
+        .current_dir(dir)
+        .output()
+        .map_err(|source| HoldError::IoError {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+
+    if !output.status.success() {
+        return Err(HoldError::CargoMetadataError(format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_workspace_members(&output.stdout)
+}
+
+/// Parse the JSON emitted by `cargo metadata --no-deps --format-version 1`.
+pub(crate) fn parse_workspace_members(json: &[u8]) -> Result<Vec<WorkspaceMember>> {
+    let value: Value = serde_json::from_slice(json).map_err(|e| {
+        HoldError::CargoMetadataError(format!("Invalid cargo metadata output: {e}"))
+    })?;
+
+    let packages = value
+        .get("packages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| {
+            HoldError::CargoMetadataError("cargo metadata has no packages".to_string())
+        })?;
+
+    let members = packages
+        .iter()
+        .filter_map(|package| {
+            let name = package.get("name")?.as_str()?.to_string();
+            let dir = Path::new(package.get("manifest_path")?.as_str()?)
+                .parent()?
+                .to_path_buf();
+            let path_deps = package
+                .get("dependencies")
+                .and_then(Value::as_array)
+                .map(|deps| {
+                    deps.iter()
+                        .filter_map(|dep| dep.get("path")?.as_str().map(PathBuf::from))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(WorkspaceMember {
+                name,
+                dir,
+                path_deps,
+            })
+        })
+        .collect();
+
+    Ok(members)
+}
+
+/// Determine which members own the changed files, plus their transitive
+/// dependents within the workspace.
+pub(crate) fn affected_members(
+    members: &[WorkspaceMember],
+    changed_files: &[PathBuf],
+) -> BTreeSet<String> {
+    let mut affected = BTreeSet::new();
+
+    for file in changed_files {
+        // The owning member is the one with the deepest directory containing the
+        // file, so nested members win over an enclosing root package.
+        if let Some(owner) = members
+            .iter()
+            .filter(|member| file.starts_with(&member.dir))
+            .max_by_key(|member| member.dir.components().count())
+        {
+            affected.insert(owner.name.clone());
+        }
+    }
+
+    // Reverse edges: dependency name -> members depending on it.
+    let by_dir: HashMap<&Path, &str> = members
+        .iter()
+        .map(|member| (member.dir.as_path(), member.name.as_str()))
+        .collect();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for member in members {
+        for dep_dir in &member.path_deps {
+            if let Some(dep_name) = by_dir.get(dep_dir.as_path()) {
+                dependents
+                    .entry(dep_name)
+                    .or_default()
+                    .push(member.name.as_str());
+            }
+        }
+    }
+
+    let mut stack: Vec<String> = affected.iter().cloned().collect();
+    while let Some(name) = stack.pop() {
+        if let Some(children) = dependents.get(name.as_str()) {
+            for child in children {
+                if affected.insert(child.to_string()) {
+                    stack.push(child.to_string());
+                }
+            }
+        }
+    }
+
+    affected
+}
+
+/// Remove every fingerprint directory belonging to one of `packages`.
+fn remove_fingerprints(fingerprint_dir: &Path, packages: &BTreeSet<String>) -> Result<usize> {
+    if !fingerprint_dir.exists() {
+        return Ok(0);
+    }
+
+    let entries = fs::read_dir(fingerprint_dir).map_err(|source| HoldError::IoError {
+        path: fingerprint_dir.to_path_buf(),
+        source,
+    })?;
+
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry.map_err(|source| HoldError::IoError {
+            path: fingerprint_dir.to_path_buf(),
+            source,
+        })?;
+        let path = entry.path();
+
+        if path.is_dir()
+            && let Some((name, _hash)) = parse_crate_artifact_name(&path)
+            && packages.contains(&name)
+        {
+            fs::remove_dir_all(&path).map_err(|source| HoldError::IoError {
+                path: path.clone(),
+                source,
+            })?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "name": "core-lib",
+                "manifest_path": "/ws/core/Cargo.toml",
+                "dependencies": []
+            },
+            {
+                "name": "macros",
+                "manifest_path": "/ws/macros/Cargo.toml",
+                "dependencies": [{ "name": "core-lib", "path": "/ws/core" }]
+            },
+            {
+                "name": "app",
+                "manifest_path": "/ws/app/Cargo.toml",
+                "dependencies": [
+                    { "name": "macros", "path": "/ws/macros" },
+                    { "name": "serde" }
+                ]
+            },
+            {
+                "name": "tool",
+                "manifest_path": "/ws/tool/Cargo.toml",
+                "dependencies": []
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_workspace_members() {
+        let members = parse_workspace_members(METADATA.as_bytes()).unwrap();
+        assert_eq!(members.len(), 4);
+        assert_eq!(members[2].name, "app");
+        assert_eq!(members[2].dir, PathBuf::from("/ws/app"));
+        assert_eq!(members[2].path_deps, vec![PathBuf::from("/ws/macros")]);
+    }
+
+    #[test]
+    fn test_affected_members_includes_transitive_dependents() {
+        let members = parse_workspace_members(METADATA.as_bytes()).unwrap();
+
+        let affected = affected_members(&members, &[PathBuf::from("/ws/core/src/lib.rs")]);
+        let expected: BTreeSet<String> = ["app", "core-lib", "macros"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(affected, expected);
+
+        let affected = affected_members(&members, &[PathBuf::from("/ws/tool/src/main.rs")]);
+        assert_eq!(affected.into_iter().collect::<Vec<_>>(), vec!["tool"]);
+
+        let affected = affected_members(&members, &[PathBuf::from("/elsewhere/README.md")]);
+        assert!(affected.is_empty());
+    }
+
+    #[test]
+    fn test_remove_fingerprints_only_matches_named_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        let fingerprint_dir = temp_dir.path().join(".fingerprint");
+        for name in [
+            "core-lib-0123456789abcdef",
+            "core-lib-fedcba9876543210",
+            "core-lib-extra-0123456789abcdef",
+            "app-0123456789abcdef",
+        ] {
+            fs::create_dir_all(fingerprint_dir.join(name)).unwrap();
+        }
+
+        let packages = BTreeSet::from(["core-lib".to_string()]);
+        let removed = remove_fingerprints(&fingerprint_dir, &packages).unwrap();
+
+        assert_eq!(removed, 2);
+        assert!(
+            fingerprint_dir
+                .join("core-lib-extra-0123456789abcdef")
+                .exists()
+        );
+        assert!(fingerprint_dir.join("app-0123456789abcdef").exists());
+    }
+}
@@ -1,9 +1,10 @@
 //! Tests for cargo home directory cleanup functionality
 
 use std::fs;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use cargo_hold::gc::config::Gc;
+use cargo_hold::gc::{GcArea, GlobalCacheMode, Runner};
 
 use crate::common::TempHomeGuard;
 
@@ -56,8 +57,14 @@ fn test_clean_cargo_registry_preserves_credentials_toml() {
     let home = TempHomeGuard::new();
     let cargo_home = home.cargo_home();
 
-    let credentials = cargo_home.join("credentials.toml");
-    fs::write(&credentials, "[registry]\ntoken = \"do-not-delete\"\n").unwrap();
+    // Both the current and the legacy name, untouched for longer than any
+    // threshold
+    let old_time = SystemTime::now() - Duration::from_secs(400 * 24 * 60 * 60);
+    let credentials = ["credentials.toml", "credentials"].map(|name| cargo_home.join(name));
+    for path in &credentials {
+        fs::write(path, "[registry]\ntoken = \"do-not-delete\"\n").unwrap();
+        filetime::set_file_mtime(path, filetime::FileTime::from_system_time(old_time)).unwrap();
+    }
 
     let config = Gc::builder()
         .target_dir(home.home().join("target"))
@@ -66,14 +73,19 @@ fn test_clean_cargo_registry_preserves_credentials_toml() {
         .age_threshold_days(7)
         .build();
 
-    config
+    let stats = config
         .clean_cargo_registry_with_home(&cargo_home, 0)
         .unwrap();
 
-    assert!(
-        credentials.exists(),
-        "cargo-hold must never delete ~/.cargo/credentials.toml; it stores private registry tokens"
-    );
+    for path in &credentials {
+        assert!(
+            path.exists(),
+            "cargo-hold must never delete {}; it stores private registry tokens",
+            path.display()
+        );
+    }
+    assert_eq!(stats.bytes_freed, 0, "credentials must not count as freed");
+    assert_eq!(stats.files_removed, 0);
 }
 
 #[test]
@@ -175,3 +187,279 @@ fn test_gc_cleans_cargo_home_even_with_missing_target() {
     assert_eq!(stats.crates_cleaned, 0); // no crates in nonexistent dir
     // bytes_freed may be > 0 from cleaning ~/.cargo
 }
+
+#[test]
+fn test_gc_cleans_configured_cargo_home() {
+    let home = TempHomeGuard::new();
+    let old_time = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+    let old_crate = |cargo_home: &std::path::Path| {
+        let cache = cargo_home.join("registry").join("cache").join("index-1");
+        fs::create_dir_all(&cache).unwrap();
+        let crate_file = cache.join("old-1.0.0.crate");
+        fs::write(&crate_file, b"crate").unwrap();
+        filetime::set_file_mtime(&crate_file, filetime::FileTime::from_system_time(old_time))
+            .unwrap();
+        crate_file
+    };
+
+    // A cargo home on a separate cache volume, while $CARGO_HOME points
+    // elsewhere
+    let volume_home = home.home().join("cache-volume").join("cargo");
+    let volume_crate = old_crate(&volume_home);
+    let env_crate = old_crate(&home.cargo_home());
+
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .age_threshold_days(7)
+        .cargo_home(&volume_home)
+        .build();
+    assert_eq!(config.cargo_home().unwrap(), volume_home);
+    config.perform_gc(0).unwrap();
+
+    assert!(!volume_crate.exists(), "Configured cargo home is cleaned");
+    assert!(env_crate.exists(), "$CARGO_HOME is left alone");
+}
+
+#[test]
+fn test_clean_git_checkouts_keeps_locked_revisions() {
+    let home = TempHomeGuard::new();
+    let cargo_home = home.cargo_home();
+    let old_time = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+    let age = |path: &std::path::Path| {
+        filetime::set_file_mtime(path, filetime::FileTime::from_system_time(old_time)).unwrap();
+    };
+
+    let checkouts = cargo_home.join("git").join("checkouts");
+    let pinned = checkouts.join("anchor-4f2d1c0b9a8e7d6c").join("8a5f1e3");
+    let superseded = checkouts.join("anchor-4f2d1c0b9a8e7d6c").join("1111111");
+    let unused = checkouts.join("unused-0011223344556677");
+    let pinned_db = cargo_home
+        .join("git")
+        .join("db")
+        .join("anchor-4f2d1c0b9a8e7d6c");
+    for dir in [&pinned, &superseded, &unused, &pinned_db] {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("file"), b"content").unwrap();
+    }
+    for dir in [&pinned, &superseded, &unused, &pinned_db] {
+        age(dir);
+    }
+    age(pinned.parent().unwrap());
+
+    let lockfile = home.home().join("Cargo.lock");
+    fs::write(
+        &lockfile,
+        "[[package]]\nname = \"anchor-lang\"\nversion = \"0.30.1\"\nsource = \
+         \"git+https://github.com/coral-xyz/anchor?branch=master#8a5f1e3b9c0d2e4f6a7b8c9d0e1f2a3b4c5d6e7f\"\n",
+    )
+    .unwrap();
+
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .age_threshold_days(7)
+        .lockfile(&lockfile)
+        .build();
+    let stats = config
+        .clean_cargo_registry_with_home(&cargo_home, 0)
+        .unwrap();
+
+    assert!(pinned.exists(), "Locked revision must survive the age rule");
+    assert!(
+        pinned_db.exists(),
+        "Database of a locked repository is kept"
+    );
+    assert!(!superseded.exists(), "Old unlocked revision is removed");
+    assert!(!unused.exists(), "Old unreferenced repository is removed");
+    assert_eq!(stats.dirs_removed, 2);
+}
+
+#[test]
+fn test_registry_cache_and_src_stay_in_sync() {
+    let home = TempHomeGuard::new();
+    let cargo_home = home.cargo_home();
+    let cache = cargo_home
+        .join("registry")
+        .join("cache")
+        .join("index.crates.io-1cd66030");
+    let src = cargo_home
+        .join("registry")
+        .join("src")
+        .join("index.crates.io-1cd66030");
+    let old_time = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+
+    let package = |name: &str, crate_old: Option<bool>, src_old: Option<bool>| {
+        let crate_file = cache.join(format!("{name}-1.0.0.crate"));
+        let src_dir = src.join(format!("{name}-1.0.0"));
+        if let Some(old) = crate_old {
+            fs::create_dir_all(&cache).unwrap();
+            fs::write(&crate_file, b"crate").unwrap();
+            if old {
+                filetime::set_file_mtime(
+                    &crate_file,
+                    filetime::FileTime::from_system_time(old_time),
+                )
+                .unwrap();
+            }
+        }
+        if let Some(old) = src_old {
+            fs::create_dir_all(&src_dir).unwrap();
+            fs::write(src_dir.join("lib.rs"), b"// source").unwrap();
+            if old {
+                filetime::set_file_mtime(&src_dir, filetime::FileTime::from_system_time(old_time))
+                    .unwrap();
+            }
+        }
+        (crate_file, src_dir)
+    };
+
+    let stale = package("stale", Some(true), Some(true));
+    let recently_downloaded = package("downloaded", Some(false), Some(true));
+    let recently_extracted = package("extracted", Some(true), Some(false));
+    let orphaned_src = package("orphan", None, Some(true));
+
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .age_threshold_days(7)
+        .build();
+    let stats = config
+        .clean_cargo_registry_with_home(&cargo_home, 0)
+        .unwrap();
+
+    assert!(!stale.0.exists() && !stale.1.exists());
+    assert!(recently_downloaded.0.exists() && recently_downloaded.1.exists());
+    assert!(recently_extracted.0.exists() && recently_extracted.1.exists());
+    assert!(!orphaned_src.1.exists());
+    assert_eq!(stats.files_removed, 1);
+    assert_eq!(stats.dirs_removed, 2);
+}
+
+/// Create a `.global-cache` database recording last uses of registry crates
+/// in `index`, like cargo 1.78+ does
+fn global_cache_db(cargo_home: &std::path::Path, index: &str, crates: &[(&str, SystemTime)]) {
+    let conn = rusqlite::Connection::open(cargo_home.join(".global-cache")).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE registry_index (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT \
+         NULL, timestamp INTEGER NOT NULL);
+         CREATE TABLE registry_crate (registry_id INTEGER NOT NULL, name TEXT NOT NULL, size \
+         INTEGER NOT NULL, timestamp INTEGER NOT NULL, PRIMARY KEY (registry_id, name));
+         CREATE TABLE registry_src (registry_id INTEGER NOT NULL, name TEXT NOT NULL, size \
+         INTEGER, timestamp INTEGER NOT NULL, PRIMARY KEY (registry_id, name));
+         CREATE TABLE git_db (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, \
+         timestamp INTEGER NOT NULL);
+         CREATE TABLE git_checkout (git_id INTEGER NOT NULL, name TEXT NOT NULL, size INTEGER, \
+         timestamp INTEGER NOT NULL, PRIMARY KEY (git_id, name));",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO registry_index (id, name, timestamp) VALUES (1, ?1, 0)",
+        [index],
+    )
+    .unwrap();
+    for (name, last_use) in crates {
+        let timestamp = last_use.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        conn.execute(
+            "INSERT INTO registry_crate VALUES (1, ?1, 5, ?2)",
+            rusqlite::params![name, timestamp],
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_registry_cleanup_follows_global_cache_db() {
+    let home = TempHomeGuard::new();
+    let cargo_home = home.cargo_home();
+    let index = "index.crates.io-1cd66030";
+    let cache = cargo_home.join("registry").join("cache").join(index);
+    fs::create_dir_all(&cache).unwrap();
+
+    // Restored caches reset modification times, so cargo's record of the
+    // last use is what counts
+    let old_time = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+    let unused = cache.join("unused-1.0.0.crate");
+    let used = cache.join("used-1.0.0.crate");
+    let untracked = cache.join("untracked-1.0.0.crate");
+    for file in [&unused, &used, &untracked] {
+        fs::write(file, b"crate").unwrap();
+    }
+    filetime::set_file_mtime(&used, filetime::FileTime::from_system_time(old_time)).unwrap();
+    global_cache_db(
+        &cargo_home,
+        index,
+        &[
+            ("unused-1.0.0.crate", old_time),
+            ("used-1.0.0.crate", SystemTime::now()),
+        ],
+    );
+
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .age_threshold_days(7)
+        .build();
+    let stats = config
+        .clean_cargo_registry_with_home(&cargo_home, 0)
+        .unwrap();
+
+    assert!(
+        !unused.exists(),
+        "Crate cargo has not used recently is removed"
+    );
+    assert!(used.exists(), "Crate cargo used recently is kept");
+    assert!(
+        untracked.exists(),
+        "Untracked crate falls back to its mtime"
+    );
+    assert_eq!(stats.files_removed, 1);
+
+    let conn = rusqlite::Connection::open(cargo_home.join(".global-cache")).unwrap();
+    let names: Vec<String> = conn
+        .prepare("SELECT name FROM registry_crate")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, ["used-1.0.0.crate"], "Removed crate is forgotten");
+
+    // With the database ignored, modification times decide again
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .age_threshold_days(7)
+        .global_cache_mode(GlobalCacheMode::Off)
+        .build();
+    config
+        .clean_cargo_registry_with_home(&cargo_home, 0)
+        .unwrap();
+    assert!(!used.exists());
+    assert!(untracked.exists());
+}
+
+#[test]
+fn test_ephemeral_runner_skips_cargo_bin_cleanup() {
+    let home = TempHomeGuard::new();
+    let cargo_home = home.cargo_home();
+    let old_binary = cargo_home.join("bin").join("old-tool");
+    fs::create_dir_all(old_binary.parent().unwrap()).unwrap();
+    fs::write(&old_binary, b"old").unwrap();
+    let old_time = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+    filetime::set_file_mtime(&old_binary, filetime::FileTime::from_system_time(old_time)).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .cargo_home(&cargo_home)
+        .runner(Runner::GitHubHosted)
+        .build();
+    assert_eq!(config.runner(), Some(Runner::GitHubHosted));
+    config.perform_gc(0).unwrap();
+    assert!(old_binary.exists(), "Binaries are not cleaned by default");
+
+    // An explicit bin threshold still applies
+    let config = Gc::builder()
+        .target_dir(home.home().join("target"))
+        .cargo_home(&cargo_home)
+        .runner(Runner::GitHubHosted)
+        .area_age_threshold(GcArea::Bin, Duration::from_secs(24 * 60 * 60))
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(!old_binary.exists());
+}
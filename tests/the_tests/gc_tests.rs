@@ -2,7 +2,9 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
+use cargo_hold::context::HoldContext;
 use cargo_hold::gc::config::Gc;
+use cargo_hold::gc::{self, GcArea, GcPhase, PathFilter, PinnedCrates, RemovalReason, SizeMode};
 use tempfile::TempDir;
 
 use crate::common::TempHomeGuard;
@@ -18,6 +20,9 @@ fn test_gc_config_builder() {
     assert_eq!(config.age_threshold_days(), 7);
     assert!(config.preserve_binaries().is_empty());
     assert_eq!(config.previous_build_mtime_nanos(), None);
+    assert_eq!(config.preserve_buffer(), Duration::from_secs(5 * 60));
+    assert_eq!(config.preserve_window(), None);
+    assert_eq!(config.preserve_last_builds(), 1);
 
     // Test builder with all options
     let config = Gc::builder()
@@ -29,6 +34,10 @@ fn test_gc_config_builder() {
         .preserve_binary("cargo-hold")
         .preserve_binary("cargo-test")
         .previous_build_mtime_nanos(123456789)
+        .preserve_buffer(Duration::from_secs(2 * 60 * 60))
+        .preserve_window(Duration::from_secs(3 * 24 * 60 * 60))
+        .recent_build_mtime_nanos(vec![12345678, 123456789])
+        .preserve_last_builds(2)
         .build();
 
     assert_eq!(config.target_dir(), Path::new("/custom/target"));
@@ -38,6 +47,84 @@ fn test_gc_config_builder() {
     assert_eq!(config.age_threshold_days(), 14);
     assert_eq!(config.preserve_binaries(), &["cargo-hold", "cargo-test"]);
     assert_eq!(config.previous_build_mtime_nanos(), Some(123456789));
+    assert_eq!(config.recent_build_mtime_nanos(), &[12345678, 123456789]);
+    assert_eq!(config.preserve_last_builds(), 2);
+    assert_eq!(config.preserve_buffer(), Duration::from_secs(2 * 60 * 60));
+    assert_eq!(
+        config.preserve_window(),
+        Some(Duration::from_secs(3 * 24 * 60 * 60))
+    );
+}
+
+#[test]
+fn test_gc_area_age_thresholds() {
+    let config = Gc::builder().build();
+    assert_eq!(
+        config.age_threshold(),
+        Duration::from_secs(7 * 24 * 60 * 60)
+    );
+    assert_eq!(
+        config.age_threshold_for(GcArea::Incremental),
+        Duration::ZERO
+    );
+    assert_eq!(
+        config.age_threshold_for(GcArea::Git),
+        Duration::from_secs(30 * 24 * 60 * 60)
+    );
+
+    let config = Gc::builder()
+        .age_threshold(Duration::from_secs(36 * 60 * 60))
+        .area_age_threshold(
+            GcArea::RegistryCache,
+            Duration::from_secs(90 * 24 * 60 * 60),
+        )
+        .build();
+    assert_eq!(config.age_threshold_days(), 1);
+    assert_eq!(
+        config.age_threshold_for(GcArea::Deps),
+        Duration::from_secs(36 * 60 * 60)
+    );
+    assert_eq!(
+        config.age_threshold_for(GcArea::RegistryCache),
+        Duration::from_secs(90 * 24 * 60 * 60)
+    );
+}
+
+#[test]
+fn test_gc_hour_threshold_and_incremental_age() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+
+    // One day old is past a 12 hour threshold, but not a 7 day one
+    create_crate_artifacts(&debug_dir, "stale", "1234567890abcdef", 16, 1);
+    create_crate_artifacts(&debug_dir, "fresh", "fedcba0987654321", 16, 0);
+
+    let old_session = debug_dir.join("incremental").join("stale-3abc");
+    let new_session = debug_dir.join("incremental").join("fresh-3def");
+    create_file_with_mtime(&old_session.join("s-1").join("query-cache.bin"), 64, 3).unwrap();
+    create_file_with_mtime(&new_session.join("s-2").join("query-cache.bin"), 64, 0).unwrap();
+    let three_days_ago = SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60);
+    filetime::set_file_mtime(
+        &old_session,
+        filetime::FileTime::from_system_time(three_days_ago),
+    )
+    .unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold(Duration::from_secs(12 * 60 * 60))
+        .area_age_threshold(GcArea::Incremental, Duration::from_secs(2 * 24 * 60 * 60))
+        .quiet(true)
+        .build();
+    config.perform_gc(0).unwrap();
+
+    let deps_dir = debug_dir.join("deps");
+    assert!(!deps_dir.join("libstale-1234567890abcdef.rlib").exists());
+    assert!(deps_dir.join("libfresh-fedcba0987654321.rlib").exists());
+    assert!(!old_session.exists());
+    assert!(new_session.exists());
 }
 
 /// Helper to create a file with specific size and modification time
@@ -353,6 +440,198 @@ fn test_gc_dry_run() {
     );
 }
 
+#[test]
+fn test_gc_dry_run_records_removed_paths() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "old-crate", "abcdef1234567890", 4, 10);
+    create_crate_artifacts(&debug_dir, "new-crate", "0123456789abcdef", 4, 0);
+    fs::create_dir_all(target_dir.join("doc")).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .dry_run(true)
+        .age_threshold_days(7)
+        .record_removals(true)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+
+    let paths: Vec<&Path> = stats
+        .removed_paths
+        .iter()
+        .map(|removed| removed.path.as_path())
+        .collect();
+    let rlib = debug_dir
+        .join("deps")
+        .join("libold-crate-abcdef1234567890.rlib");
+    assert!(paths.contains(&rlib.as_path()));
+    assert!(paths.contains(&target_dir.join("doc").as_path()));
+    assert!(paths.contains(&debug_dir.join("incremental").as_path()));
+    assert!(
+        !paths
+            .iter()
+            .any(|path| path.to_string_lossy().contains("new-crate")),
+        "Fresh artifacts are not listed"
+    );
+    assert!(paths.is_sorted(), "Removed paths are listed in order");
+
+    let rlib_entry = stats
+        .removed_paths
+        .iter()
+        .find(|removed| removed.path == rlib)
+        .unwrap();
+    assert!(rlib_entry.size > 0);
+    assert_eq!(rlib_entry.phase, GcPhase::TargetDir);
+    assert_eq!(rlib_entry.reason, RemovalReason::Age);
+    let doc_entry = stats
+        .removed_paths
+        .iter()
+        .find(|removed| removed.path == target_dir.join("doc"))
+        .unwrap();
+    assert_eq!(doc_entry.reason, RemovalReason::Disposable);
+    assert!(rlib.exists(), "Dry run leaves files in place");
+
+    // Without recording, no paths are collected
+    let config = Gc::builder().target_dir(target_dir).dry_run(true).build();
+    assert!(config.perform_gc(0).unwrap().removed_paths.is_empty());
+}
+
+#[test]
+fn test_gc_compress_cold_compresses_before_removing() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "old-crate", "abcdef1234567890", 64, 10);
+    let rlib = debug_dir
+        .join("deps")
+        .join("libold-crate-abcdef1234567890.rlib");
+    let compressed = debug_dir
+        .join("deps")
+        .join("libold-crate-abcdef1234567890.rlib.zst");
+    let mtime = fs::metadata(&rlib).unwrap().modified().unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(7)
+        .compress_cold(true)
+        .build();
+
+    // First selected for eviction: compressed in place (the deps and build
+    // script groups of the crate)
+    let stats = config.perform_gc(0).unwrap();
+    assert_eq!(stats.crates_compressed, 2);
+    assert_eq!(stats.crates_cleaned, 0);
+    assert!(stats.bytes_freed > 0);
+    assert!(!rlib.exists());
+    assert!(compressed.exists());
+    assert!(target_dir.join("cargo-hold.cold.json").exists());
+
+    // Thawing restores the original contents and modification time
+    cargo_hold::commands::thaw::thaw(&HoldContext::default(), &target_dir, 0, true).unwrap();
+    assert!(!compressed.exists());
+    assert_eq!(fs::metadata(&rlib).unwrap().len(), 64 * 1024);
+    assert_eq!(fs::metadata(&rlib).unwrap().modified().unwrap(), mtime);
+    assert!(!target_dir.join("cargo-hold.cold.json").exists());
+
+    // Selected again while compressed: removed
+    config.perform_gc(0).unwrap();
+    let stats = config.perform_gc(0).unwrap();
+    assert_eq!(stats.crates_compressed, 0);
+    assert_eq!(stats.crates_cleaned, 2);
+    assert!(!compressed.exists());
+    assert!(!target_dir.join("cargo-hold.cold.json").exists());
+}
+
+#[test]
+fn test_gc_never_evicts_pinned_crates() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "rocksdb-sys", "abcdef1234567890", 64, 30);
+    create_crate_artifacts(&debug_dir, "old-crate", "1234567890abcdef", 64, 30);
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(7)
+        .pinned_crates(PinnedCrates::new(&["rocksdb_sys".to_string()]).unwrap())
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+
+    assert_eq!(stats.crates_cleaned, 2);
+    assert!(
+        debug_dir
+            .join("deps")
+            .join("librocksdb-sys-abcdef1234567890.rlib")
+            .exists()
+    );
+    assert!(
+        !debug_dir
+            .join("deps")
+            .join("libold-crate-1234567890abcdef.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_public_scanning_api_groups_artifacts_by_crate() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "serde", "abcdef1234567890", 16, 3);
+
+    let profiles = gc::find_profile_directories(&target_dir).unwrap();
+    assert_eq!(profiles, [debug_dir.as_path()]);
+
+    let artifacts = gc::collect_crate_artifacts(&debug_dir, SizeMode::Apparent).unwrap();
+    let groups: Vec<_> = artifacts
+        .iter()
+        .map(|artifact| (artifact.name(), artifact.hash()))
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            ("libserde", "abcdef1234567890"),
+            ("serde", "abcdef1234567890")
+        ]
+    );
+
+    let lib = &artifacts[0];
+    assert_eq!(lib.total_size(), 128 + 256 + 16 * 1024 + 1024);
+    assert_eq!(
+        lib.total_size(),
+        lib.artifacts().iter().map(|info| info.size()).sum::<u64>()
+    );
+    assert!(
+        lib.artifacts()
+            .iter()
+            .any(|info| info.path() == debug_dir.join("deps/libserde-abcdef1234567890.rlib"))
+    );
+    let age = SystemTime::now()
+        .duration_since(lib.newest_mtime())
+        .unwrap();
+    assert!(age > Duration::from_secs(2 * 24 * 60 * 60));
+
+    assert_eq!(
+        gc::parse_crate_artifact_name(Path::new("libserde-abcdef1234567890.rlib")),
+        Some(("libserde".to_string(), "abcdef1234567890".to_string()))
+    );
+    let usage = gc::calculate_disk_usage(&target_dir).unwrap();
+    assert!(usage.apparent >= lib.total_size() + artifacts[1].total_size());
+    assert_eq!(
+        gc::calculate_directory_size(&target_dir, SizeMode::Apparent).unwrap(),
+        usage.apparent
+    );
+    assert_eq!(gc::parse_size("16K").unwrap(), 16 * 1024);
+    assert_eq!(gc::format_size(16 * 1024), "16.0 KiB");
+}
+
 #[test]
 fn test_gc_incremental_cleanup() {
     let _home = TempHomeGuard::new();
@@ -416,6 +695,50 @@ fn test_gc_misc_directories() {
     assert!(!tmp_dir.exists());
 }
 
+#[test]
+fn test_gc_keeps_what_the_local_profile_keeps() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let incremental_dir = target_dir.join("debug").join("incremental");
+    create_file_with_mtime(
+        &incremental_dir.join("myproject-1234").join("dep-graph.bin"),
+        1024,
+        0,
+    )
+    .unwrap();
+    let doc_dir = target_dir.join("doc");
+    create_file_with_mtime(&doc_dir.join("index.html"), 1024, 0).unwrap();
+    let tmp_dir = target_dir.join("tmp");
+    create_file_with_mtime(&tmp_dir.join("tempfile"), 1024, 0).unwrap();
+    let cargo_home = temp_dir.path().join("cargo-home");
+    let crate_file = cargo_home
+        .join("registry")
+        .join("cache")
+        .join("index-1")
+        .join("old-1.0.0.crate");
+    create_file_with_mtime(&crate_file, 1024, 400).unwrap();
+
+    let mut config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .cargo_home(&cargo_home)
+        .keep_cargo_home(true)
+        .keep_incremental(true)
+        .keep_docs(true)
+        .build();
+    let preview = config.preview().unwrap();
+    assert!(preview.bytes_freed >= 1024, "tmp is still freeable");
+    assert!(tmp_dir.exists(), "The preview removes nothing");
+    assert!(!config.dry_run() && !config.quiet());
+
+    config.perform_gc(0).unwrap();
+    assert!(incremental_dir.exists());
+    assert!(doc_dir.exists());
+    assert!(crate_file.exists());
+    assert!(!tmp_dir.exists());
+}
+
 #[test]
 fn test_gc_preserve_binaries() {
     let _home = TempHomeGuard::new();
@@ -632,3 +955,518 @@ fn test_gc_with_custom_preserve_binaries() {
     // cleanup with custom preserve_binaries list. Skipping for now as it
     // requires complex setup.
 }
+
+#[test]
+fn test_gc_only_cleans_matching_subtrees() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "old-crate", "1234567890abcdef", 64, 10);
+    create_file_with_mtime(&debug_dir.join("incremental").join("data"), 1024, 10).unwrap();
+
+    let filter = PathFilter::new(&target_dir, &["target/**/build".to_string()]).unwrap();
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(7)
+        .path_filter(filter)
+        .build();
+
+    let stats = config.perform_gc(0).unwrap();
+
+    assert!(
+        !debug_dir
+            .join("build")
+            .join("old-crate-1234567890abcdef")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join("deps")
+            .join("libold-crate-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join(".fingerprint")
+            .join("libold-crate-1234567890abcdef")
+            .exists()
+    );
+    assert!(debug_dir.join("incremental").join("data").exists());
+
+    // Sizes only cover the build directories
+    assert!(stats.initial_usage.apparent >= 2048);
+    assert!(stats.initial_usage.apparent < 64 * 1024);
+    assert_eq!(stats.final_usage.apparent, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gc_counts_only_removed_paths_as_freed() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "stuck", "1234567890abcdef", 16, 10);
+
+    // The build script directory of the crate cannot be emptied
+    let build_dir = debug_dir.join("build").join("stuck-1234567890abcdef");
+    fs::set_permissions(&build_dir, fs::Permissions::from_mode(0o555)).unwrap();
+    // Permissions do not bind root
+    let enforced = fs::write(build_dir.join("probe"), "").is_err();
+    let _ = fs::remove_file(build_dir.join("probe"));
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .size_mode(SizeMode::Apparent)
+        .keep_cargo_home(true)
+        .quiet(true)
+        .build();
+    let stats = config.perform_gc(0);
+    // Gone when permissions were not enforced
+    let _ = fs::set_permissions(&build_dir, fs::Permissions::from_mode(0o755));
+    let stats = stats.unwrap();
+    if !enforced {
+        return;
+    }
+
+    // The directory and the file in it
+    assert_eq!(stats.errors, 2);
+    // Only the library's artifact group is gone
+    assert_eq!(stats.crates_cleaned, 1);
+    assert!(build_dir.join("out").exists());
+    assert!(
+        !debug_dir
+            .join("deps")
+            .join("libstuck-1234567890abcdef.rlib")
+            .exists()
+    );
+    // The library and its dep-info file, not the build script output left
+    assert_eq!(stats.bytes_freed, 17 * 1024);
+}
+
+#[test]
+fn test_gc_stops_when_out_of_time() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "old-crate", "1234567890abcdef", 16, 10);
+
+    // A budget spent before anything is removed leaves it all
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .max_duration(Duration::ZERO)
+        .keep_cargo_home(true)
+        .quiet(true)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(stats.out_of_time);
+    assert_eq!(stats.bytes_freed, 0);
+    assert!(
+        debug_dir
+            .join("deps")
+            .join("libold-crate-1234567890abcdef.rlib")
+            .exists()
+    );
+
+    // Within the budget, the collection runs as usual
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .max_duration(Duration::from_secs(600))
+        .keep_cargo_home(true)
+        .quiet(true)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(!stats.out_of_time);
+    assert_eq!(stats.remaining_crates, 0);
+    assert!(
+        !debug_dir
+            .join("deps")
+            .join("libold-crate-1234567890abcdef.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_gc_dep_info_evicts_stale_and_keeps_unchanged_crates() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let workspace = temp_dir.path();
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    create_file_with_mtime(&workspace.join("src/lib.rs"), 16, 0).unwrap();
+    create_file_with_mtime(&workspace.join("src/main.rs"), 16, 20).unwrap();
+
+    // stale-crate was built before lib.rs changed, kept-crate after main.rs
+    // last did, and old-crate has no dep-info to go by
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "stale-crate", "1111111111111111", 4, 1);
+    create_crate_artifacts(&debug_dir, "kept-crate", "2222222222222222", 4, 10);
+    create_crate_artifacts(&debug_dir, "old-crate", "3333333333333333", 4, 10);
+    for (name, hash, source) in [
+        ("stale-crate", "1111111111111111", "src/lib.rs"),
+        ("kept-crate", "2222222222222222", "src/main.rs"),
+    ] {
+        let dep_info = debug_dir.join(format!("deps/lib{name}-{hash}.d"));
+        fs::write(&dep_info, format!("{}: {source}\n", dep_info.display())).unwrap();
+    }
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(7)
+        .dep_info(true)
+        .record_removals(true)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+
+    let stale_rlib = debug_dir.join("deps/libstale-crate-1111111111111111.rlib");
+    let stale_entry = stats
+        .removed_paths
+        .iter()
+        .find(|removed| removed.path == stale_rlib)
+        .unwrap();
+    assert_eq!(stale_entry.reason, RemovalReason::Stale);
+    assert!(!stale_rlib.exists());
+    assert!(
+        debug_dir
+            .join("deps/libkept-crate-2222222222222222.rlib")
+            .exists(),
+        "Crates whose sources are unchanged outlive the age threshold"
+    );
+    assert!(
+        !debug_dir
+            .join("deps/libold-crate-3333333333333333.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_gc_cleans_a_separate_build_dir_in_the_new_layout() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let build_dir = temp_dir.path().join("build-dir");
+
+    // cargo's new layout keeps every file of a unit in build/<package>/<hash>
+    let unit = |package: &str, hash: &str, age_days: u32| {
+        let unit_dir = build_dir.join("debug/build").join(package).join(hash);
+        create_file_with_mtime(&unit_dir.join("fingerprint/dep-lib"), 256, age_days).unwrap();
+        create_file_with_mtime(
+            &unit_dir.join(format!("out/lib{package}.rlib")),
+            64 * 1024,
+            age_days,
+        )
+        .unwrap();
+        unit_dir
+    };
+    let old = unit("old-crate", "1234567890abcdef", 10);
+    let fresh = unit("fresh-crate", "fedcba0987654321", 1);
+
+    let profile_dir = build_dir.join("debug");
+    let artifacts = gc::collect_crate_artifacts(&profile_dir, SizeMode::Apparent).unwrap();
+    assert_eq!(
+        artifacts
+            .iter()
+            .map(|artifact| (artifact.name(), artifact.hash()))
+            .collect::<Vec<_>>(),
+        [
+            ("fresh-crate", "fedcba0987654321"),
+            ("old-crate", "1234567890abcdef")
+        ]
+    );
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .build_dir(build_dir.clone())
+        .age_threshold_days(7)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(!old.exists());
+    assert!(fresh.exists());
+    assert_eq!(stats.crates_cleaned, 1);
+    assert!(stats.build_dir_initial_size > stats.build_dir_final_size);
+
+    // The build directory's own cap applies to it, not the target's
+    let config = Gc::builder()
+        .target_dir(target_dir)
+        .max_target_size(1024 * 1024 * 1024)
+        .build_dir(build_dir)
+        .max_build_dir_size(1024)
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(!fresh.exists());
+}
+
+#[test]
+fn test_gc_cleans_custom_profiles_nested_at_any_depth() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    // A tool's target directory whose own scratch `build` directory makes it
+    // look like a profile, hiding the custom profile below its triple
+    let tool_dir = target_dir.join("llvm-cov-target");
+    fs::create_dir_all(tool_dir.join("build")).unwrap();
+    let coverage_dir = tool_dir.join("x86_64-unknown-linux-gnu").join("coverage");
+    create_crate_artifacts(&coverage_dir, "old-crate", "1234567890abcdef", 16, 10);
+    // A custom profile with nothing but incremental data
+    let lto_dir = target_dir.join("aarch64-apple-darwin").join("release-lto");
+    let session = lto_dir.join("incremental").join("myproject-1234");
+    create_file_with_mtime(&session.join("s-1234-working.bin"), 1024, 10).unwrap();
+
+    let profiles = gc::find_profile_directories(&target_dir).unwrap();
+    assert!(!profiles.contains(&coverage_dir));
+    assert!(!profiles.contains(&lto_dir));
+
+    let names = ["debug", "release", "coverage", "release-lto"].map(String::from);
+    let profiles = gc::find_named_profile_directories(&target_dir, &names).unwrap();
+    assert_eq!(
+        profiles,
+        [
+            target_dir.join("aarch64-apple-darwin/release-lto"),
+            target_dir.join("debug"),
+            tool_dir.clone(),
+            coverage_dir.clone(),
+        ]
+    );
+
+    let config = Gc::builder()
+        .target_dir(target_dir)
+        .age_threshold_days(7)
+        .profile_names(names.to_vec())
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(
+        !coverage_dir
+            .join("deps/libold-crate-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(!session.exists());
+}
+
+#[test]
+fn test_gc_cleans_coverage_data_under_its_own_policy() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "serde", "abcdef1234567890", 16, 3);
+
+    let old_profraw = target_dir.join("default_1234_0.profraw");
+    create_file_with_mtime(&old_profraw, 64 * 1024, 5).unwrap();
+    let profdata = target_dir.join("coverage/merged.profdata");
+    create_file_with_mtime(&profdata, 32 * 1024, 2).unwrap();
+    let llvm_cov = target_dir.join("llvm-cov-target");
+    create_file_with_mtime(&llvm_cov.join("ws-5678-1.profraw"), 16 * 1024, 1).unwrap();
+    let old_build = llvm_cov.join("x86_64-unknown-linux-gnu");
+    create_crate_artifacts(
+        &old_build.join("debug"),
+        "old-crate",
+        "1234567890abcdef",
+        16,
+        10,
+    );
+
+    // Coverage data has its own age threshold
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .area_age_threshold(GcArea::Coverage, Duration::from_secs(4 * 24 * 60 * 60))
+        .record_removals(true)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(!old_profraw.exists());
+    assert!(!old_build.exists());
+    assert!(profdata.exists());
+    assert!(llvm_cov.join("ws-5678-1.profraw").exists());
+    assert!(
+        stats
+            .removed_paths
+            .iter()
+            .any(|removed| removed.path == old_build && removed.reason == RemovalReason::Age)
+    );
+    // The crates of the regular profiles follow the general threshold
+    assert!(
+        debug_dir
+            .join("deps/libserde-abcdef1234567890.rlib")
+            .exists()
+    );
+
+    // And its own size cap, evicting the oldest first
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .max_coverage_size(20 * 1024)
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(!profdata.exists());
+    assert!(llvm_cov.join("ws-5678-1.profraw").exists());
+    assert!(
+        debug_dir
+            .join("deps/libserde-abcdef1234567890.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_gc_cleans_test_tool_outputs_under_their_own_thresholds() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let old_run = target_dir.join("nextest/ci/junit.xml");
+    create_file_with_mtime(&old_run, 4096, 3).unwrap();
+    let new_run = target_dir.join("nextest/default/junit.xml");
+    create_file_with_mtime(&new_run, 4096, 0).unwrap();
+
+    let trybuild = target_dir.join("tests/trybuild");
+    let old_project = trybuild.join("my-macros");
+    create_file_with_mtime(&old_project.join("Cargo.toml"), 512, 3).unwrap();
+    let trybuild_profile = trybuild.join("debug");
+    create_crate_artifacts(&trybuild_profile, "my-macros", "1234567890abcdef", 16, 3);
+
+    let snapshot = target_dir.join("snapshots/parse__output.snap.new");
+    create_file_with_mtime(&snapshot, 256, 3).unwrap();
+    let pending = target_dir.join("debug/build/.pending-snap");
+    create_file_with_mtime(&pending, 256, 1).unwrap();
+
+    // Nothing is old enough for the general threshold
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .area_age_threshold(GcArea::Snapshots, Duration::from_secs(2 * 24 * 60 * 60))
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(old_run.exists());
+    assert!(old_project.exists());
+    assert!(!snapshot.exists());
+    assert!(pending.exists());
+
+    let day = Duration::from_secs(24 * 60 * 60);
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .area_age_threshold(GcArea::Nextest, 2 * day)
+        .area_age_threshold(GcArea::Trybuild, 2 * day)
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(!old_run.exists());
+    assert!(new_run.exists());
+    assert!(!old_project.exists());
+    assert!(!trybuild_profile.exists());
+    assert!(pending.exists());
+}
+
+#[test]
+fn test_gc_keeps_or_evicts_web_build_outputs() {
+    let _home = TempHomeGuard::new();
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+
+    let bundle = target_dir.join("dx/my-app/release/web/public/index.html");
+    create_file_with_mtime(&bundle, 1024, 10).unwrap();
+    let bindings = target_dir.join("wasm-bindgen/debug/my_app_bg.wasm");
+    create_file_with_mtime(&bindings, 64 * 1024, 0).unwrap();
+    let stage = target_dir.join("site/.stage");
+    create_file_with_mtime(&stage.join("index.html"), 1024, 10).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .area_age_threshold(GcArea::Dx, Duration::MAX)
+        .area_age_threshold(GcArea::WasmBindgen, Duration::ZERO)
+        .build();
+    config.perform_gc(0).unwrap();
+    assert!(bundle.exists());
+    assert!(!bindings.exists());
+    assert!(!stage.exists());
+    assert!(target_dir.join("site").exists());
+
+    // Without a policy of its own, a bundle follows the general threshold
+    let config = Gc::builder().target_dir(target_dir.clone()).build();
+    config.perform_gc(0).unwrap();
+    assert!(!target_dir.join("dx/my-app/release").exists());
+}
+
+#[test]
+fn test_gc_in_sandbox_cleans_target_dir_and_cargo_home() {
+    let home = TempHomeGuard::new();
+    let cargo_home = home.cargo_home();
+    let registry_cache = cargo_home.join("registry/cache/index.crates.io-1234");
+    let old_crate = registry_cache.join("old-crate-1.0.0.crate");
+    create_file_with_mtime(&old_crate, 1024, 60).unwrap();
+    let credentials = cargo_home.join("credentials.toml");
+    fs::write(&credentials, "[registry]\n").unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "serde", "abcdef1234567890", 16, 10);
+    create_crate_artifacts(&debug_dir, "tokio", "1234567890abcdef", 16, 1);
+
+    // Auto runs unconfined where Landlock is unavailable, so this holds
+    // either way
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .sandbox(gc::SandboxMode::Auto)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(stats.crates_cleaned > 0);
+    assert!(
+        !debug_dir
+            .join("deps/libserde-abcdef1234567890.rlib")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join("deps/libtokio-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(!old_crate.exists());
+    assert!(credentials.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gc_refuses_removals_through_symlinks() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let outside = temp_dir.path().join("elsewhere");
+    create_crate_artifacts(&outside, "serde", "abcdef1234567890", 16, 10);
+    fs::remove_dir_all(target_dir.join("debug")).unwrap();
+    std::os::unix::fs::symlink(&outside, target_dir.join("debug")).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(1)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert!(stats.refused_removals > 0);
+    assert_eq!(stats.artifacts_removed, 0);
+    assert!(outside.join("deps/libserde-abcdef1234567890.rlib").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gc_removes_symlinked_artifacts_as_links() {
+    let temp_dir = TempDir::new().unwrap();
+    let target_dir = setup_target_dir(&temp_dir);
+    let debug_dir = target_dir.join("debug");
+    create_crate_artifacts(&debug_dir, "serde", "abcdef1234567890", 16, 10);
+    let shared = temp_dir.path().join("shared-cache");
+    fs::create_dir_all(&shared).unwrap();
+    fs::write(shared.join("blob"), vec![0u8; 64 * 1024]).unwrap();
+    let link = debug_dir.join("build/serde-abcdef1234567890/cache");
+    std::os::unix::fs::symlink(&shared, &link).unwrap();
+    let old = filetime::FileTime::from_system_time(
+        SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60),
+    );
+    filetime::set_symlink_file_times(&link, old, old).unwrap();
+
+    let config = Gc::builder()
+        .target_dir(target_dir.clone())
+        .age_threshold_days(1)
+        .size_mode(SizeMode::Apparent)
+        .build();
+    let stats = config.perform_gc(0).unwrap();
+    assert_eq!(stats.refused_removals, 0);
+    assert!(stats.bytes_freed < 64 * 1024);
+    assert!(fs::symlink_metadata(&link).is_err());
+    assert!(shared.join("blob").exists());
+}
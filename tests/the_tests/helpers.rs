@@ -5,7 +5,7 @@ use std::process::Command;
 use std::time::SystemTime;
 
 use assert_fs::TempDir;
-use cargo_hold::cli::{Cli, Commands, GcArgs};
+use cargo_hold::cli::{AnchorArgs, Cli, Commands, GcArgs};
 use cargo_hold::commands::execute_with_dir;
 use cargo_hold::error::Result;
 use miette::{Context, IntoDiagnostic};
@@ -70,6 +70,13 @@ pub fn execute_command(command: Commands, temp_dir: &TempDir, verbose: u8) -> Re
     execute_command_with_dir(command, temp_dir, temp_dir.path(), verbose)
 }
 
+/// Helper to build an anchor command with default options
+pub fn anchor_command() -> Commands {
+    Commands::Anchor {
+        anchor: AnchorArgs::default(),
+    }
+}
+
 /// Helper to execute a command from a specific directory
 pub fn execute_command_with_dir(
     command: Commands,
@@ -212,11 +219,14 @@ fn cargo_executable() -> OsString {
 pub fn run_voyage(temp_dir: &TempDir, verbose: u8) -> Result<()> {
     execute_command(
         Commands::Voyage {
+            anchor: AnchorArgs::default(),
             gc: GcArgs::new(None, vec![]),
             gc_dry_run: false,
             gc_debug: false,
             gc_age_threshold_days: 7,
             gc_auto_max_target_size: true,
+            no_anchor: false,
+            no_gc: false,
         },
         temp_dir,
         verbose,
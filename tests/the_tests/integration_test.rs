@@ -3,7 +3,7 @@ use std::path::Path;
 use std::process::Command;
 use std::time::{Duration, SystemTime};
 
-use cargo_hold::cli::{Cli, Commands, GcArgs};
+use cargo_hold::cli::{AnchorArgs, Cli, Commands, GcArgs};
 use cargo_hold::commands::execute_with_dir;
 
 use super::helpers::*;
@@ -14,7 +14,7 @@ fn test_anchor_command_creates_cache() {
     let metadata_path = temp_dir.path().join("target/cargo-hold.metadata");
 
     // Run sync command
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
 
     // Verify cache was created
     assert!(metadata_path.exists());
@@ -26,7 +26,7 @@ fn test_anchor_command_with_modifications() {
     let main_rs = temp_dir.path().join("src/main.rs");
 
     // First sync
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
 
     // Record original mtime
     let original_mtime = fs::metadata(&main_rs).unwrap().modified().unwrap();
@@ -38,7 +38,7 @@ fn test_anchor_command_with_modifications() {
     fs::write(&main_rs, "fn main() { println!(\"Modified\"); }").unwrap();
 
     // Second sync
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
 
     // Verify mtime was updated
     let new_mtime = fs::metadata(&main_rs).unwrap().modified().unwrap();
@@ -103,7 +103,7 @@ fn test_verbose_output() {
 
     // Capture stderr by running in a thread
     let output = std::panic::catch_unwind(|| {
-        execute_command(Commands::Anchor, &temp_dir, 1).unwrap();
+        execute_command(anchor_command(), &temp_dir, 1).unwrap();
     });
 
     assert!(output.is_ok());
@@ -173,11 +173,11 @@ fn test_idempotent_sync() {
     let lib_rs = temp_dir.path().join("src/lib.rs");
 
     // First sync
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
     let mtime1 = fs::metadata(&lib_rs).unwrap().modified().unwrap();
 
     // Second sync without changes
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
     let mtime2 = fs::metadata(&lib_rs).unwrap().modified().unwrap();
 
     // Timestamps should remain the same for unchanged files
@@ -189,7 +189,7 @@ fn test_new_file_detection() {
     let temp_dir = setup_test_repo();
 
     // First sync
-    execute_command(Commands::Anchor, &temp_dir, 0).unwrap();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
 
     // Add new file
     let new_file = temp_dir.path().join("src/new.rs");
@@ -202,7 +202,7 @@ fn test_new_file_detection() {
     index.write().unwrap();
 
     // Sync again - should detect the new file
-    execute_command(Commands::Anchor, &temp_dir, 1).unwrap();
+    execute_command(anchor_command(), &temp_dir, 1).unwrap();
 }
 
 #[test]
@@ -210,7 +210,7 @@ fn test_not_in_git_repo() {
     let temp_dir = TestWorkspace::new();
 
     // Try to run in non-git directory
-    let result = execute_command(Commands::Anchor, &temp_dir, 0);
+    let result = execute_command(anchor_command(), &temp_dir, 0);
 
     assert!(result.is_err());
     let err_msg = format!("{}", result.unwrap_err());
@@ -237,7 +237,32 @@ fn test_sync_with_symlink() {
     index.write().unwrap();
 
     // Run sync - should handle symlink gracefully
-    execute_command(Commands::Anchor, &temp_dir, 1).unwrap();
+    execute_command(anchor_command(), &temp_dir, 1).unwrap();
+}
+
+#[test]
+fn test_anchor_invalidates_fingerprints_of_modified_members() {
+    let temp_dir = setup_cargo_project();
+    execute_command(anchor_command(), &temp_dir, 0).unwrap();
+
+    let fingerprint_dir = temp_dir.path().join("target/debug/.fingerprint");
+    let own = fingerprint_dir.join("test-project-0123456789abcdef");
+    let foreign = fingerprint_dir.join("serde-0123456789abcdef");
+    fs::create_dir_all(&own).unwrap();
+    fs::create_dir_all(&foreign).unwrap();
+
+    fs::write(temp_dir.path().join("src/lib.rs"), "pub fn changed() {}\n").unwrap();
+
+    let command = Commands::Anchor {
+        anchor: AnchorArgs::new(true),
+    };
+    execute_command(command, &temp_dir, 0).unwrap();
+
+    assert!(
+        !own.exists(),
+        "fingerprint of modified member should be removed"
+    );
+    assert!(foreign.exists(), "unrelated fingerprints must be kept");
 }
 
 #[test]
@@ -254,6 +279,10 @@ fn test_heave_command() {
         debug: false,
         age_threshold_days: 7,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
 
     // Run heave command
@@ -265,11 +294,14 @@ fn test_voyage_command() {
     let temp_dir = setup_test_repo();
 
     let voyage_command = Commands::Voyage {
+        anchor: AnchorArgs::default(),
         gc: GcArgs::new(None, vec![]),
         gc_dry_run: true,
         gc_debug: false,
         gc_age_threshold_days: 7,
         gc_auto_max_target_size: true,
+        no_anchor: false,
+        no_gc: false,
     };
 
     // Run voyage command (anchor + heave)
@@ -287,11 +319,14 @@ fn test_voyage_command_from_subdirectory() {
     fs::create_dir(&subdir).unwrap();
 
     let voyage_command = Commands::Voyage {
+        anchor: AnchorArgs::default(),
         gc: GcArgs::new(None, vec![]),
         gc_dry_run: true,
         gc_debug: false,
         gc_age_threshold_days: 7,
         gc_auto_max_target_size: true,
+        no_anchor: false,
+        no_gc: false,
     };
 
     execute_command_with_dir(voyage_command, &temp_dir, &subdir, 0).unwrap();
@@ -301,6 +336,36 @@ fn test_voyage_command_from_subdirectory() {
     assert!(!subdir.join("target/cargo-hold.metadata").exists());
 }
 
+#[test]
+fn test_voyage_can_skip_anchor_or_gc() {
+    let temp_dir = setup_test_repo();
+    let lib_rs = temp_dir.path().join("src/lib.rs");
+    execute_command(Commands::Stow, &temp_dir, 0).unwrap();
+
+    let old_time = SystemTime::now() - Duration::from_secs(3600);
+    let file = fs::OpenOptions::new().write(true).open(&lib_rs).unwrap();
+    file.set_modified(old_time).unwrap();
+
+    let voyage = |no_anchor, no_gc| Commands::Voyage {
+        anchor: AnchorArgs::default(),
+        gc: GcArgs::new(None, vec![]),
+        gc_dry_run: true,
+        gc_debug: false,
+        gc_age_threshold_days: 7,
+        gc_auto_max_target_size: true,
+        no_anchor,
+        no_gc,
+    };
+
+    // Without the anchor step, timestamps are left alone
+    execute_command(voyage(true, false), &temp_dir, 0).unwrap();
+    assert_eq!(fs::metadata(&lib_rs).unwrap().modified().unwrap(), old_time);
+
+    // Anchoring alone restores them
+    execute_command(voyage(false, true), &temp_dir, 0).unwrap();
+    assert!(fs::metadata(&lib_rs).unwrap().modified().unwrap() > old_time);
+}
+
 #[test]
 fn test_core_voyage_workflow_integration() {
     let temp_dir = setup_cargo_project();
@@ -530,11 +595,14 @@ fn test_voyage_from_subdirectory() {
     // Run voyage from subdirectory using execute_command_with_dir
     execute_command_with_dir(
         Commands::Voyage {
+            anchor: AnchorArgs::default(),
             gc: GcArgs::new(None, vec![]),
             gc_dry_run: false,
             gc_debug: false,
             gc_age_threshold_days: 7,
             gc_auto_max_target_size: true,
+            no_anchor: false,
+            no_gc: false,
         },
         &temp_dir,
         &subdir,
@@ -635,11 +703,14 @@ edition = "2021"
         .verbose(0)
         .quiet(false)
         .command(Commands::Voyage {
+            anchor: AnchorArgs::default(),
             gc: GcArgs::new(None, vec![]),
             gc_dry_run: false,
             gc_debug: false,
             gc_age_threshold_days: 7,
             gc_auto_max_target_size: true,
+            no_anchor: false,
+            no_gc: false,
         })
         .build()
         .expect("Failed to build Cli");
@@ -702,6 +773,10 @@ fn test_timestamp_preservation_workflow() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
     execute_command(initial_heave, &temp_dir, 2).unwrap();
 
@@ -762,6 +837,10 @@ fn test_timestamp_preservation_workflow() {
         debug: true,
         age_threshold_days: 30, // High so age doesn't interfere
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
 
     let initial_size = get_directory_size(&target_dir);
@@ -821,6 +900,10 @@ fn test_heave_removes_old_artifacts_by_age() {
         debug: true,
         age_threshold_days: 7,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
 
     execute_command(heave_command, &temp_dir, 2).unwrap();
@@ -860,6 +943,10 @@ fn test_heave_preserves_recent_artifact_after_delayed_stow() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
     execute_command(initial_heave, &temp_dir, 2).unwrap();
 
@@ -894,6 +981,10 @@ fn test_heave_preserves_recent_artifact_after_delayed_stow() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
 
     // The artifact is newer than the previous GC timestamp, so it should survive
@@ -921,6 +1012,10 @@ fn test_heave_preserves_artifacts_newer_than_previous_gc() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
     execute_command(initial_heave, &temp_dir, 2).unwrap();
 
@@ -948,6 +1043,10 @@ fn test_heave_preserves_artifacts_newer_than_previous_gc() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
     execute_command(heave_command, &temp_dir, 2).unwrap();
 
@@ -976,6 +1075,10 @@ fn test_heave_with_preservation_message() {
         debug: true,
         age_threshold_days: 30,
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
     execute_command(initial_heave, &temp_dir, 2).unwrap();
 
@@ -998,6 +1101,10 @@ fn test_heave_with_preservation_message() {
         debug: true,
         age_threshold_days: 0, // Remove everything old
         auto_max_target_size: true,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
     };
 
     // Execute with verbose output to see the preservation message.
@@ -1005,3 +1112,297 @@ fn test_heave_with_preservation_message() {
     // be shown.
     execute_command(heave_command, &temp_dir, 2).unwrap();
 }
+
+#[test]
+fn test_clean_crate_removes_all_artifacts_of_named_crate() {
+    let temp_dir = setup_test_repo();
+    let target_dir = temp_dir.path().join("target");
+
+    let mut doomed = Vec::new();
+    let mut kept = Vec::new();
+    for profile in ["debug", "release"] {
+        let profile_dir = target_dir.join(profile);
+        for dir in ["deps", ".fingerprint", "build", "incremental"] {
+            fs::create_dir_all(profile_dir.join(dir)).unwrap();
+        }
+
+        let fingerprint = profile_dir.join(".fingerprint/my-proto-0123456789abcdef");
+        let rlib = profile_dir.join("deps/libmy_proto-0123456789abcdef.rlib");
+        let build = profile_dir.join("build/my-proto-fedcba9876543210");
+        let incremental = profile_dir.join("incremental/my_proto-1q2w3e4r5t6y7");
+        for dir in [&fingerprint, &build, &incremental] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("data"), b"data").unwrap();
+        }
+        fs::write(&rlib, b"rlib").unwrap();
+        doomed.extend([fingerprint, rlib, build, incremental]);
+
+        let other = profile_dir.join("deps/libmy_protocol-0123456789abcdef.rlib");
+        fs::write(&other, b"rlib").unwrap();
+        kept.push(other);
+    }
+
+    let dry_run = Commands::CleanCrate {
+        name: "my-proto".to_string(),
+        dry_run: true,
+    };
+    execute_command(dry_run, &temp_dir, 0).unwrap();
+    assert!(doomed.iter().all(|path| path.exists()));
+
+    let clean = Commands::CleanCrate {
+        name: "my_proto".to_string(),
+        dry_run: false,
+    };
+    execute_command(clean, &temp_dir, 1).unwrap();
+
+    for path in &doomed {
+        assert!(!path.exists(), "{} should be removed", path.display());
+    }
+    for path in &kept {
+        assert!(path.exists(), "{} should be kept", path.display());
+    }
+}
+
+/// Create the fingerprint and deps artifacts of a crate built `age_days` ago
+fn create_built_crate(profile_dir: &Path, name: &str, hash: &str, age_days: u64) {
+    let mtime = SystemTime::now() - Duration::from_secs(age_days * 24 * 60 * 60);
+    let fingerprint_dir = profile_dir
+        .join(".fingerprint")
+        .join(format!("lib{name}-{hash}"));
+    let files = [
+        fingerprint_dir.join("dep-lib"),
+        profile_dir
+            .join("deps")
+            .join(format!("lib{name}-{hash}.rlib")),
+        profile_dir.join("deps").join(format!("lib{name}-{hash}.d")),
+    ];
+    for file in &files {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, vec![b'x'; 4096]).unwrap();
+        filetime::set_file_mtime(file, filetime::FileTime::from_system_time(mtime)).unwrap();
+    }
+    filetime::set_file_mtime(
+        &fingerprint_dir,
+        filetime::FileTime::from_system_time(mtime),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_dock_records_the_build_embark_preserves() {
+    let temp_dir = setup_test_repo();
+    let debug_dir = temp_dir.path().join("target/debug");
+    create_built_crate(&debug_dir, "stale", "fedcba0987654321", 30);
+    execute_command(Commands::Dock, &temp_dir, 0).unwrap();
+
+    // A build spanning a day, long past the age threshold
+    create_built_crate(&debug_dir, "first", "1234567890abcdef", 21);
+    create_built_crate(&debug_dir, "last", "abcdef1234567890", 20);
+    execute_command(Commands::Dock, &temp_dir, 0).unwrap();
+
+    let embark = Commands::Embark {
+        gc: GcArgs::new(None, vec![]).with_preserve_window("60d"),
+        gc_dry_run: false,
+        gc_debug: false,
+        gc_age_threshold_days: 7,
+        gc_auto_max_target_size: true,
+    };
+    execute_command(embark, &temp_dir, 0).unwrap();
+
+    // Everything the docked build produced is kept, from its first artifact
+    assert!(
+        debug_dir
+            .join("deps/libfirst-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join("deps/liblast-abcdef1234567890.rlib")
+            .exists()
+    );
+    assert!(
+        !debug_dir
+            .join("deps/libstale-fedcba0987654321.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_failed_builds_keep_the_last_successful_build_preserved() {
+    let temp_dir = setup_test_repo();
+    let debug_dir = temp_dir.path().join("target/debug");
+    let report = |status: &str| Commands::ReportBuild {
+        status: status.to_string(),
+        duration: Some("812s".to_string()),
+        timings: None,
+    };
+    let embark = || Commands::Embark {
+        gc: GcArgs::new(None, vec![]).with_preserve_window("60d"),
+        gc_dry_run: false,
+        gc_debug: false,
+        gc_age_threshold_days: 7,
+        gc_auto_max_target_size: true,
+    };
+
+    create_built_crate(&debug_dir, "stale", "fedcba0987654321", 30);
+    execute_command(report("success"), &temp_dir, 0).unwrap();
+    create_built_crate(&debug_dir, "first", "1234567890abcdef", 21);
+    create_built_crate(&debug_dir, "last", "abcdef1234567890", 20);
+    execute_command(report("success"), &temp_dir, 0).unwrap();
+    execute_command(embark(), &temp_dir, 0).unwrap();
+
+    create_built_crate(&debug_dir, "broken", "0123456789abcdef", 0);
+    execute_command(report("failure"), &temp_dir, 0).unwrap();
+    execute_command(embark(), &temp_dir, 0).unwrap();
+
+    // The GC after the successful build moved preservation to its end, but
+    // the failure rolls it back to its start
+    assert!(
+        debug_dir
+            .join("deps/libfirst-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join("deps/liblast-abcdef1234567890.rlib")
+            .exists()
+    );
+    assert!(
+        debug_dir
+            .join("deps/libbroken-0123456789abcdef.rlib")
+            .exists()
+    );
+    assert!(
+        !debug_dir
+            .join("deps/libstale-fedcba0987654321.rlib")
+            .exists()
+    );
+
+    assert!(
+        execute_command(
+            Commands::ReportBuild {
+                status: "flaky".to_string(),
+                duration: None,
+                timings: None,
+            },
+            &temp_dir,
+            0
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_heave_keeps_crates_costly_to_rebuild() {
+    let temp_dir = setup_test_repo();
+    let debug_dir = temp_dir.path().join("target/debug");
+    let timings = temp_dir.path().join("timings.json");
+    fs::write(
+        &timings,
+        concat!(
+            r#"{"reason":"timing-info","package_id":"ring 0.17.0","target":{"name":"ring"},"mode":"build","duration":300.0}"#,
+            "\n",
+            r#"{"reason":"timing-info","package_id":"itoa 1.0.0","target":{"name":"itoa"},"mode":"build","duration":1.0}"#,
+            "\n",
+            r#"{"reason":"timing-info","package_id":"serde 1.0.0","target":{"name":"serde"},"mode":"build","duration":2.0}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    execute_command(
+        Commands::ReportBuild {
+            status: "success".to_string(),
+            duration: None,
+            timings: Some(timings),
+        },
+        &temp_dir,
+        0,
+    )
+    .unwrap();
+
+    create_built_crate(&debug_dir, "ring", "1234567890abcdef", 10);
+    create_built_crate(&debug_dir, "itoa", "abcdef1234567890", 5);
+    create_built_crate(&debug_dir, "serde", "0123456789abcdef", 5);
+    execute_command(
+        Commands::Heave {
+            gc: GcArgs::new(Some("24K".to_string()), vec![])
+                .with_size_mode("apparent")
+                .with_force(true),
+            dry_run: false,
+            debug: false,
+            age_threshold_days: 30,
+            auto_max_target_size: false,
+            only: vec![],
+            simulate: false,
+            suggest_only: false,
+            policies: vec![],
+        },
+        &temp_dir,
+        0,
+    )
+    .unwrap();
+
+    // The oldest crate takes far longer to rebuild than the others
+    assert!(
+        debug_dir
+            .join("deps/libring-1234567890abcdef.rlib")
+            .exists()
+    );
+    assert!(
+        !debug_dir
+            .join("deps/libitoa-abcdef1234567890.rlib")
+            .exists()
+    );
+    assert!(
+        !debug_dir
+            .join("deps/libserde-0123456789abcdef.rlib")
+            .exists()
+    );
+}
+
+#[test]
+fn test_heave_appends_removals_to_audit_log() {
+    let temp_dir = setup_test_repo();
+    let debug_dir = temp_dir.path().join("target/debug");
+    let audit_log = temp_dir.path().join("gc-audit.jsonl");
+    create_built_crate(&debug_dir, "ring", "1234567890abcdef", 10);
+    create_built_crate(&debug_dir, "itoa", "abcdef1234567890", 40);
+    create_built_crate(&debug_dir, "serde", "0123456789abcdef", 5);
+    let heave = |dry_run| Commands::Heave {
+        gc: GcArgs::new(Some("12K".to_string()), vec![])
+            .with_size_mode("apparent")
+            .with_gc_audit_log(&audit_log)
+            .with_gc_audit_log_format("jsonl")
+            .with_force(true),
+        dry_run,
+        debug: false,
+        age_threshold_days: 30,
+        auto_max_target_size: false,
+        only: vec![],
+        simulate: false,
+        suggest_only: false,
+        policies: vec![],
+    };
+
+    // Dry runs remove nothing, so they log nothing
+    execute_command(heave(true), &temp_dir, 0).unwrap();
+    assert!(!audit_log.exists());
+
+    execute_command(heave(false), &temp_dir, 0).unwrap();
+    let entries: Vec<serde_json::Value> = fs::read_to_string(&audit_log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let entry = |name: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["path"].as_str().unwrap().ends_with(name))
+            .unwrap()
+    };
+    let itoa = entry("libitoa-abcdef1234567890.rlib");
+    assert_eq!(itoa["reason"], "age");
+    assert_eq!(itoa["phase"], "target-dir");
+    assert_eq!(itoa["size"], 4096);
+    assert_eq!(entry("libring-1234567890abcdef.rlib")["reason"], "cap");
+}